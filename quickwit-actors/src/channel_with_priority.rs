@@ -125,6 +125,11 @@ impl<T> Sender<T> {
         self.channel(priority).try_send(msg)?;
         Ok(())
     }
+
+    /// Returns the number of low priority messages waiting in the channel.
+    pub fn num_pending_low_priority(&self) -> usize {
+        self.low_priority_tx.len()
+    }
 }
 
 pub struct Receiver<T> {
@@ -286,6 +291,19 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_num_pending_low_priority() -> anyhow::Result<()> {
+        let (sender, mut receiver) = super::channel::<usize>(QueueCapacity::Unbounded);
+        sender.send(1, Priority::Low).await?;
+        sender.send(2, Priority::Low).await?;
+        sender.send(3, Priority::High).await?;
+        assert_eq!(sender.num_pending_low_priority(), 2);
+        assert_eq!(receiver.recv_timeout(TEST_TIMEOUT).await, Ok(3));
+        assert_eq!(receiver.recv_timeout(TEST_TIMEOUT).await, Ok(1));
+        assert_eq!(sender.num_pending_low_priority(), 1);
+        Ok(())
+    }
+
     #[tokio::test]
     async fn test_recv_high_priority_timeout() -> anyhow::Result<()> {
         let (sender, mut receiver) = super::channel::<usize>(QueueCapacity::Unbounded);
@@ -237,6 +237,11 @@ impl<Message> Mailbox<Message> {
             .tx
             .try_send(CommandOrMessage::Message(message), Priority::Low)
     }
+
+    /// Returns the number of messages waiting in the mailbox, commands excluded.
+    pub fn num_pending_messages(&self) -> usize {
+        self.inner.tx.num_pending_low_priority()
+    }
 }
 
 pub struct Inbox<Message> {
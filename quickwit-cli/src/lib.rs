@@ -39,12 +39,12 @@ use quickwit_indexing::actors::{
     IndexerParams, IndexingPipelineParams, IndexingPipelineSupervisor,
 };
 use quickwit_indexing::models::{CommitPolicy, IndexingStatistics, ScratchDirectory};
-use quickwit_indexing::source::{FileSourceParams, SourceConfig};
+use quickwit_indexing::source::{FileSourceParams, IngestApiQueues, SourceConfig};
 use quickwit_metastore::checkpoint::Checkpoint;
 use quickwit_metastore::{IndexMetadata, MetastoreUriResolver};
 use quickwit_proto::{SearchRequest, SearchResponse};
 use quickwit_search::{single_node_search, SearchResponseRest};
-use quickwit_storage::quickwit_storage_uri_resolver;
+use quickwit_storage::{quickwit_storage_uri_resolver, LocalKeyFileProvider, StorageUriResolver};
 use quickwit_telemetry::payload::TelemetryEvent;
 use tracing::debug;
 
@@ -57,12 +57,15 @@ pub struct CreateIndexArgs {
     index_uri: String,
     index_config: Arc<dyn IndexConfig>,
     overwrite: bool,
+    encryption_key_id: Option<String>,
 }
 impl PartialEq for CreateIndexArgs {
     // index_config is opaque and not compared currently, need to change the trait to enable
     // IndexConfig comparison
     fn eq(&self, other: &Self) -> bool {
-        self.index_uri == other.index_uri && self.overwrite == other.overwrite
+        self.index_uri == other.index_uri
+            && self.overwrite == other.overwrite
+            && self.encryption_key_id == other.encryption_key_id
     }
 }
 
@@ -72,6 +75,7 @@ impl CreateIndexArgs {
         index_uri: String,
         index_config_path: PathBuf,
         overwrite: bool,
+        encryption_key_id: Option<String>,
     ) -> anyhow::Result<Self> {
         let json_file = std::fs::File::open(index_config_path.clone())
             .with_context(|| format!("Cannot open index-config-path {:?}", index_config_path))?;
@@ -94,6 +98,7 @@ impl CreateIndexArgs {
             index_uri,
             index_config,
             overwrite,
+            encryption_key_id,
         })
     }
 }
@@ -107,6 +112,8 @@ pub struct IndexDataArgs {
     pub temp_dir: Option<PathBuf>,
     pub heap_size: Byte,
     pub overwrite: bool,
+    pub encryption_key_file: Option<PathBuf>,
+    pub allow_plaintext_reads: bool,
 }
 
 #[derive(Debug, PartialEq, Eq, Default)]
@@ -120,6 +127,8 @@ pub struct SearchIndexArgs {
     pub start_timestamp: Option<i64>,
     pub end_timestamp: Option<i64>,
     pub tags: Option<Vec<String>>,
+    pub encryption_key_file: Option<PathBuf>,
+    pub allow_plaintext_reads: bool,
 }
 
 #[derive(Debug, PartialEq, Eq)]
@@ -151,6 +160,7 @@ pub async fn create_index_cli(args: CreateIndexArgs) -> anyhow::Result<()> {
         index_uri: args.index_uri.to_string(),
         index_config: args.index_config,
         checkpoint: Checkpoint::default(),
+        encryption_key_id: args.encryption_key_id,
     };
     create_index(&args.metastore_uri, index_metadata).await?;
     Ok(())
@@ -170,7 +180,10 @@ pub async fn index_data_cli(args: IndexDataArgs) -> anyhow::Result<()> {
         ScratchDirectory::try_new_temp()
             .with_context(|| "Failed to create a tempdir for the indexer")?
     };
-    let storage_uri_resolver = quickwit_storage_uri_resolver();
+    let storage_uri_resolver = storage_uri_resolver(
+        args.encryption_key_file.as_ref(),
+        args.allow_plaintext_reads,
+    )?;
     let metastore_uri_resolver = MetastoreUriResolver::default();
     let metastore = metastore_uri_resolver.resolve(&args.metastore_uri).await?;
 
@@ -195,6 +208,7 @@ pub async fn index_data_cli(args: IndexDataArgs) -> anyhow::Result<()> {
         indexer_params,
         metastore,
         storage_uri_resolver: storage_uri_resolver.clone(),
+        ingest_api_queues: IngestApiQueues::default(),
     };
 
     let indexing_supervisor = IndexingPipelineSupervisor::new(indexing_pipeline_params);
@@ -228,6 +242,21 @@ pub async fn index_data_cli(args: IndexDataArgs) -> anyhow::Result<()> {
     Ok(())
 }
 
+/// Returns the quickwit storage URI resolver, able to read and write encrypted split files
+/// if an encryption key file is provided.
+fn storage_uri_resolver(
+    encryption_key_file_opt: Option<&PathBuf>,
+    allow_plaintext_reads: bool,
+) -> anyhow::Result<StorageUriResolver> {
+    let storage_uri_resolver = quickwit_storage_uri_resolver().clone();
+    if let Some(encryption_key_file) = encryption_key_file_opt {
+        let key_provider = LocalKeyFileProvider::open(encryption_key_file)?;
+        return Ok(storage_uri_resolver
+            .with_encryption_key_provider(Arc::new(key_provider), allow_plaintext_reads));
+    }
+    Ok(storage_uri_resolver)
+}
+
 /// Inspects the CLI arguments and creates the appropriate [`SourceConfig`]. When a source config
 /// path is provided, the source config is loaded from file. Otherwise, a source config for a
 /// [`quickwit_indexing::source::FileSource`] is returned.
@@ -274,7 +303,10 @@ async fn create_source_config_from_args(
 
 pub async fn search_index(args: SearchIndexArgs) -> anyhow::Result<SearchResponse> {
     debug!(args = ?args, "search-index");
-    let storage_uri_resolver = quickwit_storage_uri_resolver();
+    let storage_uri_resolver = storage_uri_resolver(
+        args.encryption_key_file.as_ref(),
+        args.allow_plaintext_reads,
+    )?;
     let metastore_uri_resolver = MetastoreUriResolver::default();
     let metastore = metastore_uri_resolver.resolve(&args.metastore_uri).await?;
     let search_request = SearchRequest {
@@ -286,6 +318,8 @@ pub async fn search_index(args: SearchIndexArgs) -> anyhow::Result<SearchRespons
         max_hits: args.max_hits as u64,
         start_offset: args.start_offset as u64,
         tags: args.tags.unwrap_or_default(),
+        allow_partial_results: false,
+        timeout_millis: None,
     };
     let search_response: SearchResponse =
         single_node_search(&search_request, &*metastore, storage_uri_resolver.clone()).await?;
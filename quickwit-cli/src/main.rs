@@ -20,6 +20,7 @@
 use std::env;
 use std::net::SocketAddr;
 use std::path::{Path, PathBuf};
+use std::str::FromStr;
 use std::time::Duration;
 
 use anyhow::{bail, Context};
@@ -29,7 +30,10 @@ use opentelemetry::global;
 use opentelemetry::sdk::propagation::TraceContextPropagator;
 use quickwit_cli::*;
 use quickwit_common::net::socket_addr_from_str;
-use quickwit_serve::{serve_cli, ServeArgs};
+use quickwit_serve::{
+    serve_cli, AdmissionControllerConfig, CacheRouteConfig, FailureDetectorConfig, LaneConfig,
+    NodeRole, ServeArgs, TlsConfig,
+};
 use quickwit_telemetry::payload::TelemetryEvent;
 use tracing::Level;
 use tracing_subscriber::prelude::*;
@@ -87,12 +91,16 @@ impl CliCommand {
             .map(|metastore_uri_str| metastore_uri_str.to_string())
             .context("'metastore-uri' is a required arg")?;
         let overwrite = matches.is_present("overwrite");
+        let encryption_key_id = matches
+            .value_of("encryption-key-id")
+            .map(|encryption_key_id| encryption_key_id.to_string());
 
         Ok(CliCommand::New(CreateIndexArgs::new(
             metastore_uri,
             index_uri,
             index_config_path,
             overwrite,
+            encryption_key_id,
         )?))
     }
 
@@ -114,6 +122,9 @@ impl CliCommand {
             .expect("`heap-size` has a default value.");
         let heap_size = Byte::from_str(heap_size_str)?;
         let overwrite = matches.is_present("overwrite");
+        let encryption_key_file: Option<PathBuf> =
+            matches.value_of("encryption-key-file").map(PathBuf::from);
+        let allow_plaintext_reads = matches.is_present("allow-plaintext-reads");
 
         Ok(CliCommand::Index(IndexDataArgs {
             index_id,
@@ -123,6 +134,8 @@ impl CliCommand {
             heap_size,
             metastore_uri,
             overwrite,
+            encryption_key_file,
+            allow_plaintext_reads,
         }))
     }
 
@@ -157,6 +170,9 @@ impl CliCommand {
         let tags = matches
             .values_of("tags")
             .map(|values| values.map(|value| value.to_string()).collect());
+        let encryption_key_file: Option<PathBuf> =
+            matches.value_of("encryption-key-file").map(PathBuf::from);
+        let allow_plaintext_reads = matches.is_present("allow-plaintext-reads");
 
         Ok(CliCommand::Search(SearchIndexArgs {
             index_id,
@@ -168,6 +184,8 @@ impl CliCommand {
             end_timestamp,
             tags,
             metastore_uri,
+            encryption_key_file,
+            allow_plaintext_reads,
         }))
     }
 
@@ -190,6 +208,28 @@ impl CliCommand {
         let host_key_path =
             Path::new(format!("{}-{}-{}", host_key_path_prefix, host, port.to_string()).as_str())
                 .to_path_buf();
+        let cluster_id = matches
+            .value_of("cluster-id")
+            .context("'cluster-id' has a default value")?
+            .to_string();
+        let cluster_secret_file: Option<PathBuf> =
+            matches.value_of("cluster-secret-file").map(PathBuf::from);
+        let failure_detector_config = FailureDetectorConfig {
+            ping_interval: matches
+                .value_of("gossip-interval")
+                .map(parse_duration_with_unit)
+                .context("'gossip-interval' has a default value")??,
+            ping_timeout: matches
+                .value_of("gossip-ping-timeout")
+                .map(parse_duration_with_unit)
+                .context("'gossip-ping-timeout' has a default value")??,
+            suspect_timeout: matches
+                .value_of("gossip-suspect-timeout")
+                .map(parse_duration_with_unit)
+                .context("'gossip-suspect-timeout' has a default value")??,
+            ping_fanout: value_t!(matches, "gossip-fanout", usize)?,
+            num_indirect_pings: value_t!(matches, "gossip-indirect-pings", usize)?,
+        };
         let mut peer_socket_addrs: Vec<SocketAddr> = Vec::new();
         if matches.is_present("peer-seed") {
             if let Some(values) = matches.values_of("peer-seed") {
@@ -199,11 +239,108 @@ impl CliCommand {
             }
         }
 
+        let roles = matches
+            .values_of("role")
+            .context("'role' has a default value")?
+            .map(NodeRole::from_str)
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        let split_cache_dir: Option<PathBuf> =
+            matches.value_of("split-cache-dir").map(PathBuf::from);
+        let split_cache_capacity_str = matches
+            .value_of("split-cache-capacity")
+            .context("'split-cache-capacity' has a default value")?;
+        let split_cache_capacity_in_bytes =
+            Byte::from_str(split_cache_capacity_str)?.get_bytes() as u64;
+        let prefetch_index_ids = matches
+            .values_of("prefetch-index")
+            .map(|values| values.map(|value| value.to_string()).collect())
+            .unwrap_or_default();
+        let num_prefetched_splits = value_t!(matches, "num-prefetched-splits", usize)?;
+        let cache_routes = matches
+            .values_of("cache-route")
+            .context("'cache-route' has a default value")?
+            .map(parse_cache_route)
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        let leaf_search_cache_capacity_str = matches
+            .value_of("leaf-search-cache-capacity")
+            .context("'leaf-search-cache-capacity' has a default value")?;
+        let leaf_search_cache_capacity_in_bytes =
+            Byte::from_str(leaf_search_cache_capacity_str)?.get_bytes() as u64;
+        let search_memory_budget_str = matches
+            .value_of("search-memory-budget")
+            .context("'search-memory-budget' has a default value")?;
+        let stream_memory_budget_str = matches
+            .value_of("stream-memory-budget")
+            .context("'stream-memory-budget' has a default value")?;
+        let admission_controller_config = AdmissionControllerConfig {
+            search: LaneConfig {
+                max_concurrent_requests: value_t!(matches, "max-concurrent-searches", usize)?,
+                max_queued_requests: value_t!(matches, "max-queued-searches", usize)?,
+                memory_budget_in_bytes: Byte::from_str(search_memory_budget_str)?.get_bytes()
+                    as usize,
+            },
+            stream: LaneConfig {
+                max_concurrent_requests: value_t!(matches, "max-concurrent-streams", usize)?,
+                max_queued_requests: value_t!(matches, "max-queued-streams", usize)?,
+                memory_budget_in_bytes: Byte::from_str(stream_memory_budget_str)?.get_bytes()
+                    as usize,
+            },
+        };
+        let leaf_request_timeout = matches
+            .value_of("leaf-request-timeout")
+            .map(parse_duration_with_unit)
+            .context("'leaf-request-timeout' has a default value")??;
+        let tls_config = if let Some(cert_path) = matches.value_of("tls-cert-file") {
+            Some(TlsConfig {
+                cert_path: PathBuf::from(cert_path),
+                key_path: matches
+                    .value_of("tls-key-file")
+                    .map(PathBuf::from)
+                    .context("'tls-cert-file' requires 'tls-key-file'")?,
+                ca_cert_path: matches
+                    .value_of("tls-ca-file")
+                    .map(PathBuf::from)
+                    .context("'tls-cert-file' requires 'tls-ca-file'")?,
+                domain_name: matches
+                    .value_of("tls-domain-name")
+                    .context("'tls-cert-file' requires 'tls-domain-name'")?
+                    .to_string(),
+                client_auth: matches.is_present("tls-client-auth"),
+            })
+        } else {
+            None
+        };
+        let auth_file: Option<PathBuf> = matches.value_of("auth-file").map(PathBuf::from);
+        let encryption_key_file: Option<PathBuf> =
+            matches.value_of("encryption-key-file").map(PathBuf::from);
+        let allow_plaintext_reads = matches.is_present("allow-plaintext-reads");
+        let otlp_indexes_root_uri = matches
+            .value_of("otlp-indexes-root-uri")
+            .map(|otlp_indexes_root_uri_str| otlp_indexes_root_uri_str.to_string());
+
         Ok(CliCommand::Serve(ServeArgs {
             rest_socket_addr,
             host_key_path,
+            cluster_id,
+            cluster_secret_file,
+            failure_detector_config,
             peer_socket_addrs,
+            roles,
             metastore_uri,
+            split_cache_dir,
+            split_cache_capacity_in_bytes,
+            prefetch_index_ids,
+            num_prefetched_splits,
+            cache_routes,
+            leaf_search_cache_capacity_in_bytes,
+            admission_controller_config,
+            leaf_request_timeout,
+            tls_config,
+            auth_file,
+            encryption_key_file,
+            allow_plaintext_reads,
+            otlp_indexes_root_uri,
         }))
     }
 
@@ -332,7 +469,7 @@ fn about_text() -> String {
 }
 
 /// Parse duration with unit.
-/// examples: 1s 2m 3h 5d
+/// examples: 500ms 1s 2m 3h 5d
 pub fn parse_duration_with_unit(duration: &str) -> anyhow::Result<Duration> {
     let mut value = "".to_string();
     let mut unit = "".to_string();
@@ -346,16 +483,38 @@ pub fn parse_duration_with_unit(duration: &str) -> anyhow::Result<Duration> {
 
     match value.parse::<u64>() {
         Ok(value) => match unit.as_str() {
+            "ms" => Ok(Duration::from_millis(value)),
             "s" => Ok(Duration::from_secs(value)),
             "m" => Ok(Duration::from_secs(value * 60)),
             "h" => Ok(Duration::from_secs(value * 60 * 60)),
             "d" => Ok(Duration::from_secs(value * 60 * 60 * 24)),
-            _ => Err(anyhow::anyhow!("Invalid duration format: `[0-9]+[smhd]`")),
+            _ => Err(anyhow::anyhow!(
+                "Invalid duration format: `[0-9]+(ms|[smhd])`"
+            )),
         },
         Err(err) => Err(anyhow::anyhow!(err)),
     }
 }
 
+/// Parse a cache route.
+/// example: .fast=1GB
+pub fn parse_cache_route(cache_route: &str) -> anyhow::Result<CacheRouteConfig> {
+    let (path_suffix, capacity) = cache_route.split_once('=').ok_or_else(|| {
+        anyhow::anyhow!(
+            "Invalid cache route format: `{}`. Expected `<path suffix>=<capacity>`.",
+            cache_route
+        )
+    })?;
+    if path_suffix.is_empty() {
+        bail!("Cache route `{}` has an empty path suffix.", cache_route);
+    }
+    let capacity_in_bytes = Byte::from_str(capacity)?.get_bytes() as usize;
+    Ok(CacheRouteConfig {
+        path_suffix: path_suffix.to_string(),
+        capacity_in_bytes,
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use std::io::Write;
@@ -368,7 +527,7 @@ mod tests {
 
     use super::*;
     use crate::{
-        parse_duration_with_unit, CliCommand, CreateIndexArgs, DeleteIndexArgs,
+        parse_cache_route, parse_duration_with_unit, CliCommand, CreateIndexArgs, DeleteIndexArgs,
         GarbageCollectIndexArgs, IndexDataArgs, SearchIndexArgs,
     };
 
@@ -414,6 +573,7 @@ mod tests {
                 "file:///indexes/wikipedia".to_string(),
                 path.to_path_buf(),
                 false,
+                None,
             )
             .unwrap(),
         );
@@ -429,6 +589,8 @@ mod tests {
             "--metastore-uri",
             "file:///indexes",
             "--overwrite",
+            "--encryption-key-id",
+            "key-1",
         ])?;
         let command = CliCommand::parse_cli_args(&matches);
         let expected_cmd = CliCommand::New(
@@ -437,6 +599,7 @@ mod tests {
                 "file:///indexes/wikipedia".to_string(),
                 path.to_path_buf(),
                 true,
+                Some("key-1".to_string()),
             )
             .unwrap(),
         );
@@ -467,6 +630,8 @@ mod tests {
                 heap_size,
                 metastore_uri,
                 overwrite: false,
+                encryption_key_file: None,
+                allow_plaintext_reads: false,
             })) if &index_id == "wikipedia"
                     && &metastore_uri == "file:///indexes"
                     && heap_size.get_bytes() == 2_000_000_000
@@ -487,6 +652,9 @@ mod tests {
             "--metastore-uri",
             "file:///indexes",
             "--overwrite",
+            "--encryption-key-file",
+            "/etc/quickwit/keys.json",
+            "--allow-plaintext-reads",
         ])?;
         let command = CliCommand::parse_cli_args(&matches);
         assert!(matches!(
@@ -499,7 +667,10 @@ mod tests {
                 heap_size,
                 metastore_uri,
                 overwrite: true,
+                encryption_key_file: Some(encryption_key_file),
+                allow_plaintext_reads: true,
             })) if &index_id == "wikipedia"
+                    && encryption_key_file == Path::new("/etc/quickwit/keys.json")
                     && source_config_path == Path::new("/conf/source_config.json")
                     && temp_dir == Some(PathBuf::from("./tmp"))
                     && &metastore_uri == "file:///indexes"
@@ -554,6 +725,8 @@ mod tests {
                 end_timestamp: None,
                 tags: None,
                 metastore_uri,
+                encryption_key_file: None,
+                allow_plaintext_reads: false,
             })) if &index_id == "wikipedia" && &query == "Barack Obama" && &metastore_uri == "file:///indexes"
         ));
 
@@ -581,6 +754,9 @@ mod tests {
             "--tags",
             "device:rpi",
             "city:paris",
+            "--encryption-key-file",
+            "/etc/quickwit/keys.json",
+            "--allow-plaintext-reads",
         ])?;
         let command = CliCommand::parse_cli_args(&matches);
         assert!(matches!(
@@ -595,7 +771,10 @@ mod tests {
                 end_timestamp: Some(1),
                 tags: Some(tags),
                 metastore_uri,
+                encryption_key_file: Some(encryption_key_file),
+                allow_plaintext_reads: true,
             })) if &index_id == "wikipedia" && query == "Barack Obama"
+                && encryption_key_file == Path::new("/etc/quickwit/keys.json")
                 && field_names == vec!["title".to_string(), "url".to_string()]
                 && tags == vec!["device:rpi".to_string(), "city:paris".to_string()] && &metastore_uri == "file:///indexes"
         ));
@@ -714,8 +893,8 @@ mod tests {
         assert!(matches!(
             command,
             Ok(CliCommand::Serve(ServeArgs {
-                rest_socket_addr, host_key_path, peer_socket_addrs, metastore_uri,
-            })) if rest_socket_addr == socket_addr_from_str("127.0.0.1:9090").unwrap() && host_key_path == Path::new("/etc/quickwit-host-key-127.0.0.1-9090").to_path_buf() && peer_socket_addrs == vec![socket_addr_from_str("192.168.1.13:9090").unwrap()] && &metastore_uri == "file:///indexes"
+                rest_socket_addr, host_key_path, cluster_id, cluster_secret_file, failure_detector_config, peer_socket_addrs, roles, metastore_uri, split_cache_dir, split_cache_capacity_in_bytes, prefetch_index_ids, num_prefetched_splits, cache_routes, leaf_search_cache_capacity_in_bytes, admission_controller_config, leaf_request_timeout, tls_config, auth_file, encryption_key_file, allow_plaintext_reads, otlp_indexes_root_uri,
+            })) if &cluster_id == "quickwit-cluster" && admission_controller_config == AdmissionControllerConfig::default() && leaf_search_cache_capacity_in_bytes == 200_000_000 && leaf_request_timeout == Duration::from_secs(30) && tls_config.is_none() && auth_file.is_none() && failure_detector_config == FailureDetectorConfig::default() && cluster_secret_file.is_none() && rest_socket_addr == socket_addr_from_str("127.0.0.1:9090").unwrap() && host_key_path == Path::new("/etc/quickwit-host-key-127.0.0.1-9090").to_path_buf() && peer_socket_addrs == vec![socket_addr_from_str("192.168.1.13:9090").unwrap()] && &metastore_uri == "file:///indexes" && split_cache_dir.is_none() && split_cache_capacity_in_bytes == 10_000_000_000 && prefetch_index_ids.is_empty() && num_prefetched_splits == 10 && roles == vec![NodeRole::Searcher] && cache_routes.len() == 3 && encryption_key_file.is_none() && !allow_plaintext_reads && otlp_indexes_root_uri.is_none()
         ));
 
         let yaml = load_yaml!("cli.yaml");
@@ -732,20 +911,85 @@ mod tests {
             "/etc/quickwit-host-key",
             "--peer-seed",
             "192.168.1.13:9090,192.168.1.14:9090",
+            "--role",
+            "searcher,janitor",
+            "--split-cache-dir",
+            "/var/cache/quickwit",
+            "--split-cache-capacity",
+            "2G",
+            "--prefetch-index",
+            "hdfs-logs,wikipedia",
+            "--num-prefetched-splits",
+            "5",
+            "--cache-route",
+            ".fast=2GB",
+            "--encryption-key-file",
+            "/etc/quickwit/keys.json",
+            "--allow-plaintext-reads",
+            "--cluster-id",
+            "prod-cluster",
+            "--cluster-secret-file",
+            "/etc/quickwit/cluster-secret",
+            "--gossip-interval",
+            "500ms",
+            "--gossip-suspect-timeout",
+            "10s",
+            "--gossip-fanout",
+            "2",
+            "--leaf-search-cache-capacity",
+            "0",
+            "--max-concurrent-searches",
+            "8",
+            "--stream-memory-budget",
+            "2GB",
+            "--leaf-request-timeout",
+            "5s",
+            "--tls-cert-file",
+            "/etc/quickwit/node.pem",
+            "--tls-key-file",
+            "/etc/quickwit/node.key",
+            "--tls-ca-file",
+            "/etc/quickwit/ca.pem",
+            "--tls-domain-name",
+            "quickwit.internal",
+            "--tls-client-auth",
+            "--auth-file",
+            "/etc/quickwit/users.json",
+            "--otlp-indexes-root-uri",
+            "s3://quickwit-indexes/otlp",
         ])?;
         let command = CliCommand::parse_cli_args(&matches);
         assert!(matches!(
             command,
             Ok(CliCommand::Serve(ServeArgs {
-                rest_socket_addr, host_key_path, peer_socket_addrs, metastore_uri,
-            })) if rest_socket_addr == socket_addr_from_str("127.0.0.1:9090").unwrap() && host_key_path == Path::new("/etc/quickwit-host-key-127.0.0.1-9090").to_path_buf() && peer_socket_addrs == vec![socket_addr_from_str("192.168.1.13:9090").unwrap(), socket_addr_from_str("192.168.1.14:9090").unwrap()] && &metastore_uri == "file:///indexes"
+                rest_socket_addr, host_key_path, cluster_id, cluster_secret_file, failure_detector_config, peer_socket_addrs, roles, metastore_uri, split_cache_dir, split_cache_capacity_in_bytes, prefetch_index_ids, num_prefetched_splits, cache_routes, leaf_search_cache_capacity_in_bytes, admission_controller_config, leaf_request_timeout, tls_config, auth_file, encryption_key_file, allow_plaintext_reads, otlp_indexes_root_uri,
+            })) if &cluster_id == "prod-cluster" && admission_controller_config.search.max_concurrent_requests == 8 && admission_controller_config.search.max_queued_requests == 100 && admission_controller_config.stream.memory_budget_in_bytes == 2_000_000_000 && leaf_search_cache_capacity_in_bytes == 0 && leaf_request_timeout == Duration::from_secs(5) && tls_config == Some(TlsConfig { cert_path: PathBuf::from("/etc/quickwit/node.pem"), key_path: PathBuf::from("/etc/quickwit/node.key"), ca_cert_path: PathBuf::from("/etc/quickwit/ca.pem"), domain_name: "quickwit.internal".to_string(), client_auth: true }) && auth_file == Some(PathBuf::from("/etc/quickwit/users.json")) && failure_detector_config == FailureDetectorConfig { ping_interval: Duration::from_millis(500), ping_timeout: Duration::from_secs(3), suspect_timeout: Duration::from_secs(10), ping_fanout: 2, num_indirect_pings: 3 } && cluster_secret_file == Some(PathBuf::from("/etc/quickwit/cluster-secret")) && cache_routes == vec![CacheRouteConfig { path_suffix: ".fast".to_string(), capacity_in_bytes: 2_000_000_000 }] && split_cache_dir == Some(PathBuf::from("/var/cache/quickwit")) && split_cache_capacity_in_bytes == 2_000_000_000 && prefetch_index_ids == vec!["hdfs-logs".to_string(), "wikipedia".to_string()] && num_prefetched_splits == 5 && roles == vec![NodeRole::Searcher, NodeRole::Janitor] && encryption_key_file == Some(PathBuf::from("/etc/quickwit/keys.json")) && allow_plaintext_reads && rest_socket_addr == socket_addr_from_str("127.0.0.1:9090").unwrap() && host_key_path == Path::new("/etc/quickwit-host-key-127.0.0.1-9090").to_path_buf() && peer_socket_addrs == vec![socket_addr_from_str("192.168.1.13:9090").unwrap(), socket_addr_from_str("192.168.1.14:9090").unwrap()] && &metastore_uri == "file:///indexes" && otlp_indexes_root_uri == Some("s3://quickwit-indexes/otlp".to_string())
         ));
 
         Ok(())
     }
 
+    #[test]
+    fn test_parse_cache_route() -> anyhow::Result<()> {
+        assert_eq!(
+            parse_cache_route("hotcache=500MB")?,
+            CacheRouteConfig {
+                path_suffix: "hotcache".to_string(),
+                capacity_in_bytes: 500_000_000,
+            }
+        );
+        assert!(parse_cache_route(".fast").is_err());
+        assert!(parse_cache_route("=1GB").is_err());
+        assert!(parse_cache_route(".fast=lots").is_err());
+        Ok(())
+    }
+
     #[test]
     fn test_parse_duration_with_unit() -> anyhow::Result<()> {
+        assert_eq!(
+            parse_duration_with_unit("500ms")?,
+            Duration::from_millis(500)
+        );
         assert_eq!(parse_duration_with_unit("8s")?, Duration::from_secs(8));
         assert_eq!(parse_duration_with_unit("5m")?, Duration::from_secs(5 * 60));
         assert_eq!(
@@ -782,6 +782,7 @@ async fn test_all_with_s3_localstack_internal_api() -> Result<()> {
         index_uri.clone(),
         test_env.resource_files["config"].to_path_buf(),
         false,
+        None,
     )?;
     create_index_cli(args).await?;
     let index_metadata = test_env.metastore().index_metadata(index_id).await;
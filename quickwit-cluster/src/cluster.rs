@@ -17,16 +17,18 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program. If not, see <http://www.gnu.org/licenses/>.
 
-use std::fs;
+use std::collections::{BTreeMap, BTreeSet};
 use std::net::{IpAddr, Ipv4Addr, SocketAddr};
 use std::path::Path;
+use std::str::FromStr;
 use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
+use std::{fmt, fs};
 
 use quickwit_swim::prelude::{
     ArtilleryError, ArtilleryMember, ArtilleryMemberEvent, ArtilleryMemberState,
-    Cluster as ArtilleryCluster, ClusterConfig as ArtilleryClusterConfig,
+    Cluster as ArtilleryCluster, ClusterConfig as ArtilleryClusterConfig, MessageCipher,
 };
 use tokio::sync::watch;
 use tokio::time::timeout;
@@ -37,11 +39,98 @@ use uuid::Uuid;
 
 use crate::error::{ClusterError, ClusterResult};
 
-/// The ID that makes the cluster unique.
-const CLUSTER_ID: &str = "quickwit-cluster";
+/// The ID of the cluster nodes join when none is specified.
+/// Only the nodes sharing the same cluster ID can see each other.
+pub const DEFAULT_CLUSTER_ID: &str = "quickwit-cluster";
 
 const CLUSTER_EVENT_TIMEOUT: Duration = Duration::from_millis(200);
 
+/// Metadata key holding the comma-separated roles of a node.
+const ROLES_METADATA_KEY: &str = "roles";
+
+/// Metadata key holding the version of Quickwit run by a node.
+pub const VERSION_METADATA_KEY: &str = "version";
+
+/// Metadata key holding the number of bytes available in the split cache of a node.
+pub const AVAILABLE_CACHE_BYTES_METADATA_KEY: &str = "available_cache_bytes";
+
+/// Metadata key holding the current load of a node, i.e. the number of splits it is
+/// searching.
+pub const LOAD_METADATA_KEY: &str = "load";
+
+/// Settings of the failure detector, i.e. how fast unresponsive nodes are evicted from the
+/// cluster.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FailureDetectorConfig {
+    /// Interval at which nodes are pinged.
+    pub ping_interval: Duration,
+    /// Duration after which a node that did not answer a ping is suspected.
+    pub ping_timeout: Duration,
+    /// Duration after which a suspected node that did not refute the suspicion is
+    /// declared down.
+    pub suspect_timeout: Duration,
+    /// Number of nodes pinged every ping interval.
+    pub ping_fanout: usize,
+    /// Number of nodes asked to ping a suspected node on our behalf.
+    pub num_indirect_pings: usize,
+}
+
+impl Default for FailureDetectorConfig {
+    fn default() -> Self {
+        FailureDetectorConfig {
+            ping_interval: Duration::from_secs(1),
+            ping_timeout: Duration::from_secs(3),
+            suspect_timeout: Duration::from_secs(3),
+            ping_fanout: 1,
+            num_indirect_pings: 3,
+        }
+    }
+}
+
+/// The role a node plays in the cluster.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum NodeRole {
+    /// Indexes documents into splits.
+    Indexer,
+    /// Serves leaf search and fetch docs requests.
+    Searcher,
+    /// Garbage collects the splits of the indexes.
+    Janitor,
+}
+
+impl NodeRole {
+    /// Returns the name of the role.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            NodeRole::Indexer => "indexer",
+            NodeRole::Searcher => "searcher",
+            NodeRole::Janitor => "janitor",
+        }
+    }
+}
+
+impl fmt::Display for NodeRole {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str(self.as_str())
+    }
+}
+
+impl FromStr for NodeRole {
+    type Err = anyhow::Error;
+
+    fn from_str(role_str: &str) -> anyhow::Result<Self> {
+        match role_str {
+            "indexer" => Ok(NodeRole::Indexer),
+            "searcher" => Ok(NodeRole::Searcher),
+            "janitor" => Ok(NodeRole::Janitor),
+            _ => anyhow::bail!(
+                "Unknown node role `{}`. Valid roles are `indexer`, `searcher` and `janitor`.",
+                role_str
+            ),
+        }
+    }
+}
+
 /// Reads the key that makes a node unique from the given file.
 /// If the file does not exist, it generates an ID and writes it to the file
 /// so that it can be reused on reboot.
@@ -79,6 +168,34 @@ pub fn read_or_create_host_key(host_key_path: &Path) -> ClusterResult<Uuid> {
     Ok(host_key)
 }
 
+/// Reads the secret keys used to encrypt the gossip messages of the cluster.
+/// The file holds one base64 encoded 32 bytes key per line, blank lines and lines starting
+/// with `#` being ignored. The first key encrypts outgoing messages while all of them are
+/// accepted for incoming messages, so that keys can be rotated without downtime.
+pub fn read_cluster_secret_keys(secret_keys_path: &Path) -> ClusterResult<Vec<Vec<u8>>> {
+    let secret_keys_contents =
+        fs::read_to_string(secret_keys_path).map_err(|err| ClusterError::ReadSecretKeysError {
+            message: err.to_string(),
+        })?;
+    let secret_keys = secret_keys_contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| {
+            base64::decode(line).map_err(|err| ClusterError::ReadSecretKeysError {
+                message: format!("Key is not valid base64: {}", err),
+            })
+        })
+        .collect::<ClusterResult<Vec<_>>>()?;
+    if secret_keys.is_empty() {
+        return Err(ClusterError::ReadSecretKeysError {
+            message: format!("No key found in `{}`.", secret_keys_path.display()),
+        });
+    }
+    info!(secret_keys_path=?secret_keys_path, num_keys=secret_keys.len(), "Read cluster secret keys.");
+    Ok(secret_keys)
+}
+
 /// A member information.
 #[derive(Clone, Debug, PartialEq)]
 pub struct Member {
@@ -90,6 +207,34 @@ pub struct Member {
 
     /// If true, it means self.
     pub is_self: bool,
+
+    /// Roles of the member. Members that do not gossip their roles are considered
+    /// searchers.
+    pub roles: BTreeSet<NodeRole>,
+
+    /// Key/value metadata gossiped by the member, roles excluded.
+    pub metadata: BTreeMap<String, String>,
+}
+
+impl Member {
+    /// Returns true if the member plays the given role.
+    pub fn has_role(&self, role: NodeRole) -> bool {
+        self.roles.contains(&role)
+    }
+
+    /// Returns the current load of the member, if it gossips it.
+    pub fn load(&self) -> Option<u64> {
+        self.metadata
+            .get(LOAD_METADATA_KEY)
+            .and_then(|load_str| load_str.parse().ok())
+    }
+
+    /// Returns the number of bytes available in the split cache of the member, if it gossips it.
+    pub fn available_cache_bytes(&self) -> Option<u64> {
+        self.metadata
+            .get(AVAILABLE_CACHE_BYTES_METADATA_KEY)
+            .and_then(|num_bytes_str| num_bytes_str.parse().ok())
+    }
 }
 
 /// This is an implementation of a cluster using the SWIM protocol.
@@ -103,6 +248,9 @@ pub struct Cluster {
     /// A receiver(channel) for exchanging members in a cluster.
     members: watch::Receiver<Vec<Member>>,
 
+    /// Metadata gossiped along with this node.
+    metadata: Mutex<BTreeMap<String, String>>,
+
     /// A stop flag of cluster monitoring task.
     /// Once the cluster is created, a task to monitor cluster events will be started.
     /// Nodes do not need to be monitored for events once they are detached from the cluster.
@@ -111,14 +259,42 @@ pub struct Cluster {
 }
 
 impl Cluster {
-    /// Create a cluster given a host key and a listen address.
+    /// Create a cluster given a host key, a listen address and a cluster ID.
+    /// When secret keys are given, gossip messages are encrypted and authenticated so that
+    /// only the nodes sharing one of the keys can join the cluster.
+    /// The failure detector config controls how fast unresponsive nodes are evicted.
+    /// The roles are gossiped from the first message on, so that the other members never
+    /// see this node with the default roles.
     /// When a cluster is created, the thread that monitors cluster events
     /// will be started at the same time.
-    pub fn new(host_key: Uuid, listen_addr: SocketAddr) -> ClusterResult<Self> {
-        info!( host_key=?host_key, listen_addr=?listen_addr, "Create new cluster.");
+    pub fn new(
+        host_key: Uuid,
+        listen_addr: SocketAddr,
+        cluster_id: &str,
+        secret_keys: &[Vec<u8>],
+        failure_detector_config: FailureDetectorConfig,
+        roles: &[NodeRole],
+    ) -> ClusterResult<Self> {
+        info!(host_key=?host_key, listen_addr=?listen_addr, cluster_id=?cluster_id, encrypted=!secret_keys.is_empty(), "Create new cluster.");
+        let message_cipher = if secret_keys.is_empty() {
+            None
+        } else {
+            let message_cipher = MessageCipher::new(secret_keys).map_err(|err| {
+                ClusterError::CreateClusterError {
+                    message: err.to_string(),
+                }
+            })?;
+            Some(message_cipher)
+        };
         let config = ArtilleryClusterConfig {
-            cluster_key: CLUSTER_ID.as_bytes().to_vec(),
+            cluster_key: cluster_id.as_bytes().to_vec(),
             listen_addr,
+            message_cipher,
+            ping_interval: failure_detector_config.ping_interval,
+            ping_timeout: failure_detector_config.ping_timeout,
+            suspect_timeout: failure_detector_config.suspect_timeout,
+            ping_fanout: failure_detector_config.ping_fanout,
+            ping_request_host_count: failure_detector_config.num_indirect_pings,
             ..Default::default()
         };
         let (artillery_cluster, swim_event_rx) =
@@ -134,20 +310,33 @@ impl Cluster {
 
         let (members_sender, members_receiver) = watch::channel(Vec::new());
 
+        let mut metadata = BTreeMap::new();
+        metadata.insert(
+            VERSION_METADATA_KEY.to_string(),
+            env!("CARGO_PKG_VERSION").to_string(),
+        );
+        let member_metadata = metadata.clone();
+        metadata.insert(ROLES_METADATA_KEY.to_string(), roles_to_string(roles));
+        artillery_cluster.set_metadata(metadata.clone());
+
+        // Add itself as the initial member of the cluster.
+        let member = Member {
+            host_key,
+            listen_addr,
+            is_self: true,
+            roles: roles.iter().copied().collect(),
+            metadata: member_metadata,
+        };
+
         // Create cluster.
         let cluster = Cluster {
             listen_addr,
             artillery_cluster,
             members: members_receiver,
+            metadata: Mutex::new(metadata),
             stop: Arc::new(AtomicBool::new(false)),
         };
 
-        // Add itself as the initial member of the cluster.
-        let member = Member {
-            host_key,
-            listen_addr,
-            is_self: true,
-        };
         let initial_members: Vec<Member> = vec![member];
         if members_sender.send(initial_members).is_err() {
             error!("Failed to add itself as the initial member of the cluster.");
@@ -205,6 +394,36 @@ impl Cluster {
         self.members.borrow().clone()
     }
 
+    /// Sets the roles gossiped along with this node.
+    pub fn set_roles(&self, roles: &[NodeRole]) {
+        self.set_metadata(ROLES_METADATA_KEY, roles_to_string(roles));
+    }
+
+    /// Sets a key/value pair gossiped along with this node.
+    ///
+    /// Gossiping a new value is not free, so values changing often should be
+    /// rounded by the caller.
+    pub fn set_metadata(&self, key: &str, value: String) {
+        self.set_metadata_entries(vec![(key, value)]);
+    }
+
+    /// Sets several key/value pairs gossiped along with this node.
+    ///
+    /// The node is reincarnated at most once, and only if one of the values changed.
+    pub fn set_metadata_entries(&self, entries: Vec<(&str, String)>) {
+        let mut metadata = self.metadata.lock().unwrap();
+        let mut changed = false;
+        for (key, value) in entries {
+            if metadata.get(key) != Some(&value) {
+                metadata.insert(key.to_string(), value);
+                changed = true;
+            }
+        }
+        if changed {
+            self.artillery_cluster.set_metadata(metadata.clone());
+        }
+    }
+
     /// Specify the address of a running node and join the cluster to which the node belongs.
     pub async fn add_peer_node(&self, peer_addr: SocketAddr) {
         info!(self_addr = ?self.listen_addr, peer_addr = ?peer_addr, "Adding peer node.");
@@ -247,13 +466,43 @@ fn convert_member(member: ArtilleryMember, self_listen_addr: SocketAddr) -> Memb
         self_listen_addr
     };
 
+    let mut metadata = member.metadata().clone();
+    let roles = match metadata.remove(ROLES_METADATA_KEY) {
+        Some(roles_str) => roles_str
+            .split(',')
+            .filter_map(|role_str| match NodeRole::from_str(role_str) {
+                Ok(role) => Some(role),
+                Err(err) => {
+                    // The member may run a more recent version.
+                    debug!(host_key=?member.host_key(), err=?err, "Ignoring unknown role.");
+                    None
+                }
+            })
+            .collect(),
+        None => default_roles(),
+    };
     Member {
         host_key: member.host_key(),
         listen_addr,
         is_self: member.is_current(),
+        roles,
+        metadata,
     }
 }
 
+fn roles_to_string(roles: &[NodeRole]) -> String {
+    roles
+        .iter()
+        .map(NodeRole::as_str)
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// Roles of the members that do not gossip their roles.
+fn default_roles() -> BTreeSet<NodeRole> {
+    std::iter::once(NodeRole::Searcher).collect()
+}
+
 /// Output member event as log.
 fn log_artillery_event(artillery_member_event: ArtilleryMemberEvent) {
     match artillery_member_event {
@@ -272,6 +521,9 @@ fn log_artillery_event(artillery_member_event: ArtilleryMemberEvent) {
         ArtilleryMemberEvent::Left(artillery_member) => {
             info!(host_key=?artillery_member.host_key(), remote_host=?artillery_member.remote_host(), "Left.");
         }
+        ArtilleryMemberEvent::MetadataUpdated(artillery_member) => {
+            debug!(host_key=?artillery_member.host_key(), remote_host=?artillery_member.remote_host(), metadata=?artillery_member.metadata(), "Metadata updated.");
+        }
         ArtilleryMemberEvent::Payload(artillery_member, message) => {
             info!(host_key=?artillery_member.host_key(), remote_host=?artillery_member.remote_host(), message=?message, "Payload.");
         }
@@ -280,10 +532,34 @@ fn log_artillery_event(artillery_member_event: ArtilleryMemberEvent) {
 
 /// Creates a local cluster listening on a random port.
 pub fn create_cluster_for_test() -> anyhow::Result<Cluster> {
+    create_cluster_for_test_with_secret_keys(&[])
+}
+
+/// Creates a local cluster listening on a random port and encrypting its messages with the
+/// given secret keys.
+pub fn create_cluster_for_test_with_secret_keys(
+    secret_keys: &[Vec<u8>],
+) -> anyhow::Result<Cluster> {
+    create_cluster_for_test_with_config(secret_keys, FailureDetectorConfig::default())
+}
+
+/// Creates a local cluster listening on a random port with the given secret keys and
+/// failure detector config.
+pub fn create_cluster_for_test_with_config(
+    secret_keys: &[Vec<u8>],
+    failure_detector_config: FailureDetectorConfig,
+) -> anyhow::Result<Cluster> {
     let peer_uuid = Uuid::new_v4();
     let port = quickwit_common::net::find_available_port()?;
     let peer_addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), port);
-    let cluster = Cluster::new(peer_uuid, peer_addr)?;
+    let cluster = Cluster::new(
+        peer_uuid,
+        peer_addr,
+        DEFAULT_CLUSTER_ID,
+        secret_keys,
+        failure_detector_config,
+        &[NodeRole::Searcher],
+    )?;
     Ok(cluster)
 }
 
@@ -327,6 +603,8 @@ mod tests {
                 host_key,
                 listen_addr: remote_host,
                 is_self: false,
+                roles: default_roles(),
+                metadata: BTreeMap::new(),
             };
             assert_eq!(member, expected_member);
         }
@@ -337,9 +615,36 @@ mod tests {
                 host_key,
                 listen_addr: remote_host,
                 is_self: true,
+                roles: default_roles(),
+                metadata: BTreeMap::new(),
             };
             assert_eq!(member, expected_member);
         }
+        {
+            let mut artillery_member = ArtilleryMember::current(host_key);
+            let mut metadata = BTreeMap::new();
+            metadata.insert(
+                ROLES_METADATA_KEY.to_string(),
+                "indexer,janitor,unknown".to_string(),
+            );
+            metadata.insert(LOAD_METADATA_KEY.to_string(), "3".to_string());
+            artillery_member.set_metadata(metadata);
+            let member = convert_member(artillery_member, remote_host);
+            assert!(member.has_role(NodeRole::Indexer));
+            assert!(member.has_role(NodeRole::Janitor));
+            assert!(!member.has_role(NodeRole::Searcher));
+            assert_eq!(member.load(), Some(3));
+            assert_eq!(member.available_cache_bytes(), None);
+            assert!(!member.metadata.contains_key(ROLES_METADATA_KEY));
+        }
+    }
+
+    #[test]
+    fn test_node_role_from_str() {
+        for role in [NodeRole::Indexer, NodeRole::Searcher, NodeRole::Janitor] {
+            assert_eq!(NodeRole::from_str(role.as_str()).unwrap(), role);
+        }
+        assert!(NodeRole::from_str("leader").is_err());
     }
 
     #[tokio::test]
@@ -358,6 +663,28 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_cluster_self_member_starts_with_configured_roles() -> anyhow::Result<()> {
+        let port = quickwit_common::net::find_available_port()?;
+        let listen_addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), port);
+        let cluster = Cluster::new(
+            Uuid::new_v4(),
+            listen_addr,
+            DEFAULT_CLUSTER_ID,
+            &[],
+            FailureDetectorConfig::default(),
+            &[NodeRole::Indexer],
+        )?;
+        let members = cluster.members();
+        assert_eq!(members.len(), 1);
+        assert!(members[0].is_self);
+        assert!(members[0].has_role(NodeRole::Indexer));
+        assert!(!members[0].has_role(NodeRole::Searcher));
+        assert!(!members[0].metadata.contains_key(ROLES_METADATA_KEY));
+        cluster.leave().await;
+        Ok(())
+    }
+
     #[tokio::test]
     async fn test_cluster_multiple_nodes() -> anyhow::Result<()> {
         let cluster1 = create_cluster_for_test()?;
@@ -399,4 +726,199 @@ mod tests {
             .await?;
         Ok(())
     }
+
+    #[tokio::test]
+    async fn test_cluster_leave_propagates_immediately() -> anyhow::Result<()> {
+        // Intervals long enough for the test to fail if leaving relied on regular gossip or
+        // timeouts.
+        let failure_detector_config = FailureDetectorConfig {
+            ping_interval: Duration::from_secs(5),
+            ping_timeout: Duration::from_secs(60),
+            suspect_timeout: Duration::from_secs(60),
+            ..Default::default()
+        };
+        let cluster1 = create_cluster_for_test_with_config(&[], failure_detector_config)?;
+        let cluster2 = create_cluster_for_test_with_config(&[], failure_detector_config)?;
+        cluster2.add_peer_node(cluster1.listen_addr).await;
+
+        let ten_secs = Duration::from_secs(10);
+        cluster1
+            .wait_for_members(|members| members.len() == 2, ten_secs)
+            .await?;
+
+        cluster2.leave().await;
+        cluster1
+            .wait_for_members(|members| members.len() == 1, Duration::from_millis(500))
+            .await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_cluster_evicts_unresponsive_node_after_suspect_timeout() -> anyhow::Result<()> {
+        let failure_detector_config = FailureDetectorConfig {
+            ping_interval: Duration::from_millis(100),
+            ping_timeout: Duration::from_millis(300),
+            suspect_timeout: Duration::from_millis(300),
+            ..Default::default()
+        };
+        let cluster1 = create_cluster_for_test_with_config(&[], failure_detector_config)?;
+        let cluster2 = create_cluster_for_test_with_config(&[], failure_detector_config)?;
+        cluster2.add_peer_node(cluster1.listen_addr).await;
+
+        let ten_secs = Duration::from_secs(10);
+        cluster1
+            .wait_for_members(|members| members.len() == 2, ten_secs)
+            .await?;
+
+        drop(cluster2);
+        cluster1
+            .wait_for_members(|members| members.len() == 1, Duration::from_secs(2))
+            .await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_cluster_gossips_metadata() -> anyhow::Result<()> {
+        let cluster1 = create_cluster_for_test()?;
+        let cluster2 = create_cluster_for_test()?;
+        cluster2.set_roles(&[NodeRole::Indexer]);
+        cluster2.add_peer_node(cluster1.listen_addr).await;
+
+        let ten_secs = Duration::from_secs(10);
+        let cluster2_addr = cluster2.listen_addr;
+        cluster1
+            .wait_for_members(
+                |members| {
+                    members.iter().any(|member| {
+                        member.listen_addr == cluster2_addr
+                            && member.has_role(NodeRole::Indexer)
+                            && !member.has_role(NodeRole::Searcher)
+                    })
+                },
+                ten_secs,
+            )
+            .await?;
+
+        cluster2.set_metadata(LOAD_METADATA_KEY, "7".to_string());
+        cluster1
+            .wait_for_members(
+                |members| {
+                    members.iter().any(|member| {
+                        member.listen_addr == cluster2_addr && member.load() == Some(7)
+                    })
+                },
+                ten_secs,
+            )
+            .await?;
+        let cluster2_member = cluster1
+            .members()
+            .into_iter()
+            .find(|member| member.listen_addr == cluster2_addr)
+            .unwrap();
+        assert_eq!(
+            cluster2_member
+                .metadata
+                .get(VERSION_METADATA_KEY)
+                .map(String::as_str),
+            Some(env!("CARGO_PKG_VERSION"))
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_cluster_with_secret_keys() -> anyhow::Result<()> {
+        let old_key = vec![1u8; 32];
+        let new_key = vec![2u8; 32];
+        let cluster1 = create_cluster_for_test_with_secret_keys(std::slice::from_ref(&old_key))?;
+        // A node that has started rotating its keys still talks to the nodes using the old key.
+        let cluster2 = create_cluster_for_test_with_secret_keys(&[old_key, new_key])?;
+        let intruder = create_cluster_for_test_with_secret_keys(&[vec![3u8; 32]])?;
+        let clear_text_node = create_cluster_for_test()?;
+        cluster2.add_peer_node(cluster1.listen_addr).await;
+        intruder.add_peer_node(cluster1.listen_addr).await;
+        clear_text_node.add_peer_node(cluster1.listen_addr).await;
+
+        let ten_secs = Duration::from_secs(10);
+        cluster1
+            .wait_for_members(|members| members.len() == 2, ten_secs)
+            .await?;
+        cluster2
+            .wait_for_members(|members| members.len() == 2, ten_secs)
+            .await?;
+        tokio::time::sleep(Duration::from_secs(1)).await;
+
+        let mut cluster1_member_addrs = cluster1
+            .members()
+            .into_iter()
+            .map(|member| member.listen_addr)
+            .collect_vec();
+        cluster1_member_addrs.sort();
+        let mut expected_addrs = vec![cluster1.listen_addr, cluster2.listen_addr];
+        expected_addrs.sort();
+        assert_eq!(cluster1_member_addrs, expected_addrs);
+        assert_eq!(intruder.members().len(), 1);
+        assert_eq!(clear_text_node.members().len(), 1);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_cluster_secret_key_rotation() -> anyhow::Result<()> {
+        let old_key = vec![1u8; 32];
+        let new_key = vec![2u8; 32];
+        // Nodes one step apart in the rotation talk to each other: the new key has been
+        // appended on the first node and moved first on the second one...
+        let appended_node =
+            create_cluster_for_test_with_secret_keys(&[old_key.clone(), new_key.clone()])?;
+        let moved_node =
+            create_cluster_for_test_with_secret_keys(&[new_key.clone(), old_key.clone()])?;
+        moved_node.add_peer_node(appended_node.listen_addr).await;
+        // ... and the old key has been removed from the third one.
+        let rotated_node = create_cluster_for_test_with_secret_keys(&[new_key])?;
+        let stale_node = create_cluster_for_test_with_secret_keys(&[old_key])?;
+        rotated_node.add_peer_node(moved_node.listen_addr).await;
+        stale_node.add_peer_node(rotated_node.listen_addr).await;
+
+        let ten_secs = Duration::from_secs(10);
+        appended_node
+            .wait_for_members(|members| members.len() >= 2, ten_secs)
+            .await?;
+        moved_node
+            .wait_for_members(|members| members.len() >= 3, ten_secs)
+            .await?;
+        rotated_node
+            .wait_for_members(|members| members.len() >= 2, ten_secs)
+            .await?;
+        tokio::time::sleep(Duration::from_secs(1)).await;
+
+        // A node still encrypting with the old key cannot join once it has been removed.
+        assert!(rotated_node
+            .members()
+            .iter()
+            .all(|member| member.listen_addr != stale_node.listen_addr));
+        assert_eq!(stale_node.members().len(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_cluster_secret_keys() -> anyhow::Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let secret_keys_path = temp_dir.path().join("cluster-secret");
+        std::fs::write(
+            &secret_keys_path,
+            format!(
+                "# New key first.\n{}\n\n{}\n",
+                base64::encode([2u8; 32]),
+                base64::encode([1u8; 32])
+            ),
+        )?;
+        let secret_keys = read_cluster_secret_keys(&secret_keys_path)?;
+        assert_eq!(secret_keys, vec![vec![2u8; 32], vec![1u8; 32]]);
+
+        std::fs::write(&secret_keys_path, "# No key.\n")?;
+        assert!(read_cluster_secret_keys(&secret_keys_path).is_err());
+
+        std::fs::write(&secret_keys_path, "not base64!\n")?;
+        assert!(read_cluster_secret_keys(&secret_keys_path).is_err());
+        Ok(())
+    }
 }
@@ -17,6 +17,8 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program. If not, see <http://www.gnu.org/licenses/>.
 
+use quickwit_common::service_error::{ErrorCode, ServiceError};
+use quickwit_proto::error::GrpcServiceError;
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
@@ -46,6 +48,13 @@ pub enum ClusterError {
         message: String,
     },
 
+    /// Read cluster secret keys error.
+    #[error("Failed to read cluster secret keys: `{message}`")]
+    ReadSecretKeysError {
+        /// Underlying error message.
+        message: String,
+    },
+
     /// Write host key error.
     #[error("Failed to write host key: `{message}`")]
     WriteHostKeyError {
@@ -54,16 +63,16 @@ pub enum ClusterError {
     },
 }
 
+impl ServiceError for ClusterError {
+    fn error_code(&self) -> ErrorCode {
+        // These errors all come from the node setup and are unrelated to the request.
+        ErrorCode::Internal
+    }
+}
+
 impl From<ClusterError> for tonic::Status {
     fn from(error: ClusterError) -> tonic::Status {
-        let code = match error {
-            ClusterError::CreateClusterError { .. } => tonic::Code::Internal,
-            ClusterError::UDPPortBindingError { .. } => tonic::Code::PermissionDenied,
-            ClusterError::ReadHostKeyError { .. } => tonic::Code::Internal,
-            ClusterError::WriteHostKeyError { .. } => tonic::Code::Internal,
-        };
-        let message = error.to_string();
-        tonic::Status::new(code, message)
+        error.grpc_status()
     }
 }
 
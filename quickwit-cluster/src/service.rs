@@ -35,6 +35,12 @@ impl From<Member> for PMember {
             id: member.host_key.to_string(),
             listen_address: member.listen_addr.to_string(),
             is_self: member.is_self,
+            roles: member
+                .roles
+                .iter()
+                .map(|role| role.as_str().to_string())
+                .collect(),
+            metadata: member.metadata.into_iter().collect(),
         }
     }
 }
@@ -92,12 +98,13 @@ impl ClusterService for ClusterServiceImpl {
 
 #[cfg(test)]
 mod tests {
+    use std::collections::BTreeMap;
     use std::net::ToSocketAddrs;
 
     use quickwit_proto::Member as PMember;
     use uuid::Uuid;
 
-    use crate::cluster::Member;
+    use crate::cluster::{Member, NodeRole};
 
     #[tokio::test]
     async fn test_cluster_convert_proto_member() {
@@ -105,10 +112,16 @@ mod tests {
         let listen_addr = "localhost:12345".to_socket_addrs().unwrap().next().unwrap();
         let is_self = true;
 
+        let mut metadata = BTreeMap::new();
+        metadata.insert("load".to_string(), "2".to_string());
         let member = Member {
             host_key,
             listen_addr,
             is_self,
+            roles: vec![NodeRole::Searcher, NodeRole::Janitor]
+                .into_iter()
+                .collect(),
+            metadata: metadata.clone(),
         };
         println!("member={:?}", member);
 
@@ -119,6 +132,8 @@ mod tests {
             id: host_key.to_string(),
             listen_address: listen_addr.to_string(),
             is_self,
+            roles: vec!["searcher".to_string(), "janitor".to_string()],
+            metadata: metadata.into_iter().collect(),
         };
         println!("expected={:?}", expected);
 
@@ -0,0 +1,94 @@
+// Copyright (C) 2021 Quickwit, Inc.
+//
+// Quickwit is offered under the AGPL v3.0 and as commercial software.
+// For commercial licensing, contact us at hello@quickwit.io.
+//
+// AGPL:
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+//! Thin helpers around the `prometheus` crate.
+//!
+//! All metrics are registered in the default prometheus registry, so that
+//! [`metrics_text_payload`] exposes every metric defined in any quickwit crate.
+
+use prometheus::{Encoder, HistogramOpts, Opts, TextEncoder};
+pub use prometheus::{Histogram, HistogramTimer, IntCounter, IntCounterVec, IntGauge, IntGaugeVec};
+
+/// Creates and registers a new counter.
+pub fn new_counter(name: &str, help: &str) -> IntCounter {
+    let counter = IntCounter::new(name, help).expect("Failed to create counter.");
+    prometheus::register(Box::new(counter.clone())).expect("Failed to register counter.");
+    counter
+}
+
+/// Creates and registers a new counter partitioned by the given labels.
+pub fn new_counter_vec(name: &str, help: &str, label_names: &[&str]) -> IntCounterVec {
+    let counter_vec =
+        IntCounterVec::new(Opts::new(name, help), label_names).expect("Failed to create counter.");
+    prometheus::register(Box::new(counter_vec.clone())).expect("Failed to register counter.");
+    counter_vec
+}
+
+/// Creates and registers a new gauge.
+pub fn new_gauge(name: &str, help: &str) -> IntGauge {
+    let gauge = IntGauge::new(name, help).expect("Failed to create gauge.");
+    prometheus::register(Box::new(gauge.clone())).expect("Failed to register gauge.");
+    gauge
+}
+
+/// Creates and registers a new gauge partitioned by the given labels.
+pub fn new_gauge_vec(name: &str, help: &str, label_names: &[&str]) -> IntGaugeVec {
+    let gauge_vec =
+        IntGaugeVec::new(Opts::new(name, help), label_names).expect("Failed to create gauge.");
+    prometheus::register(Box::new(gauge_vec.clone())).expect("Failed to register gauge.");
+    gauge_vec
+}
+
+/// Creates and registers a new histogram with the default buckets.
+pub fn new_histogram(name: &str, help: &str) -> Histogram {
+    let histogram =
+        Histogram::with_opts(HistogramOpts::new(name, help)).expect("Failed to create histogram.");
+    prometheus::register(Box::new(histogram.clone())).expect("Failed to register histogram.");
+    histogram
+}
+
+/// Renders all the registered metrics using the prometheus text exposition format.
+pub fn metrics_text_payload() -> String {
+    let metric_families = prometheus::gather();
+    let mut buffer = Vec::new();
+    // Encoding into a `Vec` cannot fail, unless a metric is malformed.
+    TextEncoder::new()
+        .encode(&metric_families, &mut buffer)
+        .expect("Failed to encode metrics.");
+    String::from_utf8(buffer).expect("Prometheus text format should be valid UTF-8.")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_metrics_text_payload() {
+        let counter = new_counter("test_counter", "A test counter.");
+        counter.inc_by(3);
+        let counter_vec = new_counter_vec("test_counter_vec", "A test counter vec.", &["label"]);
+        counter_vec.with_label_values(&["foo"]).inc();
+        let gauge = new_gauge("test_gauge", "A test gauge.");
+        gauge.set(7);
+        let payload = metrics_text_payload();
+        assert!(payload.contains("test_counter 3"));
+        assert!(payload.contains("test_counter_vec{label=\"foo\"} 1"));
+        assert!(payload.contains("test_gauge 7"));
+    }
+}
@@ -0,0 +1,261 @@
+// Copyright (C) 2021 Quickwit, Inc.
+//
+// Quickwit is offered under the AGPL v3.0 and as commercial software.
+// For commercial licensing, contact us at hello@quickwit.io.
+//
+// AGPL:
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+//! Structured errors shared by the REST and gRPC APIs.
+//!
+//! Every error returned to an API client is described by an [`ErrorResponse`], whose
+//! `code` is stable across releases and can be matched on, unlike the message.
+
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+/// Stable error codes exposed to the API clients.
+///
+/// Codes are serialized in screaming snake case, e.g. `INDEX_DOES_NOT_EXIST`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(into = "&'static str", try_from = "String")]
+pub enum ErrorCode {
+    /// The request is malformed: unknown parameter, invalid value, etc.
+    InvalidArgument,
+    /// The search query could not be parsed or run against the index.
+    InvalidQuery,
+    /// The request lacks valid credentials.
+    Unauthenticated,
+    /// The credentials do not grant access to the resource.
+    Forbidden,
+    /// The route or the resource does not exist.
+    NotFound,
+    /// The index does not exist.
+    IndexDoesNotExist,
+    /// The index already exists.
+    IndexAlreadyExists,
+    /// The request conflicts with the current state of the resource.
+    Conflict,
+    /// The request did not complete in time.
+    Timeout,
+    /// The server is overloaded and rejected the request without doing any work.
+    Overloaded,
+    /// A dependency of the server (metastore, storage, etc.) could not be reached.
+    Unavailable,
+    /// Any other error.
+    Internal,
+}
+
+const ERROR_CODES: [ErrorCode; 12] = [
+    ErrorCode::InvalidArgument,
+    ErrorCode::InvalidQuery,
+    ErrorCode::Unauthenticated,
+    ErrorCode::Forbidden,
+    ErrorCode::NotFound,
+    ErrorCode::IndexDoesNotExist,
+    ErrorCode::IndexAlreadyExists,
+    ErrorCode::Conflict,
+    ErrorCode::Timeout,
+    ErrorCode::Overloaded,
+    ErrorCode::Unavailable,
+    ErrorCode::Internal,
+];
+
+impl ErrorCode {
+    /// Returns the serialized form of the code.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ErrorCode::InvalidArgument => "INVALID_ARGUMENT",
+            ErrorCode::InvalidQuery => "INVALID_QUERY",
+            ErrorCode::Unauthenticated => "UNAUTHENTICATED",
+            ErrorCode::Forbidden => "FORBIDDEN",
+            ErrorCode::NotFound => "NOT_FOUND",
+            ErrorCode::IndexDoesNotExist => "INDEX_DOES_NOT_EXIST",
+            ErrorCode::IndexAlreadyExists => "INDEX_ALREADY_EXISTS",
+            ErrorCode::Conflict => "CONFLICT",
+            ErrorCode::Timeout => "TIMEOUT",
+            ErrorCode::Overloaded => "OVERLOADED",
+            ErrorCode::Unavailable => "UNAVAILABLE",
+            ErrorCode::Internal => "INTERNAL",
+        }
+    }
+
+    /// Returns the HTTP status code of the error.
+    pub fn http_status_code(&self) -> u16 {
+        match self {
+            ErrorCode::InvalidArgument | ErrorCode::InvalidQuery => 400,
+            ErrorCode::Unauthenticated => 401,
+            ErrorCode::Forbidden => 403,
+            ErrorCode::NotFound | ErrorCode::IndexDoesNotExist => 404,
+            ErrorCode::IndexAlreadyExists | ErrorCode::Conflict => 409,
+            ErrorCode::Internal => 500,
+            ErrorCode::Overloaded | ErrorCode::Unavailable => 503,
+            ErrorCode::Timeout => 504,
+        }
+    }
+
+    /// Returns true if the same request may succeed if it is sent again later.
+    pub fn is_retryable(&self) -> bool {
+        matches!(
+            self,
+            ErrorCode::Timeout | ErrorCode::Overloaded | ErrorCode::Unavailable
+        )
+    }
+}
+
+impl fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for ErrorCode {
+    type Err = String;
+
+    fn from_str(code_str: &str) -> Result<Self, Self::Err> {
+        ERROR_CODES
+            .iter()
+            .copied()
+            .find(|code| code.as_str() == code_str)
+            .ok_or_else(|| format!("Unknown error code `{}`.", code_str))
+    }
+}
+
+impl From<ErrorCode> for &'static str {
+    fn from(code: ErrorCode) -> Self {
+        code.as_str()
+    }
+}
+
+impl TryFrom<String> for ErrorCode {
+    type Error = String;
+
+    fn try_from(code_str: String) -> Result<Self, Self::Error> {
+        code_str.parse()
+    }
+}
+
+/// Error returned to the API clients.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ErrorResponse {
+    /// Stable code of the error.
+    pub code: ErrorCode,
+    /// Human readable description of the error.
+    pub message: String,
+    /// Context of the error, e.g. the ID of the index that does not exist.
+    #[serde(default)]
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub details: BTreeMap<String, String>,
+    /// True if the same request may succeed if it is sent again later.
+    pub retryable: bool,
+}
+
+/// Errors that can be returned to the API clients.
+pub trait ServiceError: fmt::Display {
+    /// Returns the code of the error.
+    fn error_code(&self) -> ErrorCode;
+
+    /// Returns the message of the error. Defaults to the `Display` implementation.
+    fn error_message(&self) -> String {
+        self.to_string()
+    }
+
+    /// Returns the context of the error.
+    fn error_details(&self) -> BTreeMap<String, String> {
+        BTreeMap::new()
+    }
+
+    /// Builds the response returned to the API clients.
+    fn error_response(&self) -> ErrorResponse {
+        let code = self.error_code();
+        ErrorResponse {
+            code,
+            message: self.error_message(),
+            details: self.error_details(),
+            retryable: code.is_retryable(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug)]
+    struct IndexDoesNotExist;
+
+    impl fmt::Display for IndexDoesNotExist {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            write!(f, "Index `my-index` does not exist.")
+        }
+    }
+
+    impl ServiceError for IndexDoesNotExist {
+        fn error_code(&self) -> ErrorCode {
+            ErrorCode::IndexDoesNotExist
+        }
+
+        fn error_details(&self) -> BTreeMap<String, String> {
+            vec![("index_id".to_string(), "my-index".to_string())]
+                .into_iter()
+                .collect()
+        }
+    }
+
+    #[test]
+    fn test_error_response_serialization() -> anyhow::Result<()> {
+        let error_response = IndexDoesNotExist.error_response();
+        assert_eq!(
+            serde_json::to_value(&error_response)?,
+            serde_json::json!({
+                "code": "INDEX_DOES_NOT_EXIST",
+                "message": "Index `my-index` does not exist.",
+                "details": {"index_id": "my-index"},
+                "retryable": false,
+            })
+        );
+        let timeout_response = ErrorResponse {
+            code: ErrorCode::Timeout,
+            message: "Deadline exceeded.".to_string(),
+            details: BTreeMap::new(),
+            retryable: true,
+        };
+        let timeout_response_json = serde_json::to_string(&timeout_response)?;
+        assert_eq!(
+            timeout_response_json,
+            r#"{"code":"TIMEOUT","message":"Deadline exceeded.","retryable":true}"#
+        );
+        assert_eq!(
+            serde_json::from_str::<ErrorResponse>(&timeout_response_json)?,
+            timeout_response
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_error_code_string_round_trip() {
+        for code in ERROR_CODES {
+            assert_eq!(code.as_str().parse::<ErrorCode>(), Ok(code));
+            assert_eq!(
+                serde_json::to_string(&code).unwrap(),
+                format!("\"{}\"", code)
+            );
+        }
+        assert!("NOT_A_CODE".parse::<ErrorCode>().is_err());
+    }
+}
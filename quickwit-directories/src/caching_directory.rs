@@ -24,7 +24,7 @@ use std::{fmt, io};
 
 use async_trait::async_trait;
 use bytes::Bytes;
-use quickwit_storage::SliceCache;
+use quickwit_storage::{ReadCoalescer, SliceCache};
 use stable_deref_trait::StableDeref;
 use tantivy::directory::error::{DeleteError, OpenReadError, OpenWriteError};
 use tantivy::directory::{FileHandle, OwnedBytes, WatchHandle, WritePtr};
@@ -36,6 +36,7 @@ pub struct CachingDirectory {
     underlying: Arc<dyn Directory>,
     // TODO fixme: that's a pretty ugly cache we have here.
     cache: Arc<SliceCache>,
+    read_coalescer: Arc<ReadCoalescer>,
 }
 
 impl CachingDirectory {
@@ -44,8 +45,9 @@ impl CachingDirectory {
     ///
     /// The implementation is voluntarily very naive as it was design solely to
     /// address Quickwit's requirements.
-    /// Most notably, if two reads targetting the same read on the same path
-    /// happen concurrently, the read will be executed twice.
+    /// Concurrent async reads targetting bytes that are already being read on the same path
+    /// wait for the pending read instead of being executed twice. Blocking reads are not
+    /// deduplicated.
     ///
     /// The overall number of bytes held in memory may exceed the capacity at one point
     /// if a read request is large than `capacity_in_bytes`.
@@ -57,6 +59,7 @@ impl CachingDirectory {
         CachingDirectory {
             underlying,
             cache: Arc::new(SliceCache::with_capacity_in_bytes(capacity_in_bytes)),
+            read_coalescer: Arc::new(ReadCoalescer::default()),
         }
     }
 
@@ -69,6 +72,7 @@ impl CachingDirectory {
         CachingDirectory {
             underlying,
             cache: Arc::new(SliceCache::with_infinite_capacity()),
+            read_coalescer: Arc::new(ReadCoalescer::default()),
         }
     }
 }
@@ -82,6 +86,7 @@ impl fmt::Debug for CachingDirectory {
 struct CachingFileHandle {
     path: PathBuf,
     cache: Arc<SliceCache>,
+    read_coalescer: Arc<ReadCoalescer>,
     underlying_filehandle: Box<dyn FileHandle>,
 }
 
@@ -106,6 +111,18 @@ impl Deref for BytesWrapper {
     }
 }
 
+impl CachingFileHandle {
+    async fn read_bytes_and_fill_cache(&self, byte_range: Range<usize>) -> AsyncIoResult<Bytes> {
+        let read_bytes = self
+            .underlying_filehandle
+            .read_bytes_async(byte_range.clone())
+            .await?;
+        let bytes = Bytes::from(read_bytes.to_vec());
+        self.cache.put(self.path.clone(), byte_range, bytes.clone());
+        Ok(bytes)
+    }
+}
+
 #[async_trait]
 impl FileHandle for CachingFileHandle {
     fn read_bytes(&self, byte_range: Range<usize>) -> io::Result<OwnedBytes> {
@@ -125,16 +142,13 @@ impl FileHandle for CachingFileHandle {
         if let Some(owned_bytes) = self.cache.get(&self.path, byte_range.clone()) {
             return Ok(OwnedBytes::new(BytesWrapper(owned_bytes)));
         }
-        let read_bytes = self
-            .underlying_filehandle
-            .read_bytes_async(byte_range.clone())
+        let bytes = self
+            .read_coalescer
+            .read(&self.path, byte_range.clone(), || {
+                self.read_bytes_and_fill_cache(byte_range.clone())
+            })
             .await?;
-        self.cache.put(
-            self.path.clone(),
-            byte_range,
-            Bytes::from(read_bytes.to_vec()),
-        );
-        Ok(read_bytes)
+        Ok(OwnedBytes::new(BytesWrapper(bytes)))
     }
 }
 
@@ -157,6 +171,7 @@ impl Directory for CachingDirectory {
         let caching_file_handle = CachingFileHandle {
             path: path.to_path_buf(),
             cache: self.cache.clone(),
+            read_coalescer: self.read_coalescer.clone(),
             underlying_filehandle,
         };
         Ok(Box::new(caching_file_handle))
@@ -111,6 +111,8 @@ mod test {
             max_hits: 20,
             start_offset: 0,
             tags: vec![],
+            allow_partial_results: false,
+            timeout_millis: None,
         };
 
         let default_field_names = vec!["title".to_string(), "desc".to_string()];
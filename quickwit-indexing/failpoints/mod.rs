@@ -163,6 +163,7 @@ async fn aux_test_failpoints() -> anyhow::Result<()> {
             index_uri: "ram://test-index/".to_string(),
             index_config: Arc::new(index_config),
             checkpoint: Checkpoint::default(),
+            encryption_key_id: None,
         })
         .await?;
     let params = IndexerParams {
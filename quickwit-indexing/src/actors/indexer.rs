@@ -32,6 +32,7 @@ use tantivy::schema::{Field, Value};
 use tantivy::{Document, IndexBuilder, IndexSettings, IndexSortByField};
 use tracing::{info, warn};
 
+use crate::metrics::INDEXING_METRICS;
 use crate::models::{CommitPolicy, IndexedSplit, IndexerMessage, RawDocBatch, ScratchDirectory};
 
 #[derive(Clone, Default, Debug, Eq, PartialEq)]
@@ -180,6 +181,8 @@ impl IndexerState {
             .checkpoint_delta
             .extend(batch.checkpoint_delta)
             .with_context(|| "Batch delta does not follow indexer checkpoint")?;
+        let num_valid_docs_before = counters.num_valid_docs;
+        let overall_num_bytes_before = counters.overall_num_bytes;
         for doc_json in batch.docs {
             counters.overall_num_bytes += doc_json.len() as u64;
             indexed_split.docs_size_in_bytes += doc_json.len() as u64;
@@ -205,6 +208,12 @@ impl IndexerState {
             }
             ctx.record_progress();
         }
+        INDEXING_METRICS
+            .indexed_docs_total
+            .inc_by(counters.num_valid_docs - num_valid_docs_before);
+        INDEXING_METRICS
+            .indexed_bytes_total
+            .inc_by(counters.overall_num_bytes - overall_num_bytes_before);
         Ok(())
     }
 }
@@ -271,6 +280,9 @@ impl SyncActor for Indexer {
             IndexerMessage::CommitTimeout { split_id } => {
                 self.process_commit_timeout(&split_id, ctx)?;
             }
+            IndexerMessage::Commit => {
+                self.send_to_packager(CommitTrigger::Requested, ctx)?;
+            }
         }
         Ok(())
     }
@@ -298,6 +310,7 @@ enum CommitTrigger {
     Timeout,
     NoMoreDocs,
     NumDocsLimit,
+    Requested,
 }
 
 impl Indexer {
@@ -396,7 +409,7 @@ mod tests {
     use super::Indexer;
     use crate::actors::indexer::{record_timestamp, IndexerCounters};
     use crate::actors::IndexerParams;
-    use crate::models::{CommitPolicy, RawDocBatch, ScratchDirectory};
+    use crate::models::{CommitPolicy, IndexerMessage, RawDocBatch, ScratchDirectory};
 
     #[test]
     fn test_record_timestamp() {
@@ -551,6 +564,46 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_indexer_commit() -> anyhow::Result<()> {
+        quickwit_common::setup_logging_for_tests();
+        let universe = Universe::new();
+        let (mailbox, inbox) = create_test_mailbox();
+        let index_config = Arc::new(quickwit_index_config::default_config_for_tests());
+        let indexer_params = IndexerParams::for_test()?;
+        let indexer = Indexer::try_new(
+            "test-index".to_string(),
+            index_config,
+            indexer_params,
+            mailbox,
+        )?;
+        let (indexer_mailbox, indexer_handle) = universe.spawn_actor(indexer).spawn_sync();
+        // Nothing to commit.
+        universe
+            .send_message(&indexer_mailbox, IndexerMessage::Commit)
+            .await?;
+        universe
+            .send_message(
+                &indexer_mailbox,
+                RawDocBatch {
+                    docs: vec![r#"{"body": "happy", "timestamp": 1628837062}"#.to_string()],
+                    checkpoint_delta: CheckpointDelta::from(0..1),
+                }
+                .into(),
+            )
+            .await?;
+        universe
+            .send_message(&indexer_mailbox, IndexerMessage::Commit)
+            .await?;
+        let indexer_counters = indexer_handle.process_pending_and_observe().await.state;
+        assert_eq!(indexer_counters.num_splits_emitted, 1);
+        assert_eq!(indexer_counters.num_docs_in_split, 0);
+        let output_messages = inbox.drain_available_message_for_test();
+        assert_eq!(output_messages.len(), 1);
+        assert_eq!(output_messages[0].num_docs, 1);
+        Ok(())
+    }
+
     #[tokio::test]
     async fn test_indexer_eof() -> anyhow::Result<()> {
         quickwit_common::setup_logging_for_tests();
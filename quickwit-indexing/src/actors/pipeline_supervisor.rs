@@ -36,7 +36,9 @@ use crate::actors::{
     Uploader,
 };
 use crate::models::IndexingStatistics;
-use crate::source::{quickwit_supported_sources, SourceActor, SourceConfig};
+use crate::source::{
+    quickwit_supported_sources, IngestApiQueues, SourceActor, SourceConfig, SourceExecutionContext,
+};
 use crate::{MergePolicy, StableMultitenantWithTimestampMergePolicy};
 
 pub struct IndexingPipelineHandler {
@@ -182,7 +184,10 @@ impl IndexingPipelineSupervisor {
         let index_storage = self
             .params
             .storage_uri_resolver
-            .resolve(&index_metadata.index_uri)?;
+            .resolve_with_encryption_key(
+                &index_metadata.index_uri,
+                index_metadata.encryption_key_id.as_deref(),
+            )?;
         let tags_field = index_metadata
             .index_config
             .tags_field(&index_metadata.index_config.schema());
@@ -284,8 +289,17 @@ impl IndexingPipelineSupervisor {
             .spawn_sync();
 
         // Source
+        let source_ctx = Arc::new(SourceExecutionContext {
+            index_id: self.params.index_id.clone(),
+            metastore: self.params.metastore.clone(),
+            ingest_api_queues: self.params.ingest_api_queues.clone(),
+        });
         let source = quickwit_supported_sources()
-            .load_source(self.params.source_config.clone(), index_metadata.checkpoint)
+            .load_source(
+                source_ctx,
+                self.params.source_config.clone(),
+                index_metadata.checkpoint,
+            )
             .await?;
         let actor_source = SourceActor {
             source,
@@ -419,6 +433,8 @@ pub struct IndexingPipelineParams {
     pub indexer_params: IndexerParams,
     pub metastore: Arc<dyn Metastore>,
     pub storage_uri_resolver: StorageUriResolver,
+    /// Queues consumed by the pipeline if its source is an ingest API source.
+    pub ingest_api_queues: IngestApiQueues,
 }
 
 #[cfg(test)]
@@ -434,7 +450,7 @@ mod tests {
 
     use super::{IndexingPipelineParams, IndexingPipelineSupervisor};
     use crate::actors::IndexerParams;
-    use crate::source::SourceConfig;
+    use crate::source::{IngestApiQueues, SourceConfig};
 
     #[tokio::test]
     async fn test_indexing_pipeline() -> anyhow::Result<()> {
@@ -454,6 +470,7 @@ mod tests {
                     index_uri: "ram://test-index".to_string(),
                     index_config: Arc::new(quickwit_index_config::default_config_for_tests()),
                     checkpoint: Default::default(),
+                    encryption_key_id: None,
                 };
                 Ok(index_metadata)
             });
@@ -487,6 +504,7 @@ mod tests {
             indexer_params,
             metastore: Arc::new(metastore),
             storage_uri_resolver: StorageUriResolver::for_test(),
+            ingest_api_queues: IngestApiQueues::default(),
         };
         let indexing_supervisor = IndexingPipelineSupervisor::new(indexing_pipeline_params);
         let (_pipeline_mailbox, pipeline_handler) =
@@ -27,6 +27,7 @@ use quickwit_metastore::Metastore;
 use tokio::sync::oneshot::Receiver;
 use tracing::info;
 
+use crate::metrics::INDEXING_METRICS;
 use crate::models::{MergePlannerMessage, PublishOperation, PublisherMessage};
 
 #[derive(Debug, Clone, Default)]
@@ -116,6 +117,9 @@ impl AsyncActor for Publisher {
         ctx: &ActorContext<Receiver<PublisherMessage>>,
     ) -> Result<(), quickwit_actors::ActorExitStatus> {
         fail_point!("publisher:before");
+        INDEXING_METRICS
+            .publisher_queue_depth
+            .set(ctx.mailbox().num_pending_messages() as i64);
         let publisher_message = {
             let _protect_guard = ctx.protect_zone();
             uploaded_split_future
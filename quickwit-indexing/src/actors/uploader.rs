@@ -33,6 +33,7 @@ use tantivy::chrono::Utc;
 use tokio::sync::oneshot::Receiver;
 use tracing::{info, warn};
 
+use crate::metrics::INDEXING_METRICS;
 use crate::models::{PackagedSplit, PublishOperation, PublisherMessage};
 use crate::semaphore::Semaphore;
 
@@ -187,6 +188,9 @@ impl AsyncActor for Uploader {
         // publishing in the order splits finish their uploading.
         ctx.send_message(&self.publisher_mailbox, split_uploaded_rx)
             .await?;
+        INDEXING_METRICS
+            .publisher_queue_depth
+            .set(self.publisher_mailbox.num_pending_messages() as i64);
 
         // The permit will be added back manually to the semaphore the task after it is finished.
         let permit_guard = self.concurrent_upload_permits.acquire().await;
@@ -199,6 +203,7 @@ impl AsyncActor for Uploader {
 
         tokio::spawn(async move {
             fail_point!("uploader:intask:before");
+            INDEXING_METRICS.num_ongoing_uploads.inc();
             let stage_and_upload_res: anyhow::Result<()> =
                 stage_and_upload_split(split, &*index_storage, &*metastore, counters)
                     .await
@@ -216,6 +221,7 @@ impl AsyncActor for Uploader {
                 warn!(cause=%cause, "Failed to upload split. Killing!");
                 kill_switch.kill();
             }
+            INDEXING_METRICS.num_ongoing_uploads.dec();
 
             // we explicitely drop it in order to force move the permit guard into the async task.
             mem::drop(permit_guard);
@@ -26,11 +26,12 @@ use quickwit_storage::StorageUriResolver;
 
 use crate::actors::{IndexerParams, IndexingPipelineParams, IndexingPipelineSupervisor};
 use crate::models::IndexingStatistics;
-use crate::source::SourceConfig;
+use crate::source::{IngestApiQueues, SourceConfig};
 
 pub mod actors;
 mod garbage_collection;
 mod merge_policy;
+mod metrics;
 pub mod models;
 pub(crate) mod semaphore;
 pub mod source;
@@ -57,6 +58,7 @@ pub async fn index_data(
         indexer_params,
         metastore,
         storage_uri_resolver,
+        ingest_api_queues: IngestApiQueues::default(),
     };
     let indexing_supervisor = IndexingPipelineSupervisor::new(indexing_pipeline_params);
     let (_pipeline_mailbox, pipeline_handler) =
@@ -0,0 +1,54 @@
+// Copyright (C) 2021 Quickwit, Inc.
+//
+// Quickwit is offered under the AGPL v3.0 and as commercial software.
+// For commercial licensing, contact us at hello@quickwit.io.
+//
+// AGPL:
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use once_cell::sync::Lazy;
+use quickwit_common::metrics::{new_counter, new_gauge, IntCounter, IntGauge};
+
+pub(crate) struct IndexingMetrics {
+    pub indexed_docs_total: IntCounter,
+    pub indexed_bytes_total: IntCounter,
+    pub num_ongoing_uploads: IntGauge,
+    pub publisher_queue_depth: IntGauge,
+}
+
+impl Default for IndexingMetrics {
+    fn default() -> Self {
+        IndexingMetrics {
+            indexed_docs_total: new_counter(
+                "quickwit_indexing_indexed_docs_total",
+                "Number of valid documents indexed.",
+            ),
+            indexed_bytes_total: new_counter(
+                "quickwit_indexing_indexed_bytes_total",
+                "Number of bytes of documents processed by the indexer, including invalid ones.",
+            ),
+            num_ongoing_uploads: new_gauge(
+                "quickwit_indexing_ongoing_uploads",
+                "Number of splits being staged and uploaded by the uploader.",
+            ),
+            publisher_queue_depth: new_gauge(
+                "quickwit_indexing_publisher_queue_depth",
+                "Number of splits waiting in the publisher mailbox.",
+            ),
+        }
+    }
+}
+
+/// Indexing metrics, shared by all the indexing pipelines of the process.
+pub(crate) static INDEXING_METRICS: Lazy<IndexingMetrics> = Lazy::new(IndexingMetrics::default);
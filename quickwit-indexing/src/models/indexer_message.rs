@@ -22,7 +22,12 @@ use crate::models::RawDocBatch;
 #[derive(Debug)]
 pub enum IndexerMessage {
     Batch(RawDocBatch),
-    CommitTimeout { split_id: String },
+    CommitTimeout {
+        split_id: String,
+    },
+    /// Commits the current split right away, e.g. before the source gives up some of its
+    /// partitions.
+    Commit,
 }
 
 impl From<RawDocBatch> for IndexerMessage {
@@ -20,6 +20,7 @@
 use std::io;
 use std::io::SeekFrom;
 use std::path::PathBuf;
+use std::sync::Arc;
 
 use anyhow::Context;
 use async_trait::async_trait;
@@ -31,7 +32,7 @@ use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncSeekExt, BufReader};
 use tracing::info;
 
 use crate::models::{IndexerMessage, RawDocBatch};
-use crate::source::{Source, SourceContext, TypedSourceFactory};
+use crate::source::{Source, SourceContext, SourceExecutionContext, TypedSourceFactory};
 
 /// Cut a new batch as soon as we have read BATCH_NUM_BYTES_THRESHOLD.
 const BATCH_NUM_BYTES_THRESHOLD: u64 = 500_000u64;
@@ -129,6 +130,7 @@ impl TypedSourceFactory for FileSourceFactory {
 
     // TODO handle checkpoint for files.
     async fn typed_create_source(
+        _ctx: Arc<SourceExecutionContext>,
         mut params: FileSourceParams,
         checkpoint: quickwit_metastore::checkpoint::Checkpoint,
     ) -> anyhow::Result<FileSource> {
@@ -180,6 +182,7 @@ mod tests {
 
     use quickwit_actors::{create_test_mailbox, Command, CommandOrMessage, Universe};
     use quickwit_metastore::checkpoint::Checkpoint;
+    use quickwit_metastore::SingleFileMetastore;
 
     use super::*;
     use crate::source::SourceActor;
@@ -192,8 +195,12 @@ mod tests {
         let params = FileSourceParams {
             filepath: Some(PathBuf::from("data/test_corpus.json")),
         };
+        let ctx = SourceExecutionContext::for_test(
+            Arc::new(SingleFileMetastore::for_test()),
+            "test-index",
+        );
         let file_source =
-            FileSourceFactory::typed_create_source(params, Checkpoint::default()).await?;
+            FileSourceFactory::typed_create_source(ctx, params, Checkpoint::default()).await?;
         let file_source_actor = SourceActor {
             source: Box::new(file_source),
             batch_sink: mailbox,
@@ -235,7 +242,12 @@ mod tests {
         let params = FileSourceParams {
             filepath: Some(temp_path.as_path().to_path_buf()),
         };
-        let source = FileSourceFactory::typed_create_source(params, Checkpoint::default()).await?;
+        let ctx = SourceExecutionContext::for_test(
+            Arc::new(SingleFileMetastore::for_test()),
+            "test-index",
+        );
+        let source =
+            FileSourceFactory::typed_create_source(ctx, params, Checkpoint::default()).await?;
         let file_source_actor = SourceActor {
             source: Box::new(source),
             batch_sink: mailbox,
@@ -319,7 +331,11 @@ mod tests {
             Position::from(4u64),
         );
         checkpoint.try_apply_delta(checkpoint_delta)?;
-        let source = FileSourceFactory::typed_create_source(params, checkpoint).await?;
+        let ctx = SourceExecutionContext::for_test(
+            Arc::new(SingleFileMetastore::for_test()),
+            "test-index",
+        );
+        let source = FileSourceFactory::typed_create_source(ctx, params, checkpoint).await?;
         let file_source_actor = SourceActor {
             source: Box::new(source),
             batch_sink: mailbox,
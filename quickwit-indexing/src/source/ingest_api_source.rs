@@ -0,0 +1,354 @@
+// Copyright (C) 2021 Quickwit, Inc.
+//
+// Quickwit is offered under the AGPL v3.0 and as commercial software.
+// For commercial licensing, contact us at hello@quickwit.io.
+//
+// AGPL:
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use anyhow::bail;
+use async_trait::async_trait;
+use futures::FutureExt;
+use quickwit_actors::{ActorExitStatus, Mailbox};
+use quickwit_metastore::checkpoint::{Checkpoint, CheckpointDelta, PartitionId, Position};
+use serde::{Deserialize, Serialize};
+use tokio::sync::{mpsc, oneshot};
+use tracing::warn;
+
+use crate::models::{IndexerMessage, RawDocBatch};
+use crate::source::vec_source::position_from_offset;
+use crate::source::{Source, SourceContext, SourceExecutionContext, TypedSourceFactory};
+
+/// Maximum number of batches of documents waiting in a queue before the pushes block.
+const QUEUE_CAPACITY: usize = 100;
+
+/// Minimum delay between two fetches of the checkpoint of the index, polled to notify the
+/// pushers once their documents are published.
+const CHECKPOINT_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Batch of documents waiting in an [`IngestApiQueue`].
+struct QueuedDocs {
+    docs: Vec<String>,
+    /// Notified once the documents are published.
+    published_tx: oneshot::Sender<()>,
+}
+
+/// Queue of documents pushed in-process, for instance by the ingest endpoints of the REST
+/// API, and consumed by an [`IngestApiSource`].
+///
+/// Documents are only held in memory until they are published: a push only returns once its
+/// documents are published, and fails if they are dropped before that, e.g. because the
+/// process stops or the indexing pipeline fails.
+pub struct IngestApiQueue {
+    sender: mpsc::Sender<QueuedDocs>,
+    receiver: tokio::sync::Mutex<mpsc::Receiver<QueuedDocs>>,
+}
+
+impl IngestApiQueue {
+    fn new() -> Self {
+        let (sender, receiver) = mpsc::channel(QUEUE_CAPACITY);
+        IngestApiQueue {
+            sender,
+            receiver: tokio::sync::Mutex::new(receiver),
+        }
+    }
+
+    /// Pushes a batch of documents, waiting for room in the queue if it is full, and then for
+    /// the documents to be published.
+    pub async fn push(&self, docs: Vec<String>) -> anyhow::Result<()> {
+        let (published_tx, published_rx) = oneshot::channel();
+        let queued_docs = QueuedDocs { docs, published_tx };
+        if self.sender.send(queued_docs).await.is_err() {
+            bail!("The ingest API queue is closed.");
+        }
+        if published_rx.await.is_err() {
+            bail!("The documents were dropped before being published.");
+        }
+        Ok(())
+    }
+}
+
+/// Set of ingest API queues, identified by their ID.
+///
+/// Its owner, e.g. the ingest service of a node, passes it to the indexing pipelines it
+/// spawns (see [`SourceExecutionContext`]), so that their sources consume its queues. Clones
+/// share the same queues.
+#[derive(Clone, Default)]
+pub struct IngestApiQueues {
+    queues: Arc<Mutex<HashMap<String, Arc<IngestApiQueue>>>>,
+}
+
+impl IngestApiQueues {
+    /// Returns the queue with the given ID, creating it if it does not exist yet.
+    pub fn queue(&self, queue_id: &str) -> Arc<IngestApiQueue> {
+        self.queues
+            .lock()
+            .expect("The ingest API queues lock should not be poisoned.")
+            .entry(queue_id.to_string())
+            .or_insert_with(|| Arc::new(IngestApiQueue::new()))
+            .clone()
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct IngestApiSourceParams {
+    pub queue_id: String,
+    /// Partition of the index checkpoint recording the documents consumed by the source.
+    ///
+    /// Sources consuming queues of different processes, e.g. one per node of a cluster, must
+    /// use distinct partitions.
+    pub partition_id: String,
+}
+
+/// Source consuming the documents pushed to an [`IngestApiQueue`] of the
+/// [`IngestApiQueues`] of its [`SourceExecutionContext`].
+///
+/// Batches are numbered from the last position recorded in the checkpoint, so that the
+/// positions keep increasing when the source is restarted. The source polls the checkpoint of
+/// the index, at most once per `CHECKPOINT_POLL_INTERVAL`, to notify the pushers once their
+/// documents are published.
+pub struct IngestApiSource {
+    ctx: Arc<SourceExecutionContext>,
+    queue: Arc<IngestApiQueue>,
+    partition: PartitionId,
+    next_doc_offset: usize,
+    num_docs_processed: u64,
+    /// Batches emitted but not published yet, along with the offset following their last
+    /// document.
+    unpublished_batches: VecDeque<(usize, oneshot::Sender<()>)>,
+    /// Last time the checkpoint of the index was fetched.
+    last_checkpoint_poll_opt: Option<Instant>,
+}
+
+/// Returns the offset of the first document that is not recorded in the checkpoint yet.
+fn next_doc_offset(checkpoint: &Checkpoint, partition: &PartitionId) -> anyhow::Result<usize> {
+    match checkpoint.position_for_partition(partition) {
+        Some(Position::Offset(offset_str)) => Ok(offset_str.parse::<usize>()? + 1),
+        Some(Position::Beginning) | None => Ok(0),
+    }
+}
+
+impl IngestApiSource {
+    /// Notifies the pushers of the batches published since the last call.
+    async fn notify_published_batches(&mut self) {
+        if self.unpublished_batches.is_empty() {
+            return;
+        }
+        if matches!(self.last_checkpoint_poll_opt, Some(last_checkpoint_poll) if last_checkpoint_poll.elapsed() < CHECKPOINT_POLL_INTERVAL)
+        {
+            return;
+        }
+        self.last_checkpoint_poll_opt = Some(Instant::now());
+        let published_doc_offset = match self
+            .ctx
+            .metastore
+            .index_metadata(&self.ctx.index_id)
+            .await
+            .map_err(anyhow::Error::from)
+            .and_then(|index_metadata| next_doc_offset(&index_metadata.checkpoint, &self.partition))
+        {
+            Ok(published_doc_offset) => published_doc_offset,
+            Err(error) => {
+                warn!(index_id = %self.ctx.index_id, error = ?error, "Failed to fetch the checkpoint of the index.");
+                return;
+            }
+        };
+        while matches!(self.unpublished_batches.front(), Some((end_doc_offset, _)) if *end_doc_offset <= published_doc_offset)
+        {
+            if let Some((_, published_tx)) = self.unpublished_batches.pop_front() {
+                // The pusher may have given up waiting.
+                let _ = published_tx.send(());
+            }
+        }
+    }
+}
+
+pub struct IngestApiSourceFactory;
+
+#[async_trait]
+impl TypedSourceFactory for IngestApiSourceFactory {
+    type Source = IngestApiSource;
+    type Params = IngestApiSourceParams;
+
+    async fn typed_create_source(
+        ctx: Arc<SourceExecutionContext>,
+        params: IngestApiSourceParams,
+        checkpoint: Checkpoint,
+    ) -> anyhow::Result<Self::Source> {
+        let partition = PartitionId::from(params.partition_id.as_str());
+        let next_doc_offset = next_doc_offset(&checkpoint, &partition)?;
+        let queue = ctx.ingest_api_queues.queue(&params.queue_id);
+        Ok(IngestApiSource {
+            ctx,
+            queue,
+            partition,
+            next_doc_offset,
+            num_docs_processed: 0,
+            unpublished_batches: VecDeque::new(),
+            last_checkpoint_poll_opt: None,
+        })
+    }
+}
+
+#[async_trait]
+impl Source for IngestApiSource {
+    async fn emit_batches(
+        &mut self,
+        batch_sink: &Mailbox<IndexerMessage>,
+        ctx: &SourceContext,
+    ) -> Result<(), ActorExitStatus> {
+        self.notify_published_batches().await;
+        let mut receiver = self.queue.receiver.lock().await;
+        // We wait for documents until shortly before the heartbeat...
+        let queued_docs_opt =
+            tokio::time::timeout(quickwit_actors::HEARTBEAT * 4 / 5, receiver.recv())
+                .await
+                .ok()
+                .flatten();
+        let mut queued_docs_batch = match queued_docs_opt {
+            Some(queued_docs) => vec![queued_docs],
+            None => return Ok(()),
+        };
+        // ...and then take the batches already waiting in the queue, if any.
+        while let Some(Some(queued_docs)) = receiver.recv().now_or_never() {
+            queued_docs_batch.push(queued_docs);
+        }
+        drop(receiver);
+        let from_doc_offset = self.next_doc_offset;
+        let mut docs = Vec::new();
+        for queued_docs in queued_docs_batch {
+            self.next_doc_offset += queued_docs.docs.len();
+            docs.extend(queued_docs.docs);
+            self.unpublished_batches
+                .push_back((self.next_doc_offset, queued_docs.published_tx));
+        }
+        self.num_docs_processed += docs.len() as u64;
+        let checkpoint_delta = CheckpointDelta::from_partition_delta(
+            self.partition.clone(),
+            position_from_offset(from_doc_offset),
+            position_from_offset(self.next_doc_offset),
+        );
+        let batch = RawDocBatch {
+            docs,
+            checkpoint_delta,
+        };
+        ctx.send_message(batch_sink, IndexerMessage::from(batch))
+            .await?;
+        Ok(())
+    }
+
+    fn observable_state(&self) -> serde_json::Value {
+        serde_json::json!({
+            "next_doc_offset": self.next_doc_offset,
+            "num_docs_processed": self.num_docs_processed,
+            "num_unpublished_batches": self.unpublished_batches.len(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use quickwit_actors::{create_test_mailbox, Universe};
+    use quickwit_metastore::{IndexMetadata, MockMetastore};
+    use serde_json::json;
+
+    use super::*;
+    use crate::source::SourceActor;
+
+    fn checkpoint_at(partition_id: &str, position: u64) -> anyhow::Result<Checkpoint> {
+        let mut checkpoint = Checkpoint::default();
+        checkpoint.try_apply_delta(CheckpointDelta::from_partition_delta(
+            PartitionId::from(partition_id),
+            Position::Beginning,
+            Position::from(position),
+        ))?;
+        Ok(checkpoint)
+    }
+
+    #[tokio::test]
+    async fn test_ingest_api_source() -> anyhow::Result<()> {
+        quickwit_common::setup_logging_for_tests();
+        let universe = Universe::new();
+        let (mailbox, inbox) = create_test_mailbox();
+        let published_checkpoint = Arc::new(Mutex::new(checkpoint_at("test-node", 9)?));
+        let published_checkpoint_clone = published_checkpoint.clone();
+        let mut mock_metastore = MockMetastore::default();
+        mock_metastore
+            .expect_index_metadata()
+            .returning(move |index_id| {
+                Ok(IndexMetadata {
+                    index_id: index_id.to_string(),
+                    index_uri: "ram://test-index".to_string(),
+                    index_config: Arc::new(quickwit_index_config::default_config_for_tests()),
+                    checkpoint: published_checkpoint_clone.lock().unwrap().clone(),
+                    encryption_key_id: None,
+                })
+            });
+        let params = IngestApiSourceParams {
+            queue_id: "test-ingest-api-source".to_string(),
+            partition_id: "test-node".to_string(),
+        };
+        let ingest_api_queues = IngestApiQueues::default();
+        let ctx = Arc::new(SourceExecutionContext {
+            index_id: "test-index".to_string(),
+            metastore: Arc::new(mock_metastore),
+            ingest_api_queues: ingest_api_queues.clone(),
+        });
+        let checkpoint = published_checkpoint.lock().unwrap().clone();
+        let ingest_api_source =
+            IngestApiSourceFactory::typed_create_source(ctx, params, checkpoint).await?;
+        let queue = ingest_api_queues.queue("test-ingest-api-source");
+        let queue_clone = queue.clone();
+        let first_push_handle = tokio::spawn(async move {
+            queue_clone
+                .push(vec!["{}".to_string(), "{}".to_string()])
+                .await
+        });
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        let second_push_handle =
+            tokio::spawn(async move { queue.push(vec!["{}".to_string()]).await });
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        let ingest_api_source_actor = SourceActor {
+            source: Box::new(ingest_api_source),
+            batch_sink: mailbox,
+        };
+        let (_ingest_api_source_mailbox, ingest_api_source_handle) =
+            universe.spawn_actor(ingest_api_source_actor).spawn_async();
+        let observation = ingest_api_source_handle.observe().await;
+        assert_eq!(
+            observation.state,
+            json!({"next_doc_offset": 13, "num_docs_processed": 3, "num_unpublished_batches": 2})
+        );
+        let batches = inbox.drain_available_message_for_test();
+        assert_eq!(batches.len(), 1);
+        assert!(
+            matches!(&batches[0], IndexerMessage::Batch(raw_batch) if format!("{:?}", raw_batch.checkpoint_delta) == "∆(test-node:(00000000000000000009..00000000000000000012])")
+        );
+        // The documents of the first push are published.
+        *published_checkpoint.lock().unwrap() = checkpoint_at("test-node", 11)?;
+        tokio::time::timeout(Duration::from_secs(5), first_push_handle).await???;
+        let observation = ingest_api_source_handle.observe().await;
+        assert_eq!(observation.state["num_unpublished_batches"], 1);
+
+        *published_checkpoint.lock().unwrap() = checkpoint_at("test-node", 12)?;
+        tokio::time::timeout(Duration::from_secs(5), second_push_handle).await???;
+        Ok(())
+    }
+}
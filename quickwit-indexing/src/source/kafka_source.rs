@@ -17,32 +17,64 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program. If not, see <http://www.gnu.org/licenses/>.
 
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::fmt;
+use std::sync::{mpsc as std_mpsc, Arc, Mutex};
 use std::time::Duration;
 
 use anyhow::{bail, Context};
 use async_trait::async_trait;
-use futures::StreamExt;
 use itertools::Itertools;
 use quickwit_actors::{ActorExitStatus, Mailbox};
 use quickwit_metastore::checkpoint::{Checkpoint, CheckpointDelta, PartitionId, Position};
 use rdkafka::config::{ClientConfig, RDKafkaLogLevel};
-use rdkafka::consumer::stream_consumer::StreamConsumer;
-use rdkafka::consumer::{Consumer, ConsumerContext, Rebalance};
-use rdkafka::error::{KafkaError, KafkaResult};
-use rdkafka::message::BorrowedMessage;
+use rdkafka::consumer::{BaseConsumer, Consumer, ConsumerContext, Rebalance};
+use rdkafka::error::{KafkaError, KafkaResult, RDKafkaErrorCode};
+use rdkafka::message::OwnedMessage;
+use rdkafka::statistics::Statistics;
 use rdkafka::topic_partition_list::TopicPartitionList;
 use rdkafka::util::Timeout;
 use rdkafka::{ClientContext, Message, Offset};
 use serde::{Deserialize, Serialize};
 use serde_json::json;
-use tracing::{debug, info, warn};
+use tokio::sync::{mpsc, oneshot};
+use tracing::{debug, error, info, warn};
 
 use crate::models::RawDocBatch;
-use crate::source::{IndexerMessage, Source, SourceContext, TypedSourceFactory};
+use crate::source::{
+    IndexerMessage, Source, SourceContext, SourceExecutionContext, TypedSourceFactory,
+};
+
+/// Maximum number of consecutive attempts made to recover from a Kafka error before the source
+/// fails.
+const MAX_RETRY_ATTEMPTS: u32 = 30;
+const BASE_RETRY_DELAY: Duration = Duration::from_millis(250);
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(20);
+
+/// Maximum amount of time the poll loop blocks waiting for a message.
+const POLL_TIMEOUT: Duration = Duration::from_millis(200);
+
+/// Capacity of the channel between the poll loop and the source.
+const EVENTS_CHANNEL_CAPACITY: usize = 1_000;
+
+/// Maximum amount of time the source waits for the messages of the revoked partitions to be
+/// published before giving up the partitions. It must stay well below the rebalance timeout of the
+/// consumer group (`max.poll.interval.ms`, 5 minutes by default).
+const REVOKE_PUBLISH_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Maximum amount of time the poll loop is blocked by a revocation. The source fails once
+/// `REVOKE_PUBLISH_TIMEOUT` has elapsed, but it may only process the revocation late, for instance
+/// if it is blocked on a full indexer mailbox. The partitions are then given up anyway before the
+/// consumer group times the consumer out.
+const REVOKE_WAIT_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// Interval at which the checkpoint of the index is fetched while waiting for the messages of the
+/// revoked partitions to be published.
+const CHECKPOINT_POLL_INTERVAL: Duration = Duration::from_millis(500);
 
 /// Required parameters for instantiating a `KafkaSource`.
+///
+/// Exactly one of `topic`, `topics`, or `topic_pattern` must be set.
 #[derive(Clone, Deserialize, Serialize)]
 pub struct KafkaSourceParams {
     /// Comma-separated list of host and port pairs that provide the initial addresses of Kafka
@@ -51,13 +83,80 @@ pub struct KafkaSourceParams {
     pub bootstrap_servers: String,
     /// Specifies the name of the consumer group a Kafka consumer belongs to.
     pub group_id: String,
-    /// Name of the topic that the source consumes.
-    pub topic: String,
+    /// Name of the topic that the source consumes. Partitions of a source configured with this
+    /// parameter are checkpointed under their bare partition number, which is the way the first
+    /// versions of the source identified them.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub topic: Option<String>,
+    /// Names of the topics that the source consumes. Partitions are checkpointed under the
+    /// `{topic}:{partition}` ID.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub topics: Vec<String>,
+    /// Regular expression matching the names of the topics that the source consumes. Topics
+    /// created after the source started are picked up on the next metadata refresh.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub topic_pattern: Option<String>,
     /// When set to `true`, the source will terminate after reading the last message of each
-    /// assigned partition. Otherwise, it will keep waiting for new incoming messages.
+    /// assigned partition. Otherwise, it will keep waiting for new incoming messages. It can only
+    /// be enabled for sources consuming a single topic.
     pub enable_partition_eof: Option<bool>,
 }
 
+impl KafkaSourceParams {
+    fn subscription(&self) -> anyhow::Result<KafkaSubscription> {
+        let subscription = match (&self.topic, self.topics.as_slice(), &self.topic_pattern) {
+            (Some(topic), [], None) => KafkaSubscription::Topic(topic.clone()),
+            (None, [_, ..], None) => KafkaSubscription::Topics(self.topics.clone()),
+            (None, [], Some(topic_pattern)) => {
+                // librdkafka treats topic names starting with `^` as regular expressions.
+                if topic_pattern.starts_with('^') {
+                    KafkaSubscription::Pattern(topic_pattern.clone())
+                } else {
+                    KafkaSubscription::Pattern(format!("^{}", topic_pattern))
+                }
+            }
+            _ => bail!("Exactly one of `topic`, `topics`, or `topic_pattern` must be set."),
+        };
+        let is_single_topic = match &subscription {
+            KafkaSubscription::Topic(_) => true,
+            KafkaSubscription::Topics(topics) => topics.len() == 1,
+            KafkaSubscription::Pattern(_) => false,
+        };
+        if self.enable_partition_eof.unwrap_or(false) && !is_single_topic {
+            bail!("`enable_partition_eof` is only supported for sources consuming a single topic.");
+        }
+        Ok(subscription)
+    }
+}
+
+/// Topics the consumer subscribes to.
+#[derive(Clone, Debug, PartialEq)]
+enum KafkaSubscription {
+    Topic(String),
+    Topics(Vec<String>),
+    Pattern(String),
+}
+
+impl KafkaSubscription {
+    fn topics(&self) -> Vec<&str> {
+        match self {
+            KafkaSubscription::Topic(topic) => vec![topic.as_str()],
+            KafkaSubscription::Topics(topics) => topics.iter().map(String::as_str).collect(),
+            KafkaSubscription::Pattern(topic_pattern) => vec![topic_pattern.as_str()],
+        }
+    }
+
+    /// Returns the ID under which the partition is recorded in the checkpoint.
+    fn partition_id(&self, topic: &str, partition: i32) -> PartitionId {
+        match self {
+            KafkaSubscription::Topic(_) => PartitionId::from(partition),
+            KafkaSubscription::Topics(_) | KafkaSubscription::Pattern(_) => {
+                PartitionId::from(format!("{}:{:0>10}", topic, partition))
+            }
+        }
+    }
+}
+
 /// Factory for instantiating a `KafkaSource`.
 pub struct KafkaSourceFactory;
 
@@ -67,24 +166,149 @@ impl TypedSourceFactory for KafkaSourceFactory {
     type Params = KafkaSourceParams;
 
     async fn typed_create_source(
+        ctx: Arc<SourceExecutionContext>,
         params: KafkaSourceParams,
         checkpoint: Checkpoint,
     ) -> anyhow::Result<Self::Source> {
-        KafkaSource::try_new(params, checkpoint).await
+        KafkaSource::try_new(ctx, params, checkpoint).await
     }
 }
 
-struct KafkaSourceContext;
+/// Events sent by the poll loop to the source.
+enum KafkaEvent {
+    Message(OwnedMessage),
+    PartitionEOF(i32),
+    /// The consumer group assigned new partitions to the consumer. The poll loop is blocked until
+    /// the source replies with the offsets from which to resume consuming these partitions.
+    AssignPartitions {
+        partitions: Vec<(String, i32)>,
+        assignment_tx: oneshot::Sender<Vec<(String, i32, Offset)>>,
+    },
+    /// The consumer group revoked partitions from the consumer. The poll loop is blocked until
+    /// the source has published the messages of these partitions it already consumed, so that
+    /// their next owner resumes right after them.
+    RevokePartitions {
+        partitions: Vec<(String, i32)>,
+        revocation_tx: std_mpsc::SyncSender<()>,
+    },
+    /// High watermarks of the partitions, as reported by the consumer statistics.
+    HighWatermarks(HashMap<(String, i32), i64>),
+    Error(anyhow::Error),
+}
+
+struct KafkaSourceContext {
+    events_tx: mpsc::Sender<KafkaEvent>,
+    /// Partitions assigned to the consumer by the last rebalance.
+    ///
+    /// The consumer uses the eager rebalance protocol, so a revocation revokes all the partitions
+    /// assigned to the consumer. The revocation callback does not have access to the consumer,
+    /// and `Rebalance::Revoke` does not list the revoked partitions, hence their tracking here.
+    assigned_partitions: Mutex<Vec<(String, i32)>>,
+}
+
+impl KafkaSourceContext {
+    fn send_event(&self, event: KafkaEvent) -> bool {
+        // Callbacks may also be invoked while the consumer is dropped from an async context, hence
+        // the use of the `futures` executor instead of `blocking_send`.
+        !self.events_tx.is_closed()
+            && futures::executor::block_on(self.events_tx.send(event)).is_ok()
+    }
+}
 
-impl ClientContext for KafkaSourceContext {}
+impl ClientContext for KafkaSourceContext {
+    fn stats(&self, statistics: Statistics) {
+        let high_watermarks = statistics
+            .topics
+            .into_iter()
+            .flat_map(|(topic, topic_statistics)| {
+                topic_statistics
+                    .partitions
+                    .into_iter()
+                    // librdkafka reports the internal unassigned partition as partition `-1`.
+                    .filter(|(partition, partition_statistics)| {
+                        *partition >= 0 && partition_statistics.hi_offset >= 0
+                    })
+                    .map(move |(partition, partition_statistics)| {
+                        ((topic.clone(), partition), partition_statistics.hi_offset)
+                    })
+            })
+            .collect();
+        // Statistics are periodically refreshed, so it is fine to drop them if the source lags
+        // behind.
+        let _ = self
+            .events_tx
+            .try_send(KafkaEvent::HighWatermarks(high_watermarks));
+    }
+}
 
 impl ConsumerContext for KafkaSourceContext {
     fn pre_rebalance(&self, rebalance: &Rebalance) {
-        info!("Pre rebalance {:?}", rebalance);
+        match rebalance {
+            Rebalance::Assign(tpl) => {
+                let partitions = topic_partitions(tpl);
+                info!(partitions = ?partitions, "Assigning partitions.");
+                *self
+                    .assigned_partitions
+                    .lock()
+                    .expect("The assigned partitions lock should not be poisoned.") =
+                    partitions.clone();
+                let (assignment_tx, assignment_rx) = oneshot::channel();
+                if !self.send_event(KafkaEvent::AssignPartitions {
+                    partitions,
+                    assignment_tx,
+                }) {
+                    return;
+                }
+                let assignment = match futures::executor::block_on(assignment_rx) {
+                    Ok(assignment) => assignment,
+                    Err(_) => return,
+                };
+                for (topic, partition, offset) in assignment {
+                    let set_offset_res = tpl
+                        .find_partition(&topic, partition)
+                        .map(|mut elem| elem.set_offset(offset));
+                    if let Some(Err(error)) = set_offset_res {
+                        error!(
+                            topic = ?topic,
+                            partition = ?partition,
+                            offset = ?offset,
+                            error = ?error,
+                            "Failed to set partition offset."
+                        );
+                    }
+                }
+            }
+            Rebalance::Revoke => {
+                let partitions = std::mem::take(
+                    &mut *self
+                        .assigned_partitions
+                        .lock()
+                        .expect("The assigned partitions lock should not be poisoned."),
+                );
+                info!(partitions = ?partitions, "Revoking partitions.");
+                let (revocation_tx, revocation_rx) = std_mpsc::sync_channel(1);
+                if !self.send_event(KafkaEvent::RevokePartitions {
+                    partitions,
+                    revocation_tx,
+                }) {
+                    return;
+                }
+                // Blocking the poll loop, which runs on its own thread, is intended: the
+                // partitions must not be given up before the messages consumed from them are
+                // published, otherwise their next owner would consume these messages again. The
+                // source drops the sender if it fails or exits in the meantime.
+                if revocation_rx.recv_timeout(REVOKE_WAIT_TIMEOUT)
+                    == Err(std_mpsc::RecvTimeoutError::Timeout)
+                {
+                    warn!("Timed out waiting for the source to process the revocation.");
+                }
+            }
+            Rebalance::Error(error) => error!(error = ?error, "Failed to rebalance partitions."),
+        }
     }
 
     fn post_rebalance(&self, rebalance: &Rebalance) {
-        info!("Post rebalance {:?}", rebalance);
+        debug!("Post rebalance {:?}", rebalance);
     }
 
     fn commit_callback(&self, result: KafkaResult<()>, _offsets: &TopicPartitionList) {
@@ -92,16 +316,37 @@ impl ConsumerContext for KafkaSourceContext {
     }
 }
 
-type KafkaSourceConsumer = StreamConsumer<KafkaSourceContext>;
+type KafkaSourceConsumer = BaseConsumer<KafkaSourceContext>;
+
+/// State of a partition assigned to the source.
+#[derive(Debug, Default)]
+pub struct KafkaPartitionState {
+    /// Offset of the last message received or of the last checkpointed message.
+    pub current_offset: Option<i64>,
+    /// Offset of the next message to consume, if known.
+    pub next_offset: Option<i64>,
+    /// Offset of the latest message in the partition + 1, as last reported by the consumer.
+    pub high_watermark: Option<i64>,
+    /// Whether the source has read the last message of the partition.
+    pub reached_eof: bool,
+}
+
+impl KafkaPartitionState {
+    /// Number of messages available in the partition that the source has not consumed yet.
+    fn consumer_lag(&self) -> Option<i64> {
+        match (self.next_offset, self.high_watermark) {
+            (Some(next_offset), Some(high_watermark)) => {
+                Some((high_watermark - next_offset).max(0))
+            }
+            _ => None,
+        }
+    }
+}
 
 #[derive(Default)]
 pub struct KafkaSourceState {
-    /// Partitions IDs assigned to the source.
-    pub assigned_partition_ids: HashMap<i32, PartitionId>,
-    /// Offset for each partition of the last message received.
-    pub current_positions: HashMap<i32, Position>,
-    /// Number of active partitions, i.e., that have not reached EOF.
-    pub num_active_partitions: usize,
+    /// Partitions assigned to the source, keyed by topic and partition.
+    pub assigned_partitions: HashMap<(String, i32), KafkaPartitionState>,
     /// Number of bytes processed by the source.
     pub num_bytes_processed: u64,
     /// Number of messages processed by the source (including invalid messages).
@@ -110,12 +355,34 @@ pub struct KafkaSourceState {
     pub num_invalid_messages: u64,
 }
 
-/// A `KafkaSource` consumes a topic and forwards its messages to an `Indexer`.
+impl KafkaSourceState {
+    /// Number of active partitions, i.e., that have not reached EOF.
+    fn num_active_partitions(&self) -> usize {
+        self.assigned_partitions
+            .values()
+            .filter(|partition_state| !partition_state.reached_eof)
+            .count()
+    }
+}
+
+/// A `KafkaSource` consumes one or several topics as a member of a consumer group and forwards
+/// their messages to an `Indexer`.
+///
+/// The consumer is polled from a dedicated thread. Whenever the group assigns partitions to the
+/// source, the consumption resumes from the positions recorded in the index checkpoint stored in
+/// the metastore.
+/// Conversely, partitions are only handed over to another member of the group once the messages
+/// consumed from them are published, so that no message is indexed twice.
 pub struct KafkaSource {
+    ctx: Arc<SourceExecutionContext>,
     bootstrap_servers: String,
     group_id: String,
-    topic: String,
-    consumer: KafkaSourceConsumer,
+    subscription: KafkaSubscription,
+    enable_partition_eof: bool,
+    // The receiver must be dropped before the consumer so that the rebalance callbacks invoked
+    // when the consumer is closed do not block.
+    events_rx: mpsc::Receiver<KafkaEvent>,
+    consumer: Arc<KafkaSourceConsumer>,
     state: KafkaSourceState,
 }
 
@@ -123,63 +390,289 @@ impl fmt::Debug for KafkaSource {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(
             f,
-            "KafkaSource {{ bootstrap_servers: {}, group_id: {}, topic:{} }}",
-            self.bootstrap_servers, self.group_id, self.topic
+            "KafkaSource {{ bootstrap_servers: {}, group_id: {}, subscription: {:?} }}",
+            self.bootstrap_servers, self.group_id, self.subscription
         )
     }
 }
 
 impl KafkaSource {
     /// Instantiates a new `KafkaSource`.
+    ///
+    /// The checkpoint is not used to resume the consumption: the positions of the partitions are
+    /// fetched from the metastore each time partitions are assigned to the source.
     pub async fn try_new(
+        ctx: Arc<SourceExecutionContext>,
         params: KafkaSourceParams,
-        checkpoint: Checkpoint,
+        _checkpoint: Checkpoint,
     ) -> anyhow::Result<KafkaSource> {
-        let consumer = create_consumer(
+        let subscription = params.subscription()?;
+        let enable_partition_eof = params.enable_partition_eof.unwrap_or(false);
+        let (events_tx, events_rx) = mpsc::channel(EVENTS_CHANNEL_CAPACITY);
+        let consumer = Arc::new(create_consumer(
             params.bootstrap_servers.as_str(),
             params.group_id.as_str(),
-            params.enable_partition_eof.unwrap_or(false),
-        )?;
-        let topic = params.topic.as_str();
-        let partition_ids = fetch_partition_ids(&consumer, topic).await?;
-        let assigned_partition_ids = partition_ids
-            .iter()
-            .map(|partition_id| {
-                (
-                    partition_id.clone(),
-                    PartitionId::from(partition_id.clone()),
-                )
-            })
-            .collect();
-        let watermarks = fetch_watermarks(&consumer, topic, &partition_ids).await?;
-        let kafka_checkpoint = kafka_checkpoint_from_checkpoint(&checkpoint)?;
-        let assignment = compute_assignment(topic, &partition_ids, &kafka_checkpoint, &watermarks)?;
+            enable_partition_eof,
+            events_tx.clone(),
+        )?);
+        if !matches!(subscription, KafkaSubscription::Pattern(_)) {
+            for topic in subscription.topics() {
+                fetch_partition_ids(consumer.clone(), topic).await?;
+            }
+        }
+        consumer
+            .subscribe(&subscription.topics())
+            .with_context(|| format!("Failed to subscribe to `{:?}`.", subscription.topics()))?;
 
         debug!(
             bootstrap_servers = ?params.bootstrap_servers.as_str(),
             group_id = ?params.group_id.as_str(),
-            topic = ?params.topic.as_str(),
-            assignment = ?assignment,
+            subscription = ?subscription,
             "Starting Kafka source."
         );
+        spawn_poll_loop(consumer.clone(), events_tx)?;
 
-        consumer
-            .assign(&assignment)
-            .context("Failed to resume from checkpoint.")?;
-
-        let state = KafkaSourceState {
-            assigned_partition_ids,
-            num_active_partitions: partition_ids.len(),
-            ..Default::default()
-        };
         Ok(KafkaSource {
+            ctx,
             bootstrap_servers: params.bootstrap_servers,
             group_id: params.group_id,
-            topic: params.topic,
+            subscription,
+            enable_partition_eof,
+            events_rx,
             consumer,
-            state,
+            state: KafkaSourceState::default(),
         })
     }
+
+    /// Returns whether the source has read all the messages of its assigned partitions, in which
+    /// case it terminates if `enable_partition_eof` is set.
+    fn reached_end_of_source(&self) -> bool {
+        self.enable_partition_eof
+            && !self.state.assigned_partitions.is_empty()
+            && self.state.num_active_partitions() == 0
+    }
+
+    fn process_message(
+        &mut self,
+        message: OwnedMessage,
+        docs: &mut Vec<String>,
+        checkpoint_delta: &mut CheckpointDelta,
+    ) -> anyhow::Result<()> {
+        if let Some(doc) = parse_message_payload(&message) {
+            docs.push(doc);
+        } else {
+            self.state.num_invalid_messages += 1;
+        }
+        self.state.num_bytes_processed +=
+            message.payload().map_or(0, |payload| payload.len()) as u64;
+        self.state.num_messages_processed += 1;
+
+        let topic = message.topic();
+        let partition = message.partition();
+        let offset = message.offset();
+        let partition_state = self
+            .state
+            .assigned_partitions
+            .get_mut(&(topic.to_string(), partition))
+            .ok_or_else(|| {
+                anyhow::anyhow!(
+                    "Received unexpected message from topic `{}` and partition `{}`.",
+                    topic,
+                    partition,
+                )
+            })?;
+        partition_state.reached_eof = false;
+        partition_state.next_offset = Some(offset + 1);
+        let previous_position = partition_state
+            .current_offset
+            .replace(offset)
+            .map(Position::from)
+            .unwrap_or_else(|| previous_position_for_offset(offset));
+        let partition_id = self.subscription.partition_id(topic, partition);
+        checkpoint_delta
+            .record_partition_delta(partition_id, previous_position, Position::from(offset))
+            .context("Failed to record partition delta.")?;
+        Ok(())
+    }
+
+    fn process_partition_eof(&mut self, partition: i32) {
+        // Partition EOF is only enabled for sources consuming a single topic.
+        let topic = self.subscription.topics()[0];
+        if let Some(partition_state) = self
+            .state
+            .assigned_partitions
+            .get_mut(&(topic.to_string(), partition))
+        {
+            partition_state.reached_eof = true;
+        }
+        info!(
+            topic = ?topic,
+            partition = ?partition,
+            num_active_partitions = ?self.state.num_active_partitions(),
+            "Reached end of partition."
+        );
+    }
+
+    async fn fetch_checkpoint(&self) -> anyhow::Result<Checkpoint> {
+        let checkpoint = self
+            .ctx
+            .metastore
+            .index_metadata(&self.ctx.index_id)
+            .await
+            .with_context(|| {
+                format!(
+                    "Failed to fetch checkpoint of index `{}`.",
+                    self.ctx.index_id
+                )
+            })?
+            .checkpoint;
+        Ok(checkpoint)
+    }
+
+    /// Computes the offsets from which to resume consuming the newly assigned partitions from
+    /// the latest checkpoint of the index.
+    async fn process_assign_partitions(
+        &mut self,
+        partitions: Vec<(String, i32)>,
+        assignment_tx: oneshot::Sender<Vec<(String, i32, Offset)>>,
+    ) -> anyhow::Result<()> {
+        let checkpoint = self.fetch_checkpoint().await?;
+        let mut assignment = Vec::with_capacity(partitions.len());
+        let mut partition_ids_per_topic: BTreeMap<String, Vec<i32>> = BTreeMap::new();
+
+        for (topic, partition_id) in partitions {
+            partition_ids_per_topic
+                .entry(topic)
+                .or_default()
+                .push(partition_id);
+        }
+        for (topic, mut partition_ids) in partition_ids_per_topic {
+            partition_ids.sort_unstable();
+            let kafka_checkpoint = kafka_checkpoint_from_checkpoint(
+                &checkpoint,
+                &self.subscription,
+                &topic,
+                &partition_ids,
+            )?;
+            let checkpointed_partition_ids: Vec<i32> =
+                kafka_checkpoint.keys().copied().sorted().collect();
+            let watermarks =
+                fetch_watermarks(self.consumer.clone(), &topic, &checkpointed_partition_ids)
+                    .await?;
+            let topic_assignment =
+                compute_assignment(&topic, &partition_ids, &kafka_checkpoint, &watermarks)?;
+
+            for elem in topic_assignment.elements() {
+                let partition = elem.partition();
+                let offset = elem.offset();
+                let partition_state = KafkaPartitionState {
+                    current_offset: kafka_checkpoint.get(&partition).copied(),
+                    next_offset: match offset {
+                        Offset::Offset(next_offset) => Some(next_offset),
+                        _ => None,
+                    },
+                    ..Default::default()
+                };
+                self.state
+                    .assigned_partitions
+                    .insert((topic.clone(), partition), partition_state);
+                assignment.push((topic.clone(), partition, offset));
+            }
+        }
+        debug!(assignment = ?assignment, "Resuming from checkpoint.");
+        // The poll loop may have been interrupted in the meantime.
+        let _ = assignment_tx.send(assignment);
+        Ok(())
+    }
+
+    /// Gives up the revoked partitions once the messages consumed from them are published.
+    ///
+    /// The pending batch is flushed and the indexer is asked to commit its current split, so that
+    /// the source does not have to wait for the commit timeout. If the messages are not published
+    /// within `REVOKE_PUBLISH_TIMEOUT`, the source fails and the splits that are still in flight
+    /// are dropped with the indexing pipeline.
+    async fn process_revoke_partitions(
+        &mut self,
+        partitions: Vec<(String, i32)>,
+        revocation_tx: std_mpsc::SyncSender<()>,
+        batch: &mut RawDocBatch,
+        batch_sink: &Mailbox<IndexerMessage>,
+        ctx: &SourceContext,
+    ) -> Result<(), ActorExitStatus> {
+        if !batch.checkpoint_delta.is_empty() {
+            let pending_batch = std::mem::take(batch);
+            ctx.send_message(batch_sink, IndexerMessage::from(pending_batch))
+                .await?;
+        }
+        let mut unpublished_offsets: Vec<(String, i32, i64)> = partitions
+            .iter()
+            .filter_map(|(topic, partition)| {
+                let partition_state = self
+                    .state
+                    .assigned_partitions
+                    .get(&(topic.clone(), *partition))?;
+                let current_offset = partition_state.current_offset?;
+                Some((topic.clone(), *partition, current_offset))
+            })
+            .collect();
+        let deadline = tokio::time::Instant::now() + REVOKE_PUBLISH_TIMEOUT;
+        let mut requested_commit = false;
+        while !unpublished_offsets.is_empty() {
+            let checkpoint = self.fetch_checkpoint().await?;
+            let mut still_unpublished_offsets = Vec::with_capacity(unpublished_offsets.len());
+            for (topic, partition, current_offset) in unpublished_offsets {
+                let kafka_checkpoint = kafka_checkpoint_from_checkpoint(
+                    &checkpoint,
+                    &self.subscription,
+                    &topic,
+                    &[partition],
+                )?;
+                let is_published = kafka_checkpoint
+                    .get(&partition)
+                    .map(|&checkpoint_offset| checkpoint_offset >= current_offset)
+                    .unwrap_or(false);
+                if !is_published {
+                    still_unpublished_offsets.push((topic, partition, current_offset));
+                }
+            }
+            unpublished_offsets = still_unpublished_offsets;
+            if unpublished_offsets.is_empty() {
+                break;
+            }
+            if tokio::time::Instant::now() >= deadline {
+                return Err(anyhow::anyhow!(
+                    "Timed out waiting for the messages of the revoked partitions `{:?}` to be \
+                     published.",
+                    unpublished_offsets
+                )
+                .into());
+            }
+            if !requested_commit {
+                info!(
+                    unpublished_offsets = ?unpublished_offsets,
+                    "Waiting for the messages of the revoked partitions to be published."
+                );
+                ctx.send_message(batch_sink, IndexerMessage::Commit).await?;
+                requested_commit = true;
+            }
+            ctx.record_progress();
+            tokio::time::sleep(CHECKPOINT_POLL_INTERVAL).await;
+        }
+        for partition in &partitions {
+            self.state.assigned_partitions.remove(partition);
+        }
+        // The poll loop may have been interrupted in the meantime.
+        let _ = revocation_tx.send(());
+        Ok(())
+    }
+
+    fn process_high_watermarks(&mut self, high_watermarks: HashMap<(String, i32), i64>) {
+        for (partition, high_watermark) in high_watermarks {
+            if let Some(partition_state) = self.state.assigned_partitions.get_mut(&partition) {
+                partition_state.high_watermark = Some(high_watermark);
+            }
+        }
+    }
 }
 
 #[async_trait]
@@ -189,99 +682,96 @@ impl Source for KafkaSource {
         batch_sink: &Mailbox<IndexerMessage>,
         ctx: &SourceContext,
     ) -> Result<(), ActorExitStatus> {
-        let mut docs = Vec::new();
-        let mut checkpoint_delta = CheckpointDelta::default();
-
-        let deadline = tokio::time::sleep(quickwit_actors::HEARTBEAT * 4 / 5);
-        let mut message_stream = Box::pin(self.consumer.stream().take_until(deadline));
-
-        while let Some(message_res) = message_stream.next().await {
-            let message = match message_res {
-                Ok(message) => message,
-                Err(KafkaError::PartitionEOF(partition_id)) => {
-                    self.state.num_active_partitions -= 1;
-                    info!(
-                        topic = ?self.topic.as_str(),
-                        partition_id = ?partition_id,
-                        num_active_partitions = ?self.state.num_active_partitions,
-                        "Reached end of partition."
-                    );
-                    continue;
+        let mut batch = RawDocBatch::default();
+
+        let deadline = tokio::time::Instant::now() + quickwit_actors::HEARTBEAT * 4 / 5;
+        while let Ok(event_opt) = tokio::time::timeout_at(deadline, self.events_rx.recv()).await {
+            let event = event_opt
+                .ok_or_else(|| anyhow::anyhow!("Kafka consumer poll loop exited unexpectedly."))?;
+            match event {
+                KafkaEvent::Message(message) => {
+                    self.process_message(message, &mut batch.docs, &mut batch.checkpoint_delta)?
                 }
-                // FIXME: This is assuming that Kafka errors are not recoverable, it may not be the
-                // case.
-                Err(err) => return Err(ActorExitStatus::from(anyhow::anyhow!(err))),
-            };
-            if let Some(doc) = parse_message_payload(&message) {
-                docs.push(doc);
-            } else {
-                self.state.num_invalid_messages += 1;
-            }
-            self.state.num_bytes_processed += message.payload_len() as u64;
-            self.state.num_messages_processed += 1;
-
-            let partition_id = self
-                .state
-                .assigned_partition_ids
-                .get(&message.partition())
-                .ok_or_else(|| {
-                    anyhow::anyhow!(
-                        "Received unexpected message from partition `{}`. Assigned partitions: \
-                         `{{{}}}`.",
-                        message.partition(),
-                        self.state.assigned_partition_ids.keys().join(", "),
+                KafkaEvent::PartitionEOF(partition) => self.process_partition_eof(partition),
+                KafkaEvent::AssignPartitions {
+                    partitions,
+                    assignment_tx,
+                } => {
+                    self.process_assign_partitions(partitions, assignment_tx)
+                        .await?
+                }
+                KafkaEvent::RevokePartitions {
+                    partitions,
+                    revocation_tx,
+                } => {
+                    self.process_revoke_partitions(
+                        partitions,
+                        revocation_tx,
+                        &mut batch,
+                        batch_sink,
+                        ctx,
                     )
-                })?
-                .clone();
-            let current_position = Position::from(message.offset());
-            let previous_position = self
-                .state
-                .current_positions
-                .insert(message.partition(), current_position.clone())
-                .unwrap_or_else(|| previous_position_for_offset(message.offset()));
-            checkpoint_delta
-                .record_partition_delta(partition_id, previous_position, current_position)
-                .context("Failed to record partition delta.")?;
-        }
-        if !checkpoint_delta.is_empty() {
-            let batch = RawDocBatch {
-                docs,
-                checkpoint_delta,
-            };
+                    .await?
+                }
+                KafkaEvent::HighWatermarks(high_watermarks) => {
+                    self.process_high_watermarks(high_watermarks)
+                }
+                KafkaEvent::Error(error) => return Err(ActorExitStatus::from(error)),
+            }
+            if self.reached_end_of_source() {
+                break;
+            }
+        }
+        if !batch.checkpoint_delta.is_empty() {
             ctx.send_message(batch_sink, IndexerMessage::from(batch))
                 .await?;
         }
-        if self.state.num_active_partitions == 0 {
-            info!(topic = &self.topic.as_str(), "Reached end of topic.");
-            ctx.send_message(batch_sink, IndexerMessage::EndOfSource)
-                .await?;
+        if self.reached_end_of_source() {
+            info!(subscription = ?self.subscription, "Reached end of topic.");
+            ctx.send_exit_with_success(batch_sink).await?;
             return Err(ActorExitStatus::Success);
         }
         Ok(())
     }
 
     fn observable_state(&self) -> serde_json::Value {
-        let assigned_partition_ids: Vec<&i32> =
-            self.state.assigned_partition_ids.keys().sorted().collect();
-        let current_positions: Vec<(&i32, i64)> = self
+        let mut assigned_partitions: BTreeMap<&str, Vec<i32>> = BTreeMap::new();
+        let mut current_positions: BTreeMap<&str, Vec<(i32, i64)>> = BTreeMap::new();
+        let mut consumer_lag: BTreeMap<&str, Vec<(i32, i64)>> = BTreeMap::new();
+        let mut total_consumer_lag = 0;
+
+        for ((topic, partition), partition_state) in self
             .state
-            .current_positions
+            .assigned_partitions
             .iter()
-            .filter_map(|(partition_id, position)| match position {
-                Position::Offset(offset_str) => offset_str
-                    .parse::<i64>()
-                    .ok()
-                    .map(|offset| (partition_id, offset)),
-                Position::Beginning => None,
-            })
-            .sorted()
-            .collect();
+            .sorted_by(|(left, _), (right, _)| left.cmp(right))
+        {
+            assigned_partitions
+                .entry(topic.as_str())
+                .or_default()
+                .push(*partition);
+            if let Some(current_offset) = partition_state.current_offset {
+                current_positions
+                    .entry(topic.as_str())
+                    .or_default()
+                    .push((*partition, current_offset));
+            }
+            if let Some(partition_consumer_lag) = partition_state.consumer_lag() {
+                consumer_lag
+                    .entry(topic.as_str())
+                    .or_default()
+                    .push((*partition, partition_consumer_lag));
+                total_consumer_lag += partition_consumer_lag;
+            }
+        }
         json!({
             "group_id": self.group_id,
-            "topic":  self.topic,
-            "assigned_partition_ids": assigned_partition_ids,
+            "subscription": self.subscription.topics(),
+            "assigned_partitions": assigned_partitions,
             "current_positions": current_positions,
-            "num_active_partitions": self.state.num_active_partitions,
+            "consumer_lag": consumer_lag,
+            "total_consumer_lag": total_consumer_lag,
+            "num_active_partitions": self.state.num_active_partitions(),
             "num_bytes_processed": self.state.num_bytes_processed,
             "num_messages_processed": self.state.num_messages_processed,
             "num_invalid_messages": self.state.num_invalid_messages,
@@ -298,11 +788,19 @@ fn previous_position_for_offset(offset: i64) -> Position {
     }
 }
 
+fn topic_partitions(tpl: &TopicPartitionList) -> Vec<(String, i32)> {
+    tpl.elements()
+        .iter()
+        .map(|elem| (elem.topic().to_string(), elem.partition()))
+        .collect()
+}
+
 /// Creates a new `KafkaSourceConsumer`.
 fn create_consumer(
     bootstrap_servers: &str,
     group_id: &str,
     enable_partition_eof: bool,
+    events_tx: mpsc::Sender<KafkaEvent>,
 ) -> anyhow::Result<KafkaSourceConsumer> {
     let consumer: KafkaSourceConsumer = ClientConfig::new()
         .set("bootstrap.servers", bootstrap_servers)
@@ -310,10 +808,13 @@ fn create_consumer(
         .set("enable.partition.eof", enable_partition_eof.to_string())
         .set("session.timeout.ms", "6000")
         .set("enable.auto.commit", "false")
-        //.set("statistics.interval.ms", "30000")
-        //.set("auto.offset.reset", "smallest")
+        .set("statistics.interval.ms", "10000")
+        .set("auto.offset.reset", "earliest")
         .set_log_level(RDKafkaLogLevel::Info) // TODO: read log level from env variable.
-        .create_with_context(KafkaSourceContext)
+        .create_with_context(KafkaSourceContext {
+            events_tx,
+            assigned_partitions: Mutex::new(Vec::new()),
+        })
         .with_context(|| {
             format!(
                 "Failed to create consumer with bootstrap servers `{}` and group ID `{}`.",
@@ -323,17 +824,99 @@ fn create_consumer(
     Ok(consumer)
 }
 
-/// Represents a checkpoint with the Kafka native types: `i32` for partition IDs and `i64` for
-/// offsets.
-fn kafka_checkpoint_from_checkpoint(checkpoint: &Checkpoint) -> anyhow::Result<HashMap<i32, i64>> {
-    let mut kafka_checkpoint = HashMap::with_capacity(checkpoint.num_partitions());
-    for (partition_id, position) in checkpoint.iter() {
-        let partition_i32 = partition_id.0.parse::<i32>().with_context(|| {
-            format!("Failed to parse partition ID `{}` to i32.", partition_id.0)
-        })?;
-        let offset_i64 = match position {
-            Position::Beginning => continue,
-            Position::Offset(offset_str) => offset_str
+/// Spawns the thread polling the consumer. Rebalance callbacks are invoked from this thread, so
+/// they can block while the source fetches the checkpoint from the metastore.
+///
+/// The loop exits when the source is dropped or after a non-recoverable error.
+fn spawn_poll_loop(
+    consumer: Arc<KafkaSourceConsumer>,
+    events_tx: mpsc::Sender<KafkaEvent>,
+) -> anyhow::Result<()> {
+    std::thread::Builder::new()
+        .name("kafka-source-poll-loop".to_string())
+        .spawn(move || {
+            let mut num_attempts = 0;
+            while !events_tx.is_closed() {
+                let event = match consumer.poll(POLL_TIMEOUT) {
+                    Some(Ok(message)) => KafkaEvent::Message(message.detach()),
+                    Some(Err(KafkaError::PartitionEOF(partition))) => {
+                        KafkaEvent::PartitionEOF(partition)
+                    }
+                    Some(Err(error)) if is_recoverable(&error) => {
+                        num_attempts += 1;
+                        if num_attempts >= MAX_RETRY_ATTEMPTS {
+                            let _ = events_tx.blocking_send(KafkaEvent::Error(
+                                anyhow::anyhow!(error).context(format!(
+                                    "Failed to recover from Kafka error after {} attempts.",
+                                    num_attempts
+                                )),
+                            ));
+                            return;
+                        }
+                        let delay = retry_delay(num_attempts);
+                        warn!(
+                            num_attempts = %num_attempts,
+                            delay_ms = %delay.as_millis(),
+                            error = ?error,
+                            "Failed to poll Kafka consumer, retrying."
+                        );
+                        std::thread::sleep(delay);
+                        continue;
+                    }
+                    Some(Err(error)) => {
+                        let _ = events_tx.blocking_send(KafkaEvent::Error(anyhow::anyhow!(error)));
+                        return;
+                    }
+                    None => continue,
+                };
+                num_attempts = 0;
+                if events_tx.blocking_send(event).is_err() {
+                    return;
+                }
+            }
+        })
+        .context("Failed to spawn Kafka consumer poll loop.")?;
+    Ok(())
+}
+
+/// Returns whether the consumer is expected to recover from the error on its own, for instance,
+/// once the broker it is connected to comes back online.
+fn is_recoverable(error: &KafkaError) -> bool {
+    match error {
+        KafkaError::MessageConsumption(error_code) => !matches!(
+            error_code,
+            RDKafkaErrorCode::Fatal
+                | RDKafkaErrorCode::Authentication
+                | RDKafkaErrorCode::SaslAuthenticationFailed
+                | RDKafkaErrorCode::TopicAuthorizationFailed
+                | RDKafkaErrorCode::GroupAuthorizationFailed
+                | RDKafkaErrorCode::ClusterAuthorizationFailed
+        ),
+        _ => false,
+    }
+}
+
+/// Returns the delay before the next attempt, which grows exponentially with the number of
+/// attempts up to `MAX_RETRY_DELAY`.
+fn retry_delay(num_attempts: u32) -> Duration {
+    let delay_ms = BASE_RETRY_DELAY.as_millis() as u64 * 2u64.saturating_pow(num_attempts - 1);
+    Duration::from_millis(delay_ms).min(MAX_RETRY_DELAY)
+}
+
+/// Represents the checkpoint of the given topic partitions with the Kafka native types: `i32` for
+/// partition IDs and `i64` for offsets.
+fn kafka_checkpoint_from_checkpoint(
+    checkpoint: &Checkpoint,
+    subscription: &KafkaSubscription,
+    topic: &str,
+    partition_ids: &[i32],
+) -> anyhow::Result<HashMap<i32, i64>> {
+    let mut kafka_checkpoint = HashMap::with_capacity(partition_ids.len());
+    for &partition_i32 in partition_ids {
+        let partition_id = subscription.partition_id(topic, partition_i32);
+        let offset_i64 = match checkpoint.position_for_partition(&partition_id) {
+            None | Some(Position::Beginning) => continue,
+            Some(Position::Offset(offset_str)) => offset_str
                 .parse::<i64>()
                 .with_context(|| format!("Failed to parse offset `{}` to i64.", offset_str))?,
         };
@@ -342,18 +925,20 @@ fn kafka_checkpoint_from_checkpoint(checkpoint: &Checkpoint) -> anyhow::Result<H
     Ok(kafka_checkpoint)
 }
 
-/// Retrieves the list of all partition IDs of a given topic.
+/// Retrieves the list of all partition IDs of a given topic. The metadata request blocks until
+/// the broker answers, so it is sent from the blocking thread pool.
 async fn fetch_partition_ids(
-    consumer: &KafkaSourceConsumer,
+    consumer: Arc<KafkaSourceConsumer>,
     topic: &str,
 ) -> anyhow::Result<Vec<i32>> {
-    let cluster_metadata = async move {
+    let topic_clone = topic.to_string();
+    let cluster_metadata = tokio::task::spawn_blocking(move || {
         let timeout = Timeout::After(Duration::from_secs(5));
         consumer
-            .fetch_metadata(Some(topic), timeout)
-            .with_context(|| format!("Failed to fetch metadata for topic `{}`.", topic))
-    }
-    .await?;
+            .fetch_metadata(Some(&topic_clone), timeout)
+            .with_context(|| format!("Failed to fetch metadata for topic `{}`.", topic_clone))
+    })
+    .await??;
 
     if cluster_metadata.topics().is_empty() {
         bail!("Topic `{}` does not exist.", topic);
@@ -380,22 +965,31 @@ async fn fetch_partition_ids(
 ///
 /// The high watermark is the offset of the latest message in the partition available for
 /// consumption + 1.
+///
+/// The requests block until the broker answers, so they are sent from the blocking thread pool.
 async fn fetch_watermarks(
-    consumer: &KafkaSourceConsumer,
+    consumer: Arc<KafkaSourceConsumer>,
     topic: &str,
     partition_ids: &[i32],
 ) -> anyhow::Result<HashMap<i32, (i64, i64)>> {
     let timeout = Duration::from_secs(10);
-    let tasks = partition_ids.iter().map(|&partition_id| async move {
-        consumer
-            .fetch_watermarks(topic, partition_id, timeout)
-            .map(|watermarks| (partition_id, watermarks))
-            .with_context(|| {
-                format!(
-                    "Failed to fetch watermarks for topic `{}` and partition `{}`.",
-                    topic, partition_id
-                )
+    let tasks = partition_ids.iter().map(|&partition_id| {
+        let consumer = consumer.clone();
+        let topic = topic.to_string();
+        async move {
+            tokio::task::spawn_blocking(move || {
+                consumer
+                    .fetch_watermarks(&topic, partition_id, timeout)
+                    .map(|watermarks| (partition_id, watermarks))
+                    .with_context(|| {
+                        format!(
+                            "Failed to fetch watermarks for topic `{}` and partition `{}`.",
+                            topic, partition_id
+                        )
+                    })
             })
+            .await?
+        }
     });
     let watermarks = futures::future::try_join_all(tasks)
         .await?
@@ -457,7 +1051,7 @@ fn compute_next_offset(
 
 /// Converts the raw bytes of the message payload to a `String` skipping corrupted or empty
 /// messages.
-fn parse_message_payload(message: &BorrowedMessage) -> Option<String> {
+fn parse_message_payload(message: &OwnedMessage) -> Option<String> {
     match message.payload_view::<str>() {
         Some(Ok(payload)) if payload.len() > 0 => {
             let doc = payload.to_string();
@@ -466,7 +1060,7 @@ fn parse_message_payload(message: &BorrowedMessage) -> Option<String> {
                 partition_id = ?message.partition(),
                 offset = ?message.offset(),
                 timestamp = ?message.timestamp(),
-                num_bytes = ?message.payload_len(),
+                num_bytes = ?payload.len(),
                 doc = ?doc.as_str(),
                 "Message received.",
             );
@@ -502,6 +1096,160 @@ fn parse_message_payload(message: &BorrowedMessage) -> Option<String> {
 mod tests {
     use super::*;
 
+    fn params_for_test(
+        topic: Option<&str>,
+        topics: &[&str],
+        topic_pattern: Option<&str>,
+        enable_partition_eof: bool,
+    ) -> KafkaSourceParams {
+        KafkaSourceParams {
+            bootstrap_servers: "localhost:9092".to_string(),
+            group_id: "test-group".to_string(),
+            topic: topic.map(ToString::to_string),
+            topics: topics.iter().map(ToString::to_string).collect(),
+            topic_pattern: topic_pattern.map(ToString::to_string),
+            enable_partition_eof: Some(enable_partition_eof),
+        }
+    }
+
+    #[test]
+    fn test_kafka_source_params_subscription() {
+        assert_eq!(
+            params_for_test(Some("topic"), &[], None, true)
+                .subscription()
+                .unwrap(),
+            KafkaSubscription::Topic("topic".to_string())
+        );
+        assert_eq!(
+            params_for_test(None, &["topic-1", "topic-2"], None, false)
+                .subscription()
+                .unwrap(),
+            KafkaSubscription::Topics(vec!["topic-1".to_string(), "topic-2".to_string()])
+        );
+        assert_eq!(
+            params_for_test(None, &[], Some("topic-.*"), false)
+                .subscription()
+                .unwrap(),
+            KafkaSubscription::Pattern("^topic-.*".to_string())
+        );
+        assert_eq!(
+            params_for_test(None, &[], Some("^topic-.*"), false)
+                .subscription()
+                .unwrap(),
+            KafkaSubscription::Pattern("^topic-.*".to_string())
+        );
+        assert!(params_for_test(None, &[], None, false)
+            .subscription()
+            .is_err());
+        assert!(params_for_test(Some("topic"), &["topic"], None, false)
+            .subscription()
+            .is_err());
+        assert!(params_for_test(None, &["topic-1", "topic-2"], None, true)
+            .subscription()
+            .is_err());
+        assert!(params_for_test(None, &[], Some("topic-.*"), true)
+            .subscription()
+            .is_err());
+    }
+
+    #[test]
+    fn test_kafka_source_params_deserialization() -> anyhow::Result<()> {
+        let params: KafkaSourceParams = serde_json::from_value(json!({
+            "bootstrap_servers": "localhost:9092",
+            "group_id": "test-group",
+            "topic": "topic",
+        }))?;
+        assert_eq!(
+            params.subscription()?,
+            KafkaSubscription::Topic("topic".to_string())
+        );
+        let params: KafkaSourceParams = serde_json::from_value(json!({
+            "bootstrap_servers": "localhost:9092",
+            "group_id": "test-group",
+            "topics": ["topic-1", "topic-2"],
+        }))?;
+        assert_eq!(
+            params.subscription()?,
+            KafkaSubscription::Topics(vec!["topic-1".to_string(), "topic-2".to_string()])
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_kafka_subscription_partition_id() {
+        assert_eq!(
+            KafkaSubscription::Topic("topic".to_string()).partition_id("topic", 1),
+            PartitionId::from(1)
+        );
+        assert_eq!(
+            KafkaSubscription::Topics(vec!["topic".to_string()]).partition_id("topic", 1),
+            PartitionId::from("topic:0000000001")
+        );
+        assert_eq!(
+            KafkaSubscription::Pattern("^topic-.*".to_string()).partition_id("topic-1", 12),
+            PartitionId::from("topic-1:0000000012")
+        );
+    }
+
+    #[test]
+    fn test_kafka_checkpoint_from_checkpoint() -> anyhow::Result<()> {
+        let subscription = KafkaSubscription::Topics(vec!["topic-1".to_string()]);
+        let checkpoint: Checkpoint = vec![
+            (
+                subscription.partition_id("topic-1", 0),
+                Position::from(42u64),
+            ),
+            (subscription.partition_id("topic-1", 1), Position::Beginning),
+            (
+                subscription.partition_id("topic-2", 0),
+                Position::from(1337u64),
+            ),
+        ]
+        .into_iter()
+        .collect();
+        let kafka_checkpoint =
+            kafka_checkpoint_from_checkpoint(&checkpoint, &subscription, "topic-1", &[0, 1, 2])?;
+        let expected_kafka_checkpoint = vec![(0, 42)].into_iter().collect();
+        assert_eq!(kafka_checkpoint, expected_kafka_checkpoint);
+        Ok(())
+    }
+
+    #[test]
+    fn test_kafka_partition_state_consumer_lag() {
+        let mut partition_state = KafkaPartitionState::default();
+        assert_eq!(partition_state.consumer_lag(), None);
+        partition_state.next_offset = Some(10);
+        assert_eq!(partition_state.consumer_lag(), None);
+        partition_state.high_watermark = Some(15);
+        assert_eq!(partition_state.consumer_lag(), Some(5));
+        partition_state.next_offset = Some(16);
+        assert_eq!(partition_state.consumer_lag(), Some(0));
+    }
+
+    #[test]
+    fn test_retry_delay() {
+        assert_eq!(retry_delay(1), Duration::from_millis(250));
+        assert_eq!(retry_delay(2), Duration::from_millis(500));
+        assert_eq!(retry_delay(5), Duration::from_secs(4));
+        assert_eq!(retry_delay(MAX_RETRY_ATTEMPTS), MAX_RETRY_DELAY);
+    }
+
+    #[test]
+    fn test_is_recoverable() {
+        assert!(is_recoverable(&KafkaError::MessageConsumption(
+            RDKafkaErrorCode::BrokerTransportFailure
+        )));
+        assert!(is_recoverable(&KafkaError::MessageConsumption(
+            RDKafkaErrorCode::AllBrokersDown
+        )));
+        assert!(!is_recoverable(&KafkaError::MessageConsumption(
+            RDKafkaErrorCode::Fatal
+        )));
+        assert!(!is_recoverable(&KafkaError::MessageConsumption(
+            RDKafkaErrorCode::TopicAuthorizationFailed
+        )));
+    }
+
     #[test]
     fn test_compute_assignment() -> anyhow::Result<()> {
         let partition_ids = &[0, 1, 2];
@@ -587,7 +1335,10 @@ mod tests {
 
 #[cfg(all(test, feature = "kafka-broker-external-service"))]
 mod kafka_broker_tests {
-    use quickwit_actors::{create_test_mailbox, Universe};
+    use std::sync::Mutex;
+
+    use quickwit_actors::{create_test_mailbox, Command, CommandOrMessage, Universe};
+    use quickwit_metastore::{IndexMetadata, Metastore, SingleFileMetastore};
     use rand::distributions::Alphanumeric;
     use rand::Rng;
     use rdkafka::admin::{AdminClient, AdminOptions, NewTopic, TopicReplication};
@@ -609,6 +1360,26 @@ mod kafka_broker_tests {
         format!("{}-{}", string, slug)
     }
 
+    async fn source_ctx_for_test(
+        index_id: &str,
+        checkpoint: Checkpoint,
+    ) -> anyhow::Result<Arc<SourceExecutionContext>> {
+        let metastore = SingleFileMetastore::for_test();
+        metastore
+            .create_index(IndexMetadata {
+                index_id: index_id.to_string(),
+                index_uri: format!("ram://indexes/{}", index_id),
+                index_config: Arc::new(quickwit_index_config::default_config_for_tests()),
+                checkpoint,
+                encryption_key_id: None,
+            })
+            .await?;
+        Ok(SourceExecutionContext::for_test(
+            Arc::new(metastore),
+            index_id,
+        ))
+    }
+
     fn create_admin_client(
         bootstrap_servers: &str,
     ) -> anyhow::Result<AdminClient<DefaultClientContext>> {
@@ -702,10 +1473,12 @@ mod kafka_broker_tests {
         format!("Message #{}", id)
     }
 
-    fn merge_messages(messages: Vec<IndexerMessage>) -> anyhow::Result<RawDocBatch> {
+    fn merge_messages(
+        messages: Vec<CommandOrMessage<IndexerMessage>>,
+    ) -> anyhow::Result<RawDocBatch> {
         let mut merged_batch = RawDocBatch::default();
         for message in messages {
-            if let IndexerMessage::Batch(batch) = message {
+            if let CommandOrMessage::Message(IndexerMessage::Batch(batch)) = message {
                 merged_batch.docs.extend(batch.docs);
                 merged_batch
                     .checkpoint_delta
@@ -716,6 +1489,15 @@ mod kafka_broker_tests {
         Ok(merged_batch)
     }
 
+    /// Removes the per-partition consumer lag from the observable state of the source since it
+    /// depends on when the consumer statistics were last refreshed. The source exits once all the
+    /// partitions are consumed, so the total consumer lag is always 0.
+    fn remove_consumer_lag(state: &mut serde_json::Value) {
+        let state_object = state.as_object_mut().unwrap();
+        state_object.remove("consumer_lag");
+        assert_eq!(state_object.remove("total_consumer_lag"), Some(json!(0)));
+    }
+
     #[tokio::test]
     async fn test_kafka_source() -> anyhow::Result<()> {
         quickwit_common::setup_logging_for_tests();
@@ -743,27 +1525,31 @@ mod kafka_broker_tests {
         {
             let (sink, inbox) = create_test_mailbox();
             let checkpoint = Checkpoint::default();
+            let ctx = source_ctx_for_test("test-kafka-source-index", checkpoint.clone()).await?;
             let source = source_loader
-                .load_source(source_config.clone(), checkpoint)
+                .load_source(ctx, source_config.clone(), checkpoint)
                 .await?;
             let actor = SourceActor {
                 source,
                 batch_sink: sink.clone(),
             };
             let (_mailbox, handle) = universe.spawn_actor(actor).spawn_async();
-            let (exit_status, exit_state) = handle.join().await;
+            let (exit_status, mut exit_state) = handle.join().await;
             assert!(exit_status.is_success());
+            remove_consumer_lag(&mut exit_state);
 
-            let messages = inbox.drain_available_message_for_test();
+            let messages = inbox.drain_available_message_or_command_for_test();
             assert_eq!(messages.len(), 1);
-            assert!(matches!(messages[0], IndexerMessage::EndOfSource));
+            assert!(matches!(
+                messages[0],
+                CommandOrMessage::Command(Command::ExitWithSuccess)
+            ));
 
-            let expected_current_positions: Vec<(i32, i64)> = vec![];
             let expected_state = json!({
                 "group_id": group_id,
-                "topic":  topic,
-                "assigned_partition_ids": vec![0, 1, 2],
-                "current_positions":  expected_current_positions,
+                "subscription": vec![&topic],
+                "assigned_partitions": json!({ &topic: vec![0, 1, 2] }),
+                "current_positions": json!({}),
                 "num_active_partitions": 0,
                 "num_bytes_processed": 0,
                 "num_messages_processed": 0,
@@ -792,20 +1578,25 @@ mod kafka_broker_tests {
         {
             let (sink, inbox) = create_test_mailbox();
             let checkpoint = Checkpoint::default();
+            let ctx = source_ctx_for_test("test-kafka-source-index", checkpoint.clone()).await?;
             let source = source_loader
-                .load_source(source_config.clone(), checkpoint)
+                .load_source(ctx, source_config.clone(), checkpoint)
                 .await?;
             let actor = SourceActor {
                 source,
                 batch_sink: sink.clone(),
             };
             let (_mailbox, handle) = universe.spawn_actor(actor).spawn_async();
-            let (exit_status, state) = handle.join().await;
+            let (exit_status, mut state) = handle.join().await;
             assert!(exit_status.is_success());
+            remove_consumer_lag(&mut state);
 
-            let messages = inbox.drain_available_message_for_test();
+            let messages = inbox.drain_available_message_or_command_for_test();
             assert!(messages.len() >= 2);
-            assert!(matches!(messages.last(), Some(IndexerMessage::EndOfSource)));
+            assert!(matches!(
+                messages.last(),
+                Some(CommandOrMessage::Command(Command::ExitWithSuccess))
+            ));
 
             let batch = merge_messages(messages)?;
             let expected_docs = vec![
@@ -830,9 +1621,9 @@ mod kafka_broker_tests {
 
             let expected_state = json!({
                 "group_id": group_id,
-                "topic":  topic,
-                "assigned_partition_ids": vec![0, 1, 2],
-                "current_positions":  vec![(0, 2), (1, 2), (2, 2)],
+                "subscription": vec![&topic],
+                "assigned_partitions": json!({ &topic: vec![0, 1, 2] }),
+                "current_positions": json!({ &topic: vec![(0, 2), (1, 2), (2, 2)] }),
                 "num_active_partitions": 0,
                 "num_bytes_processed": 72,
                 "num_messages_processed": 9,
@@ -848,20 +1639,25 @@ mod kafka_broker_tests {
                     (PartitionId::from(partition_id), Position::from(offset))
                 })
                 .collect();
+            let ctx = source_ctx_for_test("test-kafka-source-index", checkpoint.clone()).await?;
             let source = source_loader
-                .load_source(source_config.clone(), checkpoint)
+                .load_source(ctx, source_config.clone(), checkpoint)
                 .await?;
             let actor = SourceActor {
                 source,
                 batch_sink: sink.clone(),
             };
             let (_mailbox, handle) = universe.spawn_actor(actor).spawn_async();
-            let (exit_status, exit_state) = handle.join().await;
+            let (exit_status, mut exit_state) = handle.join().await;
             assert!(exit_status.is_success());
+            remove_consumer_lag(&mut exit_state);
 
-            let messages = inbox.drain_available_message_for_test();
+            let messages = inbox.drain_available_message_or_command_for_test();
             assert!(messages.len() >= 2);
-            assert!(matches!(messages.last(), Some(IndexerMessage::EndOfSource)));
+            assert!(matches!(
+                messages.last(),
+                Some(CommandOrMessage::Command(Command::ExitWithSuccess))
+            ));
 
             let batch = merge_messages(messages)?;
             let expected_docs = vec!["Message #002", "Message #200", "Message #202"];
@@ -882,9 +1678,10 @@ mod kafka_broker_tests {
 
             let expected_exit_state = json!({
                 "group_id": group_id,
-                "topic":  topic,
-                "assigned_partition_ids": vec![0, 1, 2],
-                "current_positions":  vec![(0, 2), (2, 2)],
+                "subscription": vec![&topic],
+                "assigned_partitions": json!({ &topic: vec![0, 1, 2] }),
+                // The position of partition 1 comes from the checkpoint.
+                "current_positions": json!({ &topic: vec![(0, 2), (1, 2), (2, 2)] }),
                 "num_active_partitions": 0,
                 "num_bytes_processed": 36,
                 "num_messages_processed": 5,
@@ -895,6 +1692,185 @@ mod kafka_broker_tests {
         Ok(())
     }
 
+    /// Publishes the checkpoint deltas of the batches emitted by a source after a delay, as the
+    /// indexing pipeline would, and collects the documents of the published batches. A publish
+    /// fails if a message is published twice.
+    fn spawn_test_publisher<D>(
+        drain_messages: D,
+        metastore: Arc<dyn Metastore>,
+        index_id: &str,
+        publish_delay: Duration,
+        published_docs: Arc<Mutex<Vec<String>>>,
+        publish_errors: Arc<Mutex<Vec<String>>>,
+    ) -> tokio::task::JoinHandle<()>
+    where
+        D: Fn() -> Vec<IndexerMessage> + Send + 'static,
+    {
+        let index_id = index_id.to_string();
+        tokio::spawn(async move {
+            loop {
+                for message in drain_messages() {
+                    let batch = match message {
+                        IndexerMessage::Batch(batch) => batch,
+                        _ => continue,
+                    };
+                    tokio::time::sleep(publish_delay).await;
+                    match metastore
+                        .publish_splits(&index_id, &[], batch.checkpoint_delta)
+                        .await
+                    {
+                        Ok(()) => published_docs.lock().unwrap().extend(batch.docs),
+                        Err(error) => publish_errors.lock().unwrap().push(error.to_string()),
+                    }
+                }
+                tokio::time::sleep(Duration::from_millis(100)).await;
+            }
+        })
+    }
+
+    /// Waits until the checkpoint of the index reaches the given offsets.
+    async fn wait_for_checkpoint(
+        metastore: &dyn Metastore,
+        index_id: &str,
+        expected_offsets: &[(i32, i64)],
+    ) -> anyhow::Result<()> {
+        let deadline = tokio::time::Instant::now() + Duration::from_secs(60);
+        loop {
+            let checkpoint = metastore.index_metadata(index_id).await?.checkpoint;
+            let reached_offsets = expected_offsets.iter().all(|(partition, offset)| {
+                checkpoint.position_for_partition(&PartitionId::from(*partition))
+                    == Some(&Position::from(*offset))
+            });
+            if reached_offsets {
+                return Ok(());
+            }
+            if tokio::time::Instant::now() >= deadline {
+                bail!(
+                    "Checkpoint `{:?}` did not reach offsets `{:?}`.",
+                    checkpoint,
+                    expected_offsets
+                );
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+    }
+
+    #[tokio::test]
+    async fn test_kafka_source_rebalance() -> anyhow::Result<()> {
+        quickwit_common::setup_logging_for_tests();
+
+        let universe = Universe::new();
+
+        let bootstrap_servers = "localhost:9092".to_string();
+        let group_id = append_random_suffix("test-kafka-source-rebalance-consumer-group");
+        let topic = append_random_suffix("test-kafka-source-rebalance-topic");
+
+        let admin_client = create_admin_client(&bootstrap_servers)?;
+        create_topic(&admin_client, &topic, 2).await?;
+
+        let source_config = SourceConfig {
+            source_id: "kafka-test-source".to_string(),
+            source_type: "kafka".to_string(),
+            params: json!({
+                "bootstrap_servers": bootstrap_servers,
+                "group_id": group_id,
+                "topic": topic,
+            }),
+        };
+        let index_id = "test-kafka-source-rebalance-index";
+        let ctx = source_ctx_for_test(index_id, Checkpoint::default()).await?;
+        let published_docs = Arc::new(Mutex::new(Vec::new()));
+        let publish_errors = Arc::new(Mutex::new(Vec::new()));
+        let source_loader = quickwit_supported_sources();
+
+        let mut expected_docs = Vec::new();
+        for round in 0..2 {
+            for partition_id in 0..2 {
+                let message_fn =
+                    |message_id| format!("Message #{}{}{}", round, partition_id, message_id);
+                populate_topic(
+                    &bootstrap_servers,
+                    &topic,
+                    5,
+                    &key_fn,
+                    &message_fn,
+                    Some(partition_id),
+                    None,
+                )
+                .await?;
+                expected_docs.extend((0..5).map(message_fn));
+            }
+            if round > 0 {
+                break;
+            }
+            // The first source consumes the two partitions alone.
+            let (sink, inbox) = create_test_mailbox();
+            let source = source_loader
+                .load_source(ctx.clone(), source_config.clone(), Checkpoint::default())
+                .await?;
+            universe
+                .spawn_actor(SourceActor {
+                    source,
+                    batch_sink: sink,
+                })
+                .spawn_async();
+            spawn_test_publisher(
+                move || inbox.drain_available_message_for_test(),
+                ctx.metastore.clone(),
+                index_id,
+                Duration::from_secs(2),
+                published_docs.clone(),
+                publish_errors.clone(),
+            );
+            wait_for_checkpoint(&*ctx.metastore, index_id, &[(0, 4), (1, 4)]).await?;
+        }
+        // The second source joins the group while the first one has not published the messages
+        // of the second round yet: the partitions must not be handed over before they are.
+        let (sink, inbox) = create_test_mailbox();
+        let source = source_loader
+            .load_source(ctx.clone(), source_config.clone(), Checkpoint::default())
+            .await?;
+        let (_mailbox, handle) = universe
+            .spawn_actor(SourceActor {
+                source,
+                batch_sink: sink,
+            })
+            .spawn_async();
+        spawn_test_publisher(
+            move || inbox.drain_available_message_for_test(),
+            ctx.metastore.clone(),
+            index_id,
+            Duration::ZERO,
+            published_docs.clone(),
+            publish_errors.clone(),
+        );
+        wait_for_checkpoint(&*ctx.metastore, index_id, &[(0, 9), (1, 9)]).await?;
+
+        let deadline = tokio::time::Instant::now() + Duration::from_secs(60);
+        loop {
+            let state = handle.observe().await.state;
+            if state["assigned_partitions"][&topic]
+                .as_array()
+                .map(Vec::len)
+                == Some(1)
+            {
+                break;
+            }
+            assert!(
+                tokio::time::Instant::now() < deadline,
+                "The second source was not assigned a partition."
+            );
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        assert!(publish_errors.lock().unwrap().is_empty());
+
+        let mut published_docs = published_docs.lock().unwrap().clone();
+        published_docs.sort();
+        expected_docs.sort();
+        assert_eq!(published_docs, expected_docs);
+        Ok(())
+    }
+
     #[tokio::test]
     async fn test_fetch_partition_ids() -> anyhow::Result<()> {
         let bootstrap_servers = "localhost:9092".to_string();
@@ -904,12 +1880,20 @@ mod kafka_broker_tests {
         let admin_client = create_admin_client(&bootstrap_servers)?;
         create_topic(&admin_client, &topic, 2).await?;
 
-        let consumer = create_consumer(&bootstrap_servers, &group_id, true)?;
-        assert!(fetch_partition_ids(&consumer, "topic-does-not-exist")
-            .await
-            .is_err());
+        let (events_tx, _events_rx) = mpsc::channel(EVENTS_CHANNEL_CAPACITY);
+        let consumer = Arc::new(create_consumer(
+            &bootstrap_servers,
+            &group_id,
+            true,
+            events_tx,
+        )?);
+        assert!(
+            fetch_partition_ids(consumer.clone(), "topic-does-not-exist")
+                .await
+                .is_err()
+        );
 
-        let partition_ids = fetch_partition_ids(&consumer, &topic).await?;
+        let partition_ids = fetch_partition_ids(consumer.clone(), &topic).await?;
         assert_eq!(&partition_ids, &[0, 1]);
         Ok(())
     }
@@ -923,21 +1907,29 @@ mod kafka_broker_tests {
         let admin_client = create_admin_client(&bootstrap_servers)?;
         create_topic(&admin_client, &topic, 2).await?;
 
-        let consumer = create_consumer(&bootstrap_servers, &group_id, true)?;
+        let (events_tx, _events_rx) = mpsc::channel(EVENTS_CHANNEL_CAPACITY);
+        let consumer = Arc::new(create_consumer(
+            &bootstrap_servers,
+            &group_id,
+            true,
+            events_tx,
+        )?);
         // Force metadata update for the consumer. Otherwise, `fetch_watermarks` may return
         // `UnknownPartition` if the broker hasn't received a metadata update since the
         // topic was created. See also https://issues.apache.org/jira/browse/KAFKA-6829.
         consumer.fetch_metadata(Some(&topic), Duration::from_secs(5))?;
-        assert!(fetch_watermarks(&consumer, "topic-does-not-exist", &[0])
-            .await
-            .is_err());
+        assert!(
+            fetch_watermarks(consumer.clone(), "topic-does-not-exist", &[0])
+                .await
+                .is_err()
+        );
         {
-            let watermarks = fetch_watermarks(&consumer, &topic, &[0]).await?;
+            let watermarks = fetch_watermarks(consumer.clone(), &topic, &[0]).await?;
             let expected_watermarks = vec![(0, (0, 0))].into_iter().collect();
             assert_eq!(watermarks, expected_watermarks);
         }
         {
-            let watermarks = fetch_watermarks(&consumer, &topic, &[0, 1]).await?;
+            let watermarks = fetch_watermarks(consumer.clone(), &topic, &[0, 1]).await?;
             let expected_watermarks = vec![(0, (0, 0)), (1, (0, 0))].into_iter().collect();
             assert_eq!(watermarks, expected_watermarks);
         }
@@ -954,7 +1946,7 @@ mod kafka_broker_tests {
             .await?;
         }
         {
-            let watermarks = fetch_watermarks(&consumer, &topic, &[0, 1]).await?;
+            let watermarks = fetch_watermarks(consumer.clone(), &topic, &[0, 1]).await?;
             let expected_watermarks = vec![(0, (0, 1)), (1, (0, 1))].into_iter().collect();
             assert_eq!(watermarks, expected_watermarks);
         }
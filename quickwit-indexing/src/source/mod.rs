@@ -18,19 +18,25 @@
 // along with this program. If not, see <http://www.gnu.org/licenses/>.
 
 mod file_source;
+mod ingest_api_source;
 #[cfg(feature = "kafka")]
 mod kafka_source;
 mod source_factory;
 mod vec_source;
 
 use std::fmt;
+use std::sync::Arc;
 
 use async_trait::async_trait;
 pub use file_source::{FileSource, FileSourceFactory, FileSourceParams};
+pub use ingest_api_source::{
+    IngestApiQueue, IngestApiQueues, IngestApiSource, IngestApiSourceFactory, IngestApiSourceParams,
+};
 #[cfg(feature = "kafka")]
 pub use kafka_source::{KafkaSource, KafkaSourceFactory, KafkaSourceParams};
 use once_cell::sync::OnceCell;
 use quickwit_actors::{Actor, ActorContext, ActorExitStatus, AsyncActor, Mailbox};
+use quickwit_metastore::Metastore;
 use serde::{Deserialize, Serialize};
 pub use source_factory::{SourceFactory, SourceLoader, TypedSourceFactory};
 pub use vec_source::{VecSource, VecSourceFactory, VecSourceParams};
@@ -39,6 +45,28 @@ use crate::models::IndexerMessage;
 
 pub type SourceContext = ActorContext<Loop>;
 
+/// Resources of the indexing pipeline a source is running in.
+///
+/// Sources that may be reassigned partitions while running, like the Kafka source, rely on it to
+/// fetch the latest checkpoint of the index from the metastore.
+pub struct SourceExecutionContext {
+    pub index_id: String,
+    pub metastore: Arc<dyn Metastore>,
+    /// Queues consumed by the ingest API sources.
+    pub ingest_api_queues: IngestApiQueues,
+}
+
+impl SourceExecutionContext {
+    #[cfg(test)]
+    pub(crate) fn for_test(metastore: Arc<dyn Metastore>, index_id: &str) -> Arc<Self> {
+        Arc::new(SourceExecutionContext {
+            index_id: index_id.to_string(),
+            metastore,
+            ingest_api_queues: IngestApiQueues::default(),
+        })
+    }
+}
+
 /// A source is a trait that is mounted in a light wrapping Actor called `SourceActor`.
 ///
 /// For this reason, its methods mimics those of Actor.
@@ -155,6 +183,7 @@ pub fn quickwit_supported_sources() -> &'static SourceLoader {
     SOURCE_LOADER.get_or_init(|| {
         let mut source_factory = SourceLoader::default();
         source_factory.add_source("file", FileSourceFactory);
+        source_factory.add_source("ingest-api", IngestApiSourceFactory);
         #[cfg(feature = "kafka")]
         source_factory.add_source("kafka", KafkaSourceFactory);
         source_factory.add_source("vec", VecSourceFactory);
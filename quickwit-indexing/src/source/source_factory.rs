@@ -18,19 +18,21 @@
 // along with this program. If not, see <http://www.gnu.org/licenses/>.
 
 use std::collections::HashMap;
+use std::sync::Arc;
 
 use async_trait::async_trait;
 use itertools::Itertools;
 use quickwit_metastore::checkpoint::Checkpoint;
 use thiserror::Error;
 
-use super::Source;
+use super::{Source, SourceExecutionContext};
 use crate::source::SourceConfig;
 
 #[async_trait]
 pub trait SourceFactory: 'static + Send + Sync {
     async fn create_source(
         &self,
+        ctx: Arc<SourceExecutionContext>,
         params: serde_json::Value,
         checkpoint: Checkpoint,
     ) -> anyhow::Result<Box<dyn Source>>;
@@ -41,6 +43,7 @@ pub trait TypedSourceFactory: Send + Sync + 'static {
     type Source: Source;
     type Params: serde::de::DeserializeOwned + Send + Sync + 'static;
     async fn typed_create_source(
+        ctx: Arc<SourceExecutionContext>,
         params: Self::Params,
         checkpoint: quickwit_metastore::checkpoint::Checkpoint,
     ) -> anyhow::Result<Self::Source>;
@@ -50,11 +53,12 @@ pub trait TypedSourceFactory: Send + Sync + 'static {
 impl<T: TypedSourceFactory> SourceFactory for T {
     async fn create_source(
         &self,
+        ctx: Arc<SourceExecutionContext>,
         params: serde_json::Value,
         checkpoint: quickwit_metastore::checkpoint::Checkpoint,
     ) -> anyhow::Result<Box<dyn Source>> {
         let typed_params: T::Params = serde_json::from_value(params)?;
-        let file_source = Self::typed_create_source(typed_params, checkpoint).await?;
+        let file_source = Self::typed_create_source(ctx, typed_params, checkpoint).await?;
         Ok(Box::new(file_source))
     }
 }
@@ -91,6 +95,7 @@ impl SourceLoader {
 
     pub async fn load_source(
         &self,
+        ctx: Arc<SourceExecutionContext>,
         source_config: SourceConfig,
         checkpoint: Checkpoint,
     ) -> Result<Box<dyn Source>, SourceLoaderError> {
@@ -107,7 +112,7 @@ impl SourceLoader {
             params,
         } = source_config;
         source_factory
-            .create_source(params, checkpoint)
+            .create_source(ctx, params, checkpoint)
             .await
             .map_err(|error| SourceLoaderError::FailedToCreateSource {
                 source_id,
@@ -119,6 +124,7 @@ impl SourceLoader {
 
 #[cfg(test)]
 mod tests {
+    use quickwit_metastore::SingleFileMetastore;
     use serde_json::json;
 
     use super::*;
@@ -127,13 +133,15 @@ mod tests {
     #[tokio::test]
     async fn test_source_loader_success() -> anyhow::Result<()> {
         let source_loader = quickwit_supported_sources();
+        let metastore = Arc::new(SingleFileMetastore::for_test());
+        let ctx = SourceExecutionContext::for_test(metastore, "test-index");
         let source_config = SourceConfig {
             source_id: "test-source".to_string(),
             source_type: "vec".to_string(),
             params: json!({"items": [], "batch_num_docs": 3}),
         };
         source_loader
-            .load_source(source_config, Checkpoint::default())
+            .load_source(ctx, source_config, Checkpoint::default())
             .await?;
         Ok(())
     }
@@ -141,13 +149,15 @@ mod tests {
     #[tokio::test]
     async fn test_source_loader_missing_type() {
         let source_loader = quickwit_supported_sources();
+        let metastore = Arc::new(SingleFileMetastore::for_test());
+        let ctx = SourceExecutionContext::for_test(metastore, "test-index");
         let source_config = SourceConfig {
             source_id: "test-source".to_string(),
             source_type: "vec2".to_string(),
             params: json!({"items": []}),
         };
         let source_result = source_loader
-            .load_source(source_config, Checkpoint::default())
+            .load_source(ctx, source_config, Checkpoint::default())
             .await;
         assert!(matches!(
             source_result,
@@ -158,13 +168,15 @@ mod tests {
     #[tokio::test]
     async fn test_source_loader_invalid_params() -> anyhow::Result<()> {
         let source_loader = quickwit_supported_sources();
+        let metastore = Arc::new(SingleFileMetastore::for_test());
+        let ctx = SourceExecutionContext::for_test(metastore, "test-index");
         let source_config = SourceConfig {
             source_id: "test-source".to_string(),
             source_type: "vec".to_string(),
             params: json!({"item": [], "batch_num_docs": 3}), //< item is misspelled
         };
         let source_result = source_loader
-            .load_source(source_config, Checkpoint::default())
+            .load_source(ctx, source_config, Checkpoint::default())
             .await;
         assert!(matches!(
             source_result,
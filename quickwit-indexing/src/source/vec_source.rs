@@ -17,6 +17,8 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program. If not, see <http://www.gnu.org/licenses/>.
 
+use std::sync::Arc;
+
 use async_trait::async_trait;
 use quickwit_actors::{ActorExitStatus, Mailbox};
 use quickwit_metastore::checkpoint::{Checkpoint, CheckpointDelta, PartitionId, Position};
@@ -24,7 +26,7 @@ use serde::{Deserialize, Serialize};
 use tracing::info;
 
 use crate::models::{IndexerMessage, RawDocBatch};
-use crate::source::{Source, SourceContext, TypedSourceFactory};
+use crate::source::{Source, SourceContext, SourceExecutionContext, TypedSourceFactory};
 
 #[derive(Deserialize, Serialize)]
 pub struct VecSourceParams {
@@ -46,6 +48,7 @@ impl TypedSourceFactory for VecSourceFactory {
     type Source = VecSource;
     type Params = VecSourceParams;
     async fn typed_create_source(
+        _ctx: Arc<SourceExecutionContext>,
         params: VecSourceParams,
         checkpoint: Checkpoint,
     ) -> anyhow::Result<Self::Source> {
@@ -62,7 +65,7 @@ impl TypedSourceFactory for VecSourceFactory {
     }
 }
 
-fn position_from_offset(offset: usize) -> Position {
+pub(crate) fn position_from_offset(offset: usize) -> Position {
     if offset == 0 {
         return Position::Beginning;
     }
@@ -113,6 +116,7 @@ impl Source for VecSource {
 #[cfg(test)]
 mod tests {
     use quickwit_actors::{create_test_mailbox, Command, CommandOrMessage, Universe};
+    use quickwit_metastore::SingleFileMetastore;
     use serde_json::json;
 
     use super::*;
@@ -131,8 +135,12 @@ mod tests {
             batch_num_docs: 3,
             partition: "partition".to_string(),
         };
+        let ctx = SourceExecutionContext::for_test(
+            Arc::new(SingleFileMetastore::for_test()),
+            "test-index",
+        );
         let vec_source =
-            VecSourceFactory::typed_create_source(params, Checkpoint::default()).await?;
+            VecSourceFactory::typed_create_source(ctx, params, Checkpoint::default()).await?;
         let vec_source_actor = SourceActor {
             source: Box::new(vec_source),
             batch_sink: mailbox,
@@ -168,7 +176,11 @@ mod tests {
         let mut checkpoint = Checkpoint::default();
         checkpoint.try_apply_delta(CheckpointDelta::from(0u64..2u64))?;
 
-        let vec_source = VecSourceFactory::typed_create_source(params, checkpoint).await?;
+        let ctx = SourceExecutionContext::for_test(
+            Arc::new(SingleFileMetastore::for_test()),
+            "test-index",
+        );
+        let vec_source = VecSourceFactory::typed_create_source(ctx, params, checkpoint).await?;
         let vec_source_actor = SourceActor {
             source: Box::new(vec_source),
             batch_sink: mailbox,
@@ -65,6 +65,7 @@ impl TestSandbox {
             index_uri,
             index_config,
             checkpoint: Checkpoint::default(),
+            encryption_key_id: None,
         };
         let storage_uri_resolver = StorageUriResolver::for_test();
         let metastore_uri_resolver = MetastoreUriResolver::default();
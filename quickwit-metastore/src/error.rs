@@ -17,8 +17,10 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program. If not, see <http://www.gnu.org/licenses/>.
 
+use std::collections::BTreeMap;
 use std::io;
 
+use quickwit_common::service_error::{ErrorCode, ServiceError};
 use thiserror::Error;
 
 use crate::checkpoint::IncompatibleCheckpointDelta;
@@ -68,6 +70,46 @@ pub enum MetastoreError {
     DbError(diesel::result::Error),
 }
 
+impl ServiceError for MetastoreError {
+    fn error_code(&self) -> ErrorCode {
+        match self {
+            MetastoreError::Io(io_error) if io_error.kind() == io::ErrorKind::TimedOut => {
+                ErrorCode::Timeout
+            }
+            MetastoreError::ConnectionError { .. } | MetastoreError::Io(_) => {
+                ErrorCode::Unavailable
+            }
+            MetastoreError::IndexAlreadyExists { .. } => ErrorCode::IndexAlreadyExists,
+            MetastoreError::Forbidden { .. } => ErrorCode::Forbidden,
+            MetastoreError::IndexDoesNotExist { .. } => ErrorCode::IndexDoesNotExist,
+            MetastoreError::InternalError { .. } | MetastoreError::InvalidManifest { .. } => {
+                ErrorCode::Internal
+            }
+            MetastoreError::SplitDoesNotExist { .. } => ErrorCode::NotFound,
+            MetastoreError::SplitIsNotStaged { .. }
+            | MetastoreError::IncompatibleCheckpointDelta(_) => ErrorCode::Conflict,
+            #[cfg(feature = "postgres")]
+            MetastoreError::DbError(_) => ErrorCode::Internal,
+        }
+    }
+
+    fn error_details(&self) -> BTreeMap<String, String> {
+        let mut details = BTreeMap::new();
+        match self {
+            MetastoreError::IndexAlreadyExists { index_id }
+            | MetastoreError::IndexDoesNotExist { index_id } => {
+                details.insert("index_id".to_string(), index_id.clone());
+            }
+            MetastoreError::SplitDoesNotExist { split_id }
+            | MetastoreError::SplitIsNotStaged { split_id } => {
+                details.insert("split_id".to_string(), split_id.clone());
+            }
+            _ => {}
+        }
+        details
+    }
+}
+
 #[cfg(feature = "postgres")]
 impl From<diesel::result::Error> for MetastoreError {
     fn from(err: diesel::result::Error) -> MetastoreError {
@@ -47,6 +47,10 @@ pub struct IndexMetadata {
     pub index_config: Arc<dyn IndexConfig>,
     /// Checkpoint relative to a source. It express up to where documents have been indexed.
     pub checkpoint: Checkpoint,
+    /// ID of the key used to encrypt the split files of this index. Split files are
+    /// stored in plaintext if it is not set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub encryption_key_id: Option<String>,
 }
 
 /// Carries split and bundle offsets for single read metadata.
@@ -199,6 +203,11 @@ pub trait Metastore: Send + Sync + 'static {
     /// TODO consider merging with list_splits to remove one round-trip
     async fn index_metadata(&self, index_id: &str) -> MetastoreResult<IndexMetadata>;
 
+    /// Lists the indexes.
+    /// Returns the metadata of all the indexes currently known to the metastore, sorted by
+    /// index ID.
+    async fn list_indexes_metadatas(&self) -> MetastoreResult<Vec<IndexMetadata>>;
+
     /// Deletes an index.
     /// This API removes the specified index metadata set from the metastore,
     /// but does not remove the index from the storage.
@@ -1002,6 +1002,34 @@ impl Metastore for PostgresqlMetastore {
         Ok(())
     }
 
+    async fn list_indexes_metadatas(&self) -> MetastoreResult<Vec<IndexMetadata>> {
+        let conn = self.connection_pool.get().map_err(|err| {
+            error!(err=?err, "Failed to get connection");
+            MetastoreError::ConnectionError {
+                message: format!("Failed to get connection {:?}", err),
+            }
+        })?;
+
+        let select_indexes_statement =
+            schema::indexes::dsl::indexes.order(schema::indexes::dsl::index_id.asc());
+        debug!(sql=%debug_query::<Pg, _>(&select_indexes_statement).to_string());
+        let model_indexes = select_indexes_statement
+            .load::<model::Index>(&conn)
+            .map_err(MetastoreError::DbError)?;
+
+        model_indexes
+            .into_iter()
+            .map(|model_index| {
+                model_index
+                    .make_index_metadata()
+                    .map_err(|err| MetastoreError::InternalError {
+                        message: "Failed to make index metadata".to_string(),
+                        cause: anyhow::anyhow!(err),
+                    })
+            })
+            .collect()
+    }
+
     async fn index_metadata(&self, index_id: &str) -> MetastoreResult<IndexMetadata> {
         let conn = self.connection_pool.get().map_err(|err| {
             error!(err=?err, "Failed to get connection");
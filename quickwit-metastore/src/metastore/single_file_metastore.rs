@@ -17,7 +17,7 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program. If not, see <http://www.gnu.org/licenses/>.
 
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap};
 use std::ops::{Range, RangeInclusive};
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
@@ -28,7 +28,7 @@ use quickwit_storage::{
     quickwit_storage_uri_resolver, PutPayload, Storage, StorageErrorKind, StorageResolverError,
     StorageUriResolver,
 };
-use tokio::sync::RwLock;
+use tokio::sync::{Mutex, RwLock};
 
 use crate::checkpoint::CheckpointDelta;
 use crate::metastore::match_tags_filter;
@@ -41,6 +41,9 @@ use crate::{
 /// Metadata file managed by [`SingleFileMetastore`].
 const META_FILENAME: &str = "quickwit.json";
 
+/// File listing the IDs of the indexes managed by [`SingleFileMetastore`].
+const INDEXES_FILENAME: &str = "indexes.json";
+
 /// Creates a path to the metadata file from the given index ID.
 fn meta_path(index_id: &str) -> PathBuf {
     Path::new(index_id).join(Path::new(META_FILENAME))
@@ -52,9 +55,17 @@ fn is_disjoint(left: &Range<i64>, right: &RangeInclusive<i64>) -> bool {
 }
 
 /// Single file metastore implementation.
+///
+/// The metadata of each index is stored in its own file, and the IDs of the indexes are listed
+/// in the indexes file. Files are read and rewritten as a whole and cached in memory, so the
+/// metastore assumes a single writer: updates made by another process are not seen, and
+/// concurrent updates from several processes may overwrite each other. Several processes
+/// writing to the same indexes should use the PostgreSQL metastore instead.
 pub struct SingleFileMetastore {
     storage: Arc<dyn Storage>,
     cache: Arc<RwLock<HashMap<String, MetadataSet>>>,
+    // Serializes the reads and updates of the indexes file.
+    index_ids_lock: Mutex<()>,
 }
 
 #[allow(dead_code)]
@@ -71,6 +82,7 @@ impl SingleFileMetastore {
         SingleFileMetastore {
             storage,
             cache: Arc::new(RwLock::new(HashMap::new())),
+            index_ids_lock: Mutex::new(()),
         }
     }
 
@@ -188,6 +200,82 @@ impl SingleFileMetastore {
         Ok(())
     }
 
+    /// Returns the IDs of the indexes listed in the indexes file.
+    ///
+    /// The indexes file is created from the indexes found in the storage if it does not exist
+    /// yet, e.g. for metastores created before it was introduced. The caller must hold the
+    /// `index_ids_lock`.
+    async fn get_index_ids(&self) -> MetastoreResult<BTreeSet<String>> {
+        let content = match self.storage.get_all(Path::new(INDEXES_FILENAME)).await {
+            Ok(content) => content,
+            Err(storage_err) if storage_err.kind() == StorageErrorKind::DoesNotExist => {
+                let index_ids = self.list_index_ids().await?;
+                self.put_index_ids(&index_ids).await?;
+                return Ok(index_ids);
+            }
+            Err(storage_err) => {
+                return Err(MetastoreError::InternalError {
+                    message: "Failed to get indexes file.".to_string(),
+                    cause: anyhow::anyhow!(storage_err),
+                })
+            }
+        };
+        serde_json::from_slice(&content[..])
+            .map_err(|serde_err| MetastoreError::InvalidManifest { cause: serde_err })
+    }
+
+    /// Lists the IDs of the indexes whose metadata file is in the storage.
+    async fn list_index_ids(&self) -> MetastoreResult<BTreeSet<String>> {
+        let directories =
+            self.storage
+                .list_directories(Path::new(""))
+                .await
+                .map_err(|storage_err| MetastoreError::InternalError {
+                    message: "Failed to list indexes.".to_string(),
+                    cause: anyhow::anyhow!(storage_err),
+                })?;
+        let mut index_ids = BTreeSet::new();
+        // The directories of the indexes may also hold their split files, or other files.
+        for directory in directories {
+            if self.index_exists(&directory).await? {
+                index_ids.insert(directory);
+            }
+        }
+        Ok(index_ids)
+    }
+
+    /// Writes the indexes file.
+    async fn put_index_ids(&self, index_ids: &BTreeSet<String>) -> MetastoreResult<()> {
+        let content: Vec<u8> = serde_json::to_vec_pretty(index_ids).map_err(|serde_err| {
+            MetastoreError::InternalError {
+                message: "Failed to serialize index IDs".to_string(),
+                cause: anyhow::anyhow!(serde_err),
+            }
+        })?;
+        self.storage
+            .put(Path::new(INDEXES_FILENAME), PutPayload::from(content))
+            .await
+            .map_err(|storage_err| MetastoreError::InternalError {
+                message: format!("Failed to write indexes file to `{}`.", INDEXES_FILENAME),
+                cause: anyhow::anyhow!(storage_err),
+            })
+    }
+
+    /// Adds or removes an index ID from the indexes file.
+    async fn update_index_ids(&self, index_id: &str, is_added: bool) -> MetastoreResult<()> {
+        let _index_ids_guard = self.index_ids_lock.lock().await;
+        let mut index_ids = self.get_index_ids().await?;
+        let is_modified = if is_added {
+            index_ids.insert(index_id.to_string())
+        } else {
+            index_ids.remove(index_id)
+        };
+        if !is_modified {
+            return Ok(());
+        }
+        self.put_index_ids(&index_ids).await
+    }
+
     /// Helper to mark a list of splits as published.
     fn mark_splits_as_published_helper<'a>(
         split_ids: &[&'a str],
@@ -262,11 +350,13 @@ impl Metastore for SingleFileMetastore {
             });
         }
 
+        let index_id = index_metadata.index_id.clone();
         let metadata_set = MetadataSet {
             index: index_metadata,
             splits: HashMap::new(),
         };
         self.put_index(metadata_set).await?;
+        self.update_index_ids(&index_id, true).await?;
 
         Ok(())
     }
@@ -301,8 +391,8 @@ impl Metastore for SingleFileMetastore {
             })?;
 
         // Update the internal data if the storage is successfully updated.
-        let mut cache = self.cache.write().await;
-        cache.remove(index_id);
+        self.cache.write().await.remove(index_id);
+        self.update_index_ids(index_id, false).await?;
 
         Ok(())
     }
@@ -480,6 +570,24 @@ impl Metastore for SingleFileMetastore {
         Ok(index_metadata.index)
     }
 
+    async fn list_indexes_metadatas(&self) -> MetastoreResult<Vec<IndexMetadata>> {
+        let mut index_ids = {
+            let _index_ids_guard = self.index_ids_lock.lock().await;
+            self.get_index_ids().await?
+        };
+        index_ids.extend(self.cache.read().await.keys().cloned());
+        let mut indexes_metadatas = Vec::with_capacity(index_ids.len());
+        for index_id in index_ids {
+            match self.get_index(&index_id).await {
+                Ok(metadata_set) => indexes_metadatas.push(metadata_set.index),
+                // The index may have been deleted in the meantime.
+                Err(MetastoreError::IndexDoesNotExist { .. }) => {}
+                Err(error) => return Err(error),
+            }
+        }
+        Ok(indexes_metadatas)
+    }
+
     fn uri(&self) -> String {
         self.storage.uri()
     }
@@ -569,6 +677,7 @@ mod tests {
                 index_uri: "ram://indexes/my-index".to_string(),
                 index_config: Arc::new(WikipediaIndexConfig::default()),
                 checkpoint: Checkpoint::default(),
+                encryption_key_id: None,
             };
 
             // Create index
@@ -597,6 +706,7 @@ mod tests {
                 index_uri: "ram://indexes/my-index".to_string(),
                 index_config: Arc::new(WikipediaIndexConfig::default()),
                 checkpoint: Checkpoint::default(),
+                encryption_key_id: None,
             };
 
             // Create index
@@ -642,6 +752,22 @@ mod tests {
         mock_storage // remove this if we end up changing the semantics of create.
             .expect_exists()
             .returning(|_| Ok(false));
+        mock_storage
+            .expect_get_all()
+            .withf(|uri| uri == Path::new("indexes.json"))
+            .returning(
+                |_| Err(StorageErrorKind::DoesNotExist.with_error(anyhow::anyhow!("Nope."))),
+            );
+        mock_storage
+            .expect_list_directories()
+            .times(1)
+            .returning(|_| Ok(Vec::new()));
+        // The indexes file is created empty, and then updated with the new index.
+        mock_storage
+            .expect_put()
+            .withf(|uri, _| uri == Path::new("indexes.json"))
+            .times(2)
+            .returning(|_, _| Ok(()));
         mock_storage.expect_put().times(2).returning(|uri, _| {
             assert_eq!(uri, Path::new("my-index/quickwit.json"));
             Ok(())
@@ -673,6 +799,7 @@ mod tests {
             index_uri: "ram://indexes/my-index".to_string(),
             index_config: Arc::new(quickwit_index_config::default_config_for_tests()),
             checkpoint: Checkpoint::default(),
+            encryption_key_id: None,
         };
 
         // create index
@@ -717,6 +844,7 @@ mod tests {
                 index_uri: "ram://indexes/my-index".to_string(),
                 index_config: Arc::new(WikipediaIndexConfig::default()),
                 checkpoint: Checkpoint::default(),
+                encryption_key_id: None,
             },
             splits: HashMap::new(),
         };
@@ -735,4 +863,58 @@ mod tests {
             MetastoreError::InternalError { .. }
         ));
     }
+
+    #[tokio::test]
+    async fn test_single_file_metastore_backfills_indexes_file() {
+        let metastore = SingleFileMetastore::for_test();
+        // Indexes created before the indexes file was introduced.
+        for index_id in &["index-1", "index-2"] {
+            let metadata_set = MetadataSet {
+                index: IndexMetadata {
+                    index_id: index_id.to_string(),
+                    index_uri: format!("ram://indexes/{}", index_id),
+                    index_config: Arc::new(WikipediaIndexConfig::default()),
+                    checkpoint: Checkpoint::default(),
+                    encryption_key_id: None,
+                },
+                splits: HashMap::new(),
+            };
+            let content: Vec<u8> = serde_json::to_vec(&metadata_set).unwrap();
+            metastore
+                .storage
+                .put(&meta_path(index_id), PutPayload::from(content))
+                .await
+                .unwrap();
+        }
+        // A directory without metadata file, e.g. holding the splits of a deleted index.
+        metastore
+            .storage
+            .put(
+                Path::new("deleted-index/split.split"),
+                PutPayload::from(b"".as_ref()),
+            )
+            .await
+            .unwrap();
+
+        let index_ids: Vec<String> = metastore
+            .list_indexes_metadatas()
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|index_metadata| index_metadata.index_id)
+            .collect();
+        assert_eq!(
+            index_ids,
+            vec!["index-1".to_string(), "index-2".to_string()]
+        );
+
+        let indexes_file_content = metastore
+            .storage
+            .get_all(Path::new("indexes.json"))
+            .await
+            .unwrap();
+        let index_ids_in_file: Vec<String> =
+            serde_json::from_slice(&indexes_file_content[..]).unwrap();
+        assert_eq!(index_ids_in_file, index_ids);
+    }
 }
@@ -79,6 +79,7 @@ pub mod test_suite {
             index_uri: "ram://indexes/my-index".to_string(),
             index_config: Arc::new(quickwit_index_config::default_config_for_tests()),
             checkpoint: Checkpoint::default(),
+            encryption_key_id: None,
         };
 
         // Create an index
@@ -100,6 +101,7 @@ pub mod test_suite {
             index_uri: "ram://indexes/my-index".to_string(),
             index_config: Arc::new(quickwit_index_config::default_config_for_tests()),
             checkpoint: Checkpoint::default(),
+            encryption_key_id: None,
         };
 
         // Delete a non-existent index
@@ -129,6 +131,7 @@ pub mod test_suite {
             index_uri: "ram://indexes/my-index".to_string(),
             index_config: Arc::new(quickwit_index_config::default_config_for_tests()),
             checkpoint: Checkpoint::default(),
+            encryption_key_id: None,
         };
 
         // Get a non-existent index metadata
@@ -150,6 +153,40 @@ pub mod test_suite {
         cleanup_index(&metastore, index_id).await;
     }
 
+    pub async fn test_metastore_list_indexes_metadatas<
+        MetastoreToTest: Metastore + DefaultForTest,
+    >() {
+        let metastore = MetastoreToTest::default_for_test().await;
+
+        let index_ids = ["list-indexes-index-1", "list-indexes-index-2"];
+        for index_id in index_ids.iter() {
+            let index_metadata = IndexMetadata {
+                index_id: index_id.to_string(),
+                index_uri: format!("ram://indexes/{}", index_id),
+                index_config: Arc::new(quickwit_index_config::default_config_for_tests()),
+                checkpoint: Checkpoint::default(),
+                encryption_key_id: None,
+            };
+            metastore.create_index(index_metadata).await.unwrap();
+        }
+        // The metastore may be shared with other tests, so we only look at our indexes.
+        let listed_index_ids = |indexes_metadatas: Vec<IndexMetadata>| {
+            indexes_metadatas
+                .into_iter()
+                .map(|index_metadata| index_metadata.index_id)
+                .filter(|index_id| index_id.starts_with("list-indexes-index"))
+                .collect::<Vec<_>>()
+        };
+        let indexes_metadatas = metastore.list_indexes_metadatas().await.unwrap();
+        assert_eq!(listed_index_ids(indexes_metadatas), index_ids);
+
+        cleanup_index(&metastore, index_ids[0]).await;
+        let indexes_metadatas = metastore.list_indexes_metadatas().await.unwrap();
+        assert_eq!(listed_index_ids(indexes_metadatas), &index_ids[1..]);
+
+        cleanup_index(&metastore, index_ids[1]).await;
+    }
+
     pub async fn test_metastore_stage_split<MetastoreToTest: Metastore + DefaultForTest>() {
         let metastore = MetastoreToTest::default_for_test().await;
 
@@ -161,6 +198,7 @@ pub mod test_suite {
             index_uri: "ram://indexes/my-index".to_string(),
             index_config: Arc::new(quickwit_index_config::default_config_for_tests()),
             checkpoint: Checkpoint::default(),
+            encryption_key_id: None,
         };
 
         let split_id = "stage-split-my-index-one";
@@ -217,6 +255,7 @@ pub mod test_suite {
             index_uri: "ram://indexes/my-index".to_string(),
             index_config: Arc::new(quickwit_index_config::default_config_for_tests()),
             checkpoint: Checkpoint::default(),
+            encryption_key_id: None,
         };
 
         let split_id_1 = "publish-splits-index-one";
@@ -571,6 +610,7 @@ pub mod test_suite {
             index_uri: "ram://indexes/my-index".to_string(),
             index_config: Arc::new(quickwit_index_config::default_config_for_tests()),
             checkpoint: Checkpoint::default(),
+            encryption_key_id: None,
         };
 
         let split_id_1 = "replace_splits-index-one";
@@ -795,6 +835,7 @@ pub mod test_suite {
             index_uri: "ram://indexes/my-index".to_string(),
             index_config: Arc::new(quickwit_index_config::default_config_for_tests()),
             checkpoint: Checkpoint::default(),
+            encryption_key_id: None,
         };
 
         let split_id_1 = "mark-splits-as-deleted-my-index-one";
@@ -869,6 +910,7 @@ pub mod test_suite {
             index_uri: "ram://indexes/my-index".to_string(),
             index_config: Arc::new(quickwit_index_config::default_config_for_tests()),
             checkpoint: Checkpoint::default(),
+            encryption_key_id: None,
         };
 
         let split_id_1 = "delete-splits-index-one";
@@ -995,6 +1037,7 @@ pub mod test_suite {
             index_uri: "ram://indexes/my-index".to_string(),
             index_config: Arc::new(quickwit_index_config::default_config_for_tests()),
             checkpoint: Checkpoint::default(),
+            encryption_key_id: None,
         };
 
         let split_id_1 = "list-all-splits-index-one";
@@ -1130,6 +1173,7 @@ pub mod test_suite {
             index_uri: "ram://indexes/my-index".to_string(),
             index_config: Arc::new(quickwit_index_config::default_config_for_tests()),
             checkpoint: Checkpoint::default(),
+            encryption_key_id: None,
         };
 
         let split_id_1 = "list-splits-one";
@@ -1659,6 +1703,7 @@ pub mod test_suite {
             index_uri: "ram://indexes/my-index".to_string(),
             index_config: Arc::new(quickwit_index_config::default_config_for_tests()),
             checkpoint: Checkpoint::default(),
+            encryption_key_id: None,
         };
 
         let split_id = "split-update-timestamp-one";
@@ -1741,6 +1786,13 @@ macro_rules! metastore_test_suite {
                 crate::tests::test_suite::test_metastore_index_metadata::<$metastore_type>().await;
             }
 
+            #[tokio::test]
+            async fn test_metastore_list_indexes_metadatas() {
+                crate::tests::test_suite::test_metastore_list_indexes_metadatas::<$metastore_type>(
+                )
+                .await;
+            }
+
             #[tokio::test]
             async fn test_metastore_stage_split() {
                 crate::tests::test_suite::test_metastore_stage_split::<$metastore_type>().await;
@@ -1808,6 +1860,13 @@ macro_rules! metastore_test_suite_for_postgresql {
                 crate::tests::test_suite::test_metastore_index_metadata::<$metastore_type>().await;
             }
 
+            #[tokio::test]
+            async fn test_metastore_list_indexes_metadatas() {
+                crate::tests::test_suite::test_metastore_list_indexes_metadatas::<$metastore_type>(
+                )
+                .await;
+            }
+
             #[tokio::test]
             async fn test_metastore_stage_split() {
                 crate::tests::test_suite::test_metastore_stage_split::<$metastore_type>().await;
@@ -20,6 +20,9 @@
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     println!("cargo:rerun-if-changed=proto/cluster.proto");
     println!("cargo:rerun-if-changed=proto/search_api.proto");
+    println!("cargo:rerun-if-changed=proto/opentelemetry");
+    println!("cargo:rerun-if-changed=proto/jaeger");
+    println!("cargo:rerun-if-changed=proto/google");
 
     let mut prost_config = prost_build::Config::default();
     prost_config.protoc_arg("--experimental_allow_proto3_optional");
@@ -35,5 +38,40 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
             &["./proto/cluster.proto", "./proto/search_api.proto"],
             &["./proto"],
         )?;
+
+    // The OpenTelemetry protocol messages are only exchanged as protobuf.
+    let mut prost_config = prost_build::Config::default();
+    prost_config.protoc_arg("--experimental_allow_proto3_optional");
+    tonic_build::configure()
+        .format(true)
+        .out_dir("src/opentelemetry/")
+        .compile_with_config(
+            prost_config,
+            &[
+                "./proto/opentelemetry/proto/collector/logs/v1/logs_service.proto",
+                "./proto/opentelemetry/proto/collector/trace/v1/trace_service.proto",
+            ],
+            &["./proto"],
+        )?;
+
+    let mut prost_config = prost_build::Config::default();
+    prost_config.protoc_arg("--experimental_allow_proto3_optional");
+    tonic_build::configure()
+        .format(true)
+        .out_dir("src/jaeger/")
+        .compile_with_config(
+            prost_config,
+            &["./proto/jaeger/storage/v1/storage.proto"],
+            &["./proto"],
+        )?;
+
+    // The gRPC error model, used to attach structured details to gRPC errors.
+    prost_build::Config::new().out_dir("src/google/").compile_protos(
+        &[
+            "./proto/google/rpc/status.proto",
+            "./proto/google/rpc/error_details.proto",
+        ],
+        &["./proto"],
+    )?;
     Ok(())
 }
@@ -13,6 +13,14 @@ pub struct Member {
     //// If true, it means self.
     #[prost(bool, tag = "3")]
     pub is_self: bool,
+    //// Roles of the member, e.g. `indexer`, `searcher` or `janitor`.
+    #[prost(string, repeated, tag = "4")]
+    pub roles: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+    //// Key/value metadata gossiped by the member, e.g. its version,
+    //// available cache bytes and current load.
+    #[prost(map = "string, string", tag = "5")]
+    pub metadata:
+        ::std::collections::HashMap<::prost::alloc::string::String, ::prost::alloc::string::String>,
 }
 #[derive(Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
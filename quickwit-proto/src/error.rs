@@ -0,0 +1,139 @@
+// Copyright (C) 2021 Quickwit, Inc.
+//
+// Quickwit is offered under the AGPL v3.0 and as commercial software.
+// For commercial licensing, contact us at hello@quickwit.io.
+//
+// AGPL:
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+//! gRPC representation of the errors returned to the API clients.
+//!
+//! Following the gRPC richer error model, the details of a gRPC status hold a
+//! `google.rpc.Status` message, which carries a `google.rpc.ErrorInfo` whose reason is the
+//! [`ErrorCode`] of the error and whose metadata are the details of the error.
+
+use prost::Message;
+use quickwit_common::service_error::{ErrorCode, ErrorResponse, ServiceError};
+
+/// Messages of the gRPC error model, generated from the protos of `proto/google/rpc`.
+pub mod google_rpc {
+    include!("google/google.rpc.rs");
+}
+
+/// Domain of the `google.rpc.ErrorInfo` reasons.
+const ERROR_DOMAIN: &str = "quickwit.io";
+
+const ERROR_INFO_TYPE_URL: &str = "type.googleapis.com/google.rpc.ErrorInfo";
+
+/// Returns the gRPC status code of an error code.
+pub fn grpc_code(error_code: ErrorCode) -> tonic::Code {
+    match error_code {
+        ErrorCode::InvalidArgument | ErrorCode::InvalidQuery => tonic::Code::InvalidArgument,
+        ErrorCode::Unauthenticated => tonic::Code::Unauthenticated,
+        ErrorCode::Forbidden => tonic::Code::PermissionDenied,
+        ErrorCode::NotFound | ErrorCode::IndexDoesNotExist => tonic::Code::NotFound,
+        ErrorCode::IndexAlreadyExists => tonic::Code::AlreadyExists,
+        ErrorCode::Conflict => tonic::Code::FailedPrecondition,
+        ErrorCode::Timeout => tonic::Code::DeadlineExceeded,
+        ErrorCode::Overloaded => tonic::Code::ResourceExhausted,
+        ErrorCode::Unavailable => tonic::Code::Unavailable,
+        ErrorCode::Internal => tonic::Code::Internal,
+    }
+}
+
+/// Builds the gRPC status of an error response.
+pub fn grpc_status(error_response: &ErrorResponse) -> tonic::Status {
+    let code = grpc_code(error_response.code);
+    let error_info = google_rpc::ErrorInfo {
+        reason: error_response.code.as_str().to_string(),
+        domain: ERROR_DOMAIN.to_string(),
+        metadata: error_response.details.clone().into_iter().collect(),
+    };
+    let status = google_rpc::Status {
+        code: code as i32,
+        message: error_response.message.clone(),
+        details: vec![prost_types::Any {
+            type_url: ERROR_INFO_TYPE_URL.to_string(),
+            value: error_info.encode_to_vec(),
+        }],
+    };
+    tonic::Status::with_details(
+        code,
+        error_response.message.clone(),
+        status.encode_to_vec().into(),
+    )
+}
+
+/// Extracts the error response attached to a gRPC status by [`grpc_status`], if any.
+pub fn error_response_from_grpc_status(grpc_status: &tonic::Status) -> Option<ErrorResponse> {
+    let status = google_rpc::Status::decode(grpc_status.details()).ok()?;
+    let error_info = status
+        .details
+        .iter()
+        .filter(|detail| detail.type_url == ERROR_INFO_TYPE_URL)
+        .filter_map(|detail| google_rpc::ErrorInfo::decode(&detail.value[..]).ok())
+        .find(|error_info| error_info.domain == ERROR_DOMAIN)?;
+    let code: ErrorCode = error_info.reason.parse().ok()?;
+    Some(ErrorResponse {
+        code,
+        message: status.message,
+        details: error_info.metadata.into_iter().collect(),
+        retryable: code.is_retryable(),
+    })
+}
+
+/// Conversion of the errors returned to the API clients into gRPC statuses.
+pub trait GrpcServiceError: ServiceError {
+    /// Builds the gRPC status of the error, see [`grpc_status`].
+    fn grpc_status(&self) -> tonic::Status {
+        grpc_status(&self.error_response())
+    }
+}
+
+impl<E: ServiceError + ?Sized> GrpcServiceError for E {}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::*;
+
+    #[test]
+    fn test_error_response_grpc_status() {
+        let mut details = BTreeMap::new();
+        details.insert("index_id".to_string(), "my-index".to_string());
+        let error_response = ErrorResponse {
+            code: ErrorCode::IndexDoesNotExist,
+            message: "Index `my-index` does not exist.".to_string(),
+            details,
+            retryable: false,
+        };
+        let status = grpc_status(&error_response);
+        assert_eq!(status.code(), tonic::Code::NotFound);
+        assert_eq!(status.message(), "Index `my-index` does not exist.");
+        assert_eq!(
+            error_response_from_grpc_status(&status),
+            Some(error_response)
+        );
+        // The details are a `google.rpc.Status` that any gRPC client can decode.
+        let rpc_status = google_rpc::Status::decode(status.details()).unwrap();
+        assert_eq!(rpc_status.code, tonic::Code::NotFound as i32);
+        let error_info = google_rpc::ErrorInfo::decode(&rpc_status.details[0].value[..]).unwrap();
+        assert_eq!(error_info.reason, "INDEX_DOES_NOT_EXIST");
+        assert_eq!(error_info.metadata["index_id"], "my-index");
+
+        let status = tonic::Status::new(tonic::Code::Internal, "Boom.");
+        assert_eq!(error_response_from_grpc_status(&status), None);
+    }
+}
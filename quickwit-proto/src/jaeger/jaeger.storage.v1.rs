@@ -0,0 +1,513 @@
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct GetTraceRequest {
+    #[prost(bytes = "vec", tag = "1")]
+    pub trace_id: ::prost::alloc::vec::Vec<u8>,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct SpansResponseChunk {
+    #[prost(message, repeated, tag = "1")]
+    pub spans: ::prost::alloc::vec::Vec<super::super::api_v2::Span>,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct GetServicesRequest {}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct GetServicesResponse {
+    #[prost(string, repeated, tag = "1")]
+    pub services: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct GetOperationsRequest {
+    #[prost(string, tag = "1")]
+    pub service: ::prost::alloc::string::String,
+    #[prost(string, tag = "2")]
+    pub span_kind: ::prost::alloc::string::String,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct Operation {
+    #[prost(string, tag = "1")]
+    pub name: ::prost::alloc::string::String,
+    #[prost(string, tag = "2")]
+    pub span_kind: ::prost::alloc::string::String,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct GetOperationsResponse {
+    /// deprecated
+    #[prost(string, repeated, tag = "1")]
+    pub operation_names: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+    #[prost(message, repeated, tag = "2")]
+    pub operations: ::prost::alloc::vec::Vec<Operation>,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct TraceQueryParameters {
+    #[prost(string, tag = "1")]
+    pub service_name: ::prost::alloc::string::String,
+    #[prost(string, tag = "2")]
+    pub operation_name: ::prost::alloc::string::String,
+    #[prost(map = "string, string", tag = "3")]
+    pub tags:
+        ::std::collections::HashMap<::prost::alloc::string::String, ::prost::alloc::string::String>,
+    #[prost(message, optional, tag = "4")]
+    pub start_time_min: ::core::option::Option<::prost_types::Timestamp>,
+    #[prost(message, optional, tag = "5")]
+    pub start_time_max: ::core::option::Option<::prost_types::Timestamp>,
+    #[prost(message, optional, tag = "6")]
+    pub duration_min: ::core::option::Option<::prost_types::Duration>,
+    #[prost(message, optional, tag = "7")]
+    pub duration_max: ::core::option::Option<::prost_types::Duration>,
+    #[prost(int32, tag = "8")]
+    pub num_traces: i32,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct FindTracesRequest {
+    #[prost(message, optional, tag = "1")]
+    pub query: ::core::option::Option<TraceQueryParameters>,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct FindTraceIDsRequest {
+    #[prost(message, optional, tag = "1")]
+    pub query: ::core::option::Option<TraceQueryParameters>,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct FindTraceIDsResponse {
+    #[prost(bytes = "vec", repeated, tag = "1")]
+    pub trace_ids: ::prost::alloc::vec::Vec<::prost::alloc::vec::Vec<u8>>,
+}
+#[doc = r" Generated client implementations."]
+pub mod span_reader_plugin_client {
+    #![allow(unused_variables, dead_code, missing_docs, clippy::let_unit_value)]
+    use tonic::codegen::*;
+    #[derive(Debug, Clone)]
+    pub struct SpanReaderPluginClient<T> {
+        inner: tonic::client::Grpc<T>,
+    }
+    impl SpanReaderPluginClient<tonic::transport::Channel> {
+        #[doc = r" Attempt to create a new client by connecting to a given endpoint."]
+        pub async fn connect<D>(dst: D) -> Result<Self, tonic::transport::Error>
+        where
+            D: std::convert::TryInto<tonic::transport::Endpoint>,
+            D::Error: Into<StdError>,
+        {
+            let conn = tonic::transport::Endpoint::new(dst)?.connect().await?;
+            Ok(Self::new(conn))
+        }
+    }
+    impl<T> SpanReaderPluginClient<T>
+    where
+        T: tonic::client::GrpcService<tonic::body::BoxBody>,
+        T::ResponseBody: Body + Send + Sync + 'static,
+        T::Error: Into<StdError>,
+        <T::ResponseBody as Body>::Error: Into<StdError> + Send,
+    {
+        pub fn new(inner: T) -> Self {
+            let inner = tonic::client::Grpc::new(inner);
+            Self { inner }
+        }
+        pub fn with_interceptor<F>(
+            inner: T,
+            interceptor: F,
+        ) -> SpanReaderPluginClient<InterceptedService<T, F>>
+        where
+            F: tonic::service::Interceptor,
+            T: tonic::codegen::Service<
+                http::Request<tonic::body::BoxBody>,
+                Response = http::Response<
+                    <T as tonic::client::GrpcService<tonic::body::BoxBody>>::ResponseBody,
+                >,
+            >,
+            <T as tonic::codegen::Service<http::Request<tonic::body::BoxBody>>>::Error:
+                Into<StdError> + Send + Sync,
+        {
+            SpanReaderPluginClient::new(InterceptedService::new(inner, interceptor))
+        }
+        #[doc = r" Compress requests with `gzip`."]
+        #[doc = r""]
+        #[doc = r" This requires the server to support it otherwise it might respond with an"]
+        #[doc = r" error."]
+        pub fn send_gzip(mut self) -> Self {
+            self.inner = self.inner.send_gzip();
+            self
+        }
+        #[doc = r" Enable decompressing responses with `gzip`."]
+        pub fn accept_gzip(mut self) -> Self {
+            self.inner = self.inner.accept_gzip();
+            self
+        }
+        #[doc = " spanstore/Reader"]
+        pub async fn get_trace(
+            &mut self,
+            request: impl tonic::IntoRequest<super::GetTraceRequest>,
+        ) -> Result<
+            tonic::Response<tonic::codec::Streaming<super::SpansResponseChunk>>,
+            tonic::Status,
+        > {
+            self.inner.ready().await.map_err(|e| {
+                tonic::Status::new(
+                    tonic::Code::Unknown,
+                    format!("Service was not ready: {}", e.into()),
+                )
+            })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/jaeger.storage.v1.SpanReaderPlugin/GetTrace",
+            );
+            self.inner
+                .server_streaming(request.into_request(), path, codec)
+                .await
+        }
+        pub async fn get_services(
+            &mut self,
+            request: impl tonic::IntoRequest<super::GetServicesRequest>,
+        ) -> Result<tonic::Response<super::GetServicesResponse>, tonic::Status> {
+            self.inner.ready().await.map_err(|e| {
+                tonic::Status::new(
+                    tonic::Code::Unknown,
+                    format!("Service was not ready: {}", e.into()),
+                )
+            })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/jaeger.storage.v1.SpanReaderPlugin/GetServices",
+            );
+            self.inner.unary(request.into_request(), path, codec).await
+        }
+        pub async fn get_operations(
+            &mut self,
+            request: impl tonic::IntoRequest<super::GetOperationsRequest>,
+        ) -> Result<tonic::Response<super::GetOperationsResponse>, tonic::Status> {
+            self.inner.ready().await.map_err(|e| {
+                tonic::Status::new(
+                    tonic::Code::Unknown,
+                    format!("Service was not ready: {}", e.into()),
+                )
+            })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/jaeger.storage.v1.SpanReaderPlugin/GetOperations",
+            );
+            self.inner.unary(request.into_request(), path, codec).await
+        }
+        pub async fn find_traces(
+            &mut self,
+            request: impl tonic::IntoRequest<super::FindTracesRequest>,
+        ) -> Result<
+            tonic::Response<tonic::codec::Streaming<super::SpansResponseChunk>>,
+            tonic::Status,
+        > {
+            self.inner.ready().await.map_err(|e| {
+                tonic::Status::new(
+                    tonic::Code::Unknown,
+                    format!("Service was not ready: {}", e.into()),
+                )
+            })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/jaeger.storage.v1.SpanReaderPlugin/FindTraces",
+            );
+            self.inner
+                .server_streaming(request.into_request(), path, codec)
+                .await
+        }
+        pub async fn find_trace_i_ds(
+            &mut self,
+            request: impl tonic::IntoRequest<super::FindTraceIDsRequest>,
+        ) -> Result<tonic::Response<super::FindTraceIDsResponse>, tonic::Status> {
+            self.inner.ready().await.map_err(|e| {
+                tonic::Status::new(
+                    tonic::Code::Unknown,
+                    format!("Service was not ready: {}", e.into()),
+                )
+            })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/jaeger.storage.v1.SpanReaderPlugin/FindTraceIDs",
+            );
+            self.inner.unary(request.into_request(), path, codec).await
+        }
+    }
+}
+#[doc = r" Generated server implementations."]
+pub mod span_reader_plugin_server {
+    #![allow(unused_variables, dead_code, missing_docs, clippy::let_unit_value)]
+    use tonic::codegen::*;
+    #[doc = "Generated trait containing gRPC methods that should be implemented for use with SpanReaderPluginServer."]
+    #[async_trait]
+    pub trait SpanReaderPlugin: Send + Sync + 'static {
+        #[doc = "Server streaming response type for the GetTrace method."]
+        type GetTraceStream: futures_core::Stream<Item = Result<super::SpansResponseChunk, tonic::Status>>
+            + Send
+            + Sync
+            + 'static;
+        #[doc = " spanstore/Reader"]
+        async fn get_trace(
+            &self,
+            request: tonic::Request<super::GetTraceRequest>,
+        ) -> Result<tonic::Response<Self::GetTraceStream>, tonic::Status>;
+        async fn get_services(
+            &self,
+            request: tonic::Request<super::GetServicesRequest>,
+        ) -> Result<tonic::Response<super::GetServicesResponse>, tonic::Status>;
+        async fn get_operations(
+            &self,
+            request: tonic::Request<super::GetOperationsRequest>,
+        ) -> Result<tonic::Response<super::GetOperationsResponse>, tonic::Status>;
+        #[doc = "Server streaming response type for the FindTraces method."]
+        type FindTracesStream: futures_core::Stream<Item = Result<super::SpansResponseChunk, tonic::Status>>
+            + Send
+            + Sync
+            + 'static;
+        async fn find_traces(
+            &self,
+            request: tonic::Request<super::FindTracesRequest>,
+        ) -> Result<tonic::Response<Self::FindTracesStream>, tonic::Status>;
+        async fn find_trace_i_ds(
+            &self,
+            request: tonic::Request<super::FindTraceIDsRequest>,
+        ) -> Result<tonic::Response<super::FindTraceIDsResponse>, tonic::Status>;
+    }
+    #[derive(Debug)]
+    pub struct SpanReaderPluginServer<T: SpanReaderPlugin> {
+        inner: _Inner<T>,
+        accept_compression_encodings: (),
+        send_compression_encodings: (),
+    }
+    struct _Inner<T>(Arc<T>);
+    impl<T: SpanReaderPlugin> SpanReaderPluginServer<T> {
+        pub fn new(inner: T) -> Self {
+            let inner = Arc::new(inner);
+            let inner = _Inner(inner);
+            Self {
+                inner,
+                accept_compression_encodings: Default::default(),
+                send_compression_encodings: Default::default(),
+            }
+        }
+        pub fn with_interceptor<F>(inner: T, interceptor: F) -> InterceptedService<Self, F>
+        where
+            F: tonic::service::Interceptor,
+        {
+            InterceptedService::new(Self::new(inner), interceptor)
+        }
+    }
+    impl<T, B> tonic::codegen::Service<http::Request<B>> for SpanReaderPluginServer<T>
+    where
+        T: SpanReaderPlugin,
+        B: Body + Send + Sync + 'static,
+        B::Error: Into<StdError> + Send + 'static,
+    {
+        type Response = http::Response<tonic::body::BoxBody>;
+        type Error = Never;
+        type Future = BoxFuture<Self::Response, Self::Error>;
+        fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+            Poll::Ready(Ok(()))
+        }
+        fn call(&mut self, req: http::Request<B>) -> Self::Future {
+            let inner = self.inner.clone();
+            match req.uri().path() {
+                "/jaeger.storage.v1.SpanReaderPlugin/GetTrace" => {
+                    #[allow(non_camel_case_types)]
+                    struct GetTraceSvc<T: SpanReaderPlugin>(pub Arc<T>);
+                    impl<T: SpanReaderPlugin>
+                        tonic::server::ServerStreamingService<super::GetTraceRequest>
+                        for GetTraceSvc<T>
+                    {
+                        type Response = super::SpansResponseChunk;
+                        type ResponseStream = T::GetTraceStream;
+                        type Future =
+                            BoxFuture<tonic::Response<Self::ResponseStream>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::GetTraceRequest>,
+                        ) -> Self::Future {
+                            let inner = self.0.clone();
+                            let fut = async move { (*inner).get_trace(request).await };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = GetTraceSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec).apply_compression_config(
+                            accept_compression_encodings,
+                            send_compression_encodings,
+                        );
+                        let res = grpc.server_streaming(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/jaeger.storage.v1.SpanReaderPlugin/GetServices" => {
+                    #[allow(non_camel_case_types)]
+                    struct GetServicesSvc<T: SpanReaderPlugin>(pub Arc<T>);
+                    impl<T: SpanReaderPlugin> tonic::server::UnaryService<super::GetServicesRequest>
+                        for GetServicesSvc<T>
+                    {
+                        type Response = super::GetServicesResponse;
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::GetServicesRequest>,
+                        ) -> Self::Future {
+                            let inner = self.0.clone();
+                            let fut = async move { (*inner).get_services(request).await };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = GetServicesSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec).apply_compression_config(
+                            accept_compression_encodings,
+                            send_compression_encodings,
+                        );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/jaeger.storage.v1.SpanReaderPlugin/GetOperations" => {
+                    #[allow(non_camel_case_types)]
+                    struct GetOperationsSvc<T: SpanReaderPlugin>(pub Arc<T>);
+                    impl<T: SpanReaderPlugin>
+                        tonic::server::UnaryService<super::GetOperationsRequest>
+                        for GetOperationsSvc<T>
+                    {
+                        type Response = super::GetOperationsResponse;
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::GetOperationsRequest>,
+                        ) -> Self::Future {
+                            let inner = self.0.clone();
+                            let fut = async move { (*inner).get_operations(request).await };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = GetOperationsSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec).apply_compression_config(
+                            accept_compression_encodings,
+                            send_compression_encodings,
+                        );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/jaeger.storage.v1.SpanReaderPlugin/FindTraces" => {
+                    #[allow(non_camel_case_types)]
+                    struct FindTracesSvc<T: SpanReaderPlugin>(pub Arc<T>);
+                    impl<T: SpanReaderPlugin>
+                        tonic::server::ServerStreamingService<super::FindTracesRequest>
+                        for FindTracesSvc<T>
+                    {
+                        type Response = super::SpansResponseChunk;
+                        type ResponseStream = T::FindTracesStream;
+                        type Future =
+                            BoxFuture<tonic::Response<Self::ResponseStream>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::FindTracesRequest>,
+                        ) -> Self::Future {
+                            let inner = self.0.clone();
+                            let fut = async move { (*inner).find_traces(request).await };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = FindTracesSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec).apply_compression_config(
+                            accept_compression_encodings,
+                            send_compression_encodings,
+                        );
+                        let res = grpc.server_streaming(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/jaeger.storage.v1.SpanReaderPlugin/FindTraceIDs" => {
+                    #[allow(non_camel_case_types)]
+                    struct FindTraceIDsSvc<T: SpanReaderPlugin>(pub Arc<T>);
+                    impl<T: SpanReaderPlugin>
+                        tonic::server::UnaryService<super::FindTraceIDsRequest>
+                        for FindTraceIDsSvc<T>
+                    {
+                        type Response = super::FindTraceIDsResponse;
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::FindTraceIDsRequest>,
+                        ) -> Self::Future {
+                            let inner = self.0.clone();
+                            let fut = async move { (*inner).find_trace_i_ds(request).await };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = FindTraceIDsSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec).apply_compression_config(
+                            accept_compression_encodings,
+                            send_compression_encodings,
+                        );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                _ => Box::pin(async move {
+                    Ok(http::Response::builder()
+                        .status(200)
+                        .header("grpc-status", "12")
+                        .header("content-type", "application/grpc")
+                        .body(empty_body())
+                        .unwrap())
+                }),
+            }
+        }
+    }
+    impl<T: SpanReaderPlugin> Clone for SpanReaderPluginServer<T> {
+        fn clone(&self) -> Self {
+            let inner = self.inner.clone();
+            Self {
+                inner,
+                accept_compression_encodings: self.accept_compression_encodings,
+                send_compression_encodings: self.send_compression_encodings,
+            }
+        }
+    }
+    impl<T: SpanReaderPlugin> Clone for _Inner<T> {
+        fn clone(&self) -> Self {
+            Self(self.0.clone())
+        }
+    }
+    impl<T: std::fmt::Debug> std::fmt::Debug for _Inner<T> {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            write!(f, "{:?}", self.0)
+        }
+    }
+    impl<T: SpanReaderPlugin> tonic::transport::NamedService for SpanReaderPluginServer<T> {
+        const NAME: &'static str = "jaeger.storage.v1.SpanReaderPlugin";
+    }
+}
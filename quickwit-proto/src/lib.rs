@@ -18,6 +18,9 @@
 // along with this program. If not, see <http://www.gnu.org/licenses/>.
 
 mod cluster;
+pub mod error;
+pub mod jaeger;
+pub mod opentelemetry;
 mod quickwit;
 
 #[macro_use]
@@ -37,6 +40,8 @@ impl From<SearchStreamRequest> for SearchRequest {
             max_hits: 0,
             start_offset: 0,
             tags: item.tags,
+            allow_partial_results: false,
+            timeout_millis: None,
         }
     }
 }
@@ -0,0 +1,56 @@
+// Copyright (C) 2021 Quickwit, Inc.
+//
+// Quickwit is offered under the AGPL v3.0 and as commercial software.
+// For commercial licensing, contact us at hello@quickwit.io.
+//
+// AGPL:
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+//! Messages and services of the OpenTelemetry protocol (OTLP), generated from the protos of
+//! `proto/opentelemetry`.
+
+pub mod proto {
+    pub mod common {
+        pub mod v1 {
+            include!("opentelemetry/opentelemetry.proto.common.v1.rs");
+        }
+    }
+    pub mod resource {
+        pub mod v1 {
+            include!("opentelemetry/opentelemetry.proto.resource.v1.rs");
+        }
+    }
+    pub mod logs {
+        pub mod v1 {
+            include!("opentelemetry/opentelemetry.proto.logs.v1.rs");
+        }
+    }
+    pub mod trace {
+        pub mod v1 {
+            include!("opentelemetry/opentelemetry.proto.trace.v1.rs");
+        }
+    }
+    pub mod collector {
+        pub mod logs {
+            pub mod v1 {
+                include!("opentelemetry/opentelemetry.proto.collector.logs.v1.rs");
+            }
+        }
+        pub mod trace {
+            pub mod v1 {
+                include!("opentelemetry/opentelemetry.proto.collector.trace.v1.rs");
+            }
+        }
+    }
+}
@@ -31,6 +31,14 @@ pub struct SearchRequest {
     /// Split tag filter
     #[prost(string, repeated, tag = "8")]
     pub tags: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+    /// If set, splits that could not be searched are reported in the response
+    /// instead of failing the whole request.
+    #[prost(bool, tag = "9")]
+    pub allow_partial_results: bool,
+    /// Deadline of the requests sent to the leaf nodes, in milliseconds. It is capped
+    /// by the leaf request timeout of the root node, which applies if not set.
+    #[prost(uint64, optional, tag = "10")]
+    pub timeout_millis: ::core::option::Option<u64>,
 }
 #[derive(Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -49,6 +57,10 @@ pub struct SearchResponse {
     /// The searcherrors that occured formatted as string.
     #[prost(string, repeated, tag = "4")]
     pub errors: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+    /// Splits that could not be searched. This is only populated
+    /// when the request allows partial results.
+    #[prost(message, repeated, tag = "5")]
+    pub failed_splits: ::prost::alloc::vec::Vec<SplitSearchError>,
 }
 #[derive(Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -83,6 +95,9 @@ pub struct LeafSearchRequest {
     /// split files.
     #[prost(string, tag = "6")]
     pub index_uri: ::prost::alloc::string::String,
+    /// ID of the key the split files of the index are encrypted with, if any.
+    #[prost(string, optional, tag = "7")]
+    pub encryption_key_id: ::core::option::Option<::prost::alloc::string::String>,
 }
 #[derive(Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -98,6 +113,11 @@ pub struct SplitIdAndFooterOffsets {
     /// The offset of the end of the footer in split bundle. The footer contains the file bundle metada and the hotcache.
     #[prost(uint64, tag = "3")]
     pub split_footer_end: u64,
+    /// Time range covered by the documents of the split, if known. Both bounds are inclusive.
+    #[prost(int64, optional, tag = "4")]
+    pub timestamp_start: ::core::option::Option<i64>,
+    #[prost(int64, optional, tag = "5")]
+    pub timestamp_end: ::core::option::Option<i64>,
 }
 #[derive(Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -177,6 +197,9 @@ pub struct FetchDocsRequest {
     /// split files.
     #[prost(string, tag = "4")]
     pub index_uri: ::prost::alloc::string::String,
+    /// ID of the key the split files of the index are encrypted with, if any.
+    #[prost(string, optional, tag = "5")]
+    pub encryption_key_id: ::core::option::Option<::prost::alloc::string::String>,
 }
 #[derive(Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -204,15 +227,24 @@ pub struct SearchStreamRequest {
     pub start_timestamp: ::core::option::Option<i64>,
     #[prost(int64, optional, tag = "5")]
     pub end_timestamp: ::core::option::Option<i64>,
-    /// Name of the fast field to extract
-    #[prost(string, tag = "6")]
-    pub fast_field: ::prost::alloc::string::String,
+    /// Names of the fast fields to extract, one column per fast field
+    #[prost(string, repeated, tag = "6")]
+    pub fast_fields: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
     /// The output format
     #[prost(enumeration = "OutputFormat", tag = "7")]
     pub output_format: i32,
     /// Split tag filter
     #[prost(string, repeated, tag = "8")]
     pub tags: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+    /// If set, the CSV output starts with a header row listing the fast fields
+    #[prost(bool, tag = "9")]
+    pub csv_header: bool,
+    /// Order of the streamed values, based on the index timestamp field
+    #[prost(enumeration = "SortOrder", tag = "10")]
+    pub sort_order: i32,
+    /// If set, the stream stops after this number of values
+    #[prost(uint64, optional, tag = "11")]
+    pub limit: ::core::option::Option<u64>,
 }
 #[derive(Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -233,6 +265,9 @@ pub struct LeafSearchStreamRequest {
     /// split files.
     #[prost(string, tag = "6")]
     pub index_uri: ::prost::alloc::string::String,
+    /// ID of the key the split files of the index are encrypted with, if any.
+    #[prost(string, optional, tag = "7")]
+    pub encryption_key_id: ::core::option::Option<::prost::alloc::string::String>,
 }
 #[derive(Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -244,6 +279,9 @@ pub struct LeafSearchStreamResult {
     /// Split id.
     #[prost(string, tag = "2")]
     pub split_id: ::prost::alloc::string::String,
+    /// Timestamps of the rows, in the same order, if the stream is sorted.
+    #[prost(int64, repeated, tag = "3")]
+    pub sort_values: ::prost::alloc::vec::Vec<i64>,
 }
 // -- Stream -------------------
 
@@ -260,6 +298,24 @@ pub enum OutputFormat {
     //// Format data by row in ClickHouse binary format.
     //// https://clickhouse.tech/docs/en/interfaces/formats/#rowbinary
     ClickHouseRowBinary = 1,
+    //// Apache Arrow IPC streaming format.
+    //// https://arrow.apache.org/docs/format/Columnar.html#ipc-streaming-format
+    ArrowIpcStream = 2,
+    //// Apache Parquet file.
+    //// https://parquet.apache.org/documentation/latest/
+    Parquet = 3,
+}
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+#[repr(i32)]
+pub enum SortOrder {
+    //// Values are streamed in no particular order.
+    Unsorted = 0,
+    //// Values are streamed by increasing timestamp.
+    Asc = 1,
+    //// Values are streamed by decreasing timestamp.
+    Desc = 2,
 }
 #[doc = r" Generated client implementations."]
 pub mod search_service_client {
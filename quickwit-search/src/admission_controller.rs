@@ -0,0 +1,298 @@
+// Copyright (C) 2021 Quickwit, Inc.
+//
+// Quickwit is offered under the AGPL v3.0 and as commercial software.
+// For commercial licensing, contact us at hello@quickwit.io.
+//
+// AGPL:
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use tokio::sync::{OwnedSemaphorePermit, Semaphore, TryAcquireError};
+
+use crate::metrics::SEARCH_METRICS;
+use crate::SearchError;
+
+/// Memory budgets are accounted in KiB so that they fit in the `u32` permits of a semaphore.
+const MEMORY_UNIT_NUM_BYTES: usize = 1_024;
+
+/// Limits of one lane of the admission controller.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LaneConfig {
+    /// Maximum number of requests running concurrently.
+    pub max_concurrent_requests: usize,
+    /// Maximum number of requests waiting for one of the running requests to complete.
+    /// Requests beyond that are rejected.
+    pub max_queued_requests: usize,
+    /// Maximum amount of memory used by the running requests.
+    pub memory_budget_in_bytes: usize,
+}
+
+/// Configuration of the admission controller.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AdmissionControllerConfig {
+    /// Limits of the interactive leaf search requests.
+    pub search: LaneConfig,
+    /// Limits of the leaf search stream requests.
+    pub stream: LaneConfig,
+}
+
+impl Default for AdmissionControllerConfig {
+    fn default() -> Self {
+        AdmissionControllerConfig {
+            search: LaneConfig {
+                max_concurrent_requests: 20,
+                max_queued_requests: 100,
+                memory_budget_in_bytes: 1_000_000_000,
+            },
+            stream: LaneConfig {
+                max_concurrent_requests: 2,
+                max_queued_requests: 10,
+                memory_budget_in_bytes: 500_000_000,
+            },
+        }
+    }
+}
+
+/// Kind of leaf request, each kind has its own lane so that long running exports cannot
+/// starve interactive searches.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RequestKind {
+    /// Leaf search requests.
+    Search,
+    /// Leaf search stream requests.
+    Stream,
+}
+
+struct Lane {
+    name: &'static str,
+    max_queued_requests: usize,
+    num_queued_requests: AtomicUsize,
+    concurrency_semaphore: Arc<Semaphore>,
+    memory_budget_in_units: u32,
+    memory_semaphore: Arc<Semaphore>,
+}
+
+impl Lane {
+    fn new(name: &'static str, config: LaneConfig) -> Self {
+        let memory_budget_in_units = (config.memory_budget_in_bytes / MEMORY_UNIT_NUM_BYTES)
+            .clamp(1, u32::MAX as usize) as u32;
+        Lane {
+            name,
+            max_queued_requests: config.max_queued_requests,
+            num_queued_requests: AtomicUsize::new(0),
+            concurrency_semaphore: Arc::new(Semaphore::new(config.max_concurrent_requests.max(1))),
+            memory_budget_in_units,
+            memory_semaphore: Arc::new(Semaphore::new(memory_budget_in_units as usize)),
+        }
+    }
+}
+
+/// Decrements the number of queued requests of a lane when dropped, so that requests
+/// cancelled while waiting leave the queue.
+struct QueueSlot<'a>(&'a AtomicUsize);
+
+impl<'a> Drop for QueueSlot<'a> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Bounds the number of leaf requests a searcher runs concurrently and the memory they use.
+///
+/// Requests wait in the queue of their lane until there is room for them. When the queue is
+/// full, they are rejected right away with [`SearchError::Overloaded`] so that the root can
+/// send them to another searcher.
+pub struct AdmissionController {
+    search_lane: Lane,
+    stream_lane: Lane,
+}
+
+/// Room reserved for a request by the admission controller, released when dropped.
+pub struct AdmissionPermit {
+    _concurrency_permit: OwnedSemaphorePermit,
+    _memory_permit: OwnedSemaphorePermit,
+}
+
+impl AdmissionController {
+    /// Creates an admission controller with the given limits.
+    pub fn new(config: AdmissionControllerConfig) -> Self {
+        AdmissionController {
+            search_lane: Lane::new("search", config.search),
+            stream_lane: Lane::new("stream", config.stream),
+        }
+    }
+
+    fn lane(&self, request_kind: RequestKind) -> &Lane {
+        match request_kind {
+            RequestKind::Search => &self.search_lane,
+            RequestKind::Stream => &self.stream_lane,
+        }
+    }
+
+    /// Waits until the request can run, or rejects it if too many requests are already
+    /// waiting.
+    ///
+    /// Requests estimated to use more memory than the whole budget are admitted once they
+    /// run alone.
+    pub async fn admit(
+        &self,
+        request_kind: RequestKind,
+        estimated_memory_in_bytes: usize,
+    ) -> crate::Result<AdmissionPermit> {
+        let lane = self.lane(request_kind);
+        let concurrency_permit = match lane.concurrency_semaphore.clone().try_acquire_owned() {
+            Ok(concurrency_permit) => concurrency_permit,
+            Err(TryAcquireError::NoPermits) => {
+                let num_queued_requests = lane.num_queued_requests.fetch_add(1, Ordering::SeqCst);
+                let _queue_slot = QueueSlot(&lane.num_queued_requests);
+                if num_queued_requests >= lane.max_queued_requests {
+                    SEARCH_METRICS.leaf_rejected_requests_total.inc();
+                    return Err(SearchError::Overloaded(format!(
+                        "Too many {} requests are waiting ({}).",
+                        lane.name, num_queued_requests
+                    )));
+                }
+                lane.concurrency_semaphore
+                    .clone()
+                    .acquire_owned()
+                    .await
+                    .map_err(|_| admission_controller_closed())?
+            }
+            Err(TryAcquireError::Closed) => return Err(admission_controller_closed()),
+        };
+        let memory_in_units = (estimated_memory_in_bytes / MEMORY_UNIT_NUM_BYTES + 1)
+            .min(lane.memory_budget_in_units as usize) as u32;
+        let memory_permit = lane
+            .memory_semaphore
+            .clone()
+            .acquire_many_owned(memory_in_units)
+            .await
+            .map_err(|_| admission_controller_closed())?;
+        Ok(AdmissionPermit {
+            _concurrency_permit: concurrency_permit,
+            _memory_permit: memory_permit,
+        })
+    }
+}
+
+fn admission_controller_closed() -> SearchError {
+    SearchError::InternalError("Admission controller closed.".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    fn test_config() -> AdmissionControllerConfig {
+        AdmissionControllerConfig {
+            search: LaneConfig {
+                max_concurrent_requests: 1,
+                max_queued_requests: 1,
+                memory_budget_in_bytes: 10 * MEMORY_UNIT_NUM_BYTES,
+            },
+            stream: LaneConfig {
+                max_concurrent_requests: 1,
+                max_queued_requests: 0,
+                memory_budget_in_bytes: 10 * MEMORY_UNIT_NUM_BYTES,
+            },
+        }
+    }
+
+    #[tokio::test]
+    async fn test_admission_controller_rejects_when_queue_is_full() -> anyhow::Result<()> {
+        let admission_controller = Arc::new(AdmissionController::new(test_config()));
+        let permit = admission_controller.admit(RequestKind::Search, 0).await?;
+        let admission_controller_clone = admission_controller.clone();
+        let queued_request = tokio::spawn(async move {
+            admission_controller_clone
+                .admit(RequestKind::Search, 0)
+                .await
+                .map(|_| ())
+        });
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(matches!(
+            admission_controller.admit(RequestKind::Search, 0).await,
+            Err(SearchError::Overloaded(_))
+        ));
+        drop(permit);
+        queued_request.await??;
+        // The queue drained, new requests are admitted again.
+        admission_controller.admit(RequestKind::Search, 0).await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_admission_controller_lanes_are_independent() -> anyhow::Result<()> {
+        let admission_controller = AdmissionController::new(test_config());
+        let _stream_permit = admission_controller.admit(RequestKind::Stream, 0).await?;
+        assert!(matches!(
+            admission_controller.admit(RequestKind::Stream, 0).await,
+            Err(SearchError::Overloaded(_))
+        ));
+        admission_controller.admit(RequestKind::Search, 0).await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_admission_controller_memory_budget() -> anyhow::Result<()> {
+        let mut config = test_config();
+        config.search.max_concurrent_requests = 10;
+        let admission_controller = Arc::new(AdmissionController::new(config));
+        let permit = admission_controller
+            .admit(RequestKind::Search, 8 * MEMORY_UNIT_NUM_BYTES)
+            .await?;
+        let admission_controller_clone = admission_controller.clone();
+        let mut queued_request = tokio::spawn(async move {
+            admission_controller_clone
+                .admit(RequestKind::Search, 4 * MEMORY_UNIT_NUM_BYTES)
+                .await
+                .map(|_| ())
+        });
+        assert!(
+            tokio::time::timeout(Duration::from_millis(50), &mut queued_request)
+                .await
+                .is_err()
+        );
+        drop(permit);
+        queued_request.await??;
+        // Requests larger than the budget still run on their own.
+        admission_controller
+            .admit(RequestKind::Search, 100 * MEMORY_UNIT_NUM_BYTES)
+            .await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_admission_controller_cancelled_request_leaves_queue() -> anyhow::Result<()> {
+        let admission_controller = AdmissionController::new(test_config());
+        let _permit = admission_controller.admit(RequestKind::Search, 0).await?;
+        assert!(tokio::time::timeout(
+            Duration::from_millis(10),
+            admission_controller.admit(RequestKind::Search, 0)
+        )
+        .await
+        .is_err());
+        assert!(tokio::time::timeout(
+            Duration::from_millis(10),
+            admission_controller.admit(RequestKind::Search, 0)
+        )
+        .await
+        .is_err());
+        Ok(())
+    }
+}
@@ -20,19 +20,21 @@
 use std::fmt;
 use std::net::SocketAddr;
 use std::sync::Arc;
+use std::time::Duration;
 
 use futures::{StreamExt, TryStreamExt};
 use http::Uri;
 use opentelemetry::global;
 use opentelemetry::propagation::Injector;
 use quickwit_proto::LeafSearchStreamResult;
-use tokio_stream::wrappers::UnboundedReceiverStream;
-use tonic::transport::{Channel, Endpoint};
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::transport::{Channel, ClientTlsConfig, Endpoint};
 use tonic::Request;
 use tracing::*;
 use tracing_opentelemetry::OpenTelemetrySpanExt;
 
 use crate::error::parse_grpc_error;
+use crate::search_stream::SEARCH_STREAM_BUFFER_CAPACITY;
 use crate::{SearchError, SearchService};
 
 struct MetadataMap<'a>(&'a mut tonic::metadata::MetadataMap);
@@ -122,34 +124,51 @@ impl SearchServiceClient {
     }
 
     /// Perform leaf search.
+    ///
+    /// The request fails with a [`SearchError::Timeout`] if it does not complete within
+    /// `timeout`. The deadline is propagated over gRPC so that the leaf node stops searching
+    /// once it is exceeded.
     pub async fn leaf_search(
         &mut self,
         request: quickwit_proto::LeafSearchRequest,
+        timeout: Duration,
     ) -> Result<quickwit_proto::LeafSearchResponse, SearchError> {
-        match &mut self.client_impl {
-            SearchServiceClientImpl::Grpc(grpc_client) => {
-                let mut tonic_request = Request::new(request);
-                global::get_text_map_propagator(|propagator| {
-                    propagator.inject_context(
-                        &tracing::Span::current().context(),
-                        &mut MetadataMap(tonic_request.metadata_mut()),
-                    )
-                });
-                let tonic_response = grpc_client
-                    .leaf_search(tonic_request)
-                    .await
-                    .map_err(|tonic_error| parse_grpc_error(&tonic_error))?;
-                Ok(tonic_response.into_inner())
+        let grpc_addr = self.grpc_addr;
+        let leaf_search_future = async {
+            match &mut self.client_impl {
+                SearchServiceClientImpl::Grpc(grpc_client) => {
+                    let mut tonic_request = Request::new(request);
+                    tonic_request.set_timeout(timeout);
+                    global::get_text_map_propagator(|propagator| {
+                        propagator.inject_context(
+                            &tracing::Span::current().context(),
+                            &mut MetadataMap(tonic_request.metadata_mut()),
+                        )
+                    });
+                    grpc_client
+                        .leaf_search(tonic_request)
+                        .await
+                        .map(|tonic_response| tonic_response.into_inner())
+                        .map_err(|tonic_error| parse_grpc_error(&tonic_error))
+                }
+                SearchServiceClientImpl::Local(service) => service.leaf_search(request).await,
             }
-            SearchServiceClientImpl::Local(service) => service.leaf_search(request).await,
-        }
+        };
+        tokio::time::timeout(timeout, leaf_search_future)
+            .await
+            .map_err(|_| {
+                SearchError::Timeout(format!(
+                    "Leaf search on `{}` did not complete within {:?}.",
+                    grpc_addr, timeout
+                ))
+            })?
     }
 
     /// Perform leaf stream.
     pub async fn leaf_search_stream(
         &mut self,
         request: quickwit_proto::LeafSearchStreamRequest,
-    ) -> UnboundedReceiverStream<crate::Result<LeafSearchStreamResult>> {
+    ) -> ReceiverStream<crate::Result<LeafSearchStreamResult>> {
         match &mut self.client_impl {
             SearchServiceClientImpl::Grpc(grpc_client) => {
                 let mut grpc_client_clone = grpc_client.clone();
@@ -164,84 +183,123 @@ impl SearchServiceClient {
                         &mut MetadataMap(tonic_request.metadata_mut()),
                     )
                 });
-                let (result_sender, result_receiver) = tokio::sync::mpsc::unbounded_channel();
+                let (result_sender, result_receiver) =
+                    tokio::sync::mpsc::channel(SEARCH_STREAM_BUFFER_CAPACITY);
                 tokio::spawn(
                     async move {
-                        let tonic_result = grpc_client_clone
-                            .leaf_search_stream(tonic_request)
-                            .await
-                            .map_err(|tonic_error| parse_grpc_error(&tonic_error));
-                        // If the grpc client fails, send the error in the channel and stop.
-                        if let Err(error) = tonic_result {
-                            // It is ok to ignore error sending error.
-                            let _ = result_sender.send(Err(error));
-                            return;
-                        }
-                        let mut results_stream = tonic_result
-                            .unwrap()
-                            .into_inner()
-                            .map_err(|tonic_error| parse_grpc_error(&tonic_error));
-                        while let Some(search_result) = results_stream.next().await {
-                            let send_result = result_sender.send(search_result);
-                            // If we get a sending error, stop consuming the stream.
-                            if send_result.is_err() {
-                                break;
+                        let closed_result_sender = result_sender.clone();
+                        let forward_results = async move {
+                            let tonic_result = grpc_client_clone
+                                .leaf_search_stream(tonic_request)
+                                .await
+                                .map_err(|tonic_error| parse_grpc_error(&tonic_error));
+                            // If the grpc client fails, send the error in the channel and stop.
+                            if let Err(error) = tonic_result {
+                                // It is ok to ignore error sending error.
+                                let _ = result_sender.send(Err(error)).await;
+                                return;
+                            }
+                            let mut results_stream = tonic_result
+                                .unwrap()
+                                .into_inner()
+                                .map_err(|tonic_error| parse_grpc_error(&tonic_error));
+                            while let Some(search_result) = results_stream.next().await {
+                                let send_result = result_sender.send(search_result).await;
+                                // If we get a sending error, stop consuming the stream.
+                                if send_result.is_err() {
+                                    break;
+                                }
+                            }
+                        };
+                        // Dropping the results stream cancels the grpc request, which in turn
+                        // cancels the search on the leaf.
+                        tokio::select! {
+                            _ = forward_results => {}
+                            _ = closed_result_sender.closed() => {
+                                debug!("Leaf search stream receiver dropped. Cancelling grpc request.");
                             }
                         }
                     }
                     .instrument(span),
                 );
-                UnboundedReceiverStream::new(result_receiver)
+                ReceiverStream::new(result_receiver)
             }
             SearchServiceClientImpl::Local(service) => {
                 let stream_result = service.leaf_search_stream(request).await;
                 stream_result.unwrap_or_else(|error| {
-                    let (result_sender, result_receiver) = tokio::sync::mpsc::unbounded_channel();
-                    // Receiver cannot be closed here, ignore error.
-                    let _ = result_sender.send(Err(error));
-                    UnboundedReceiverStream::new(result_receiver)
+                    let (result_sender, result_receiver) = tokio::sync::mpsc::channel(1);
+                    // Receiver cannot be closed nor full here, ignore error.
+                    let _ = result_sender.try_send(Err(error));
+                    ReceiverStream::new(result_receiver)
                 })
             }
         }
     }
 
     /// Perform fetch docs.
+    ///
+    /// Like [`Self::leaf_search`], the request fails with a [`SearchError::Timeout`] if it does
+    /// not complete within `timeout`.
     pub async fn fetch_docs(
         &mut self,
         request: quickwit_proto::FetchDocsRequest,
+        timeout: Duration,
     ) -> Result<quickwit_proto::FetchDocsResult, SearchError> {
-        match &mut self.client_impl {
-            SearchServiceClientImpl::Grpc(grpc_client) => {
-                let mut tonic_request = Request::new(request);
-                global::get_text_map_propagator(|propagator| {
-                    propagator.inject_context(
-                        &tracing::Span::current().context(),
-                        &mut MetadataMap(tonic_request.metadata_mut()),
-                    )
-                });
-                let tonic_response = grpc_client
-                    .fetch_docs(tonic_request)
-                    .await
-                    .map_err(|tonic_error| parse_grpc_error(&tonic_error))?;
-                Ok(tonic_response.into_inner())
+        let grpc_addr = self.grpc_addr;
+        let fetch_docs_future = async {
+            match &mut self.client_impl {
+                SearchServiceClientImpl::Grpc(grpc_client) => {
+                    let mut tonic_request = Request::new(request);
+                    tonic_request.set_timeout(timeout);
+                    global::get_text_map_propagator(|propagator| {
+                        propagator.inject_context(
+                            &tracing::Span::current().context(),
+                            &mut MetadataMap(tonic_request.metadata_mut()),
+                        )
+                    });
+                    grpc_client
+                        .fetch_docs(tonic_request)
+                        .await
+                        .map(|tonic_response| tonic_response.into_inner())
+                        .map_err(|tonic_error| parse_grpc_error(&tonic_error))
+                }
+                SearchServiceClientImpl::Local(service) => service.fetch_docs(request).await,
             }
-            SearchServiceClientImpl::Local(service) => service.fetch_docs(request).await,
-        }
+        };
+        tokio::time::timeout(timeout, fetch_docs_future)
+            .await
+            .map_err(|_| {
+                SearchError::Timeout(format!(
+                    "Fetch docs on `{}` did not complete within {:?}.",
+                    grpc_addr, timeout
+                ))
+            })?
     }
 }
 
 /// Create a SearchServiceClient with SocketAddr as an argument.
 /// It will try to reconnect to the node automatically.
+/// The connection goes over TLS if a TLS config is given.
 pub async fn create_search_service_client(
     grpc_addr: SocketAddr,
+    tls_config_opt: Option<ClientTlsConfig>,
 ) -> anyhow::Result<SearchServiceClient> {
+    let scheme = if tls_config_opt.is_some() {
+        "https"
+    } else {
+        "http"
+    };
     let uri = Uri::builder()
-        .scheme("http")
+        .scheme(scheme)
         .authority(grpc_addr.to_string().as_str())
         .path_and_query("/")
         .build()?;
+    let mut endpoint = Endpoint::from(uri);
+    if let Some(tls_config) = tls_config_opt {
+        endpoint = endpoint.tls_config(tls_config)?;
+    }
     // Create a channel with connect_lazy to automatically reconnect to the node.
-    let channel = Endpoint::from(uri).connect_lazy()?;
+    let channel = endpoint.connect_lazy()?;
     let client = SearchServiceClient::from_grpc_client(
         quickwit_proto::search_service_client::SearchServiceClient::new(channel),
         grpc_addr,
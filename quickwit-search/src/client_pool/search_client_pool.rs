@@ -24,16 +24,23 @@ use std::net::SocketAddr;
 use std::sync::Arc;
 
 use async_trait::async_trait;
-use quickwit_cluster::cluster::Cluster;
+use quickwit_cluster::cluster::{Cluster, Member, NodeRole};
 use tokio::sync::RwLock;
 use tokio_stream::StreamExt;
+use tonic::transport::ClientTlsConfig;
 use tracing::*;
 
 use crate::client::create_search_service_client;
 use crate::client_pool::{ClientPool, Job};
-use crate::rendezvous_hasher::{sort_by_rendez_vous_hash, Node};
+use crate::rendezvous_hasher::{pick_node_with_bounded_load, sort_by_rendez_vous_hash, Node};
 use crate::{swim_addr_to_grpc_addr, SearchServiceClient};
 
+/// Maximum load of a node when assigning jobs, relative to the average load of the nodes.
+///
+/// The lower the factor, the more evenly jobs are spread; the higher the factor, the more jobs
+/// stick to the node that has the split in cache.
+const MAX_LOAD_FACTOR: f64 = 1.25;
+
 /// Search client pool implementation.
 #[derive(Clone)]
 pub struct SearchClientPool {
@@ -41,6 +48,9 @@ pub struct SearchClientPool {
     /// A hash map with gRPC's SocketAddr as the key and SearchServiceClient as the value.
     /// It is not the cluster listen address.
     pub clients: Arc<RwLock<HashMap<SocketAddr, SearchServiceClient>>>,
+    /// Loads gossiped by the cluster members, i.e. the number of splits they are searching.
+    /// A hash map with gRPC's SocketAddr as the key and the load as the value.
+    pub loads: Arc<RwLock<HashMap<SocketAddr, u64>>>,
 }
 
 impl SearchClientPool {
@@ -58,19 +68,28 @@ impl SearchClientPool {
 
         Ok(SearchClientPool {
             clients: Arc::new(RwLock::new(mock_clients)),
+            loads: Default::default(),
         })
     }
 
     /// Create a search client pool given a cluster.
     /// When a client pool is created, the thread that monitors cluster members
     /// will be started at the same time.
-    pub async fn new(cluster: Arc<Cluster>) -> anyhow::Result<Self> {
+    ///
+    /// Only the members playing the searcher role are part of the pool.
+    /// The clients connect over TLS if a TLS config is given.
+    pub async fn new(
+        cluster: Arc<Cluster>,
+        tls_config_opt: Option<ClientTlsConfig>,
+    ) -> anyhow::Result<Self> {
         let mut clients = HashMap::new();
+        let members = searcher_members(cluster.members());
+        let loads = member_loads(&members);
 
         // Initialize the client pool with members of the cluster.
-        for member in cluster.members() {
+        for member in members {
             let grpc_addr = swim_addr_to_grpc_addr(member.listen_addr);
-            match create_search_service_client(grpc_addr).await {
+            match create_search_service_client(grpc_addr, tls_config_opt.clone()).await {
                 Ok(client) => {
                     debug!(grpc_addr=?grpc_addr, "Add a new client to connect to the members of the cluster.");
                     clients.insert(grpc_addr, client);
@@ -84,15 +103,19 @@ impl SearchClientPool {
         // Create search client pool.
         let client_pool = SearchClientPool {
             clients: Arc::new(RwLock::new(clients)),
+            loads: Arc::new(RwLock::new(loads)),
         };
 
         // Prepare to start a thread that will monitor cluster members.
         let thread_clients = Arc::clone(&client_pool.clients);
+        let thread_loads = Arc::clone(&client_pool.loads);
         let mut members_watch_channel = cluster.member_change_watcher();
 
         // Start to monitor the cluster members.
         tokio::spawn(async move {
             while let Some(members) = members_watch_channel.next().await {
+                let members = searcher_members(members);
+                *thread_loads.write().await = member_loads(&members);
                 let mut clients = thread_clients.write().await;
 
                 // Create a list of addresses to be removed.
@@ -118,7 +141,8 @@ impl SearchClientPool {
                 for member in members {
                     let grpc_addr = swim_addr_to_grpc_addr(member.listen_addr);
                     if let Entry::Vacant(_entry) = clients.entry(grpc_addr) {
-                        match create_search_service_client(grpc_addr).await {
+                        match create_search_service_client(grpc_addr, tls_config_opt.clone()).await
+                        {
                             Ok(client) => {
                                 debug!(grpc_addr=?grpc_addr, "Add a new client that is connecting to the node that has been joined the cluster.");
                                 clients.insert(grpc_addr, client);
@@ -136,6 +160,23 @@ impl SearchClientPool {
     }
 }
 
+fn searcher_members(members: Vec<Member>) -> Vec<Member> {
+    members
+        .into_iter()
+        .filter(|member| member.has_role(NodeRole::Searcher))
+        .collect()
+}
+
+fn member_loads(members: &[Member]) -> HashMap<SocketAddr, u64> {
+    members
+        .iter()
+        .map(|member| {
+            let grpc_addr = swim_addr_to_grpc_addr(member.listen_addr);
+            (grpc_addr, member.load().unwrap_or(0))
+        })
+        .collect()
+}
+
 #[async_trait]
 impl ClientPool for SearchClientPool {
     /// Assign the given job to the clients.
@@ -158,6 +199,16 @@ impl ClientPool for SearchClientPool {
 
             // TODO optimize the case where there are few jobs and many clients.
             let clients = self.clients.read().await;
+            let loads = self.loads.read().await;
+
+            // Loads are gossiped as a number of splits. We convert them into costs using the
+            // average cost of the jobs to assign.
+            let average_job_cost = if jobs.is_empty() {
+                1
+            } else {
+                let total_job_cost: u64 = jobs.iter().map(|job| job.cost as u64).sum();
+                (total_job_cost / jobs.len() as u64).max(1)
+            };
 
             let fallback = HashSet::default();
             // when exclude_addresses excludes all adresses we discard it
@@ -169,7 +220,8 @@ impl ClientPool for SearchClientPool {
                 .iter()
                 .filter(|(grpc_addr, _)| !exclude_addresses.contains(grpc_addr))
             {
-                let node = Node::new(*grpc_addr, 0);
+                let load = loads.get(grpc_addr).copied().unwrap_or(0) * average_job_cost;
+                let node = Node::new(*grpc_addr, load);
                 nodes.push(node);
                 socket_to_client.insert(*grpc_addr, client.clone());
             }
@@ -184,18 +236,23 @@ impl ClientPool for SearchClientPool {
             left.split_id.cmp(&right.split_id)
         });
 
+        // Bound the load of each node once all jobs are assigned. A node can always accept at
+        // least one job.
+        let total_load: u64 = nodes.iter().map(|node| node.load).sum::<u64>()
+            + jobs.iter().map(|job| job.cost as u64).sum::<u64>();
+        let max_job_cost = jobs.iter().map(|job| job.cost as u64).max().unwrap_or(0);
+        let max_load = if nodes.is_empty() {
+            0
+        } else {
+            let average_load = total_load as f64 / nodes.len() as f64;
+            ((MAX_LOAD_FACTOR * average_load) as u64).max(max_job_cost)
+        };
+
         for job in jobs {
             sort_by_rendez_vous_hash(&mut nodes, &job.split_id);
-            // choose one of the the first two nodes based on least loaded
-            let chosen_node_index: usize = if nodes.len() >= 2 {
-                if nodes[0].load > nodes[1].load {
-                    1
-                } else {
-                    0
-                }
-            } else {
-                0
-            };
+            // choose the first node in rendez-vous order that is not overloaded
+            let chosen_node_index = pick_node_with_bounded_load(&nodes, job.cost as u64, max_load)
+                .ok_or_else(|| anyhow::anyhow!("No search node available to assign jobs to."))?;
 
             // update node load for next round
             nodes[chosen_node_index].load += job.cost as u64;
@@ -229,16 +286,32 @@ mod tests {
     use std::time::Duration;
 
     use itertools::Itertools;
-    use quickwit_cluster::cluster::create_cluster_for_test;
+    use quickwit_cluster::cluster::{create_cluster_for_test, NodeRole};
 
     use crate::client_pool::search_client_pool::create_search_service_client;
     use crate::client_pool::{ClientPool, Job};
-    use crate::{swim_addr_to_grpc_addr, SearchClientPool};
+    use crate::{swim_addr_to_grpc_addr, MockSearchService, SearchClientPool, SearchService};
+
+    async fn mock_client_pool(num_nodes: usize) -> anyhow::Result<SearchClientPool> {
+        let mock_services = (0..num_nodes)
+            .map(|_| Arc::new(MockSearchService::new()) as Arc<dyn SearchService>)
+            .collect();
+        SearchClientPool::from_mocks(mock_services).await
+    }
+
+    fn unit_cost_jobs(num_jobs: usize) -> Vec<Job> {
+        (0..num_jobs)
+            .map(|split_ord| Job {
+                split_id: format!("split{}", split_ord),
+                cost: 1,
+            })
+            .collect()
+    }
 
     #[tokio::test]
     async fn test_search_client_pool_single_node() -> anyhow::Result<()> {
         let cluster = Arc::new(create_cluster_for_test()?);
-        let client_pool = Arc::new(SearchClientPool::new(cluster.clone()).await?);
+        let client_pool = Arc::new(SearchClientPool::new(cluster.clone(), None).await?);
         let clients = client_pool.clients.read().await;
 
         let addrs: Vec<SocketAddr> = clients.clone().into_keys().collect();
@@ -257,7 +330,7 @@ mod tests {
             .wait_for_members(|members| members.len() == 2, Duration::from_secs(5))
             .await?;
 
-        let client_pool = Arc::new(SearchClientPool::new(cluster1.clone()).await?);
+        let client_pool = Arc::new(SearchClientPool::new(cluster1.clone(), None).await?);
         let clients = client_pool.clients.read().await;
 
         let addrs: Vec<SocketAddr> = clients.clone().into_keys().sorted().collect();
@@ -270,10 +343,38 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_search_client_pool_only_includes_searchers() -> anyhow::Result<()> {
+        let cluster1 = Arc::new(create_cluster_for_test()?);
+        let cluster2 = Arc::new(create_cluster_for_test()?);
+        cluster2.set_roles(&[NodeRole::Indexer]);
+
+        cluster2.add_peer_node(cluster1.listen_addr).await;
+        let cluster2_addr = cluster2.listen_addr;
+        cluster1
+            .wait_for_members(
+                |members| {
+                    members.iter().any(|member| {
+                        member.listen_addr == cluster2_addr && member.has_role(NodeRole::Indexer)
+                    })
+                },
+                Duration::from_secs(5),
+            )
+            .await?;
+
+        let client_pool = Arc::new(SearchClientPool::new(cluster1.clone(), None).await?);
+        let clients = client_pool.clients.read().await;
+
+        let addrs: Vec<SocketAddr> = clients.clone().into_keys().collect();
+        let expected_addrs = vec![swim_addr_to_grpc_addr(cluster1.listen_addr)];
+        assert_eq!(addrs, expected_addrs);
+        Ok(())
+    }
+
     #[tokio::test]
     async fn test_search_client_pool_single_node_assign_jobs() -> anyhow::Result<()> {
         let cluster = Arc::new(create_cluster_for_test()?);
-        let client_pool = Arc::new(SearchClientPool::new(cluster.clone()).await?);
+        let client_pool = Arc::new(SearchClientPool::new(cluster.clone(), None).await?);
 
         let jobs = vec![
             Job {
@@ -296,7 +397,7 @@ mod tests {
 
         let assigned_jobs = client_pool.assign_jobs(jobs, &HashSet::default()).await?;
         let expected_assigned_jobs = vec![(
-            create_search_service_client(swim_addr_to_grpc_addr(cluster.listen_addr)).await?,
+            create_search_service_client(swim_addr_to_grpc_addr(cluster.listen_addr), None).await?,
             vec![
                 Job {
                     split_id: "split4".to_string(),
@@ -322,4 +423,63 @@ mod tests {
         );
         Ok(())
     }
+
+    #[tokio::test]
+    async fn test_search_client_pool_assign_jobs_bounds_load() -> anyhow::Result<()> {
+        let client_pool = mock_client_pool(3).await?;
+        let assigned_jobs = client_pool
+            .assign_jobs(unit_cost_jobs(30), &HashSet::default())
+            .await?;
+        assert_eq!(assigned_jobs.len(), 3);
+        // Each node is assigned at most 1.25 times the average number of jobs.
+        for (_client, jobs) in &assigned_jobs {
+            assert!(jobs.len() <= 12);
+        }
+        let num_assigned_jobs: usize = assigned_jobs.iter().map(|(_, jobs)| jobs.len()).sum();
+        assert_eq!(num_assigned_jobs, 30);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_search_client_pool_assign_jobs_accounts_for_gossiped_load() -> anyhow::Result<()>
+    {
+        let client_pool = mock_client_pool(3).await?;
+        let busy_grpc_addr: SocketAddr = "127.0.0.1:10000".parse()?;
+        client_pool.loads.write().await.insert(busy_grpc_addr, 100);
+        let assigned_jobs = client_pool
+            .assign_jobs(unit_cost_jobs(30), &HashSet::default())
+            .await?;
+        assert_eq!(assigned_jobs.len(), 2);
+        assert!(assigned_jobs
+            .iter()
+            .all(|(client, _jobs)| client.grpc_addr() != busy_grpc_addr));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_search_client_pool_assign_jobs_is_stable() -> anyhow::Result<()> {
+        let client_pool = mock_client_pool(3).await?;
+        let assignment = |assigned_jobs: Vec<(crate::SearchServiceClient, Vec<Job>)>| {
+            assigned_jobs
+                .into_iter()
+                .flat_map(|(client, jobs)| {
+                    let grpc_addr = client.grpc_addr();
+                    jobs.into_iter().map(move |job| (job.split_id, grpc_addr))
+                })
+                .sorted()
+                .collect_vec()
+        };
+        let first_assignment = assignment(
+            client_pool
+                .assign_jobs(unit_cost_jobs(30), &HashSet::default())
+                .await?,
+        );
+        let second_assignment = assignment(
+            client_pool
+                .assign_jobs(unit_cost_jobs(30), &HashSet::default())
+                .await?,
+        );
+        assert_eq!(first_assignment, second_assignment);
+        Ok(())
+    }
 }
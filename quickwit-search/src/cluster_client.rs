@@ -18,6 +18,7 @@
 // along with this program. If not, see <http://www.gnu.org/licenses/>.
 
 use std::sync::Arc;
+use std::time::Duration;
 
 use futures::StreamExt;
 use quickwit_proto::{
@@ -25,27 +26,62 @@ use quickwit_proto::{
     LeafSearchStreamRequest, LeafSearchStreamResult,
 };
 use tokio::sync::mpsc::error::SendError;
-use tokio::sync::mpsc::{unbounded_channel, UnboundedSender};
-use tokio_stream::wrappers::UnboundedReceiverStream;
+use tokio::sync::mpsc::{channel, Sender};
+use tokio::time::Instant;
+use tokio_stream::wrappers::ReceiverStream;
 use tracing::debug;
 
+use crate::latency_tracker::LatencyTracker;
+use crate::metrics::SEARCH_METRICS;
 use crate::retry::search::LeafSearchRetryPolicy;
 use crate::retry::search_stream::{LeafSearchStreamRetryPolicy, SuccessfullSplitIds};
 use crate::retry::{retry_client, DefaultRetryPolicy, RetryPolicy};
+use crate::search_stream::{is_sorted_or_limited, SEARCH_STREAM_BUFFER_CAPACITY};
 use crate::{SearchClientPool, SearchError, SearchServiceClient};
 
+/// Default deadline of leaf search and fetch docs requests, retries and hedged requests
+/// included. Search requests may set a shorter one.
+const DEFAULT_LEAF_REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Percentile of the per-split leaf search latencies above which a leaf search request is
+/// hedged. The threshold of a request is this latency times its number of splits.
+const HEDGING_LATENCY_PERCENTILE: f64 = 95.0;
+
+/// Minimum delay before a leaf search request is hedged. This prevents sending a flurry of
+/// hedged requests when leaf searches are very fast.
+const MIN_HEDGING_DELAY: Duration = Duration::from_millis(50);
+
 /// Client that executes placed requests (Request, `SearchServiceClient`) and provides
 /// retry policies for `FetchDocsRequest`, `LeafSearchRequest` and `LeafSearchStreamRequest`
 /// to retry on other `SearchServiceClient`.
+///
+/// Leaf search requests that take longer than most of the previous ones are hedged: the same
+/// request is sent to another node, the first response wins and the other request is cancelled.
+///
+/// Cancelling a request drops it on the leaf node, which only stops at its next await point:
+/// a split being searched runs to completion.
 #[derive(Clone)]
 pub struct ClusterClient {
     client_pool: Arc<SearchClientPool>,
+    leaf_request_timeout: Duration,
+    leaf_search_latencies: Arc<LatencyTracker>,
 }
 
 impl ClusterClient {
     /// Instanciate ClusterClient.
     pub fn new(client_pool: Arc<SearchClientPool>) -> Self {
-        Self { client_pool }
+        Self {
+            client_pool,
+            leaf_request_timeout: DEFAULT_LEAF_REQUEST_TIMEOUT,
+            leaf_search_latencies: Arc::new(LatencyTracker::default()),
+        }
+    }
+
+    /// Sets the deadline of leaf search and fetch docs requests. The deadline of a leaf search
+    /// request is shortened if its search request sets a lower `timeout_millis`.
+    pub fn with_leaf_request_timeout(mut self, leaf_request_timeout: Duration) -> Self {
+        self.leaf_request_timeout = leaf_request_timeout;
+        self
     }
 
     /// Fetch docs with retry on another node client.
@@ -54,84 +90,245 @@ impl ClusterClient {
         placed_request: (FetchDocsRequest, SearchServiceClient),
     ) -> Result<FetchDocsResult, SearchError> {
         let (request, mut client) = placed_request;
-        let mut result = client.fetch_docs(request.clone()).await;
+        let deadline = Instant::now() + self.leaf_request_timeout;
+        let mut result = client
+            .fetch_docs(request.clone(), time_until(deadline))
+            .await;
         let retry_policy = DefaultRetryPolicy {};
         if let Some(retry_request) = retry_policy.retry_request(&request, result.as_ref()) {
+            if Instant::now() >= deadline {
+                return result;
+            }
             client = retry_client(&self.client_pool, &client, &retry_request).await?;
             debug!(
                 "Fetch docs response error: `{:?}`. Retry once to execute {:?} with {:?}",
                 result, retry_request, client
             );
-            result = client.fetch_docs(retry_request).await;
+            result = client.fetch_docs(retry_request, time_until(deadline)).await;
         }
         result
     }
 
-    /// Leaf search with retry on another node client.
+    /// Leaf search with hedging and retry on another node client.
     pub async fn leaf_search(
         &self,
         placed_request: (LeafSearchRequest, SearchServiceClient),
     ) -> Result<LeafSearchResponse, SearchError> {
-        let (request, mut client) = placed_request;
-        let mut result = client.leaf_search(request.clone()).await;
+        let (request, client) = placed_request;
+        let deadline = Instant::now() + self.leaf_search_timeout(&request);
+        let (mut result, mut client) = self
+            .hedged_leaf_search(request.clone(), client, deadline)
+            .await;
         let retry_policy = LeafSearchRetryPolicy {};
         if let Some(retry_request) = retry_policy.retry_request(&request, result.as_ref()) {
+            if Instant::now() >= deadline {
+                return result;
+            }
             client = retry_client(&self.client_pool, &client, &retry_request).await?;
             debug!(
                 "Leaf search response error: `{:?}`. Retry once to execute {:?} with {:?}",
                 result, retry_request, client
             );
-            let retry_result = client.leaf_search(retry_request).await;
+            let retry_result = client
+                .leaf_search(retry_request, time_until(deadline))
+                .await;
             result = merge_leaf_search_results(result, retry_result);
         }
         result
     }
 
+    fn leaf_search_timeout(&self, request: &LeafSearchRequest) -> Duration {
+        request
+            .search_request
+            .as_ref()
+            .and_then(|search_request| search_request.timeout_millis)
+            .map(|timeout_millis| {
+                Duration::from_millis(timeout_millis).min(self.leaf_request_timeout)
+            })
+            .unwrap_or(self.leaf_request_timeout)
+    }
+
+    // Returns the delay after which a leaf search request on `num_splits` splits is hedged,
+    // or `None` if too few leaf searches have completed to tell.
+    fn hedging_delay(&self, num_splits: usize) -> Option<Duration> {
+        let split_latency = self
+            .leaf_search_latencies
+            .percentile(HEDGING_LATENCY_PERCENTILE)?;
+        let hedging_delay = split_latency.saturating_mul(num_splits.max(1) as u32);
+        Some(hedging_delay.max(MIN_HEDGING_DELAY))
+    }
+
+    // Executes a leaf search request. If the request does not complete within the usual leaf
+    // search latency for its number of splits, the same request is sent to another node and the
+    // first successful response is returned along with the client that produced it. The losing
+    // request is dropped, which cancels it on its node at its next await point.
+    async fn hedged_leaf_search(
+        &self,
+        request: LeafSearchRequest,
+        client: SearchServiceClient,
+        deadline: Instant,
+    ) -> (Result<LeafSearchResponse, SearchError>, SearchServiceClient) {
+        let start = Instant::now();
+        let num_splits = request.split_metadata.len();
+        let hedging_delay_opt = self.hedging_delay(num_splits);
+
+        let mut primary_client = client.clone();
+        let primary_future = primary_client.leaf_search(request.clone(), time_until(deadline));
+        tokio::pin!(primary_future);
+
+        let hedging_delay = match hedging_delay_opt {
+            Some(hedging_delay) if start + hedging_delay < deadline => hedging_delay,
+            _ => {
+                let result = primary_future.await;
+                self.record_leaf_search_latency(start, num_splits, &result);
+                return (result, client);
+            }
+        };
+        tokio::select! {
+            result = &mut primary_future => {
+                self.record_leaf_search_latency(start, num_splits, &result);
+                return (result, client);
+            }
+            _ = tokio::time::sleep(hedging_delay) => {}
+        }
+
+        let hedge_client = match retry_client(&self.client_pool, &client, &request).await {
+            Ok(hedge_client) if hedge_client.grpc_addr() != client.grpc_addr() => hedge_client,
+            _ => {
+                // There is no other node to send the request to.
+                let result = primary_future.await;
+                self.record_leaf_search_latency(start, num_splits, &result);
+                return (result, client);
+            }
+        };
+        debug!(
+            "Leaf search on {:?} did not complete within {:?}. Hedge request with {:?}",
+            client, hedging_delay, hedge_client
+        );
+        SEARCH_METRICS.leaf_hedged_requests_total.inc();
+        let mut hedge_client_clone = hedge_client.clone();
+        let hedge_future = hedge_client_clone.leaf_search(request, time_until(deadline));
+        tokio::pin!(hedge_future);
+
+        let (result, client) = tokio::select! {
+            result = &mut primary_future => {
+                if result.is_ok() {
+                    (result, client)
+                } else {
+                    (hedge_future.await, hedge_client)
+                }
+            }
+            result = &mut hedge_future => {
+                if result.is_ok() {
+                    (result, hedge_client)
+                } else {
+                    (primary_future.await, client)
+                }
+            }
+        };
+        self.record_leaf_search_latency(start, num_splits, &result);
+        (result, client)
+    }
+
+    // Records the latency per split, so that requests on few and many splits can be compared.
+    fn record_leaf_search_latency(
+        &self,
+        start: Instant,
+        num_splits: usize,
+        result: &Result<LeafSearchResponse, SearchError>,
+    ) {
+        if result.is_ok() {
+            let split_latency = start.elapsed() / num_splits.max(1) as u32;
+            self.leaf_search_latencies.record(split_latency);
+        }
+    }
+
     /// Leaf search stream with retry on another node client.
     pub async fn leaf_search_stream(
         &self,
         placed_request: (LeafSearchStreamRequest, SearchServiceClient),
-    ) -> UnboundedReceiverStream<Result<LeafSearchStreamResult, SearchError>> {
+    ) -> ReceiverStream<Result<LeafSearchStreamResult, SearchError>> {
         let (request, mut client) = placed_request;
         // We need a dedicated channel to send results with retry. First we send only the successful
         // responses and and ignore errors. If there are some errors, we make one retry and
         // in this case we send all results.
-        let (result_sender, result_receiver) = unbounded_channel();
+        let (result_sender, result_receiver) = channel(SEARCH_STREAM_BUFFER_CAPACITY);
         let client_pool = self.client_pool.clone();
         let retry_policy = LeafSearchStreamRetryPolicy {};
         tokio::spawn(async move {
-            let result_stream = client.leaf_search_stream(request.clone()).await;
-            // Forward only responses and not errors to the sender as we will make one retry on
-            // errors.
-            let forward_result =
-                forward_leaf_search_stream(result_stream, result_sender.clone(), false).await;
-            if let Some(retry_request) =
-                retry_policy.retry_request(&request, forward_result.as_ref())
-            {
-                let retry_client_opt = retry_client(&client_pool, &client, &retry_request).await;
-                // Propagates the error if we cannot get a new client and stops the task.
-                if let Err(error) = retry_client_opt {
-                    let _ = result_sender.send(Err(SearchError::from(error)));
-                    return;
+            let closed_result_sender = result_sender.clone();
+            let forward_results = async move {
+                let result_stream = client.leaf_search_stream(request.clone()).await;
+                let is_sorted_or_limited = request
+                    .request
+                    .as_ref()
+                    .map(is_sorted_or_limited)
+                    .unwrap_or(false);
+                let retry_request_opt = if is_sorted_or_limited {
+                    // Rows of sorted or limited streams are merged across splits and the leaf
+                    // may skip splits once the limit is reached: once results have been
+                    // forwarded, they cannot be completed by a retry on the missing splits.
+                    // A stream failing before forwarding anything, e.g. because its node is
+                    // overloaded, is retried as a whole.
+                    match forward_sorted_leaf_search_stream(result_stream, result_sender.clone())
+                        .await
+                    {
+                        Ok(Some(error)) => {
+                            debug!(error=?error, "Leaf search stream failed before sending any result.");
+                            Some(request.clone())
+                        }
+                        _ => None,
+                    }
+                } else {
+                    // Forward only responses and not errors to the sender as we will make one
+                    // retry on errors.
+                    let forward_result =
+                        forward_leaf_search_stream(result_stream, result_sender.clone(), false)
+                            .await;
+                    retry_policy.retry_request(&request, forward_result.as_ref())
+                };
+                if let Some(retry_request) = retry_request_opt {
+                    let retry_client_opt =
+                        retry_client(&client_pool, &client, &retry_request).await;
+                    // Propagates the error if we cannot get a new client and stops the task.
+                    if let Err(error) = retry_client_opt {
+                        let _ = result_sender.send(Err(SearchError::from(error))).await;
+                        return;
+                    }
+                    let mut retry_client = retry_client_opt.unwrap();
+                    debug!(
+                        "Leaf search stream response error. Retry once to execute {:?} with {:?}",
+                        retry_request, client
+                    );
+                    let retry_results_stream = retry_client.leaf_search_stream(retry_request).await;
+                    // Forward all results to the result_sender as we won't do another retry.
+                    // It is ok to ignore send errors, there is nothing else to do.
+                    let _ = forward_leaf_search_stream(
+                        retry_results_stream,
+                        result_sender.clone(),
+                        true,
+                    )
+                    .await;
+                }
+            };
+            // Stop forwarding, and drop the leaf streams, as soon as the receiver is dropped.
+            tokio::select! {
+                _ = forward_results => {}
+                _ = closed_result_sender.closed() => {
+                    debug!("Leaf search stream receiver dropped. Cancelling leaf search stream.");
                 }
-                let mut retry_client = retry_client_opt.unwrap();
-                debug!(
-                    "Leaf search stream response error. Retry once to execute {:?} with {:?}",
-                    retry_request, client
-                );
-                let retry_results_stream = retry_client.leaf_search_stream(retry_request).await;
-                // Forward all results to the result_sender as we won't do another retry.
-                // It is ok to ignore send errors, there is nothing else to do.
-                let _ =
-                    forward_leaf_search_stream(retry_results_stream, result_sender.clone(), true)
-                        .await;
             }
         });
 
-        UnboundedReceiverStream::new(result_receiver)
+        ReceiverStream::new(result_receiver)
     }
 }
 
+// Returns the time left until the deadline.
+fn time_until(deadline: Instant) -> Duration {
+    deadline.saturating_duration_since(Instant::now())
+}
+
 // Merge initial leaf search results with results obtained from a retry.
 fn merge_leaf_search_results(
     initial_response_result: Result<LeafSearchResponse, SearchError>,
@@ -157,13 +354,37 @@ fn merge_leaf_search_results(
     }
 }
 
+// Forward the results of a sorted or limited leaf search stream into a sender.
+// Returns the error that stopped the stream if it came before any result, so that
+// the whole request can be retried. Later errors are forwarded.
+async fn forward_sorted_leaf_search_stream(
+    mut stream: ReceiverStream<Result<LeafSearchStreamResult, SearchError>>,
+    sender: Sender<Result<LeafSearchStreamResult, SearchError>>,
+) -> Result<Option<SearchError>, SendError<Result<LeafSearchStreamResult, SearchError>>> {
+    let mut has_forwarded_results = false;
+    while let Some(result) = stream.next().await {
+        match result {
+            Ok(response) => {
+                has_forwarded_results = true;
+                sender.send(Ok(response)).await?;
+            }
+            Err(error) if !has_forwarded_results => return Ok(Some(error)),
+            Err(error) => {
+                sender.send(Err(error)).await?;
+                return Ok(None);
+            }
+        }
+    }
+    Ok(None)
+}
+
 // Forward leaf search stream results into a sender and
 // returns the split ids of Ok(response).
 // If `send_error` is false, errors are ignored and not forwarded. This is
 // useful if you want to make a retry before propagating errors.
 async fn forward_leaf_search_stream(
-    mut stream: UnboundedReceiverStream<Result<LeafSearchStreamResult, SearchError>>,
-    sender: UnboundedSender<Result<LeafSearchStreamResult, SearchError>>,
+    mut stream: ReceiverStream<Result<LeafSearchStreamResult, SearchError>>,
+    sender: Sender<Result<LeafSearchStreamResult, SearchError>>,
     send_error: bool,
 ) -> Result<SuccessfullSplitIds, SendError<Result<LeafSearchStreamResult, SearchError>>> {
     let mut successful_split_ids: Vec<String> = Vec::new();
@@ -171,11 +392,11 @@ async fn forward_leaf_search_stream(
         match result {
             Ok(response) => {
                 successful_split_ids.push(response.split_id.clone());
-                sender.send(Ok(response))?;
+                sender.send(Ok(response)).await?;
             }
             Err(error) => {
                 if send_error {
-                    sender.send(Err(error))?;
+                    sender.send(Err(error)).await?;
                 }
             }
         }
@@ -188,18 +409,65 @@ mod tests {
     use std::collections::HashSet;
     use std::net::SocketAddr;
     use std::sync::Arc;
+    use std::time::{Duration, Instant};
 
+    use async_trait::async_trait;
+    use bytes::Bytes;
     use futures::StreamExt;
     use quickwit_proto::{
-        FetchDocsRequest, LeafSearchRequest, LeafSearchResponse, LeafSearchStreamRequest,
-        LeafSearchStreamResult, PartialHit, SearchRequest, SearchStreamRequest,
-        SplitIdAndFooterOffsets, SplitSearchError,
+        FetchDocsRequest, FetchDocsResult, LeafSearchRequest, LeafSearchResponse,
+        LeafSearchStreamRequest, LeafSearchStreamResult, PartialHit, SearchRequest, SearchResponse,
+        SearchStreamRequest, SortOrder, SplitIdAndFooterOffsets, SplitSearchError,
     };
-    use tokio_stream::wrappers::UnboundedReceiverStream;
+    use tokio_stream::wrappers::ReceiverStream;
 
     use crate::client_pool::Job;
-    use crate::cluster_client::{merge_leaf_search_results, ClusterClient};
-    use crate::{ClientPool, MockSearchService, SearchClientPool, SearchError};
+    use crate::cluster_client::{merge_leaf_search_results, ClusterClient, MIN_HEDGING_DELAY};
+    use crate::{ClientPool, MockSearchService, SearchClientPool, SearchError, SearchService};
+
+    /// Search service answering leaf search requests after a delay.
+    struct SlowSearchService {
+        delay: Duration,
+        num_hits: u64,
+    }
+
+    #[async_trait]
+    impl SearchService for SlowSearchService {
+        async fn root_search(&self, _request: SearchRequest) -> crate::Result<SearchResponse> {
+            unimplemented!()
+        }
+
+        async fn leaf_search(
+            &self,
+            _request: LeafSearchRequest,
+        ) -> crate::Result<LeafSearchResponse> {
+            tokio::time::sleep(self.delay).await;
+            Ok(LeafSearchResponse {
+                num_hits: self.num_hits,
+                partial_hits: vec![],
+                failed_splits: vec![],
+                num_attempted_splits: 1,
+            })
+        }
+
+        async fn fetch_docs(&self, _request: FetchDocsRequest) -> crate::Result<FetchDocsResult> {
+            unimplemented!()
+        }
+
+        async fn root_search_stream(
+            &self,
+            _request: SearchStreamRequest,
+        ) -> crate::Result<ReceiverStream<crate::Result<Bytes>>> {
+            unimplemented!()
+        }
+
+        async fn leaf_search_stream(
+            &self,
+            _request: LeafSearchStreamRequest,
+        ) -> crate::Result<ReceiverStream<crate::Result<LeafSearchStreamResult>>> {
+            unimplemented!()
+        }
+    }
 
     fn mock_partial_hit(split_id: &str, sorting_field_value: u64, doc_id: u32) -> PartialHit {
         PartialHit {
@@ -215,10 +483,13 @@ mod tests {
             partial_hits: vec![],
             index_id: "id".to_string(),
             index_uri: "uri".to_string(),
+            encryption_key_id: None,
             split_metadata: vec![SplitIdAndFooterOffsets {
                 split_id: split_id.to_string(),
                 split_footer_end: 100,
                 split_footer_start: 0,
+                timestamp_start: None,
+                timestamp_end: None,
             }],
         }
     }
@@ -233,21 +504,28 @@ mod tests {
             max_hits: 10,
             start_offset: 0,
             tags: vec![],
+            allow_partial_results: false,
+            timeout_millis: None,
         };
         LeafSearchRequest {
             search_request: Some(search_request),
             index_config: "config".to_string(),
             index_uri: "uri".to_string(),
+            encryption_key_id: None,
             split_metadata: vec![
                 SplitIdAndFooterOffsets {
                     split_id: "split_1".to_string(),
                     split_footer_start: 0,
                     split_footer_end: 100,
+                    timestamp_start: None,
+                    timestamp_end: None,
                 },
                 SplitIdAndFooterOffsets {
                     split_id: "split_2".to_string(),
                     split_footer_start: 0,
                     split_footer_end: 100,
+                    timestamp_start: None,
+                    timestamp_end: None,
                 },
             ],
         }
@@ -260,24 +538,32 @@ mod tests {
             search_fields: vec!["body".to_string()],
             start_timestamp: None,
             end_timestamp: None,
-            fast_field: "fast".to_string(),
+            fast_fields: vec!["fast".to_string()],
             output_format: 0,
             tags: vec![],
+            csv_header: false,
+            sort_order: 0,
+            limit: None,
         };
         LeafSearchStreamRequest {
             request: Some(search_request),
             index_config: "config".to_string(),
             index_uri: "uri".to_string(),
+            encryption_key_id: None,
             split_metadata: vec![
                 SplitIdAndFooterOffsets {
                     split_id: "split_1".to_string(),
                     split_footer_start: 0,
                     split_footer_end: 100,
+                    timestamp_start: None,
+                    timestamp_end: None,
                 },
                 SplitIdAndFooterOffsets {
                     split_id: "split_2".to_string(),
                     split_footer_start: 0,
                     split_footer_end: 100,
+                    timestamp_start: None,
+                    timestamp_end: None,
                 },
             ],
         }
@@ -444,6 +730,41 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_cluster_client_leaf_search_retry_on_overloaded_node() -> anyhow::Result<()> {
+        let request = mock_leaf_search_request();
+        let mut mock_service_1 = MockSearchService::new();
+        mock_service_1
+            .expect_leaf_search()
+            .return_once(|_: LeafSearchRequest| {
+                Err(SearchError::Overloaded("too many requests".to_string()))
+            });
+        let mut mock_service_2 = MockSearchService::new();
+        mock_service_2
+            .expect_leaf_search()
+            .return_once(|_: LeafSearchRequest| {
+                Ok(LeafSearchResponse {
+                    num_hits: 2,
+                    partial_hits: vec![],
+                    failed_splits: vec![],
+                    num_attempted_splits: 2,
+                })
+            });
+        let client_pool = Arc::new(
+            SearchClientPool::from_mocks(vec![Arc::new(mock_service_1), Arc::new(mock_service_2)])
+                .await?,
+        );
+        let client_hashmap = client_pool.clients.read().await;
+        let first_grpc_addr: SocketAddr = "127.0.0.1:10000".parse()?;
+        let first_client = client_hashmap.get(&first_grpc_addr).unwrap();
+        let cluster_client = ClusterClient::new(client_pool.clone());
+        let result = cluster_client
+            .leaf_search((request, first_client.clone()))
+            .await;
+        assert_eq!(result.unwrap().num_hits, 2);
+        Ok(())
+    }
+
     #[test]
     fn test_merge_leaf_search_retry_on_partial_success() -> anyhow::Result<()> {
         let split_error = SplitSearchError {
@@ -516,21 +837,26 @@ mod tests {
             .expect_leaf_search_stream()
             .return_once(|_| Err(SearchError::InternalError("error".to_string())));
         let mut mock_service_2 = MockSearchService::new();
-        let (result_sender, result_receiver) = tokio::sync::mpsc::unbounded_channel();
+        let (result_sender, result_receiver) = tokio::sync::mpsc::channel(10);
         mock_service_2
             .expect_leaf_search_stream()
-            .return_once(|_| Ok(UnboundedReceiverStream::new(result_receiver)));
+            .return_once(|_| Ok(ReceiverStream::new(result_receiver)));
         let client_pool = Arc::new(
             SearchClientPool::from_mocks(vec![Arc::new(mock_service_1), Arc::new(mock_service_2)])
                 .await?,
         );
-        result_sender.send(Ok(LeafSearchStreamResult {
-            data: Vec::new(),
-            split_id: "split_1".to_string(),
-        }))?;
-        result_sender.send(Err(SearchError::InternalError(
-            "last split error".to_string(),
-        )))?;
+        result_sender
+            .send(Ok(LeafSearchStreamResult {
+                data: Vec::new(),
+                split_id: "split_1".to_string(),
+                sort_values: Vec::new(),
+            }))
+            .await?;
+        result_sender
+            .send(Err(SearchError::InternalError(
+                "last split error".to_string(),
+            )))
+            .await?;
         drop(result_sender);
         let client_hashmap = client_pool.clients.read().await;
         let first_grpc_addr: SocketAddr = "127.0.0.1:10000".parse()?;
@@ -544,4 +870,225 @@ mod tests {
         assert!(results[0].is_ok());
         Ok(())
     }
+
+    fn mock_sorted_leaf_search_stream_request() -> LeafSearchStreamRequest {
+        let mut request = mock_leaf_search_stream_request();
+        if let Some(search_request) = request.request.as_mut() {
+            search_request.sort_order = SortOrder::Desc as i32;
+            search_request.limit = Some(10);
+        }
+        request
+    }
+
+    #[tokio::test]
+    async fn test_cluster_client_sorted_leaf_stream_retry_on_overloaded_node() -> anyhow::Result<()>
+    {
+        let request = mock_sorted_leaf_search_stream_request();
+        let mut mock_service_1 = MockSearchService::new();
+        mock_service_1
+            .expect_leaf_search_stream()
+            .return_once(|_| Err(SearchError::Overloaded("too many requests".to_string())));
+        let mut mock_service_2 = MockSearchService::new();
+        let (result_sender, result_receiver) = tokio::sync::mpsc::channel(10);
+        mock_service_2
+            .expect_leaf_search_stream()
+            .withf(|request| request.split_metadata.len() == 2)
+            .return_once(|_| Ok(ReceiverStream::new(result_receiver)));
+        let client_pool = Arc::new(
+            SearchClientPool::from_mocks(vec![Arc::new(mock_service_1), Arc::new(mock_service_2)])
+                .await?,
+        );
+        for split_id in ["split_1", "split_2"] {
+            result_sender
+                .send(Ok(LeafSearchStreamResult {
+                    data: Vec::new(),
+                    split_id: split_id.to_string(),
+                    sort_values: Vec::new(),
+                }))
+                .await?;
+        }
+        drop(result_sender);
+        let client_hashmap = client_pool.clients.read().await;
+        let first_grpc_addr: SocketAddr = "127.0.0.1:10000".parse()?;
+        let first_client = client_hashmap.get(&first_grpc_addr).unwrap();
+        let cluster_client = ClusterClient::new(client_pool.clone());
+        let results: Vec<_> = cluster_client
+            .leaf_search_stream((request, first_client.clone()))
+            .await
+            .collect()
+            .await;
+        assert_eq!(results.len(), 2);
+        assert!(results.iter().all(|result| result.is_ok()));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_cluster_client_sorted_leaf_stream_no_retry_once_results_forwarded(
+    ) -> anyhow::Result<()> {
+        let request = mock_sorted_leaf_search_stream_request();
+        let mut mock_service_1 = MockSearchService::new();
+        let (result_sender, result_receiver) = tokio::sync::mpsc::channel(10);
+        mock_service_1
+            .expect_leaf_search_stream()
+            .return_once(|_| Ok(ReceiverStream::new(result_receiver)));
+        let mut mock_service_2 = MockSearchService::new();
+        mock_service_2.expect_leaf_search_stream().times(0);
+        let client_pool = Arc::new(
+            SearchClientPool::from_mocks(vec![Arc::new(mock_service_1), Arc::new(mock_service_2)])
+                .await?,
+        );
+        result_sender
+            .send(Ok(LeafSearchStreamResult {
+                data: Vec::new(),
+                split_id: "split_1".to_string(),
+                sort_values: Vec::new(),
+            }))
+            .await?;
+        result_sender
+            .send(Err(SearchError::InternalError(
+                "last split error".to_string(),
+            )))
+            .await?;
+        drop(result_sender);
+        let client_hashmap = client_pool.clients.read().await;
+        let first_grpc_addr: SocketAddr = "127.0.0.1:10000".parse()?;
+        let first_client = client_hashmap.get(&first_grpc_addr).unwrap();
+        let cluster_client = ClusterClient::new(client_pool.clone());
+        let results: Vec<_> = cluster_client
+            .leaf_search_stream((request, first_client.clone()))
+            .await
+            .collect()
+            .await;
+        assert_eq!(results.len(), 2);
+        assert!(results[0].is_ok());
+        assert!(matches!(results[1], Err(SearchError::InternalError(_))));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_cluster_client_leaf_stream_cancelled_on_drop() -> anyhow::Result<()> {
+        let request = mock_leaf_search_stream_request();
+        let mut mock_service = MockSearchService::new();
+        let (result_sender, result_receiver) = tokio::sync::mpsc::channel(10);
+        mock_service
+            .expect_leaf_search_stream()
+            .return_once(|_| Ok(ReceiverStream::new(result_receiver)));
+        let client_pool =
+            Arc::new(SearchClientPool::from_mocks(vec![Arc::new(mock_service)]).await?);
+        result_sender
+            .send(Ok(LeafSearchStreamResult {
+                data: Vec::new(),
+                split_id: "split_1".to_string(),
+                sort_values: Vec::new(),
+            }))
+            .await?;
+        let client_hashmap = client_pool.clients.read().await;
+        let first_grpc_addr: SocketAddr = "127.0.0.1:10000".parse()?;
+        let first_client = client_hashmap.get(&first_grpc_addr).unwrap();
+        let cluster_client = ClusterClient::new(client_pool.clone());
+        let mut result_stream = cluster_client
+            .leaf_search_stream((request, first_client.clone()))
+            .await;
+        assert!(result_stream.next().await.unwrap().is_ok());
+        drop(result_stream);
+        // The leaf stream is dropped as soon as nobody listens to the results anymore.
+        tokio::time::timeout(Duration::from_secs(1), result_sender.closed()).await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_cluster_client_leaf_search_timeout() -> anyhow::Result<()> {
+        let request = mock_leaf_search_request();
+        let slow_service = SlowSearchService {
+            delay: Duration::from_secs(10),
+            num_hits: 1,
+        };
+        let client_pool =
+            Arc::new(SearchClientPool::from_mocks(vec![Arc::new(slow_service)]).await?);
+        let client_hashmap = client_pool.clients.read().await;
+        let first_grpc_addr: SocketAddr = "127.0.0.1:10000".parse()?;
+        let first_client = client_hashmap.get(&first_grpc_addr).unwrap();
+        let cluster_client = ClusterClient::new(client_pool.clone())
+            .with_leaf_request_timeout(Duration::from_millis(100));
+        let result = cluster_client
+            .leaf_search((request, first_client.clone()))
+            .await;
+        assert!(matches!(result, Err(SearchError::Timeout(_))));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_cluster_client_leaf_search_hedges_slow_node() -> anyhow::Result<()> {
+        let request = mock_leaf_search_request();
+        let slow_service = SlowSearchService {
+            delay: Duration::from_secs(10),
+            num_hits: 1,
+        };
+        let fast_service = SlowSearchService {
+            delay: Duration::from_millis(10),
+            num_hits: 2,
+        };
+        let client_pool = Arc::new(
+            SearchClientPool::from_mocks(vec![Arc::new(slow_service), Arc::new(fast_service)])
+                .await?,
+        );
+        let client_hashmap = client_pool.clients.read().await;
+        let first_grpc_addr: SocketAddr = "127.0.0.1:10000".parse()?;
+        let first_client = client_hashmap.get(&first_grpc_addr).unwrap();
+        let cluster_client = ClusterClient::new(client_pool.clone());
+        // Leaf searches usually take 5ms per split.
+        for _ in 0..100 {
+            cluster_client
+                .leaf_search_latencies
+                .record(Duration::from_millis(5));
+        }
+        let start = Instant::now();
+        let leaf_search_response = cluster_client
+            .leaf_search((request, first_client.clone()))
+            .await?;
+        assert_eq!(leaf_search_response.num_hits, 2);
+        assert!(start.elapsed() < Duration::from_secs(5));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_cluster_client_hedging_delay_scales_with_num_splits() -> anyhow::Result<()> {
+        let client_pool = Arc::new(SearchClientPool::from_mocks(Vec::new()).await?);
+        let cluster_client = ClusterClient::new(client_pool);
+        assert_eq!(cluster_client.hedging_delay(1), None);
+        for _ in 0..100 {
+            cluster_client
+                .leaf_search_latencies
+                .record(Duration::from_millis(20));
+        }
+        assert_eq!(cluster_client.hedging_delay(1), Some(MIN_HEDGING_DELAY));
+        assert_eq!(
+            cluster_client.hedging_delay(10),
+            Some(Duration::from_millis(200))
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_cluster_client_leaf_search_timeout_from_request() -> anyhow::Result<()> {
+        let mut request = mock_leaf_search_request();
+        request.search_request.as_mut().unwrap().timeout_millis = Some(100);
+        let slow_service = SlowSearchService {
+            delay: Duration::from_secs(10),
+            num_hits: 1,
+        };
+        let client_pool =
+            Arc::new(SearchClientPool::from_mocks(vec![Arc::new(slow_service)]).await?);
+        let client_hashmap = client_pool.clients.read().await;
+        let first_grpc_addr: SocketAddr = "127.0.0.1:10000".parse()?;
+        let first_client = client_hashmap.get(&first_grpc_addr).unwrap();
+        let cluster_client = ClusterClient::new(client_pool.clone());
+        let start = Instant::now();
+        let result = cluster_client
+            .leaf_search((request, first_client.clone()))
+            .await;
+        assert!(matches!(result, Err(SearchError::Timeout(_))));
+        assert!(start.elapsed() < Duration::from_secs(5));
+        Ok(())
+    }
 }
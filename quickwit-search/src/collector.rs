@@ -26,7 +26,8 @@ use quickwit_proto::{LeafSearchResponse, PartialHit, SearchRequest};
 use tantivy::collector::{Collector, SegmentCollector};
 use tantivy::fastfield::{DynamicFastFieldReader, FastFieldReader};
 use tantivy::schema::{Field, Schema};
-use tantivy::{DocId, Score, SegmentOrdinal, SegmentReader};
+use tantivy::{DocId, Score, SegmentOrdinal, SegmentReader, TantivyError};
+use tokio_util::sync::CancellationToken;
 
 use crate::filters::TimestampFilter;
 use crate::partial_hit_sorting_key;
@@ -229,6 +230,9 @@ pub struct QuickwitCollector {
     pub timestamp_field_opt: Option<Field>,
     pub start_timestamp_opt: Option<i64>,
     pub end_timestamp_opt: Option<i64>,
+    /// Checked before collecting each segment, so that the search of a split
+    /// stops early once nobody is waiting for its result anymore.
+    pub cancellation_token: CancellationToken,
 }
 
 impl GenericQuickwitCollector for QuickwitCollector {
@@ -246,6 +250,12 @@ impl Collector for QuickwitCollector {
         segment_ord: SegmentOrdinal,
         segment_reader: &SegmentReader,
     ) -> tantivy::Result<Self::Child> {
+        if self.cancellation_token.is_cancelled() {
+            return Err(TantivyError::InvalidArgument(format!(
+                "Search of split {} was cancelled.",
+                self.split_id
+            )));
+        }
         let sort_by = resolve_sort_by(&self.sort_by, segment_reader)?;
         // Regardless of the start_offset, we need to collect top-K
         // starting from 0 for every leaves.
@@ -365,6 +375,7 @@ pub fn make_collector_for_split(
     index_config: &dyn IndexConfig,
     search_request: &SearchRequest,
     split_schema: &Schema,
+    cancellation_token: CancellationToken,
 ) -> QuickwitCollector {
     QuickwitCollector {
         split_id,
@@ -375,6 +386,7 @@ pub fn make_collector_for_split(
         timestamp_field_opt: index_config.timestamp_field(split_schema),
         start_timestamp_opt: search_request.start_timestamp,
         end_timestamp_opt: search_request.end_timestamp,
+        cancellation_token,
     }
 }
 
@@ -392,16 +404,23 @@ pub fn make_merge_collector(search_request: &SearchRequest) -> QuickwitCollector
         timestamp_field_opt: None,
         start_timestamp_opt: search_request.start_timestamp,
         end_timestamp_opt: search_request.end_timestamp,
+        cancellation_token: CancellationToken::new(),
     }
 }
 
 #[cfg(test)]
 mod tests {
     use std::cmp::Ordering;
+    use std::collections::HashSet;
 
+    use quickwit_index_config::SortBy;
     use quickwit_proto::PartialHit;
+    use tantivy::query::AllQuery;
+    use tantivy::schema::{Schema, TEXT};
+    use tantivy::{doc, Index};
+    use tokio_util::sync::CancellationToken;
 
-    use super::PartialHitHeapItem;
+    use super::{PartialHitHeapItem, QuickwitCollector};
     use crate::collector::top_k_partial_hits;
 
     #[test]
@@ -451,4 +470,34 @@ mod tests {
             vec![make_hit_given_split_id(1), make_hit_given_split_id(2)]
         );
     }
+
+    #[test]
+    fn test_quickwit_collector_stops_once_cancelled() -> tantivy::Result<()> {
+        let mut schema_builder = Schema::builder();
+        let body = schema_builder.add_text_field("body", TEXT);
+        let index = Index::create_in_ram(schema_builder.build());
+        let mut index_writer = index.writer_with_num_threads(1, 10_000_000)?;
+        index_writer.add_document(doc!(body => "hello"));
+        index_writer.commit()?;
+        let searcher = index.reader()?.searcher();
+        let cancellation_token = CancellationToken::new();
+        let collector = QuickwitCollector {
+            split_id: "split1".to_string(),
+            start_offset: 0,
+            max_hits: 10,
+            sort_by: SortBy::DocId,
+            fast_field_names: HashSet::new(),
+            timestamp_field_opt: None,
+            start_timestamp_opt: None,
+            end_timestamp_opt: None,
+            cancellation_token: cancellation_token.clone(),
+        };
+        let leaf_search_response = searcher.search(&AllQuery, &collector)?;
+        assert_eq!(leaf_search_response.num_hits, 1);
+
+        cancellation_token.cancel();
+        let search_error = searcher.search(&AllQuery, &collector).unwrap_err();
+        assert!(search_error.to_string().contains("cancelled"));
+        Ok(())
+    }
 }
@@ -17,9 +17,13 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program. If not, see <http://www.gnu.org/licenses/>.
 
+use std::collections::BTreeMap;
+
+use quickwit_common::service_error::{ErrorCode, ErrorResponse, ServiceError};
 use quickwit_index_config::QueryParserError;
 use quickwit_metastore::MetastoreError;
-use quickwit_storage::StorageResolverError;
+use quickwit_proto::error::{error_response_from_grpc_status, GrpcServiceError};
+use quickwit_storage::{StorageError, StorageResolverError};
 use serde::{Deserialize, Serialize};
 use tantivy::TantivyError;
 use thiserror::Error;
@@ -37,23 +41,93 @@ pub enum SearchError {
     StorageResolverError(#[from] StorageResolverError),
     #[error("Invalid query: {0}")]
     InvalidQuery(String),
+    #[error("Timeout: {0}")]
+    Timeout(String),
+    #[error("Overloaded: {0}")]
+    Overloaded(String),
+    #[error("Unavailable: {0}")]
+    Unavailable(String),
+}
+
+impl ServiceError for SearchError {
+    fn error_code(&self) -> ErrorCode {
+        match self {
+            SearchError::IndexDoesNotExist { .. } => ErrorCode::IndexDoesNotExist,
+            SearchError::InternalError(_) => ErrorCode::Internal,
+            SearchError::StorageResolverError(storage_resolver_error) => {
+                storage_resolver_error.error_code()
+            }
+            SearchError::InvalidQuery(_) => ErrorCode::InvalidQuery,
+            SearchError::Timeout(_) => ErrorCode::Timeout,
+            SearchError::Overloaded(_) => ErrorCode::Overloaded,
+            SearchError::Unavailable(_) => ErrorCode::Unavailable,
+        }
+    }
+
+    fn error_message(&self) -> String {
+        // The code already tells the kind of error, so we only return the underlying
+        // message. This lets `parse_grpc_error` rebuild the exact same error.
+        match self {
+            SearchError::InternalError(message)
+            | SearchError::InvalidQuery(message)
+            | SearchError::Timeout(message)
+            | SearchError::Overloaded(message)
+            | SearchError::Unavailable(message) => message.clone(),
+            SearchError::IndexDoesNotExist { .. } | SearchError::StorageResolverError(_) => {
+                self.to_string()
+            }
+        }
+    }
+
+    fn error_details(&self) -> BTreeMap<String, String> {
+        match self {
+            SearchError::IndexDoesNotExist { index_id } => {
+                let mut details = BTreeMap::new();
+                details.insert("index_id".to_string(), index_id.clone());
+                details
+            }
+            SearchError::StorageResolverError(storage_resolver_error) => {
+                storage_resolver_error.error_details()
+            }
+            _ => BTreeMap::new(),
+        }
+    }
 }
 
 impl From<SearchError> for tonic::Status {
     fn from(error: SearchError) -> tonic::Status {
-        let code = match error {
-            SearchError::IndexDoesNotExist { .. } => tonic::Code::NotFound,
-            SearchError::InternalError(_) => tonic::Code::Internal,
-            SearchError::StorageResolverError(_) => tonic::Code::Internal,
-            SearchError::InvalidQuery(_) => tonic::Code::InvalidArgument,
-        };
-        let message = error.to_string();
-        tonic::Status::new(code, message)
+        error.grpc_status()
+    }
+}
+
+impl From<ErrorResponse> for SearchError {
+    fn from(mut error_response: ErrorResponse) -> SearchError {
+        let message = error_response.message;
+        match error_response.code {
+            ErrorCode::IndexDoesNotExist => match error_response.details.remove("index_id") {
+                Some(index_id) => SearchError::IndexDoesNotExist { index_id },
+                None => SearchError::InternalError(message),
+            },
+            ErrorCode::InvalidArgument | ErrorCode::InvalidQuery => {
+                SearchError::InvalidQuery(message)
+            }
+            ErrorCode::Timeout => SearchError::Timeout(message),
+            ErrorCode::Overloaded => SearchError::Overloaded(message),
+            ErrorCode::Unavailable => SearchError::Unavailable(message),
+            _ => SearchError::InternalError(message),
+        }
     }
 }
 
 /// Parse tonic error and returns `SearchError`.
 pub fn parse_grpc_error(grpc_error: &tonic::Status) -> SearchError {
+    if let Some(error_response) = error_response_from_grpc_status(grpc_error) {
+        return SearchError::from(error_response);
+    }
+    if grpc_error.code() == tonic::Code::ResourceExhausted {
+        // The node rejected the request without doing any work, it can be retried elsewhere.
+        return SearchError::Overloaded(grpc_error.message().to_string());
+    }
     serde_json::from_str(grpc_error.message())
         .unwrap_or_else(|_| SearchError::InternalError(grpc_error.message().to_string()))
 }
@@ -66,7 +140,22 @@ impl From<TantivyError> for SearchError {
 
 impl From<anyhow::Error> for SearchError {
     fn from(any_err: anyhow::Error) -> Self {
-        SearchError::InternalError(format!("{}", any_err))
+        // Metastore and storage errors are often wrapped with some context on their way up,
+        // we still want the client to know it can retry.
+        let error_code = any_err.chain().find_map(|cause| {
+            if let Some(storage_error) = cause.downcast_ref::<StorageError>() {
+                return Some(storage_error.error_code());
+            }
+            cause
+                .downcast_ref::<MetastoreError>()
+                .map(|metastore_error| metastore_error.error_code())
+        });
+        let message = format!("{:#}", any_err);
+        match error_code {
+            Some(ErrorCode::Unavailable) => SearchError::Unavailable(message),
+            Some(ErrorCode::Timeout) => SearchError::Timeout(message),
+            _ => SearchError::InternalError(message),
+        }
     }
 }
 
@@ -82,7 +171,21 @@ impl From<MetastoreError> for SearchError {
             MetastoreError::IndexDoesNotExist { index_id } => {
                 SearchError::IndexDoesNotExist { index_id }
             }
-            _ => SearchError::InternalError(format!("{}", metastore_error)),
+            _ => match metastore_error.error_code() {
+                ErrorCode::Unavailable => SearchError::Unavailable(metastore_error.to_string()),
+                ErrorCode::Timeout => SearchError::Timeout(metastore_error.to_string()),
+                _ => SearchError::InternalError(metastore_error.to_string()),
+            },
+        }
+    }
+}
+
+impl From<StorageError> for SearchError {
+    fn from(storage_error: StorageError) -> SearchError {
+        match storage_error.error_code() {
+            ErrorCode::Unavailable => SearchError::Unavailable(storage_error.to_string()),
+            ErrorCode::Timeout => SearchError::Timeout(storage_error.to_string()),
+            _ => SearchError::InternalError(storage_error.to_string()),
         }
     }
 }
@@ -98,3 +201,72 @@ pub struct NodeSearchError {
     pub search_error: SearchError,
     pub split_ids: Vec<String>,
 }
+
+#[cfg(test)]
+mod tests {
+    use quickwit_storage::StorageErrorKind;
+
+    use super::*;
+
+    #[test]
+    fn test_search_error_grpc_round_trip() {
+        let search_errors = vec![
+            SearchError::IndexDoesNotExist {
+                index_id: "my-index".to_string(),
+            },
+            SearchError::InvalidQuery("Field does not exist: `foo`".to_string()),
+            SearchError::Timeout("Deadline exceeded.".to_string()),
+            SearchError::Overloaded("Too many requests.".to_string()),
+            SearchError::Unavailable("Connection refused.".to_string()),
+            SearchError::InternalError("Boom.".to_string()),
+        ];
+        for search_error in search_errors {
+            let grpc_status = tonic::Status::from(search_error.clone());
+            assert_eq!(
+                parse_grpc_error(&grpc_status).to_string(),
+                search_error.to_string()
+            );
+        }
+    }
+
+    #[test]
+    fn test_search_error_response() {
+        let error_response =
+            SearchError::Timeout("Deadline exceeded.".to_string()).error_response();
+        assert_eq!(error_response.code, ErrorCode::Timeout);
+        assert_eq!(error_response.message, "Deadline exceeded.");
+        assert!(error_response.retryable);
+        let grpc_status = tonic::Status::new(tonic::Code::ResourceExhausted, "Too many requests.");
+        assert!(matches!(
+            parse_grpc_error(&grpc_status),
+            SearchError::Overloaded(_)
+        ));
+    }
+
+    #[test]
+    fn test_search_error_from_metastore_and_storage_errors() {
+        let search_error = SearchError::from(MetastoreError::ConnectionError {
+            message: "connection refused".to_string(),
+        });
+        assert!(matches!(search_error, SearchError::Unavailable(_)));
+        assert!(search_error.error_response().retryable);
+        let storage_error = StorageErrorKind::Service.with_error(anyhow::anyhow!("throttled"));
+        assert!(matches!(
+            SearchError::from(storage_error),
+            SearchError::Unavailable(_)
+        ));
+        let wrapped_storage_error = anyhow::Error::from(
+            StorageErrorKind::Io.with_error(anyhow::anyhow!("connection reset")),
+        )
+        .context("Failed to open split.");
+        assert!(matches!(
+            SearchError::from(wrapped_storage_error),
+            SearchError::Unavailable(_)
+        ));
+        let storage_error = StorageErrorKind::DoesNotExist.with_error(anyhow::anyhow!("gone"));
+        assert!(matches!(
+            SearchError::from(storage_error),
+            SearchError::InternalError(_)
+        ));
+    }
+}
@@ -23,7 +23,7 @@ use std::sync::Arc;
 use anyhow::Context;
 use itertools::Itertools;
 use quickwit_proto::{FetchDocsResult, Hit, PartialHit, SplitIdAndFooterOffsets};
-use quickwit_storage::Storage;
+use quickwit_storage::{Cache, Storage};
 use tantivy::{IndexReader, ReloadPolicy};
 use tracing::error;
 
@@ -37,6 +37,7 @@ async fn fetch_docs_to_map<'a>(
     mut global_doc_addrs: Vec<GlobalDocAddress<'a>>,
     index_storage: Arc<dyn Storage>,
     splits: &[SplitIdAndFooterOffsets],
+    split_cache_opt: Option<Arc<dyn Cache>>,
 ) -> anyhow::Result<HashMap<GlobalDocAddress<'a>, String>> {
     let mut split_fetch_docs_futures = Vec::new();
 
@@ -61,6 +62,7 @@ async fn fetch_docs_to_map<'a>(
             global_doc_addrs,
             index_storage.clone(),
             *split_and_offset,
+            split_cache_opt.clone(),
         ));
     }
 
@@ -98,6 +100,7 @@ pub async fn fetch_docs(
     partial_hits: Vec<PartialHit>,
     index_storage: Arc<dyn Storage>,
     splits: &[SplitIdAndFooterOffsets],
+    split_cache_opt: Option<Arc<dyn Cache>>,
 ) -> anyhow::Result<FetchDocsResult> {
     let global_doc_addrs: Vec<GlobalDocAddress> = partial_hits
         .iter()
@@ -105,7 +108,7 @@ pub async fn fetch_docs(
         .collect();
 
     let mut global_doc_addr_to_doc_json =
-        fetch_docs_to_map(global_doc_addrs, index_storage, splits).await?;
+        fetch_docs_to_map(global_doc_addrs, index_storage, splits, split_cache_opt).await?;
 
     let hits: Vec<Hit> = partial_hits
         .iter()
@@ -128,8 +131,9 @@ async fn get_searcher_for_split(
     num_searchers: usize,
     index_storage: Arc<dyn Storage>,
     split: &SplitIdAndFooterOffsets,
+    split_cache_opt: Option<Arc<dyn Cache>>,
 ) -> anyhow::Result<IndexReader> {
-    let index = open_index(index_storage, split)
+    let index = open_index(index_storage, split, split_cache_opt)
         .await
         .with_context(|| "open-index-for-split")?;
     let reader = index
@@ -141,14 +145,21 @@ async fn get_searcher_for_split(
 }
 
 /// Fetching docs from a specific split.
-#[tracing::instrument(skip(global_doc_addrs, index_storage, split))]
+#[tracing::instrument(skip(global_doc_addrs, index_storage, split, split_cache_opt))]
 #[allow(clippy::needless_lifetimes)]
 async fn fetch_docs_in_split<'a>(
     global_doc_addrs: Vec<GlobalDocAddress<'a>>,
     index_storage: Arc<dyn Storage>,
     split: &SplitIdAndFooterOffsets,
+    split_cache_opt: Option<Arc<dyn Cache>>,
 ) -> anyhow::Result<Vec<(GlobalDocAddress<'a>, String)>> {
-    let index_reader = get_searcher_for_split(global_doc_addrs.len(), index_storage, split).await?;
+    let index_reader = get_searcher_for_split(
+        global_doc_addrs.len(),
+        index_storage,
+        split,
+        split_cache_opt,
+    )
+    .await?;
     let mut doc_futures = Vec::new();
     for global_doc_addr in global_doc_addrs {
         let searcher = index_reader.searcher();
@@ -0,0 +1,110 @@
+// Copyright (C) 2021 Quickwit, Inc.
+//
+// Quickwit is offered under the AGPL v3.0 and as commercial software.
+// For commercial licensing, contact us at hello@quickwit.io.
+//
+// AGPL:
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Number of latencies kept by a [`LatencyTracker`].
+const LATENCY_WINDOW_SIZE: usize = 1_000;
+
+/// Minimum number of latencies required before a [`LatencyTracker`] reports percentiles.
+const MIN_NUM_LATENCIES: usize = 20;
+
+/// Keeps track of the latencies of the last requests sent to leaf nodes, in order to
+/// detect the ones that are abnormally slow.
+#[derive(Default)]
+pub(crate) struct LatencyTracker {
+    latencies: Mutex<VecDeque<Duration>>,
+}
+
+impl LatencyTracker {
+    /// Records the latency of a request.
+    pub fn record(&self, latency: Duration) {
+        let mut latencies = self.latencies.lock().unwrap();
+        if latencies.len() == LATENCY_WINDOW_SIZE {
+            latencies.pop_front();
+        }
+        latencies.push_back(latency);
+    }
+
+    /// Returns the latency at the given percentile (between 0 and 100) of the last requests.
+    ///
+    /// Returns `None` if too few requests have been recorded to get a meaningful value.
+    pub fn percentile(&self, percentile: f64) -> Option<Duration> {
+        let mut latencies: Vec<Duration> = {
+            let latencies = self.latencies.lock().unwrap();
+            if latencies.len() < MIN_NUM_LATENCIES {
+                return None;
+            }
+            latencies.iter().copied().collect()
+        };
+        latencies.sort_unstable();
+        let rank = (percentile / 100.0 * latencies.len() as f64).ceil() as usize;
+        let index = rank.clamp(1, latencies.len()) - 1;
+        Some(latencies[index])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{LatencyTracker, LATENCY_WINDOW_SIZE, MIN_NUM_LATENCIES};
+
+    #[test]
+    fn test_latency_tracker_percentile() {
+        let latency_tracker = LatencyTracker::default();
+        for latency_millis in 1..MIN_NUM_LATENCIES as u64 {
+            latency_tracker.record(Duration::from_millis(latency_millis));
+        }
+        assert_eq!(latency_tracker.percentile(95.0), None);
+
+        for latency_millis in MIN_NUM_LATENCIES as u64..=100 {
+            latency_tracker.record(Duration::from_millis(latency_millis));
+        }
+        assert_eq!(
+            latency_tracker.percentile(95.0),
+            Some(Duration::from_millis(95))
+        );
+        assert_eq!(
+            latency_tracker.percentile(0.0),
+            Some(Duration::from_millis(1))
+        );
+        assert_eq!(
+            latency_tracker.percentile(100.0),
+            Some(Duration::from_millis(100))
+        );
+    }
+
+    #[test]
+    fn test_latency_tracker_forgets_old_latencies() {
+        let latency_tracker = LatencyTracker::default();
+        for _ in 0..LATENCY_WINDOW_SIZE {
+            latency_tracker.record(Duration::from_secs(10));
+        }
+        for _ in 0..LATENCY_WINDOW_SIZE {
+            latency_tracker.record(Duration::from_millis(10));
+        }
+        assert_eq!(
+            latency_tracker.percentile(100.0),
+            Some(Duration::from_millis(10))
+        );
+    }
+}
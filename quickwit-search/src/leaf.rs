@@ -27,19 +27,24 @@ use bytes::Bytes;
 use futures::future::try_join_all;
 use itertools::{Either, Itertools};
 use once_cell::sync::OnceCell;
+use quickwit_common::HOTCACHE_FILENAME;
 use quickwit_directories::{CachingDirectory, HotDirectory, StorageDirectory};
 use quickwit_index_config::IndexConfig;
 use quickwit_proto::{
     LeafSearchResponse, SearchRequest, SplitIdAndFooterOffsets, SplitSearchError,
 };
-use quickwit_storage::{BundleStorage, MemorySizedCache, Storage};
+use quickwit_storage::{
+    add_prefix_to_cache, wrap_storage_with_cache, BundleStorage, Cache, MemorySizedCache, Storage,
+};
 use tantivy::collector::Collector;
 use tantivy::query::Query;
 use tantivy::{Index, ReloadPolicy, Searcher, Term};
 use tokio::task::spawn_blocking;
+use tokio_util::sync::CancellationToken;
 use tracing::*;
 
 use crate::collector::{make_collector_for_split, make_merge_collector, GenericQuickwitCollector};
+use crate::leaf_cache::LeafSearchCache;
 use crate::SearchError;
 
 fn global_split_footer_cache() -> &'static MemorySizedCache<String> {
@@ -47,17 +52,29 @@ fn global_split_footer_cache() -> &'static MemorySizedCache<String> {
     INSTANCE.get_or_init(|| MemorySizedCache::with_capacity_in_bytes(500_000_000))
 }
 
+/// Returns the footer of a split, i.e. its hotcache followed by the bundle metadata.
+///
+/// If a split cache is given, the footer is cached in it under the `<split_id>.split/hotcache`
+/// path, so that it can be routed like the files bundled in the split. The footer is only a
+/// byte range of the split file, which would otherwise never match a `hotcache` route.
+/// Without a split cache, the footer is kept in a process-wide cache.
 async fn get_split_footer_from_cache_or_fetch(
     index_storage: Arc<dyn Storage>,
     split_and_footer_offsets: &SplitIdAndFooterOffsets,
+    split_cache_opt: Option<&Arc<dyn Cache>>,
 ) -> anyhow::Result<Bytes> {
-    {
+    let split_file = PathBuf::from(format!("{}.split", split_and_footer_offsets.split_id));
+    let footer_path = split_file.join(HOTCACHE_FILENAME);
+    if let Some(split_cache) = split_cache_opt {
+        if let Some(footer_data) = split_cache.get_all(&footer_path).await {
+            return Ok(footer_data);
+        }
+    } else {
         let possible_val = global_split_footer_cache().get(&split_and_footer_offsets.split_id);
         if let Some(footer_data) = possible_val {
             return Ok(footer_data);
         }
     }
-    let split_file = PathBuf::from(format!("{}.split", split_and_footer_offsets.split_id));
     let footer_data_opt = index_storage
         .get_slice(
             &split_file,
@@ -73,10 +90,16 @@ async fn get_split_footer_from_cache_or_fetch(
             )
         })?;
 
-    global_split_footer_cache().put(
-        split_and_footer_offsets.split_id.to_owned(),
-        footer_data_opt.clone(),
-    );
+    if let Some(split_cache) = split_cache_opt {
+        split_cache
+            .put_all(footer_path, footer_data_opt.clone())
+            .await;
+    } else {
+        global_split_footer_cache().put(
+            split_and_footer_offsets.split_id.to_owned(),
+            footer_data_opt.clone(),
+        );
+    }
 
     Ok(footer_data_opt)
 }
@@ -84,22 +107,34 @@ async fn get_split_footer_from_cache_or_fetch(
 /// Opens a `tantivy::Index` for the given split.
 ///
 /// The resulting index uses a dynamic and a static cache.
+/// If a split cache is given, the files of the split are also read through it.
 pub(crate) async fn open_index(
     index_storage: Arc<dyn Storage>,
     split_and_footer_offsets: &SplitIdAndFooterOffsets,
+    split_cache_opt: Option<Arc<dyn Cache>>,
 ) -> anyhow::Result<Index> {
     let split_file = PathBuf::from(format!("{}.split", split_and_footer_offsets.split_id));
-    let mut footer_data =
-        get_split_footer_from_cache_or_fetch(index_storage.clone(), split_and_footer_offsets)
-            .await?;
+    let mut footer_data = get_split_footer_from_cache_or_fetch(
+        index_storage.clone(),
+        split_and_footer_offsets,
+        split_cache_opt.as_ref(),
+    )
+    .await?;
     let hotcache_len_bytes = footer_data.split_off(footer_data.len() - 8);
     let hotcache_num_bytes =
         u64::from_le_bytes((&*hotcache_len_bytes).try_into().unwrap()) as usize;
 
     let hotcache_bytes = footer_data.split_off(footer_data.len() - hotcache_num_bytes);
 
-    let bundle = BundleStorage::new(index_storage, split_file, &footer_data)?;
-    let directory = StorageDirectory::new(Arc::new(bundle));
+    let bundle = BundleStorage::new(index_storage, split_file.clone(), &footer_data)?;
+    let mut bundle_storage: Arc<dyn Storage> = Arc::new(bundle);
+    if let Some(split_cache) = split_cache_opt {
+        // Bundled files are cached under the split file path, as their
+        // names are only unique within a split.
+        let bundle_cache = add_prefix_to_cache(split_cache, split_file);
+        bundle_storage = wrap_storage_with_cache(bundle_cache, bundle_storage);
+    }
+    let directory = StorageDirectory::new(bundle_storage);
     let caching_directory = CachingDirectory::new_with_unlimited_capacity(Arc::new(directory));
     let hot_directory = HotDirectory::open(caching_directory, hotcache_bytes)?;
     let index = Index::open(hot_directory)?;
@@ -186,21 +221,25 @@ async fn warm_up_terms(searcher: &Searcher, query: &dyn Query) -> anyhow::Result
 }
 
 /// Apply a leaf search on a single split.
-#[instrument(skip(search_request, storage, split, index_config))]
+#[instrument(skip(search_request, storage, split, index_config, split_cache_opt))]
 async fn leaf_search_single_split(
     search_request: &SearchRequest,
     storage: Arc<dyn Storage>,
     split: SplitIdAndFooterOffsets,
     index_config: Arc<dyn IndexConfig>,
+    split_cache_opt: Option<Arc<dyn Cache>>,
+    cancellation_token: CancellationToken,
 ) -> crate::Result<LeafSearchResponse> {
     let split_id = split.split_id.to_string();
-    let index = open_index(storage, &split).await?;
+    check_not_cancelled(&cancellation_token, &split_id)?;
+    let index = open_index(storage, &split, split_cache_opt).await?;
     let split_schema = index.schema();
     let quickwit_collector = make_collector_for_split(
-        split_id,
+        split_id.clone(),
         index_config.as_ref(),
         search_request,
         &split_schema,
+        cancellation_token.clone(),
     );
     let query = index_config.query(split_schema, search_request)?;
 
@@ -210,41 +249,87 @@ async fn leaf_search_single_split(
         .reload_policy(ReloadPolicy::Manual)
         .try_into()?;
     let searcher = reader.searcher();
+    check_not_cancelled(&cancellation_token, &split_id)?;
     warmup(&*searcher, &query, &quickwit_collector.fast_field_names()).await?;
+    check_not_cancelled(&cancellation_token, &split_id)?;
     let span = info_span!(
         "search",
         split_id = %split.split_id,
     );
-    let _ = span.enter();
-    let leaf_search_response = searcher.search(&query, &quickwit_collector)?;
+    // The search runs on the blocking thread pool, where it checks the cancellation token
+    // between segments.
+    let leaf_search_response = spawn_blocking(move || {
+        let _span_guard = span.enter();
+        searcher.search(&query, &quickwit_collector)
+    })
+    .await??;
     Ok(leaf_search_response)
 }
 
+fn check_not_cancelled(
+    cancellation_token: &CancellationToken,
+    split_id: &str,
+) -> crate::Result<()> {
+    if cancellation_token.is_cancelled() {
+        return Err(SearchError::InternalError(format!(
+            "Search of split {} was cancelled.",
+            split_id
+        )));
+    }
+    Ok(())
+}
+
 /// `leaf` step of search.
 ///
 /// The leaf search collects all kind of information, and returns a set of [PartialHit] candidates.
 /// The root will be in charge to consolidate, identify the actual final top hits to display, and
 /// fetch the actual documents to convert the partial hits into actual Hits.
+///
+/// If a leaf search cache is given, splits whose response is cached are not searched again.
+///
+/// Dropping the returned future cancels the search: splits that have not been searched yet
+/// are skipped and the splits being searched stop at their next segment.
 pub async fn leaf_search(
     request: &SearchRequest,
     index_storage: Arc<dyn Storage>,
     splits: &[SplitIdAndFooterOffsets],
     index_config: Arc<dyn IndexConfig>,
+    split_cache_opt: Option<Arc<dyn Cache>>,
+    leaf_search_cache_opt: Option<Arc<LeafSearchCache>>,
 ) -> Result<LeafSearchResponse, SearchError> {
+    let cancellation_token = CancellationToken::new();
+    let _cancel_on_exit_guard = cancellation_token.clone().drop_guard();
     let leaf_search_single_split_futures: Vec<_> = splits
         .iter()
         .map(|split| {
+            let cancellation_token_clone = cancellation_token.clone();
             let index_config_clone = index_config.clone();
             let index_storage_clone = index_storage.clone();
+            let split_cache_opt_clone = split_cache_opt.clone();
+            let leaf_search_cache_opt_clone = leaf_search_cache_opt.clone();
             async move {
-                leaf_search_single_split(
+                if let Some(leaf_search_cache) = leaf_search_cache_opt_clone.as_ref() {
+                    if let Some(cached_response) = leaf_search_cache.get(split, request) {
+                        return Ok(cached_response);
+                    }
+                }
+                let leaf_search_result = leaf_search_single_split(
                     request,
                     index_storage_clone,
                     split.clone(),
                     index_config_clone,
+                    split_cache_opt_clone,
+                    cancellation_token_clone,
                 )
                 .await
-                .map_err(|err| (split.split_id.clone(), err))
+                .map_err(|err| (split.split_id.clone(), err));
+                if let (Some(leaf_search_cache), Ok(leaf_search_response)) = (
+                    leaf_search_cache_opt_clone.as_ref(),
+                    leaf_search_result.as_ref(),
+                ) {
+                    leaf_search_cache.put(split, request, leaf_search_response);
+                }
+                leaf_search_result
             }
         })
         .collect();
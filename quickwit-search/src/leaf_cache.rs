@@ -0,0 +1,266 @@
+// Copyright (C) 2021 Quickwit, Inc.
+//
+// Quickwit is offered under the AGPL v3.0 and as commercial software.
+// For commercial licensing, contact us at hello@quickwit.io.
+//
+// AGPL:
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use bytes::Bytes;
+use prost::Message;
+use quickwit_proto::{LeafSearchResponse, SearchRequest, SplitIdAndFooterOffsets};
+use quickwit_storage::{CacheStats, MemorySizedCache};
+
+use crate::metrics::SEARCH_METRICS;
+
+/// Caches the leaf search responses of individual splits.
+///
+/// Published splits are immutable, so the response of a split to a given request never
+/// changes. Dashboards refreshing the same queries only need to search the splits that
+/// were published since their last refresh.
+pub struct LeafSearchCache {
+    content: MemorySizedCache<CacheKey>,
+    capacity_in_bytes: usize,
+}
+
+impl LeafSearchCache {
+    /// Creates a cache holding at most `capacity_in_bytes` bytes of responses.
+    /// A capacity of zero disables the cache.
+    pub fn with_capacity_in_bytes(capacity_in_bytes: usize) -> Self {
+        LeafSearchCache {
+            content: MemorySizedCache::with_capacity_in_bytes(capacity_in_bytes),
+            capacity_in_bytes,
+        }
+    }
+
+    /// Returns the cached response of the split to the request, if any.
+    pub(crate) fn get(
+        &self,
+        split: &SplitIdAndFooterOffsets,
+        search_request: &SearchRequest,
+    ) -> Option<LeafSearchResponse> {
+        if self.capacity_in_bytes == 0 {
+            return None;
+        }
+        let cache_key = CacheKey::from_split_and_request(split, search_request);
+        let encoded_response_opt = self.content.get(&cache_key);
+        if encoded_response_opt.is_some() {
+            SEARCH_METRICS.leaf_search_cache_hits_total.inc();
+        } else {
+            SEARCH_METRICS.leaf_search_cache_misses_total.inc();
+        }
+        LeafSearchResponse::decode(encoded_response_opt?).ok()
+    }
+
+    /// Caches the response of the split to the request.
+    pub(crate) fn put(
+        &self,
+        split: &SplitIdAndFooterOffsets,
+        search_request: &SearchRequest,
+        leaf_search_response: &LeafSearchResponse,
+    ) {
+        if self.capacity_in_bytes == 0 || !leaf_search_response.failed_splits.is_empty() {
+            return;
+        }
+        let cache_key = CacheKey::from_split_and_request(split, search_request);
+        let encoded_response = Bytes::from(leaf_search_response.encode_to_vec());
+        self.content.put(cache_key, encoded_response);
+    }
+
+    /// Returns the hit, miss and eviction counters of the cache, and its current size.
+    pub fn stats(&self) -> CacheStats {
+        self.content.stats()
+    }
+}
+
+/// Identifies the response of a split to a request.
+///
+/// The request is normalized so that requests yielding the same response on the split
+/// share the same key.
+#[derive(Debug, Hash, PartialEq, Eq)]
+struct CacheKey {
+    split_id: String,
+    encoded_request: Vec<u8>,
+}
+
+impl CacheKey {
+    fn from_split_and_request(
+        split: &SplitIdAndFooterOffsets,
+        search_request: &SearchRequest,
+    ) -> Self {
+        let mut normalized_request = search_request.clone();
+        // The leaf collects the top `start_offset + max_hits` hits regardless of the offset.
+        normalized_request.max_hits += normalized_request.start_offset;
+        normalized_request.start_offset = 0;
+        // Split ids are unique across indexes, and partial results and deadlines only matter
+        // to the root.
+        normalized_request.index_id.clear();
+        normalized_request.allow_partial_results = false;
+        normalized_request.timeout_millis = None;
+        // A time bound that does not cut through the split does not filter any of its
+        // documents. Dropping it lets sliding time windows hit the cache for older splits.
+        if let (Some(start_timestamp), Some(split_start)) =
+            (normalized_request.start_timestamp, split.timestamp_start)
+        {
+            if start_timestamp <= split_start {
+                normalized_request.start_timestamp = None;
+            }
+        }
+        if let (Some(end_timestamp), Some(split_end)) =
+            (normalized_request.end_timestamp, split.timestamp_end)
+        {
+            // The end timestamp is exclusive while the split end is inclusive.
+            if split_end < end_timestamp {
+                normalized_request.end_timestamp = None;
+            }
+        }
+        CacheKey {
+            split_id: split.split_id.clone(),
+            encoded_request: normalized_request.encode_to_vec(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use quickwit_proto::PartialHit;
+
+    use super::*;
+
+    fn split(split_id: &str, time_range_opt: Option<(i64, i64)>) -> SplitIdAndFooterOffsets {
+        SplitIdAndFooterOffsets {
+            split_id: split_id.to_string(),
+            split_footer_start: 0,
+            split_footer_end: 100,
+            timestamp_start: time_range_opt.map(|(start, _)| start),
+            timestamp_end: time_range_opt.map(|(_, end)| end),
+        }
+    }
+
+    fn search_request(
+        start_timestamp: Option<i64>,
+        end_timestamp: Option<i64>,
+        start_offset: u64,
+        max_hits: u64,
+    ) -> SearchRequest {
+        SearchRequest {
+            index_id: "logs".to_string(),
+            query: "severity:error".to_string(),
+            search_fields: vec![],
+            start_timestamp,
+            end_timestamp,
+            max_hits,
+            start_offset,
+            tags: vec![],
+            allow_partial_results: false,
+            timeout_millis: None,
+        }
+    }
+
+    fn leaf_search_response(num_hits: u64) -> LeafSearchResponse {
+        LeafSearchResponse {
+            num_hits,
+            partial_hits: vec![PartialHit {
+                sorting_field_value: 1,
+                split_id: "split_1".to_string(),
+                segment_ord: 0,
+                doc_id: 2,
+            }],
+            failed_splits: vec![],
+            num_attempted_splits: 1,
+        }
+    }
+
+    #[test]
+    fn test_leaf_search_cache_get_put() {
+        let cache = LeafSearchCache::with_capacity_in_bytes(1_000_000);
+        let split_1 = split("split_1", None);
+        let split_2 = split("split_2", None);
+        let request = search_request(None, None, 0, 10);
+        assert!(cache.get(&split_1, &request).is_none());
+        cache.put(&split_1, &request, &leaf_search_response(3));
+        assert_eq!(cache.get(&split_1, &request), Some(leaf_search_response(3)));
+        assert!(cache.get(&split_2, &request).is_none());
+        let other_request = SearchRequest {
+            query: "severity:warn".to_string(),
+            ..request
+        };
+        assert!(cache.get(&split_1, &other_request).is_none());
+        let stats = cache.stats();
+        assert_eq!(stats.num_hits, 1);
+        assert_eq!(stats.num_misses, 3);
+    }
+
+    #[test]
+    fn test_leaf_search_cache_normalizes_pagination() {
+        let cache = LeafSearchCache::with_capacity_in_bytes(1_000_000);
+        let split_1 = split("split_1", None);
+        cache.put(
+            &split_1,
+            &search_request(None, None, 0, 20),
+            &leaf_search_response(3),
+        );
+        assert!(cache
+            .get(&split_1, &search_request(None, None, 10, 10))
+            .is_some());
+        assert!(cache
+            .get(&split_1, &search_request(None, None, 10, 20))
+            .is_none());
+    }
+
+    #[test]
+    fn test_leaf_search_cache_ignores_time_bounds_covering_the_split() {
+        let cache = LeafSearchCache::with_capacity_in_bytes(1_000_000);
+        let split_1 = split("split_1", Some((100, 199)));
+        cache.put(
+            &split_1,
+            &search_request(Some(50), Some(200), 0, 10),
+            &leaf_search_response(3),
+        );
+        // The time window slid but still covers the whole split.
+        assert!(cache
+            .get(&split_1, &search_request(Some(100), Some(300), 0, 10))
+            .is_some());
+        assert!(cache
+            .get(&split_1, &search_request(None, None, 0, 10))
+            .is_some());
+        // The window now cuts through the split.
+        assert!(cache
+            .get(&split_1, &search_request(Some(150), Some(300), 0, 10))
+            .is_none());
+        assert!(cache
+            .get(&split_1, &search_request(Some(50), Some(199), 0, 10))
+            .is_none());
+        // Without the split time range, the bounds are part of the key.
+        let split_2 = split("split_2", None);
+        cache.put(
+            &split_2,
+            &search_request(Some(50), Some(200), 0, 10),
+            &leaf_search_response(3),
+        );
+        assert!(cache
+            .get(&split_2, &search_request(Some(100), Some(300), 0, 10))
+            .is_none());
+    }
+
+    #[test]
+    fn test_leaf_search_cache_disabled() {
+        let cache = LeafSearchCache::with_capacity_in_bytes(0);
+        let split_1 = split("split_1", None);
+        let request = search_request(None, None, 0, 10);
+        cache.put(&split_1, &request, &leaf_search_response(3));
+        assert!(cache.get(&split_1, &request).is_none());
+        assert_eq!(cache.stats(), CacheStats::default());
+    }
+}
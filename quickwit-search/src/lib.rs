@@ -21,6 +21,7 @@
 #![warn(missing_docs)]
 #![allow(clippy::bool_assert_comparison)]
 
+mod admission_controller;
 mod client;
 mod client_pool;
 mod cluster_client;
@@ -28,7 +29,10 @@ mod collector;
 mod error;
 mod fetch_docs;
 mod filters;
+mod latency_tracker;
 mod leaf;
+mod leaf_cache;
+mod metrics;
 mod rendezvous_hasher;
 mod retry;
 mod root;
@@ -49,6 +53,9 @@ use quickwit_proto::{PartialHit, SearchRequest, SearchResponse, SplitIdAndFooter
 use quickwit_storage::StorageUriResolver;
 use tantivy::DocAddress;
 
+pub use crate::admission_controller::{
+    AdmissionController, AdmissionControllerConfig, AdmissionPermit, LaneConfig, RequestKind,
+};
 pub use crate::client::{create_search_service_client, SearchServiceClient};
 pub use crate::client_pool::search_client_pool::SearchClientPool;
 pub use crate::client_pool::ClientPool;
@@ -56,8 +63,10 @@ pub use crate::cluster_client::ClusterClient;
 pub use crate::error::{parse_grpc_error, SearchError};
 use crate::fetch_docs::fetch_docs;
 use crate::leaf::leaf_search;
+pub use crate::leaf_cache::LeafSearchCache;
+pub use crate::metrics::num_leaf_splits_in_flight;
 pub use crate::root::root_search;
-pub use crate::search_response_rest::SearchResponseRest;
+pub use crate::search_response_rest::{FailedSplitRest, SearchResponseRest};
 pub use crate::search_stream::root_search_stream;
 pub use crate::service::{MockSearchService, SearchService, SearchServiceImpl};
 
@@ -133,6 +142,16 @@ fn extract_split_and_footer_offsets(
             .clone(),
         split_footer_start: split_metadata_and_footer_offsets.footer_offsets.start as u64,
         split_footer_end: split_metadata_and_footer_offsets.footer_offsets.end as u64,
+        timestamp_start: split_metadata_and_footer_offsets
+            .split_metadata
+            .time_range
+            .as_ref()
+            .map(|time_range| *time_range.start()),
+        timestamp_end: split_metadata_and_footer_offsets
+            .split_metadata
+            .time_range
+            .as_ref()
+            .map(|time_range| *time_range.end()),
     }
 }
 
@@ -162,7 +181,10 @@ pub async fn single_node_search(
 ) -> crate::Result<SearchResponse> {
     let start_instant = tokio::time::Instant::now();
     let index_metadata = metastore.index_metadata(&search_request.index_id).await?;
-    let index_storage = storage_resolver.resolve(&index_metadata.index_uri)?;
+    let index_storage = storage_resolver.resolve_with_encryption_key(
+        &index_metadata.index_uri,
+        index_metadata.encryption_key_id.as_deref(),
+    )?;
     let metas = list_relevant_splits(search_request, metastore).await?;
     let split_metadata: Vec<SplitIdAndFooterOffsets> =
         metas.iter().map(extract_split_and_footer_offsets).collect();
@@ -172,6 +194,8 @@ pub async fn single_node_search(
         index_storage.clone(),
         &split_metadata[..],
         index_config,
+        None,
+        None,
     )
     .await
     .context("Failed to perform leaf search.")?;
@@ -179,6 +203,7 @@ pub async fn single_node_search(
         leaf_search_response.partial_hits,
         index_storage,
         &split_metadata,
+        None,
     )
     .await
     .context("Failed to perform fetch docs.")?;
@@ -188,6 +213,7 @@ pub async fn single_node_search(
         hits: fetch_docs_result.hits,
         elapsed_time_micros: elapsed.as_micros() as u64,
         errors: vec![],
+        failed_splits: vec![],
     })
 }
 
@@ -221,6 +247,8 @@ mod tests {
             max_hits: 2,
             start_offset: 0,
             tags: vec![],
+            allow_partial_results: false,
+            timeout_millis: None,
         };
         let single_node_result = single_node_search(
             &search_request,
@@ -276,6 +304,8 @@ mod tests {
             max_hits: 6,
             start_offset: 0,
             tags: vec![],
+            allow_partial_results: false,
+            timeout_millis: None,
         };
         let single_node_result = single_node_search(
             &search_request,
@@ -338,6 +368,8 @@ mod tests {
             max_hits: 15,
             start_offset: 0,
             tags: vec![],
+            allow_partial_results: false,
+            timeout_millis: None,
         };
         let single_node_response = single_node_search(
             &search_request,
@@ -360,6 +392,8 @@ mod tests {
             max_hits: 25,
             start_offset: 0,
             tags: vec![],
+            allow_partial_results: false,
+            timeout_millis: None,
         };
         let single_node_response = single_node_search(
             &search_request,
@@ -382,6 +416,8 @@ mod tests {
             max_hits: 25,
             start_offset: 0,
             tags: vec!["foo".to_string()],
+            allow_partial_results: false,
+            timeout_millis: None,
         };
         let single_node_response = single_node_search(
             &search_request,
@@ -0,0 +1,124 @@
+// Copyright (C) 2021 Quickwit, Inc.
+//
+// Quickwit is offered under the AGPL v3.0 and as commercial software.
+// For commercial licensing, contact us at hello@quickwit.io.
+//
+// AGPL:
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use once_cell::sync::Lazy;
+use quickwit_common::metrics::{
+    new_counter, new_gauge, new_histogram, Histogram, IntCounter, IntGauge,
+};
+
+pub(crate) struct SearchMetrics {
+    pub root_search_duration_seconds: Histogram,
+    pub leaf_search_duration_seconds: Histogram,
+    pub fetch_docs_duration_seconds: Histogram,
+    pub leaf_searched_splits_total: IntCounter,
+    pub leaf_failed_splits_total: IntCounter,
+    pub leaf_hedged_requests_total: IntCounter,
+    pub leaf_search_cache_hits_total: IntCounter,
+    pub leaf_search_cache_misses_total: IntCounter,
+    pub leaf_rejected_requests_total: IntCounter,
+    pub leaf_requests_in_flight: IntGauge,
+    pub leaf_splits_in_flight: IntGauge,
+}
+
+impl Default for SearchMetrics {
+    fn default() -> Self {
+        SearchMetrics {
+            root_search_duration_seconds: new_histogram(
+                "quickwit_search_root_search_duration_seconds",
+                "Duration of root search requests in seconds.",
+            ),
+            leaf_search_duration_seconds: new_histogram(
+                "quickwit_search_leaf_search_duration_seconds",
+                "Duration of leaf search requests in seconds.",
+            ),
+            fetch_docs_duration_seconds: new_histogram(
+                "quickwit_search_fetch_docs_duration_seconds",
+                "Duration of fetch docs requests in seconds.",
+            ),
+            leaf_searched_splits_total: new_counter(
+                "quickwit_search_leaf_searched_splits_total",
+                "Number of splits searched by this node.",
+            ),
+            leaf_failed_splits_total: new_counter(
+                "quickwit_search_leaf_failed_splits_total",
+                "Number of splits this node failed to search.",
+            ),
+            leaf_hedged_requests_total: new_counter(
+                "quickwit_search_leaf_hedged_requests_total",
+                "Number of leaf search requests sent to a second node because the first one was \
+                 too slow to respond.",
+            ),
+            leaf_search_cache_hits_total: new_counter(
+                "quickwit_search_leaf_search_cache_hits_total",
+                "Number of splits whose leaf search response was found in the cache.",
+            ),
+            leaf_search_cache_misses_total: new_counter(
+                "quickwit_search_leaf_search_cache_misses_total",
+                "Number of splits whose leaf search response was not found in the cache.",
+            ),
+            leaf_rejected_requests_total: new_counter(
+                "quickwit_search_leaf_rejected_requests_total",
+                "Number of leaf search and leaf search stream requests rejected because this node \
+                 was overloaded.",
+            ),
+            leaf_requests_in_flight: new_gauge(
+                "quickwit_search_leaf_requests_in_flight",
+                "Number of leaf search and fetch docs requests being served by this node.",
+            ),
+            leaf_splits_in_flight: new_gauge(
+                "quickwit_search_leaf_splits_in_flight",
+                "Number of splits being searched by this node.",
+            ),
+        }
+    }
+}
+
+/// Search metrics, exposed through the `/metrics` endpoint.
+pub(crate) static SEARCH_METRICS: Lazy<SearchMetrics> = Lazy::new(SearchMetrics::default);
+
+/// Increments a gauge for as long as it is alive.
+pub(crate) struct GaugeGuard {
+    gauge: &'static IntGauge,
+    delta: i64,
+}
+
+impl GaugeGuard {
+    pub fn new(gauge: &'static IntGauge) -> Self {
+        Self::with_delta(gauge, 1)
+    }
+
+    pub fn with_delta(gauge: &'static IntGauge, delta: i64) -> Self {
+        gauge.add(delta);
+        GaugeGuard { gauge, delta }
+    }
+}
+
+impl Drop for GaugeGuard {
+    fn drop(&mut self) {
+        self.gauge.sub(self.delta);
+    }
+}
+
+/// Returns the number of splits being searched by this node.
+///
+/// This is the load gossiped to the other nodes of the cluster, which use it to place leaf
+/// search jobs.
+pub fn num_leaf_splits_in_flight() -> u64 {
+    SEARCH_METRICS.leaf_splits_in_flight.get().max(0) as u64
+}
@@ -60,6 +60,28 @@ pub fn sort_by_rendez_vous_hash(nodes: &mut [Node], key: &str) {
     nodes.sort_unstable_by(|left, right| (right.hash_key).cmp(&(left.hash_key)));
 }
 
+/// Picks the node a job of the given `cost` should be assigned to, among nodes sorted by
+/// rendez-vous hashing.
+///
+/// This implements rendez-vous hashing with bounded loads: the first node in the rendez-vous
+/// order that can accept the job without exceeding `max_load` is chosen, so that a job sticks to
+/// the same node as long as this node is not overloaded. If no node can accept the job, the least
+/// loaded node is chosen.
+///
+/// Returns `None` if `nodes` is empty.
+pub fn pick_node_with_bounded_load(nodes: &[Node], cost: u64, max_load: u64) -> Option<usize> {
+    nodes
+        .iter()
+        .position(|node| node.load + cost <= max_load)
+        .or_else(|| {
+            nodes
+                .iter()
+                .enumerate()
+                .min_by_key(|(_, node)| node.load)
+                .map(|(node_index, _)| node_index)
+        })
+}
+
 #[cfg(test)]
 mod tests {
     use std::net::{IpAddr, Ipv4Addr};
@@ -108,4 +130,22 @@ mod tests {
         sort_by_rendez_vous_hash(&mut node_set3, "key");
         check_nodes_order(node_set3, vec![socket1, socket4]);
     }
+
+    #[test]
+    fn test_utils_pick_node_with_bounded_load() {
+        let socket1 = test_socket_addr(1);
+        let socket2 = test_socket_addr(2);
+        let socket3 = test_socket_addr(3);
+        let nodes = vec![
+            Node::new(socket1, 4),
+            Node::new(socket2, 2),
+            Node::new(socket3, 3),
+        ];
+        assert_eq!(pick_node_with_bounded_load(&nodes, 1, 5), Some(0));
+        assert_eq!(pick_node_with_bounded_load(&nodes, 2, 5), Some(1));
+        assert_eq!(pick_node_with_bounded_load(&nodes, 3, 5), Some(1));
+        // No node can accept the job, the least loaded node is picked.
+        assert_eq!(pick_node_with_bounded_load(&nodes, 4, 5), Some(1));
+        assert_eq!(pick_node_with_bounded_load(&[], 1, 5), None);
+    }
 }
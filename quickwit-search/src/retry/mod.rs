@@ -129,10 +129,13 @@ mod tests {
             partial_hits: vec![],
             index_id: "id".to_string(),
             index_uri: "uri".to_string(),
+            encryption_key_id: None,
             split_metadata: vec![SplitIdAndFooterOffsets {
                 split_id: "split_1".to_string(),
                 split_footer_end: 100,
                 split_footer_start: 0,
+                timestamp_start: None,
+                timestamp_end: None,
             }],
         }
     }
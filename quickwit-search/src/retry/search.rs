@@ -75,19 +75,26 @@ mod tests {
                 max_hits: 10,
                 start_offset: 0,
                 tags: vec![],
+                allow_partial_results: false,
+                timeout_millis: None,
             }),
             index_config: "index_config".to_string(),
             index_uri: "uri".to_string(),
+            encryption_key_id: None,
             split_metadata: vec![
                 SplitIdAndFooterOffsets {
                     split_id: "split_1".to_string(),
                     split_footer_end: 100,
                     split_footer_start: 0,
+                    timestamp_start: None,
+                    timestamp_end: None,
                 },
                 SplitIdAndFooterOffsets {
                     split_id: "split_2".to_string(),
                     split_footer_end: 100,
                     split_footer_start: 0,
+                    timestamp_start: None,
+                    timestamp_end: None,
                 },
             ],
         }
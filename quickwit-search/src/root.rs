@@ -18,19 +18,21 @@
 // along with this program. If not, see <http://www.gnu.org/licenses/>.
 
 use std::collections::{HashMap, HashSet};
+use std::future::Future;
 use std::sync::Arc;
 
-use futures::{StreamExt, TryStreamExt};
+use futures::{FutureExt, StreamExt};
 use itertools::Itertools;
 use quickwit_metastore::{Metastore, SplitMetadata, SplitMetadataAndFooterOffsets};
 use quickwit_proto::{
-    FetchDocsRequest, FetchDocsResult, LeafSearchRequest, LeafSearchResponse, PartialHit,
-    SearchRequest, SearchResponse,
+    FetchDocsRequest, LeafSearchRequest, LeafSearchResponse, PartialHit, SearchRequest,
+    SearchResponse, SplitSearchError,
 };
 use tantivy::collector::Collector;
 use tantivy::TantivyError;
 use tokio::task::spawn_blocking;
-use tracing::{debug, error, instrument};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, error, instrument, warn, Instrument};
 
 use crate::client_pool::Job;
 use crate::cluster_client::ClusterClient;
@@ -47,6 +49,9 @@ pub const MAX_CONCURRENT_LEAF_TASKS: usize = if cfg!(test) { 2 } else { 10 };
 /// 2. Merges the search results.
 /// 3. Sends fetch docs requests to multiple leaf nodes.
 /// 4. Builds the response with docs and returns.
+///
+/// If the request allows partial results, the splits that could not be searched are reported
+/// in the response. Otherwise, the search fails.
 #[instrument(skip(search_request, cluster_client, client_pool, metastore))]
 pub async fn root_search(
     search_request: &SearchRequest,
@@ -66,21 +71,46 @@ pub async fn root_search(
     let jobs: Vec<Job> = job_for_splits(&split_metadata_map.keys().collect(), &split_metadata_map);
     let assigned_leaf_search_jobs = client_pool.assign_jobs(jobs, &HashSet::default()).await?;
     debug!(assigned_leaf_search_jobs=?assigned_leaf_search_jobs, "Assigned leaf search jobs.");
-    let leaf_search_responses: Vec<LeafSearchResponse> =
-        futures::stream::iter(assigned_leaf_search_jobs.into_iter())
-            .map(|(client, client_jobs)| {
-                let leaf_request = jobs_to_leaf_request(
-                    search_request,
-                    &index_config_str,
-                    &index_metadata.index_uri,
-                    &split_metadata_map,
-                    &client_jobs,
-                );
-                cluster_client.leaf_search((leaf_request, client))
+    let cancellation_token = CancellationToken::new();
+    // Cancels the leaf requests still running when the root search returns early or is
+    // dropped, e.g. because its client went away.
+    let _cancel_on_exit_guard = cancellation_token.clone().drop_guard();
+    let mut leaf_search_results = futures::stream::iter(assigned_leaf_search_jobs.into_iter())
+        .map(|(client, client_jobs)| {
+            let leaf_request = jobs_to_leaf_request(
+                search_request,
+                &index_config_str,
+                &index_metadata.index_uri,
+                index_metadata.encryption_key_id.as_deref(),
+                &split_metadata_map,
+                &client_jobs,
+            );
+            let cluster_client = cluster_client.clone();
+            spawn_cancellable(cancellation_token.clone(), async move {
+                cluster_client.leaf_search((leaf_request, client)).await
             })
-            .buffer_unordered(MAX_CONCURRENT_LEAF_TASKS)
-            .try_collect()
-            .await?;
+            .map(move |result| (client_jobs, result))
+        })
+        .buffer_unordered(MAX_CONCURRENT_LEAF_TASKS);
+    let mut leaf_search_responses = Vec::new();
+    while let Some((client_jobs, leaf_search_result)) = leaf_search_results.next().await {
+        match leaf_search_result {
+            Ok(leaf_search_response) => leaf_search_responses.push(leaf_search_response),
+            Err(error) if search_request.allow_partial_results => {
+                leaf_search_responses.push(LeafSearchResponse {
+                    num_hits: 0,
+                    partial_hits: Vec::new(),
+                    failed_splits: split_search_errors(&client_jobs, &error),
+                    num_attempted_splits: client_jobs.len() as u64,
+                });
+            }
+            Err(error) => {
+                // The search fails anyway, the other leaf requests are not worth waiting for.
+                cancellation_token.cancel();
+                return Err(error);
+            }
+        }
+    }
 
     let merge_collector = make_merge_collector(search_request);
     let leaf_search_response =
@@ -91,12 +121,13 @@ pub async fn root_search(
             })?;
     debug!(leaf_search_response = ?leaf_search_response, "Merged leaf search response.");
 
-    if !leaf_search_response.failed_splits.is_empty() {
-        error!(failed_splits = ?leaf_search_response.failed_splits, "Leaf search response contains at least one failed split.");
-        return Err(SearchError::InternalError(format!(
-            "{:?}",
-            leaf_search_response.failed_splits
-        )));
+    let mut failed_splits = leaf_search_response.failed_splits;
+    if !failed_splits.is_empty() {
+        if !search_request.allow_partial_results {
+            error!(failed_splits = ?failed_splits, "Leaf search response contains at least one failed split.");
+            return Err(SearchError::InternalError(format!("{:?}", failed_splits)));
+        }
+        warn!(failed_splits = ?failed_splits, "Leaf search response contains at least one failed split. Returning partial results.");
     }
 
     // Create a hash map of PartialHit with split as a key.
@@ -118,21 +149,37 @@ pub async fn root_search(
     let assigned_doc_fetch_jobs = client_pool
         .assign_jobs(fetch_docs_req_jobs, &HashSet::new())
         .await?;
-    let fetch_docs_responses: Vec<FetchDocsResult> =
-        futures::stream::iter(assigned_doc_fetch_jobs.into_iter())
-            .map(|(client, client_jobs)| {
-                let doc_request = jobs_to_fetch_docs_request(
-                    &search_request.index_id,
-                    &index_metadata.index_uri,
-                    &split_metadata_map,
-                    &mut partial_hits_map,
-                    &client_jobs,
-                );
-                cluster_client.fetch_docs((doc_request, client))
+    let mut fetch_docs_results = futures::stream::iter(assigned_doc_fetch_jobs.into_iter())
+        .map(|(client, client_jobs)| {
+            let doc_request = jobs_to_fetch_docs_request(
+                &search_request.index_id,
+                &index_metadata.index_uri,
+                index_metadata.encryption_key_id.as_deref(),
+                &split_metadata_map,
+                &mut partial_hits_map,
+                &client_jobs,
+            );
+            let cluster_client = cluster_client.clone();
+            spawn_cancellable(cancellation_token.clone(), async move {
+                cluster_client.fetch_docs((doc_request, client)).await
             })
-            .buffer_unordered(MAX_CONCURRENT_LEAF_TASKS)
-            .try_collect()
-            .await?;
+            .map(move |result| (client_jobs, result))
+        })
+        .buffer_unordered(MAX_CONCURRENT_LEAF_TASKS);
+    let mut fetch_docs_responses = Vec::new();
+    while let Some((client_jobs, fetch_docs_result)) = fetch_docs_results.next().await {
+        match fetch_docs_result {
+            Ok(fetch_docs_response) => fetch_docs_responses.push(fetch_docs_response),
+            Err(error) if search_request.allow_partial_results => {
+                warn!(error = ?error, "Failed to fetch docs. Returning partial results.");
+                failed_splits.extend(split_search_errors(&client_jobs, &error));
+            }
+            Err(error) => {
+                cancellation_token.cancel();
+                return Err(error);
+            }
+        }
+    }
 
     // Merge the fetched docs.
     let hits = fetch_docs_responses
@@ -162,13 +209,57 @@ pub async fn root_search(
         hits,
         elapsed_time_micros: elapsed.as_micros() as u64,
         errors: vec![],
+        failed_splits,
     })
 }
 
+/// Runs a leaf request on a task of its own, so that leaf requests progress in parallel.
+/// The request is dropped, and thereby cancelled on its node, as soon as the token is
+/// cancelled.
+async fn spawn_cancellable<T: Send + 'static>(
+    cancellation_token: CancellationToken,
+    request_future: impl Future<Output = crate::Result<T>> + Send + 'static,
+) -> crate::Result<T> {
+    tokio::spawn(
+        async move {
+            tokio::select! {
+                result = request_future => result,
+                _ = cancellation_token.cancelled() => Err(SearchError::InternalError(
+                    "Leaf request was cancelled.".to_string(),
+                )),
+            }
+        }
+        .in_current_span(),
+    )
+    .await?
+}
+
+// Reports all the splits of a request that failed as a whole as failed splits.
+fn split_search_errors(jobs: &[Job], error: &SearchError) -> Vec<SplitSearchError> {
+    jobs.iter()
+        .map(|job| SplitSearchError {
+            error: error.to_string(),
+            split_id: job.split_id.clone(),
+            retryable_error: true,
+        })
+        .collect()
+}
+
+/// Number of records accounting for one unit of split search cost.
+const NUM_RECORDS_PER_COST_UNIT: usize = 1_000_000;
+
+/// Number of bytes accounting for one unit of split search cost.
+const NUM_BYTES_PER_COST_UNIT: u64 = 100_000_000;
+
 // Measure the cost associated to searching in a given split metadata.
-fn compute_split_cost(_split_metadata: &SplitMetadata) -> u32 {
-    // TODO: Have a smarter cost, by smoothing the number of docs.
-    1
+// The cost grows linearly with the number of records and the size of the split,
+// whichever dominates, so that a large merged split weighs more than a freshly
+// indexed one. Every split costs at least 1.
+fn compute_split_cost(split_metadata: &SplitMetadata) -> u32 {
+    let num_records_cost = (split_metadata.num_records / NUM_RECORDS_PER_COST_UNIT) as u64;
+    let num_bytes_cost = split_metadata.size_in_bytes / NUM_BYTES_PER_COST_UNIT;
+    let cost = 1 + num_records_cost.max(num_bytes_cost);
+    cost.min(u32::MAX as u64) as u32
 }
 
 pub(crate) fn job_for_splits(
@@ -192,6 +283,7 @@ fn jobs_to_leaf_request(
     request: &SearchRequest,
     index_config_str: &str,
     index_uri: &str,
+    encryption_key_id_opt: Option<&str>,
     split_metadata_map: &HashMap<String, SplitMetadataAndFooterOffsets>,
     jobs: &[Job],
 ) -> LeafSearchRequest {
@@ -209,12 +301,14 @@ fn jobs_to_leaf_request(
             .collect(),
         index_config: index_config_str.to_string(),
         index_uri: index_uri.to_string(),
+        encryption_key_id: encryption_key_id_opt.map(str::to_string),
     }
 }
 
 fn jobs_to_fetch_docs_request(
     index_id: &str,
     index_uri: &str,
+    encryption_key_id_opt: Option<&str>,
     split_metadata_map: &HashMap<String, SplitMetadataAndFooterOffsets>,
     partial_hits_map: &mut HashMap<String, Vec<PartialHit>>,
     jobs: &[Job],
@@ -235,21 +329,29 @@ fn jobs_to_fetch_docs_request(
         index_id: index_id.to_string(),
         split_metadata: splits_footer_and_offsets,
         index_uri: index_uri.to_string(),
+        encryption_key_id: encryption_key_id_opt.map(str::to_string),
     }
 }
 
 #[cfg(test)]
 mod tests {
     use std::ops::Range;
+    use std::sync::atomic::{AtomicBool, Ordering as AtomicOrdering};
+    use std::time::Duration;
 
+    use async_trait::async_trait;
+    use bytes::Bytes;
     use quickwit_index_config::WikipediaIndexConfig;
     use quickwit_indexing::mock_split_meta;
     use quickwit_metastore::checkpoint::Checkpoint;
     use quickwit_metastore::{IndexMetadata, MockMetastore, SplitState};
-    use quickwit_proto::SplitSearchError;
+    use quickwit_proto::{
+        LeafSearchStreamRequest, LeafSearchStreamResult, SearchStreamRequest, SplitSearchError,
+    };
+    use tokio_stream::wrappers::ReceiverStream;
 
     use super::*;
-    use crate::MockSearchService;
+    use crate::{MockSearchService, SearchService};
 
     fn mock_partial_hit(
         split_id: &str,
@@ -279,6 +381,22 @@ mod tests {
             .collect_vec()
     }
 
+    #[test]
+    fn test_compute_split_cost() {
+        let mut split_metadata = mock_split_meta("split1").split_metadata;
+        assert_eq!(compute_split_cost(&split_metadata), 1);
+
+        split_metadata.num_records = 10_000_000;
+        assert_eq!(compute_split_cost(&split_metadata), 11);
+
+        split_metadata.size_in_bytes = 5_000_000_000;
+        assert_eq!(compute_split_cost(&split_metadata), 51);
+
+        split_metadata.num_records = 500_000;
+        split_metadata.size_in_bytes = 40_000_000;
+        assert_eq!(compute_split_cost(&split_metadata), 1);
+    }
+
     #[tokio::test]
     async fn test_root_search_single_split() -> anyhow::Result<()> {
         let search_request = quickwit_proto::SearchRequest {
@@ -290,6 +408,8 @@ mod tests {
             max_hits: 10,
             start_offset: 0,
             tags: vec![],
+            allow_partial_results: false,
+            timeout_millis: None,
         };
         let mut metastore = MockMetastore::new();
         metastore
@@ -300,6 +420,7 @@ mod tests {
                     index_uri: "file:///path/to/index/test-idx".to_string(),
                     index_config: Arc::new(WikipediaIndexConfig::new()),
                     checkpoint: Checkpoint::default(),
+                    encryption_key_id: None,
                 })
             });
         metastore.expect_list_splits().returning(
@@ -351,6 +472,8 @@ mod tests {
             max_hits: 10,
             start_offset: 0,
             tags: vec![],
+            allow_partial_results: false,
+            timeout_millis: None,
         };
         let mut metastore = MockMetastore::new();
         metastore
@@ -361,6 +484,7 @@ mod tests {
                     index_uri: "file:///path/to/index/test-idx".to_string(),
                     index_config: Arc::new(WikipediaIndexConfig::new()),
                     checkpoint: Checkpoint::default(),
+                    encryption_key_id: None,
                 })
             });
         metastore.expect_list_splits().returning(
@@ -436,6 +560,8 @@ mod tests {
             max_hits: 10,
             start_offset: 0,
             tags: vec![],
+            allow_partial_results: false,
+            timeout_millis: None,
         };
         let mut metastore = MockMetastore::new();
         metastore
@@ -446,6 +572,7 @@ mod tests {
                     index_uri: "file:///path/to/index/test-idx".to_string(),
                     index_config: Arc::new(WikipediaIndexConfig::new()),
                     checkpoint: Checkpoint::default(),
+                    encryption_key_id: None,
                 })
             });
         metastore.expect_list_splits().returning(
@@ -547,6 +674,8 @@ mod tests {
             max_hits: 10,
             start_offset: 0,
             tags: vec![],
+            allow_partial_results: false,
+            timeout_millis: None,
         };
         let mut metastore = MockMetastore::new();
         metastore
@@ -557,6 +686,7 @@ mod tests {
                     index_uri: "file:///path/to/index/test-idx".to_string(),
                     index_config: Arc::new(WikipediaIndexConfig::new()),
                     checkpoint: Checkpoint::default(),
+                    encryption_key_id: None,
                 })
             });
         metastore.expect_list_splits().returning(
@@ -674,6 +804,8 @@ mod tests {
             max_hits: 10,
             start_offset: 0,
             tags: vec![],
+            allow_partial_results: false,
+            timeout_millis: None,
         };
         let mut metastore = MockMetastore::new();
         metastore
@@ -684,6 +816,7 @@ mod tests {
                     index_uri: "file:///path/to/index/test-idx".to_string(),
                     index_config: Arc::new(WikipediaIndexConfig::new()),
                     checkpoint: Checkpoint::default(),
+                    encryption_key_id: None,
                 })
             });
         metastore.expect_list_splits().returning(
@@ -748,6 +881,8 @@ mod tests {
             max_hits: 10,
             start_offset: 0,
             tags: vec![],
+            allow_partial_results: false,
+            timeout_millis: None,
         };
         let mut metastore = MockMetastore::new();
         metastore
@@ -758,6 +893,7 @@ mod tests {
                     index_uri: "file:///path/to/index/test-idx".to_string(),
                     index_config: Arc::new(WikipediaIndexConfig::new()),
                     checkpoint: Checkpoint::default(),
+                    encryption_key_id: None,
                 })
             });
         metastore.expect_list_splits().returning(
@@ -797,6 +933,93 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_root_search_with_partial_results() -> anyhow::Result<()> {
+        let search_request = quickwit_proto::SearchRequest {
+            index_id: "test-idx".to_string(),
+            query: "test".to_string(),
+            search_fields: vec!["body".to_string()],
+            start_timestamp: None,
+            end_timestamp: None,
+            max_hits: 10,
+            start_offset: 0,
+            tags: vec![],
+            allow_partial_results: true,
+            timeout_millis: None,
+        };
+        let mut metastore = MockMetastore::new();
+        metastore
+            .expect_index_metadata()
+            .returning(|_index_id: &str| {
+                Ok(IndexMetadata {
+                    index_id: "test-idx".to_string(),
+                    index_uri: "file:///path/to/index/test-idx".to_string(),
+                    index_config: Arc::new(WikipediaIndexConfig::new()),
+                    checkpoint: Checkpoint::default(),
+                    encryption_key_id: None,
+                })
+            });
+        metastore.expect_list_splits().returning(
+            |_index_id: &str,
+             _split_state: SplitState,
+             _time_range: Option<Range<i64>>,
+             _tags: &[String]| {
+                Ok(vec![mock_split_meta("split1"), mock_split_meta("split2")])
+            },
+        );
+        let mut mock_search_service = MockSearchService::new();
+        mock_search_service.expect_leaf_search().times(4).returning(
+            |leaf_search_req: quickwit_proto::LeafSearchRequest| {
+                // split2 always fails, even on retry.
+                let partial_hits = if leaf_search_req
+                    .split_metadata
+                    .iter()
+                    .any(|split_metadata| split_metadata.split_id == "split1")
+                {
+                    vec![mock_partial_hit("split1", 3, 1)]
+                } else {
+                    Vec::new()
+                };
+                Ok(quickwit_proto::LeafSearchResponse {
+                    num_hits: partial_hits.len() as u64,
+                    partial_hits,
+                    failed_splits: vec![SplitSearchError {
+                        error: "mock_error".to_string(),
+                        split_id: "split2".to_string(),
+                        retryable_error: true,
+                    }],
+                    num_attempted_splits: leaf_search_req.split_metadata.len() as u64,
+                })
+            },
+        );
+        mock_search_service.expect_fetch_docs().returning(
+            |fetch_docs_req: quickwit_proto::FetchDocsRequest| {
+                Ok(quickwit_proto::FetchDocsResult {
+                    hits: get_doc_for_fetch_req(fetch_docs_req),
+                })
+            },
+        );
+        let client_pool =
+            Arc::new(SearchClientPool::from_mocks(vec![Arc::new(mock_search_service)]).await?);
+        let cluster_client = ClusterClient::new(client_pool.clone());
+        let search_response =
+            root_search(&search_request, &metastore, &cluster_client, &client_pool).await?;
+        assert_eq!(search_response.num_hits, 1);
+        assert_eq!(search_response.hits.len(), 1);
+        assert_eq!(search_response.failed_splits.len(), 1);
+        assert_eq!(search_response.failed_splits[0].split_id, "split2");
+
+        let search_request = quickwit_proto::SearchRequest {
+            allow_partial_results: false,
+            timeout_millis: None,
+            ..search_request
+        };
+        let search_result =
+            root_search(&search_request, &metastore, &cluster_client, &client_pool).await;
+        assert!(search_result.is_err());
+        Ok(())
+    }
+
     #[tokio::test]
     async fn test_root_search_one_splits_two_nodes_but_one_is_failing_for_split(
     ) -> anyhow::Result<()> {
@@ -809,6 +1032,8 @@ mod tests {
             max_hits: 10,
             start_offset: 0,
             tags: vec![],
+            allow_partial_results: false,
+            timeout_millis: None,
         };
         let mut metastore = MockMetastore::new();
         metastore
@@ -819,6 +1044,7 @@ mod tests {
                     index_uri: "file:///path/to/index/test-idx".to_string(),
                     index_config: Arc::new(WikipediaIndexConfig::new()),
                     checkpoint: Checkpoint::default(),
+                    encryption_key_id: None,
                 })
             });
         metastore.expect_list_splits().returning(
@@ -895,6 +1121,8 @@ mod tests {
             max_hits: 10,
             start_offset: 0,
             tags: vec![],
+            allow_partial_results: false,
+            timeout_millis: None,
         };
         let mut metastore = MockMetastore::new();
         metastore
@@ -905,6 +1133,7 @@ mod tests {
                     index_uri: "file:///path/to/index/test-idx".to_string(),
                     index_config: Arc::new(WikipediaIndexConfig::new()),
                     checkpoint: Checkpoint::default(),
+                    encryption_key_id: None,
                 })
             });
         metastore.expect_list_splits().returning(
@@ -959,4 +1188,109 @@ mod tests {
         assert_eq!(search_response.hits.len(), 1);
         Ok(())
     }
+
+    /// Search service whose leaf search requests never complete. It records when they
+    /// are dropped.
+    struct HangingSearchService {
+        leaf_search_dropped: Arc<AtomicBool>,
+    }
+
+    struct SetOnDrop(Arc<AtomicBool>);
+
+    impl Drop for SetOnDrop {
+        fn drop(&mut self) {
+            self.0.store(true, AtomicOrdering::SeqCst);
+        }
+    }
+
+    #[async_trait]
+    impl SearchService for HangingSearchService {
+        async fn root_search(&self, _request: SearchRequest) -> crate::Result<SearchResponse> {
+            unimplemented!()
+        }
+
+        async fn leaf_search(
+            &self,
+            _request: LeafSearchRequest,
+        ) -> crate::Result<LeafSearchResponse> {
+            let _set_on_drop = SetOnDrop(self.leaf_search_dropped.clone());
+            futures::future::pending().await
+        }
+
+        async fn fetch_docs(
+            &self,
+            _request: FetchDocsRequest,
+        ) -> crate::Result<quickwit_proto::FetchDocsResult> {
+            unimplemented!()
+        }
+
+        async fn root_search_stream(
+            &self,
+            _request: SearchStreamRequest,
+        ) -> crate::Result<ReceiverStream<crate::Result<Bytes>>> {
+            unimplemented!()
+        }
+
+        async fn leaf_search_stream(
+            &self,
+            _request: LeafSearchStreamRequest,
+        ) -> crate::Result<ReceiverStream<crate::Result<LeafSearchStreamResult>>> {
+            unimplemented!()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_root_search_cancels_leaf_requests_on_drop() -> anyhow::Result<()> {
+        let search_request = quickwit_proto::SearchRequest {
+            index_id: "test-idx".to_string(),
+            query: "test".to_string(),
+            search_fields: vec!["body".to_string()],
+            start_timestamp: None,
+            end_timestamp: None,
+            max_hits: 10,
+            start_offset: 0,
+            tags: vec![],
+            allow_partial_results: false,
+            timeout_millis: None,
+        };
+        let mut metastore = MockMetastore::new();
+        metastore
+            .expect_index_metadata()
+            .returning(|_index_id: &str| {
+                Ok(IndexMetadata {
+                    index_id: "test-idx".to_string(),
+                    index_uri: "file:///path/to/index/test-idx".to_string(),
+                    index_config: Arc::new(WikipediaIndexConfig::new()),
+                    checkpoint: Checkpoint::default(),
+                    encryption_key_id: None,
+                })
+            });
+        metastore.expect_list_splits().returning(
+            |_index_id: &str,
+             _split_state: SplitState,
+             _time_range: Option<Range<i64>>,
+             _tags: &[String]| { Ok(vec![mock_split_meta("split1")]) },
+        );
+        let leaf_search_dropped = Arc::new(AtomicBool::new(false));
+        let hanging_search_service = HangingSearchService {
+            leaf_search_dropped: leaf_search_dropped.clone(),
+        };
+        let client_pool =
+            Arc::new(SearchClientPool::from_mocks(vec![Arc::new(hanging_search_service)]).await?);
+        let cluster_client = ClusterClient::new(client_pool.clone());
+        let timeout_result = tokio::time::timeout(
+            Duration::from_millis(100),
+            root_search(&search_request, &metastore, &cluster_client, &client_pool),
+        )
+        .await;
+        assert!(timeout_result.is_err());
+        // The leaf request runs on a task of its own, which gets cancelled with the root search.
+        tokio::time::timeout(Duration::from_secs(1), async {
+            while !leaf_search_dropped.load(AtomicOrdering::SeqCst) {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await?;
+        Ok(())
+    }
 }
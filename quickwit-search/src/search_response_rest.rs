@@ -34,6 +34,28 @@ pub struct SearchResponseRest {
     pub hits: Vec<serde_json::Value>,
     /// Elapsed time.
     pub elapsed_time_micros: u64,
+    /// Splits that could not be searched, when partial results are allowed.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub failed_splits: Vec<FailedSplitRest>,
+}
+
+/// A split that could not be searched, along with the reason why.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FailedSplitRest {
+    /// Split ID.
+    pub split_id: String,
+    /// Reason why the split could not be searched.
+    pub error: String,
+}
+
+impl From<quickwit_proto::SplitSearchError> for FailedSplitRest {
+    fn from(split_search_error: quickwit_proto::SplitSearchError) -> Self {
+        FailedSplitRest {
+            split_id: split_search_error.split_id,
+            error: split_search_error.error,
+        }
+    }
 }
 
 impl TryFrom<quickwit_proto::SearchResponse> for SearchResponseRest {
@@ -56,6 +78,11 @@ impl TryFrom<quickwit_proto::SearchResponse> for SearchResponseRest {
             num_hits: search_response.num_hits,
             hits,
             elapsed_time_micros: search_response.elapsed_time_micros,
+            failed_splits: search_response
+                .failed_splits
+                .into_iter()
+                .map(FailedSplitRest::from)
+                .collect(),
         })
     }
 }
@@ -0,0 +1,463 @@
+// Copyright (C) 2021 Quickwit, Inc.
+//
+// Quickwit is offered under the AGPL v3.0 and as commercial software.
+// For commercial licensing, contact us at hello@quickwit.io.
+//
+// AGPL:
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+//! Arrow IPC stream and Parquet serialization of search stream rows.
+//!
+//! Leaves serialize the rows of each split as the record batch messages of an Arrow IPC
+//! stream. The root wraps them with the schema message and the end-of-stream marker,
+//! or re-encodes them as a single Parquet file.
+
+use std::io::{self, Seek, SeekFrom, Write};
+use std::sync::{Arc, Mutex};
+
+use arrow::array::{ArrayRef, Float64Array, Int64Array, TimestampSecondArray, UInt64Array};
+use arrow::datatypes::{DataType, Field, Schema, SchemaRef, TimeUnit};
+use arrow::ipc::reader::StreamReader;
+use arrow::ipc::writer::StreamWriter;
+use arrow::record_batch::RecordBatch;
+use parquet::arrow::ArrowWriter;
+use parquet::file::writer::TryClone;
+use tantivy::schema::Type;
+
+use super::{num_rows, FastFieldColumn};
+use crate::SearchError;
+
+/// Marks the end of an Arrow IPC stream.
+pub const ARROW_IPC_END_OF_STREAM: [u8; 8] = [0xFF, 0xFF, 0xFF, 0xFF, 0, 0, 0, 0];
+
+/// Dates are exported as UTC timestamps in seconds.
+fn arrow_data_type(value_type: Type) -> Option<DataType> {
+    match value_type {
+        Type::U64 => Some(DataType::UInt64),
+        Type::I64 => Some(DataType::Int64),
+        Type::F64 => Some(DataType::Float64),
+        Type::Date => Some(DataType::Timestamp(
+            TimeUnit::Second,
+            Some("UTC".to_string()),
+        )),
+        _ => None,
+    }
+}
+
+/// Builds the Arrow schema of the exported rows, one non-nullable column per fast field,
+/// typed after the field mapping of the fast field in the index config.
+pub fn arrow_schema(
+    schema: &tantivy::schema::Schema,
+    fast_field_names: &[String],
+) -> crate::Result<SchemaRef> {
+    let fields = fast_field_names
+        .iter()
+        .map(|fast_field_name| {
+            let field = schema.get_field(fast_field_name).ok_or_else(|| {
+                SearchError::InvalidQuery(format!(
+                    "Fast field `{}` does not exist.",
+                    fast_field_name
+                ))
+            })?;
+            let value_type = schema.get_field_entry(field).field_type().value_type();
+            let data_type = arrow_data_type(value_type).ok_or_else(|| {
+                SearchError::InvalidQuery(format!(
+                    "Fast field type `{:?}` of field `{}` not supported",
+                    value_type, fast_field_name
+                ))
+            })?;
+            Ok(Field::new(fast_field_name, data_type, false))
+        })
+        .collect::<crate::Result<Vec<_>>>()?;
+    Ok(Arc::new(Schema::new(fields)))
+}
+
+fn into_io_error<E>(error: E) -> io::Error
+where E: Into<Box<dyn std::error::Error + Send + Sync>> {
+    io::Error::new(io::ErrorKind::Other, error)
+}
+
+fn to_record_batch(schema: SchemaRef, columns: &[FastFieldColumn]) -> io::Result<RecordBatch> {
+    let arrays = columns
+        .iter()
+        .map(|column| -> ArrayRef {
+            match column {
+                FastFieldColumn::U64(values) => {
+                    Arc::new(UInt64Array::from_iter_values(values.iter().copied()))
+                }
+                FastFieldColumn::I64(values) => {
+                    Arc::new(Int64Array::from_iter_values(values.iter().copied()))
+                }
+                FastFieldColumn::F64(values) => {
+                    Arc::new(Float64Array::from_iter_values(values.iter().copied()))
+                }
+                FastFieldColumn::Date(values) => Arc::new(TimestampSecondArray::from_vec(
+                    values.iter().map(|value| value.timestamp()).collect(),
+                    Some("UTC".to_string()),
+                )),
+            }
+        })
+        .collect();
+    RecordBatch::try_new(schema, arrays).map_err(into_io_error)
+}
+
+/// Writes a complete Arrow IPC stream holding the given record batches.
+fn write_arrow_ipc_stream(
+    schema: &Schema,
+    record_batches: &[RecordBatch],
+    buffer: &mut Vec<u8>,
+) -> io::Result<()> {
+    let mut stream_writer = StreamWriter::try_new(&mut *buffer, schema).map_err(into_io_error)?;
+    for record_batch in record_batches {
+        stream_writer.write(record_batch).map_err(into_io_error)?;
+    }
+    stream_writer.finish().map_err(into_io_error)?;
+    // Dropping the writer flushes it into the buffer.
+    drop(stream_writer);
+    if !buffer.ends_with(&ARROW_IPC_END_OF_STREAM) {
+        return Err(into_io_error(
+            "Arrow IPC stream is missing its end-of-stream marker.",
+        ));
+    }
+    Ok(())
+}
+
+/// Returns the beginning of an Arrow IPC stream, i.e. its schema message.
+pub fn arrow_ipc_stream_header(schema: &Schema) -> io::Result<Vec<u8>> {
+    let mut buffer = Vec::new();
+    write_arrow_ipc_stream(schema, &[], &mut buffer)?;
+    buffer.truncate(buffer.len() - ARROW_IPC_END_OF_STREAM.len());
+    Ok(buffer)
+}
+
+/// Serializes the columns into the `buffer` as Arrow IPC record batch messages, to be
+/// inserted between the header of an Arrow IPC stream and its end-of-stream marker.
+///
+/// No record batch is written if there are no rows. In particular, splits without segments
+/// have no columns at all.
+///
+/// Please note that the `buffer` is always cleared.
+pub fn serialize_arrow_ipc_record_batch(
+    columns: &[FastFieldColumn],
+    schema: SchemaRef,
+    buffer: &mut Vec<u8>,
+) -> io::Result<()> {
+    buffer.clear();
+    if num_rows(columns) == 0 {
+        return Ok(());
+    }
+    let header_len = arrow_ipc_stream_header(&schema)?.len();
+    let record_batch = to_record_batch(schema.clone(), columns)?;
+    write_arrow_ipc_stream(&schema, &[record_batch], buffer)?;
+    buffer.truncate(buffer.len() - ARROW_IPC_END_OF_STREAM.len());
+    buffer.drain(..header_len);
+    Ok(())
+}
+
+/// Rows are buffered until their Arrow arrays hold that many bytes, and then written as
+/// one row group.
+const MAX_ROW_GROUP_NUM_BYTES: usize = if cfg!(test) { 10_000 } else { 16_000_000 };
+
+/// Parquet sink keeping the bytes written until they are taken, so that a Parquet file can
+/// be sent while it is being written.
+///
+/// Parquet writers append to their sink and only seek it to know their current position.
+#[derive(Clone, Default)]
+struct ParquetSink {
+    inner: Arc<Mutex<ParquetSinkInner>>,
+}
+
+#[derive(Default)]
+struct ParquetSinkInner {
+    buffer: Vec<u8>,
+    position: u64,
+}
+
+impl ParquetSink {
+    /// Returns the bytes written since the last call.
+    fn take_bytes(&self) -> Vec<u8> {
+        std::mem::take(&mut self.inner.lock().unwrap().buffer)
+    }
+}
+
+impl Write for ParquetSink {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut inner = self.inner.lock().unwrap();
+        inner.buffer.extend_from_slice(buf);
+        inner.position += buf.len() as u64;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Seek for ParquetSink {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        match pos {
+            SeekFrom::Current(0) => Ok(self.inner.lock().unwrap().position),
+            _ => Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "Parquet sink can only be appended to.",
+            )),
+        }
+    }
+}
+
+impl TryClone for ParquetSink {
+    fn try_clone(&self) -> io::Result<Self> {
+        Ok(self.clone())
+    }
+}
+
+/// Re-encodes the Arrow IPC record batches produced by the leaves as a Parquet file.
+///
+/// The file is produced as it is written: its magic number first, then its row groups as
+/// soon as they hold [`MAX_ROW_GROUP_NUM_BYTES`], and its footer once all the rows are
+/// known. A file without rows has no row groups.
+///
+/// Encoding is CPU intensive: it is meant to run on the blocking thread pool.
+pub struct ParquetEncoder {
+    arrow_ipc_stream_header: Vec<u8>,
+    schema: SchemaRef,
+    sink: ParquetSink,
+    arrow_writer: ArrowWriter<ParquetSink>,
+    pending_record_batches: Vec<RecordBatch>,
+    num_pending_bytes: usize,
+}
+
+impl ParquetEncoder {
+    pub fn try_new(schema: SchemaRef) -> io::Result<Self> {
+        let arrow_ipc_stream_header = arrow_ipc_stream_header(&schema)?;
+        let sink = ParquetSink::default();
+        let arrow_writer =
+            ArrowWriter::try_new(sink.clone(), schema.clone(), None).map_err(into_io_error)?;
+        Ok(ParquetEncoder {
+            arrow_ipc_stream_header,
+            schema,
+            sink,
+            arrow_writer,
+            pending_record_batches: Vec::new(),
+            num_pending_bytes: 0,
+        })
+    }
+
+    /// Appends the rows of a chunk of Arrow IPC record batch messages, and returns the
+    /// bytes of the file that are ready to be sent, if any.
+    pub fn write_arrow_ipc_record_batches(&mut self, chunk: &[u8]) -> io::Result<Vec<u8>> {
+        let arrow_ipc_stream = [
+            &self.arrow_ipc_stream_header[..],
+            chunk,
+            &ARROW_IPC_END_OF_STREAM[..],
+        ]
+        .concat();
+        let stream_reader =
+            StreamReader::try_new(io::Cursor::new(arrow_ipc_stream)).map_err(into_io_error)?;
+        for record_batch_result in stream_reader {
+            let record_batch = record_batch_result.map_err(into_io_error)?;
+            self.num_pending_bytes += record_batch
+                .columns()
+                .iter()
+                .map(|array| array.get_array_memory_size())
+                .sum::<usize>();
+            self.pending_record_batches.push(record_batch);
+            if self.num_pending_bytes >= MAX_ROW_GROUP_NUM_BYTES {
+                self.write_row_group()?;
+            }
+        }
+        Ok(self.sink.take_bytes())
+    }
+
+    fn write_row_group(&mut self) -> io::Result<()> {
+        if self.pending_record_batches.is_empty() {
+            return Ok(());
+        }
+        let record_batch = RecordBatch::concat(&self.schema, &self.pending_record_batches)
+            .map_err(into_io_error)?;
+        self.pending_record_batches.clear();
+        self.num_pending_bytes = 0;
+        self.arrow_writer
+            .write(&record_batch)
+            .map_err(into_io_error)
+    }
+
+    /// Writes the rows left and the Parquet footer, and returns the end of the file.
+    pub fn finish(mut self) -> io::Result<Vec<u8>> {
+        self.write_row_group()?;
+        self.arrow_writer.close().map_err(into_io_error)?;
+        Ok(self.sink.take_bytes())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use parquet::file::reader::FileReader;
+    use parquet::file::serialized_reader::{SerializedFileReader, SliceableCursor};
+    use tantivy::chrono::{TimeZone, Utc};
+    use tantivy::schema::{Cardinality, IntOptions, SchemaBuilder, TEXT};
+
+    use super::*;
+
+    fn test_schema() -> tantivy::schema::Schema {
+        let mut schema_builder = SchemaBuilder::new();
+        let fast_options = IntOptions::default().set_fast(Cardinality::SingleValue);
+        schema_builder.add_text_field("body", TEXT);
+        schema_builder.add_i64_field("ts", fast_options.clone());
+        schema_builder.add_f64_field("latency", fast_options.clone());
+        schema_builder.add_date_field("created_at", fast_options);
+        schema_builder.build()
+    }
+
+    fn test_columns() -> Vec<FastFieldColumn> {
+        vec![
+            FastFieldColumn::I64(vec![1, 2]),
+            FastFieldColumn::F64(vec![0.5, 1.5]),
+            FastFieldColumn::Date(vec![Utc.timestamp(1, 0), Utc.timestamp(2, 0)]),
+        ]
+    }
+
+    fn test_fast_field_names() -> Vec<String> {
+        vec![
+            "ts".to_string(),
+            "latency".to_string(),
+            "created_at".to_string(),
+        ]
+    }
+
+    #[test]
+    fn test_arrow_schema() -> anyhow::Result<()> {
+        let schema = arrow_schema(&test_schema(), &test_fast_field_names())?;
+        assert_eq!(
+            schema.fields(),
+            &vec![
+                Field::new("ts", DataType::Int64, false),
+                Field::new("latency", DataType::Float64, false),
+                Field::new(
+                    "created_at",
+                    DataType::Timestamp(TimeUnit::Second, Some("UTC".to_string())),
+                    false
+                ),
+            ]
+        );
+        assert!(matches!(
+            arrow_schema(&test_schema(), &["body".to_string()]),
+            Err(SearchError::InvalidQuery(_))
+        ));
+        assert!(matches!(
+            arrow_schema(&test_schema(), &["missing".to_string()]),
+            Err(SearchError::InvalidQuery(_))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_arrow_ipc_record_batches_concatenate_into_a_stream() -> anyhow::Result<()> {
+        let schema = arrow_schema(&test_schema(), &test_fast_field_names())?;
+        let mut arrow_ipc_stream = arrow_ipc_stream_header(&schema)?;
+        let mut buffer = Vec::new();
+        for _ in 0..2 {
+            serialize_arrow_ipc_record_batch(&test_columns(), schema.clone(), &mut buffer)?;
+            arrow_ipc_stream.extend_from_slice(&buffer);
+        }
+        arrow_ipc_stream.extend_from_slice(&ARROW_IPC_END_OF_STREAM);
+        let record_batches = StreamReader::try_new(io::Cursor::new(arrow_ipc_stream))?
+            .collect::<Result<Vec<_>, _>>()?;
+        assert_eq!(record_batches.len(), 2);
+        for record_batch in record_batches {
+            assert_eq!(record_batch.schema(), schema);
+            assert_eq!(record_batch.num_rows(), 2);
+            let latencies = record_batch
+                .column(1)
+                .as_any()
+                .downcast_ref::<Float64Array>()
+                .unwrap();
+            assert_eq!(latencies.value(1), 1.5);
+            let dates = record_batch
+                .column(2)
+                .as_any()
+                .downcast_ref::<TimestampSecondArray>()
+                .unwrap();
+            assert_eq!(dates.value(1), 2);
+        }
+        Ok(())
+    }
+
+    fn parquet_num_rows_per_row_group(parquet_file: Vec<u8>) -> anyhow::Result<Vec<i64>> {
+        let file_reader = SerializedFileReader::new(SliceableCursor::new(parquet_file))?;
+        Ok(file_reader
+            .metadata()
+            .row_groups()
+            .iter()
+            .map(|row_group| row_group.num_rows())
+            .collect())
+    }
+
+    #[test]
+    fn test_parquet_encoder() -> anyhow::Result<()> {
+        let schema = arrow_schema(&test_schema(), &test_fast_field_names())?;
+        let mut parquet_encoder = ParquetEncoder::try_new(schema.clone())?;
+        let mut buffer = Vec::new();
+        serialize_arrow_ipc_record_batch(&test_columns(), schema, &mut buffer)?;
+        let mut parquet_file = parquet_encoder.write_arrow_ipc_record_batches(&buffer)?;
+        assert_eq!(parquet_file, b"PAR1");
+        parquet_file.extend(parquet_encoder.write_arrow_ipc_record_batches(&buffer)?);
+        parquet_file.extend(parquet_encoder.finish()?);
+        assert!(parquet_file.ends_with(b"PAR1"));
+        // Small chunks are buffered into a single row group.
+        assert_eq!(parquet_num_rows_per_row_group(parquet_file)?, vec![4]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_parquet_encoder_streams_row_groups() -> anyhow::Result<()> {
+        let schema = arrow_schema(&test_schema(), &["ts".to_string()])?;
+        let mut parquet_encoder = ParquetEncoder::try_new(schema.clone())?;
+        let num_rows = MAX_ROW_GROUP_NUM_BYTES / 8;
+        let columns = vec![FastFieldColumn::I64((0..num_rows as i64).collect())];
+        let mut buffer = Vec::new();
+        serialize_arrow_ipc_record_batch(&columns, schema, &mut buffer)?;
+        let mut parquet_file = parquet_encoder.write_arrow_ipc_record_batches(&buffer)?;
+        assert!(parquet_file.len() > 4);
+        let parquet_end = parquet_encoder.finish()?;
+        assert!(parquet_end.len() < parquet_file.len());
+        parquet_file.extend(parquet_end);
+        assert_eq!(
+            parquet_num_rows_per_row_group(parquet_file)?,
+            vec![num_rows as i64]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_parquet_encoder_without_rows() -> anyhow::Result<()> {
+        let schema = arrow_schema(&test_schema(), &test_fast_field_names())?;
+        let mut parquet_encoder = ParquetEncoder::try_new(schema.clone())?;
+        let mut buffer = Vec::new();
+        // Splits without segments have no columns at all.
+        serialize_arrow_ipc_record_batch(&[], schema.clone(), &mut buffer)?;
+        assert!(buffer.is_empty());
+        let empty_columns = vec![
+            FastFieldColumn::I64(Vec::new()),
+            FastFieldColumn::F64(Vec::new()),
+            FastFieldColumn::Date(Vec::new()),
+        ];
+        serialize_arrow_ipc_record_batch(&empty_columns, schema, &mut buffer)?;
+        assert!(buffer.is_empty());
+        let mut parquet_file = parquet_encoder.write_arrow_ipc_record_batches(&buffer)?;
+        parquet_file.extend(parquet_encoder.finish()?);
+        assert!(parquet_num_rows_per_row_group(parquet_file)?.is_empty());
+        Ok(())
+    }
+}
@@ -18,35 +18,136 @@
 // along with this program. If not, see <http://www.gnu.org/licenses/>.
 
 use std::collections::HashSet;
-use std::marker::PhantomData;
 
 use tantivy::collector::{Collector, SegmentCollector};
 use tantivy::fastfield::{DynamicFastFieldReader, FastFieldReader, FastValue};
 use tantivy::schema::{Field, Type};
-use tantivy::{DocId, Score, SegmentOrdinal, SegmentReader, TantivyError};
+use tantivy::{DateTime, DocId, Score, SegmentOrdinal, SegmentReader, TantivyError};
+use tokio_util::sync::CancellationToken;
 
 use crate::filters::TimestampFilter;
 use crate::SearchError;
 
-#[derive(Clone)]
-pub struct FastFieldSegmentCollector<Item: FastValue> {
-    fast_field_values: Vec<Item>,
-    fast_field_reader: DynamicFastFieldReader<Item>,
-    timestamp_filter_opt: Option<TimestampFilter>,
+/// The values of a fast field collected for the matching documents, in document order.
+#[derive(Clone, Debug, PartialEq)]
+pub enum FastFieldColumn {
+    U64(Vec<u64>),
+    I64(Vec<i64>),
+    F64(Vec<f64>),
+    Date(Vec<DateTime>),
 }
 
-impl<Item: FastValue> FastFieldSegmentCollector<Item> {
-    pub fn new(
-        fast_field_reader: DynamicFastFieldReader<Item>,
-        timestamp_filter_opt: Option<TimestampFilter>,
-    ) -> Self {
-        Self {
-            fast_field_values: vec![],
-            fast_field_reader,
-            timestamp_filter_opt,
+impl FastFieldColumn {
+    pub(crate) fn len(&self) -> usize {
+        match self {
+            FastFieldColumn::U64(values) => values.len(),
+            FastFieldColumn::I64(values) => values.len(),
+            FastFieldColumn::F64(values) => values.len(),
+            FastFieldColumn::Date(values) => values.len(),
+        }
+    }
+
+    /// Appends the values of `other`, which must hold values of the same type.
+    fn append(&mut self, other: FastFieldColumn) {
+        match (self, other) {
+            (FastFieldColumn::U64(values), FastFieldColumn::U64(other_values)) => {
+                values.extend(other_values)
+            }
+            (FastFieldColumn::I64(values), FastFieldColumn::I64(other_values)) => {
+                values.extend(other_values)
+            }
+            (FastFieldColumn::F64(values), FastFieldColumn::F64(other_values)) => {
+                values.extend(other_values)
+            }
+            (FastFieldColumn::Date(values), FastFieldColumn::Date(other_values)) => {
+                values.extend(other_values)
+            }
+            _ => panic!("Fast field columns of different types cannot be appended."),
+        }
+    }
+}
+
+/// Collects the values of a single fast field for a segment.
+enum FastFieldColumnSegmentCollector {
+    U64(DynamicFastFieldReader<u64>, Vec<u64>),
+    I64(DynamicFastFieldReader<i64>, Vec<i64>),
+    F64(DynamicFastFieldReader<f64>, Vec<f64>),
+    Date(DynamicFastFieldReader<DateTime>, Vec<DateTime>),
+}
+
+impl FastFieldColumnSegmentCollector {
+    fn open(
+        segment_reader: &SegmentReader,
+        fast_field_name: &str,
+        value_type: Type,
+    ) -> tantivy::Result<Self> {
+        let field = segment_reader
+            .schema()
+            .get_field(fast_field_name)
+            .ok_or_else(|| TantivyError::SchemaError("field does not exist".to_owned()))?;
+        let column_collector = match value_type {
+            Type::U64 => FastFieldColumnSegmentCollector::U64(
+                open_fast_field_reader(segment_reader, field)?,
+                Vec::new(),
+            ),
+            Type::I64 => FastFieldColumnSegmentCollector::I64(
+                open_fast_field_reader(segment_reader, field)?,
+                Vec::new(),
+            ),
+            Type::F64 => FastFieldColumnSegmentCollector::F64(
+                open_fast_field_reader(segment_reader, field)?,
+                Vec::new(),
+            ),
+            Type::Date => FastFieldColumnSegmentCollector::Date(
+                open_fast_field_reader(segment_reader, field)?,
+                Vec::new(),
+            ),
+            _ => {
+                return Err(TantivyError::SchemaError(format!(
+                    "Fast field type `{:?}` not supported",
+                    value_type
+                )))
+            }
+        };
+        Ok(column_collector)
+    }
+
+    fn collect(&mut self, doc_id: DocId) {
+        match self {
+            FastFieldColumnSegmentCollector::U64(reader, values) => values.push(reader.get(doc_id)),
+            FastFieldColumnSegmentCollector::I64(reader, values) => values.push(reader.get(doc_id)),
+            FastFieldColumnSegmentCollector::F64(reader, values) => values.push(reader.get(doc_id)),
+            FastFieldColumnSegmentCollector::Date(reader, values) => {
+                values.push(reader.get(doc_id))
+            }
+        }
+    }
+
+    fn harvest(self) -> FastFieldColumn {
+        match self {
+            FastFieldColumnSegmentCollector::U64(_, values) => FastFieldColumn::U64(values),
+            FastFieldColumnSegmentCollector::I64(_, values) => FastFieldColumn::I64(values),
+            FastFieldColumnSegmentCollector::F64(_, values) => FastFieldColumn::F64(values),
+            FastFieldColumnSegmentCollector::Date(_, values) => FastFieldColumn::Date(values),
         }
     }
+}
+
+fn open_fast_field_reader<Item: FastValue>(
+    segment_reader: &SegmentReader,
+    field: Field,
+) -> tantivy::Result<DynamicFastFieldReader<Item>> {
+    // TODO: would be nice to access directly to typed_fast_field_reader
+    let fast_field_slice = segment_reader.fast_fields().fast_field_data(field, 0)?;
+    DynamicFastFieldReader::open(fast_field_slice)
+}
 
+pub struct FastFieldSegmentCollector {
+    column_collectors: Vec<FastFieldColumnSegmentCollector>,
+    timestamp_filter_opt: Option<TimestampFilter>,
+}
+
+impl FastFieldSegmentCollector {
     fn accept_document(&self, doc_id: DocId) -> bool {
         if let Some(ref timestamp_filter) = self.timestamp_filter_opt {
             return timestamp_filter.is_within_range(doc_id);
@@ -55,40 +156,52 @@ impl<Item: FastValue> FastFieldSegmentCollector<Item> {
     }
 }
 
-impl<Item: FastValue> SegmentCollector for FastFieldSegmentCollector<Item> {
-    type Fruit = Vec<Item>;
+impl SegmentCollector for FastFieldSegmentCollector {
+    type Fruit = Vec<FastFieldColumn>;
 
     fn collect(&mut self, doc_id: DocId, _score: Score) {
         if !self.accept_document(doc_id) {
             return;
         }
-        let fast_field_value = self.fast_field_reader.get(doc_id);
-        self.fast_field_values.push(fast_field_value);
+        for column_collector in &mut self.column_collectors {
+            column_collector.collect(doc_id);
+        }
     }
 
-    fn harvest(self) -> Vec<Item> {
-        self.fast_field_values
+    fn harvest(self) -> Vec<FastFieldColumn> {
+        self.column_collectors
+            .into_iter()
+            .map(FastFieldColumnSegmentCollector::harvest)
+            .collect()
     }
 }
 
+/// Collects the values of several fast fields, one column per fast field.
 #[derive(Clone)]
-pub struct FastFieldCollector<Item: FastValue> {
-    pub fast_field_to_collect: String,
+pub struct FastFieldCollector {
+    pub fast_fields: Vec<(String, Type)>,
     pub timestamp_field_opt: Option<Field>,
     pub start_timestamp_opt: Option<i64>,
     pub end_timestamp_opt: Option<i64>,
-    _marker: PhantomData<Item>,
+    /// Checked before collecting each segment, so that the collection
+    /// stops early once nobody is waiting for its result anymore.
+    pub cancellation_token: CancellationToken,
 }
 
-impl<Item: FastValue> Collector for FastFieldCollector<Item> {
-    type Child = FastFieldSegmentCollector<Item>;
-    type Fruit = Vec<Item>;
+impl Collector for FastFieldCollector {
+    type Child = FastFieldSegmentCollector;
+    type Fruit = Vec<FastFieldColumn>;
 
     fn for_segment(
         &self,
         _segment_ord: SegmentOrdinal,
         segment_reader: &SegmentReader,
     ) -> tantivy::Result<Self::Child> {
+        if self.cancellation_token.is_cancelled() {
+            return Err(TantivyError::InvalidArgument(
+                "Fast field collection was cancelled.".to_owned(),
+            ));
+        }
         let timestamp_filter_opt = if let Some(timestamp_field) = self.timestamp_field_opt {
             TimestampFilter::new(
                 timestamp_field,
@@ -99,17 +212,17 @@ impl<Item: FastValue> Collector for FastFieldCollector<Item> {
         } else {
             None
         };
-        let field = segment_reader
-            .schema()
-            .get_field(&self.fast_field_to_collect)
-            .ok_or_else(|| TantivyError::SchemaError("field does not exist".to_owned()))?;
-        // TODO: would be nice to access directly to typed_fast_field_reader
-        let fast_field_slice = segment_reader.fast_fields().fast_field_data(field, 0)?;
-        let fast_field_reader = DynamicFastFieldReader::open(fast_field_slice)?;
-        Ok(FastFieldSegmentCollector::new(
-            fast_field_reader,
+        let column_collectors = self
+            .fast_fields
+            .iter()
+            .map(|(fast_field_name, value_type)| {
+                FastFieldColumnSegmentCollector::open(segment_reader, fast_field_name, *value_type)
+            })
+            .collect::<tantivy::Result<_>>()?;
+        Ok(FastFieldSegmentCollector {
+            column_collectors,
             timestamp_filter_opt,
-        ))
+        })
     }
 
     fn requires_scoring(&self) -> bool {
@@ -117,81 +230,97 @@ impl<Item: FastValue> Collector for FastFieldCollector<Item> {
         false
     }
 
-    fn merge_fruits(&self, segment_fruits: Vec<Vec<Item>>) -> tantivy::Result<Self::Fruit> {
-        Ok(segment_fruits.into_iter().flatten().collect::<Vec<_>>())
+    fn merge_fruits(
+        &self,
+        segment_fruits: Vec<Vec<FastFieldColumn>>,
+    ) -> tantivy::Result<Self::Fruit> {
+        let mut segment_fruits_iter = segment_fruits.into_iter();
+        let mut columns = match segment_fruits_iter.next() {
+            Some(columns) => columns,
+            None => return Ok(Vec::new()),
+        };
+        for segment_columns in segment_fruits_iter {
+            for (column, segment_column) in columns.iter_mut().zip(segment_columns) {
+                column.append(segment_column);
+            }
+        }
+        Ok(columns)
     }
 }
 
 #[derive(Clone)]
 pub struct FastFieldCollectorBuilder {
-    fast_field_value_type: Type,
-    fast_field_name: String,
+    fast_fields: Vec<(String, Type)>,
     timestamp_field_name: Option<String>,
     timestamp_field: Option<Field>,
     start_timestamp: Option<i64>,
     end_timestamp: Option<i64>,
+    cancellation_token: CancellationToken,
 }
 
 impl FastFieldCollectorBuilder {
+    /// Creates a builder for collectors extracting the given fast fields, given
+    /// as pairs of field name and value type.
     pub fn new(
-        fast_field_value_type: Type,
-        fast_field_name: String,
+        fast_fields: Vec<(String, Type)>,
         timestamp_field_name: Option<String>,
         timestamp_field: Option<Field>,
         start_timestamp: Option<i64>,
         end_timestamp: Option<i64>,
     ) -> crate::Result<Self> {
-        match fast_field_value_type {
-            Type::U64 | Type::I64 => (),
-            _ => {
-                return Err(SearchError::InvalidQuery(format!(
-                    "Fast field type `{:?}` not supported",
-                    fast_field_value_type
-                )));
+        if fast_fields.is_empty() {
+            return Err(SearchError::InvalidQuery(
+                "At least one fast field must be requested".to_owned(),
+            ));
+        }
+        for (fast_field_name, value_type) in &fast_fields {
+            match value_type {
+                Type::U64 | Type::I64 | Type::F64 | Type::Date => (),
+                _ => {
+                    return Err(SearchError::InvalidQuery(format!(
+                        "Fast field type `{:?}` of field `{}` not supported",
+                        value_type, fast_field_name
+                    )));
+                }
             }
         }
         Ok(Self {
-            fast_field_value_type,
-            fast_field_name,
+            fast_fields,
             timestamp_field_name,
             timestamp_field,
             start_timestamp,
             end_timestamp,
+            cancellation_token: CancellationToken::new(),
         })
     }
 
-    pub fn value_type(&self) -> Type {
-        self.fast_field_value_type
+    /// Makes the built collectors stop between segments once `cancellation_token` is cancelled.
+    pub fn with_cancellation_token(mut self, cancellation_token: CancellationToken) -> Self {
+        self.cancellation_token = cancellation_token;
+        self
     }
 
     pub fn fast_field_to_warm(&self) -> HashSet<String> {
-        let mut fields = HashSet::new();
-        fields.insert(self.fast_field_name.clone());
+        let mut fields: HashSet<String> = self
+            .fast_fields
+            .iter()
+            .map(|(fast_field_name, _)| fast_field_name.clone())
+            .collect();
         if let Some(timestamp_field_name) = &self.timestamp_field_name {
             fields.insert(timestamp_field_name.clone());
         }
         fields
     }
 
-    pub fn typed_build<TFastValue: FastValue>(&self) -> FastFieldCollector<TFastValue> {
-        FastFieldCollector::<TFastValue> {
-            fast_field_to_collect: self.fast_field_name.clone(),
+    pub fn build(&self) -> FastFieldCollector {
+        FastFieldCollector {
+            fast_fields: self.fast_fields.clone(),
             timestamp_field_opt: self.timestamp_field,
             start_timestamp_opt: self.start_timestamp,
             end_timestamp_opt: self.end_timestamp,
-            _marker: PhantomData,
+            cancellation_token: self.cancellation_token.clone(),
         }
     }
-
-    pub fn build_i64(&self) -> FastFieldCollector<i64> {
-        // TODO: check type
-        self.typed_build::<i64>()
-    }
-
-    pub fn build_u64(&self) -> FastFieldCollector<u64> {
-        // TODO: check type
-        self.typed_build::<u64>()
-    }
 }
 
 #[cfg(test)]
@@ -203,8 +332,7 @@ mod tests {
     #[test]
     fn test_fast_field_collector_builder() -> anyhow::Result<()> {
         let builder = FastFieldCollectorBuilder::new(
-            Type::U64,
-            "field_name".to_string(),
+            vec![("field_name".to_string(), Type::U64)],
             Some("field_name".to_string()),
             None,
             None,
@@ -215,8 +343,10 @@ mod tests {
             HashSet::from_iter(["field_name".to_string()])
         );
         let builder = FastFieldCollectorBuilder::new(
-            Type::U64,
-            "field_name".to_string(),
+            vec![
+                ("field_name".to_string(), Type::U64),
+                ("other_field_name".to_string(), Type::F64),
+            ],
             Some("timestamp_field_name".to_string()),
             None,
             None,
@@ -224,8 +354,64 @@ mod tests {
         )?;
         assert_eq!(
             builder.fast_field_to_warm(),
-            HashSet::from_iter(["field_name".to_string(), "timestamp_field_name".to_string()])
+            HashSet::from_iter([
+                "field_name".to_string(),
+                "other_field_name".to_string(),
+                "timestamp_field_name".to_string()
+            ])
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_fast_field_collector_builder_unsupported_fields() {
+        assert!(matches!(
+            FastFieldCollectorBuilder::new(Vec::new(), None, None, None, None),
+            Err(SearchError::InvalidQuery(_))
+        ));
+        assert!(matches!(
+            FastFieldCollectorBuilder::new(
+                vec![("body".to_string(), Type::Str)],
+                None,
+                None,
+                None,
+                None
+            ),
+            Err(SearchError::InvalidQuery(_))
+        ));
+    }
+
+    #[test]
+    fn test_fast_field_collector_merge_fruits() -> anyhow::Result<()> {
+        let collector = FastFieldCollectorBuilder::new(
+            vec![
+                ("ts".to_string(), Type::I64),
+                ("latency".to_string(), Type::F64),
+            ],
+            None,
+            None,
+            None,
+            None,
+        )?
+        .build();
+        let columns = collector.merge_fruits(vec![
+            vec![
+                FastFieldColumn::I64(vec![1, 2]),
+                FastFieldColumn::F64(vec![0.5, 1.5]),
+            ],
+            vec![
+                FastFieldColumn::I64(vec![3]),
+                FastFieldColumn::F64(vec![2.5]),
+            ],
+        ])?;
+        assert_eq!(
+            columns,
+            vec![
+                FastFieldColumn::I64(vec![1, 2, 3]),
+                FastFieldColumn::F64(vec![0.5, 1.5, 2.5]),
+            ]
         );
+        assert!(collector.merge_fruits(Vec::new())?.is_empty());
         Ok(())
     }
 }
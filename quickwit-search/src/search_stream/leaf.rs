@@ -17,25 +17,29 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program. If not, see <http://www.gnu.org/licenses/>.
 
+use std::cmp::Reverse;
 use std::sync::Arc;
 
+use arrow::datatypes::SchemaRef;
+use futures::stream::BoxStream;
 use futures::{FutureExt, StreamExt};
 use quickwit_index_config::IndexConfig;
 use quickwit_proto::{
-    LeafSearchStreamResult, OutputFormat, SearchRequest, SearchStreamRequest,
+    LeafSearchStreamResult, OutputFormat, SearchRequest, SearchStreamRequest, SortOrder,
     SplitIdAndFooterOffsets,
 };
-use quickwit_storage::Storage;
+use quickwit_storage::{Cache, Storage};
 use tantivy::query::Query;
-use tantivy::schema::Type;
 use tantivy::{LeasedItem, ReloadPolicy, Searcher};
 use tokio::task::spawn_blocking;
-use tokio_stream::wrappers::UnboundedReceiverStream;
+use tokio_stream::wrappers::ReceiverStream;
+use tokio_util::sync::CancellationToken;
 use tracing::*;
 
-use super::FastFieldCollectorBuilder;
+use super::rows::Rows;
+use super::{FastFieldCollectorBuilder, FastFieldColumn, SEARCH_STREAM_BUFFER_CAPACITY};
 use crate::leaf::{open_index, warmup};
-use crate::SearchError;
+use crate::{AdmissionPermit, SearchError};
 
 // TODO: buffer of 5 seems to be sufficient to do the job locally, needs to be tested on a cluster.
 const CONCURRENT_SPLIT_SEARCH_STREAM: usize = 5;
@@ -47,87 +51,279 @@ const CONCURRENT_SPLIT_SEARCH_STREAM: usize = 5;
 // to process stream in grpc_adapater.rs to change SearchError
 // to tonic::Status as tonic::Status is required by the stream result
 // signature defined by proto generated code.
+//
+// The admission permit, if any, is held until all the splits have been searched.
+//
+// Dropping the returned stream cancels the search: splits that have not been
+// searched yet are skipped and the ongoing collections stop at the next segment.
+// The returned stream is bounded: splits are searched at the pace results are consumed.
 pub async fn leaf_search_stream(
     request: SearchStreamRequest,
     storage: Arc<dyn Storage>,
     splits: Vec<SplitIdAndFooterOffsets>,
     index_config: Arc<dyn IndexConfig>,
-) -> UnboundedReceiverStream<crate::Result<LeafSearchStreamResult>> {
-    let (result_sender, result_receiver) = tokio::sync::mpsc::unbounded_channel();
+    split_cache_opt: Option<Arc<dyn Cache>>,
+    admission_permit_opt: Option<AdmissionPermit>,
+) -> ReceiverStream<crate::Result<LeafSearchStreamResult>> {
+    let (result_sender, result_receiver) =
+        tokio::sync::mpsc::channel(SEARCH_STREAM_BUFFER_CAPACITY);
     let span = info_span!("leaf_search_stream",);
+    let cancellation_token = CancellationToken::new();
     tokio::spawn(
         async move {
-            let mut stream =
-                leaf_search_results_stream(request, storage, splits, index_config).await;
-            while let Some(item) = stream.next().await {
-                if let Err(error) = result_sender.send(item) {
-                    error!(
-                        "Failed to send leaf search stream result. Stop sending. Cause: {}",
-                        error
-                    );
-                    break;
+            let _admission_permit_opt = admission_permit_opt;
+            let _cancel_on_exit_guard = cancellation_token.clone().drop_guard();
+            let mut stream = leaf_search_results_stream(
+                request,
+                storage,
+                splits,
+                index_config,
+                split_cache_opt,
+                cancellation_token,
+            )
+            .await;
+            loop {
+                tokio::select! {
+                    item_opt = stream.next() => {
+                        let item = match item_opt {
+                            Some(item) => item,
+                            None => break,
+                        };
+                        if let Err(error) = result_sender.send(item).await {
+                            error!(
+                                "Failed to send leaf search stream result. Stop sending. Cause: {}",
+                                error
+                            );
+                            break;
+                        }
+                    }
+                    _ = result_sender.closed() => {
+                        info!("Leaf search stream receiver dropped. Cancelling search.");
+                        break;
+                    }
                 }
             }
         }
         .instrument(span),
     );
-    UnboundedReceiverStream::new(result_receiver)
+    ReceiverStream::new(result_receiver)
 }
 
 async fn leaf_search_results_stream(
     request: SearchStreamRequest,
     storage: Arc<dyn Storage>,
-    splits: Vec<SplitIdAndFooterOffsets>,
+    mut splits: Vec<SplitIdAndFooterOffsets>,
     index_config: Arc<dyn IndexConfig>,
-) -> impl futures::Stream<Item = crate::Result<LeafSearchStreamResult>> + Sync + Send + 'static {
-    futures::stream::iter(splits)
-        .map(move |split| {
-            leaf_search_stream_single_split(
-                split,
-                index_config.clone(),
-                request.clone(),
-                storage.clone(),
-            )
-            .shared()
-        })
-        .buffer_unordered(CONCURRENT_SPLIT_SEARCH_STREAM)
+    split_cache_opt: Option<Arc<dyn Cache>>,
+    cancellation_token: CancellationToken,
+) -> BoxStream<'static, crate::Result<LeafSearchStreamResult>> {
+    let sort_order = match SortOrder::from_i32(request.sort_order) {
+        Some(sort_order) => sort_order,
+        None => {
+            let error = SearchError::InvalidQuery("Invalid sort order specified.".to_string());
+            return futures::stream::once(async { Err(error) }).boxed();
+        }
+    };
+    let limit_opt = request.limit.map(|limit| limit as usize);
+    let num_fast_fields = request.fast_fields.len();
+    // Splits are searched in time order so that the rows of a split can be sent as soon as
+    // no split left to search can hold rows coming before them.
+    match sort_order {
+        SortOrder::Unsorted => {}
+        SortOrder::Asc => splits.sort_by_key(|split| split_sort_bound(split, sort_order)),
+        SortOrder::Desc => splits.sort_by_key(|split| Reverse(split_sort_bound(split, sort_order))),
+    }
+    let split_bounds: Vec<i64> = if sort_order == SortOrder::Unsorted {
+        Vec::new()
+    } else {
+        splits
+            .iter()
+            .skip(1)
+            .map(|split| split_sort_bound(split, sort_order))
+            .collect()
+    };
+    let split_results = futures::stream::iter(splits).map(move |split| {
+        leaf_search_stream_single_split(
+            split,
+            index_config.clone(),
+            request.clone(),
+            storage.clone(),
+            split_cache_opt.clone(),
+            cancellation_token.clone(),
+        )
+        .shared()
+    });
+    let split_results = if sort_order == SortOrder::Unsorted {
+        split_results
+            .buffer_unordered(CONCURRENT_SPLIT_SEARCH_STREAM)
+            .boxed()
+    } else {
+        split_results
+            .buffered(CONCURRENT_SPLIT_SEARCH_STREAM)
+            .boxed()
+    };
+    if sort_order == SortOrder::Unsorted && limit_opt.is_none() {
+        return split_results;
+    }
+    let split_rows_merger = SplitRowsMerger {
+        sort_order,
+        remaining_rows_opt: limit_opt,
+        pending_rows: Rows::new(num_fast_fields),
+        next_split_bounds: split_bounds.into_iter(),
+        num_fast_fields,
+    };
+    merge_split_rows(split_results, split_rows_merger).boxed()
+}
+
+/// Timestamp of the first row a split can hold in the given order.
+fn split_sort_bound(split: &SplitIdAndFooterOffsets, sort_order: SortOrder) -> i64 {
+    match sort_order {
+        SortOrder::Desc => split.timestamp_end.unwrap_or(i64::MAX),
+        _ => split.timestamp_start.unwrap_or(i64::MIN),
+    }
+}
+
+/// Merges the sorted rows of the splits, received in time order, and stops once the limit
+/// is reached, cancelling the search of the splits left.
+struct SplitRowsMerger {
+    sort_order: SortOrder,
+    remaining_rows_opt: Option<usize>,
+    pending_rows: Rows,
+    /// Timestamps of the first rows the splits left to receive can hold.
+    next_split_bounds: std::vec::IntoIter<i64>,
+    num_fast_fields: usize,
+}
+
+impl SplitRowsMerger {
+    /// Adds the rows of the next split and returns the rows that cannot be preceded by
+    /// the rows of the splits left.
+    fn add_split_rows(&mut self, split_rows: Rows) -> Rows {
+        let pending_rows = std::mem::replace(&mut self.pending_rows, Rows::new(0));
+        let mut ready_rows = pending_rows.merge_sorted(split_rows, self.sort_order);
+        if let Some(remaining_rows) = self.remaining_rows_opt {
+            ready_rows.truncate(remaining_rows);
+        }
+        let num_ready_rows = match self.next_split_bounds.next() {
+            Some(next_split_bound) => ready_rows.num_rows_before(next_split_bound, self.sort_order),
+            None => ready_rows.len(),
+        };
+        self.pending_rows = ready_rows.split_off(num_ready_rows);
+        if let Some(remaining_rows) = self.remaining_rows_opt.as_mut() {
+            *remaining_rows -= ready_rows.len();
+        }
+        ready_rows
+    }
+
+    fn is_done(&self) -> bool {
+        self.remaining_rows_opt == Some(0)
+    }
+}
+
+fn merge_split_rows(
+    split_results: BoxStream<'static, crate::Result<LeafSearchStreamResult>>,
+    split_rows_merger: SplitRowsMerger,
+) -> impl futures::Stream<Item = crate::Result<LeafSearchStreamResult>> + Send + 'static {
+    futures::stream::unfold(
+        Some((split_results, split_rows_merger)),
+        |state_opt| async move {
+            let (mut split_results, mut split_rows_merger) = state_opt?;
+            while !split_rows_merger.is_done() {
+                let split_result = match split_results.next().await? {
+                    Ok(split_result) => split_result,
+                    // Like a failed request, the stream stops at the first error.
+                    Err(error) => return Some((Err(error), None)),
+                };
+                let split_id = split_result.split_id.clone();
+                let split_rows =
+                    match Rows::from_leaf_result(split_result, split_rows_merger.num_fast_fields) {
+                        Ok(split_rows) => split_rows,
+                        Err(error) => return Some((Err(error), None)),
+                    };
+                let ready_rows = split_rows_merger.add_split_rows(split_rows);
+                if !ready_rows.is_empty() {
+                    let leaf_result = ready_rows.into_leaf_result(split_id);
+                    return Some((Ok(leaf_result), Some((split_results, split_rows_merger))));
+                }
+            }
+            None
+        },
+    )
 }
 
 /// Apply a leaf search on a single split.
-#[instrument(fields(split_id = %split.split_id), skip(split, index_config, stream_request, storage))]
+#[instrument(fields(split_id = %split.split_id), skip(split, index_config, stream_request, storage, split_cache_opt, cancellation_token))]
 async fn leaf_search_stream_single_split(
     split: SplitIdAndFooterOffsets,
     index_config: Arc<dyn IndexConfig>,
     stream_request: SearchStreamRequest,
     storage: Arc<dyn Storage>,
+    split_cache_opt: Option<Arc<dyn Cache>>,
+    cancellation_token: CancellationToken,
 ) -> crate::Result<LeafSearchStreamResult> {
-    let index = open_index(storage, &split).await?;
+    if cancellation_token.is_cancelled() {
+        return Err(SearchError::InternalError(format!(
+            "Search stream was cancelled before searching split {}.",
+            split.split_id
+        )));
+    }
+    let index = open_index(storage, &split, split_cache_opt).await?;
     let split_schema = index.schema();
-    let fast_field_to_extract = stream_request.fast_field.clone();
-    let fast_field = split_schema
-        .get_field(&fast_field_to_extract)
-        .ok_or_else(|| {
-            SearchError::InvalidQuery(format!(
-                "Fast field `{}` does not exist for split {}.",
-                fast_field_to_extract, split.split_id,
-            ))
+    let fast_fields_to_extract = stream_request.fast_fields.join(",");
+    let output_format = OutputFormat::from_i32(stream_request.output_format).ok_or_else(|| {
+        SearchError::InternalError(format!(
+            "Invalid output format specified for split {}.",
+            split.split_id
+        ))
+    })?;
+    let sort_order = SortOrder::from_i32(stream_request.sort_order).ok_or_else(|| {
+        SearchError::InternalError(format!(
+            "Invalid sort order specified for split {}.",
+            split.split_id
+        ))
+    })?;
+    let split_output = if sort_order == SortOrder::Unsorted && stream_request.limit.is_none() {
+        SplitOutput::Serialized(output_format)
+    } else {
+        SplitOutput::Rows {
+            sort_order,
+            limit_opt: stream_request.limit.map(|limit| limit as usize),
+        }
+    };
+    let mut fast_field_names = stream_request.fast_fields.clone();
+    if sort_order != SortOrder::Unsorted {
+        // The timestamps are collected as an extra column, used to sort the rows.
+        let timestamp_field_name = index_config.timestamp_field_name().ok_or_else(|| {
+            SearchError::InvalidQuery(
+                "Sorting a search stream requires a timestamp field.".to_string(),
+            )
         })?;
-    let fast_field_type = split_schema.get_field_entry(fast_field).field_type();
+        fast_field_names.push(timestamp_field_name);
+    }
+    let fast_fields = fast_field_names
+        .iter()
+        .map(|fast_field_name| {
+            let fast_field = split_schema.get_field(fast_field_name).ok_or_else(|| {
+                SearchError::InvalidQuery(format!(
+                    "Fast field `{}` does not exist for split {}.",
+                    fast_field_name, split.split_id,
+                ))
+            })?;
+            let fast_field_type = split_schema.get_field_entry(fast_field).field_type();
+            Ok((fast_field_name.clone(), fast_field_type.value_type()))
+        })
+        .collect::<crate::Result<Vec<_>>>()?;
     let fast_field_collector_builder = FastFieldCollectorBuilder::new(
-        fast_field_type.value_type(),
-        stream_request.fast_field.clone(),
+        fast_fields,
         index_config.timestamp_field_name(),
         index_config.timestamp_field(&split_schema),
         stream_request.start_timestamp,
         stream_request.end_timestamp,
-    )?;
+    )?
+    .with_cancellation_token(cancellation_token);
 
-    let output_format = OutputFormat::from_i32(stream_request.output_format).ok_or_else(|| {
-        SearchError::InternalError(format!(
-            "Invalid output format specified for split {}.",
-            split.split_id
-        ))
-    })?;
+    // Arrow columns are typed after the index config, so that the record batches of all
+    // the splits share the schema sent once by the root.
+    let arrow_schema = super::arrow_schema(&index_config.schema(), &stream_request.fast_fields)?;
     let search_request = SearchRequest::from(stream_request.clone());
     let query = index_config.query(split_schema, &search_request)?;
 
@@ -143,68 +339,101 @@ async fn leaf_search_stream_single_split(
         &fast_field_collector_builder.fast_field_to_warm(),
     )
     .await?;
+    let split_id = split.split_id.clone();
     let collect_handle = spawn_blocking(move || {
         collect_fast_field_values(
             &fast_field_collector_builder,
             &searcher,
             query,
-            output_format,
+            arrow_schema,
+            split_output,
+            split_id,
         )
     });
     let span = info_span!(
         "collect_fast_field",
         split_id = %split.split_id,
-        fast_field=%fast_field_to_extract,
+        fast_fields=%fast_fields_to_extract,
     );
     let _ = span.enter();
-    let buffer = collect_handle.await.map_err(|error| {
-        error!(split_id = %split.split_id, fast_field=%fast_field_to_extract, error_message=%error, "Failed to collect fast field");
+    let leaf_result = collect_handle.await.map_err(|error| {
+        error!(split_id = %split.split_id, fast_fields=%fast_fields_to_extract, error_message=%error, "Failed to collect fast field");
         SearchError::InternalError(format!("Error when collecting fast field values for split {}: {:?}", split.split_id, error))
     })??;
-    Ok(LeafSearchStreamResult {
-        data: buffer,
-        split_id: split.split_id.clone(),
-    })
+    Ok(leaf_result)
+}
+
+/// How the values collected on a split are sent to the root.
+#[derive(Clone, Copy, Debug)]
+enum SplitOutput {
+    /// Rows serialized in the requested output format.
+    Serialized(OutputFormat),
+    /// Rows in the ClickHouse RowBinary format, along with their timestamps if they are
+    /// sorted, so that they can be merged and limited before being serialized by the root.
+    Rows {
+        sort_order: SortOrder,
+        limit_opt: Option<usize>,
+    },
 }
 
 fn collect_fast_field_values(
     fast_field_collector_builder: &FastFieldCollectorBuilder,
     searcher: &LeasedItem<Searcher>,
     query: Box<dyn Query>,
-    output_format: OutputFormat,
-) -> crate::Result<Vec<u8>> {
-    let mut buffer = Vec::new();
-    match fast_field_collector_builder.value_type() {
-        Type::I64 => {
-            let fast_field_collector = fast_field_collector_builder.build_i64();
-            let fast_field_values = searcher.search(query.as_ref(), &fast_field_collector)?;
-            super::serialize::<i64>(&fast_field_values, &mut buffer, output_format).map_err(
-                |_| {
-                    SearchError::InternalError(
-                        "Error when serializing i64 during export".to_owned(),
-                    )
-                },
-            )?;
-        }
-        Type::U64 => {
-            let fast_field_collector = fast_field_collector_builder.build_u64();
-            let fast_field_values = searcher.search(query.as_ref(), &fast_field_collector)?;
-            super::serialize::<u64>(&fast_field_values, &mut buffer, output_format).map_err(
-                |_| {
-                    SearchError::InternalError(
-                        "Error when serializing u64 during export".to_owned(),
-                    )
-                },
-            )?;
+    arrow_schema: SchemaRef,
+    split_output: SplitOutput,
+    split_id: String,
+) -> crate::Result<LeafSearchStreamResult> {
+    let fast_field_collector = fast_field_collector_builder.build();
+    let mut fast_field_columns = searcher.search(query.as_ref(), &fast_field_collector)?;
+    match split_output {
+        SplitOutput::Serialized(output_format) => {
+            let mut buffer = Vec::new();
+            super::serialize(
+                &fast_field_columns,
+                arrow_schema,
+                &mut buffer,
+                output_format,
+            )
+            .map_err(|_| {
+                SearchError::InternalError(
+                    "Error when serializing fast field values during export".to_owned(),
+                )
+            })?;
+            Ok(LeafSearchStreamResult {
+                data: buffer,
+                split_id,
+                sort_values: Vec::new(),
+            })
         }
-        value_type => {
-            return Err(SearchError::InvalidQuery(format!(
-                "Fast field type `{:?}` not supported",
-                value_type
-            )));
+        SplitOutput::Rows {
+            sort_order,
+            limit_opt,
+        } => {
+            let sort_values = if sort_order == SortOrder::Unsorted {
+                Vec::new()
+            } else {
+                match fast_field_columns.pop() {
+                    Some(FastFieldColumn::I64(timestamps)) => timestamps,
+                    Some(FastFieldColumn::U64(timestamps)) => timestamps
+                        .into_iter()
+                        .map(|timestamp| timestamp as i64)
+                        .collect(),
+                    _ => {
+                        return Err(SearchError::InternalError(
+                            "Timestamp field must be of type i64 or u64.".to_string(),
+                        ))
+                    }
+                }
+            };
+            let mut rows = Rows::from_columns(&fast_field_columns, sort_values);
+            rows.sort(sort_order);
+            if let Some(limit) = limit_opt {
+                rows.truncate(limit);
+            }
+            Ok(rows.into_leaf_result(split_id))
         }
     }
-    Ok(buffer)
 }
 
 #[cfg(test)]
@@ -212,9 +441,11 @@ mod tests {
     use std::str::from_utf8;
     use std::sync::Arc;
 
+    use futures::TryStreamExt;
     use quickwit_index_config::DefaultIndexConfigBuilder;
     use quickwit_indexing::TestSandbox;
     use serde_json::json;
+    use tantivy::schema::Type;
 
     use super::*;
 
@@ -259,9 +490,12 @@ mod tests {
             search_fields: vec![],
             start_timestamp: None,
             end_timestamp: Some(end_timestamp),
-            fast_field: "ts".to_string(),
+            fast_fields: vec!["ts".to_string()],
             output_format: 0,
             tags: vec![],
+            csv_header: false,
+            sort_order: 0,
+            limit: None,
         };
         let index_metadata = test_sandbox.metastore().index_metadata(index_id).await?;
         let splits = test_sandbox.metastore().list_all_splits(index_id).await?;
@@ -271,6 +505,8 @@ mod tests {
                 split_id: split_meta.split_metadata.split_id,
                 split_footer_start: split_meta.footer_offsets.start,
                 split_footer_end: split_meta.footer_offsets.end,
+                timestamp_start: None,
+                timestamp_end: None,
             })
             .collect();
         let mut single_node_stream = leaf_search_stream(
@@ -280,6 +516,8 @@ mod tests {
                 .resolve(&index_metadata.index_uri)?,
             splits_offsets,
             index_config,
+            None,
+            None,
         )
         .await;
         let res = single_node_stream.next().await.expect("no leaf result")?;
@@ -289,4 +527,181 @@ mod tests {
         );
         Ok(())
     }
+
+    #[tokio::test]
+    async fn test_leaf_search_stream_multiple_fast_fields() -> anyhow::Result<()> {
+        let index_config = r#"{
+            "default_search_fields": ["body"],
+            "timestamp_field": "ts",
+            "tag_fields": [],
+            "field_mappings": [
+                {
+                    "name": "body",
+                    "type": "text"
+                },
+                {
+                    "name": "ts",
+                    "type": "i64",
+                    "fast": true
+                },
+                {
+                    "name": "latency",
+                    "type": "f64",
+                    "fast": true
+                },
+                {
+                    "name": "created_at",
+                    "type": "date",
+                    "fast": true
+                }
+            ]
+        }"#;
+        let index_config =
+            Arc::new(serde_json::from_str::<DefaultIndexConfigBuilder>(index_config)?.build()?);
+        let index_id = "multiple-fast-fields";
+        let test_sandbox = TestSandbox::create(index_id, index_config.clone()).await?;
+        let docs: Vec<serde_json::Value> = (1..=3)
+            .map(|i| {
+                json!({
+                    "body": "info",
+                    "ts": i,
+                    "latency": i as f64 + 0.5,
+                    "created_at": format!("2021-01-0{}T00:00:00Z", i),
+                })
+            })
+            .collect();
+        test_sandbox.add_documents(docs).await?;
+
+        let request = SearchStreamRequest {
+            index_id: index_id.to_string(),
+            query: "info".to_string(),
+            search_fields: vec![],
+            start_timestamp: None,
+            end_timestamp: None,
+            fast_fields: vec![
+                "ts".to_string(),
+                "latency".to_string(),
+                "created_at".to_string(),
+            ],
+            output_format: 0,
+            tags: vec![],
+            csv_header: false,
+            sort_order: 0,
+            limit: None,
+        };
+        let index_metadata = test_sandbox.metastore().index_metadata(index_id).await?;
+        let splits = test_sandbox.metastore().list_all_splits(index_id).await?;
+        let splits_offsets = splits
+            .into_iter()
+            .map(|split_meta| SplitIdAndFooterOffsets {
+                split_id: split_meta.split_metadata.split_id,
+                split_footer_start: split_meta.footer_offsets.start,
+                split_footer_end: split_meta.footer_offsets.end,
+                timestamp_start: None,
+                timestamp_end: None,
+            })
+            .collect();
+        let mut single_node_stream = leaf_search_stream(
+            request,
+            test_sandbox
+                .storage_uri_resolver()
+                .resolve(&index_metadata.index_uri)?,
+            splits_offsets,
+            index_config,
+            None,
+            None,
+        )
+        .await;
+        let res = single_node_stream.next().await.expect("no leaf result")?;
+        assert_eq!(
+            from_utf8(&res.data)?,
+            "1,1.5,2021-01-01T00:00:00+00:00\n2,2.5,2021-01-02T00:00:00+00:00\n3,3.5,\
+             2021-01-03T00:00:00+00:00\n"
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_leaf_search_stream_sorted_with_limit() -> anyhow::Result<()> {
+        let index_config = r#"{
+            "default_search_fields": ["body"],
+            "timestamp_field": "ts",
+            "tag_fields": [],
+            "field_mappings": [
+                {
+                    "name": "body",
+                    "type": "text"
+                },
+                {
+                    "name": "ts",
+                    "type": "i64",
+                    "fast": true
+                },
+                {
+                    "name": "latency",
+                    "type": "u64",
+                    "fast": true
+                }
+            ]
+        }"#;
+        let index_config =
+            Arc::new(serde_json::from_str::<DefaultIndexConfigBuilder>(index_config)?.build()?);
+        let index_id = "sorted-with-limit";
+        let test_sandbox = TestSandbox::create(index_id, index_config.clone()).await?;
+        // One split per batch, with overlapping time ranges.
+        for timestamps in [[1, 5, 9], [2, 3, 4], [6, 7, 8]] {
+            let docs = timestamps
+                .iter()
+                .map(
+                    |timestamp| json!({"body": "info", "ts": timestamp, "latency": timestamp * 10}),
+                )
+                .collect::<Vec<_>>();
+            test_sandbox.add_documents(docs).await?;
+        }
+        let request = SearchStreamRequest {
+            index_id: index_id.to_string(),
+            query: "info".to_string(),
+            search_fields: vec![],
+            start_timestamp: None,
+            end_timestamp: None,
+            fast_fields: vec!["latency".to_string()],
+            output_format: OutputFormat::Csv as i32,
+            tags: vec![],
+            csv_header: false,
+            sort_order: SortOrder::Desc as i32,
+            limit: Some(4),
+        };
+        let index_metadata = test_sandbox.metastore().index_metadata(index_id).await?;
+        let splits = test_sandbox.metastore().list_all_splits(index_id).await?;
+        assert_eq!(splits.len(), 3);
+        let splits_offsets = splits
+            .iter()
+            .map(crate::extract_split_and_footer_offsets)
+            .collect();
+        let leaf_results: Vec<LeafSearchStreamResult> = leaf_search_stream(
+            request,
+            test_sandbox
+                .storage_uri_resolver()
+                .resolve(&index_metadata.index_uri)?,
+            splits_offsets,
+            index_config,
+            None,
+            None,
+        )
+        .await
+        .try_collect()
+        .await?;
+        let mut rows = Rows::new(1);
+        for leaf_result in leaf_results {
+            let leaf_rows = Rows::from_leaf_result(leaf_result, 1)?;
+            for row in 0..leaf_rows.len() {
+                rows.push_row(&leaf_rows, row);
+            }
+        }
+        assert_eq!(
+            rows.to_columns(&[Type::U64])?,
+            vec![FastFieldColumn::U64(vec![90, 80, 70, 60])]
+        );
+        Ok(())
+    }
 }
@@ -17,76 +17,179 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program. If not, see <http://www.gnu.org/licenses/>.
 
+mod arrow_format;
 mod collector;
 mod leaf;
 mod root;
+mod rows;
 
-use std::fmt::Display;
 use std::io;
 use std::io::Write;
 
-pub use collector::{FastFieldCollector, FastFieldCollectorBuilder};
+use arrow::datatypes::SchemaRef;
+pub use arrow_format::{
+    arrow_ipc_stream_header, arrow_schema, ParquetEncoder, ARROW_IPC_END_OF_STREAM,
+};
+pub use collector::{FastFieldCollectorBuilder, FastFieldColumn};
 pub use leaf::leaf_search_stream;
-use quickwit_proto::OutputFormat;
+use quickwit_proto::{OutputFormat, SearchStreamRequest, SortOrder};
 pub use root::root_search_stream;
-use tantivy::fastfield::FastValue;
 
-/// Serialize the values into the `buffer` as bytes.
+/// Number of results buffered between two stages of a search stream.
+///
+/// Once the buffer is full, the producer waits for the consumer, so that a slow
+/// client slows down the root, which in turn slows down the leaves.
+pub(crate) const SEARCH_STREAM_BUFFER_CAPACITY: usize = 3;
+
+/// Returns true if the leaves send rows, merged across splits, rather than serialized values.
+/// See [`rows::Rows`].
+pub(crate) fn is_sorted_or_limited(request: &SearchStreamRequest) -> bool {
+    request.sort_order != SortOrder::Unsorted as i32 || request.limit.is_some()
+}
+
+/// Serialize the columns into the `buffer` as bytes, one row per document.
+///
+/// For the Arrow IPC stream and Parquet formats, leaves only produce Arrow IPC record
+/// batches typed after `arrow_schema`. The root is in charge of wrapping them into a
+/// proper stream or file.
 ///
 /// Please note that the `buffer` is always cleared.
-pub fn serialize<TFastValue: FastValue + Display>(
-    values: &[TFastValue],
+pub fn serialize(
+    columns: &[FastFieldColumn],
+    arrow_schema: SchemaRef,
     buffer: &mut Vec<u8>,
     format: OutputFormat,
 ) -> io::Result<()> {
     match format {
-        OutputFormat::Csv => serialize_csv(values, buffer),
-        OutputFormat::ClickHouseRowBinary => serialize_click_house_row_binary(values, buffer),
+        OutputFormat::Csv => serialize_csv(columns, buffer),
+        OutputFormat::ClickHouseRowBinary => serialize_click_house_row_binary(columns, buffer),
+        OutputFormat::ArrowIpcStream | OutputFormat::Parquet => {
+            arrow_format::serialize_arrow_ipc_record_batch(columns, arrow_schema, buffer)
+        }
     }
 }
 
-fn serialize_csv<TFastValue: FastValue + Display>(
-    values: &[TFastValue],
-    buffer: &mut Vec<u8>,
-) -> io::Result<()> {
+/// Serialize the CSV header row naming the columns into the `buffer`.
+///
+/// Please note that the `buffer` is always cleared.
+pub fn serialize_csv_header(fast_field_names: &[String], buffer: &mut Vec<u8>) -> io::Result<()> {
+    buffer.clear();
+    writeln!(buffer, "{}", fast_field_names.join(","))
+}
+
+fn num_rows(columns: &[FastFieldColumn]) -> usize {
+    columns.first().map(FastFieldColumn::len).unwrap_or(0)
+}
+
+fn serialize_csv(columns: &[FastFieldColumn], buffer: &mut Vec<u8>) -> io::Result<()> {
     buffer.clear();
-    for value in values {
-        writeln!(buffer, "{}", value)?;
+    for row in 0..num_rows(columns) {
+        for (column_ord, column) in columns.iter().enumerate() {
+            if column_ord > 0 {
+                buffer.push(b',');
+            }
+            match column {
+                FastFieldColumn::U64(values) => write!(buffer, "{}", values[row])?,
+                FastFieldColumn::I64(values) => write!(buffer, "{}", values[row])?,
+                FastFieldColumn::F64(values) => write!(buffer, "{}", values[row])?,
+                FastFieldColumn::Date(values) => write!(buffer, "{}", values[row].to_rfc3339())?,
+            }
+        }
+        buffer.push(b'\n');
     }
     Ok(())
 }
 
-fn serialize_click_house_row_binary<TFastValue: FastValue + Display>(
-    values: &[TFastValue],
+/// Dates are serialized as ClickHouse `DateTime64(0)`, i.e. as a number of seconds
+/// since the Unix epoch.
+fn serialize_click_house_row_binary(
+    columns: &[FastFieldColumn],
     buffer: &mut Vec<u8>,
 ) -> io::Result<()> {
     buffer.clear();
-    buffer.reserve_exact(std::mem::size_of::<TFastValue>() * values.len());
-    for value in values {
-        buffer.extend(value.as_u64().to_le_bytes());
+    let num_rows = num_rows(columns);
+    // All the supported types are 8 bytes long.
+    buffer.reserve_exact(8 * columns.len() * num_rows);
+    for row in 0..num_rows {
+        for column in columns {
+            match column {
+                FastFieldColumn::U64(values) => buffer.extend(values[row].to_le_bytes()),
+                FastFieldColumn::I64(values) => buffer.extend(values[row].to_le_bytes()),
+                FastFieldColumn::F64(values) => buffer.extend(values[row].to_le_bytes()),
+                FastFieldColumn::Date(values) => {
+                    buffer.extend(values[row].timestamp().to_le_bytes())
+                }
+            }
+        }
     }
     Ok(())
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::search_stream::{serialize_click_house_row_binary, serialize_csv};
+    use tantivy::chrono::{TimeZone, Utc};
+
+    use crate::search_stream::{
+        serialize_click_house_row_binary, serialize_csv, serialize_csv_header, FastFieldColumn,
+    };
 
     #[test]
     fn test_serialize_row_binary() {
         let mut buffer = Vec::new();
-        serialize_click_house_row_binary::<i64>(&[-10i64], &mut buffer).unwrap();
+        serialize_click_house_row_binary(&[FastFieldColumn::I64(vec![-10i64])], &mut buffer)
+            .unwrap();
         assert_eq!(buffer, (-10i64).to_le_bytes());
 
         let mut buffer = Vec::new();
-        serialize_click_house_row_binary::<f64>(&[-10f64], &mut buffer).unwrap();
+        serialize_click_house_row_binary(&[FastFieldColumn::F64(vec![-10f64])], &mut buffer)
+            .unwrap();
         assert_eq!(buffer, (-10f64).to_le_bytes());
     }
 
+    #[test]
+    fn test_serialize_row_binary_multiple_columns() {
+        let mut buffer = Vec::new();
+        let columns = [
+            FastFieldColumn::Date(vec![Utc.timestamp(1, 0), Utc.timestamp(2, 0)]),
+            FastFieldColumn::U64(vec![3u64, 4u64]),
+        ];
+        serialize_click_house_row_binary(&columns, &mut buffer).unwrap();
+        let expected_buffer: Vec<u8> = [
+            1i64.to_le_bytes(),
+            3u64.to_le_bytes(),
+            2i64.to_le_bytes(),
+            4u64.to_le_bytes(),
+        ]
+        .concat();
+        assert_eq!(buffer, expected_buffer);
+    }
+
     #[test]
     fn test_serialize_csv() {
         let mut buffer = Vec::new();
-        serialize_csv::<i64>(&[-10i64], &mut buffer).unwrap();
+        serialize_csv(&[FastFieldColumn::I64(vec![-10i64])], &mut buffer).unwrap();
         assert_eq!(buffer, "-10\n".as_bytes());
     }
+
+    #[test]
+    fn test_serialize_csv_multiple_columns() {
+        let mut buffer = Vec::new();
+        let columns = [
+            FastFieldColumn::Date(vec![Utc.timestamp(1, 0), Utc.timestamp(2, 0)]),
+            FastFieldColumn::U64(vec![3u64, 4u64]),
+            FastFieldColumn::F64(vec![0.5f64, 1.25f64]),
+        ];
+        serialize_csv(&columns, &mut buffer).unwrap();
+        assert_eq!(
+            buffer,
+            "1970-01-01T00:00:01+00:00,3,0.5\n1970-01-01T00:00:02+00:00,4,1.25\n".as_bytes()
+        );
+    }
+
+    #[test]
+    fn test_serialize_csv_header() {
+        let mut buffer = Vec::new();
+        serialize_csv_header(&["ts".to_string(), "latency".to_string()], &mut buffer).unwrap();
+        assert_eq!(buffer, "ts,latency\n".as_bytes());
+    }
 }
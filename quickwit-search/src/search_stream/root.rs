@@ -20,34 +20,69 @@
 use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
 
+use arrow::datatypes::SchemaRef;
 use bytes::Bytes;
-use futures::{StreamExt, TryStreamExt};
+use futures::stream::BoxStream;
+use futures::{FutureExt, Stream, StreamExt, TryStreamExt};
+use quickwit_index_config::IndexConfig;
 use quickwit_metastore::{Metastore, SplitMetadataAndFooterOffsets};
-use quickwit_proto::{LeafSearchStreamRequest, SearchRequest, SearchStreamRequest};
+use quickwit_proto::{
+    LeafSearchStreamRequest, LeafSearchStreamResult, OutputFormat, SearchRequest,
+    SearchStreamRequest, SortOrder,
+};
+use tantivy::schema::Type;
+use tokio::sync::mpsc::Sender;
+use tokio::task::spawn_blocking;
+use tokio_stream::wrappers::ReceiverStream;
 use tracing::*;
 
+use super::rows::{is_before, Rows};
+use super::{
+    arrow_ipc_stream_header, arrow_schema, is_sorted_or_limited, serialize, serialize_csv_header,
+    ParquetEncoder, ARROW_IPC_END_OF_STREAM, SEARCH_STREAM_BUFFER_CAPACITY,
+};
 use crate::client_pool::Job;
 use crate::cluster_client::ClusterClient;
-use crate::root::{job_for_splits, MAX_CONCURRENT_LEAF_TASKS};
+use crate::root::job_for_splits;
 use crate::{
     extract_split_and_footer_offsets, list_relevant_splits, ClientPool, SearchClientPool,
     SearchError,
 };
 
 /// Perform a distributed search stream.
+///
+/// The returned stream forwards the leaves' data chunks as soon as they arrive. It is bounded:
+/// the leaves are only asked for more data as the chunks are consumed, and dropping it
+/// cancels the leaf streams.
 #[instrument(skip(metastore, cluster_client, client_pool))]
 pub async fn root_search_stream(
     search_stream_request: &SearchStreamRequest,
     metastore: &dyn Metastore,
     cluster_client: &ClusterClient,
     client_pool: &Arc<SearchClientPool>,
-) -> Result<Vec<Bytes>, SearchError> {
+) -> Result<ReceiverStream<crate::Result<Bytes>>, SearchError> {
     let start_instant = tokio::time::Instant::now();
+    if search_stream_request.fast_fields.is_empty() {
+        return Err(SearchError::InvalidQuery(
+            "At least one fast field must be requested".to_string(),
+        ));
+    }
+    let output_format = OutputFormat::from_i32(search_stream_request.output_format)
+        .ok_or_else(|| SearchError::InvalidQuery("Invalid output format".to_string()))?;
+    let sort_order = SortOrder::from_i32(search_stream_request.sort_order)
+        .ok_or_else(|| SearchError::InvalidQuery("Invalid sort order".to_string()))?;
     // TODO: building a search request should not be necessary for listing splits.
     // This needs some refactoring: relevant splits, metadata_map, jobs...
     let search_request = SearchRequest::from(search_stream_request.clone());
     let split_metadata_list = list_relevant_splits(&search_request, metastore).await?;
     let index_metadata = metastore.index_metadata(&search_request.index_id).await?;
+    if sort_order != SortOrder::Unsorted
+        && index_metadata.index_config.timestamp_field_name().is_none()
+    {
+        return Err(SearchError::InvalidQuery(
+            "Sorting a search stream requires a timestamp field".to_string(),
+        ));
+    }
 
     // Create a hash map of SplitMetadata with split id as a key.
     let split_metadata_map: HashMap<String, SplitMetadataAndFooterOffsets> = split_metadata_list
@@ -65,31 +100,391 @@ pub async fn root_search_stream(
     let index_config_str = serde_json::to_string(&index_metadata.index_config).map_err(|err| {
         SearchError::InternalError(format!("Could not serialize index config {}", err))
     })?;
-    let bytes: Vec<_> = futures::stream::iter(assigned_leaf_search_jobs.into_iter())
+    let leaf_streams = assigned_leaf_search_jobs
+        .into_iter()
         .map(|(client, client_jobs)| {
             let leaf_request = jobs_to_leaf_request(
                 search_stream_request,
                 &index_config_str,
                 &index_metadata.index_uri,
+                index_metadata.encryption_key_id.as_deref(),
                 &split_metadata_map,
                 &client_jobs,
             );
             cluster_client.leaf_search_stream((leaf_request, client))
+        });
+    let leaf_streams = futures::future::join_all(leaf_streams).await;
+    let leaf_data_stream = if is_sorted_or_limited(search_stream_request) {
+        let index_schema = index_metadata.index_config.schema();
+        let fast_field_types = search_stream_request
+            .fast_fields
+            .iter()
+            .map(|fast_field_name| {
+                let fast_field = index_schema.get_field(fast_field_name).ok_or_else(|| {
+                    SearchError::InvalidQuery(format!(
+                        "Fast field `{}` does not exist.",
+                        fast_field_name
+                    ))
+                })?;
+                Ok(index_schema
+                    .get_field_entry(fast_field)
+                    .field_type()
+                    .value_type())
+            })
+            .collect::<crate::Result<Vec<_>>>()?;
+        let leaf_streams = if sort_order == SortOrder::Unsorted {
+            // Rows are sent in arrival order: the leaf streams are merged as a single one.
+            vec![futures::stream::select_all(leaf_streams).boxed()]
+        } else {
+            leaf_streams
+                .into_iter()
+                .map(|leaf_stream| leaf_stream.boxed())
+                .collect()
+        };
+        let num_leaf_streams = leaf_streams.len();
+        let leaf_rows_merger = LeafRowsMerger {
+            leaf_streams,
+            leaf_rows: (0..num_leaf_streams)
+                .map(|_| Some((Rows::new(fast_field_types.len()), 0)))
+                .collect(),
+            sort_order,
+            remaining_rows_opt: search_stream_request.limit.map(|limit| limit as usize),
+            arrow_schema: arrow_schema(&index_schema, &search_stream_request.fast_fields)?,
+            fast_field_types,
+            output_format,
+        };
+        merge_leaf_rows(leaf_rows_merger).boxed()
+    } else {
+        futures::stream::select_all(leaf_streams)
+            .map_ok(|response| Bytes::from(response.data))
+            .boxed()
+    };
+    let (data_sender, data_receiver) = tokio::sync::mpsc::channel(SEARCH_STREAM_BUFFER_CAPACITY);
+    let data_stream_future = match output_format {
+        OutputFormat::Parquet => {
+            let arrow_schema = arrow_schema(
+                &index_metadata.index_config.schema(),
+                &search_stream_request.fast_fields,
+            )?;
+            let parquet_encoder = ParquetEncoder::try_new(arrow_schema).map_err(|error| {
+                SearchError::InternalError(format!(
+                    "Error when creating the Parquet writer: {}",
+                    error
+                ))
+            })?;
+            encode_parquet(leaf_data_stream, parquet_encoder, data_sender).boxed()
+        }
+        _ => {
+            let (header_opt, trailer_opt) = stream_header_and_trailer(
+                search_stream_request,
+                &*index_metadata.index_config,
+                output_format,
+            )?;
+            let data_stream = futures::stream::iter(header_opt.map(Ok))
+                .chain(leaf_data_stream)
+                .chain(futures::stream::iter(trailer_opt.map(Ok)));
+            forward_data_stream(data_stream, data_sender).boxed()
+        }
+    };
+    tokio::spawn(
+        async move {
+            if data_stream_future.await {
+                let elapsed = start_instant.elapsed();
+                info!("Root search stream completed in {:?}", elapsed);
+            }
+        }
+        .instrument(Span::current()),
+    );
+    Ok(ReceiverStream::new(data_receiver))
+}
+
+/// Leaves only produce rows: the bytes framing them, if any, are sent once by the root.
+fn stream_header_and_trailer(
+    search_stream_request: &SearchStreamRequest,
+    index_config: &dyn IndexConfig,
+    output_format: OutputFormat,
+) -> crate::Result<(Option<Bytes>, Option<Bytes>)> {
+    match output_format {
+        OutputFormat::Csv if search_stream_request.csv_header => {
+            let mut buffer = Vec::new();
+            serialize_csv_header(&search_stream_request.fast_fields, &mut buffer).map_err(
+                |_| SearchError::InternalError("Error when serializing the CSV header".to_string()),
+            )?;
+            Ok((Some(Bytes::from(buffer)), None))
+        }
+        OutputFormat::ArrowIpcStream => {
+            let arrow_schema =
+                arrow_schema(&index_config.schema(), &search_stream_request.fast_fields)?;
+            let header = arrow_ipc_stream_header(&arrow_schema).map_err(|_| {
+                SearchError::InternalError("Error when serializing the Arrow schema".to_string())
+            })?;
+            Ok((
+                Some(Bytes::from(header)),
+                Some(Bytes::from_static(&ARROW_IPC_END_OF_STREAM)),
+            ))
+        }
+        _ => Ok((None, None)),
+    }
+}
+
+/// Forwards the data stream to the sender, stopping at the first error.
+///
+/// The data stream is dropped as soon as the receiver is, even while waiting for the
+/// leaves, so that their streams get cancelled.
+///
+/// Returns `true` if the whole stream was forwarded.
+async fn forward_data_stream<S>(
+    mut data_stream: S,
+    data_sender: Sender<crate::Result<Bytes>>,
+) -> bool
+where
+    S: Stream<Item = crate::Result<Bytes>> + Unpin,
+{
+    loop {
+        let data_result = tokio::select! {
+            data_result_opt = data_stream.next() => match data_result_opt {
+                Some(data_result) => data_result,
+                None => return true,
+            },
+            _ = data_sender.closed() => {
+                info!("Root search stream receiver dropped. Cancelling search stream.");
+                return false;
+            }
+        };
+        let is_error = data_result.is_err();
+        if data_sender.send(data_result).await.is_err() {
+            info!("Root search stream receiver dropped. Cancelling search stream.");
+            return false;
+        }
+        // Like a failed request, the stream stops at the first error.
+        if is_error {
+            return false;
+        }
+    }
+}
+
+/// Re-encodes the Arrow IPC record batches of the leaves as a Parquet file, sent row group
+/// by row group as they get written. Encoding runs on the blocking thread pool.
+///
+/// Returns `true` if the whole file was sent.
+async fn encode_parquet<S>(
+    mut leaf_data_stream: S,
+    mut parquet_encoder: ParquetEncoder,
+    data_sender: Sender<crate::Result<Bytes>>,
+) -> bool
+where
+    S: Stream<Item = crate::Result<Bytes>> + Unpin,
+{
+    loop {
+        let data_result = tokio::select! {
+            data_result_opt = leaf_data_stream.next() => match data_result_opt {
+                Some(data_result) => data_result,
+                None => break,
+            },
+            _ = data_sender.closed() => {
+                info!("Root search stream receiver dropped. Cancelling search stream.");
+                return false;
+            }
+        };
+        let chunk = match data_result {
+            Ok(chunk) => chunk,
+            Err(error) => {
+                let _ = data_sender.send(Err(error)).await;
+                return false;
+            }
+        };
+        let encode_result = spawn_blocking(move || {
+            let encode_result = parquet_encoder.write_arrow_ipc_record_batches(&chunk);
+            (parquet_encoder, encode_result)
         })
-        .buffer_unordered(MAX_CONCURRENT_LEAF_TASKS)
-        .flatten()
-        .map_ok(|response| Bytes::from(response.data))
-        .try_collect()
-        .await?;
-    let elapsed = start_instant.elapsed();
-    info!("Root search stream completed in {:?}", elapsed);
-    Ok(bytes)
+        .await;
+        let parquet_bytes_result = match encode_result {
+            Ok((returned_parquet_encoder, encode_result)) => {
+                parquet_encoder = returned_parquet_encoder;
+                encode_result.map_err(|error| {
+                    SearchError::InternalError(format!(
+                        "Error when encoding Parquet rows: {}",
+                        error
+                    ))
+                })
+            }
+            Err(join_error) => {
+                let _ = data_sender.send(Err(SearchError::from(join_error))).await;
+                return false;
+            }
+        };
+        if !send_parquet_bytes(parquet_bytes_result, &data_sender).await {
+            return false;
+        }
+    }
+    let parquet_bytes_result = match spawn_blocking(move || parquet_encoder.finish()).await {
+        Ok(finish_result) => finish_result.map_err(|error| {
+            SearchError::InternalError(format!("Error when writing the Parquet file: {}", error))
+        }),
+        Err(join_error) => Err(SearchError::from(join_error)),
+    };
+    send_parquet_bytes(parquet_bytes_result, &data_sender).await
+}
+
+/// Sends the bytes of the Parquet file written so far, if any.
+///
+/// Returns `false` if the stream must stop, i.e. on error or if the receiver was dropped.
+async fn send_parquet_bytes(
+    parquet_bytes_result: crate::Result<Vec<u8>>,
+    data_sender: &Sender<crate::Result<Bytes>>,
+) -> bool {
+    let is_error = parquet_bytes_result.is_err();
+    if matches!(&parquet_bytes_result, Ok(parquet_bytes) if parquet_bytes.is_empty()) {
+        return true;
+    }
+    if data_sender
+        .send(parquet_bytes_result.map(Bytes::from))
+        .await
+        .is_err()
+    {
+        info!("Root search stream receiver dropped. Cancelling search stream.");
+        return false;
+    }
+    !is_error
+}
+
+/// Maximum number of rows serialized at once by the root when merging leaf rows.
+const MAX_NUM_ROWS_PER_MERGED_CHUNK: usize = 10_000;
+
+/// Merges the rows sent by the leaves of a sorted or limited stream, and serializes them
+/// in the requested output format, the way leaves do for other streams.
+struct LeafRowsMerger {
+    leaf_streams: Vec<BoxStream<'static, crate::Result<LeafSearchStreamResult>>>,
+    /// Rows received from each leaf stream, along with the position of the next row to
+    /// merge, or `None` once the leaf stream is exhausted.
+    leaf_rows: Vec<Option<(Rows, usize)>>,
+    sort_order: SortOrder,
+    remaining_rows_opt: Option<usize>,
+    fast_field_types: Vec<Type>,
+    arrow_schema: SchemaRef,
+    output_format: OutputFormat,
+}
+
+impl LeafRowsMerger {
+    /// Receives rows from the leaf streams whose rows have all been merged.
+    ///
+    /// Returns false once all the leaf streams are exhausted.
+    async fn receive_leaf_rows(&mut self) -> crate::Result<bool> {
+        let num_fast_fields = self.fast_field_types.len();
+        for (leaf_stream, leaf_rows_opt) in self.leaf_streams.iter_mut().zip(&mut self.leaf_rows) {
+            while let Some((rows, next_row)) = leaf_rows_opt {
+                if *next_row < rows.len() {
+                    break;
+                }
+                *leaf_rows_opt = match leaf_stream.next().await.transpose()? {
+                    Some(leaf_result) => {
+                        Some((Rows::from_leaf_result(leaf_result, num_fast_fields)?, 0))
+                    }
+                    None => None,
+                };
+            }
+        }
+        Ok(self.leaf_rows.iter().any(Option::is_some))
+    }
+
+    /// Returns the leaf stream holding the next row in the requested order.
+    fn first_leaf_ord(&self) -> Option<usize> {
+        let mut leaf_rows_iter =
+            self.leaf_rows
+                .iter()
+                .enumerate()
+                .filter_map(|(leaf_ord, leaf_rows_opt)| {
+                    let (rows, next_row) = leaf_rows_opt.as_ref()?;
+                    Some((leaf_ord, rows, *next_row))
+                });
+        let mut first_leaf_rows = leaf_rows_iter.next()?;
+        if self.sort_order == SortOrder::Unsorted {
+            return Some(first_leaf_rows.0);
+        }
+        for (leaf_ord, rows, next_row) in leaf_rows_iter {
+            let (_, first_rows, first_next_row) = first_leaf_rows;
+            if is_before(
+                rows.sort_value(next_row),
+                first_rows.sort_value(first_next_row),
+                self.sort_order,
+            ) {
+                first_leaf_rows = (leaf_ord, rows, next_row);
+            }
+        }
+        Some(first_leaf_rows.0)
+    }
+
+    /// Merges rows until the rows received from a leaf stream are all merged.
+    fn merge_rows(&mut self) -> Rows {
+        let mut merged_rows = Rows::new(self.fast_field_types.len());
+        let max_num_rows = self
+            .remaining_rows_opt
+            .unwrap_or(usize::MAX)
+            .min(MAX_NUM_ROWS_PER_MERGED_CHUNK);
+        while merged_rows.len() < max_num_rows {
+            let first_leaf_ord = match self.first_leaf_ord() {
+                Some(first_leaf_ord) => first_leaf_ord,
+                None => break,
+            };
+            let (rows, next_row) = self.leaf_rows[first_leaf_ord]
+                .as_mut()
+                .expect("The first leaf rows should be present.");
+            merged_rows.push_row(rows, *next_row);
+            *next_row += 1;
+            if *next_row == rows.len() {
+                break;
+            }
+        }
+        if let Some(remaining_rows) = self.remaining_rows_opt.as_mut() {
+            *remaining_rows -= merged_rows.len();
+        }
+        merged_rows
+    }
+
+    async fn next_chunk(&mut self) -> crate::Result<Option<Bytes>> {
+        while self.remaining_rows_opt != Some(0) && self.receive_leaf_rows().await? {
+            let merged_rows = self.merge_rows();
+            if merged_rows.is_empty() {
+                continue;
+            }
+            let columns = merged_rows.to_columns(&self.fast_field_types)?;
+            let mut buffer = Vec::new();
+            serialize(
+                &columns,
+                self.arrow_schema.clone(),
+                &mut buffer,
+                self.output_format,
+            )
+            .map_err(|_| {
+                SearchError::InternalError(
+                    "Error when serializing fast field values during export".to_string(),
+                )
+            })?;
+            return Ok(Some(Bytes::from(buffer)));
+        }
+        Ok(None)
+    }
+}
+
+fn merge_leaf_rows(
+    leaf_rows_merger: LeafRowsMerger,
+) -> impl Stream<Item = crate::Result<Bytes>> + Send + 'static {
+    futures::stream::unfold(Some(leaf_rows_merger), |leaf_rows_merger_opt| async move {
+        let mut leaf_rows_merger = leaf_rows_merger_opt?;
+        match leaf_rows_merger.next_chunk().await {
+            Ok(Some(chunk)) => Some((Ok(chunk), Some(leaf_rows_merger))),
+            Ok(None) => None,
+            // Like a failed request, the stream stops at the first error.
+            Err(error) => Some((Err(error), None)),
+        }
+    })
 }
 
 fn jobs_to_leaf_request(
     request: &SearchStreamRequest,
     index_config_str: &str,
     index_uri: &str,
+    encryption_key_id_opt: Option<&str>,
     split_metadata_map: &HashMap<String, SplitMetadataAndFooterOffsets>,
     jobs: &[Job],
 ) -> LeafSearchStreamRequest {
@@ -103,6 +498,7 @@ fn jobs_to_leaf_request(
             .collect(),
         index_config: index_config_str.to_string(),
         index_uri: index_uri.to_string(),
+        encryption_key_id: encryption_key_id_opt.map(str::to_string),
     }
 }
 
@@ -110,14 +506,16 @@ fn jobs_to_leaf_request(
 mod tests {
     use std::ops::Range;
 
-    use quickwit_index_config::WikipediaIndexConfig;
+    use arrow::ipc::reader::StreamReader;
+    use parquet::file::reader::FileReader;
+    use parquet::file::serialized_reader::{SerializedFileReader, SliceableCursor};
+    use quickwit_index_config::{DefaultIndexConfigBuilder, WikipediaIndexConfig};
     use quickwit_indexing::mock_split_meta;
     use quickwit_metastore::checkpoint::Checkpoint;
     use quickwit_metastore::{IndexMetadata, MockMetastore, SplitState};
-    use quickwit_proto::OutputFormat;
-    use tokio_stream::wrappers::UnboundedReceiverStream;
 
     use super::*;
+    use crate::search_stream::FastFieldColumn;
     use crate::MockSearchService;
 
     #[tokio::test]
@@ -128,9 +526,12 @@ mod tests {
             search_fields: vec!["body".to_string()],
             start_timestamp: None,
             end_timestamp: None,
-            fast_field: "timestamp".to_string(),
+            fast_fields: vec!["timestamp".to_string()],
             output_format: OutputFormat::Csv as i32,
             tags: vec![],
+            csv_header: false,
+            sort_order: SortOrder::Unsorted as i32,
+            limit: None,
         };
         let mut metastore = MockMetastore::new();
         metastore
@@ -141,6 +542,7 @@ mod tests {
                     index_uri: "file:///path/to/index/test-idx".to_string(),
                     index_config: Arc::new(WikipediaIndexConfig::new()),
                     checkpoint: Checkpoint::default(),
+                    encryption_key_id: None,
                 })
             });
         metastore.expect_list_splits().returning(
@@ -150,18 +552,24 @@ mod tests {
              _tags: &[String]| { Ok(vec![mock_split_meta("split1")]) },
         );
         let mut mock_search_service = MockSearchService::new();
-        let (result_sender, result_receiver) = tokio::sync::mpsc::unbounded_channel();
-        result_sender.send(Ok(quickwit_proto::LeafSearchStreamResult {
-            data: b"123".to_vec(),
-            split_id: "1".to_string(),
-        }))?;
-        result_sender.send(Ok(quickwit_proto::LeafSearchStreamResult {
-            data: b"456".to_vec(),
-            split_id: "2".to_string(),
-        }))?;
+        let (result_sender, result_receiver) = tokio::sync::mpsc::channel(10);
+        result_sender
+            .send(Ok(quickwit_proto::LeafSearchStreamResult {
+                data: b"123".to_vec(),
+                split_id: "1".to_string(),
+                sort_values: Vec::new(),
+            }))
+            .await?;
+        result_sender
+            .send(Ok(quickwit_proto::LeafSearchStreamResult {
+                data: b"456".to_vec(),
+                split_id: "2".to_string(),
+                sort_values: Vec::new(),
+            }))
+            .await?;
         mock_search_service.expect_leaf_search_stream().return_once(
             |_leaf_search_req: quickwit_proto::LeafSearchStreamRequest| {
-                Ok(UnboundedReceiverStream::new(result_receiver))
+                Ok(ReceiverStream::new(result_receiver))
             },
         );
         // The test will hang on indefinitely if we don't drop the sender.
@@ -170,13 +578,401 @@ mod tests {
             Arc::new(SearchClientPool::from_mocks(vec![Arc::new(mock_search_service)]).await?);
         let cluster_client = ClusterClient::new(client_pool.clone());
         let result: Vec<Bytes> =
-            root_search_stream(&request, &metastore, &cluster_client, &client_pool).await?;
+            root_search_stream(&request, &metastore, &cluster_client, &client_pool)
+                .await?
+                .try_collect()
+                .await?;
         assert_eq!(result.len(), 2);
         assert_eq!(&result[0], &b"123"[..]);
         assert_eq!(&result[1], &b"456"[..]);
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_root_search_stream_csv_header() -> anyhow::Result<()> {
+        let request = quickwit_proto::SearchStreamRequest {
+            index_id: "test-idx".to_string(),
+            query: "test".to_string(),
+            search_fields: vec!["body".to_string()],
+            start_timestamp: None,
+            end_timestamp: None,
+            fast_fields: vec!["timestamp".to_string()],
+            output_format: OutputFormat::Csv as i32,
+            tags: vec![],
+            csv_header: true,
+            sort_order: SortOrder::Unsorted as i32,
+            limit: None,
+        };
+        let mut metastore = MockMetastore::new();
+        metastore
+            .expect_index_metadata()
+            .returning(|_index_id: &str| {
+                Ok(IndexMetadata {
+                    index_id: "test-idx".to_string(),
+                    index_uri: "file:///path/to/index/test-idx".to_string(),
+                    index_config: Arc::new(WikipediaIndexConfig::new()),
+                    checkpoint: Checkpoint::default(),
+                    encryption_key_id: None,
+                })
+            });
+        metastore.expect_list_splits().returning(
+            |_index_id: &str,
+             _split_state: SplitState,
+             _time_range: Option<Range<i64>>,
+             _tags: &[String]| { Ok(vec![mock_split_meta("split1")]) },
+        );
+        let mut mock_search_service = MockSearchService::new();
+        let (result_sender, result_receiver) = tokio::sync::mpsc::channel(10);
+        result_sender
+            .send(Ok(quickwit_proto::LeafSearchStreamResult {
+                data: b"123".to_vec(),
+                split_id: "1".to_string(),
+                sort_values: Vec::new(),
+            }))
+            .await?;
+        result_sender
+            .send(Ok(quickwit_proto::LeafSearchStreamResult {
+                data: b"456".to_vec(),
+                split_id: "2".to_string(),
+                sort_values: Vec::new(),
+            }))
+            .await?;
+        mock_search_service.expect_leaf_search_stream().return_once(
+            |_leaf_search_req: quickwit_proto::LeafSearchStreamRequest| {
+                Ok(ReceiverStream::new(result_receiver))
+            },
+        );
+        // The test will hang on indefinitely if we don't drop the sender.
+        drop(result_sender);
+        let client_pool =
+            Arc::new(SearchClientPool::from_mocks(vec![Arc::new(mock_search_service)]).await?);
+        let cluster_client = ClusterClient::new(client_pool.clone());
+        let result: Vec<Bytes> =
+            root_search_stream(&request, &metastore, &cluster_client, &client_pool)
+                .await?
+                .try_collect()
+                .await?;
+        assert_eq!(result.len(), 3);
+        assert_eq!(&result[0], &b"timestamp\n"[..]);
+        assert_eq!(&result[1], &b"123"[..]);
+        assert_eq!(&result[2], &b"456"[..]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_root_search_stream_arrow_ipc_stream() -> anyhow::Result<()> {
+        let index_config = r#"{
+            "default_search_fields": ["body"],
+            "timestamp_field": "ts",
+            "tag_fields": [],
+            "field_mappings": [
+                {
+                    "name": "body",
+                    "type": "text"
+                },
+                {
+                    "name": "ts",
+                    "type": "i64",
+                    "fast": true
+                }
+            ]
+        }"#;
+        let index_config: Arc<dyn IndexConfig> =
+            Arc::new(serde_json::from_str::<DefaultIndexConfigBuilder>(index_config)?.build()?);
+        let request = quickwit_proto::SearchStreamRequest {
+            index_id: "test-idx".to_string(),
+            query: "test".to_string(),
+            search_fields: vec!["body".to_string()],
+            start_timestamp: None,
+            end_timestamp: None,
+            fast_fields: vec!["ts".to_string()],
+            output_format: OutputFormat::ArrowIpcStream as i32,
+            tags: vec![],
+            csv_header: false,
+            sort_order: SortOrder::Unsorted as i32,
+            limit: None,
+        };
+        let arrow_schema = arrow_schema(&index_config.schema(), &request.fast_fields)?;
+        let mut metastore = MockMetastore::new();
+        metastore
+            .expect_index_metadata()
+            .returning(move |_index_id: &str| {
+                Ok(IndexMetadata {
+                    index_id: "test-idx".to_string(),
+                    index_uri: "file:///path/to/index/test-idx".to_string(),
+                    index_config: index_config.clone(),
+                    checkpoint: Checkpoint::default(),
+                    encryption_key_id: None,
+                })
+            });
+        metastore.expect_list_splits().returning(
+            |_index_id: &str,
+             _split_state: SplitState,
+             _time_range: Option<Range<i64>>,
+             _tags: &[String]| { Ok(vec![mock_split_meta("split1")]) },
+        );
+        let mut mock_search_service = MockSearchService::new();
+        let (result_sender, result_receiver) = tokio::sync::mpsc::channel(10);
+        for (split_id, timestamps) in [("1", vec![1, 2]), ("2", vec![3])] {
+            let mut data = Vec::new();
+            serialize(
+                &[FastFieldColumn::I64(timestamps)],
+                arrow_schema.clone(),
+                &mut data,
+                OutputFormat::ArrowIpcStream,
+            )?;
+            result_sender
+                .send(Ok(quickwit_proto::LeafSearchStreamResult {
+                    data,
+                    split_id: split_id.to_string(),
+                    sort_values: Vec::new(),
+                }))
+                .await?;
+        }
+        mock_search_service.expect_leaf_search_stream().return_once(
+            |_leaf_search_req: quickwit_proto::LeafSearchStreamRequest| {
+                Ok(ReceiverStream::new(result_receiver))
+            },
+        );
+        // The test will hang on indefinitely if we don't drop the sender.
+        drop(result_sender);
+        let client_pool =
+            Arc::new(SearchClientPool::from_mocks(vec![Arc::new(mock_search_service)]).await?);
+        let cluster_client = ClusterClient::new(client_pool.clone());
+        let result: Vec<Bytes> =
+            root_search_stream(&request, &metastore, &cluster_client, &client_pool)
+                .await?
+                .try_collect()
+                .await?;
+        assert_eq!(result.len(), 4);
+        let arrow_ipc_stream = result.concat();
+        let record_batches = StreamReader::try_new(std::io::Cursor::new(arrow_ipc_stream))?
+            .collect::<Result<Vec<_>, _>>()?;
+        assert_eq!(record_batches.len(), 2);
+        assert_eq!(record_batches[0].schema(), arrow_schema);
+        assert_eq!(record_batches[0].num_rows(), 2);
+        assert_eq!(record_batches[1].num_rows(), 1);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_root_search_stream_parquet() -> anyhow::Result<()> {
+        let index_config = r#"{
+            "default_search_fields": ["body"],
+            "timestamp_field": "ts",
+            "tag_fields": [],
+            "field_mappings": [
+                {
+                    "name": "body",
+                    "type": "text"
+                },
+                {
+                    "name": "ts",
+                    "type": "i64",
+                    "fast": true
+                }
+            ]
+        }"#;
+        let index_config: Arc<dyn IndexConfig> =
+            Arc::new(serde_json::from_str::<DefaultIndexConfigBuilder>(index_config)?.build()?);
+        let request = quickwit_proto::SearchStreamRequest {
+            index_id: "test-idx".to_string(),
+            query: "test".to_string(),
+            search_fields: vec!["body".to_string()],
+            start_timestamp: None,
+            end_timestamp: None,
+            fast_fields: vec!["ts".to_string()],
+            output_format: OutputFormat::Parquet as i32,
+            tags: vec![],
+            csv_header: false,
+            sort_order: SortOrder::Unsorted as i32,
+            limit: None,
+        };
+        let arrow_schema = arrow_schema(&index_config.schema(), &request.fast_fields)?;
+        let mut metastore = MockMetastore::new();
+        metastore
+            .expect_index_metadata()
+            .returning(move |_index_id: &str| {
+                Ok(IndexMetadata {
+                    index_id: "test-idx".to_string(),
+                    index_uri: "file:///path/to/index/test-idx".to_string(),
+                    index_config: index_config.clone(),
+                    checkpoint: Checkpoint::default(),
+                    encryption_key_id: None,
+                })
+            });
+        metastore.expect_list_splits().returning(
+            |_index_id: &str,
+             _split_state: SplitState,
+             _time_range: Option<Range<i64>>,
+             _tags: &[String]| { Ok(vec![mock_split_meta("split1")]) },
+        );
+        let mut mock_search_service = MockSearchService::new();
+        let (result_sender, result_receiver) = tokio::sync::mpsc::channel(10);
+        // The second split has no segments, hence no columns.
+        for (split_id, columns) in [
+            ("1", vec![FastFieldColumn::I64(vec![1, 2])]),
+            ("2", Vec::new()),
+        ] {
+            let mut data = Vec::new();
+            serialize(
+                &columns,
+                arrow_schema.clone(),
+                &mut data,
+                OutputFormat::Parquet,
+            )?;
+            result_sender
+                .send(Ok(quickwit_proto::LeafSearchStreamResult {
+                    data,
+                    split_id: split_id.to_string(),
+                    sort_values: Vec::new(),
+                }))
+                .await?;
+        }
+        mock_search_service.expect_leaf_search_stream().return_once(
+            |_leaf_search_req: quickwit_proto::LeafSearchStreamRequest| {
+                Ok(ReceiverStream::new(result_receiver))
+            },
+        );
+        // The test will hang on indefinitely if we don't drop the sender.
+        drop(result_sender);
+        let client_pool =
+            Arc::new(SearchClientPool::from_mocks(vec![Arc::new(mock_search_service)]).await?);
+        let cluster_client = ClusterClient::new(client_pool.clone());
+        let result: Vec<Bytes> =
+            root_search_stream(&request, &metastore, &cluster_client, &client_pool)
+                .await?
+                .try_collect()
+                .await?;
+        // The magic number is sent with the first rows, the row group and the footer at the
+        // end.
+        assert_eq!(result.len(), 2);
+        assert_eq!(&result[0][..], b"PAR1");
+        let parquet_file = result.concat();
+        let file_reader = SerializedFileReader::new(SliceableCursor::new(parquet_file))?;
+        assert_eq!(file_reader.metadata().file_metadata().num_rows(), 2);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_root_search_stream_sorted_with_limit() -> anyhow::Result<()> {
+        let index_config = r#"{
+            "default_search_fields": ["body"],
+            "timestamp_field": "ts",
+            "tag_fields": [],
+            "field_mappings": [
+                {
+                    "name": "body",
+                    "type": "text"
+                },
+                {
+                    "name": "ts",
+                    "type": "i64",
+                    "fast": true
+                }
+            ]
+        }"#;
+        let index_config: Arc<dyn IndexConfig> =
+            Arc::new(serde_json::from_str::<DefaultIndexConfigBuilder>(index_config)?.build()?);
+        let request = quickwit_proto::SearchStreamRequest {
+            index_id: "test-idx".to_string(),
+            query: "test".to_string(),
+            search_fields: vec!["body".to_string()],
+            start_timestamp: None,
+            end_timestamp: None,
+            fast_fields: vec!["ts".to_string()],
+            output_format: OutputFormat::Csv as i32,
+            tags: vec![],
+            csv_header: false,
+            sort_order: SortOrder::Desc as i32,
+            limit: Some(3),
+        };
+        let mut metastore = MockMetastore::new();
+        metastore
+            .expect_index_metadata()
+            .returning(move |_index_id: &str| {
+                Ok(IndexMetadata {
+                    index_id: "test-idx".to_string(),
+                    index_uri: "file:///path/to/index/test-idx".to_string(),
+                    index_config: index_config.clone(),
+                    checkpoint: Checkpoint::default(),
+                    encryption_key_id: None,
+                })
+            });
+        metastore.expect_list_splits().returning(
+            |_index_id: &str,
+             _split_state: SplitState,
+             _time_range: Option<Range<i64>>,
+             _tags: &[String]| { Ok(vec![mock_split_meta("split1")]) },
+        );
+        let mut mock_search_service = MockSearchService::new();
+        let (result_sender, result_receiver) = tokio::sync::mpsc::channel(10);
+        for timestamps in [vec![9, 7], vec![5, 2]] {
+            let rows = Rows::from_columns(&[FastFieldColumn::I64(timestamps.clone())], timestamps);
+            result_sender
+                .send(Ok(rows.into_leaf_result("split1".to_string())))
+                .await?;
+        }
+        mock_search_service.expect_leaf_search_stream().return_once(
+            |_leaf_search_req: quickwit_proto::LeafSearchStreamRequest| {
+                Ok(ReceiverStream::new(result_receiver))
+            },
+        );
+        // The test will hang on indefinitely if we don't drop the sender.
+        drop(result_sender);
+        let client_pool =
+            Arc::new(SearchClientPool::from_mocks(vec![Arc::new(mock_search_service)]).await?);
+        let cluster_client = ClusterClient::new(client_pool.clone());
+        let result: Vec<Bytes> =
+            root_search_stream(&request, &metastore, &cluster_client, &client_pool)
+                .await?
+                .try_collect()
+                .await?;
+        assert_eq!(result.concat(), b"9\n7\n5\n");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_root_search_stream_sorted_requires_timestamp_field() -> anyhow::Result<()> {
+        let request = quickwit_proto::SearchStreamRequest {
+            index_id: "test-idx".to_string(),
+            query: "test".to_string(),
+            search_fields: vec!["body".to_string()],
+            start_timestamp: None,
+            end_timestamp: None,
+            fast_fields: vec!["timestamp".to_string()],
+            output_format: OutputFormat::Csv as i32,
+            tags: vec![],
+            csv_header: false,
+            sort_order: SortOrder::Asc as i32,
+            limit: None,
+        };
+        let mut metastore = MockMetastore::new();
+        metastore
+            .expect_index_metadata()
+            .returning(|_index_id: &str| {
+                Ok(IndexMetadata {
+                    index_id: "test-idx".to_string(),
+                    index_uri: "file:///path/to/index/test-idx".to_string(),
+                    index_config: Arc::new(WikipediaIndexConfig::new()),
+                    checkpoint: Checkpoint::default(),
+                    encryption_key_id: None,
+                })
+            });
+        metastore.expect_list_splits().returning(
+            |_index_id: &str,
+             _split_state: SplitState,
+             _time_range: Option<Range<i64>>,
+             _tags: &[String]| { Ok(vec![mock_split_meta("split1")]) },
+        );
+        let client_pool =
+            Arc::new(SearchClientPool::from_mocks(vec![Arc::new(MockSearchService::new())]).await?);
+        let cluster_client = ClusterClient::new(client_pool.clone());
+        let result = root_search_stream(&request, &metastore, &cluster_client, &client_pool).await;
+        assert!(matches!(result, Err(SearchError::InvalidQuery(_))));
+        Ok(())
+    }
+
     #[tokio::test]
     async fn test_root_search_stream_single_split_with_error() -> anyhow::Result<()> {
         let request = quickwit_proto::SearchStreamRequest {
@@ -185,9 +981,12 @@ mod tests {
             search_fields: vec!["body".to_string()],
             start_timestamp: None,
             end_timestamp: None,
-            fast_field: "timestamp".to_string(),
+            fast_fields: vec!["timestamp".to_string()],
             output_format: OutputFormat::Csv as i32,
             tags: vec![],
+            csv_header: false,
+            sort_order: SortOrder::Unsorted as i32,
+            limit: None,
         };
         let mut metastore = MockMetastore::new();
         metastore
@@ -198,6 +997,7 @@ mod tests {
                     index_uri: "file:///path/to/index/test-idx".to_string(),
                     index_config: Arc::new(WikipediaIndexConfig::new()),
                     checkpoint: Checkpoint::default(),
+                    encryption_key_id: None,
                 })
             });
         metastore.expect_list_splits().returning(
@@ -209,18 +1009,23 @@ mod tests {
             },
         );
         let mut mock_search_service = MockSearchService::new();
-        let (result_sender, result_receiver) = tokio::sync::mpsc::unbounded_channel();
-        result_sender.send(Ok(quickwit_proto::LeafSearchStreamResult {
-            data: b"123".to_vec(),
-            split_id: "split1".to_string(),
-        }))?;
-        result_sender.send(Err(SearchError::InternalError("error".to_string())))?;
+        let (result_sender, result_receiver) = tokio::sync::mpsc::channel(10);
+        result_sender
+            .send(Ok(quickwit_proto::LeafSearchStreamResult {
+                data: b"123".to_vec(),
+                split_id: "split1".to_string(),
+                sort_values: Vec::new(),
+            }))
+            .await?;
+        result_sender
+            .send(Err(SearchError::InternalError("error".to_string())))
+            .await?;
         mock_search_service
             .expect_leaf_search_stream()
             .withf(|request| request.split_metadata.len() == 2) // First request.
             .return_once(
                 |_leaf_search_req: quickwit_proto::LeafSearchStreamRequest| {
-                    Ok(UnboundedReceiverStream::new(result_receiver))
+                    Ok(ReceiverStream::new(result_receiver))
                 },
             );
         mock_search_service
@@ -236,9 +1041,74 @@ mod tests {
         let client_pool =
             Arc::new(SearchClientPool::from_mocks(vec![Arc::new(mock_search_service)]).await?);
         let cluster_client = ClusterClient::new(client_pool.clone());
-        let result = root_search_stream(&request, &metastore, &cluster_client, &client_pool).await;
+        let result: Result<Vec<Bytes>, SearchError> =
+            root_search_stream(&request, &metastore, &cluster_client, &client_pool)
+                .await?
+                .try_collect()
+                .await;
         assert_eq!(result.is_err(), true);
         assert_eq!(result.unwrap_err().to_string(), "Internal error: `error`.");
         Ok(())
     }
+
+    #[tokio::test]
+    async fn test_root_search_stream_forwards_data_before_leaves_complete() -> anyhow::Result<()> {
+        let request = quickwit_proto::SearchStreamRequest {
+            index_id: "test-idx".to_string(),
+            query: "test".to_string(),
+            search_fields: vec!["body".to_string()],
+            start_timestamp: None,
+            end_timestamp: None,
+            fast_fields: vec!["timestamp".to_string()],
+            output_format: OutputFormat::Csv as i32,
+            tags: vec![],
+            csv_header: false,
+            sort_order: SortOrder::Unsorted as i32,
+            limit: None,
+        };
+        let mut metastore = MockMetastore::new();
+        metastore
+            .expect_index_metadata()
+            .returning(|_index_id: &str| {
+                Ok(IndexMetadata {
+                    index_id: "test-idx".to_string(),
+                    index_uri: "file:///path/to/index/test-idx".to_string(),
+                    index_config: Arc::new(WikipediaIndexConfig::new()),
+                    checkpoint: Checkpoint::default(),
+                    encryption_key_id: None,
+                })
+            });
+        metastore.expect_list_splits().returning(
+            |_index_id: &str,
+             _split_state: SplitState,
+             _time_range: Option<Range<i64>>,
+             _tags: &[String]| { Ok(vec![mock_split_meta("split1")]) },
+        );
+        let mut mock_search_service = MockSearchService::new();
+        let (result_sender, result_receiver) = tokio::sync::mpsc::channel(10);
+        mock_search_service.expect_leaf_search_stream().return_once(
+            |_leaf_search_req: quickwit_proto::LeafSearchStreamRequest| {
+                Ok(ReceiverStream::new(result_receiver))
+            },
+        );
+        let client_pool =
+            Arc::new(SearchClientPool::from_mocks(vec![Arc::new(mock_search_service)]).await?);
+        let cluster_client = ClusterClient::new(client_pool.clone());
+        let mut data_stream =
+            root_search_stream(&request, &metastore, &cluster_client, &client_pool).await?;
+        result_sender
+            .send(Ok(quickwit_proto::LeafSearchStreamResult {
+                data: b"123".to_vec(),
+                split_id: "split1".to_string(),
+                sort_values: Vec::new(),
+            }))
+            .await?;
+        // The leaf stream is still open, the first chunk is nonetheless available.
+        let first_chunk = data_stream.next().await.unwrap()?;
+        assert_eq!(&first_chunk, &b"123"[..]);
+        // Dropping the root stream cancels the leaf stream.
+        drop(data_stream);
+        tokio::time::timeout(std::time::Duration::from_secs(1), result_sender.closed()).await?;
+        Ok(())
+    }
 }
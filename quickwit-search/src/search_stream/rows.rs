@@ -0,0 +1,298 @@
+// Copyright (C) 2021 Quickwit, Inc.
+//
+// Quickwit is offered under the AGPL v3.0 and as commercial software.
+// For commercial licensing, contact us at hello@quickwit.io.
+//
+// AGPL:
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+//! Rows exchanged between the leaves and the root of sorted or limited search streams.
+//!
+//! The rows are serialized in the ClickHouse RowBinary format, in which every value takes
+//! 8 bytes, so that the root can merge, truncate and re-serialize them in the requested
+//! output format without knowing anything but the fast field types.
+
+use std::cmp::Reverse;
+use std::convert::TryInto;
+
+use quickwit_proto::{LeafSearchStreamResult, SortOrder};
+use tantivy::chrono::{TimeZone, Utc};
+use tantivy::schema::Type;
+
+use super::{serialize_click_house_row_binary, FastFieldColumn};
+use crate::SearchError;
+
+const NUM_BYTES_PER_VALUE: usize = 8;
+
+/// Rows of fast field values, with their timestamps if they are sorted.
+#[derive(Debug, PartialEq)]
+pub(crate) struct Rows {
+    num_bytes_per_row: usize,
+    sort_values: Vec<i64>,
+    data: Vec<u8>,
+}
+
+impl Rows {
+    pub fn new(num_fast_fields: usize) -> Rows {
+        Rows {
+            num_bytes_per_row: num_fast_fields * NUM_BYTES_PER_VALUE,
+            sort_values: Vec::new(),
+            data: Vec::new(),
+        }
+    }
+
+    /// Builds the rows out of the collected columns. `sort_values` is either empty or
+    /// holds one timestamp per row.
+    pub fn from_columns(columns: &[FastFieldColumn], sort_values: Vec<i64>) -> Rows {
+        let mut data = Vec::new();
+        serialize_click_house_row_binary(columns, &mut data)
+            .expect("Serializing to a Vec should never fail.");
+        Rows {
+            num_bytes_per_row: columns.len() * NUM_BYTES_PER_VALUE,
+            sort_values,
+            data,
+        }
+    }
+
+    pub fn from_leaf_result(
+        leaf_result: LeafSearchStreamResult,
+        num_fast_fields: usize,
+    ) -> crate::Result<Rows> {
+        let rows = Rows {
+            num_bytes_per_row: num_fast_fields * NUM_BYTES_PER_VALUE,
+            sort_values: leaf_result.sort_values,
+            data: leaf_result.data,
+        };
+        let is_valid = rows.num_bytes_per_row > 0
+            && rows.data.len() % rows.num_bytes_per_row == 0
+            && (rows.sort_values.is_empty() || rows.sort_values.len() == rows.len());
+        if !is_valid {
+            return Err(SearchError::InternalError(format!(
+                "Invalid rows received from split {}.",
+                leaf_result.split_id
+            )));
+        }
+        Ok(rows)
+    }
+
+    pub fn into_leaf_result(self, split_id: String) -> LeafSearchStreamResult {
+        LeafSearchStreamResult {
+            data: self.data,
+            split_id,
+            sort_values: self.sort_values,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        if self.num_bytes_per_row == 0 {
+            return 0;
+        }
+        self.data.len() / self.num_bytes_per_row
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    pub fn sort_value(&self, row: usize) -> i64 {
+        self.sort_values[row]
+    }
+
+    fn row(&self, row: usize) -> &[u8] {
+        &self.data[row * self.num_bytes_per_row..(row + 1) * self.num_bytes_per_row]
+    }
+
+    /// Appends a row of `other`, keeping its timestamp if any.
+    pub fn push_row(&mut self, other: &Rows, row: usize) {
+        self.data.extend_from_slice(other.row(row));
+        if !other.sort_values.is_empty() {
+            self.sort_values.push(other.sort_values[row]);
+        }
+    }
+
+    pub fn truncate(&mut self, len: usize) {
+        self.sort_values.truncate(len);
+        self.data.truncate(len * self.num_bytes_per_row);
+    }
+
+    /// Keeps the rows before `at` and returns the other ones.
+    pub fn split_off(&mut self, at: usize) -> Rows {
+        Rows {
+            num_bytes_per_row: self.num_bytes_per_row,
+            sort_values: self.sort_values.split_off(at.min(self.sort_values.len())),
+            data: self.data.split_off(at * self.num_bytes_per_row),
+        }
+    }
+
+    pub fn sort(&mut self, sort_order: SortOrder) {
+        let mut permutation: Vec<usize> = (0..self.len()).collect();
+        match sort_order {
+            SortOrder::Unsorted => return,
+            SortOrder::Asc => permutation.sort_by_key(|&row| self.sort_values[row]),
+            SortOrder::Desc => permutation.sort_by_key(|&row| Reverse(self.sort_values[row])),
+        }
+        let mut sorted_rows = Rows {
+            num_bytes_per_row: self.num_bytes_per_row,
+            sort_values: Vec::with_capacity(self.sort_values.len()),
+            data: Vec::with_capacity(self.data.len()),
+        };
+        for row in permutation {
+            sorted_rows.push_row(self, row);
+        }
+        *self = sorted_rows;
+    }
+
+    /// Merges two sets of rows sorted in the given order.
+    pub fn merge_sorted(self, other: Rows, sort_order: SortOrder) -> Rows {
+        let mut merged_rows = Rows {
+            num_bytes_per_row: self.num_bytes_per_row,
+            sort_values: Vec::with_capacity(self.sort_values.len() + other.sort_values.len()),
+            data: Vec::with_capacity(self.data.len() + other.data.len()),
+        };
+        let (mut row, mut other_row) = (0, 0);
+        while row < self.len() || other_row < other.len() {
+            let take_self = other_row == other.len()
+                || (row < self.len()
+                    && !is_before(
+                        other.sort_values[other_row],
+                        self.sort_values[row],
+                        sort_order,
+                    ));
+            if take_self {
+                merged_rows.push_row(&self, row);
+                row += 1;
+            } else {
+                merged_rows.push_row(&other, other_row);
+                other_row += 1;
+            }
+        }
+        merged_rows
+    }
+
+    /// Returns the number of rows strictly before `bound` in the given order.
+    pub fn num_rows_before(&self, bound: i64, sort_order: SortOrder) -> usize {
+        self.sort_values
+            .partition_point(|&sort_value| is_before(sort_value, bound, sort_order))
+    }
+
+    /// Deserializes the rows into columns of the given types.
+    pub fn to_columns(&self, fast_field_types: &[Type]) -> crate::Result<Vec<FastFieldColumn>> {
+        fast_field_types
+            .iter()
+            .enumerate()
+            .map(|(column_ord, fast_field_type)| {
+                let values = (0..self.len()).map(|row| {
+                    let start = row * self.num_bytes_per_row + column_ord * NUM_BYTES_PER_VALUE;
+                    let value_bytes: [u8; NUM_BYTES_PER_VALUE] = self.data
+                        [start..start + NUM_BYTES_PER_VALUE]
+                        .try_into()
+                        .expect("Slice should be 8 bytes long.");
+                    value_bytes
+                });
+                let column = match fast_field_type {
+                    Type::U64 => FastFieldColumn::U64(values.map(u64::from_le_bytes).collect()),
+                    Type::I64 => FastFieldColumn::I64(values.map(i64::from_le_bytes).collect()),
+                    Type::F64 => FastFieldColumn::F64(values.map(f64::from_le_bytes).collect()),
+                    Type::Date => FastFieldColumn::Date(
+                        values
+                            .map(|value_bytes| Utc.timestamp(i64::from_le_bytes(value_bytes), 0))
+                            .collect(),
+                    ),
+                    _ => {
+                        return Err(SearchError::InvalidQuery(format!(
+                            "Fast field type `{:?}` not supported",
+                            fast_field_type
+                        )))
+                    }
+                };
+                Ok(column)
+            })
+            .collect()
+    }
+}
+
+/// Returns true if `sort_value` comes strictly before `other_sort_value`.
+pub(crate) fn is_before(sort_value: i64, other_sort_value: i64, sort_order: SortOrder) -> bool {
+    match sort_order {
+        SortOrder::Unsorted => false,
+        SortOrder::Asc => sort_value < other_sort_value,
+        SortOrder::Desc => sort_value > other_sort_value,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rows(timestamps: &[i64]) -> Rows {
+        let column = FastFieldColumn::I64(timestamps.to_vec());
+        Rows::from_columns(&[column], timestamps.to_vec())
+    }
+
+    #[test]
+    fn test_rows_sort_and_merge() {
+        let mut left_rows = rows(&[3, 1, 5]);
+        left_rows.sort(SortOrder::Desc);
+        assert_eq!(left_rows, rows(&[5, 3, 1]));
+        let right_rows = rows(&[4, 3, 0]);
+        let merged_rows = left_rows.merge_sorted(right_rows, SortOrder::Desc);
+        assert_eq!(merged_rows, rows(&[5, 4, 3, 3, 1, 0]));
+        assert_eq!(merged_rows.num_rows_before(3, SortOrder::Desc), 2);
+        assert_eq!(merged_rows.num_rows_before(-1, SortOrder::Desc), 6);
+
+        let merged_rows = rows(&[1, 5]).merge_sorted(rows(&[2, 3, 6]), SortOrder::Asc);
+        assert_eq!(merged_rows, rows(&[1, 2, 3, 5, 6]));
+        assert_eq!(merged_rows.num_rows_before(5, SortOrder::Asc), 3);
+    }
+
+    #[test]
+    fn test_rows_split_off_and_truncate() {
+        let mut rows_to_split = rows(&[1, 2, 3, 4]);
+        let tail_rows = rows_to_split.split_off(1);
+        assert_eq!(rows_to_split, rows(&[1]));
+        assert_eq!(tail_rows, rows(&[2, 3, 4]));
+        let mut rows_to_truncate = rows(&[1, 2, 3]);
+        rows_to_truncate.truncate(2);
+        assert_eq!(rows_to_truncate, rows(&[1, 2]));
+    }
+
+    #[test]
+    fn test_rows_to_columns() -> anyhow::Result<()> {
+        let columns = vec![
+            FastFieldColumn::U64(vec![1, 2]),
+            FastFieldColumn::F64(vec![0.5, 1.5]),
+            FastFieldColumn::Date(vec![Utc.timestamp(10, 0), Utc.timestamp(20, 0)]),
+        ];
+        let rows = Rows::from_columns(&columns, Vec::new());
+        assert_eq!(rows.len(), 2);
+        assert_eq!(
+            rows.to_columns(&[Type::U64, Type::F64, Type::Date])?,
+            columns
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_rows_from_invalid_leaf_result() {
+        let leaf_result = LeafSearchStreamResult {
+            data: vec![0u8; 12],
+            split_id: "split1".to_string(),
+            sort_values: Vec::new(),
+        };
+        assert!(matches!(
+            Rows::from_leaf_result(leaf_result, 1),
+            Err(SearchError::InternalError(_))
+        ));
+    }
+}
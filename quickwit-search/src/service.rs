@@ -26,14 +26,18 @@ use quickwit_metastore::Metastore;
 use quickwit_proto::{
     FetchDocsRequest, FetchDocsResult, LeafSearchRequest, LeafSearchResponse,
     LeafSearchStreamRequest, LeafSearchStreamResult, SearchRequest, SearchResponse,
-    SearchStreamRequest,
+    SearchStreamRequest, SplitIdAndFooterOffsets,
 };
-use quickwit_storage::StorageUriResolver;
-use tokio_stream::wrappers::UnboundedReceiverStream;
+use quickwit_storage::{Cache, StorageUriResolver};
+use tokio_stream::wrappers::ReceiverStream;
 use tracing::info;
 
+use crate::metrics::{GaugeGuard, SEARCH_METRICS};
 use crate::search_stream::{leaf_search_stream, root_search_stream};
-use crate::{fetch_docs, leaf_search, root_search, ClusterClient, SearchClientPool, SearchError};
+use crate::{
+    fetch_docs, leaf_search, root_search, AdmissionController, ClusterClient, LeafSearchCache,
+    RequestKind, SearchClientPool, SearchError,
+};
 
 #[derive(Clone)]
 /// The search service implementation.
@@ -42,6 +46,9 @@ pub struct SearchServiceImpl {
     storage_resolver: StorageUriResolver,
     cluster_client: ClusterClient,
     client_pool: Arc<SearchClientPool>,
+    split_cache_opt: Option<Arc<dyn Cache>>,
+    leaf_search_cache: Arc<LeafSearchCache>,
+    admission_controller: Arc<AdmissionController>,
 }
 
 /// Trait representing a search service.
@@ -73,33 +80,61 @@ pub trait SearchService: 'static + Send + Sync {
     /// This methods takes `PartialHit`s and returns `Hit`s.
     async fn fetch_docs(&self, request: FetchDocsRequest) -> crate::Result<FetchDocsResult>;
 
-    /// Performs a root search returning a receiver for streaming
-    async fn root_search_stream(&self, request: SearchStreamRequest) -> crate::Result<Vec<Bytes>>;
+    /// Performs a root search returning a stream of data chunks, forwarded as soon as
+    /// the leaves produce them.
+    async fn root_search_stream(
+        &self,
+        request: SearchStreamRequest,
+    ) -> crate::Result<ReceiverStream<crate::Result<Bytes>>>;
 
     /// Performs a leaf search on a given set of splits and returns a stream.
     async fn leaf_search_stream(
         &self,
         request: LeafSearchStreamRequest,
-    ) -> crate::Result<UnboundedReceiverStream<crate::Result<LeafSearchStreamResult>>>;
+    ) -> crate::Result<ReceiverStream<crate::Result<LeafSearchStreamResult>>>;
 }
 
 impl SearchServiceImpl {
     /// Creates a new search service.
+    ///
+    /// If a split cache is given, the files bundled in the splits, e.g. fast fields or
+    /// term dictionaries, are read through it.
+    /// Leaf search responses are cached per split in the leaf search cache.
+    /// Leaf requests only run once admitted by the admission controller.
     pub fn new(
         metastore: Arc<dyn Metastore>,
         storage_resolver: StorageUriResolver,
         cluster_client: ClusterClient,
         client_pool: Arc<SearchClientPool>,
+        split_cache_opt: Option<Arc<dyn Cache>>,
+        leaf_search_cache: Arc<LeafSearchCache>,
+        admission_controller: Arc<AdmissionController>,
     ) -> Self {
         SearchServiceImpl {
             metastore,
             storage_resolver,
             cluster_client,
             client_pool,
+            split_cache_opt,
+            leaf_search_cache,
+            admission_controller,
         }
     }
 }
 
+/// Estimates the memory used to search the given splits. Searching a split loads its footer,
+/// which holds the hotcache, in memory.
+fn estimate_leaf_request_memory(splits: &[SplitIdAndFooterOffsets]) -> usize {
+    splits
+        .iter()
+        .map(|split| {
+            split
+                .split_footer_end
+                .saturating_sub(split.split_footer_start) as usize
+        })
+        .sum()
+}
+
 fn deserialize_index_config(index_config_str: &str) -> crate::Result<Arc<dyn IndexConfig>> {
     let index_config =
         serde_json::from_str::<Arc<dyn IndexConfig>>(index_config_str).map_err(|err| {
@@ -114,6 +149,7 @@ fn deserialize_index_config(index_config_str: &str) -> crate::Result<Arc<dyn Ind
 #[async_trait]
 impl SearchService for SearchServiceImpl {
     async fn root_search(&self, search_request: SearchRequest) -> crate::Result<SearchResponse> {
+        let _timer = SEARCH_METRICS.root_search_duration_seconds.start_timer();
         let search_result = root_search(
             &search_request,
             self.metastore.as_ref(),
@@ -129,14 +165,28 @@ impl SearchService for SearchServiceImpl {
         &self,
         leaf_search_request: LeafSearchRequest,
     ) -> crate::Result<LeafSearchResponse> {
+        let _timer = SEARCH_METRICS.leaf_search_duration_seconds.start_timer();
+        let _in_flight_guard = GaugeGuard::new(&SEARCH_METRICS.leaf_requests_in_flight);
         let search_request = leaf_search_request
             .search_request
             .ok_or_else(|| SearchError::InternalError("No search request.".to_string()))?;
         info!(index=?search_request.index_id, splits=?leaf_search_request.split_metadata, "leaf_search");
-        let storage = self
-            .storage_resolver
-            .resolve(&leaf_search_request.index_uri)?;
+        let storage = self.storage_resolver.resolve_with_encryption_key(
+            &leaf_search_request.index_uri,
+            leaf_search_request.encryption_key_id.as_deref(),
+        )?;
         let split_ids = leaf_search_request.split_metadata;
+        let _admission_permit = self
+            .admission_controller
+            .admit(
+                RequestKind::Search,
+                estimate_leaf_request_memory(&split_ids),
+            )
+            .await?;
+        let _splits_in_flight_guard = GaugeGuard::with_delta(
+            &SEARCH_METRICS.leaf_splits_in_flight,
+            split_ids.len() as i64,
+        );
         let index_config = deserialize_index_config(&leaf_search_request.index_config)?;
 
         let leaf_search_response = leaf_search(
@@ -144,8 +194,18 @@ impl SearchService for SearchServiceImpl {
             storage.clone(),
             &split_ids[..],
             index_config,
+            self.split_cache_opt.clone(),
+            Some(self.leaf_search_cache.clone()),
         )
         .await?;
+        // Recorded here rather than in `leaf_search`, which the CLI also uses for
+        // single node searches.
+        SEARCH_METRICS
+            .leaf_searched_splits_total
+            .inc_by(split_ids.len() as u64);
+        SEARCH_METRICS
+            .leaf_failed_splits_total
+            .inc_by(leaf_search_response.failed_splits.len() as u64);
 
         Ok(leaf_search_response)
     }
@@ -154,14 +214,18 @@ impl SearchService for SearchServiceImpl {
         &self,
         fetch_docs_request: FetchDocsRequest,
     ) -> crate::Result<FetchDocsResult> {
-        let storage = self
-            .storage_resolver
-            .resolve(&fetch_docs_request.index_uri)?;
+        let _timer = SEARCH_METRICS.fetch_docs_duration_seconds.start_timer();
+        let _in_flight_guard = GaugeGuard::new(&SEARCH_METRICS.leaf_requests_in_flight);
+        let storage = self.storage_resolver.resolve_with_encryption_key(
+            &fetch_docs_request.index_uri,
+            fetch_docs_request.encryption_key_id.as_deref(),
+        )?;
 
         let fetch_docs_result = fetch_docs(
             fetch_docs_request.partial_hits,
             storage,
             &fetch_docs_request.split_metadata,
+            self.split_cache_opt.clone(),
         )
         .await?;
 
@@ -171,34 +235,44 @@ impl SearchService for SearchServiceImpl {
     async fn root_search_stream(
         &self,
         stream_request: SearchStreamRequest,
-    ) -> crate::Result<Vec<Bytes>> {
-        let data = root_search_stream(
+    ) -> crate::Result<ReceiverStream<crate::Result<Bytes>>> {
+        let data_stream = root_search_stream(
             &stream_request,
             self.metastore.as_ref(),
             &self.cluster_client,
             &self.client_pool,
         )
         .await?;
-        Ok(data)
+        Ok(data_stream)
     }
 
     async fn leaf_search_stream(
         &self,
         leaf_stream_request: LeafSearchStreamRequest,
-    ) -> crate::Result<UnboundedReceiverStream<crate::Result<LeafSearchStreamResult>>> {
+    ) -> crate::Result<ReceiverStream<crate::Result<LeafSearchStreamResult>>> {
         let stream_request = leaf_stream_request
             .request
             .ok_or_else(|| SearchError::InternalError("No search request.".to_string()))?;
         info!(index=?stream_request.index_id, splits=?leaf_stream_request.split_metadata, "leaf_search");
-        let storage = self
-            .storage_resolver
-            .resolve(&leaf_stream_request.index_uri)?;
+        let storage = self.storage_resolver.resolve_with_encryption_key(
+            &leaf_stream_request.index_uri,
+            leaf_stream_request.encryption_key_id.as_deref(),
+        )?;
         let index_config = deserialize_index_config(&leaf_stream_request.index_config)?;
+        let admission_permit = self
+            .admission_controller
+            .admit(
+                RequestKind::Stream,
+                estimate_leaf_request_memory(&leaf_stream_request.split_metadata),
+            )
+            .await?;
         let leaf_receiver = leaf_search_stream(
             stream_request,
             storage.clone(),
             leaf_stream_request.split_metadata,
             index_config,
+            self.split_cache_opt.clone(),
+            Some(admission_permit),
         )
         .await;
         Ok(leaf_receiver)
@@ -19,6 +19,11 @@
 
 use std::net::SocketAddr;
 use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::{
+    AdmissionControllerConfig, CacheRouteConfig, FailureDetectorConfig, NodeRole, TlsConfig,
+};
 
 #[derive(Debug, PartialEq)]
 pub struct ServeArgs {
@@ -28,9 +33,68 @@ pub struct ServeArgs {
     /// The path to the file that stores the ID to make the node unique.
     pub host_key_path: PathBuf,
 
+    /// ID of the cluster to join. Nodes with different cluster IDs ignore each other.
+    pub cluster_id: String,
+
+    /// File holding the secret keys used to encrypt and authenticate gossip messages.
+    /// Gossip messages travel in clear if not set.
+    pub cluster_secret_file: Option<PathBuf>,
+
+    /// Settings of the cluster failure detector.
+    pub failure_detector_config: FailureDetectorConfig,
+
     /// Socket addresses of the peer REST server.
     pub peer_socket_addrs: Vec<SocketAddr>,
 
+    /// Roles gossiped to the other members of the cluster.
+    pub roles: Vec<NodeRole>,
+
     /// Metastore URI.
     pub metastore_uri: String,
+
+    /// Directory of the local disk split cache. The cache is disabled if not set.
+    pub split_cache_dir: Option<PathBuf>,
+
+    /// Capacity of the local disk split cache.
+    pub split_cache_capacity_in_bytes: u64,
+
+    /// Indexes whose most recent splits are prefetched in the local disk split cache.
+    pub prefetch_index_ids: Vec<String>,
+
+    /// Number of splits prefetched per index.
+    pub num_prefetched_splits: usize,
+
+    /// In-memory cache routes. Files matching no route are not cached in memory.
+    pub cache_routes: Vec<CacheRouteConfig>,
+
+    /// Capacity of the in-memory cache of leaf search responses. The cache is disabled if
+    /// zero.
+    pub leaf_search_cache_capacity_in_bytes: u64,
+
+    /// Concurrency and memory limits of the leaf search and leaf search stream requests.
+    pub admission_controller_config: AdmissionControllerConfig,
+
+    /// Deadline of the leaf search and fetch docs requests sent by this node, retries and
+    /// hedged requests included.
+    pub leaf_request_timeout: Duration,
+
+    /// TLS settings of the REST and gRPC servers and of the gRPC clients. Everything is
+    /// served in clear if not set.
+    pub tls_config: Option<TlsConfig>,
+
+    /// File declaring the users of the REST API, the OTLP receiver and the Jaeger span
+    /// reader, and their permissions. They are open to anyone if not set.
+    pub auth_file: Option<PathBuf>,
+
+    /// Key file used to decrypt the encrypted split files. Encrypted splits cannot be
+    /// searched if not set.
+    pub encryption_key_file: Option<PathBuf>,
+
+    /// Lets encrypted indexes read split files that are not encrypted, e.g. while migrating
+    /// an index to encryption.
+    pub allow_plaintext_reads: bool,
+
+    /// Root URI under which the `otel-logs` and `otel-traces` indexes of the OTLP receiver
+    /// are created if they do not exist.
+    pub otlp_indexes_root_uri: Option<String>,
 }
@@ -0,0 +1,447 @@
+// Copyright (C) 2021 Quickwit, Inc.
+//
+// Quickwit is offered under the AGPL v3.0 and as commercial software.
+// For commercial licensing, contact us at hello@quickwit.io.
+//
+// AGPL:
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+//! Authentication and authorization of the REST API, and of the OTLP receiver and Jaeger
+//! span reader served by the gRPC server.
+//!
+//! Users are declared in a JSON file of the form:
+//! ```json
+//! {
+//!     "users": [
+//!         {"name": "grafana", "token_sha256": "<hex>", "permissions": {"logs-*": "read"}},
+//!         {"name": "admin", "password_sha256": "<hex>", "permissions": {"*": "write"}}
+//!     ]
+//! }
+//! ```
+//! Users authenticate with a bearer token or with HTTP basic auth, and are granted read or
+//! write access to the indexes matching the patterns of their permissions. A pattern is
+//! either an index ID, or a prefix followed by `*`.
+//!
+//! The file only holds the hex-encoded SHA-256 digests of the tokens and passwords, as output
+//! by `echo -n <token> | sha256sum`. The digests are not salted, so tokens and passwords
+//! should be long random strings.
+//!
+//! gRPC clients pass their credentials in the `authorization` metadata of their requests, in
+//! the same format as the HTTP header.
+
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
+
+use anyhow::Context;
+use quickwit_proto::error::GrpcServiceError;
+use serde::{Deserialize, Deserializer};
+use sha2::{Digest, Sha256};
+use warp::{Filter, Rejection};
+
+use crate::ApiError;
+
+/// Access level to an index. Write access implies read access.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum Access {
+    /// Searching the index.
+    Read,
+    /// Ingesting documents into the index.
+    Write,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct AuthFile {
+    users: Vec<User>,
+}
+
+/// A user of the REST API.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct User {
+    name: String,
+    #[serde(default)]
+    #[serde(deserialize_with = "deserialize_sha256_opt")]
+    token_sha256: Option<[u8; SHA256_NUM_BYTES]>,
+    #[serde(default)]
+    #[serde(deserialize_with = "deserialize_sha256_opt")]
+    password_sha256: Option<[u8; SHA256_NUM_BYTES]>,
+    #[serde(default)]
+    permissions: HashMap<String, Access>,
+}
+
+const SHA256_NUM_BYTES: usize = 32;
+
+/// gRPC metadata key of the bearer token propagated by `jaeger-query`.
+const JAEGER_BEARER_TOKEN_KEY: &str = "bearer.token";
+
+fn deserialize_sha256_opt<'de, D>(
+    deserializer: D,
+) -> Result<Option<[u8; SHA256_NUM_BYTES]>, D::Error>
+where D: Deserializer<'de> {
+    let hex_digest = String::deserialize(deserializer)?;
+    let mut digest = [0u8; SHA256_NUM_BYTES];
+    hex::decode_to_slice(hex_digest.trim(), &mut digest).map_err(|_| {
+        serde::de::Error::custom("expected the hex-encoded SHA-256 digest of the secret")
+    })?;
+    Ok(Some(digest))
+}
+
+fn sha256(secret: &str) -> [u8; SHA256_NUM_BYTES] {
+    Sha256::digest(secret.as_bytes()).into()
+}
+
+impl User {
+    fn access(&self, index_id: &str) -> Option<Access> {
+        self.permissions
+            .iter()
+            .filter(|(pattern, _)| index_pattern_matches(pattern, index_id))
+            .map(|(_, access)| *access)
+            .max()
+    }
+}
+
+fn index_pattern_matches(pattern: &str, index_id: &str) -> bool {
+    match pattern.strip_suffix('*') {
+        Some(prefix) => index_id.starts_with(prefix),
+        None => pattern == index_id,
+    }
+}
+
+/// The author of a request.
+#[derive(Clone, Debug)]
+pub enum Principal {
+    /// Authentication is disabled: every request is granted every access.
+    Anonymous,
+    /// An authenticated user.
+    User(Arc<User>),
+}
+
+impl Principal {
+    /// Checks that the principal has the given access to the index.
+    pub fn check_index_access(&self, index_id: &str, access: Access) -> Result<(), ApiError> {
+        match self {
+            Principal::Anonymous => Ok(()),
+            Principal::User(user) => match user.access(index_id) {
+                Some(granted_access) if granted_access >= access => Ok(()),
+                _ => Err(ApiError::Forbidden(format!(
+                    "User `{}` does not have {:?} access to index `{}`",
+                    user.name, access, index_id
+                ))),
+            },
+        }
+    }
+}
+
+/// Authenticates the requests of the REST API.
+#[derive(Debug, Default)]
+pub struct Authenticator {
+    /// `None` when authentication is disabled.
+    users_opt: Option<Vec<Arc<User>>>,
+}
+
+impl Authenticator {
+    /// Creates an authenticator that lets every request through.
+    pub fn disabled() -> Self {
+        Authenticator::default()
+    }
+
+    /// Loads the users of the given auth file.
+    pub fn open(auth_file_path: &Path) -> anyhow::Result<Self> {
+        let auth_file_content = std::fs::read(auth_file_path)
+            .with_context(|| format!("Failed to read auth file `{}`.", auth_file_path.display()))?;
+        let auth_file: AuthFile =
+            serde_json::from_slice(&auth_file_content).with_context(|| {
+                format!("Failed to parse auth file `{}`.", auth_file_path.display())
+            })?;
+        for user in &auth_file.users {
+            if user.token_sha256.is_none() && user.password_sha256.is_none() {
+                anyhow::bail!("User `{}` has neither a token nor a password.", user.name);
+            }
+        }
+        let users = auth_file.users.into_iter().map(Arc::new).collect();
+        Ok(Authenticator {
+            users_opt: Some(users),
+        })
+    }
+
+    /// Returns the principal identified by the `Authorization` header of a request.
+    pub fn authenticate(&self, authorization_opt: Option<&str>) -> Option<Principal> {
+        let users = match &self.users_opt {
+            Some(users) => users,
+            None => return Some(Principal::Anonymous),
+        };
+        let credentials = Credentials::parse(authorization_opt?)?;
+        users
+            .iter()
+            .find(|user| credentials.identify(user))
+            .map(|user| Principal::User(user.clone()))
+    }
+}
+
+enum Credentials {
+    Bearer(String),
+    Basic { name: String, password: String },
+}
+
+impl Credentials {
+    fn parse(authorization: &str) -> Option<Self> {
+        let (scheme, value) = authorization.trim().split_once(' ')?;
+        let value = value.trim();
+        if scheme.eq_ignore_ascii_case("bearer") {
+            return Some(Credentials::Bearer(value.to_string()));
+        }
+        if scheme.eq_ignore_ascii_case("basic") {
+            let decoded = String::from_utf8(base64::decode(value).ok()?).ok()?;
+            let (name, password) = decoded.split_once(':')?;
+            return Some(Credentials::Basic {
+                name: name.to_string(),
+                password: password.to_string(),
+            });
+        }
+        None
+    }
+
+    fn identify(&self, user: &User) -> bool {
+        match self {
+            Credentials::Bearer(token) => user
+                .token_sha256
+                .as_ref()
+                .map(|token_sha256| constant_time_eq(token_sha256, &sha256(token)))
+                .unwrap_or(false),
+            Credentials::Basic { name, password } => {
+                &user.name == name
+                    && user
+                        .password_sha256
+                        .as_ref()
+                        .map(|password_sha256| constant_time_eq(password_sha256, &sha256(password)))
+                        .unwrap_or(false)
+            }
+        }
+    }
+}
+
+/// Compares digests in a time that does not depend on the position of the first difference.
+fn constant_time_eq(left: &[u8], right: &[u8]) -> bool {
+    left.len() == right.len()
+        && left
+            .iter()
+            .zip(right.iter())
+            .fold(0u8, |acc, (left_byte, right_byte)| {
+                acc | (left_byte ^ right_byte)
+            })
+            == 0
+}
+
+/// Rejection of the requests without valid credentials.
+#[derive(Debug)]
+pub struct Unauthenticated;
+
+impl warp::reject::Reject for Unauthenticated {}
+
+/// Extracts the principal of the request, rejecting the requests without valid credentials.
+pub fn authentication_filter(
+    authenticator: Arc<Authenticator>,
+) -> impl Filter<Extract = (Principal,), Error = Rejection> + Clone {
+    warp::header::optional::<String>("authorization").and_then(
+        move |authorization_opt: Option<String>| {
+            let principal_opt = authenticator.authenticate(authorization_opt.as_deref());
+            async move { principal_opt.ok_or_else(|| warp::reject::custom(Unauthenticated)) }
+        },
+    )
+}
+
+/// Rejects the requests without valid credentials.
+pub fn require_authentication(
+    authenticator: Arc<Authenticator>,
+) -> impl Filter<Extract = (), Error = Rejection> + Clone {
+    authentication_filter(authenticator)
+        .map(|_principal| ())
+        .untuple_one()
+}
+
+/// Authenticates the gRPC requests from their `authorization` metadata, and stores their
+/// principal in their extensions, where the handlers get it with [`grpc_principal`].
+///
+/// The `bearer.token` metadata, set by `jaeger-query` when it propagates the bearer token of
+/// the Jaeger UI requests, is accepted in place of a bearer `authorization`.
+pub fn grpc_auth_interceptor(
+    authenticator: Arc<Authenticator>,
+) -> impl FnMut(tonic::Request<()>) -> Result<tonic::Request<()>, tonic::Status> + Clone {
+    move |mut request: tonic::Request<()>| {
+        let metadata = request.metadata();
+        let authorization_opt = match metadata.get("authorization") {
+            Some(authorization) => authorization.to_str().ok().map(ToString::to_string),
+            None => metadata
+                .get(JAEGER_BEARER_TOKEN_KEY)
+                .and_then(|token| token.to_str().ok())
+                .map(|token| format!("Bearer {}", token)),
+        };
+        let principal = authenticator
+            .authenticate(authorization_opt.as_deref())
+            .ok_or_else(|| ApiError::Unauthenticated.grpc_status())?;
+        request.extensions_mut().insert(principal);
+        Ok(request)
+    }
+}
+
+/// Returns the principal of a gRPC request authenticated by [`grpc_auth_interceptor`].
+pub fn grpc_principal<T>(request: &tonic::Request<T>) -> Result<Principal, tonic::Status> {
+    request
+        .extensions()
+        .get::<Principal>()
+        .cloned()
+        .ok_or_else(|| ApiError::Unauthenticated.grpc_status())
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    pub(crate) fn test_authenticator() -> anyhow::Result<Authenticator> {
+        let temp_dir = tempfile::tempdir()?;
+        let auth_file_path = temp_dir.path().join("auth.json");
+        std::fs::write(
+            &auth_file_path,
+            r#"{
+                "users": [
+                    {"name": "grafana", "token_sha256": "5a4806aaf8e14a7c4a89f70d1ef7b2324ddae2b85c417a9270d87b4db806b6a0", "permissions": {"logs-*": "read"}},
+                    {"name": "admin", "password_sha256": "8e70fdbd0400b7a21539fd15fb4ab86c129f7cbd99261dbb0d95c18df8dec177", "permissions": {"*": "write"}}
+                ]
+            }"#,
+        )?;
+        Authenticator::open(&auth_file_path)
+    }
+
+    #[test]
+    fn test_sha256() {
+        // echo -n grafana-token | sha256sum
+        assert_eq!(
+            hex::encode(sha256("grafana-token")),
+            "5a4806aaf8e14a7c4a89f70d1ef7b2324ddae2b85c417a9270d87b4db806b6a0"
+        );
+    }
+
+    #[test]
+    fn test_authenticator_authenticate() -> anyhow::Result<()> {
+        let authenticator = test_authenticator()?;
+        assert!(matches!(
+            authenticator.authenticate(Some("Bearer grafana-token")),
+            Some(Principal::User(user)) if user.name == "grafana"
+        ));
+        let basic_credentials = format!("Basic {}", base64::encode("admin:admin-password"));
+        assert!(matches!(
+            authenticator.authenticate(Some(&basic_credentials)),
+            Some(Principal::User(user)) if user.name == "admin"
+        ));
+        assert!(authenticator.authenticate(None).is_none());
+        assert!(authenticator
+            .authenticate(Some("Bearer wrong-token"))
+            .is_none());
+        let wrong_credentials = format!("Basic {}", base64::encode("grafana:admin-password"));
+        assert!(authenticator
+            .authenticate(Some(&wrong_credentials))
+            .is_none());
+        assert!(matches!(
+            Authenticator::disabled().authenticate(None),
+            Some(Principal::Anonymous)
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_principal_check_index_access() -> anyhow::Result<()> {
+        let authenticator = test_authenticator()?;
+        let grafana = authenticator
+            .authenticate(Some("Bearer grafana-token"))
+            .unwrap();
+        assert!(grafana
+            .check_index_access("logs-nginx", Access::Read)
+            .is_ok());
+        assert!(grafana
+            .check_index_access("logs-nginx", Access::Write)
+            .is_err());
+        assert!(grafana
+            .check_index_access("wikipedia", Access::Read)
+            .is_err());
+        let basic_credentials = format!("Basic {}", base64::encode("admin:admin-password"));
+        let admin = authenticator
+            .authenticate(Some(&basic_credentials))
+            .unwrap();
+        assert!(admin.check_index_access("wikipedia", Access::Read).is_ok());
+        assert!(admin.check_index_access("wikipedia", Access::Write).is_ok());
+        assert!(Principal::Anonymous
+            .check_index_access("wikipedia", Access::Write)
+            .is_ok());
+        Ok(())
+    }
+
+    #[test]
+    fn test_grpc_auth_interceptor() -> anyhow::Result<()> {
+        let mut interceptor = grpc_auth_interceptor(Arc::new(test_authenticator()?));
+        let status = interceptor(tonic::Request::new(())).unwrap_err();
+        assert_eq!(status.code(), tonic::Code::Unauthenticated);
+        let mut request = tonic::Request::new(());
+        request
+            .metadata_mut()
+            .insert("authorization", "Bearer wrong-token".parse()?);
+        let status = interceptor(request).unwrap_err();
+        assert_eq!(status.code(), tonic::Code::Unauthenticated);
+        let mut request = tonic::Request::new(());
+        request
+            .metadata_mut()
+            .insert("authorization", "Bearer grafana-token".parse()?);
+        let request = interceptor(request)?;
+        let principal = grpc_principal(&request)?;
+        assert!(principal
+            .check_index_access("logs-app", Access::Read)
+            .is_ok());
+        assert!(principal
+            .check_index_access("logs-app", Access::Write)
+            .is_err());
+        let mut anonymous_interceptor = grpc_auth_interceptor(Arc::new(Authenticator::disabled()));
+        let request = anonymous_interceptor(tonic::Request::new(()))?;
+        assert!(matches!(grpc_principal(&request)?, Principal::Anonymous));
+        assert!(grpc_principal(&tonic::Request::new(())).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_authenticator_rejects_user_without_credentials() -> anyhow::Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let auth_file_path = temp_dir.path().join("auth.json");
+        std::fs::write(&auth_file_path, r#"{"users": [{"name": "nobody"}]}"#)?;
+        assert!(Authenticator::open(&auth_file_path).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_authenticator_rejects_plaintext_secrets() -> anyhow::Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let auth_file_path = temp_dir.path().join("auth.json");
+        std::fs::write(
+            &auth_file_path,
+            r#"{"users": [{"name": "grafana", "token": "grafana-token"}]}"#,
+        )?;
+        assert!(Authenticator::open(&auth_file_path).is_err());
+        std::fs::write(
+            &auth_file_path,
+            r#"{"users": [{"name": "grafana", "token_sha256": "grafana-token"}]}"#,
+        )?;
+        assert!(Authenticator::open(&auth_file_path).is_err());
+        Ok(())
+    }
+}
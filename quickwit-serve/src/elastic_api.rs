@@ -0,0 +1,764 @@
+// Copyright (C) 2021 Quickwit, Inc.
+//
+// Quickwit is offered under the AGPL v3.0 and as commercial software.
+// For commercial licensing, contact us at hello@quickwit.io.
+//
+// AGPL:
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+//! Subset of the Elasticsearch REST API, for the tools that speak the Elasticsearch protocol.
+//!
+//! The endpoints are mounted under `/api/v1/_elastic`:
+//! - `POST _bulk` and `POST <index>/_bulk` push documents to the in-server ingest pipelines and
+//!   reply once they are published.
+//! - `GET|POST <index>/_search` translates a subset of the query DSL, see [`query_dsl`], and
+//!   returns the hits in the shape of Elasticsearch.
+//! - `GET _cat/indices` lists the indexes of the metastore.
+
+mod bulk;
+mod query_dsl;
+
+use std::collections::BTreeMap;
+use std::convert::Infallible;
+use std::sync::Arc;
+use std::time::Instant;
+
+use bytes::Bytes;
+use futures::future::join_all;
+use quickwit_metastore::{Metastore, SplitState};
+use quickwit_search::{SearchError, SearchService};
+use serde::{Deserialize, Serialize};
+use warp::hyper::header::CONTENT_TYPE;
+use warp::{reply, Filter, Rejection, Reply};
+
+use self::bulk::{parse_bulk_request, BulkAction, BulkItem, BulkOperation, BulkResponse};
+use self::query_dsl::{translate_query, QueryDsl, TranslatedQuery};
+use crate::auth::{authentication_filter, Access, Authenticator, Principal};
+use crate::ingest::IngestService;
+use crate::rest::{json_body, Format};
+use crate::ApiError;
+
+/// Maximum size of the body of a bulk request.
+const MAX_BULK_BODY_NUM_BYTES: u64 = 10 * 1_024 * 1_024;
+
+/// Handlers of the Elasticsearch-compatible API.
+pub fn elastic_api_handlers<TSearchService: SearchService>(
+    search_service: Arc<TSearchService>,
+    metastore: Arc<dyn Metastore>,
+    ingest_service: Arc<IngestService>,
+    authenticator: Arc<Authenticator>,
+) -> impl Filter<Extract = impl warp::Reply, Error = Rejection> + Clone {
+    bulk_handler(ingest_service, authenticator.clone())
+        .or(search_handler(
+            search_service,
+            metastore.clone(),
+            authenticator.clone(),
+        ))
+        .or(cat_indices_handler(metastore, authenticator))
+}
+
+fn bulk_handler(
+    ingest_service: Arc<IngestService>,
+    authenticator: Arc<Authenticator>,
+) -> impl Filter<Extract = impl warp::Reply, Error = Rejection> + Clone {
+    bulk_filter(authenticator)
+        .and(warp::any().map(move || ingest_service.clone()))
+        .and_then(bulk)
+}
+
+// Requests are authenticated before their body is read.
+fn bulk_filter(
+    authenticator: Arc<Authenticator>,
+) -> impl Filter<Extract = (Option<String>, Principal, Bytes), Error = Rejection> + Clone {
+    warp::path!("api" / "v1" / "_elastic" / "_bulk")
+        .map(|| None::<String>)
+        .or(warp::path!("api" / "v1" / "_elastic" / String / "_bulk").map(Some))
+        .unify()
+        .and(warp::post().or(warp::put()).unify())
+        .and(authentication_filter(authenticator))
+        .and(warp::body::content_length_limit(MAX_BULK_BODY_NUM_BYTES))
+        .and(warp::body::bytes())
+}
+
+async fn bulk(
+    default_index_id_opt: Option<String>,
+    principal: Principal,
+    body: Bytes,
+    ingest_service: Arc<IngestService>,
+) -> Result<impl warp::Reply, Infallible> {
+    Ok(Format::Json
+        .make_reply(bulk_endpoint(default_index_id_opt, &body, &principal, &ingest_service).await))
+}
+
+async fn bulk_endpoint(
+    default_index_id_opt: Option<String>,
+    body: &[u8],
+    principal: &Principal,
+    ingest_service: &IngestService,
+) -> Result<BulkResponse, ApiError> {
+    let start_instant = Instant::now();
+    let body = std::str::from_utf8(body).map_err(|_| {
+        ApiError::InvalidArgument("bulk request body is not valid UTF-8".to_string())
+    })?;
+    let operations = parse_bulk_request(body, default_index_id_opt.as_deref())
+        .map_err(ApiError::InvalidArgument)?;
+    let mut operation_errors: Vec<Option<Arc<ApiError>>> = Vec::with_capacity(operations.len());
+    // Documents to ingest, grouped by index, along with the ordinals of their operations.
+    let mut docs_per_index: BTreeMap<&str, (Vec<usize>, Vec<String>)> = BTreeMap::new();
+    for (operation_ord, operation) in operations.iter().enumerate() {
+        match check_operation(operation, principal) {
+            Ok((index_id, doc)) => {
+                let (operation_ords, docs) = docs_per_index.entry(index_id).or_default();
+                operation_ords.push(operation_ord);
+                docs.push(doc.to_string());
+                operation_errors.push(None);
+            }
+            Err(api_error) => operation_errors.push(Some(Arc::new(api_error))),
+        }
+    }
+    // Each ingestion waits for its documents to be published, so the indexes are fed
+    // concurrently.
+    let ingest_results = join_all(docs_per_index.into_iter().map(
+        |(index_id, (operation_ords, docs))| async move {
+            (operation_ords, ingest_service.ingest(index_id, docs).await)
+        },
+    ))
+    .await;
+    for (operation_ords, ingest_result) in ingest_results {
+        if let Err(api_error) = ingest_result {
+            let api_error = Arc::new(api_error);
+            for operation_ord in operation_ords {
+                operation_errors[operation_ord] = Some(api_error.clone());
+            }
+        }
+    }
+    let items = operations
+        .iter()
+        .zip(operation_errors.iter())
+        .map(|(operation, error_opt)| BulkItem::new(operation, error_opt.as_deref()))
+        .collect();
+    Ok(BulkResponse {
+        took: start_instant.elapsed().as_millis() as u64,
+        errors: operation_errors.iter().any(Option::is_some),
+        items,
+    })
+}
+
+/// Returns the index and the document of an operation that can be ingested.
+fn check_operation<'a>(
+    operation: &'a BulkOperation,
+    principal: &Principal,
+) -> Result<(&'a str, &'a str), ApiError> {
+    let index_id = operation.index_id_opt.as_deref().ok_or_else(|| {
+        ApiError::InvalidArgument("operation does not specify an `_index`".to_string())
+    })?;
+    if matches!(operation.action, BulkAction::Update | BulkAction::Delete) {
+        return Err(ApiError::InvalidArgument(
+            "only `index` and `create` operations are supported".to_string(),
+        ));
+    }
+    principal.check_index_access(index_id, Access::Write)?;
+    let doc = operation
+        .doc_opt
+        .as_deref()
+        .expect("Index and create operations should have a source.");
+    Ok((index_id, doc))
+}
+
+/// Body of a search request. Aggregations, sorting and the other parameters are not
+/// supported.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ElasticSearchRequest {
+    #[serde(default)]
+    query: Option<QueryDsl>,
+    #[serde(default = "default_size")]
+    size: u64,
+    #[serde(default)]
+    from: u64,
+}
+
+fn default_size() -> u64 {
+    10
+}
+
+impl Default for ElasticSearchRequest {
+    fn default() -> Self {
+        ElasticSearchRequest {
+            query: None,
+            size: default_size(),
+            from: 0,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct ElasticSearchResponse {
+    took: u64,
+    timed_out: bool,
+    #[serde(rename = "_shards")]
+    shards: ElasticShards,
+    hits: ElasticHits,
+}
+
+/// Quickwit searches splits rather than shards: the search is reported as a single shard.
+#[derive(Debug, Serialize)]
+struct ElasticShards {
+    total: u64,
+    successful: u64,
+    skipped: u64,
+    failed: u64,
+}
+
+#[derive(Debug, Serialize)]
+struct ElasticHits {
+    total: ElasticTotalHits,
+    max_score: Option<f32>,
+    hits: Vec<ElasticHit>,
+}
+
+#[derive(Debug, Serialize)]
+struct ElasticTotalHits {
+    value: u64,
+    relation: &'static str,
+}
+
+/// Hits are not scored, and are identified by their address within their split.
+#[derive(Debug, Serialize)]
+struct ElasticHit {
+    #[serde(rename = "_index")]
+    index_id: String,
+    #[serde(rename = "_id")]
+    doc_id: String,
+    #[serde(rename = "_score")]
+    score: Option<f32>,
+    #[serde(rename = "_source")]
+    source: serde_json::Value,
+}
+
+fn search_handler<TSearchService: SearchService>(
+    search_service: Arc<TSearchService>,
+    metastore: Arc<dyn Metastore>,
+    authenticator: Arc<Authenticator>,
+) -> impl Filter<Extract = impl warp::Reply, Error = Rejection> + Clone {
+    search_filter(authenticator)
+        .and(warp::any().map(move || search_service.clone()))
+        .and(warp::any().map(move || metastore.clone()))
+        .and_then(search)
+}
+
+// Requests are authenticated before their body is parsed.
+fn search_filter(
+    authenticator: Arc<Authenticator>,
+) -> impl Filter<Extract = (String, Principal, ElasticSearchRequest), Error = Rejection> + Clone {
+    warp::path!("api" / "v1" / "_elastic" / String / "_search")
+        .and(warp::get().or(warp::post()).unify())
+        .and(authentication_filter(authenticator))
+        .and(
+            warp::get()
+                .map(ElasticSearchRequest::default)
+                .or(warp::post().and(json_body()))
+                .unify(),
+        )
+}
+
+async fn search<TSearchService: SearchService>(
+    index_id: String,
+    principal: Principal,
+    search_request: ElasticSearchRequest,
+    search_service: Arc<TSearchService>,
+    metastore: Arc<dyn Metastore>,
+) -> Result<impl warp::Reply, Infallible> {
+    Ok(Format::Json.make_reply(
+        search_endpoint(
+            index_id,
+            search_request,
+            &principal,
+            &*search_service,
+            &*metastore,
+        )
+        .await,
+    ))
+}
+
+async fn search_endpoint<TSearchService: SearchService>(
+    index_id: String,
+    search_request: ElasticSearchRequest,
+    principal: &Principal,
+    search_service: &TSearchService,
+    metastore: &dyn Metastore,
+) -> Result<ElasticSearchResponse, ApiError> {
+    principal.check_index_access(&index_id, Access::Read)?;
+    let translated_query = if let Some(query_dsl) = search_request.query.as_ref() {
+        let index_metadata = metastore.index_metadata(&index_id).await?;
+        let schema = index_metadata.index_config.schema();
+        let timestamp_field_opt = index_metadata.index_config.timestamp_field_name();
+        translate_query(query_dsl, &schema, timestamp_field_opt.as_deref())
+            .map_err(SearchError::InvalidQuery)?
+    } else {
+        TranslatedQuery {
+            query: "*".to_string(),
+            ..Default::default()
+        }
+    };
+    let search_request = quickwit_proto::SearchRequest {
+        index_id: index_id.clone(),
+        query: translated_query.query,
+        search_fields: translated_query.search_fields,
+        start_timestamp: translated_query.start_timestamp,
+        end_timestamp: translated_query.end_timestamp,
+        max_hits: search_request.size,
+        start_offset: search_request.from,
+        tags: Vec::new(),
+        allow_partial_results: false,
+        timeout_millis: None,
+    };
+    let search_response = search_service.root_search(search_request).await?;
+    let hits = search_response
+        .hits
+        .into_iter()
+        .map(|hit| {
+            let source = serde_json::from_str(&hit.json).map_err(|error| {
+                SearchError::InternalError(format!(
+                    "Failed to parse document `{}` as JSON: `{}`.",
+                    hit.json, error
+                ))
+            })?;
+            let doc_id = hit
+                .partial_hit
+                .map(|partial_hit| {
+                    format!(
+                        "{}:{}:{}",
+                        partial_hit.split_id, partial_hit.segment_ord, partial_hit.doc_id
+                    )
+                })
+                .unwrap_or_default();
+            Ok(ElasticHit {
+                index_id: index_id.clone(),
+                doc_id,
+                score: None,
+                source,
+            })
+        })
+        .collect::<Result<Vec<_>, SearchError>>()?;
+    Ok(ElasticSearchResponse {
+        took: search_response.elapsed_time_micros / 1_000,
+        timed_out: false,
+        shards: ElasticShards {
+            total: 1,
+            successful: 1,
+            skipped: 0,
+            failed: 0,
+        },
+        hits: ElasticHits {
+            total: ElasticTotalHits {
+                value: search_response.num_hits,
+                relation: "eq",
+            },
+            max_score: None,
+            hits,
+        },
+    })
+}
+
+/// Query string of `_cat/indices` requests. As in Elasticsearch, the indexes are listed as a
+/// text table unless `format=json` is set, and the table has a header line if `v` is set.
+#[derive(Debug, Deserialize)]
+struct CatIndicesQueryString {
+    #[serde(default)]
+    format: Option<String>,
+    #[serde(default)]
+    v: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct CatIndicesRow {
+    health: &'static str,
+    status: &'static str,
+    index: String,
+    #[serde(rename = "docs.count")]
+    docs_count: String,
+    #[serde(rename = "store.size")]
+    store_size: String,
+}
+
+const CAT_INDICES_COLUMNS: [&str; 5] = ["health", "status", "index", "docs.count", "store.size"];
+
+impl CatIndicesRow {
+    fn values(&self) -> [&str; 5] {
+        [
+            self.health,
+            self.status,
+            &self.index,
+            &self.docs_count,
+            &self.store_size,
+        ]
+    }
+}
+
+fn cat_indices_handler(
+    metastore: Arc<dyn Metastore>,
+    authenticator: Arc<Authenticator>,
+) -> impl Filter<Extract = impl warp::Reply, Error = Rejection> + Clone {
+    warp::path!("api" / "v1" / "_elastic" / "_cat" / "indices")
+        .and(warp::get())
+        // Unlike `serde_qs`, `warp::query` accepts flags without a value such as `?v`.
+        .and(warp::query::<CatIndicesQueryString>())
+        .and(authentication_filter(authenticator))
+        .and(warp::any().map(move || metastore.clone()))
+        .and_then(cat_indices)
+}
+
+async fn cat_indices(
+    query_string: CatIndicesQueryString,
+    principal: Principal,
+    metastore: Arc<dyn Metastore>,
+) -> Result<warp::reply::Response, Infallible> {
+    let rows = match cat_indices_endpoint(&principal, &*metastore).await {
+        Ok(rows) => rows,
+        Err(api_error) => {
+            return Ok(Format::Json
+                .make_reply(Err::<(), ApiError>(api_error))
+                .into_response())
+        }
+    };
+    if query_string.format.as_deref() == Some("json") {
+        return Ok(Format::Json.make_reply(Ok(rows)).into_response());
+    }
+    let table = render_table(&rows, query_string.v.is_some());
+    Ok(reply::with_header(table, CONTENT_TYPE, "text/plain; charset=UTF-8").into_response())
+}
+
+async fn cat_indices_endpoint(
+    principal: &Principal,
+    metastore: &dyn Metastore,
+) -> Result<Vec<CatIndicesRow>, ApiError> {
+    let mut rows = Vec::new();
+    for index_metadata in metastore.list_indexes_metadatas().await? {
+        let index_id = index_metadata.index_id;
+        if principal
+            .check_index_access(&index_id, Access::Read)
+            .is_err()
+        {
+            continue;
+        }
+        let splits = metastore
+            .list_splits(&index_id, SplitState::Published, None, &[])
+            .await?;
+        let num_docs: usize = splits
+            .iter()
+            .map(|split| split.split_metadata.num_records)
+            .sum();
+        let num_bytes: u64 = splits
+            .iter()
+            .map(|split| split.split_metadata.size_in_bytes)
+            .sum();
+        rows.push(CatIndicesRow {
+            health: "green",
+            status: "open",
+            index: index_id,
+            docs_count: num_docs.to_string(),
+            store_size: num_bytes.to_string(),
+        });
+    }
+    Ok(rows)
+}
+
+fn render_table(rows: &[CatIndicesRow], with_header: bool) -> String {
+    let mut lines: Vec<[&str; 5]> = Vec::new();
+    if with_header {
+        lines.push(CAT_INDICES_COLUMNS);
+    }
+    lines.extend(rows.iter().map(CatIndicesRow::values));
+    let mut column_widths = [0; 5];
+    for line in &lines {
+        for (column_width, value) in column_widths.iter_mut().zip(line.iter()) {
+            *column_width = (*column_width).max(value.len());
+        }
+    }
+    let mut table = String::new();
+    for line in &lines {
+        let cells: Vec<String> = line
+            .iter()
+            .zip(column_widths.iter())
+            .map(|(value, column_width)| format!("{:width$}", value, width = *column_width))
+            .collect();
+        table.push_str(cells.join(" ").trim_end());
+        table.push('\n');
+    }
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ops::Range;
+
+    use mockall::predicate;
+    use quickwit_index_config::WikipediaIndexConfig;
+    use quickwit_indexing::mock_split_meta;
+    use quickwit_metastore::checkpoint::Checkpoint;
+    use quickwit_metastore::{IndexMetadata, MetastoreError, MockMetastore};
+    use quickwit_search::MockSearchService;
+    use quickwit_storage::StorageUriResolver;
+    use serde_json::json;
+
+    use super::*;
+    use crate::rest::recover_fn;
+
+    fn index_metadata(index_id: &str) -> IndexMetadata {
+        IndexMetadata {
+            index_id: index_id.to_string(),
+            index_uri: format!("file:///path/to/{}", index_id),
+            index_config: Arc::new(WikipediaIndexConfig::new()),
+            checkpoint: Checkpoint::default(),
+            encryption_key_id: None,
+        }
+    }
+
+    fn elastic_api_test_handlers(
+        mock_search_service: MockSearchService,
+        mock_metastore: MockMetastore,
+    ) -> impl Filter<Extract = impl warp::Reply, Error = Rejection> + Clone {
+        let metastore: Arc<dyn Metastore> = Arc::new(mock_metastore);
+        let ingest_service = Arc::new(IngestService::new(
+            "test-node".to_string(),
+            metastore.clone(),
+            StorageUriResolver::for_test(),
+        ));
+        elastic_api_handlers(
+            Arc::new(mock_search_service),
+            metastore,
+            ingest_service,
+            Arc::new(Authenticator::disabled()),
+        )
+        .recover(recover_fn)
+    }
+
+    #[tokio::test]
+    async fn test_elastic_api_search() -> anyhow::Result<()> {
+        let mut mock_metastore = MockMetastore::new();
+        mock_metastore
+            .expect_index_metadata()
+            .returning(|index_id| Ok(index_metadata(index_id)));
+        let mut mock_search_service = MockSearchService::new();
+        mock_search_service
+            .expect_root_search()
+            .with(predicate::eq(quickwit_proto::SearchRequest {
+                index_id: "wikipedia".to_string(),
+                query: r#"(+(title:"apple") -(url:"fruit"))"#.to_string(),
+                search_fields: Vec::new(),
+                start_timestamp: None,
+                end_timestamp: None,
+                max_hits: 5,
+                start_offset: 10,
+                tags: Vec::new(),
+                allow_partial_results: false,
+                timeout_millis: None,
+            }))
+            .returning(|_| {
+                Ok(quickwit_proto::SearchResponse {
+                    num_hits: 11,
+                    hits: vec![quickwit_proto::Hit {
+                        json: r#"{"title": ["Apple"]}"#.to_string(),
+                        partial_hit: Some(quickwit_proto::PartialHit {
+                            sorting_field_value: 0,
+                            split_id: "split1".to_string(),
+                            segment_ord: 0,
+                            doc_id: 3,
+                        }),
+                    }],
+                    elapsed_time_micros: 2_500,
+                    errors: Vec::new(),
+                    failed_splits: Vec::new(),
+                })
+            });
+        let handlers = elastic_api_test_handlers(mock_search_service, mock_metastore);
+        let resp = warp::test::request()
+            .method("POST")
+            .path("/api/v1/_elastic/wikipedia/_search")
+            .json(&json!({
+                "query": {
+                    "bool": {
+                        "must": {"match": {"title": "apple"}},
+                        "must_not": {"match": {"url": "fruit"}},
+                    }
+                },
+                "size": 5,
+                "from": 10,
+            }))
+            .reply(&handlers)
+            .await;
+        assert_eq!(resp.status(), 200);
+        let resp_json: serde_json::Value = serde_json::from_slice(resp.body())?;
+        assert_eq!(
+            resp_json,
+            json!({
+                "took": 2,
+                "timed_out": false,
+                "_shards": {"total": 1, "successful": 1, "skipped": 0, "failed": 0},
+                "hits": {
+                    "total": {"value": 11, "relation": "eq"},
+                    "max_score": null,
+                    "hits": [{
+                        "_index": "wikipedia",
+                        "_id": "split1:0:3",
+                        "_score": null,
+                        "_source": {"title": ["Apple"]},
+                    }],
+                },
+            })
+        );
+
+        let resp = warp::test::request()
+            .method("POST")
+            .path("/api/v1/_elastic/wikipedia/_search")
+            .json(&json!({"query": {"range": {"body": {"gte": 3}}}}))
+            .reply(&handlers)
+            .await;
+        assert_eq!(resp.status(), 400);
+        let resp_json: serde_json::Value = serde_json::from_slice(resp.body())?;
+        assert_eq!(resp_json["code"], "INVALID_QUERY");
+
+        // The `url` field is tokenized, so a `term` query would not match exact values.
+        let resp = warp::test::request()
+            .method("POST")
+            .path("/api/v1/_elastic/wikipedia/_search")
+            .json(&json!({"query": {"term": {"url": "https://en.wikipedia.org/wiki/Apple"}}}))
+            .reply(&handlers)
+            .await;
+        assert_eq!(resp.status(), 400);
+        let resp_json: serde_json::Value = serde_json::from_slice(resp.body())?;
+        assert_eq!(resp_json["code"], "INVALID_QUERY");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_elastic_api_bulk_errors() -> anyhow::Result<()> {
+        let mut mock_metastore = MockMetastore::new();
+        mock_metastore
+            .expect_index_metadata()
+            .returning(|index_id| {
+                Err(MetastoreError::IndexDoesNotExist {
+                    index_id: index_id.to_string(),
+                })
+            });
+        let handlers = elastic_api_test_handlers(MockSearchService::new(), mock_metastore);
+        let body = r#"
+            {"index": {"_index": "unknown-index"}}
+            {"message": "hello"}
+            {"delete": {"_index": "logs", "_id": "1"}}
+            {"create": {}}
+            {"message": "world"}
+        "#;
+        let resp = warp::test::request()
+            .method("POST")
+            .path("/api/v1/_elastic/_bulk")
+            .body(body)
+            .reply(&handlers)
+            .await;
+        assert_eq!(resp.status(), 200);
+        let resp_json: serde_json::Value = serde_json::from_slice(resp.body())?;
+        assert_eq!(resp_json["errors"], true);
+        assert_eq!(
+            resp_json["items"],
+            json!([
+                {"index": {
+                    "_index": "unknown-index",
+                    "status": 404,
+                    "error": {
+                        "type": "INDEX_DOES_NOT_EXIST",
+                        "reason": "Index `unknown-index` does not exist.",
+                    },
+                }},
+                {"delete": {
+                    "_index": "logs",
+                    "_id": "1",
+                    "status": 400,
+                    "error": {
+                        "type": "INVALID_ARGUMENT",
+                        "reason": "only `index` and `create` operations are supported",
+                    },
+                }},
+                {"create": {
+                    "_index": null,
+                    "status": 400,
+                    "error": {
+                        "type": "INVALID_ARGUMENT",
+                        "reason": "operation does not specify an `_index`",
+                    },
+                }},
+            ])
+        );
+
+        let resp = warp::test::request()
+            .method("POST")
+            .path("/api/v1/_elastic/logs/_bulk")
+            .body(r#"{"index": {}}"#)
+            .reply(&handlers)
+            .await;
+        assert_eq!(resp.status(), 400);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_elastic_api_cat_indices() -> anyhow::Result<()> {
+        let mut mock_metastore = MockMetastore::new();
+        mock_metastore
+            .expect_list_indexes_metadatas()
+            .returning(|| {
+                Ok(vec![
+                    index_metadata("my-index"),
+                    index_metadata("my-empty-index"),
+                ])
+            });
+        mock_metastore.expect_list_splits().returning(
+            |index_id: &str,
+             _split_state: SplitState,
+             _time_range: Option<Range<i64>>,
+             _tags: &[String]| {
+                if index_id == "my-index" {
+                    Ok(vec![mock_split_meta("split1"), mock_split_meta("split2")])
+                } else {
+                    Ok(Vec::new())
+                }
+            },
+        );
+        let handlers = elastic_api_test_handlers(MockSearchService::new(), mock_metastore);
+        let resp = warp::test::request()
+            .path("/api/v1/_elastic/_cat/indices?format=json")
+            .reply(&handlers)
+            .await;
+        assert_eq!(resp.status(), 200);
+        let resp_json: serde_json::Value = serde_json::from_slice(resp.body())?;
+        assert_eq!(
+            resp_json,
+            json!([
+                {"health": "green", "status": "open", "index": "my-index", "docs.count": "20", "store.size": "512"},
+                {"health": "green", "status": "open", "index": "my-empty-index", "docs.count": "0", "store.size": "0"},
+            ])
+        );
+        let resp = warp::test::request()
+            .path("/api/v1/_elastic/_cat/indices?v")
+            .reply(&handlers)
+            .await;
+        assert_eq!(resp.status(), 200);
+        assert_eq!(
+            std::str::from_utf8(resp.body())?,
+            "health status index          docs.count store.size\n\
+             green  open   my-index       20         512\n\
+             green  open   my-empty-index 0          0\n"
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,226 @@
+// Copyright (C) 2021 Quickwit, Inc.
+//
+// Quickwit is offered under the AGPL v3.0 and as commercial software.
+// For commercial licensing, contact us at hello@quickwit.io.
+//
+// AGPL:
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+//! Parsing of the NDJSON body of `_bulk` requests, and shape of their responses.
+
+use std::collections::BTreeMap;
+
+use quickwit_common::service_error::{ErrorCode, ServiceError};
+use serde::{Deserialize, Serialize};
+
+use crate::ApiError;
+
+/// Action of a bulk operation. Only `index` and `create` are supported, the other actions
+/// are parsed in order to report an error for each of them.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BulkAction {
+    Index,
+    Create,
+    Update,
+    Delete,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum BulkActionLine {
+    Index(BulkActionMeta),
+    Create(BulkActionMeta),
+    Update(BulkActionMeta),
+    Delete(BulkActionMeta),
+}
+
+/// Metadata of an action. The other parameters, such as `routing`, are ignored.
+#[derive(Deserialize)]
+struct BulkActionMeta {
+    #[serde(rename = "_index")]
+    #[serde(default)]
+    index_id_opt: Option<String>,
+    #[serde(rename = "_id")]
+    #[serde(default)]
+    doc_id_opt: Option<String>,
+}
+
+/// An operation of a bulk request.
+#[derive(Debug, PartialEq, Eq)]
+pub struct BulkOperation {
+    pub action: BulkAction,
+    pub index_id_opt: Option<String>,
+    /// Quickwit does not identify documents: the ID is only echoed in the response.
+    pub doc_id_opt: Option<String>,
+    /// The source line of the operation, if the action has one.
+    pub doc_opt: Option<String>,
+}
+
+/// Parses the body of a bulk request. Operations without an `_index` target the index of the
+/// request path, if any.
+pub fn parse_bulk_request(
+    body: &str,
+    default_index_id_opt: Option<&str>,
+) -> Result<Vec<BulkOperation>, String> {
+    let mut lines = body
+        .lines()
+        .enumerate()
+        .map(|(line_ord, line)| (line_ord + 1, line.trim()))
+        .filter(|(_, line)| !line.is_empty());
+    let mut operations = Vec::new();
+    while let Some((line_number, action_line)) = lines.next() {
+        let action_line: BulkActionLine = serde_json::from_str(action_line)
+            .map_err(|error| format!("invalid action on line {}: {}", line_number, error))?;
+        let (action, meta) = match action_line {
+            BulkActionLine::Index(meta) => (BulkAction::Index, meta),
+            BulkActionLine::Create(meta) => (BulkAction::Create, meta),
+            BulkActionLine::Update(meta) => (BulkAction::Update, meta),
+            BulkActionLine::Delete(meta) => (BulkAction::Delete, meta),
+        };
+        let doc_opt = if action == BulkAction::Delete {
+            None
+        } else {
+            let (doc_line_number, doc_line) = lines.next().ok_or_else(|| {
+                format!(
+                    "action on line {} is not followed by a source line",
+                    line_number
+                )
+            })?;
+            serde_json::from_str::<serde_json::Map<String, serde_json::Value>>(doc_line).map_err(
+                |error| format!("invalid source on line {}: {}", doc_line_number, error),
+            )?;
+            Some(doc_line.to_string())
+        };
+        operations.push(BulkOperation {
+            action,
+            index_id_opt: meta
+                .index_id_opt
+                .or_else(|| default_index_id_opt.map(ToString::to_string)),
+            doc_id_opt: meta.doc_id_opt,
+            doc_opt,
+        });
+    }
+    Ok(operations)
+}
+
+/// Response to a bulk request, with one item per operation, in the order of the request.
+#[derive(Debug, Serialize)]
+pub struct BulkResponse {
+    /// Time spent processing the request, in milliseconds.
+    pub took: u64,
+    /// Whether any operation failed.
+    pub errors: bool,
+    pub items: Vec<BulkItem>,
+}
+
+/// Outcome of an operation, keyed by its action, e.g. `{"index": {"status": 201, ...}}`.
+#[derive(Debug, Serialize)]
+pub struct BulkItem(BTreeMap<BulkAction, BulkItemResponse>);
+
+impl BulkItem {
+    pub fn new(operation: &BulkOperation, error_opt: Option<&ApiError>) -> Self {
+        let (status, result, error) = match error_opt {
+            None => (201, Some("created"), None),
+            Some(api_error) => (
+                api_error.http_status_code().as_u16(),
+                None,
+                Some(BulkItemError {
+                    error_type: api_error.error_code(),
+                    reason: api_error.error_message(),
+                }),
+            ),
+        };
+        let item_response = BulkItemResponse {
+            index_id: operation.index_id_opt.clone(),
+            doc_id_opt: operation.doc_id_opt.clone(),
+            status,
+            result,
+            error,
+        };
+        BulkItem(std::iter::once((operation.action, item_response)).collect())
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct BulkItemResponse {
+    #[serde(rename = "_index")]
+    index_id: Option<String>,
+    #[serde(rename = "_id")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    doc_id_opt: Option<String>,
+    status: u16,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    result: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<BulkItemError>,
+}
+
+#[derive(Debug, Serialize)]
+struct BulkItemError {
+    #[serde(rename = "type")]
+    error_type: ErrorCode,
+    reason: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_bulk_request() {
+        let body = r#"
+            {"index": {"_index": "logs", "_id": "1"}}
+            {"message": "hello"}
+            {"create": {}}
+            {"message": "world"}
+
+            {"delete": {"_index": "logs", "_id": "1"}}
+        "#;
+        let operations = parse_bulk_request(body, Some("default-index")).unwrap();
+        assert_eq!(
+            operations,
+            vec![
+                BulkOperation {
+                    action: BulkAction::Index,
+                    index_id_opt: Some("logs".to_string()),
+                    doc_id_opt: Some("1".to_string()),
+                    doc_opt: Some(r#"{"message": "hello"}"#.to_string()),
+                },
+                BulkOperation {
+                    action: BulkAction::Create,
+                    index_id_opt: Some("default-index".to_string()),
+                    doc_id_opt: None,
+                    doc_opt: Some(r#"{"message": "world"}"#.to_string()),
+                },
+                BulkOperation {
+                    action: BulkAction::Delete,
+                    index_id_opt: Some("logs".to_string()),
+                    doc_id_opt: Some("1".to_string()),
+                    doc_opt: None,
+                },
+            ]
+        );
+        assert_eq!(
+            parse_bulk_request(r#"{"index": {}}"#, None),
+            Err("action on line 1 is not followed by a source line".to_string())
+        );
+        assert!(parse_bulk_request("{\"index\": {}}\nnot json", None)
+            .unwrap_err()
+            .starts_with("invalid source on line 2"));
+        assert!(parse_bulk_request(r#"{"upsert": {}}"#, None)
+            .unwrap_err()
+            .starts_with("invalid action on line 1"));
+    }
+}
@@ -0,0 +1,628 @@
+// Copyright (C) 2021 Quickwit, Inc.
+//
+// Quickwit is offered under the AGPL v3.0 and as commercial software.
+// For commercial licensing, contact us at hello@quickwit.io.
+//
+// AGPL:
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+//! Translation of a subset of the Elasticsearch query DSL into the query language of Quickwit.
+//!
+//! The supported queries are `match_all`, `match`, `term`, `bool`, `range` and
+//! `query_string`. Since the query language does not support range clauses, `range` queries
+//! are only accepted on the timestamp field of the index, at a place where they restrict the
+//! whole query: they are then turned into the start and end timestamps of the search request.
+//! Queries that cannot be translated faithfully, like `term` queries on tokenized fields, are
+//! rejected rather than approximated.
+
+use std::collections::HashMap;
+
+use chrono::{DateTime, NaiveDate, NaiveDateTime};
+use serde::Deserialize;
+use serde_json::Value as JsonValue;
+use tantivy::schema::{FieldType, Schema};
+
+/// Formats of the `range` query bounds expressing dates in the ISO 8601 format.
+const DATE_FORMATS: [&str; 2] = ["strict_date_optional_time", "date_optional_time"];
+
+/// A query of the Elasticsearch query DSL.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum QueryDsl {
+    MatchAll(MatchAllQuery),
+    Match(HashMap<String, MatchQuery>),
+    Term(HashMap<String, TermQuery>),
+    Bool(BoolQuery),
+    Range(HashMap<String, RangeQuery>),
+    QueryString(QueryStringQuery),
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MatchAllQuery {}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum MatchQuery {
+    Params {
+        query: JsonValue,
+        #[serde(default)]
+        operator: Operator,
+    },
+    Text(JsonValue),
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Operator {
+    #[serde(alias = "OR")]
+    Or,
+    #[serde(alias = "AND")]
+    And,
+}
+
+impl Default for Operator {
+    fn default() -> Self {
+        Operator::Or
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum TermQuery {
+    Params { value: JsonValue },
+    Value(JsonValue),
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BoolQuery {
+    #[serde(default)]
+    must: OneOrMany<QueryDsl>,
+    #[serde(default)]
+    filter: OneOrMany<QueryDsl>,
+    #[serde(default)]
+    should: OneOrMany<QueryDsl>,
+    #[serde(default)]
+    must_not: OneOrMany<QueryDsl>,
+}
+
+/// Bounds of a range query. Numbers are expressed in the unit of the timestamp field, unless
+/// `format` is `epoch_second` or `epoch_millis`. Dates are converted to seconds since the Unix
+/// epoch.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RangeQuery {
+    gt: Option<RangeBound>,
+    gte: Option<RangeBound>,
+    lt: Option<RangeBound>,
+    lte: Option<RangeBound>,
+    #[serde(default)]
+    format: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum RangeBound {
+    Number(i64),
+    Text(String),
+}
+
+impl RangeQuery {
+    /// Returns the timestamp of a bound.
+    fn timestamp(&self, bound: &RangeBound) -> Result<i64, String> {
+        match (self.format.as_deref(), bound) {
+            (None, RangeBound::Number(number))
+            | (Some("epoch_second"), RangeBound::Number(number)) => Ok(*number),
+            (Some("epoch_second"), RangeBound::Text(text)) => parse_number(text),
+            (Some("epoch_millis"), RangeBound::Number(millis)) => Ok(millis.div_euclid(1_000)),
+            (Some("epoch_millis"), RangeBound::Text(text)) => {
+                Ok(parse_number(text)?.div_euclid(1_000))
+            }
+            (None, RangeBound::Text(text)) => parse_date(text),
+            (Some(format), RangeBound::Text(text)) if DATE_FORMATS.contains(&format) => {
+                parse_date(text)
+            }
+            (Some(format), _) => Err(format!(
+                "`range` query bound {:?} does not match the format `{}`, supported formats are \
+                 `epoch_second`, `epoch_millis`, `strict_date_optional_time` and \
+                 `date_optional_time`",
+                bound, format
+            )),
+        }
+    }
+}
+
+fn parse_number(text: &str) -> Result<i64, String> {
+    text.parse::<i64>()
+        .map_err(|_| format!("`range` query bound `{}` is not a number", text))
+}
+
+/// Parses an ISO 8601 date, e.g. `2021-10-11T08:30:52.000Z`, into seconds since the Unix epoch.
+/// Dates without time zone are in UTC.
+fn parse_date(text: &str) -> Result<i64, String> {
+    if text.starts_with("now") || text.contains("||") {
+        return Err(format!(
+            "`range` query bound `{}` uses date math, which is not supported",
+            text
+        ));
+    }
+    if let Ok(date_time) = DateTime::parse_from_rfc3339(text) {
+        return Ok(date_time.timestamp());
+    }
+    if let Ok(date_time) = NaiveDateTime::parse_from_str(text, "%Y-%m-%dT%H:%M:%S%.f") {
+        return Ok(date_time.timestamp());
+    }
+    if let Ok(date) = NaiveDate::parse_from_str(text, "%Y-%m-%d") {
+        return Ok(date.and_hms(0, 0, 0).timestamp());
+    }
+    Err(format!(
+        "`range` query bound `{}` is neither a number nor an ISO 8601 date",
+        text
+    ))
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct QueryStringQuery {
+    query: String,
+    #[serde(default)]
+    default_field: Option<String>,
+    #[serde(default)]
+    fields: Vec<String>,
+}
+
+/// The clauses of a bool query are either a single query or an array of queries.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum OneOrMany<T> {
+    One(Box<T>),
+    Many(Vec<T>),
+}
+
+impl<T> Default for OneOrMany<T> {
+    fn default() -> Self {
+        OneOrMany::Many(Vec::new())
+    }
+}
+
+impl<T> OneOrMany<T> {
+    fn as_slice(&self) -> &[T] {
+        match self {
+            OneOrMany::One(item) => std::slice::from_ref(item.as_ref()),
+            OneOrMany::Many(items) => items,
+        }
+    }
+}
+
+/// The query of a search request, in the query language of Quickwit.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct TranslatedQuery {
+    pub query: String,
+    pub search_fields: Vec<String>,
+    pub start_timestamp: Option<i64>,
+    pub end_timestamp: Option<i64>,
+}
+
+/// Translates a query of the DSL against the schema of the index. Range queries are only
+/// accepted on the given timestamp field, if any.
+pub fn translate_query(
+    query_dsl: &QueryDsl,
+    schema: &Schema,
+    timestamp_field_opt: Option<&str>,
+) -> Result<TranslatedQuery, String> {
+    let mut translator = Translator {
+        schema,
+        timestamp_field_opt,
+        translated_query: TranslatedQuery::default(),
+    };
+    let query_opt = translator.translate(query_dsl, true)?;
+    let mut translated_query = translator.translated_query;
+    translated_query.query = query_opt.unwrap_or_else(|| "*".to_string());
+    Ok(translated_query)
+}
+
+struct Translator<'a> {
+    schema: &'a Schema,
+    timestamp_field_opt: Option<&'a str>,
+    translated_query: TranslatedQuery,
+}
+
+impl<'a> Translator<'a> {
+    /// Returns the translation of the query, or `None` if the query only restricts the
+    /// timestamp range. `is_required` tells whether every matching document must match the
+    /// query, which is where range queries can be accepted.
+    fn translate(
+        &mut self,
+        query_dsl: &QueryDsl,
+        is_required: bool,
+    ) -> Result<Option<String>, String> {
+        match query_dsl {
+            QueryDsl::MatchAll(_) => Ok(Some("*".to_string())),
+            QueryDsl::Match(field_queries) => {
+                let (field_name, match_query) = single_field(field_queries, "match")?;
+                let (text_json, operator) = match match_query {
+                    MatchQuery::Params { query, operator } => (query, *operator),
+                    MatchQuery::Text(text_json) => (text_json, Operator::Or),
+                };
+                let text = scalar_to_string(text_json)?;
+                let prefix = if operator == Operator::And { "+" } else { "" };
+                let clauses = text
+                    .split_whitespace()
+                    .map(|word| Ok(format!("{}{}", prefix, field_clause(field_name, word)?)))
+                    .collect::<Result<Vec<_>, String>>()?;
+                if clauses.is_empty() {
+                    return Err(format!("`match` query on field `{}` is empty", field_name));
+                }
+                Ok(Some(format!("({})", clauses.join(" "))))
+            }
+            QueryDsl::Term(field_queries) => {
+                let (field_name, term_query) = single_field(field_queries, "term")?;
+                check_term_field(self.schema, field_name)?;
+                let value_json = match term_query {
+                    TermQuery::Params { value } | TermQuery::Value(value) => value,
+                };
+                let value = scalar_to_string(value_json)?;
+                Ok(Some(field_clause(field_name, &value)?))
+            }
+            QueryDsl::Range(field_queries) => {
+                let (field_name, range_query) = single_field(field_queries, "range")?;
+                if self.timestamp_field_opt != Some(field_name.as_str()) {
+                    return Err(format!(
+                        "`range` queries are only supported on the timestamp field of the index, \
+                         not on `{}`",
+                        field_name
+                    ));
+                }
+                if !is_required {
+                    return Err(
+                        "`range` queries are only supported at the top level of the query, or in \
+                         the `must` and `filter` clauses of a top-level `bool` query"
+                            .to_string(),
+                    );
+                }
+                self.restrict_timestamp_range(range_query)?;
+                Ok(None)
+            }
+            QueryDsl::QueryString(query_string_query) => {
+                let query = query_string_query.query.trim();
+                if query.is_empty() {
+                    return Err("`query_string` query is empty".to_string());
+                }
+                let search_fields: Vec<String> = query_string_query
+                    .default_field
+                    .iter()
+                    .chain(query_string_query.fields.iter())
+                    .cloned()
+                    .collect();
+                if !search_fields.is_empty() {
+                    let current_search_fields = &self.translated_query.search_fields;
+                    if !current_search_fields.is_empty() && current_search_fields != &search_fields
+                    {
+                        return Err("`query_string` queries with different fields are not \
+                                    supported"
+                            .to_string());
+                    }
+                    self.translated_query.search_fields = search_fields;
+                }
+                Ok(Some(format!("({})", query)))
+            }
+            QueryDsl::Bool(bool_query) => self.translate_bool_query(bool_query, is_required),
+        }
+    }
+
+    fn translate_bool_query(
+        &mut self,
+        bool_query: &BoolQuery,
+        is_required: bool,
+    ) -> Result<Option<String>, String> {
+        let mut clauses = Vec::new();
+        for sub_query in bool_query
+            .must
+            .as_slice()
+            .iter()
+            .chain(bool_query.filter.as_slice())
+        {
+            if let Some(clause) = self.translate(sub_query, is_required)? {
+                clauses.push(format!("+{}", clause));
+            }
+        }
+        for sub_query in bool_query.should.as_slice() {
+            if let Some(clause) = self.translate(sub_query, false)? {
+                clauses.push(clause);
+            }
+        }
+        let has_positive_clause = !clauses.is_empty();
+        for sub_query in bool_query.must_not.as_slice() {
+            if let Some(clause) = self.translate(sub_query, false)? {
+                clauses.push(format!("-{}", clause));
+            }
+        }
+        if clauses.is_empty() {
+            // An empty bool query matches every document.
+            return Ok(if is_required {
+                None
+            } else {
+                Some("*".to_string())
+            });
+        }
+        // A query made only of exclusions matches nothing unless it also matches every
+        // document.
+        if !has_positive_clause {
+            clauses.insert(0, "*".to_string());
+        }
+        Ok(Some(format!("({})", clauses.join(" "))))
+    }
+
+    fn restrict_timestamp_range(&mut self, range_query: &RangeQuery) -> Result<(), String> {
+        let timestamp_opt = |bound_opt: &Option<RangeBound>| {
+            bound_opt
+                .as_ref()
+                .map(|bound| range_query.timestamp(bound))
+                .transpose()
+        };
+        let overflow_error = || "`range` query bound is out of range".to_string();
+        // The start timestamp is inclusive and the end timestamp exclusive.
+        let start_bounds = [
+            timestamp_opt(&range_query.gte)?,
+            timestamp_opt(&range_query.gt)?
+                .map(|gt| gt.checked_add(1).ok_or_else(overflow_error))
+                .transpose()?,
+        ];
+        let end_bounds = [
+            timestamp_opt(&range_query.lt)?,
+            timestamp_opt(&range_query.lte)?
+                .map(|lte| lte.checked_add(1).ok_or_else(overflow_error))
+                .transpose()?,
+        ];
+        for start_bound in start_bounds.iter().flatten() {
+            let start_timestamp = &mut self.translated_query.start_timestamp;
+            *start_timestamp = Some(start_timestamp.map_or(*start_bound, |t| t.max(*start_bound)));
+        }
+        for end_bound in end_bounds.iter().flatten() {
+            let end_timestamp = &mut self.translated_query.end_timestamp;
+            *end_timestamp = Some(end_timestamp.map_or(*end_bound, |t| t.min(*end_bound)));
+        }
+        Ok(())
+    }
+}
+
+fn single_field<'a, T>(
+    field_queries: &'a HashMap<String, T>,
+    query_name: &str,
+) -> Result<(&'a String, &'a T), String> {
+    if field_queries.len() != 1 {
+        return Err(format!(
+            "`{}` query must target exactly one field",
+            query_name
+        ));
+    }
+    Ok(field_queries
+        .iter()
+        .next()
+        .expect("The map should have exactly one entry."))
+}
+
+fn scalar_to_string(value_json: &JsonValue) -> Result<String, String> {
+    match value_json {
+        JsonValue::String(value) => Ok(value.clone()),
+        JsonValue::Number(value) => Ok(value.to_string()),
+        JsonValue::Bool(value) => Ok(value.to_string()),
+        _ => Err(format!(
+            "expected a string, a number or a boolean, got `{}`",
+            value_json
+        )),
+    }
+}
+
+/// Checks that the field indexes its values as single terms, so that a `term` query matches
+/// them exactly, like in Elasticsearch.
+fn check_term_field(schema: &Schema, field_name: &str) -> Result<(), String> {
+    let field = schema
+        .get_field(field_name)
+        .ok_or_else(|| format!("field `{}` does not exist", field_name))?;
+    let field_entry = schema.get_field_entry(field);
+    if !field_entry.is_indexed() {
+        return Err(format!("field `{}` is not indexed", field_name));
+    }
+    match field_entry.field_type() {
+        FieldType::U64(_) | FieldType::I64(_) | FieldType::F64(_) => Ok(()),
+        FieldType::Str(text_options) => {
+            let tokenizer = text_options
+                .get_indexing_options()
+                .map(|indexing_options| indexing_options.tokenizer())
+                .unwrap_or_default();
+            if tokenizer == "raw" {
+                return Ok(());
+            }
+            Err(format!(
+                "`term` queries are only supported on numeric fields and on text fields using the \
+                 `raw` tokenizer, field `{}` uses the `{}` tokenizer: use a `match` query instead",
+                field_name, tokenizer
+            ))
+        }
+        _ => Err(format!(
+            "`term` queries are not supported on field `{}`",
+            field_name
+        )),
+    }
+}
+
+/// Returns a clause matching the value on the field. The value goes through the tokenizer of
+/// the field, as a phrase.
+fn field_clause(field_name: &str, value: &str) -> Result<String, String> {
+    let is_valid_field_name = !field_name.is_empty()
+        && field_name
+            .chars()
+            .all(|c| c.is_alphanumeric() || c == '_' || c == '.');
+    if !is_valid_field_name {
+        return Err(format!("invalid field name `{}`", field_name));
+    }
+    if value.is_empty() || value.contains('"') {
+        return Err(format!(
+            "value `{}` of field `{}` is empty or contains a double quote, which is not supported",
+            value, field_name
+        ));
+    }
+    Ok(format!("{}:\"{}\"", field_name, value))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use tantivy::schema::{FAST, INDEXED, STRING, TEXT};
+
+    use super::*;
+
+    fn translate(query_json: JsonValue) -> Result<TranslatedQuery, String> {
+        let mut schema_builder = Schema::builder();
+        schema_builder.add_i64_field("timestamp", INDEXED | FAST);
+        schema_builder.add_text_field("body", TEXT);
+        schema_builder.add_text_field("level", STRING);
+        schema_builder.add_text_field("app", STRING);
+        schema_builder.add_i64_field("status", INDEXED);
+        let schema = schema_builder.build();
+        let query_dsl: QueryDsl = serde_json::from_value(query_json).map_err(|e| e.to_string())?;
+        translate_query(&query_dsl, &schema, Some("timestamp"))
+    }
+
+    fn translated_query(query: &str) -> TranslatedQuery {
+        TranslatedQuery {
+            query: query.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_translate_leaf_queries() {
+        assert_eq!(
+            translate(json!({"match_all": {}})),
+            Ok(translated_query("*"))
+        );
+        assert_eq!(
+            translate(json!({"match": {"body": "quick fox"}})),
+            Ok(translated_query(r#"(body:"quick" body:"fox")"#))
+        );
+        assert_eq!(
+            translate(json!({"match": {"body": {"query": "quick fox", "operator": "and"}}})),
+            Ok(translated_query(r#"(+body:"quick" +body:"fox")"#))
+        );
+        assert_eq!(
+            translate(json!({"term": {"status": 404}})),
+            Ok(translated_query(r#"status:"404""#))
+        );
+        assert_eq!(
+            translate(json!({"term": {"level": {"value": "ERROR"}}})),
+            Ok(translated_query(r#"level:"ERROR""#))
+        );
+        assert_eq!(
+            translate(json!({"query_string": {"query": "fox AND dog", "fields": ["body"]}})),
+            Ok(TranslatedQuery {
+                query: "(fox AND dog)".to_string(),
+                search_fields: vec!["body".to_string()],
+                ..Default::default()
+            })
+        );
+        assert!(translate(json!({"term": {"level": "say \"hi\""}})).is_err());
+        assert!(translate(json!({"term": {"body": "fox"}}))
+            .unwrap_err()
+            .contains("use a `match` query instead"));
+        assert!(translate(json!({"term": {"unknown": "fox"}})).is_err());
+        assert!(translate(json!({"term": {"level": "a", "body": "b"}})).is_err());
+        assert!(translate(json!({"prefix": {"level": "a"}})).is_err());
+    }
+
+    #[test]
+    fn test_translate_bool_query() {
+        assert_eq!(
+            translate(json!({
+                "bool": {
+                    "must": {"term": {"level": "ERROR"}},
+                    "should": [{"term": {"app": "api"}}, {"term": {"app": "web"}}],
+                    "must_not": [{"match": {"body": "timeout"}}],
+                }
+            })),
+            Ok(translated_query(
+                r#"(+level:"ERROR" app:"api" app:"web" -(body:"timeout"))"#
+            ))
+        );
+        assert_eq!(
+            translate(json!({"bool": {"must_not": {"term": {"level": "DEBUG"}}}})),
+            Ok(translated_query(r#"(* -level:"DEBUG")"#))
+        );
+        assert_eq!(translate(json!({"bool": {}})), Ok(translated_query("*")));
+    }
+
+    #[test]
+    fn test_translate_range_query() {
+        assert_eq!(
+            translate(json!({
+                "bool": {
+                    "filter": [
+                        {"range": {"timestamp": {"gte": 10, "lte": 20}}},
+                        {"range": {"timestamp": {"gt": 12}}},
+                        {"term": {"level": "ERROR"}},
+                    ]
+                }
+            })),
+            Ok(TranslatedQuery {
+                query: r#"(+level:"ERROR")"#.to_string(),
+                start_timestamp: Some(13),
+                end_timestamp: Some(21),
+                ..Default::default()
+            })
+        );
+        assert_eq!(
+            translate(json!({"range": {"timestamp": {"lt": 20}}})),
+            Ok(TranslatedQuery {
+                query: "*".to_string(),
+                end_timestamp: Some(20),
+                ..Default::default()
+            })
+        );
+        assert!(translate(json!({"range": {"status": {"gte": 400}}})).is_err());
+        assert!(translate(json!({"range": {"timestamp": {"gte": "now-15m"}}})).is_err());
+        assert!(translate(json!({"range": {"timestamp": {"gte": 10, "format": "yyyy"}}})).is_err());
+        assert!(translate(json!({
+            "bool": {"should": [{"range": {"timestamp": {"gte": 10}}}]}
+        }))
+        .is_err());
+        assert_eq!(
+            translate(
+                json!({"range": {"timestamp": {"gte": "2021-10-11", "lte": "2021-10-11T08:30:52.999Z", "format": "strict_date_optional_time"}}})
+            ),
+            Ok(TranslatedQuery {
+                query: "*".to_string(),
+                start_timestamp: Some(1_633_910_400),
+                end_timestamp: Some(1_633_941_053),
+                ..Default::default()
+            })
+        );
+        assert_eq!(
+            translate(
+                json!({"range": {"timestamp": {"gte": 1_633_941_052_500i64, "lt": "1633941053000", "format": "epoch_millis"}}})
+            ),
+            Ok(TranslatedQuery {
+                query: "*".to_string(),
+                start_timestamp: Some(1_633_941_052),
+                end_timestamp: Some(1_633_941_053),
+                ..Default::default()
+            })
+        );
+        assert!(translate(json!({
+            "bool": {"must_not": [{"range": {"timestamp": {"gte": 10}}}]}
+        }))
+        .is_err());
+    }
+}
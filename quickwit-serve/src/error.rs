@@ -17,57 +17,129 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program. If not, see <http://www.gnu.org/licenses/>.
 
+use std::collections::BTreeMap;
+
 use quickwit_cluster::error::ClusterError;
+use quickwit_common::service_error::{ErrorCode, ServiceError};
+use quickwit_metastore::MetastoreError;
 use quickwit_search::SearchError;
-use serde::ser::SerializeMap;
+use quickwit_storage::StorageError;
 use thiserror::Error;
 use warp::http;
-use warp::hyper::StatusCode;
 
 #[derive(Debug, Error)]
 pub enum ApiError {
     #[error("InvalidArgument: {0}.")]
     InvalidArgument(String),
-    // TODO rely on some an error serialization in the messsage
-    // to rebuild a structured SearchError, (the tonic Code is pretty useless)
-    // and build a meaningful ApiError instead of this
-    // silly wrapping.
     #[error("Search error. {0}.")]
     SearchError(#[from] SearchError),
     #[error("Cluster error. {0}.")]
     ClusterError(#[from] ClusterError),
+    #[error("Metastore error. {0}")]
+    MetastoreError(#[from] MetastoreError),
+    #[error("Storage error. {0}")]
+    StorageError(#[from] StorageError),
     #[error("Route not found")]
     NotFound,
+    #[error("Unauthenticated: valid credentials are required")]
+    Unauthenticated,
+    #[error("Forbidden: {0}.")]
+    Forbidden(String),
+    #[error("Internal error: {0}.")]
+    InternalError(String),
 }
 
-impl ApiError {
-    pub fn http_status_code(&self) -> http::StatusCode {
-        match &self {
-            ApiError::SearchError(search_error) => match search_error {
-                SearchError::IndexDoesNotExist { .. } => http::StatusCode::NOT_FOUND,
-                SearchError::InternalError(_) => http::StatusCode::INTERNAL_SERVER_ERROR,
-                SearchError::StorageResolverError(_) => http::StatusCode::INTERNAL_SERVER_ERROR,
-                SearchError::InvalidQuery(_) => http::StatusCode::BAD_REQUEST,
-            },
-            ApiError::ClusterError(_cluster_error) => http::StatusCode::INTERNAL_SERVER_ERROR,
-            ApiError::InvalidArgument(_err) => StatusCode::BAD_REQUEST,
-            ApiError::NotFound => http::StatusCode::NOT_FOUND,
+impl ServiceError for ApiError {
+    fn error_code(&self) -> ErrorCode {
+        match self {
+            ApiError::InvalidArgument(_) => ErrorCode::InvalidArgument,
+            ApiError::SearchError(search_error) => search_error.error_code(),
+            ApiError::ClusterError(cluster_error) => cluster_error.error_code(),
+            ApiError::MetastoreError(metastore_error) => metastore_error.error_code(),
+            ApiError::StorageError(storage_error) => storage_error.error_code(),
+            ApiError::NotFound => ErrorCode::NotFound,
+            ApiError::Unauthenticated => ErrorCode::Unauthenticated,
+            ApiError::Forbidden(_) => ErrorCode::Forbidden,
+            ApiError::InternalError(_) => ErrorCode::Internal,
+        }
+    }
+
+    fn error_message(&self) -> String {
+        match self {
+            ApiError::InvalidArgument(message)
+            | ApiError::Forbidden(message)
+            | ApiError::InternalError(message) => message.clone(),
+            ApiError::SearchError(search_error) => search_error.error_message(),
+            ApiError::ClusterError(cluster_error) => cluster_error.error_message(),
+            ApiError::MetastoreError(metastore_error) => metastore_error.error_message(),
+            ApiError::StorageError(storage_error) => storage_error.error_message(),
+            ApiError::NotFound => "Route not found.".to_string(),
+            ApiError::Unauthenticated => "Valid credentials are required.".to_string(),
+        }
+    }
+
+    fn error_details(&self) -> BTreeMap<String, String> {
+        match self {
+            ApiError::SearchError(search_error) => search_error.error_details(),
+            ApiError::ClusterError(cluster_error) => cluster_error.error_details(),
+            ApiError::MetastoreError(metastore_error) => metastore_error.error_details(),
+            ApiError::StorageError(storage_error) => storage_error.error_details(),
+            _ => BTreeMap::new(),
         }
     }
+}
 
-    pub fn message(&self) -> String {
-        // TODO fixme
-        format!("{}", self)
+impl ApiError {
+    pub fn http_status_code(&self) -> http::StatusCode {
+        http::StatusCode::from_u16(self.error_code().http_status_code())
+            .unwrap_or(http::StatusCode::INTERNAL_SERVER_ERROR)
     }
 }
 
-// TODO implement nicer serialization of errors.
+/// Errors are serialized as an [`ErrorResponse`](quickwit_common::service_error::ErrorResponse),
+/// e.g. `{"code": "INDEX_DOES_NOT_EXIST", "message": "...", "details": {...}, "retryable":
+/// false}`.
 impl serde::Serialize for ApiError {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where S: serde::Serializer {
-        let mut map = serializer.serialize_map(Some(2))?;
-        map.serialize_key("error")?;
-        map.serialize_value(&self.message())?;
-        map.end()
+        self.error_response().serialize(serializer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_api_error_serialization() -> anyhow::Result<()> {
+        let api_error = ApiError::from(SearchError::IndexDoesNotExist {
+            index_id: "my-index".to_string(),
+        });
+        assert_eq!(api_error.http_status_code(), http::StatusCode::NOT_FOUND);
+        assert_eq!(
+            serde_json::to_value(&api_error)?,
+            serde_json::json!({
+                "code": "INDEX_DOES_NOT_EXIST",
+                "message": "Index `my-index` does not exist.",
+                "details": {"index_id": "my-index"},
+                "retryable": false,
+            })
+        );
+        let api_error = ApiError::from(MetastoreError::ConnectionError {
+            message: "connection refused".to_string(),
+        });
+        assert_eq!(
+            api_error.http_status_code(),
+            http::StatusCode::SERVICE_UNAVAILABLE
+        );
+        assert_eq!(
+            serde_json::to_value(&api_error)?,
+            serde_json::json!({
+                "code": "UNAVAILABLE",
+                "message": "Connection error: `connection refused`.",
+                "retryable": true,
+            })
+        );
+        Ok(())
     }
 }
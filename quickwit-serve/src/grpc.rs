@@ -18,23 +18,54 @@
 // along with this program. If not, see <http://www.gnu.org/licenses/>.
 
 use std::net::SocketAddr;
+use std::sync::Arc;
 
 use quickwit_proto::cluster_service_server::ClusterServiceServer;
+use quickwit_proto::jaeger::storage::v1::span_reader_plugin_server::SpanReaderPluginServer;
+use quickwit_proto::opentelemetry::proto::collector::logs::v1::logs_service_server::LogsServiceServer;
+use quickwit_proto::opentelemetry::proto::collector::trace::v1::trace_service_server::TraceServiceServer;
 use quickwit_proto::search_service_server::SearchServiceServer;
-use tonic::transport::Server;
+use tonic::transport::{Server, ServerTlsConfig};
 use tracing::*;
 
+use crate::auth::{grpc_auth_interceptor, Authenticator};
 use crate::grpc_adapter::cluster_adapter::GrpcClusterAdapter;
 use crate::grpc_adapter::search_adapter::GrpcSearchAdapter;
+use crate::jaeger::JaegerService;
+use crate::otlp::{OtlpGrpcLogsService, OtlpGrpcTraceService};
 
-/// Start gRPC service given a gRPC address and a search service and cluster service.
+/// Services internal to the cluster, served by the gRPC server.
+pub struct GrpcServices {
+    pub search_service: GrpcSearchAdapter,
+    pub cluster_service: GrpcClusterAdapter,
+}
+
+/// Services called from outside of the cluster, served by the public gRPC server.
+pub struct PublicGrpcServices {
+    pub otlp_logs_service: OtlpGrpcLogsService,
+    pub otlp_trace_service: OtlpGrpcTraceService,
+    pub jaeger_service: JaegerService,
+}
+
+/// Start gRPC service given a gRPC address and the services it serves.
+/// The service is served over TLS if a TLS config is given. These services are internal
+/// to the cluster: they are not authenticated, and the TLS config requires the callers to
+/// present their certificate when mutual TLS is enabled.
 pub async fn start_grpc_service(
     grpc_addr: SocketAddr,
-    search_service: GrpcSearchAdapter,
-    cluster_service: GrpcClusterAdapter,
+    services: GrpcServices,
+    tls_config_opt: Option<ServerTlsConfig>,
 ) -> anyhow::Result<()> {
-    info!(grpc_addr=?grpc_addr, "Start gRPC service.");
-    Server::builder()
+    let GrpcServices {
+        search_service,
+        cluster_service,
+    } = services;
+    info!(grpc_addr=?grpc_addr, tls=tls_config_opt.is_some(), "Start gRPC service.");
+    let mut server = Server::builder();
+    if let Some(tls_config) = tls_config_opt {
+        server = server.tls_config(tls_config)?;
+    }
+    server
         .add_service(ClusterServiceServer::new(cluster_service))
         .add_service(SearchServiceServer::new(search_service))
         .serve(grpc_addr)
@@ -42,3 +73,43 @@ pub async fn start_grpc_service(
 
     Ok(())
 }
+
+/// Start the public gRPC service, serving the OTLP receiver and the Jaeger span reader.
+///
+/// It listens on its own address so that the clients of these services, e.g. OpenTelemetry
+/// collectors or `jaeger-query`, do not need a node certificate when mutual TLS is enabled.
+/// Their requests are authenticated like those of the REST API instead.
+pub async fn start_public_grpc_service(
+    public_grpc_addr: SocketAddr,
+    services: PublicGrpcServices,
+    authenticator: Arc<Authenticator>,
+    tls_config_opt: Option<ServerTlsConfig>,
+) -> anyhow::Result<()> {
+    let PublicGrpcServices {
+        otlp_logs_service,
+        otlp_trace_service,
+        jaeger_service,
+    } = services;
+    info!(public_grpc_addr=?public_grpc_addr, tls=tls_config_opt.is_some(), "Start public gRPC service.");
+    let mut server = Server::builder();
+    if let Some(tls_config) = tls_config_opt {
+        server = server.tls_config(tls_config)?;
+    }
+    server
+        .add_service(LogsServiceServer::with_interceptor(
+            otlp_logs_service,
+            grpc_auth_interceptor(authenticator.clone()),
+        ))
+        .add_service(TraceServiceServer::with_interceptor(
+            otlp_trace_service,
+            grpc_auth_interceptor(authenticator.clone()),
+        ))
+        .add_service(SpanReaderPluginServer::with_interceptor(
+            jaeger_service,
+            grpc_auth_interceptor(authenticator),
+        ))
+        .serve(public_grpc_addr)
+        .await?;
+
+    Ok(())
+}
@@ -17,5 +17,7 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program. If not, see <http://www.gnu.org/licenses/>.
 
+pub mod cache;
 pub mod cluster;
 pub mod health_check;
+pub mod metrics;
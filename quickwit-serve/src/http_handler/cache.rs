@@ -0,0 +1,165 @@
+// Copyright (C) 2021 Quickwit, Inc.
+//
+// Quickwit is offered under the AGPL v3.0 and as commercial software.
+// For commercial licensing, contact us at hello@quickwit.io.
+//
+// AGPL:
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use std::convert::Infallible;
+use std::sync::Arc;
+
+use quickwit_search::LeafSearchCache;
+use quickwit_storage::CacheStats;
+use serde::{Deserialize, Serialize};
+use warp::{Filter, Rejection};
+
+use crate::quickwit_cache::{CacheRouteStats, QuickwitCache};
+use crate::rest::Format;
+use crate::ApiError;
+
+/// This struct represents the QueryString passed to
+/// the rest API.
+#[derive(Deserialize, Debug, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+#[serde(rename_all = "camelCase")]
+pub struct CacheStatsRequestQueryString {
+    /// The output format requested.
+    #[serde(default)]
+    pub format: Format,
+}
+
+/// Cache stats response.
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct CacheStatsResponse {
+    /// Statistics of each cache route.
+    pub routes: Vec<CacheRouteStats>,
+    /// Statistics of the leaf search cache.
+    pub leaf_search: LeafSearchCacheStats,
+}
+
+/// Leaf search cache stats.
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct LeafSearchCacheStats {
+    #[serde(flatten)]
+    pub stats: CacheStats,
+    /// Share of the lookups that found their entry, zero if the cache was never looked up.
+    pub hit_rate: f64,
+}
+
+impl From<CacheStats> for LeafSearchCacheStats {
+    fn from(stats: CacheStats) -> Self {
+        let num_lookups = stats.num_hits + stats.num_misses;
+        let hit_rate = if num_lookups == 0 {
+            0.0
+        } else {
+            stats.num_hits as f64 / num_lookups as f64
+        };
+        LeafSearchCacheStats { stats, hit_rate }
+    }
+}
+
+/// Cache stats handler.
+pub fn cache_stats_handler(
+    quickwit_cache: Arc<QuickwitCache>,
+    leaf_search_cache: Arc<LeafSearchCache>,
+) -> impl Filter<Extract = (impl warp::Reply,), Error = Rejection> + Clone {
+    cache_stats_filter()
+        .and(warp::any().map(move || quickwit_cache.clone()))
+        .and(warp::any().map(move || leaf_search_cache.clone()))
+        .and_then(cache_stats)
+}
+
+fn cache_stats_filter(
+) -> impl Filter<Extract = (CacheStatsRequestQueryString,), Error = Rejection> + Clone {
+    warp::path!("api" / "v1" / "cache" / "stats")
+        .and(warp::get())
+        .and(serde_qs::warp::query(serde_qs::Config::default()))
+}
+
+async fn cache_stats(
+    request: CacheStatsRequestQueryString,
+    quickwit_cache: Arc<QuickwitCache>,
+    leaf_search_cache: Arc<LeafSearchCache>,
+) -> Result<impl warp::Reply, Infallible> {
+    let cache_stats_response = CacheStatsResponse {
+        routes: quickwit_cache.route_stats(),
+        leaf_search: leaf_search_cache.stats().into(),
+    };
+    Ok(request
+        .format
+        .make_reply(Ok::<_, ApiError>(cache_stats_response)))
+}
+
+#[cfg(test)]
+mod tests {
+    use quickwit_storage::Cache;
+    use serde_json::json;
+
+    use super::*;
+    use crate::CacheRouteConfig;
+
+    #[tokio::test]
+    async fn test_rest_cache_stats() -> anyhow::Result<()> {
+        let quickwit_cache = Arc::new(QuickwitCache::new(&[CacheRouteConfig {
+            path_suffix: ".fast".to_string(),
+            capacity_in_bytes: 1_000,
+        }]));
+        quickwit_cache
+            .get(std::path::Path::new("split.split/seg.fast"), 0..3)
+            .await;
+        let leaf_search_cache = Arc::new(LeafSearchCache::with_capacity_in_bytes(1_000));
+        let cache_stats_api_handler = cache_stats_handler(quickwit_cache, leaf_search_cache);
+        let resp = warp::test::request()
+            .path("/api/v1/cache/stats?format=json")
+            .reply(&cache_stats_api_handler)
+            .await;
+        assert_eq!(resp.status(), 200);
+        let resp_json: serde_json::Value = serde_json::from_slice(resp.body())?;
+        let expected_resp_json = json!({
+            "routes": [{
+                "pathSuffix": ".fast",
+                "numHits": 0,
+                "numMisses": 1,
+                "numEvictions": 0,
+                "numBytes": 0,
+            }],
+            "leafSearch": {
+                "numHits": 0,
+                "numMisses": 0,
+                "numEvictions": 0,
+                "numBytes": 0,
+                "hitRate": 0.0,
+            }
+        });
+        assert_eq!(resp_json, expected_resp_json);
+        Ok(())
+    }
+
+    #[test]
+    fn test_leaf_search_cache_stats_hit_rate() {
+        let leaf_search_cache_stats = LeafSearchCacheStats::from(CacheStats {
+            num_hits: 3,
+            num_misses: 1,
+            ..Default::default()
+        });
+        assert_eq!(leaf_search_cache_stats.hit_rate, 0.75);
+        assert_eq!(
+            LeafSearchCacheStats::from(CacheStats::default()).hit_rate,
+            0.0
+        );
+    }
+}
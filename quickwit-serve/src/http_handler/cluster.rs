@@ -41,7 +41,7 @@ pub struct ListMembersRequestQueryString {
 /// Cluster handler.
 pub fn cluster_handler<TClusterService: ClusterService>(
     cluster_service: Arc<TClusterService>,
-) -> impl Filter<Extract = impl warp::Reply, Error = Rejection> + Clone {
+) -> impl Filter<Extract = (impl warp::Reply,), Error = Rejection> + Clone {
     list_members_filter()
         .and(warp::any().map(move || cluster_service.clone()))
         .and_then(list_members)
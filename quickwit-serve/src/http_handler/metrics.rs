@@ -0,0 +1,151 @@
+// Copyright (C) 2021 Quickwit, Inc.
+//
+// Quickwit is offered under the AGPL v3.0 and as commercial software.
+// For commercial licensing, contact us at hello@quickwit.io.
+//
+// AGPL:
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use std::convert::Infallible;
+use std::sync::Arc;
+
+use once_cell::sync::Lazy;
+use quickwit_common::metrics::{
+    metrics_text_payload, new_counter_vec, new_gauge_vec, IntCounter, IntCounterVec, IntGaugeVec,
+};
+use warp::hyper::header::CONTENT_TYPE;
+use warp::{reply, Filter, Rejection};
+
+use crate::quickwit_cache::QuickwitCache;
+
+/// Content type of the prometheus text exposition format.
+const PROMETHEUS_TEXT_CONTENT_TYPE: &str = "text/plain; version=0.0.4";
+
+struct CacheMetrics {
+    num_hits: IntCounterVec,
+    num_misses: IntCounterVec,
+    num_evictions: IntCounterVec,
+    num_bytes: IntGaugeVec,
+}
+
+impl Default for CacheMetrics {
+    fn default() -> Self {
+        CacheMetrics {
+            num_hits: new_counter_vec(
+                "quickwit_cache_hits_total",
+                "Number of cache hits since the node started.",
+                &["route"],
+            ),
+            num_misses: new_counter_vec(
+                "quickwit_cache_misses_total",
+                "Number of cache misses since the node started.",
+                &["route"],
+            ),
+            num_evictions: new_counter_vec(
+                "quickwit_cache_evictions_total",
+                "Number of cache evictions since the node started.",
+                &["route"],
+            ),
+            num_bytes: new_gauge_vec(
+                "quickwit_cache_bytes",
+                "Number of bytes currently held in the cache.",
+                &["route"],
+            ),
+        }
+    }
+}
+
+static CACHE_METRICS: Lazy<CacheMetrics> = Lazy::new(CacheMetrics::default);
+
+/// Advances a counter to the given value. Counters never go backwards.
+fn catch_up_counter(counter: IntCounter, value: u64) {
+    counter.inc_by(value.saturating_sub(counter.get()));
+}
+
+/// Copies the statistics of the cache routes into their metrics.
+///
+/// The caches keep their own counters, so they are only sampled when metrics are scraped.
+fn update_cache_metrics(quickwit_cache: &QuickwitCache) {
+    for route_stats in quickwit_cache.route_stats() {
+        let labels = [route_stats.path_suffix.as_str()];
+        let stats = route_stats.stats;
+        catch_up_counter(
+            CACHE_METRICS.num_hits.with_label_values(&labels),
+            stats.num_hits,
+        );
+        catch_up_counter(
+            CACHE_METRICS.num_misses.with_label_values(&labels),
+            stats.num_misses,
+        );
+        catch_up_counter(
+            CACHE_METRICS.num_evictions.with_label_values(&labels),
+            stats.num_evictions,
+        );
+        CACHE_METRICS
+            .num_bytes
+            .with_label_values(&labels)
+            .set(stats.num_bytes as i64);
+    }
+}
+
+/// Prometheus metrics handler.
+pub fn metrics_handler(
+    quickwit_cache: Arc<QuickwitCache>,
+) -> impl Filter<Extract = (impl warp::Reply,), Error = Rejection> + Clone {
+    warp::path!("metrics")
+        .and(warp::get())
+        .and(warp::any().map(move || quickwit_cache.clone()))
+        .and_then(metrics)
+}
+
+async fn metrics(quickwit_cache: Arc<QuickwitCache>) -> Result<impl warp::Reply, Infallible> {
+    update_cache_metrics(&quickwit_cache);
+    Ok(reply::with_header(
+        metrics_text_payload(),
+        CONTENT_TYPE,
+        PROMETHEUS_TEXT_CONTENT_TYPE,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use quickwit_storage::Cache;
+
+    use super::*;
+    use crate::CacheRouteConfig;
+
+    #[tokio::test]
+    async fn test_rest_metrics() -> anyhow::Result<()> {
+        let quickwit_cache = Arc::new(QuickwitCache::new(&[CacheRouteConfig {
+            path_suffix: ".term".to_string(),
+            capacity_in_bytes: 1_000,
+        }]));
+        quickwit_cache
+            .get(std::path::Path::new("split.split/seg.term"), 0..3)
+            .await;
+        let metrics_api_handler = metrics_handler(quickwit_cache);
+        let resp = warp::test::request()
+            .path("/metrics")
+            .reply(&metrics_api_handler)
+            .await;
+        assert_eq!(resp.status(), 200);
+        assert_eq!(
+            resp.headers().get(CONTENT_TYPE).unwrap(),
+            PROMETHEUS_TEXT_CONTENT_TYPE
+        );
+        let body = std::str::from_utf8(resp.body())?;
+        assert!(body.contains("quickwit_cache_misses_total{route=\".term\"} 1"));
+        Ok(())
+    }
+}
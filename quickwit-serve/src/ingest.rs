@@ -0,0 +1,149 @@
+// Copyright (C) 2021 Quickwit, Inc.
+//
+// Quickwit is offered under the AGPL v3.0 and as commercial software.
+// For commercial licensing, contact us at hello@quickwit.io.
+//
+// AGPL:
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+//! In-server ingestion, used by the `_bulk` endpoint of the Elasticsearch-compatible API.
+
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::time::Duration;
+
+use byte_unit::Byte;
+use quickwit_actors::Universe;
+use quickwit_indexing::actors::{
+    IndexerParams, IndexingPipelineParams, IndexingPipelineSupervisor,
+};
+use quickwit_indexing::models::{CommitPolicy, ScratchDirectory};
+use quickwit_indexing::source::{IngestApiQueues, SourceConfig};
+use quickwit_metastore::Metastore;
+use quickwit_storage::StorageUriResolver;
+use tokio::sync::Mutex;
+use tracing::{error, info};
+
+use crate::ApiError;
+
+/// Heap size of the indexer of each ingest pipeline.
+const INGEST_PIPELINE_HEAP_SIZE_IN_BYTES: u64 = 500_000_000;
+
+/// Commit timeout of the ingest pipelines. Pushes only return once their documents are
+/// published, so it bounds the latency of the ingest requests.
+const INGEST_PIPELINE_COMMIT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// ID of the source of the pipelines fed by the ingest service.
+const INGEST_SOURCE_ID: &str = "_ingest-api";
+
+/// Pushes documents to per-index indexing pipelines running within the server.
+///
+/// A pipeline reading from the ingest API queue of the index is spawned on the first push to
+/// the index, and spawned again on the next push if it exits. Pushes return once the pipeline
+/// has published the split holding their documents, which are then searchable.
+///
+/// Each node records the documents it ingested in its own partition of the index checkpoint,
+/// named after the node ID, so that several nodes can feed the same index.
+///
+/// The ingest API queues belong to the service, so that distinct services, e.g. of several
+/// nodes running in the same process, do not share queues.
+pub struct IngestService {
+    node_id: String,
+    metastore: Arc<dyn Metastore>,
+    storage_uri_resolver: StorageUriResolver,
+    ingest_api_queues: IngestApiQueues,
+    universe: Universe,
+    running_pipelines: Arc<Mutex<HashSet<String>>>,
+}
+
+impl IngestService {
+    pub fn new(
+        node_id: String,
+        metastore: Arc<dyn Metastore>,
+        storage_uri_resolver: StorageUriResolver,
+    ) -> Self {
+        IngestService {
+            node_id,
+            metastore,
+            storage_uri_resolver,
+            ingest_api_queues: IngestApiQueues::default(),
+            universe: Universe::new(),
+            running_pipelines: Default::default(),
+        }
+    }
+
+    /// Indexes the JSON documents into the index, returning once they are published.
+    pub async fn ingest(&self, index_id: &str, docs: Vec<String>) -> Result<(), ApiError> {
+        self.ensure_pipeline(index_id).await?;
+        self.ingest_api_queues
+            .queue(index_id)
+            .push(docs)
+            .await
+            .map_err(|error| ApiError::InternalError(error.to_string()))
+    }
+
+    async fn ensure_pipeline(&self, index_id: &str) -> Result<(), ApiError> {
+        let mut running_pipelines = self.running_pipelines.lock().await;
+        if running_pipelines.contains(index_id) {
+            return Ok(());
+        }
+        // Fails with `IndexDoesNotExist` before spawning anything for unknown indexes.
+        self.metastore.index_metadata(index_id).await?;
+        let scratch_directory = ScratchDirectory::try_new_temp().map_err(|io_error| {
+            ApiError::InternalError(format!("Failed to create scratch directory: {}", io_error))
+        })?;
+        let indexer_params = IndexerParams {
+            scratch_directory,
+            heap_size: Byte::from_bytes(INGEST_PIPELINE_HEAP_SIZE_IN_BYTES as u128),
+            commit_policy: CommitPolicy {
+                timeout: INGEST_PIPELINE_COMMIT_TIMEOUT,
+                ..Default::default()
+            },
+        };
+        let source_config = SourceConfig {
+            source_id: INGEST_SOURCE_ID.to_string(),
+            source_type: "ingest-api".to_string(),
+            params: serde_json::json!({
+                "queue_id": index_id,
+                "partition_id": self.node_id,
+            }),
+        };
+        let indexing_pipeline_params = IndexingPipelineParams {
+            index_id: index_id.to_string(),
+            source_config,
+            indexer_params,
+            metastore: self.metastore.clone(),
+            storage_uri_resolver: self.storage_uri_resolver.clone(),
+            ingest_api_queues: self.ingest_api_queues.clone(),
+        };
+        let indexing_supervisor = IndexingPipelineSupervisor::new(indexing_pipeline_params);
+        let (_supervisor_mailbox, supervisor_handle) =
+            self.universe.spawn_actor(indexing_supervisor).spawn_async();
+        info!(index_id = %index_id, "Spawned ingest pipeline.");
+        running_pipelines.insert(index_id.to_string());
+
+        let running_pipelines = self.running_pipelines.clone();
+        let index_id = index_id.to_string();
+        tokio::spawn(async move {
+            let (exit_status, _statistics) = supervisor_handle.join().await;
+            if exit_status.is_success() {
+                info!(index_id = %index_id, "Ingest pipeline exited.");
+            } else {
+                error!(index_id = %index_id, exit_status = ?exit_status, "Ingest pipeline failed.");
+            }
+            running_pipelines.lock().await.remove(&index_id);
+        });
+        Ok(())
+    }
+}
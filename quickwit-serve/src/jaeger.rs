@@ -0,0 +1,1001 @@
+// Copyright (C) 2021 Quickwit, Inc.
+//
+// Quickwit is offered under the AGPL v3.0 and as commercial software.
+// For commercial licensing, contact us at hello@quickwit.io.
+//
+// AGPL:
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+//! Jaeger storage plugin API, served over gRPC so that `jaeger-query` can find the traces of
+//! the `otel-traces` index with the `grpc-plugin` storage type.
+//!
+//! Only the span reader service is implemented: spans are written through the OTLP receiver.
+//! Requests are authenticated like those of the REST API, and require read access to the
+//! `otel-traces` index.
+
+use std::collections::{BTreeSet, HashMap};
+use std::ops::RangeInclusive;
+use std::pin::Pin;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use futures::stream;
+use quickwit_metastore::{Metastore, SplitState};
+use quickwit_proto::error::GrpcServiceError;
+use quickwit_proto::jaeger::api_v2::{
+    KeyValue, Log, Process, Span, SpanRef, SpanRefType, ValueType,
+};
+use quickwit_proto::jaeger::storage::v1::span_reader_plugin_server::SpanReaderPlugin;
+use quickwit_proto::jaeger::storage::v1::{
+    FindTraceIDsRequest, FindTraceIDsResponse, FindTracesRequest, GetOperationsRequest,
+    GetOperationsResponse, GetServicesRequest, GetServicesResponse, GetTraceRequest, Operation,
+    SpansResponseChunk, TraceQueryParameters,
+};
+use quickwit_proto::SearchRequest;
+use quickwit_search::SearchService;
+use serde::Deserialize;
+use serde_json::Value as JsonValue;
+
+use crate::auth::{grpc_principal, Access};
+use crate::otlp::{hex_id, OTEL_TRACES_INDEX_ID};
+use crate::ApiError;
+
+/// Number of most recent spans of a service from which its operations are listed.
+const MAX_OPERATION_SPANS: u64 = 1_000;
+
+/// Maximum number of spans matching the parameters of a trace query.
+const MAX_MATCHING_SPANS: u64 = 1_000;
+
+/// Maximum number of spans matching the query of a trace query, when some of its parameters
+/// are checked on the matching spans rather than in the query.
+const MAX_FILTERED_SPANS: u64 = 10_000;
+
+/// Maximum number of spans returned for a set of traces.
+const MAX_TRACE_SPANS: u64 = 10_000;
+
+/// Number of traces returned when the trace query does not set it, as in the Jaeger UI.
+const DEFAULT_NUM_TRACES: usize = 20;
+
+type SpansResponseStream =
+    Pin<Box<dyn futures::Stream<Item = Result<SpansResponseChunk, tonic::Status>> + Send + Sync>>;
+
+/// Span reader of the Jaeger storage plugin API, backed by the `otel-traces` index.
+pub struct JaegerService {
+    search_service: Arc<dyn SearchService>,
+    metastore: Arc<dyn Metastore>,
+}
+
+impl JaegerService {
+    pub fn new(search_service: Arc<dyn SearchService>, metastore: Arc<dyn Metastore>) -> Self {
+        JaegerService {
+            search_service,
+            metastore,
+        }
+    }
+
+    /// Searches the `otel-traces` index, and returns the matching span documents, most recent
+    /// first, along with the total number of matching spans.
+    async fn search_spans(
+        &self,
+        query: String,
+        time_range: (Option<i64>, Option<i64>),
+        tags: Vec<String>,
+        max_hits: u64,
+    ) -> Result<(Vec<SpanDocument>, u64), tonic::Status> {
+        let search_request = SearchRequest {
+            index_id: OTEL_TRACES_INDEX_ID.to_string(),
+            query,
+            search_fields: Vec::new(),
+            start_timestamp: time_range.0,
+            end_timestamp: time_range.1,
+            max_hits,
+            start_offset: 0,
+            tags,
+            allow_partial_results: false,
+            timeout_millis: None,
+        };
+        let search_response = self
+            .search_service
+            .root_search(search_request)
+            .await
+            .map_err(Into::<tonic::Status>::into)?;
+        let span_docs = search_response
+            .hits
+            .iter()
+            .map(|hit| {
+                serde_json::from_str::<SpanDocument>(&hit.json).map_err(|error| {
+                    tonic::Status::internal(format!("invalid span document: {}", error))
+                })
+            })
+            .collect::<Result<_, _>>()?;
+        Ok((span_docs, search_response.num_hits))
+    }
+
+    /// Returns the IDs of the most recent traces holding a span that matches the parameters.
+    async fn find_trace_ids(
+        &self,
+        params_opt: Option<TraceQueryParameters>,
+    ) -> Result<Vec<String>, tonic::Status> {
+        let params = params_opt
+            .ok_or_else(|| tonic::Status::invalid_argument("the trace query is missing"))?;
+        let span_query = trace_query(&params)?;
+        let time_range = (
+            params.start_time_min.as_ref().map(|time| time.seconds),
+            // The end timestamp of a search is exclusive.
+            params.start_time_max.as_ref().map(|time| time.seconds + 1),
+        );
+        let tags = vec![format!("service_name:{}", params.service_name)];
+        let max_hits = if span_query.has_span_filters() {
+            MAX_FILTERED_SPANS
+        } else {
+            MAX_MATCHING_SPANS
+        };
+        let (span_docs, num_hits) = self
+            .search_spans(span_query.query(), time_range, tags, max_hits)
+            .await?;
+        let num_fetched_spans = span_docs.len() as u64;
+        let num_traces = if params.num_traces > 0 {
+            params.num_traces as usize
+        } else {
+            DEFAULT_NUM_TRACES
+        };
+        let mut trace_ids = Vec::new();
+        for span_doc in span_docs {
+            if !span_query.matches(&span_doc) {
+                continue;
+            }
+            if let Some(trace_id) = first(span_doc.trace_id) {
+                if !trace_ids.contains(&trace_id) {
+                    trace_ids.push(trace_id);
+                }
+            }
+            if trace_ids.len() == num_traces {
+                return Ok(trace_ids);
+            }
+        }
+        // The spans that were not fetched may match the parameters checked on the spans.
+        if span_query.has_span_filters() && num_fetched_spans < num_hits {
+            return Err(tonic::Status::resource_exhausted(format!(
+                "the trace query matches more than {} spans before filtering them on their \
+                 duration or on values containing double quotes, narrow its time range",
+                num_fetched_spans
+            )));
+        }
+        Ok(trace_ids)
+    }
+
+    /// Returns the spans of the traces, grouped by trace, one chunk per trace.
+    async fn fetch_traces(
+        &self,
+        trace_ids: &[String],
+    ) -> Result<Vec<SpansResponseChunk>, tonic::Status> {
+        if trace_ids.is_empty() {
+            return Ok(Vec::new());
+        }
+        let query = format!("trace_id:({})", trace_ids.join(" OR "));
+        let (span_docs, _num_hits) = self
+            .search_spans(query, (None, None), Vec::new(), MAX_TRACE_SPANS)
+            .await?;
+        let mut trace_spans: HashMap<String, Vec<Span>> = HashMap::new();
+        for span_doc in span_docs {
+            if let Some(trace_id) = span_doc.trace_id.first().cloned() {
+                trace_spans
+                    .entry(trace_id)
+                    .or_default()
+                    .push(span_doc.into_jaeger_span());
+            }
+        }
+        let chunks = trace_ids
+            .iter()
+            .filter_map(|trace_id| trace_spans.remove(trace_id))
+            .map(|spans| SpansResponseChunk { spans })
+            .collect();
+        Ok(chunks)
+    }
+}
+
+#[async_trait]
+impl SpanReaderPlugin for JaegerService {
+    type GetTraceStream = SpansResponseStream;
+
+    async fn get_trace(
+        &self,
+        request: tonic::Request<GetTraceRequest>,
+    ) -> Result<tonic::Response<Self::GetTraceStream>, tonic::Status> {
+        check_read_access(&request)?;
+        let trace_id = hex_id(&request.into_inner().trace_id)
+            .ok_or_else(|| tonic::Status::invalid_argument("the trace ID is missing"))?;
+        let chunks = self.fetch_traces(&[trace_id]).await?;
+        // The Jaeger storage plugin client expects a `NotFound` status for unknown traces.
+        if chunks.is_empty() {
+            return Err(tonic::Status::not_found("trace not found"));
+        }
+        Ok(tonic::Response::new(spans_response_stream(chunks)))
+    }
+
+    async fn get_services(
+        &self,
+        request: tonic::Request<GetServicesRequest>,
+    ) -> Result<tonic::Response<GetServicesResponse>, tonic::Status> {
+        check_read_access(&request)?;
+        // `service_name` is a tag field: the services are listed from the split tags, without
+        // searching the index.
+        let splits = self
+            .metastore
+            .list_splits(OTEL_TRACES_INDEX_ID, SplitState::Published, None, &[])
+            .await
+            .map_err(|error| ApiError::from(error).grpc_status())?;
+        let services: BTreeSet<String> = splits
+            .iter()
+            .flat_map(|split| split.split_metadata.tags.iter())
+            .filter_map(|tag| tag.strip_prefix("service_name:"))
+            .map(ToString::to_string)
+            .collect();
+        Ok(tonic::Response::new(GetServicesResponse {
+            services: services.into_iter().collect(),
+        }))
+    }
+
+    async fn get_operations(
+        &self,
+        request: tonic::Request<GetOperationsRequest>,
+    ) -> Result<tonic::Response<GetOperationsResponse>, tonic::Status> {
+        check_read_access(&request)?;
+        let request = request.into_inner();
+        let mut span_query = SpanQuery::default();
+        span_query.add_clause(&["service_name"], &request.service);
+        if !request.span_kind.is_empty() {
+            span_query.add_clause(&["span_kind"], &request.span_kind);
+        }
+        let tags = vec![format!("service_name:{}", request.service)];
+        let (span_docs, _num_hits) = self
+            .search_spans(span_query.query(), (None, None), tags, MAX_OPERATION_SPANS)
+            .await?;
+        let operations: BTreeSet<(String, String)> = span_docs
+            .into_iter()
+            .filter(|span_doc| span_query.matches(span_doc))
+            .filter_map(|span_doc| {
+                let span_name = first(span_doc.span_name)?;
+                let span_kind = first(span_doc.span_kind).unwrap_or_default();
+                Some((span_name, span_kind))
+            })
+            .collect();
+        let operation_names: BTreeSet<String> = operations
+            .iter()
+            .map(|(span_name, _)| span_name.clone())
+            .collect();
+        Ok(tonic::Response::new(GetOperationsResponse {
+            operation_names: operation_names.into_iter().collect(),
+            operations: operations
+                .into_iter()
+                .map(|(name, span_kind)| Operation { name, span_kind })
+                .collect(),
+        }))
+    }
+
+    type FindTracesStream = SpansResponseStream;
+
+    async fn find_traces(
+        &self,
+        request: tonic::Request<FindTracesRequest>,
+    ) -> Result<tonic::Response<Self::FindTracesStream>, tonic::Status> {
+        check_read_access(&request)?;
+        let trace_ids = self.find_trace_ids(request.into_inner().query).await?;
+        let chunks = self.fetch_traces(&trace_ids).await?;
+        Ok(tonic::Response::new(spans_response_stream(chunks)))
+    }
+
+    async fn find_trace_i_ds(
+        &self,
+        request: tonic::Request<FindTraceIDsRequest>,
+    ) -> Result<tonic::Response<FindTraceIDsResponse>, tonic::Status> {
+        check_read_access(&request)?;
+        let trace_ids = self.find_trace_ids(request.into_inner().query).await?;
+        Ok(tonic::Response::new(FindTraceIDsResponse {
+            trace_ids: trace_ids
+                .iter()
+                .filter_map(|trace_id| hex_decode(trace_id))
+                .collect(),
+        }))
+    }
+}
+
+/// Checks that the author of the request can read the `otel-traces` index.
+fn check_read_access<T>(request: &tonic::Request<T>) -> Result<(), tonic::Status> {
+    grpc_principal(request)?
+        .check_index_access(OTEL_TRACES_INDEX_ID, Access::Read)
+        .map_err(|api_error| api_error.grpc_status())
+}
+
+fn spans_response_stream(chunks: Vec<SpansResponseChunk>) -> SpansResponseStream {
+    Box::pin(stream::iter(chunks.into_iter().map(Ok::<_, tonic::Status>)))
+}
+
+/// Clause matching the spans holding a value in one of the given raw fields.
+struct TermClause {
+    field_names: &'static [&'static str],
+    value: String,
+}
+
+impl TermClause {
+    /// Formats the clause in the query language, unless the value contains double quotes:
+    /// the query language has no escape sequence for them.
+    fn query(&self) -> Option<String> {
+        if self.value.contains('"') {
+            return None;
+        }
+        let field_queries: Vec<String> = self
+            .field_names
+            .iter()
+            .map(|field_name| format!("{}:\"{}\"", field_name, self.value))
+            .collect();
+        if field_queries.len() == 1 {
+            return field_queries.into_iter().next();
+        }
+        Some(format!("({})", field_queries.join(" OR ")))
+    }
+
+    fn matches(&self, span_doc: &SpanDocument) -> bool {
+        self.field_names
+            .iter()
+            .any(|field_name| span_doc.field_values(field_name).contains(&self.value))
+    }
+}
+
+/// Parameters of a span search.
+///
+/// The query language supports neither range clauses nor double quotes in values, so the
+/// span duration bounds and the clauses whose value contains double quotes are checked on
+/// the spans matching the query instead.
+struct SpanQuery {
+    clauses: Vec<TermClause>,
+    duration_nanos_range: RangeInclusive<u64>,
+}
+
+impl Default for SpanQuery {
+    fn default() -> Self {
+        SpanQuery {
+            clauses: Vec::new(),
+            duration_nanos_range: 0..=u64::MAX,
+        }
+    }
+}
+
+impl SpanQuery {
+    fn add_clause(&mut self, field_names: &'static [&'static str], value: &str) {
+        self.clauses.push(TermClause {
+            field_names,
+            value: value.to_string(),
+        });
+    }
+
+    /// Returns the query matching the clauses that the query language can express.
+    fn query(&self) -> String {
+        let clause_queries: Vec<String> =
+            self.clauses.iter().filter_map(TermClause::query).collect();
+        if clause_queries.is_empty() {
+            return "*".to_string();
+        }
+        clause_queries.join(" AND ")
+    }
+
+    /// Returns true if some spans matching the query may not match all the parameters.
+    fn has_span_filters(&self) -> bool {
+        self.duration_nanos_range != (0..=u64::MAX)
+            || self.clauses.iter().any(|clause| clause.query().is_none())
+    }
+
+    /// Checks the parameters that are not part of the query.
+    fn matches(&self, span_doc: &SpanDocument) -> bool {
+        let span_duration_nanos = span_doc
+            .span_duration_nanos
+            .first()
+            .copied()
+            .unwrap_or_default();
+        self.duration_nanos_range.contains(&span_duration_nanos)
+            && self
+                .clauses
+                .iter()
+                .filter(|clause| clause.query().is_none())
+                .all(|clause| clause.matches(span_doc))
+    }
+}
+
+/// Builds the span search matching the parameters of a trace query.
+fn trace_query(params: &TraceQueryParameters) -> Result<SpanQuery, tonic::Status> {
+    if params.service_name.is_empty() {
+        return Err(tonic::Status::invalid_argument(
+            "the service name of the trace query is missing",
+        ));
+    }
+    let mut span_query = SpanQuery::default();
+    span_query.add_clause(&["service_name"], &params.service_name);
+    if !params.operation_name.is_empty() {
+        span_query.add_clause(&["span_name"], &params.operation_name);
+    }
+    // Sorted for the queries to be deterministic.
+    let tags: BTreeSet<(&String, &String)> = params.tags.iter().collect();
+    for (key, value) in tags {
+        // `span.kind` and `error` are the tags derived from the span kind and status of the
+        // OTLP spans.
+        match (key.as_str(), value.as_str()) {
+            ("span.kind", span_kind) => span_query.add_clause(&["span_kind"], span_kind),
+            ("error", "true") => span_query.add_clause(&["span_status_code"], "error"),
+            _ => span_query.add_clause(
+                &["span_attributes", "resource_attributes"],
+                &format!("{}:{}", key, value),
+            ),
+        }
+    }
+    let duration_min_nanos = params.duration_min.as_ref().map_or(0, duration_nanos);
+    let duration_max_nanos = params
+        .duration_max
+        .as_ref()
+        .map_or(u64::MAX, duration_nanos);
+    span_query.duration_nanos_range = duration_min_nanos..=duration_max_nanos;
+    Ok(span_query)
+}
+
+fn duration_nanos(duration: &prost_types::Duration) -> u64 {
+    (duration.seconds.max(0) as u64) * 1_000_000_000 + duration.nanos.max(0) as u64
+}
+
+fn timestamp_from_nanos(nanos: u64) -> prost_types::Timestamp {
+    prost_types::Timestamp {
+        seconds: (nanos / 1_000_000_000) as i64,
+        nanos: (nanos % 1_000_000_000) as i32,
+    }
+}
+
+fn duration_from_nanos(nanos: u64) -> prost_types::Duration {
+    prost_types::Duration {
+        seconds: (nanos / 1_000_000_000) as i64,
+        nanos: (nanos % 1_000_000_000) as i32,
+    }
+}
+
+fn hex_decode(hex: &str) -> Option<Vec<u8>> {
+    if hex.len() % 2 != 0 {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|idx| u8::from_str_radix(hex.get(idx..idx + 2)?, 16).ok())
+        .collect()
+}
+
+fn first<T>(values: Vec<T>) -> Option<T> {
+    values.into_iter().next()
+}
+
+/// Span document of the `otel-traces` index, as returned by the search. Every field holds
+/// the list of its values.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct SpanDocument {
+    trace_id: Vec<String>,
+    span_id: Vec<String>,
+    parent_span_id: Vec<String>,
+    service_name: Vec<String>,
+    span_name: Vec<String>,
+    span_kind: Vec<String>,
+    span_start_timestamp_nanos: Vec<u64>,
+    span_duration_nanos: Vec<u64>,
+    span_attributes: Vec<String>,
+    resource_attributes: Vec<String>,
+    span_status_code: Vec<String>,
+    span_status_message: Vec<String>,
+    events: Vec<String>,
+}
+
+/// Span event, as stored in the `events` field.
+#[derive(Debug, Deserialize)]
+struct SpanEvent {
+    time_unix_nano: u64,
+    name: String,
+    #[serde(default)]
+    attributes: serde_json::Map<String, JsonValue>,
+}
+
+impl SpanDocument {
+    /// Returns the values of a raw text field.
+    fn field_values(&self, field_name: &str) -> &[String] {
+        match field_name {
+            "service_name" => &self.service_name,
+            "span_name" => &self.span_name,
+            "span_kind" => &self.span_kind,
+            "span_attributes" => &self.span_attributes,
+            "resource_attributes" => &self.resource_attributes,
+            "span_status_code" => &self.span_status_code,
+            _ => &[],
+        }
+    }
+
+    fn into_jaeger_span(self) -> Span {
+        let trace_id = self
+            .trace_id
+            .first()
+            .and_then(|trace_id| hex_decode(trace_id))
+            .unwrap_or_default();
+        let references = self
+            .parent_span_id
+            .first()
+            .and_then(|parent_span_id| hex_decode(parent_span_id))
+            .map(|parent_span_id| SpanRef {
+                trace_id: trace_id.clone(),
+                span_id: parent_span_id,
+                ref_type: SpanRefType::ChildOf as i32,
+            })
+            .into_iter()
+            .collect();
+        let mut tags: Vec<KeyValue> = self
+            .span_attributes
+            .iter()
+            .map(|attribute| attribute_to_key_value(attribute))
+            .collect();
+        if let Some(span_kind) = first(self.span_kind) {
+            tags.push(string_key_value("span.kind", span_kind));
+        }
+        match self.span_status_code.first().map(String::as_str) {
+            Some("ok") => tags.push(string_key_value("otel.status_code", "OK".to_string())),
+            Some("error") => {
+                tags.push(string_key_value("otel.status_code", "ERROR".to_string()));
+                tags.push(KeyValue {
+                    key: "error".to_string(),
+                    v_type: ValueType::Bool as i32,
+                    v_bool: true,
+                    ..Default::default()
+                });
+            }
+            _ => {}
+        }
+        if let Some(status_message) = first(self.span_status_message) {
+            tags.push(string_key_value("otel.status_description", status_message));
+        }
+        let logs = self
+            .events
+            .iter()
+            .filter_map(|event_json| serde_json::from_str::<SpanEvent>(event_json).ok())
+            .map(|event| {
+                let mut fields = vec![string_key_value("event", event.name)];
+                fields.extend(
+                    event
+                        .attributes
+                        .iter()
+                        .map(|(key, value)| json_to_key_value(key, value)),
+                );
+                Log {
+                    timestamp: Some(timestamp_from_nanos(event.time_unix_nano)),
+                    fields,
+                }
+            })
+            .collect();
+        let process = Process {
+            service_name: first(self.service_name).unwrap_or_default(),
+            tags: self
+                .resource_attributes
+                .iter()
+                .map(|attribute| attribute_to_key_value(attribute))
+                .collect(),
+        };
+        Span {
+            span_id: first(self.span_id)
+                .and_then(|span_id| hex_decode(&span_id))
+                .unwrap_or_default(),
+            trace_id,
+            operation_name: first(self.span_name).unwrap_or_default(),
+            references,
+            flags: 0,
+            start_time: Some(timestamp_from_nanos(
+                first(self.span_start_timestamp_nanos).unwrap_or_default(),
+            )),
+            duration: Some(duration_from_nanos(
+                first(self.span_duration_nanos).unwrap_or_default(),
+            )),
+            tags,
+            logs,
+            process: Some(process),
+            process_id: String::new(),
+            warnings: Vec::new(),
+        }
+    }
+}
+
+fn string_key_value(key: &str, value: String) -> KeyValue {
+    KeyValue {
+        key: key.to_string(),
+        v_type: ValueType::String as i32,
+        v_str: value,
+        ..Default::default()
+    }
+}
+
+/// Parses an attribute indexed as a `key:value` string. The type of the value is lost: it is
+/// returned as a string.
+fn attribute_to_key_value(attribute: &str) -> KeyValue {
+    let (key, value) = attribute.split_once(':').unwrap_or((attribute, ""));
+    string_key_value(key, value.to_string())
+}
+
+fn json_to_key_value(key: &str, value: &JsonValue) -> KeyValue {
+    let mut key_value = KeyValue {
+        key: key.to_string(),
+        ..Default::default()
+    };
+    match value {
+        JsonValue::String(value) => {
+            key_value.v_type = ValueType::String as i32;
+            key_value.v_str = value.clone();
+        }
+        JsonValue::Bool(value) => {
+            key_value.v_type = ValueType::Bool as i32;
+            key_value.v_bool = *value;
+        }
+        JsonValue::Number(number) if number.is_i64() || number.is_u64() => {
+            key_value.v_type = ValueType::Int64 as i32;
+            key_value.v_int64 = number.as_i64().unwrap_or(i64::MAX);
+        }
+        JsonValue::Number(number) => {
+            key_value.v_type = ValueType::Float64 as i32;
+            key_value.v_float64 = number.as_f64().unwrap_or_default();
+        }
+        _ => {
+            key_value.v_type = ValueType::String as i32;
+            key_value.v_str = value.to_string();
+        }
+    }
+    key_value
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ops::Range;
+
+    use futures::TryStreamExt;
+    use quickwit_indexing::mock_split_meta;
+    use quickwit_metastore::MockMetastore;
+    use quickwit_proto::{Hit, SearchResponse};
+    use quickwit_search::MockSearchService;
+    use serde_json::json;
+
+    use super::*;
+    use crate::auth::tests::test_authenticator;
+    use crate::auth::Principal;
+
+    fn request_with_principal<T>(message: T, principal: Principal) -> tonic::Request<T> {
+        let mut request = tonic::Request::new(message);
+        request.extensions_mut().insert(principal);
+        request
+    }
+
+    fn anonymous_request<T>(message: T) -> tonic::Request<T> {
+        request_with_principal(message, Principal::Anonymous)
+    }
+
+    fn span_hit(trace_id: &str, span_id: &str, parent_span_id: Option<&str>) -> Hit {
+        let mut span_doc = json!({
+            "trace_id": [trace_id],
+            "span_id": [span_id],
+            "service_name": ["checkout"],
+            "span_name": ["GET /cart"],
+            "span_kind": ["server"],
+            "span_start_timestamp_nanos": [1_633_947_052_000_000_000u64],
+            "span_duration_nanos": [250_000_000u64],
+            "span_attributes": ["http.method:GET"],
+            "resource_attributes": ["service.name:checkout"],
+            "span_status_code": ["error"],
+            "span_status_message": ["timeout"],
+            "events": [r#"{"attributes":{"retry":2},"name":"cache miss","time_unix_nano":1633947052100000000}"#],
+        });
+        if let Some(parent_span_id) = parent_span_id {
+            span_doc["parent_span_id"] = json!([parent_span_id]);
+        }
+        Hit {
+            json: span_doc.to_string(),
+            partial_hit: None,
+        }
+    }
+
+    fn search_response(hits: Vec<Hit>) -> SearchResponse {
+        SearchResponse {
+            num_hits: hits.len() as u64,
+            hits,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_jaeger_trace_query() -> anyhow::Result<()> {
+        let mut params = TraceQueryParameters {
+            service_name: "checkout".to_string(),
+            operation_name: "GET /cart".to_string(),
+            ..Default::default()
+        };
+        params
+            .tags
+            .insert("http.method".to_string(), "GET".to_string());
+        params.tags.insert("error".to_string(), "true".to_string());
+        let span_query = trace_query(&params)?;
+        assert_eq!(
+            span_query.query(),
+            r#"service_name:"checkout" AND span_name:"GET /cart" AND span_status_code:"error" AND (span_attributes:"http.method:GET" OR resource_attributes:"http.method:GET")"#
+        );
+        assert!(!span_query.has_span_filters());
+
+        // Values containing double quotes are checked on the spans.
+        params
+            .tags
+            .insert("http.url".to_string(), r#"/cart?id="1""#.to_string());
+        params.duration_min = Some(prost_types::Duration {
+            seconds: 0,
+            nanos: 100_000_000,
+        });
+        let span_query = trace_query(&params)?;
+        assert_eq!(
+            span_query.query(),
+            r#"service_name:"checkout" AND span_name:"GET /cart" AND span_status_code:"error" AND (span_attributes:"http.method:GET" OR resource_attributes:"http.method:GET")"#
+        );
+        assert!(span_query.has_span_filters());
+        let span_doc = |span_attributes: serde_json::Value, span_duration_nanos: u64| {
+            serde_json::from_value::<SpanDocument>(json!({
+                "span_attributes": span_attributes,
+                "span_duration_nanos": [span_duration_nanos],
+            }))
+        };
+        assert!(span_query.matches(&span_doc(json!([r#"http.url:/cart?id="1""#]), 250_000_000)?));
+        assert!(!span_query.matches(&span_doc(json!([r#"http.url:/cart?id="2""#]), 250_000_000)?));
+        assert!(!span_query.matches(&span_doc(json!([r#"http.url:/cart?id="1""#]), 50_000_000)?));
+        params.service_name = String::new();
+        assert!(trace_query(&params).is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_jaeger_find_traces() -> anyhow::Result<()> {
+        let mut mock_search_service = MockSearchService::new();
+        mock_search_service.expect_root_search().times(2).returning(
+            |search_request: SearchRequest| {
+                if search_request.query.starts_with("service_name:") {
+                    assert_eq!(search_request.tags, vec!["service_name:checkout"]);
+                    assert_eq!(search_request.start_timestamp, Some(1_633_947_000));
+                    assert_eq!(search_request.end_timestamp, Some(1_633_947_061));
+                    Ok(search_response(vec![
+                        span_hit("0a", "01", Some("00")),
+                        span_hit("0b", "02", None),
+                        span_hit("0a", "00", None),
+                    ]))
+                } else {
+                    assert_eq!(search_request.query, "trace_id:(0a OR 0b)");
+                    Ok(search_response(vec![
+                        span_hit("0a", "01", Some("00")),
+                        span_hit("0b", "02", None),
+                        span_hit("0a", "00", None),
+                    ]))
+                }
+            },
+        );
+        let jaeger_service = JaegerService::new(
+            Arc::new(mock_search_service),
+            Arc::new(MockMetastore::new()),
+        );
+        let request = FindTracesRequest {
+            query: Some(TraceQueryParameters {
+                service_name: "checkout".to_string(),
+                start_time_min: Some(prost_types::Timestamp {
+                    seconds: 1_633_947_000,
+                    nanos: 0,
+                }),
+                start_time_max: Some(prost_types::Timestamp {
+                    seconds: 1_633_947_060,
+                    nanos: 0,
+                }),
+                duration_min: Some(prost_types::Duration {
+                    seconds: 0,
+                    nanos: 100_000_000,
+                }),
+                ..Default::default()
+            }),
+        };
+        let chunks: Vec<SpansResponseChunk> = jaeger_service
+            .find_traces(anonymous_request(request))
+            .await?
+            .into_inner()
+            .try_collect()
+            .await?;
+        assert_eq!(chunks.len(), 2);
+        assert_eq!(chunks[0].spans.len(), 2);
+        assert_eq!(chunks[1].spans.len(), 1);
+
+        let span = &chunks[0].spans[0];
+        assert_eq!(span.trace_id, vec![10]);
+        assert_eq!(span.span_id, vec![1]);
+        assert_eq!(span.operation_name, "GET /cart");
+        assert_eq!(
+            span.references,
+            vec![SpanRef {
+                trace_id: vec![10],
+                span_id: vec![0],
+                ref_type: SpanRefType::ChildOf as i32,
+            }]
+        );
+        assert_eq!(
+            span.start_time,
+            Some(prost_types::Timestamp {
+                seconds: 1_633_947_052,
+                nanos: 0
+            })
+        );
+        assert_eq!(
+            span.duration,
+            Some(prost_types::Duration {
+                seconds: 0,
+                nanos: 250_000_000
+            })
+        );
+        let tag_keys: Vec<&str> = span.tags.iter().map(|tag| tag.key.as_str()).collect();
+        assert_eq!(
+            tag_keys,
+            vec![
+                "http.method",
+                "span.kind",
+                "otel.status_code",
+                "error",
+                "otel.status_description"
+            ]
+        );
+        assert_eq!(span.logs.len(), 1);
+        assert_eq!(
+            span.logs[0].fields,
+            vec![
+                string_key_value("event", "cache miss".to_string()),
+                KeyValue {
+                    key: "retry".to_string(),
+                    v_type: ValueType::Int64 as i32,
+                    v_int64: 2,
+                    ..Default::default()
+                },
+            ]
+        );
+        let process = span.process.as_ref().unwrap();
+        assert_eq!(process.service_name, "checkout");
+        assert_eq!(
+            process.tags,
+            vec![string_key_value("service.name", "checkout".to_string())]
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_jaeger_find_trace_ids_reports_truncation() {
+        let mut mock_search_service = MockSearchService::new();
+        mock_search_service.expect_root_search().times(1).returning(
+            |search_request: SearchRequest| {
+                assert_eq!(search_request.max_hits, MAX_FILTERED_SPANS);
+                // The span lasts 250ms, so it does not match the minimum duration.
+                Ok(SearchResponse {
+                    num_hits: MAX_FILTERED_SPANS + 1,
+                    ..search_response(vec![span_hit("0a", "01", None)])
+                })
+            },
+        );
+        let jaeger_service = JaegerService::new(
+            Arc::new(mock_search_service),
+            Arc::new(MockMetastore::new()),
+        );
+        let request = FindTraceIDsRequest {
+            query: Some(TraceQueryParameters {
+                service_name: "checkout".to_string(),
+                duration_min: Some(prost_types::Duration {
+                    seconds: 1,
+                    nanos: 0,
+                }),
+                ..Default::default()
+            }),
+        };
+        let status = jaeger_service
+            .find_trace_i_ds(anonymous_request(request))
+            .await
+            .err()
+            .unwrap();
+        assert_eq!(status.code(), tonic::Code::ResourceExhausted);
+    }
+
+    #[tokio::test]
+    async fn test_jaeger_get_trace_not_found() {
+        let mut mock_search_service = MockSearchService::new();
+        mock_search_service
+            .expect_root_search()
+            .returning(|_| Ok(search_response(Vec::new())));
+        let jaeger_service = JaegerService::new(
+            Arc::new(mock_search_service),
+            Arc::new(MockMetastore::new()),
+        );
+        let request = GetTraceRequest { trace_id: vec![10] };
+        let status = jaeger_service
+            .get_trace(anonymous_request(request))
+            .await
+            .err()
+            .unwrap();
+        assert_eq!(status.code(), tonic::Code::NotFound);
+    }
+
+    #[tokio::test]
+    async fn test_jaeger_get_services() -> anyhow::Result<()> {
+        let mut mock_metastore = MockMetastore::new();
+        mock_metastore.expect_list_splits().returning(
+            |index_id: &str,
+             _split_state: SplitState,
+             _time_range: Option<Range<i64>>,
+             _tags: &[String]| {
+                assert_eq!(index_id, OTEL_TRACES_INDEX_ID);
+                let mut split_1 = mock_split_meta("split1");
+                split_1.split_metadata.tags = vec!["service_name:checkout".to_string()]
+                    .into_iter()
+                    .collect();
+                let mut split_2 = mock_split_meta("split2");
+                split_2.split_metadata.tags = vec![
+                    "service_name:checkout".to_string(),
+                    "service_name:cart".to_string(),
+                ]
+                .into_iter()
+                .collect();
+                Ok(vec![split_1, split_2])
+            },
+        );
+        let jaeger_service =
+            JaegerService::new(Arc::new(MockSearchService::new()), Arc::new(mock_metastore));
+        let services = jaeger_service
+            .get_services(anonymous_request(GetServicesRequest {}))
+            .await?
+            .into_inner()
+            .services;
+        assert_eq!(services, vec!["cart", "checkout"]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_jaeger_requires_read_access() -> anyhow::Result<()> {
+        let jaeger_service = JaegerService::new(
+            Arc::new(MockSearchService::new()),
+            Arc::new(MockMetastore::new()),
+        );
+        let status = jaeger_service
+            .get_services(tonic::Request::new(GetServicesRequest {}))
+            .await
+            .err()
+            .unwrap();
+        assert_eq!(status.code(), tonic::Code::Unauthenticated);
+        // The `grafana` user can only read the `logs-*` indexes.
+        let principal = test_authenticator()?
+            .authenticate(Some("Bearer grafana-token"))
+            .unwrap();
+        let request = GetTraceRequest { trace_id: vec![10] };
+        let status = jaeger_service
+            .get_trace(request_with_principal(request, principal.clone()))
+            .await
+            .err()
+            .unwrap();
+        assert_eq!(status.code(), tonic::Code::PermissionDenied);
+        let status = jaeger_service
+            .find_traces(request_with_principal(
+                FindTracesRequest::default(),
+                principal,
+            ))
+            .await
+            .err()
+            .unwrap();
+        assert_eq!(status.code(), tonic::Code::PermissionDenied);
+        Ok(())
+    }
+}
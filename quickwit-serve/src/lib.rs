@@ -18,49 +18,86 @@
 // along with this program. If not, see <http://www.gnu.org/licenses/>.
 
 mod args;
+mod auth;
+mod elastic_api;
 mod error;
 mod grpc;
 mod grpc_adapter;
 mod http_handler;
+mod ingest;
+mod jaeger;
+mod node_metadata;
+mod otlp;
 mod quickwit_cache;
 mod rest;
+mod split_prefetcher;
+mod tls;
 
 use std::io::Write;
 use std::net::SocketAddr;
 use std::sync::Arc;
 
 use quickwit_cache::QuickwitCache;
-use quickwit_cluster::cluster::{read_or_create_host_key, Cluster};
+use quickwit_cluster::cluster::{read_cluster_secret_keys, read_or_create_host_key, Cluster};
+pub use quickwit_cluster::cluster::{FailureDetectorConfig, NodeRole};
 use quickwit_cluster::service::ClusterServiceImpl;
 use quickwit_metastore::MetastoreUriResolver;
 use quickwit_search::{
-    http_addr_to_grpc_addr, http_addr_to_swim_addr, ClusterClient, SearchClientPool,
-    SearchServiceImpl,
+    http_addr_to_grpc_addr, http_addr_to_swim_addr, AdmissionController, ClusterClient,
+    LeafSearchCache, SearchClientPool, SearchServiceImpl,
 };
+pub use quickwit_search::{AdmissionControllerConfig, LaneConfig};
 use quickwit_storage::{
-    LocalFileStorageFactory, RegionProvider, S3CompatibleObjectStorageFactory, StorageUriResolver,
-    StorageWithCacheFactory,
+    LocalDiskCache, LocalFileStorageFactory, LocalKeyFileProvider, PrefetchedFilesCache,
+    RegionProvider, S3CompatibleObjectStorageFactory, StorageUriResolver, StorageWithCacheFactory,
 };
 use quickwit_telemetry::payload::{ServeEvent, TelemetryEvent};
 use termcolor::{self, Color, ColorChoice, ColorSpec, StandardStream, WriteColor};
 use tracing::debug;
 
 pub use crate::args::ServeArgs;
+use crate::auth::Authenticator;
 pub use crate::error::ApiError;
-use crate::grpc::start_grpc_service;
+use crate::grpc::{
+    start_grpc_service, start_public_grpc_service, GrpcServices, PublicGrpcServices,
+};
 use crate::grpc_adapter::cluster_adapter::GrpcClusterAdapter;
 use crate::grpc_adapter::search_adapter::GrpcSearchAdapter;
-use crate::rest::start_rest_service;
+use crate::ingest::IngestService;
+use crate::jaeger::JaegerService;
+use crate::node_metadata::spawn_node_metadata_updater;
+use crate::otlp::{create_otlp_indexes, OtlpGrpcLogsService, OtlpGrpcTraceService};
+pub use crate::quickwit_cache::CacheRouteConfig;
+use crate::rest::{start_rest_service, RestServices};
+use crate::split_prefetcher::spawn_split_prefetcher;
+pub use crate::tls::TlsConfig;
+
+/// Suffix of the split files, served by the local disk cache when there is one.
+///
+/// The local disk cache route used to be hardcoded in `QuickwitCache::with_local_disk_cache`.
+/// Now that the in-memory routes are configurable, it is added here, after them, so that
+/// the configured routes take precedence over it. The route only serves the splits copied
+/// by the prefetcher: the reads missing the local disk cache do not fill it.
+const SPLIT_FILE_SUFFIX: &str = ".split";
+
+/// Computes the port of the public gRPC server from the HTTP port, i.e. the HTTP port + 3.
+///
+/// The public gRPC server serves the OTLP receiver and the Jaeger span reader, while the
+/// gRPC port (the HTTP port + 2) only serves the services internal to the cluster.
+fn http_addr_to_public_grpc_addr(http_addr: SocketAddr) -> SocketAddr {
+    SocketAddr::new(http_addr.ip(), http_addr.port() + 3)
+}
 
 fn display_help_message(
     rest_socket_addr: SocketAddr,
+    scheme: &str,
     example_index_name: &str,
 ) -> anyhow::Result<()> {
     // No-color if we are not in a terminal.
     let mut stdout = StandardStream::stdout(ColorChoice::Auto);
     write!(&mut stdout, "Server started on ")?;
     stdout.set_color(ColorSpec::new().set_fg(Some(Color::Green)))?;
-    writeln!(&mut stdout, "http://{}/", &rest_socket_addr)?;
+    writeln!(&mut stdout, "{}://{}/", scheme, &rest_socket_addr)?;
     stdout.set_color(&ColorSpec::new())?;
     writeln!(
         &mut stdout,
@@ -69,8 +106,8 @@ fn display_help_message(
     stdout.set_color(ColorSpec::new().set_fg(Some(Color::Blue)))?;
     writeln!(
         &mut stdout,
-        "curl 'http://{}/api/v1/{}/search?query=my+query'",
-        rest_socket_addr, example_index_name
+        "curl '{}://{}/api/v1/{}/search?query=my+query'",
+        scheme, rest_socket_addr, example_index_name
     )?;
     stdout.set_color(&ColorSpec::new())?;
     // TODO add link to the documentation of the query language.
@@ -78,13 +115,13 @@ fn display_help_message(
 }
 
 /// Builds a storage uri resolver that handles
-/// - s3:// uris. This storage comes with a cache that stores hotcache files.
+/// - s3:// uris. This storage comes with the given cache.
 /// - s3+localstack://
 /// - file:// uris.
-fn storage_uri_resolver() -> StorageUriResolver {
+fn storage_uri_resolver(quickwit_cache: Arc<QuickwitCache>) -> StorageUriResolver {
     let s3_storage = StorageWithCacheFactory::new(
         Arc::new(S3CompatibleObjectStorageFactory::default()),
-        Arc::new(QuickwitCache::default()),
+        quickwit_cache,
     );
     StorageUriResolver::builder()
         .register(LocalFileStorageFactory::default())
@@ -103,14 +140,72 @@ pub async fn serve_cli(args: ServeArgs) -> anyhow::Result<()> {
         has_seed: !args.peer_socket_addrs.is_empty(),
     }))
     .await;
-    let storage_resolver = storage_uri_resolver();
+    let local_disk_cache_opt = if let Some(split_cache_dir) = args.split_cache_dir.as_ref() {
+        let local_disk_cache =
+            LocalDiskCache::open(split_cache_dir, args.split_cache_capacity_in_bytes)?;
+        Some(Arc::new(local_disk_cache))
+    } else {
+        None
+    };
+    let mut quickwit_cache = QuickwitCache::new(&args.cache_routes);
+    if let Some(local_disk_cache) = local_disk_cache_opt.as_ref() {
+        quickwit_cache.add_route(
+            SPLIT_FILE_SUFFIX,
+            Arc::new(PrefetchedFilesCache::new(local_disk_cache.clone())),
+        );
+    }
+    let quickwit_cache = Arc::new(quickwit_cache);
+    let storage_resolver = storage_uri_resolver(quickwit_cache.clone());
     let metastore_resolver = MetastoreUriResolver::default();
     let example_index_name = "my_index".to_string();
     let metastore = metastore_resolver.resolve(&args.metastore_uri).await?;
+    if let Some(otlp_indexes_root_uri) = args.otlp_indexes_root_uri.as_ref() {
+        create_otlp_indexes(&*metastore, otlp_indexes_root_uri).await?;
+    }
+
+    if let Some(local_disk_cache) = local_disk_cache_opt.as_ref() {
+        if !args.prefetch_index_ids.is_empty() {
+            spawn_split_prefetcher(
+                args.prefetch_index_ids.clone(),
+                args.num_prefetched_splits,
+                metastore.clone(),
+                storage_resolver.clone(),
+                local_disk_cache.clone(),
+            );
+        }
+    }
+    // The storage level caches, i.e. the local disk cache, sit below the decryption layer:
+    // they only hold split files as they are stored, which is also how the prefetcher above
+    // copies them. On the other hand, searchers cache split footers and bundled files, e.g.
+    // `.fast` or `.term` files, after decryption: the in-memory cache routes hold plaintext.
+    let storage_resolver = if let Some(encryption_key_file) = args.encryption_key_file.as_ref() {
+        let key_provider = LocalKeyFileProvider::open(encryption_key_file)?;
+        storage_resolver
+            .with_encryption_key_provider(Arc::new(key_provider), args.allow_plaintext_reads)
+    } else {
+        storage_resolver
+    };
 
     let host_key = read_or_create_host_key(args.host_key_path.as_path())?;
     let swim_addr = http_addr_to_swim_addr(args.rest_socket_addr);
-    let cluster = Arc::new(Cluster::new(host_key, swim_addr)?);
+    let cluster_secret_keys = if let Some(cluster_secret_file) = args.cluster_secret_file.as_ref() {
+        read_cluster_secret_keys(cluster_secret_file)?
+    } else {
+        Vec::new()
+    };
+    let cluster = Arc::new(Cluster::new(
+        host_key,
+        swim_addr,
+        &args.cluster_id,
+        &cluster_secret_keys,
+        args.failure_detector_config,
+        &args.roles,
+    )?);
+    spawn_node_metadata_updater(
+        cluster.clone(),
+        local_disk_cache_opt,
+        args.split_cache_capacity_in_bytes,
+    );
     for peer_socket_addr in args
         .peer_socket_addrs
         .iter()
@@ -123,28 +218,94 @@ pub async fn serve_cli(args: ServeArgs) -> anyhow::Result<()> {
         cluster.add_peer_node(peer_swim_addr).await;
     }
 
-    let client_pool = Arc::new(SearchClientPool::new(cluster.clone()).await?);
-    let cluster_client = ClusterClient::new(client_pool.clone());
+    let client_tls_config_opt = args
+        .tls_config
+        .as_ref()
+        .map(TlsConfig::client_tls_config)
+        .transpose()?;
+    let client_pool =
+        Arc::new(SearchClientPool::new(cluster.clone(), client_tls_config_opt).await?);
+    let cluster_client = ClusterClient::new(client_pool.clone())
+        .with_leaf_request_timeout(args.leaf_request_timeout);
+    let leaf_search_cache = Arc::new(LeafSearchCache::with_capacity_in_bytes(
+        args.leaf_search_cache_capacity_in_bytes as usize,
+    ));
+    let ingest_service = Arc::new(IngestService::new(
+        host_key.to_string(),
+        metastore.clone(),
+        storage_resolver.clone(),
+    ));
     let search_service = Arc::new(SearchServiceImpl::new(
-        metastore,
+        metastore.clone(),
         storage_resolver,
         cluster_client,
         client_pool,
+        Some(quickwit_cache.clone()),
+        leaf_search_cache.clone(),
+        Arc::new(AdmissionController::new(args.admission_controller_config)),
     ));
 
     let cluster_service = Arc::new(ClusterServiceImpl::new(cluster.clone()));
 
+    let authenticator = if let Some(auth_file) = args.auth_file.as_ref() {
+        Arc::new(Authenticator::open(auth_file)?)
+    } else {
+        Arc::new(Authenticator::disabled())
+    };
+
     let grpc_socket_addr = http_addr_to_grpc_addr(args.rest_socket_addr);
-    let grpc_search_service = GrpcSearchAdapter::from(search_service.clone());
-    let grpc_cluster_service = GrpcClusterAdapter::from(cluster_service.clone());
-    let grpc_server =
-        start_grpc_service(grpc_socket_addr, grpc_search_service, grpc_cluster_service);
+    let grpc_services = GrpcServices {
+        search_service: GrpcSearchAdapter::from(search_service.clone()),
+        cluster_service: GrpcClusterAdapter::from(cluster_service.clone()),
+    };
+    let server_tls_config_opt = args
+        .tls_config
+        .as_ref()
+        .map(TlsConfig::server_tls_config)
+        .transpose()?;
+    let grpc_server = start_grpc_service(grpc_socket_addr, grpc_services, server_tls_config_opt);
 
-    let rest_server = start_rest_service(args.rest_socket_addr, search_service, cluster_service);
+    let public_grpc_socket_addr = http_addr_to_public_grpc_addr(args.rest_socket_addr);
+    let public_grpc_services = PublicGrpcServices {
+        otlp_logs_service: OtlpGrpcLogsService::new(ingest_service.clone()),
+        otlp_trace_service: OtlpGrpcTraceService::new(ingest_service.clone()),
+        jaeger_service: JaegerService::new(search_service.clone(), metastore.clone()),
+    };
+    let public_server_tls_config_opt = args
+        .tls_config
+        .as_ref()
+        .map(TlsConfig::public_server_tls_config)
+        .transpose()?;
+    let public_grpc_server = start_public_grpc_service(
+        public_grpc_socket_addr,
+        public_grpc_services,
+        authenticator.clone(),
+        public_server_tls_config_opt,
+    );
+
+    let rest_services = RestServices {
+        search_service,
+        cluster_service,
+        metastore,
+        ingest_service,
+        quickwit_cache,
+        leaf_search_cache,
+    };
+    let rest_server = start_rest_service(
+        args.rest_socket_addr,
+        rest_services,
+        authenticator,
+        args.tls_config.clone(),
+    );
 
-    display_help_message(args.rest_socket_addr, &example_index_name)?;
+    let scheme = if args.tls_config.is_some() {
+        "https"
+    } else {
+        "http"
+    };
+    display_help_message(args.rest_socket_addr, scheme, &example_index_name)?;
 
-    tokio::try_join!(rest_server, grpc_server)?;
+    tokio::try_join!(rest_server, grpc_server, public_grpc_server)?;
 
     Ok(())
 }
@@ -156,18 +317,19 @@ mod tests {
     use std::ops::Range;
     use std::sync::Arc;
 
+    use futures::TryStreamExt;
     use quickwit_index_config::WikipediaIndexConfig;
     use quickwit_indexing::mock_split_meta;
     use quickwit_metastore::checkpoint::Checkpoint;
     use quickwit_metastore::{IndexMetadata, MockMetastore, SplitState};
     use quickwit_proto::search_service_server::SearchServiceServer;
-    use quickwit_proto::OutputFormat;
+    use quickwit_proto::{OutputFormat, SortOrder};
     use quickwit_search::{
         create_search_service_client, root_search_stream, MockSearchService, SearchError,
         SearchService,
     };
     use tokio::sync::RwLock;
-    use tokio_stream::wrappers::UnboundedReceiverStream;
+    use tokio_stream::wrappers::ReceiverStream;
     use tonic::transport::Server;
 
     use super::*;
@@ -196,9 +358,12 @@ mod tests {
             search_fields: vec!["body".to_string()],
             start_timestamp: None,
             end_timestamp: None,
-            fast_field: "timestamp".to_string(),
+            fast_fields: vec!["timestamp".to_string()],
             output_format: OutputFormat::Csv as i32,
             tags: vec![],
+            csv_header: false,
+            sort_order: SortOrder::Unsorted as i32,
+            limit: None,
         };
         let mut metastore = MockMetastore::new();
         metastore
@@ -209,6 +374,7 @@ mod tests {
                     index_uri: "file:///path/to/index/test-idx".to_string(),
                     index_config: Arc::new(WikipediaIndexConfig::new()),
                     checkpoint: Checkpoint::default(),
+                    encryption_key_id: None,
                 })
             });
         metastore.expect_list_splits().returning(
@@ -220,20 +386,25 @@ mod tests {
             },
         );
         let mut mock_search_service = MockSearchService::new();
-        let (result_sender, result_receiver) = tokio::sync::mpsc::unbounded_channel();
-        result_sender.send(Ok(quickwit_proto::LeafSearchStreamResult {
-            data: b"123".to_vec(),
-            split_id: "split_1".to_string(),
-        }))?;
-        result_sender.send(Err(SearchError::InternalError(
-            "Error on `split2`".to_string(),
-        )))?;
+        let (result_sender, result_receiver) = tokio::sync::mpsc::channel(10);
+        result_sender
+            .send(Ok(quickwit_proto::LeafSearchStreamResult {
+                data: b"123".to_vec(),
+                split_id: "split_1".to_string(),
+                sort_values: Vec::new(),
+            }))
+            .await?;
+        result_sender
+            .send(Err(SearchError::InternalError(
+                "Error on `split2`".to_string(),
+            )))
+            .await?;
         mock_search_service
             .expect_leaf_search_stream()
             .withf(|request| request.split_metadata.len() == 2) // First request.
             .return_once(
                 |_leaf_search_req: quickwit_proto::LeafSearchStreamRequest| {
-                    Ok(UnboundedReceiverStream::new(result_receiver))
+                    Ok(ReceiverStream::new(result_receiver))
                 },
             );
         mock_search_service
@@ -251,18 +422,24 @@ mod tests {
 
         let grpc_addr: SocketAddr = format!("127.0.0.1:{}", 10000).parse()?;
         start_test_server(grpc_addr, Arc::new(mock_search_service)).await?;
-        let client = create_search_service_client(grpc_addr).await?;
+        let client = create_search_service_client(grpc_addr, None).await?;
         let clients: HashMap<_, _> = IntoIter::new([(grpc_addr, client)]).collect();
         let client_pool = Arc::new(SearchClientPool {
             clients: Arc::new(RwLock::new(clients)),
+            loads: Default::default(),
         });
         let cluster_client = ClusterClient::new(client_pool.clone());
-        let search_result =
-            root_search_stream(&request, &metastore, &cluster_client, &client_pool).await;
+        let search_result: Result<Vec<_>, SearchError> =
+            root_search_stream(&request, &metastore, &cluster_client, &client_pool)
+                .await?
+                .try_collect()
+                .await;
         assert!(search_result.is_err());
+        // The leaf error travels as a structured gRPC status and is rebuilt as is by the root,
+        // instead of being wrapped into another internal error.
         assert_eq!(
             search_result.unwrap_err().to_string(),
-            "Internal error: `Internal error: `Error again on `split2``.`."
+            "Internal error: `Error again on `split2``."
         );
         Ok(())
     }
@@ -0,0 +1,58 @@
+// Copyright (C) 2021 Quickwit, Inc.
+//
+// Quickwit is offered under the AGPL v3.0 and as commercial software.
+// For commercial licensing, contact us at hello@quickwit.io.
+//
+// AGPL:
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use quickwit_cluster::cluster::{Cluster, AVAILABLE_CACHE_BYTES_METADATA_KEY, LOAD_METADATA_KEY};
+use quickwit_search::num_leaf_splits_in_flight;
+use quickwit_storage::{Cache, LocalDiskCache};
+
+/// Delay between two updates of the metadata gossiped by the node.
+const METADATA_UPDATE_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Spawns a task that periodically updates the load and the available cache bytes
+/// gossiped by the node.
+pub fn spawn_node_metadata_updater(
+    cluster: Arc<Cluster>,
+    local_disk_cache_opt: Option<Arc<LocalDiskCache>>,
+    split_cache_capacity_in_bytes: u64,
+) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(METADATA_UPDATE_INTERVAL);
+        loop {
+            interval.tick().await;
+            let available_cache_bytes = local_disk_cache_opt
+                .as_ref()
+                .map(|local_disk_cache| {
+                    split_cache_capacity_in_bytes.saturating_sub(local_disk_cache.stats().num_bytes)
+                })
+                .unwrap_or(0);
+            // Both values are set at once so that the node is reincarnated at most once per
+            // update, and not at all when nothing changed.
+            cluster.set_metadata_entries(vec![
+                (LOAD_METADATA_KEY, num_leaf_splits_in_flight().to_string()),
+                (
+                    AVAILABLE_CACHE_BYTES_METADATA_KEY,
+                    available_cache_bytes.to_string(),
+                ),
+            ]);
+        }
+    });
+}
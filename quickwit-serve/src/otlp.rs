@@ -0,0 +1,383 @@
+// Copyright (C) 2021 Quickwit, Inc.
+//
+// Quickwit is offered under the AGPL v3.0 and as commercial software.
+// For commercial licensing, contact us at hello@quickwit.io.
+//
+// AGPL:
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+//! Receiver of the OpenTelemetry protocol (OTLP).
+//!
+//! Log records and spans are exported over gRPC, with the `LogsService` and `TraceService`
+//! of the gRPC server, or over HTTP, by posting protobuf-encoded export requests to
+//! `api/v1/otlp/v1/logs` and `api/v1/otlp/v1/traces`. They are mapped onto JSON documents and
+//! pushed to the in-server ingest pipelines of the `otel-logs` and `otel-traces` indexes.
+
+mod logs;
+mod traces;
+
+use std::convert::Infallible;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use bytes::Bytes;
+use prost::Message;
+use quickwit_index_config::{DefaultIndexConfig, DefaultIndexConfigBuilder};
+use quickwit_metastore::checkpoint::Checkpoint;
+use quickwit_metastore::{IndexMetadata, Metastore, MetastoreError};
+use quickwit_proto::opentelemetry::proto::collector::logs::v1::{
+    ExportLogsServiceRequest, ExportLogsServiceResponse,
+};
+use quickwit_proto::opentelemetry::proto::collector::trace::v1::{
+    ExportTraceServiceRequest, ExportTraceServiceResponse,
+};
+use quickwit_proto::opentelemetry::proto::common::v1::any_value::Value as OtlpValue;
+use quickwit_proto::opentelemetry::proto::common::v1::{AnyValue, KeyValue};
+use quickwit_proto::opentelemetry::proto::resource::v1::Resource;
+use serde_json::Value as JsonValue;
+use tracing::info;
+use warp::hyper::header::CONTENT_TYPE;
+use warp::{reply, Filter, Rejection, Reply};
+
+pub use self::logs::{OtlpGrpcLogsService, OTEL_LOGS_INDEX_ID};
+pub use self::traces::{OtlpGrpcTraceService, OTEL_TRACES_INDEX_ID};
+use crate::auth::{authentication_filter, Access, Authenticator, Principal};
+use crate::ingest::IngestService;
+use crate::rest::Format;
+use crate::ApiError;
+
+/// Maximum size of the body of an OTLP/HTTP request.
+const MAX_OTLP_BODY_NUM_BYTES: u64 = 10 * 1_024 * 1_024;
+
+/// Service name of the resources that do not declare one, as in the OpenTelemetry SDKs.
+const UNKNOWN_SERVICE_NAME: &str = "unknown_service";
+
+const PROTOBUF_CONTENT_TYPE: &str = "application/x-protobuf";
+
+/// Creates the `otel-logs` and `otel-traces` indexes under the given root URI, unless they
+/// already exist.
+pub async fn create_otlp_indexes(
+    metastore: &dyn Metastore,
+    indexes_root_uri: &str,
+) -> anyhow::Result<()> {
+    let index_configs = [
+        (OTEL_LOGS_INDEX_ID, logs::OTEL_LOGS_INDEX_CONFIG),
+        (OTEL_TRACES_INDEX_ID, traces::OTEL_TRACES_INDEX_CONFIG),
+    ];
+    for (index_id, index_config_json) in index_configs.iter() {
+        match metastore.index_metadata(index_id).await {
+            Ok(_) => continue,
+            Err(MetastoreError::IndexDoesNotExist { .. }) => {}
+            Err(error) => return Err(error.into()),
+        }
+        let index_metadata = IndexMetadata {
+            index_id: index_id.to_string(),
+            index_uri: format!("{}/{}", indexes_root_uri.trim_end_matches('/'), index_id),
+            index_config: Arc::new(build_index_config(index_config_json)?),
+            checkpoint: Checkpoint::default(),
+            encryption_key_id: None,
+        };
+        match metastore.create_index(index_metadata).await {
+            Ok(()) => info!(index_id = %index_id, "Created OTLP index."),
+            // Another node may have created the index in the meantime.
+            Err(MetastoreError::IndexAlreadyExists { .. }) => {}
+            Err(error) => return Err(error.into()),
+        }
+    }
+    Ok(())
+}
+
+fn build_index_config(index_config_json: &str) -> anyhow::Result<DefaultIndexConfig> {
+    serde_json::from_str::<DefaultIndexConfigBuilder>(index_config_json)?.build()
+}
+
+/// Signal exported over OTLP/HTTP.
+#[derive(Clone, Copy, Debug)]
+enum OtlpSignal {
+    Logs,
+    Traces,
+}
+
+impl OtlpSignal {
+    fn index_id(&self) -> &'static str {
+        match self {
+            OtlpSignal::Logs => OTEL_LOGS_INDEX_ID,
+            OtlpSignal::Traces => OTEL_TRACES_INDEX_ID,
+        }
+    }
+
+    /// Decodes an export request, and returns its documents along with the encoded response.
+    fn decode_request(&self, body: &[u8]) -> Result<(Vec<String>, Vec<u8>), prost::DecodeError> {
+        match self {
+            OtlpSignal::Logs => {
+                let request = ExportLogsServiceRequest::decode(body)?;
+                let docs = logs::logs_request_to_docs(request);
+                Ok((docs, ExportLogsServiceResponse {}.encode_to_vec()))
+            }
+            OtlpSignal::Traces => {
+                let request = ExportTraceServiceRequest::decode(body)?;
+                let docs = traces::trace_request_to_docs(request);
+                Ok((docs, ExportTraceServiceResponse {}.encode_to_vec()))
+            }
+        }
+    }
+}
+
+/// Handlers of the OTLP/HTTP receiver. Only protobuf-encoded requests are supported.
+pub fn otlp_http_handlers(
+    ingest_service: Arc<IngestService>,
+    authenticator: Arc<Authenticator>,
+) -> impl Filter<Extract = impl warp::Reply, Error = Rejection> + Clone {
+    warp::path!("api" / "v1" / "otlp" / "v1" / "logs")
+        .map(|| OtlpSignal::Logs)
+        .or(warp::path!("api" / "v1" / "otlp" / "v1" / "traces").map(|| OtlpSignal::Traces))
+        .unify()
+        .and(warp::post())
+        .and(warp::header::optional::<String>("content-type"))
+        // Requests are authenticated before their body is read.
+        .and(authentication_filter(authenticator))
+        .and(warp::body::content_length_limit(MAX_OTLP_BODY_NUM_BYTES))
+        .and(warp::body::bytes())
+        .and(warp::any().map(move || ingest_service.clone()))
+        .and_then(otlp_http_export)
+}
+
+async fn otlp_http_export(
+    signal: OtlpSignal,
+    content_type_opt: Option<String>,
+    principal: Principal,
+    body: Bytes,
+    ingest_service: Arc<IngestService>,
+) -> Result<warp::reply::Response, Infallible> {
+    let export_result = otlp_http_export_endpoint(
+        signal,
+        content_type_opt.as_deref(),
+        &body,
+        &principal,
+        &ingest_service,
+    )
+    .await;
+    let reply = match export_result {
+        Ok(response_body) => {
+            reply::with_header(response_body, CONTENT_TYPE, PROTOBUF_CONTENT_TYPE).into_response()
+        }
+        Err(api_error) => Format::Json
+            .make_reply(Err::<(), ApiError>(api_error))
+            .into_response(),
+    };
+    Ok(reply)
+}
+
+async fn otlp_http_export_endpoint(
+    signal: OtlpSignal,
+    content_type_opt: Option<&str>,
+    body: &[u8],
+    principal: &Principal,
+    ingest_service: &IngestService,
+) -> Result<Vec<u8>, ApiError> {
+    let index_id = signal.index_id();
+    principal.check_index_access(index_id, Access::Write)?;
+    let is_protobuf = content_type_opt
+        .map(|content_type| content_type.trim().starts_with(PROTOBUF_CONTENT_TYPE))
+        .unwrap_or(true);
+    if !is_protobuf {
+        return Err(ApiError::InvalidArgument(format!(
+            "OTLP/HTTP requests must be encoded as protobuf, with the content type `{}`",
+            PROTOBUF_CONTENT_TYPE
+        )));
+    }
+    let (docs, response_body) = signal.decode_request(body).map_err(|error| {
+        ApiError::InvalidArgument(format!("invalid OTLP export request: {}", error))
+    })?;
+    ingest_docs(ingest_service, index_id, docs).await?;
+    Ok(response_body)
+}
+
+async fn ingest_docs(
+    ingest_service: &IngestService,
+    index_id: &str,
+    docs: Vec<String>,
+) -> Result<(), ApiError> {
+    if docs.is_empty() {
+        return Ok(());
+    }
+    ingest_service.ingest(index_id, docs).await
+}
+
+/// Returns the `service.name` attribute of the resource.
+fn service_name(resource_opt: Option<&Resource>) -> String {
+    resource_opt
+        .and_then(|resource| {
+            resource
+                .attributes
+                .iter()
+                .find(|attribute| attribute.key == "service.name")
+        })
+        .and_then(|attribute| attribute.value.as_ref())
+        .map(any_value_to_string)
+        .filter(|service_name| !service_name.is_empty())
+        .unwrap_or_else(|| UNKNOWN_SERVICE_NAME.to_string())
+}
+
+fn resource_attributes(resource_opt: Option<&Resource>) -> Vec<String> {
+    resource_opt
+        .map(|resource| attributes_to_strings(&resource.attributes))
+        .unwrap_or_default()
+}
+
+/// Formats the attributes as `key:value` strings, which are indexed as single tokens.
+fn attributes_to_strings(attributes: &[KeyValue]) -> Vec<String> {
+    attributes
+        .iter()
+        .map(|attribute| {
+            let value = attribute
+                .value
+                .as_ref()
+                .map(any_value_to_string)
+                .unwrap_or_default();
+            format!("{}:{}", attribute.key, value)
+        })
+        .collect()
+}
+
+fn attributes_to_json(attributes: &[KeyValue]) -> JsonValue {
+    attributes
+        .iter()
+        .map(|attribute| {
+            let value = attribute
+                .value
+                .as_ref()
+                .map(any_value_to_json)
+                .unwrap_or(JsonValue::Null);
+            (attribute.key.clone(), value)
+        })
+        .collect::<serde_json::Map<_, _>>()
+        .into()
+}
+
+/// Strings are returned as is, the other values are formatted as JSON.
+fn any_value_to_string(any_value: &AnyValue) -> String {
+    match &any_value.value {
+        Some(OtlpValue::StringValue(value)) => value.clone(),
+        _ => any_value_to_json(any_value).to_string(),
+    }
+}
+
+fn any_value_to_json(any_value: &AnyValue) -> JsonValue {
+    match &any_value.value {
+        Some(OtlpValue::StringValue(value)) => JsonValue::from(value.as_str()),
+        Some(OtlpValue::BoolValue(value)) => JsonValue::from(*value),
+        Some(OtlpValue::IntValue(value)) => JsonValue::from(*value),
+        Some(OtlpValue::DoubleValue(value)) => JsonValue::from(*value),
+        Some(OtlpValue::ArrayValue(array_value)) => array_value
+            .values
+            .iter()
+            .map(any_value_to_json)
+            .collect::<Vec<_>>()
+            .into(),
+        Some(OtlpValue::KvlistValue(kvlist_value)) => attributes_to_json(&kvlist_value.values),
+        Some(OtlpValue::BytesValue(value)) => JsonValue::from(base64::encode(value)),
+        None => JsonValue::Null,
+    }
+}
+
+/// Formats a trace or span ID as lowercase hex, as in the W3C Trace Context. Returns `None`
+/// for empty IDs.
+pub(crate) fn hex_id(id: &[u8]) -> Option<String> {
+    if id.is_empty() {
+        return None;
+    }
+    Some(id.iter().map(|byte| format!("{:02x}", byte)).collect())
+}
+
+/// Returns the timestamp in seconds of a time in nanoseconds since the Unix epoch. The
+/// current time is used for the unknown times, set to 0.
+fn timestamp_secs(time_unix_nano: u64) -> i64 {
+    let time_unix_nano = if time_unix_nano == 0 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_nanos() as u64)
+            .unwrap_or_default()
+    } else {
+        time_unix_nano
+    };
+    (time_unix_nano / 1_000_000_000) as i64
+}
+
+#[cfg(test)]
+mod tests {
+    use quickwit_proto::opentelemetry::proto::common::v1::{ArrayValue, KeyValueList};
+    use serde_json::json;
+
+    use super::*;
+
+    pub(crate) fn key_value(key: &str, value: OtlpValue) -> KeyValue {
+        KeyValue {
+            key: key.to_string(),
+            value: Some(AnyValue { value: Some(value) }),
+        }
+    }
+
+    #[test]
+    fn test_otlp_attributes() {
+        let attributes = vec![
+            key_value("http.method", OtlpValue::StringValue("GET".to_string())),
+            key_value("http.status_code", OtlpValue::IntValue(200)),
+            key_value(
+                "tags",
+                OtlpValue::ArrayValue(ArrayValue {
+                    values: vec![AnyValue {
+                        value: Some(OtlpValue::BoolValue(true)),
+                    }],
+                }),
+            ),
+            key_value(
+                "peer",
+                OtlpValue::KvlistValue(KeyValueList {
+                    values: vec![key_value("port", OtlpValue::IntValue(8080))],
+                }),
+            ),
+        ];
+        assert_eq!(
+            attributes_to_strings(&attributes),
+            vec![
+                "http.method:GET",
+                "http.status_code:200",
+                "tags:[true]",
+                r#"peer:{"port":8080}"#,
+            ]
+        );
+        assert_eq!(
+            attributes_to_json(&attributes),
+            json!({
+                "http.method": "GET",
+                "http.status_code": 200,
+                "tags": [true],
+                "peer": {"port": 8080},
+            })
+        );
+        let resource = Resource {
+            attributes: vec![key_value(
+                "service.name",
+                OtlpValue::StringValue("checkout".to_string()),
+            )],
+            dropped_attributes_count: 0,
+        };
+        assert_eq!(service_name(Some(&resource)), "checkout");
+        assert_eq!(service_name(None), UNKNOWN_SERVICE_NAME);
+        assert_eq!(hex_id(&[0, 1, 171, 255]), Some("0001abff".to_string()));
+        assert_eq!(hex_id(&[]), None);
+        assert_eq!(timestamp_secs(1_633_947_052_123_456_789), 1_633_947_052);
+    }
+}
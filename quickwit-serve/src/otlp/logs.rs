@@ -0,0 +1,200 @@
+// Copyright (C) 2021 Quickwit, Inc.
+//
+// Quickwit is offered under the AGPL v3.0 and as commercial software.
+// For commercial licensing, contact us at hello@quickwit.io.
+//
+// AGPL:
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use quickwit_proto::error::GrpcServiceError;
+use quickwit_proto::opentelemetry::proto::collector::logs::v1::logs_service_server::LogsService;
+use quickwit_proto::opentelemetry::proto::collector::logs::v1::{
+    ExportLogsServiceRequest, ExportLogsServiceResponse,
+};
+use quickwit_proto::opentelemetry::proto::logs::v1::LogRecord;
+use serde_json::json;
+
+use super::{
+    any_value_to_string, attributes_to_strings, hex_id, ingest_docs, resource_attributes,
+    service_name, timestamp_secs,
+};
+use crate::auth::{grpc_principal, Access};
+use crate::ingest::IngestService;
+
+/// ID of the index receiving the log records exported over OTLP.
+pub const OTEL_LOGS_INDEX_ID: &str = "otel-logs";
+
+pub(super) const OTEL_LOGS_INDEX_CONFIG: &str = r#"{
+    "store_source": false,
+    "default_search_fields": ["body"],
+    "timestamp_field": "timestamp",
+    "tag_fields": ["service_name", "severity_text"],
+    "field_mappings": [
+        {"name": "timestamp", "type": "i64", "fast": true},
+        {"name": "time_unix_nano", "type": "u64", "indexed": false},
+        {"name": "service_name", "type": "text", "tokenizer": "raw"},
+        {"name": "severity_text", "type": "text", "tokenizer": "raw"},
+        {"name": "severity_number", "type": "u64", "fast": true},
+        {"name": "body", "type": "text", "record": "position"},
+        {"name": "attributes", "type": "array<text>", "tokenizer": "raw"},
+        {"name": "resource_attributes", "type": "array<text>", "tokenizer": "raw"},
+        {"name": "trace_id", "type": "text", "tokenizer": "raw"},
+        {"name": "span_id", "type": "text", "tokenizer": "raw"}
+    ]
+}"#;
+
+/// Maps the log records of an export request onto documents of the `otel-logs` index.
+pub(super) fn logs_request_to_docs(request: ExportLogsServiceRequest) -> Vec<String> {
+    let mut docs = Vec::new();
+    for resource_logs in request.resource_logs {
+        let service_name = service_name(resource_logs.resource.as_ref());
+        let resource_attributes = resource_attributes(resource_logs.resource.as_ref());
+        for instrumentation_library_logs in resource_logs.instrumentation_library_logs {
+            for log_record in instrumentation_library_logs.logs {
+                let doc = log_record_to_doc(log_record, &service_name, &resource_attributes);
+                docs.push(doc);
+            }
+        }
+    }
+    docs
+}
+
+fn log_record_to_doc(
+    log_record: LogRecord,
+    service_name: &str,
+    resource_attributes: &[String],
+) -> String {
+    let severity_text = Some(log_record.severity_text).filter(|text| !text.is_empty());
+    // `SEVERITY_NUMBER_UNSPECIFIED` is not indexed.
+    let severity_number = Some(log_record.severity_number)
+        .filter(|severity_number| *severity_number > 0)
+        .map(|severity_number| severity_number as u64);
+    let body = log_record.body.as_ref().map(any_value_to_string);
+    json!({
+        "timestamp": timestamp_secs(log_record.time_unix_nano),
+        "time_unix_nano": Some(log_record.time_unix_nano).filter(|time| *time > 0),
+        "service_name": service_name,
+        "severity_text": severity_text,
+        "severity_number": severity_number,
+        "body": body,
+        "attributes": attributes_to_strings(&log_record.attributes),
+        "resource_attributes": resource_attributes,
+        "trace_id": hex_id(&log_record.trace_id),
+        "span_id": hex_id(&log_record.span_id),
+    })
+    .to_string()
+}
+
+/// gRPC `LogsService` of the OTLP receiver.
+pub struct OtlpGrpcLogsService {
+    ingest_service: Arc<IngestService>,
+}
+
+impl OtlpGrpcLogsService {
+    pub fn new(ingest_service: Arc<IngestService>) -> Self {
+        OtlpGrpcLogsService { ingest_service }
+    }
+}
+
+#[async_trait]
+impl LogsService for OtlpGrpcLogsService {
+    async fn export(
+        &self,
+        request: tonic::Request<ExportLogsServiceRequest>,
+    ) -> Result<tonic::Response<ExportLogsServiceResponse>, tonic::Status> {
+        grpc_principal(&request)?
+            .check_index_access(OTEL_LOGS_INDEX_ID, Access::Write)
+            .map_err(|api_error| api_error.grpc_status())?;
+        let docs = logs_request_to_docs(request.into_inner());
+        ingest_docs(&self.ingest_service, OTEL_LOGS_INDEX_ID, docs)
+            .await
+            .map_err(|api_error| api_error.grpc_status())?;
+        Ok(tonic::Response::new(ExportLogsServiceResponse {}))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use quickwit_index_config::IndexConfig;
+    use quickwit_proto::opentelemetry::proto::common::v1::any_value::Value as OtlpValue;
+    use quickwit_proto::opentelemetry::proto::common::v1::AnyValue;
+    use quickwit_proto::opentelemetry::proto::logs::v1::{
+        InstrumentationLibraryLogs, ResourceLogs, SeverityNumber,
+    };
+    use quickwit_proto::opentelemetry::proto::resource::v1::Resource;
+    use serde_json::Value as JsonValue;
+
+    use super::*;
+    use crate::otlp::build_index_config;
+    use crate::otlp::tests::key_value;
+
+    #[test]
+    fn test_logs_request_to_docs() -> anyhow::Result<()> {
+        let log_record = LogRecord {
+            time_unix_nano: 1_633_947_052_123_456_789,
+            severity_number: SeverityNumber::Error as i32,
+            severity_text: "ERROR".to_string(),
+            name: String::new(),
+            body: Some(AnyValue {
+                value: Some(OtlpValue::StringValue("payment declined".to_string())),
+            }),
+            attributes: vec![key_value("user.id", OtlpValue::IntValue(42))],
+            dropped_attributes_count: 0,
+            flags: 0,
+            trace_id: vec![1; 16],
+            span_id: Vec::new(),
+        };
+        let request = ExportLogsServiceRequest {
+            resource_logs: vec![ResourceLogs {
+                resource: Some(Resource {
+                    attributes: vec![key_value(
+                        "service.name",
+                        OtlpValue::StringValue("checkout".to_string()),
+                    )],
+                    dropped_attributes_count: 0,
+                }),
+                instrumentation_library_logs: vec![InstrumentationLibraryLogs {
+                    instrumentation_library: None,
+                    logs: vec![log_record],
+                    schema_url: String::new(),
+                }],
+                schema_url: String::new(),
+            }],
+        };
+        let docs = logs_request_to_docs(request);
+        assert_eq!(docs.len(), 1);
+        let doc_json: JsonValue = serde_json::from_str(&docs[0])?;
+        assert_eq!(
+            doc_json,
+            json!({
+                "timestamp": 1_633_947_052,
+                "time_unix_nano": 1_633_947_052_123_456_789u64,
+                "service_name": "checkout",
+                "severity_text": "ERROR",
+                "severity_number": 17,
+                "body": "payment declined",
+                "attributes": ["user.id:42"],
+                "resource_attributes": ["service.name:checkout"],
+                "trace_id": "01010101010101010101010101010101",
+                "span_id": null,
+            })
+        );
+        let index_config = build_index_config(OTEL_LOGS_INDEX_CONFIG)?;
+        index_config.doc_from_json(&docs[0])?;
+        Ok(())
+    }
+}
@@ -0,0 +1,264 @@
+// Copyright (C) 2021 Quickwit, Inc.
+//
+// Quickwit is offered under the AGPL v3.0 and as commercial software.
+// For commercial licensing, contact us at hello@quickwit.io.
+//
+// AGPL:
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use quickwit_proto::error::GrpcServiceError;
+use quickwit_proto::opentelemetry::proto::collector::trace::v1::trace_service_server::TraceService;
+use quickwit_proto::opentelemetry::proto::collector::trace::v1::{
+    ExportTraceServiceRequest, ExportTraceServiceResponse,
+};
+use quickwit_proto::opentelemetry::proto::trace::v1::span::SpanKind;
+use quickwit_proto::opentelemetry::proto::trace::v1::status::StatusCode;
+use quickwit_proto::opentelemetry::proto::trace::v1::Span;
+use serde_json::json;
+
+use super::{
+    attributes_to_json, attributes_to_strings, hex_id, ingest_docs, resource_attributes,
+    service_name, timestamp_secs,
+};
+use crate::auth::{grpc_principal, Access};
+use crate::ingest::IngestService;
+
+/// ID of the index receiving the spans exported over OTLP.
+pub const OTEL_TRACES_INDEX_ID: &str = "otel-traces";
+
+pub(super) const OTEL_TRACES_INDEX_CONFIG: &str = r#"{
+    "store_source": false,
+    "default_search_fields": ["span_name"],
+    "timestamp_field": "timestamp",
+    "tag_fields": ["service_name"],
+    "sort_by": {"field_name": "span_start_timestamp_nanos", "order": "desc"},
+    "field_mappings": [
+        {"name": "timestamp", "type": "i64", "fast": true},
+        {"name": "trace_id", "type": "text", "tokenizer": "raw"},
+        {"name": "span_id", "type": "text", "tokenizer": "raw"},
+        {"name": "parent_span_id", "type": "text", "tokenizer": "raw"},
+        {"name": "service_name", "type": "text", "tokenizer": "raw"},
+        {"name": "span_name", "type": "text", "tokenizer": "raw"},
+        {"name": "span_kind", "type": "text", "tokenizer": "raw"},
+        {"name": "span_start_timestamp_nanos", "type": "u64", "fast": true},
+        {"name": "span_end_timestamp_nanos", "type": "u64", "indexed": false},
+        {"name": "span_duration_nanos", "type": "u64", "fast": true},
+        {"name": "span_attributes", "type": "array<text>", "tokenizer": "raw"},
+        {"name": "resource_attributes", "type": "array<text>", "tokenizer": "raw"},
+        {"name": "span_status_code", "type": "text", "tokenizer": "raw"},
+        {"name": "span_status_message", "type": "text"},
+        {"name": "events", "type": "array<text>", "indexed": false}
+    ]
+}"#;
+
+/// Maps the spans of an export request onto documents of the `otel-traces` index.
+pub(super) fn trace_request_to_docs(request: ExportTraceServiceRequest) -> Vec<String> {
+    let mut docs = Vec::new();
+    for resource_spans in request.resource_spans {
+        let service_name = service_name(resource_spans.resource.as_ref());
+        let resource_attributes = resource_attributes(resource_spans.resource.as_ref());
+        for instrumentation_library_spans in resource_spans.instrumentation_library_spans {
+            for span in instrumentation_library_spans.spans {
+                docs.push(span_to_doc(span, &service_name, &resource_attributes));
+            }
+        }
+    }
+    docs
+}
+
+fn span_kind_name(span_kind: i32) -> Option<&'static str> {
+    let span_kind_name = match SpanKind::from_i32(span_kind)? {
+        SpanKind::Unspecified => return None,
+        SpanKind::Internal => "internal",
+        SpanKind::Server => "server",
+        SpanKind::Client => "client",
+        SpanKind::Producer => "producer",
+        SpanKind::Consumer => "consumer",
+    };
+    Some(span_kind_name)
+}
+
+fn status_code_name(status_code: i32) -> &'static str {
+    match StatusCode::from_i32(status_code) {
+        Some(StatusCode::Ok) => "ok",
+        Some(StatusCode::Error) => "error",
+        Some(StatusCode::Unset) | None => "unset",
+    }
+}
+
+fn span_to_doc(span: Span, service_name: &str, resource_attributes: &[String]) -> String {
+    let span_duration_nanos = span
+        .end_time_unix_nano
+        .saturating_sub(span.start_time_unix_nano);
+    let (status_code, status_message) = match span.status {
+        Some(status) => (
+            status.code,
+            Some(status.message).filter(|msg| !msg.is_empty()),
+        ),
+        None => (StatusCode::Unset as i32, None),
+    };
+    // Span events are stored, but not indexed, as JSON objects.
+    let events: Vec<String> = span
+        .events
+        .iter()
+        .map(|event| {
+            json!({
+                "time_unix_nano": event.time_unix_nano,
+                "name": event.name,
+                "attributes": attributes_to_json(&event.attributes),
+            })
+            .to_string()
+        })
+        .collect();
+    json!({
+        "timestamp": timestamp_secs(span.start_time_unix_nano),
+        "trace_id": hex_id(&span.trace_id),
+        "span_id": hex_id(&span.span_id),
+        "parent_span_id": hex_id(&span.parent_span_id),
+        "service_name": service_name,
+        "span_name": span.name,
+        "span_kind": span_kind_name(span.kind),
+        "span_start_timestamp_nanos": span.start_time_unix_nano,
+        "span_end_timestamp_nanos": span.end_time_unix_nano,
+        "span_duration_nanos": span_duration_nanos,
+        "span_attributes": attributes_to_strings(&span.attributes),
+        "resource_attributes": resource_attributes,
+        "span_status_code": status_code_name(status_code),
+        "span_status_message": status_message,
+        "events": events,
+    })
+    .to_string()
+}
+
+/// gRPC `TraceService` of the OTLP receiver.
+pub struct OtlpGrpcTraceService {
+    ingest_service: Arc<IngestService>,
+}
+
+impl OtlpGrpcTraceService {
+    pub fn new(ingest_service: Arc<IngestService>) -> Self {
+        OtlpGrpcTraceService { ingest_service }
+    }
+}
+
+#[async_trait]
+impl TraceService for OtlpGrpcTraceService {
+    async fn export(
+        &self,
+        request: tonic::Request<ExportTraceServiceRequest>,
+    ) -> Result<tonic::Response<ExportTraceServiceResponse>, tonic::Status> {
+        grpc_principal(&request)?
+            .check_index_access(OTEL_TRACES_INDEX_ID, Access::Write)
+            .map_err(|api_error| api_error.grpc_status())?;
+        let docs = trace_request_to_docs(request.into_inner());
+        ingest_docs(&self.ingest_service, OTEL_TRACES_INDEX_ID, docs)
+            .await
+            .map_err(|api_error| api_error.grpc_status())?;
+        Ok(tonic::Response::new(ExportTraceServiceResponse {}))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use quickwit_index_config::IndexConfig;
+    use quickwit_proto::opentelemetry::proto::common::v1::any_value::Value as OtlpValue;
+    use quickwit_proto::opentelemetry::proto::trace::v1::span::Event;
+    use quickwit_proto::opentelemetry::proto::trace::v1::{
+        InstrumentationLibrarySpans, ResourceSpans, Status,
+    };
+    use serde_json::Value as JsonValue;
+
+    use super::*;
+    use crate::otlp::build_index_config;
+    use crate::otlp::tests::key_value;
+
+    #[test]
+    fn test_trace_request_to_docs() -> anyhow::Result<()> {
+        let span = Span {
+            trace_id: vec![1; 16],
+            span_id: vec![2; 8],
+            trace_state: String::new(),
+            parent_span_id: Vec::new(),
+            name: "GET /cart".to_string(),
+            kind: SpanKind::Server as i32,
+            start_time_unix_nano: 1_633_947_052_000_000_000,
+            end_time_unix_nano: 1_633_947_052_250_000_000,
+            attributes: vec![key_value(
+                "http.method",
+                OtlpValue::StringValue("GET".to_string()),
+            )],
+            dropped_attributes_count: 0,
+            events: vec![Event {
+                time_unix_nano: 1_633_947_052_100_000_000,
+                name: "cache miss".to_string(),
+                attributes: Vec::new(),
+                dropped_attributes_count: 0,
+            }],
+            dropped_events_count: 0,
+            links: Vec::new(),
+            dropped_links_count: 0,
+            status: Some(Status {
+                message: "timeout".to_string(),
+                code: StatusCode::Error as i32,
+            }),
+        };
+        let request = ExportTraceServiceRequest {
+            resource_spans: vec![ResourceSpans {
+                resource: None,
+                instrumentation_library_spans: vec![InstrumentationLibrarySpans {
+                    instrumentation_library: None,
+                    spans: vec![span],
+                    schema_url: String::new(),
+                }],
+                schema_url: String::new(),
+            }],
+        };
+        let docs = trace_request_to_docs(request);
+        assert_eq!(docs.len(), 1);
+        let mut doc_json: JsonValue = serde_json::from_str(&docs[0])?;
+        // The key order of the serialized events depends on the `serde_json` features.
+        let event_json: JsonValue = serde_json::from_str(doc_json["events"][0].as_str().unwrap())?;
+        assert_eq!(
+            event_json,
+            json!({"time_unix_nano": 1_633_947_052_100_000_000u64, "name": "cache miss", "attributes": {}})
+        );
+        doc_json["events"] = json!([]);
+        assert_eq!(
+            doc_json,
+            json!({
+                "timestamp": 1_633_947_052,
+                "trace_id": "01010101010101010101010101010101",
+                "span_id": "0202020202020202",
+                "parent_span_id": null,
+                "service_name": "unknown_service",
+                "span_name": "GET /cart",
+                "span_kind": "server",
+                "span_start_timestamp_nanos": 1_633_947_052_000_000_000u64,
+                "span_end_timestamp_nanos": 1_633_947_052_250_000_000u64,
+                "span_duration_nanos": 250_000_000,
+                "span_attributes": ["http.method:GET"],
+                "resource_attributes": [],
+                "span_status_code": "error",
+                "span_status_message": "timeout",
+                "events": [],
+            })
+        );
+        let index_config = build_index_config(OTEL_TRACES_INDEX_CONFIG)?;
+        index_config.doc_from_json(&docs[0])?;
+        Ok(())
+    }
+}
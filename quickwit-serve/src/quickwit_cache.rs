@@ -19,52 +19,78 @@
 
 use std::ops::Range;
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
 
 use async_trait::async_trait;
 use bytes::Bytes;
-use quickwit_common::HOTCACHE_FILENAME;
-use quickwit_storage::{Cache, SliceCache};
+use quickwit_storage::{Cache, CacheStats, SliceCache};
+use serde::Serialize;
 
 const FULL_SLICE: Range<usize> = 0..usize::MAX;
 
-/// Hotcache cache capacity is hardcoded to 500 MB.
-/// Once the capacity is reached, a LRU strategy is used.
-const HOTCACHE_CACHE_CAPACITY: usize = 500_000_000;
+/// Configuration of a cache route.
+///
+/// Files whose path ends with `path_suffix` are kept in an in-memory
+/// LRU cache of `capacity_in_bytes` bytes. The files bundled in a split are cached
+/// under `<split_id>.split/<file name>`, and the split footer under
+/// `<split_id>.split/hotcache`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CacheRouteConfig {
+    pub path_suffix: String,
+    pub capacity_in_bytes: usize,
+}
 
-pub struct QuickwitCache {
-    router: Vec<(&'static str, Arc<dyn Cache>)>,
+/// Statistics of a cache route, as exposed by the cache stats endpoint.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CacheRouteStats {
+    pub path_suffix: String,
+    #[serde(flatten)]
+    pub stats: CacheStats,
 }
 
-impl From<Vec<(&'static str, Arc<dyn Cache>)>> for QuickwitCache {
-    fn from(router: Vec<(&'static str, Arc<dyn Cache>)>) -> Self {
-        QuickwitCache { router }
-    }
+pub struct QuickwitCache {
+    router: Vec<(String, Arc<dyn Cache>)>,
 }
 
-impl Default for QuickwitCache {
-    fn default() -> Self {
+impl QuickwitCache {
+    /// Creates a cache with one in-memory route per route config.
+    pub fn new(route_configs: &[CacheRouteConfig]) -> QuickwitCache {
         let mut quickwit_cache = QuickwitCache::empty();
-        quickwit_cache.add_route(
-            HOTCACHE_FILENAME,
-            Arc::new(SimpleCache::with_capacity_in_bytes(HOTCACHE_CACHE_CAPACITY)),
-        );
+        for route_config in route_configs {
+            quickwit_cache.add_route(
+                &route_config.path_suffix,
+                Arc::new(SimpleCache::with_capacity_in_bytes(
+                    route_config.capacity_in_bytes,
+                )),
+            );
+        }
         quickwit_cache
     }
-}
 
-impl QuickwitCache {
     pub fn empty() -> QuickwitCache {
-        QuickwitCache::from(Vec::new())
+        QuickwitCache { router: Vec::new() }
+    }
+
+    pub fn add_route(&mut self, path_suffix: &str, route_cache: Arc<dyn Cache>) {
+        self.router.push((path_suffix.to_string(), route_cache));
     }
 
-    pub fn add_route(&mut self, path_suffix: &'static str, route_cache: Arc<dyn Cache>) {
-        self.router.push((path_suffix, route_cache));
+    /// Returns the statistics of each route, in routing order.
+    pub fn route_stats(&self) -> Vec<CacheRouteStats> {
+        self.router
+            .iter()
+            .map(|(path_suffix, cache)| CacheRouteStats {
+                path_suffix: path_suffix.clone(),
+                stats: cache.stats(),
+            })
+            .collect()
     }
 
     fn get_relevant_cache(&self, path: &Path) -> Option<&dyn Cache> {
         for (suffix, cache) in &self.router {
-            if path.to_string_lossy().ends_with(suffix) {
+            if path.to_string_lossy().ends_with(suffix.as_str()) {
                 return Some(cache.as_ref());
             }
         }
@@ -96,39 +122,62 @@ impl Cache for QuickwitCache {
 
     async fn put_all(&self, path: PathBuf, bytes: Bytes) {
         if let Some(cache) = self.get_relevant_cache(&path) {
-            cache.put(path, FULL_SLICE, bytes).await;
+            cache.put_all(path, bytes).await;
         }
     }
+
+    fn stats(&self) -> CacheStats {
+        let mut stats = CacheStats::default();
+        for (_, cache) in &self.router {
+            stats += cache.stats();
+        }
+        stats
+    }
 }
 
 /// The Quickwit cache logic is very simple for the moment.
 ///
-/// It stores hotcache files using an LRU cache.
+/// It stores files using an LRU cache.
 ///
 /// HACK! We use `0..usize::MAX` to signify the "entire file".
 /// TODO fixme
 struct SimpleCache {
     slice_cache: SliceCache,
+    // Lookups are counted here, as a single lookup may hit the slice cache twice.
+    num_hits: AtomicU64,
+    num_misses: AtomicU64,
 }
 
 impl SimpleCache {
     fn with_capacity_in_bytes(capacity_in_bytes: usize) -> Self {
         SimpleCache {
             slice_cache: SliceCache::with_capacity_in_bytes(capacity_in_bytes),
+            num_hits: AtomicU64::new(0),
+            num_misses: AtomicU64::new(0),
+        }
+    }
+
+    fn record_lookup(&self, bytes_opt: Option<Bytes>) -> Option<Bytes> {
+        if bytes_opt.is_some() {
+            self.num_hits.fetch_add(1, Ordering::Relaxed);
+        } else {
+            self.num_misses.fetch_add(1, Ordering::Relaxed);
         }
+        bytes_opt
     }
 }
 
 #[async_trait]
 impl Cache for SimpleCache {
     async fn get(&self, path: &Path, byte_range: Range<usize>) -> Option<Bytes> {
-        if let Some(bytes) = self.get_all(path).await {
-            return Some(bytes.slice(byte_range.clone()));
-        }
-        if let Some(bytes) = self.slice_cache.get(path, byte_range) {
-            return Some(bytes);
+        if let Some(bytes) = self.slice_cache.get(path, FULL_SLICE) {
+            // `Bytes::slice` panics on out of bounds ranges.
+            if byte_range.end > bytes.len() {
+                return self.record_lookup(None);
+            }
+            return self.record_lookup(Some(bytes.slice(byte_range)));
         }
-        None
+        self.record_lookup(self.slice_cache.get(path, byte_range))
     }
 
     async fn put(&self, path: PathBuf, byte_range: Range<usize>, bytes: Bytes) {
@@ -136,11 +185,19 @@ impl Cache for SimpleCache {
     }
 
     async fn get_all(&self, path: &Path) -> Option<Bytes> {
-        self.slice_cache.get(path, FULL_SLICE.clone())
+        self.record_lookup(self.slice_cache.get(path, FULL_SLICE))
     }
 
     async fn put_all(&self, path: PathBuf, bytes: Bytes) {
-        self.slice_cache.put(path, FULL_SLICE.clone(), bytes);
+        self.slice_cache.put(path, FULL_SLICE, bytes);
+    }
+
+    fn stats(&self) -> CacheStats {
+        CacheStats {
+            num_hits: self.num_hits.load(Ordering::Relaxed),
+            num_misses: self.num_misses.load(Ordering::Relaxed),
+            ..self.slice_cache.stats()
+        }
     }
 }
 
@@ -150,9 +207,9 @@ mod tests {
     use std::sync::Arc;
 
     use bytes::Bytes;
-    use quickwit_storage::{Cache, MockCache};
+    use quickwit_storage::{Cache, CacheStats, MockCache};
 
-    use super::QuickwitCache;
+    use super::{CacheRouteConfig, QuickwitCache};
 
     #[tokio::test]
     async fn test_quickwit_cache_get_all() {
@@ -204,4 +261,83 @@ mod tests {
             &b"aaaaa"[..]
         );
     }
+
+    #[tokio::test]
+    async fn test_quickwit_cache_hotcache_route() {
+        let quickwit_cache = QuickwitCache::new(&[CacheRouteConfig {
+            path_suffix: "hotcache".to_string(),
+            capacity_in_bytes: 1_000,
+        }]);
+        let footer_path = Path::new("split.split/hotcache");
+        quickwit_cache
+            .put_all(footer_path.to_path_buf(), Bytes::from_static(b"footer"))
+            .await;
+        assert_eq!(
+            quickwit_cache.get_all(footer_path).await.unwrap(),
+            &b"footer"[..]
+        );
+        assert_eq!(quickwit_cache.route_stats()[0].stats.num_hits, 1);
+    }
+
+    #[tokio::test]
+    async fn test_quickwit_cache_out_of_bounds_slice() {
+        let quickwit_cache = QuickwitCache::new(&[CacheRouteConfig {
+            path_suffix: "hotcache".to_string(),
+            capacity_in_bytes: 1_000,
+        }]);
+        let footer_path = Path::new("split.split/hotcache");
+        quickwit_cache
+            .put_all(footer_path.to_path_buf(), Bytes::from_static(b"footer"))
+            .await;
+        assert_eq!(
+            quickwit_cache.get(footer_path, 2..6).await.unwrap(),
+            &b"oter"[..]
+        );
+        assert!(quickwit_cache.get(footer_path, 2..7).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_quickwit_cache_route_stats() {
+        let quickwit_cache = QuickwitCache::new(&[
+            CacheRouteConfig {
+                path_suffix: ".fast".to_string(),
+                capacity_in_bytes: 5,
+            },
+            CacheRouteConfig {
+                path_suffix: ".term".to_string(),
+                capacity_in_bytes: 1_000,
+            },
+        ]);
+        let fast_path = Path::new("split.split/seg.fast");
+        assert!(quickwit_cache.get(fast_path, 0..3).await.is_none());
+        quickwit_cache
+            .put(fast_path.to_path_buf(), 0..3, Bytes::from_static(b"abc"))
+            .await;
+        assert!(quickwit_cache.get(fast_path, 0..3).await.is_some());
+        quickwit_cache
+            .put(fast_path.to_path_buf(), 3..6, Bytes::from_static(b"def"))
+            .await;
+        assert!(quickwit_cache.get(fast_path, 0..3).await.is_none());
+        // Files matching no route are not cached.
+        let store_path = Path::new("split.split/seg.store");
+        quickwit_cache
+            .put(store_path.to_path_buf(), 0..3, Bytes::from_static(b"abc"))
+            .await;
+        assert!(quickwit_cache.get(store_path, 0..3).await.is_none());
+
+        let route_stats = quickwit_cache.route_stats();
+        assert_eq!(route_stats.len(), 2);
+        assert_eq!(route_stats[0].path_suffix, ".fast");
+        assert_eq!(
+            route_stats[0].stats,
+            CacheStats {
+                num_hits: 1,
+                num_misses: 2,
+                num_evictions: 1,
+                num_bytes: 3,
+            }
+        );
+        assert_eq!(route_stats[1].path_suffix, ".term");
+        assert_eq!(route_stats[1].stats, CacheStats::default());
+    }
 }
@@ -24,31 +24,94 @@ use std::sync::Arc;
 use bytes::Bytes;
 use futures::stream::{self, StreamExt};
 use quickwit_cluster::service::ClusterServiceImpl;
-use quickwit_proto::OutputFormat;
-use quickwit_search::{SearchResponseRest, SearchService, SearchServiceImpl};
+use quickwit_metastore::Metastore;
+use quickwit_proto::{OutputFormat, SortOrder};
+use quickwit_search::{LeafSearchCache, SearchResponseRest, SearchService, SearchServiceImpl};
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Deserializer};
 use tracing::info;
-use warp::hyper::header::CONTENT_TYPE;
+use warp::hyper::header::{CONTENT_TYPE, WWW_AUTHENTICATE};
 use warp::hyper::StatusCode;
 use warp::{reply, Filter, Rejection, Reply};
 
+use crate::auth::{
+    authentication_filter, require_authentication, Access, Authenticator, Principal,
+    Unauthenticated,
+};
+use crate::elastic_api::elastic_api_handlers;
+use crate::http_handler::cache::cache_stats_handler;
 use crate::http_handler::cluster::cluster_handler;
 use crate::http_handler::health_check::liveness_check_handler;
+use crate::http_handler::metrics::metrics_handler;
+use crate::ingest::IngestService;
+use crate::otlp::otlp_http_handlers;
+use crate::quickwit_cache::QuickwitCache;
+use crate::tls::TlsConfig;
 use crate::ApiError;
 
-/// Start REST service given a HTTP address and a search service.
+/// Services and caches backing the routes of the REST API.
+pub struct RestServices {
+    pub search_service: Arc<SearchServiceImpl>,
+    pub cluster_service: Arc<ClusterServiceImpl>,
+    pub metastore: Arc<dyn Metastore>,
+    pub ingest_service: Arc<IngestService>,
+    pub quickwit_cache: Arc<QuickwitCache>,
+    pub leaf_search_cache: Arc<LeafSearchCache>,
+}
+
+/// Start REST service given a HTTP address and the services backing its routes.
+/// The service is served over HTTPS if a TLS config is given. All the routes but the
+/// liveness check require the credentials of a user when the authenticator is enabled.
 pub async fn start_rest_service(
     rest_addr: SocketAddr,
-    search_service: Arc<SearchServiceImpl>,
-    cluster_service: Arc<ClusterServiceImpl>,
+    services: RestServices,
+    authenticator: Arc<Authenticator>,
+    tls_config_opt: Option<TlsConfig>,
 ) -> anyhow::Result<()> {
-    info!(rest_addr=?rest_addr, "Starting REST service.");
+    info!(rest_addr=?rest_addr, tls=tls_config_opt.is_some(), "Starting REST service.");
+    let RestServices {
+        search_service,
+        cluster_service,
+        metastore,
+        ingest_service,
+        quickwit_cache,
+        leaf_search_cache,
+    } = services;
     let rest_routes = liveness_check_handler()
-        .or(cluster_handler(cluster_service))
-        .or(search_handler(search_service.clone()))
-        .or(search_stream_handler(search_service))
+        .or(require_authentication(authenticator.clone()).and(cluster_handler(cluster_service)))
+        .or(
+            require_authentication(authenticator.clone()).and(cache_stats_handler(
+                quickwit_cache.clone(),
+                leaf_search_cache,
+            )),
+        )
+        .or(require_authentication(authenticator.clone()).and(metrics_handler(quickwit_cache)))
+        .or(search_handler(
+            search_service.clone(),
+            authenticator.clone(),
+        ))
+        .or(search_stream_handler(
+            search_service.clone(),
+            authenticator.clone(),
+        ))
+        .or(elastic_api_handlers(
+            search_service,
+            metastore,
+            ingest_service.clone(),
+            authenticator.clone(),
+        ))
+        .or(otlp_http_handlers(ingest_service, authenticator))
         .recover(recover_fn);
-    warp::serve(rest_routes).run(rest_addr).await;
+    if let Some(tls_config) = tls_config_opt {
+        warp::serve(rest_routes)
+            .tls()
+            .cert_path(&tls_config.cert_path)
+            .key_path(&tls_config.key_path)
+            .run(rest_addr)
+            .await;
+    } else {
+        warp::serve(rest_routes).run(rest_addr).await;
+    }
     Ok(())
 }
 
@@ -142,13 +205,76 @@ pub struct SearchRequestQueryString {
     #[serde(default)]
     #[serde(deserialize_with = "from_simple_list")]
     pub tags: Option<Vec<String>>,
+    /// If set, splits that could not be searched are reported in the response instead of
+    /// failing the whole request.
+    #[serde(default)]
+    pub allow_partial_results: bool,
+    /// Deadline of the requests sent to the leaf nodes, in milliseconds. It is capped by the
+    /// leaf request timeout of the node.
+    pub timeout_millis: Option<u64>,
+}
+
+/// This struct represents the JSON body of a search request passed to the REST API.
+///
+/// It holds the same parameters as [`SearchRequestQueryString`], lists being JSON arrays.
+#[derive(Deserialize, Debug, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchRequestBody {
+    /// Query text. The query language is that of tantivy.
+    pub query: String,
+    /// Fields to search on.
+    #[serde(default)]
+    pub search_fields: Vec<String>,
+    /// If set, restrict search to documents with a `timestamp >= start_timestamp`.
+    pub start_timestamp: Option<i64>,
+    /// If set, restrict search to documents with a `timestamp < end_timestamp``.
+    pub end_timestamp: Option<i64>,
+    /// Maximum number of hits to return (by default 20).
+    #[serde(default = "default_max_hits")]
+    pub max_hits: u64,
+    /// First hit to return.
+    #[serde(default)]
+    pub start_offset: u64,
+    /// The output format.
+    #[serde(default)]
+    pub format: Format,
+    /// The tag filter.
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// If set, splits that could not be searched are reported in the response instead of
+    /// failing the whole request.
+    #[serde(default)]
+    pub allow_partial_results: bool,
+    /// Deadline of the requests sent to the leaf nodes, in milliseconds. It is capped by the
+    /// leaf request timeout of the node.
+    pub timeout_millis: Option<u64>,
+}
+
+impl From<SearchRequestBody> for SearchRequestQueryString {
+    fn from(search_request_body: SearchRequestBody) -> Self {
+        SearchRequestQueryString {
+            query: search_request_body.query,
+            search_fields: Some(search_request_body.search_fields),
+            start_timestamp: search_request_body.start_timestamp,
+            end_timestamp: search_request_body.end_timestamp,
+            max_hits: search_request_body.max_hits,
+            start_offset: search_request_body.start_offset,
+            format: search_request_body.format,
+            tags: Some(search_request_body.tags),
+            allow_partial_results: search_request_body.allow_partial_results,
+            timeout_millis: search_request_body.timeout_millis,
+        }
+    }
 }
 
 async fn search_endpoint<TSearchService: SearchService>(
     index_id: String,
     search_request: SearchRequestQueryString,
+    principal: &Principal,
     search_service: &TSearchService,
 ) -> Result<SearchResponseRest, ApiError> {
+    principal.check_index_access(&index_id, Access::Read)?;
     let search_request = quickwit_proto::SearchRequest {
         index_id,
         query: search_request.query,
@@ -158,6 +284,8 @@ async fn search_endpoint<TSearchService: SearchService>(
         max_hits: search_request.max_hits,
         start_offset: search_request.start_offset,
         tags: search_request.tags.unwrap_or_default(),
+        allow_partial_results: search_request.allow_partial_results,
+        timeout_millis: search_request.timeout_millis,
     };
     let search_response = search_service.root_search(search_request).await?;
     let search_response_rest =
@@ -165,31 +293,67 @@ async fn search_endpoint<TSearchService: SearchService>(
     Ok(search_response_rest)
 }
 
+// The search filters authenticate the requests before parsing their query string or body, so
+// that anonymous clients cannot make the node parse large requests.
 fn search_filter(
-) -> impl Filter<Extract = (String, SearchRequestQueryString), Error = Rejection> + Clone {
+    authenticator: Arc<Authenticator>,
+) -> impl Filter<Extract = (String, SearchRequestQueryString, Principal), Error = Rejection> + Clone
+{
     warp::path!("api" / "v1" / String / "search")
         .and(warp::get())
+        .and(authentication_filter(authenticator))
         .and(serde_qs::warp::query(serde_qs::Config::default()))
+        .map(
+            |index_id: String, principal: Principal, search_request: SearchRequestQueryString| {
+                (index_id, search_request, principal)
+            },
+        )
+        .untuple_one()
+}
+
+fn search_post_filter(
+    authenticator: Arc<Authenticator>,
+) -> impl Filter<Extract = (String, SearchRequestQueryString, Principal), Error = Rejection> + Clone
+{
+    warp::path!("api" / "v1" / String / "search")
+        .and(warp::post())
+        .and(authentication_filter(authenticator))
+        .and(json_body())
+        .map(
+            |index_id: String, principal: Principal, search_request_body: SearchRequestBody| {
+                (
+                    index_id,
+                    SearchRequestQueryString::from(search_request_body),
+                    principal,
+                )
+            },
+        )
+        .untuple_one()
 }
 
 async fn search<TSearchService: SearchService>(
     index_id: String,
     search_request: SearchRequestQueryString,
+    principal: Principal,
     search_service: Arc<TSearchService>,
 ) -> Result<impl warp::Reply, Infallible> {
     info!(index_id = %index_id, request =? search_request, "search");
     Ok(search_request
         .format
-        .make_reply(search_endpoint(index_id, search_request, &*search_service).await))
+        .make_reply(search_endpoint(index_id, search_request, &principal, &*search_service).await))
 }
 
 /// REST search handler.
 ///
-/// Parses the search request from the
+/// Parses the search request from the query string of GET requests, or from the JSON body
+/// of POST requests.
 pub fn search_handler<TSearchService: SearchService>(
     search_service: Arc<TSearchService>,
+    authenticator: Arc<Authenticator>,
 ) -> impl Filter<Extract = impl warp::Reply, Error = Rejection> + Clone {
-    search_filter()
+    search_filter(authenticator.clone())
+        .or(search_post_filter(authenticator))
+        .unify()
         .and(warp::any().map(move || search_service.clone()))
         .and_then(search)
 }
@@ -211,99 +375,306 @@ pub struct SearchStreamRequestQueryString {
     pub start_timestamp: Option<i64>,
     /// If set, restricts search to documents with a `timestamp < end_timestamp``.
     pub end_timestamp: Option<i64>,
-    /// The fast field to extract.
+    /// The fast fields to extract, one column per fast field.
     #[serde(default)]
-    pub fast_field: String,
-    /// The requested output format.
+    #[serde(rename(deserialize = "fastField"))]
+    #[serde(deserialize_with = "from_simple_list")]
+    pub fast_fields: Option<Vec<String>>,
+    /// The requested output format. If not set, it is negotiated from the `Accept` header.
     #[serde(default)]
-    pub output_format: OutputFormat,
+    pub output_format: Option<OutputFormat>,
     /// The tag filter.
     #[serde(default)]
     #[serde(deserialize_with = "from_simple_list")]
     pub tags: Option<Vec<String>>,
+    /// If set, the CSV output starts with a header row listing the fast fields.
+    #[serde(default)]
+    pub csv_header: bool,
+    /// If set, the values are streamed in the timestamp order.
+    #[serde(default)]
+    pub sort_order: Option<SortOrder>,
+    /// If set, the stream stops after this number of values.
+    pub limit: Option<u64>,
+}
+
+/// This struct represents the JSON body of a search stream request passed to the REST API.
+///
+/// It holds the same parameters as [`SearchStreamRequestQueryString`], lists being JSON
+/// arrays.
+#[derive(Deserialize, Debug, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchStreamRequestBody {
+    /// Query text. The query language is that of tantivy.
+    pub query: String,
+    /// Fields to search on.
+    #[serde(default)]
+    pub search_fields: Vec<String>,
+    /// If set, restricts search to documents with a `timestamp >= start_timestamp`.
+    pub start_timestamp: Option<i64>,
+    /// If set, restricts search to documents with a `timestamp < end_timestamp``.
+    pub end_timestamp: Option<i64>,
+    /// The fast fields to extract, one column per fast field.
+    #[serde(default)]
+    pub fast_fields: Vec<String>,
+    /// The requested output format. If not set, it is negotiated from the `Accept` header.
+    #[serde(default)]
+    pub output_format: Option<OutputFormat>,
+    /// The tag filter.
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// If set, the CSV output starts with a header row listing the fast fields.
+    #[serde(default)]
+    pub csv_header: bool,
+    /// If set, the values are streamed in the timestamp order.
+    #[serde(default)]
+    pub sort_order: Option<SortOrder>,
+    /// If set, the stream stops after this number of values.
+    pub limit: Option<u64>,
+}
+
+impl From<SearchStreamRequestBody> for SearchStreamRequestQueryString {
+    fn from(search_stream_request_body: SearchStreamRequestBody) -> Self {
+        SearchStreamRequestQueryString {
+            query: search_stream_request_body.query,
+            search_fields: Some(search_stream_request_body.search_fields),
+            start_timestamp: search_stream_request_body.start_timestamp,
+            end_timestamp: search_stream_request_body.end_timestamp,
+            fast_fields: Some(search_stream_request_body.fast_fields),
+            output_format: search_stream_request_body.output_format,
+            tags: Some(search_stream_request_body.tags),
+            csv_header: search_stream_request_body.csv_header,
+            sort_order: search_stream_request_body.sort_order,
+            limit: search_stream_request_body.limit,
+        }
+    }
+}
+
+/// Picks the output format matching the first supported media type of an `Accept` header,
+/// falling back to CSV.
+fn negotiate_output_format(accept_header_opt: Option<&str>) -> OutputFormat {
+    accept_header_opt
+        .into_iter()
+        .flat_map(|accept_header| accept_header.split(','))
+        .filter_map(|media_range| {
+            let media_type = media_range.split(';').next().unwrap_or_default().trim();
+            match media_type {
+                "text/csv" => Some(OutputFormat::Csv),
+                "application/vnd.apache.arrow.stream" => Some(OutputFormat::ArrowIpcStream),
+                "application/vnd.apache.parquet" | "application/x-parquet" => {
+                    Some(OutputFormat::Parquet)
+                }
+                _ => None,
+            }
+        })
+        .next()
+        .unwrap_or(OutputFormat::Csv)
+}
+
+fn output_format_content_type(output_format: OutputFormat) -> &'static str {
+    match output_format {
+        OutputFormat::ClickHouseRowBinary => "application/octet-stream",
+        OutputFormat::Csv => "text/csv",
+        OutputFormat::ArrowIpcStream => "application/vnd.apache.arrow.stream",
+        OutputFormat::Parquet => "application/vnd.apache.parquet",
+    }
 }
 
 async fn search_stream_endpoint<TSearchService: SearchService>(
     index_id: String,
     search_request: SearchStreamRequestQueryString,
+    output_format: OutputFormat,
+    principal: &Principal,
     search_service: &TSearchService,
 ) -> Result<hyper::Body, ApiError> {
+    principal.check_index_access(&index_id, Access::Read)?;
     let request = quickwit_proto::SearchStreamRequest {
         index_id,
         query: search_request.query,
         search_fields: search_request.search_fields.unwrap_or_default(),
         start_timestamp: search_request.start_timestamp,
         end_timestamp: search_request.end_timestamp,
-        fast_field: search_request.fast_field,
-        output_format: search_request.output_format as i32,
+        fast_fields: search_request.fast_fields.unwrap_or_default(),
+        output_format: output_format as i32,
         tags: search_request.tags.unwrap_or_default(),
+        csv_header: search_request.csv_header,
+        sort_order: search_request.sort_order.unwrap_or(SortOrder::Unsorted) as i32,
+        limit: search_request.limit,
     };
-    let data = search_service.root_search_stream(request).await?;
-    let stream = stream::iter(data).map(Result::<Bytes, std::io::Error>::Ok);
+    let mut data_stream = search_service.root_search_stream(request).await?;
+    // An error occurring before the first chunk is still reported with a proper status code.
+    // Past this point, the status is already sent and an error aborts the response.
+    let first_chunk_opt = data_stream.next().await.transpose()?;
+    let stream = stream::iter(first_chunk_opt.map(Ok)).chain(data_stream);
     let body = hyper::Body::wrap_stream(stream);
     Ok(body)
 }
 
-fn make_streaming_reply(result: Result<hyper::Body, ApiError>) -> impl Reply {
-    let status_code: StatusCode;
-    let body = match result {
-        Ok(body) => {
-            status_code = StatusCode::OK;
-            warp::reply::Response::new(body)
-        }
-        Err(err) => {
-            status_code = err.http_status_code();
-            warp::reply::Response::new(hyper::Body::from(err.message()))
-        }
-    };
-    reply::with_status(body, status_code)
+fn make_streaming_reply(
+    result: Result<hyper::Body, ApiError>,
+    output_format: OutputFormat,
+) -> warp::reply::Response {
+    match result {
+        Ok(body) => reply::with_header(
+            warp::reply::Response::new(body),
+            CONTENT_TYPE,
+            output_format_content_type(output_format),
+        )
+        .into_response(),
+        // Errors are reported in JSON, whatever the requested output format.
+        Err(err) => Format::Json
+            .make_reply(Err::<(), ApiError>(err))
+            .into_response(),
+    }
 }
 
 async fn search_stream<TSearchService: SearchService>(
     index_id: String,
     request: SearchStreamRequestQueryString,
+    principal: Principal,
+    accept_header_opt: Option<String>,
     search_service: Arc<TSearchService>,
 ) -> Result<impl warp::Reply, Infallible> {
     info!(index_id=%index_id,request=?request, "search_stream");
-    let content_type = match request.output_format {
-        OutputFormat::ClickHouseRowBinary => "application/octet-stream",
-        OutputFormat::Csv => "text/csv",
-    };
-    let reply =
-        make_streaming_reply(search_stream_endpoint(index_id, request, &*search_service).await);
-    let reply_with_header = reply::with_header(reply, CONTENT_TYPE, content_type);
-    Ok(reply_with_header)
+    // An explicit `outputFormat` parameter takes precedence over the `Accept` header.
+    let output_format = request
+        .output_format
+        .unwrap_or_else(|| negotiate_output_format(accept_header_opt.as_deref()));
+    let reply = make_streaming_reply(
+        search_stream_endpoint(
+            index_id,
+            request,
+            output_format,
+            &principal,
+            &*search_service,
+        )
+        .await,
+        output_format,
+    );
+    Ok(reply)
 }
 
 fn search_stream_filter(
-) -> impl Filter<Extract = (String, SearchStreamRequestQueryString), Error = Rejection> + Clone {
+    authenticator: Arc<Authenticator>,
+) -> impl Filter<Extract = (String, SearchStreamRequestQueryString, Principal), Error = Rejection> + Clone
+{
     warp::path!("api" / "v1" / String / "search" / "stream")
         .and(warp::get())
+        .and(authentication_filter(authenticator))
         .and(serde_qs::warp::query(serde_qs::Config::default()))
+        .map(
+            |index_id: String,
+             principal: Principal,
+             search_stream_request: SearchStreamRequestQueryString| {
+                (index_id, search_stream_request, principal)
+            },
+        )
+        .untuple_one()
 }
 
+fn search_stream_post_filter(
+    authenticator: Arc<Authenticator>,
+) -> impl Filter<Extract = (String, SearchStreamRequestQueryString, Principal), Error = Rejection> + Clone
+{
+    warp::path!("api" / "v1" / String / "search" / "stream")
+        .and(warp::post())
+        .and(authentication_filter(authenticator))
+        .and(json_body())
+        .map(
+            |index_id: String,
+             principal: Principal,
+             search_stream_request_body: SearchStreamRequestBody| {
+                (
+                    index_id,
+                    SearchStreamRequestQueryString::from(search_stream_request_body),
+                    principal,
+                )
+            },
+        )
+        .untuple_one()
+}
+
+/// REST search stream handler.
+///
+/// Parses the search stream request from the query string of GET requests, or from the
+/// JSON body of POST requests.
 pub fn search_stream_handler<TSearchService: SearchService>(
     search_service: Arc<TSearchService>,
+    authenticator: Arc<Authenticator>,
 ) -> impl Filter<Extract = impl warp::Reply, Error = Rejection> + Clone {
-    search_stream_filter()
+    search_stream_filter(authenticator.clone())
+        .or(search_stream_post_filter(authenticator))
+        .unify()
+        .and(warp::header::optional::<String>("accept"))
         .and(warp::any().map(move || search_service.clone()))
         .and_then(search_stream)
 }
 
-/// This function returns a formated error based on the given rejection reason.
-async fn recover_fn(rejection: Rejection) -> Result<impl Reply, Rejection> {
-    // TODO handle more errors.
-    match rejection.find::<serde_qs::Error>() {
-        Some(err) => {
-            // The querystring was incorrect.
-            Ok(
-                Format::PrettyJson.make_reply(Err::<(), ApiError>(ApiError::InvalidArgument(
-                    err.to_string(),
-                ))),
-            )
+/// Maximum size of the JSON body of a request.
+const MAX_REQUEST_BODY_NUM_BYTES: u64 = 1_024 * 1_024;
+
+/// Rejection of the requests whose JSON body is invalid.
+#[derive(Debug)]
+pub struct InvalidRequestBody(String);
+
+impl warp::reject::Reject for InvalidRequestBody {}
+
+/// Parses the JSON body of a request.
+pub(crate) fn json_body<T: DeserializeOwned + Send>(
+) -> impl Filter<Extract = (T,), Error = Rejection> + Clone {
+    warp::body::content_length_limit(MAX_REQUEST_BODY_NUM_BYTES)
+        .and(warp::body::bytes())
+        .and_then(|body: Bytes| async move {
+            parse_json_body(&body).map_err(|error| warp::reject::custom(InvalidRequestBody(error)))
+        })
+}
+
+/// Deserializes a JSON request body, returning an error message that points at the field
+/// that could not be parsed, if any.
+fn parse_json_body<T: DeserializeOwned>(body: &[u8]) -> Result<T, String> {
+    let mut deserializer = serde_json::Deserializer::from_slice(body);
+    let value = serde_path_to_error::deserialize(&mut deserializer).map_err(|error| {
+        let path = error.path().to_string();
+        if path == "." {
+            format!("invalid request body: {}", error.inner())
+        } else {
+            format!("invalid field `{}`: {}", path, error.inner())
         }
-        None => Ok(Format::PrettyJson.make_reply(Err::<(), ApiError>(ApiError::NotFound))),
+    })?;
+    deserializer
+        .end()
+        .map_err(|error| format!("invalid request body: {}", error))?;
+    Ok(value)
+}
+
+/// This function returns a formated error based on the given rejection reason.
+pub(crate) async fn recover_fn(rejection: Rejection) -> Result<impl Reply, Rejection> {
+    if rejection.find::<Unauthenticated>().is_some() {
+        let reply = Format::PrettyJson.make_reply(Err::<(), ApiError>(ApiError::Unauthenticated));
+        return Ok(reply::with_header(
+            reply,
+            WWW_AUTHENTICATE,
+            r#"Basic realm="quickwit", Bearer realm="quickwit""#,
+        )
+        .into_response());
     }
+    let api_error =
+        if let Some(InvalidRequestBody(error_message)) = rejection.find::<InvalidRequestBody>() {
+            ApiError::InvalidArgument(error_message.clone())
+        } else if let Some(serde_qs_error) = rejection.find::<serde_qs::Error>() {
+            // The querystring was incorrect.
+            ApiError::InvalidArgument(serde_qs_error.to_string())
+        } else if rejection.find::<warp::reject::PayloadTooLarge>().is_some() {
+            ApiError::InvalidArgument("request body is too large".to_string())
+        } else if rejection.find::<warp::reject::LengthRequired>().is_some() {
+            ApiError::InvalidArgument("request body requires a `Content-Length` header".to_string())
+        } else if let Some(invalid_header) = rejection.find::<warp::reject::InvalidHeader>() {
+            ApiError::InvalidArgument(invalid_header.to_string())
+        } else {
+            ApiError::NotFound
+        };
+    let reply = Format::PrettyJson.make_reply(Err::<(), ApiError>(api_error));
+    Ok(reply.into_response())
 }
 
 fn from_simple_list<'de, D>(deserializer: D) -> Result<Option<Vec<String>>, D::Error>
@@ -324,6 +695,7 @@ mod tests {
     use mockall::predicate;
     use quickwit_search::{MockSearchService, SearchError};
     use serde_json::json;
+    use tokio_stream::wrappers::ReceiverStream;
 
     use super::*;
 
@@ -333,6 +705,7 @@ mod tests {
             num_hits: 55,
             hits: Vec::new(),
             elapsed_time_micros: 0u64,
+            failed_splits: Vec::new(),
         };
         let search_response_json: serde_json::Value = serde_json::to_value(&search_response)?;
         let expected_search_response_json: serde_json::Value = json!({
@@ -349,8 +722,8 @@ mod tests {
 
     #[tokio::test]
     async fn test_rest_search_api_route_simple() {
-        let rest_search_api_filter = search_filter();
-        let (index, req) = warp::test::request()
+        let rest_search_api_filter = search_filter(Arc::new(Authenticator::disabled()));
+        let (index, req, _principal) = warp::test::request()
             .path(
                 "/api/v1/quickwit-demo-index/search?query=*&endTimestamp=1450720000&maxHits=10&\
                  startOffset=22",
@@ -369,15 +742,17 @@ mod tests {
                 max_hits: 10,
                 start_offset: 22,
                 format: Format::default(),
-                tags: None
+                tags: None,
+                allow_partial_results: false,
+                timeout_millis: None,
             }
         );
     }
 
     #[tokio::test]
     async fn test_rest_search_api_route_simple_default_num_hits_default_offset() {
-        let rest_search_api_filter = search_filter();
-        let (index, req) = warp::test::request()
+        let rest_search_api_filter = search_filter(Arc::new(Authenticator::disabled()));
+        let (index, req, _principal) = warp::test::request()
             .path(
                 "/api/v1/quickwit-demo-index/search?query=*&endTimestamp=1450720000&\
                  searchField=title,body",
@@ -396,15 +771,17 @@ mod tests {
                 max_hits: 20,
                 start_offset: 0,
                 format: Format::default(),
-                tags: None
+                tags: None,
+                allow_partial_results: false,
+                timeout_millis: None,
             }
         );
     }
 
     #[tokio::test]
     async fn test_rest_search_api_route_simple_format() {
-        let rest_search_api_filter = search_filter();
-        let (index, req) = warp::test::request()
+        let rest_search_api_filter = search_filter(Arc::new(Authenticator::disabled()));
+        let (index, req, _principal) = warp::test::request()
             .path("/api/v1/quickwit-demo-index/search?query=*&format=json")
             .filter(&rest_search_api_filter)
             .await
@@ -420,16 +797,88 @@ mod tests {
                 start_offset: 0,
                 format: Format::Json,
                 search_fields: None,
-                tags: None
+                tags: None,
+                allow_partial_results: false,
+                timeout_millis: None,
             }
         );
     }
 
+    #[tokio::test]
+    async fn test_rest_search_api_route_post() {
+        let rest_search_api_filter = search_post_filter(Arc::new(Authenticator::disabled()));
+        let (index, req, _principal) = warp::test::request()
+            .method("POST")
+            .path("/api/v1/quickwit-demo-index/search")
+            .json(&json!({
+                "query": "*",
+                "searchFields": ["title", "body"],
+                "endTimestamp": 1450720000,
+                "maxHits": 10,
+                "tags": ["tenant:1"],
+            }))
+            .filter(&rest_search_api_filter)
+            .await
+            .unwrap();
+        assert_eq!(&index, "quickwit-demo-index");
+        assert_eq!(
+            &req,
+            &super::SearchRequestQueryString {
+                query: "*".to_string(),
+                search_fields: Some(vec!["title".to_string(), "body".to_string()]),
+                start_timestamp: None,
+                end_timestamp: Some(1450720000),
+                max_hits: 10,
+                start_offset: 0,
+                format: Format::default(),
+                tags: Some(vec!["tenant:1".to_string()]),
+                allow_partial_results: false,
+                timeout_millis: None,
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_rest_search_api_route_post_invalid_field() -> anyhow::Result<()> {
+        let mock_search_service = MockSearchService::new();
+        let rest_search_api_handler = super::search_handler(
+            Arc::new(mock_search_service),
+            Arc::new(Authenticator::disabled()),
+        )
+        .recover(recover_fn);
+        let resp = warp::test::request()
+            .method("POST")
+            .path("/api/v1/quickwit-demo-index/search")
+            .json(&json!({"query": "*", "maxHits": "ten"}))
+            .reply(&rest_search_api_handler)
+            .await;
+        assert_eq!(resp.status(), 400);
+        let resp_json: serde_json::Value = serde_json::from_slice(resp.body())?;
+        assert_eq!(resp_json["code"], "INVALID_ARGUMENT");
+        let error_message = resp_json["message"].as_str().unwrap();
+        assert!(error_message.starts_with("invalid field `maxHits`: "));
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_json_body() {
+        let error =
+            parse_json_body::<SearchRequestBody>(br#"{"query": "*", "tags": [1]}"#).unwrap_err();
+        assert!(error.starts_with("invalid field `tags[0]`: "));
+        let error = parse_json_body::<SearchRequestBody>(br#"{"query": "*"} {}"#).unwrap_err();
+        assert!(error.starts_with("invalid request body: "));
+        let error = parse_json_body::<SearchRequestBody>(b"[]").unwrap_err();
+        assert!(error.starts_with("invalid request body: "));
+    }
+
     #[tokio::test]
     async fn test_rest_search_api_route_invalid_key() -> anyhow::Result<()> {
         let mock_search_service = MockSearchService::new();
-        let rest_search_api_handler =
-            super::search_handler(Arc::new(mock_search_service)).recover(recover_fn);
+        let rest_search_api_handler = super::search_handler(
+            Arc::new(mock_search_service),
+            Arc::new(Authenticator::disabled()),
+        )
+        .recover(recover_fn);
         let resp = warp::test::request()
             .path("/api/v1/quickwit-demo-index/search?query=*&endUnixTimestamp=1450720000")
             .reply(&rest_search_api_handler)
@@ -437,12 +886,65 @@ mod tests {
         assert_eq!(resp.status(), 400);
         let resp_json: serde_json::Value = serde_json::from_slice(resp.body())?;
         let exp_resp_json = serde_json::json!({
-            "error": "InvalidArgument: failed with reason: unknown field `endUnixTimestamp`, expected one of `query`, `searchField`, `startTimestamp`, `endTimestamp`, `maxHits`, `startOffset`, `format`, `tags`."
+            "code": "INVALID_ARGUMENT",
+            "message": "failed with reason: unknown field `endUnixTimestamp`, expected one of `query`, `searchField`, `startTimestamp`, `endTimestamp`, `maxHits`, `startOffset`, `format`, `tags`, `allowPartialResults`, `timeoutMillis`",
+            "retryable": false,
         });
         assert_eq!(resp_json, exp_resp_json);
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_rest_search_api_structured_errors() -> anyhow::Result<()> {
+        let mut mock_search_service = MockSearchService::new();
+        mock_search_service
+            .expect_root_search()
+            .with(predicate::function(
+                |request: &quickwit_proto::SearchRequest| request.index_id == "unknown-index",
+            ))
+            .returning(|request| {
+                Err(SearchError::IndexDoesNotExist {
+                    index_id: request.index_id,
+                })
+            });
+        mock_search_service
+            .expect_root_search()
+            .returning(|_| Err(SearchError::Timeout("Deadline exceeded.".to_string())));
+        let rest_search_api_handler = super::search_handler(
+            Arc::new(mock_search_service),
+            Arc::new(Authenticator::disabled()),
+        )
+        .recover(recover_fn);
+
+        let resp = warp::test::request()
+            .path("/api/v1/unknown-index/search?query=*")
+            .reply(&rest_search_api_handler)
+            .await;
+        assert_eq!(resp.status(), 404);
+        let resp_json: serde_json::Value = serde_json::from_slice(resp.body())?;
+        let expected_resp_json = json!({
+            "code": "INDEX_DOES_NOT_EXIST",
+            "message": "Index `unknown-index` does not exist.",
+            "details": {"index_id": "unknown-index"},
+            "retryable": false,
+        });
+        assert_eq!(resp_json, expected_resp_json);
+
+        let resp = warp::test::request()
+            .path("/api/v1/quickwit-demo-index/search?query=*")
+            .reply(&rest_search_api_handler)
+            .await;
+        assert_eq!(resp.status(), 504);
+        let resp_json: serde_json::Value = serde_json::from_slice(resp.body())?;
+        let expected_resp_json = json!({
+            "code": "TIMEOUT",
+            "message": "Deadline exceeded.",
+            "retryable": true,
+        });
+        assert_eq!(resp_json, expected_resp_json);
+        Ok(())
+    }
+
     #[tokio::test]
     async fn test_rest_search_api_route_serialize_with_results() -> anyhow::Result<()> {
         let mut mock_search_service = MockSearchService::new();
@@ -452,10 +954,14 @@ mod tests {
                 num_hits: 10,
                 elapsed_time_micros: 16,
                 errors: vec![],
+                failed_splits: vec![],
             })
         });
-        let rest_search_api_handler =
-            super::search_handler(Arc::new(mock_search_service)).recover(recover_fn);
+        let rest_search_api_handler = super::search_handler(
+            Arc::new(mock_search_service),
+            Arc::new(Authenticator::disabled()),
+        )
+        .recover(recover_fn);
         let resp = warp::test::request()
             .path("/api/v1/quickwit-demo-index/search?query=*")
             .reply(&rest_search_api_handler)
@@ -471,6 +977,49 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_rest_search_api_with_partial_results() -> anyhow::Result<()> {
+        let mut mock_search_service = MockSearchService::new();
+        mock_search_service
+            .expect_root_search()
+            .with(predicate::function(
+                |search_request: &quickwit_proto::SearchRequest| {
+                    search_request.allow_partial_results
+                },
+            ))
+            .returning(|_| {
+                Ok(quickwit_proto::SearchResponse {
+                    hits: Vec::new(),
+                    num_hits: 3,
+                    elapsed_time_micros: 16,
+                    errors: vec![],
+                    failed_splits: vec![quickwit_proto::SplitSearchError {
+                        error: "Timeout".to_string(),
+                        split_id: "split2".to_string(),
+                        retryable_error: true,
+                    }],
+                })
+            });
+        let rest_search_api_handler = super::search_handler(
+            Arc::new(mock_search_service),
+            Arc::new(Authenticator::disabled()),
+        )
+        .recover(recover_fn);
+        let resp = warp::test::request()
+            .path("/api/v1/quickwit-demo-index/search?query=*&allowPartialResults=true")
+            .reply(&rest_search_api_handler)
+            .await;
+        assert_eq!(resp.status(), 200);
+        let resp_json: serde_json::Value = serde_json::from_slice(resp.body())?;
+        let expected_response_json = serde_json::json!({
+            "numHits": 3,
+            "hits": [],
+            "failedSplits": [{"splitId": "split2", "error": "Timeout"}],
+        });
+        assert_json_include!(actual: resp_json, expected: expected_response_json);
+        Ok(())
+    }
+
     #[tokio::test]
     async fn test_rest_search_api_start_offset_and_num_hits_parameter() -> anyhow::Result<()> {
         let mut mock_search_service = MockSearchService::new();
@@ -482,8 +1031,11 @@ mod tests {
                 },
             ))
             .returning(|_| Ok(Default::default()));
-        let rest_search_api_handler =
-            super::search_handler(Arc::new(mock_search_service)).recover(recover_fn);
+        let rest_search_api_handler = super::search_handler(
+            Arc::new(mock_search_service),
+            Arc::new(Authenticator::disabled()),
+        )
+        .recover(recover_fn);
         assert_eq!(
             warp::test::request()
                 .path("/api/v1/quickwit-demo-index/search?query=*&startOffset=5&maxHits=30")
@@ -503,8 +1055,11 @@ mod tests {
                 index_id: "not-found-index".to_string(),
             })
         });
-        let rest_search_api_handler =
-            super::search_handler(Arc::new(mock_search_service)).recover(recover_fn);
+        let rest_search_api_handler = super::search_handler(
+            Arc::new(mock_search_service),
+            Arc::new(Authenticator::disabled()),
+        )
+        .recover(recover_fn);
         assert_eq!(
             warp::test::request()
                 .path("/api/v1/index-does-not-exist/search?query=myfield:test")
@@ -516,14 +1071,77 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_rest_search_api_with_authentication() -> anyhow::Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let auth_file_path = temp_dir.path().join("auth.json");
+        std::fs::write(
+            &auth_file_path,
+            r#"{"users": [{"name": "grafana", "token_sha256": "5a4806aaf8e14a7c4a89f70d1ef7b2324ddae2b85c417a9270d87b4db806b6a0", "permissions": {"logs-*": "read"}}]}"#,
+        )?;
+        let authenticator = Arc::new(Authenticator::open(&auth_file_path)?);
+        let mut mock_search_service = MockSearchService::new();
+        mock_search_service
+            .expect_root_search()
+            .returning(|_| Ok(Default::default()));
+        let rest_search_api_handler =
+            super::search_handler(Arc::new(mock_search_service), authenticator).recover(recover_fn);
+
+        let resp = warp::test::request()
+            .path("/api/v1/logs-nginx/search?query=*")
+            .reply(&rest_search_api_handler)
+            .await;
+        assert_eq!(resp.status(), 401);
+        assert!(resp.headers().contains_key(WWW_AUTHENTICATE));
+
+        // Requests are authenticated before their query string or body is parsed.
+        let resp = warp::test::request()
+            .path("/api/v1/logs-nginx/search?unknownField=*")
+            .reply(&rest_search_api_handler)
+            .await;
+        assert_eq!(resp.status(), 401);
+        let resp = warp::test::request()
+            .method("POST")
+            .path("/api/v1/logs-nginx/search")
+            .body("not json")
+            .reply(&rest_search_api_handler)
+            .await;
+        assert_eq!(resp.status(), 401);
+
+        let resp = warp::test::request()
+            .path("/api/v1/logs-nginx/search?query=*")
+            .header("authorization", "Bearer wrong-token")
+            .reply(&rest_search_api_handler)
+            .await;
+        assert_eq!(resp.status(), 401);
+
+        let resp = warp::test::request()
+            .path("/api/v1/wikipedia/search?query=*")
+            .header("authorization", "Bearer grafana-token")
+            .reply(&rest_search_api_handler)
+            .await;
+        assert_eq!(resp.status(), 403);
+
+        let resp = warp::test::request()
+            .path("/api/v1/logs-nginx/search?query=*")
+            .header("authorization", "Bearer grafana-token")
+            .reply(&rest_search_api_handler)
+            .await;
+        assert_eq!(resp.status(), 200);
+        Ok(())
+    }
+
     #[tokio::test]
     async fn test_rest_search_api_with_wrong_fieldname() -> anyhow::Result<()> {
         let mut mock_search_service = MockSearchService::new();
         mock_search_service
             .expect_root_search()
             .returning(|_| Err(SearchError::InternalError("ty".to_string())));
-        let rest_search_api_handler =
-            super::search_handler(Arc::new(mock_search_service)).recover(recover_fn);
+        let rest_search_api_handler = super::search_handler(
+            Arc::new(mock_search_service),
+            Arc::new(Authenticator::disabled()),
+        )
+        .recover(recover_fn);
         assert_eq!(
             warp::test::request()
                 .path("/api/v1/index-does-not-exist/search?query=myfield:test")
@@ -541,8 +1159,11 @@ mod tests {
         mock_search_service
             .expect_root_search()
             .returning(|_| Err(SearchError::InvalidQuery("invalid query".to_string())));
-        let rest_search_api_handler =
-            super::search_handler(Arc::new(mock_search_service)).recover(recover_fn);
+        let rest_search_api_handler = super::search_handler(
+            Arc::new(mock_search_service),
+            Arc::new(Authenticator::disabled()),
+        )
+        .recover(recover_fn);
         assert_eq!(
             warp::test::request()
                 .path("/api/v1/my-index/search?query=myfield:test")
@@ -557,11 +1178,18 @@ mod tests {
     #[tokio::test]
     async fn test_rest_search_stream_api() -> anyhow::Result<()> {
         let mut mock_search_service = MockSearchService::new();
+        let (data_sender, data_receiver) = tokio::sync::mpsc::channel(2);
+        data_sender.send(Ok(Bytes::from("first row\n"))).await?;
+        data_sender.send(Ok(Bytes::from("second row"))).await?;
+        drop(data_sender);
         mock_search_service
             .expect_root_search_stream()
-            .return_once(|_| Ok(vec![Bytes::from("first row\n"), Bytes::from("second row")]));
-        let rest_search_stream_api_handler =
-            super::search_stream_handler(Arc::new(mock_search_service)).recover(recover_fn);
+            .return_once(|_| Ok(ReceiverStream::new(data_receiver)));
+        let rest_search_stream_api_handler = super::search_stream_handler(
+            Arc::new(mock_search_service),
+            Arc::new(Authenticator::disabled()),
+        )
+        .recover(recover_fn);
         let response = warp::test::request()
             .path(
                 "/api/v1/my-index/search/stream?query=obama&fastField=external_id&outputFormat=csv",
@@ -574,13 +1202,54 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_rest_search_stream_api_error_before_first_chunk() -> anyhow::Result<()> {
+        let mut mock_search_service = MockSearchService::new();
+        let (data_sender, data_receiver) = tokio::sync::mpsc::channel(1);
+        data_sender
+            .send(Err(SearchError::InternalError("leaf failure".to_string())))
+            .await?;
+        drop(data_sender);
+        mock_search_service
+            .expect_root_search_stream()
+            .return_once(|_| Ok(ReceiverStream::new(data_receiver)));
+        let rest_search_stream_api_handler = super::search_stream_handler(
+            Arc::new(mock_search_service),
+            Arc::new(Authenticator::disabled()),
+        )
+        .recover(recover_fn);
+        let response = warp::test::request()
+            .path(
+                "/api/v1/my-index/search/stream?query=obama&fastField=external_id&outputFormat=csv",
+            )
+            .reply(&rest_search_stream_api_handler)
+            .await;
+        assert_eq!(response.status(), 500);
+        assert_eq!(
+            response.headers().get(CONTENT_TYPE).unwrap(),
+            "application/json"
+        );
+        let response_json: serde_json::Value = serde_json::from_slice(response.body())?;
+        assert_eq!(
+            response_json,
+            json!({
+                "code": "INTERNAL",
+                "message": "leaf failure",
+                "retryable": false,
+            })
+        );
+        Ok(())
+    }
+
     #[tokio::test]
     async fn test_rest_search_stream_api_csv() {
-        let (index, req) = warp::test::request()
+        let (index, req, _principal) = warp::test::request()
             .path(
                 "/api/v1/my-index/search/stream?query=obama&fastField=external_id&outputFormat=csv",
             )
-            .filter(&super::search_stream_filter())
+            .filter(&super::search_stream_filter(Arc::new(
+                Authenticator::disabled(),
+            )))
             .await
             .unwrap();
         assert_eq!(&index, "my-index");
@@ -591,21 +1260,105 @@ mod tests {
                 search_fields: None,
                 start_timestamp: None,
                 end_timestamp: None,
-                fast_field: "external_id".to_string(),
-                output_format: OutputFormat::Csv,
-                tags: None
+                fast_fields: Some(vec!["external_id".to_string()]),
+                output_format: Some(OutputFormat::Csv),
+                tags: None,
+                csv_header: false,
+                sort_order: None,
+                limit: None,
             }
         );
     }
 
+    #[tokio::test]
+    async fn test_rest_search_stream_api_rejects_allow_partial_results() {
+        // Partial results are only supported by regular searches.
+        let result = warp::test::request()
+            .path(
+                "/api/v1/my-index/search/stream?query=obama&fastField=external_id&\
+                 allowPartialResults=true",
+            )
+            .filter(&super::search_stream_filter(Arc::new(
+                Authenticator::disabled(),
+            )))
+            .await;
+        assert!(result.is_err());
+    }
+
     #[tokio::test]
     async fn test_rest_search_stream_api_click_house_row_binary() {
-        let (index, req) = warp::test::request()
+        let (index, req, _principal) = warp::test::request()
             .path(
                 "/api/v1/my-index/search/stream?query=obama&fastField=external_id&\
                  outputFormat=clickHouseRowBinary&tags=lang:english",
             )
-            .filter(&super::search_stream_filter())
+            .filter(&super::search_stream_filter(Arc::new(
+                Authenticator::disabled(),
+            )))
+            .await
+            .unwrap();
+        assert_eq!(&index, "my-index");
+        assert_eq!(
+            &req,
+            &super::SearchStreamRequestQueryString {
+                query: "obama".to_string(),
+                search_fields: None,
+                start_timestamp: None,
+                end_timestamp: None,
+                fast_fields: Some(vec!["external_id".to_string()]),
+                output_format: Some(OutputFormat::ClickHouseRowBinary),
+                tags: Some(vec!["lang:english".to_string()]),
+                csv_header: false,
+                sort_order: None,
+                limit: None,
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_rest_search_stream_api_multiple_fast_fields_with_csv_header() {
+        let (index, req, _principal) = warp::test::request()
+            .path(
+                "/api/v1/my-index/search/stream?query=obama&fastField=timestamp,user_id,latency&\
+                 outputFormat=csv&csvHeader=true",
+            )
+            .filter(&super::search_stream_filter(Arc::new(
+                Authenticator::disabled(),
+            )))
+            .await
+            .unwrap();
+        assert_eq!(&index, "my-index");
+        assert_eq!(
+            &req,
+            &super::SearchStreamRequestQueryString {
+                query: "obama".to_string(),
+                search_fields: None,
+                start_timestamp: None,
+                end_timestamp: None,
+                fast_fields: Some(vec![
+                    "timestamp".to_string(),
+                    "user_id".to_string(),
+                    "latency".to_string()
+                ]),
+                output_format: Some(OutputFormat::Csv),
+                tags: None,
+                csv_header: true,
+                sort_order: None,
+                limit: None,
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_rest_search_stream_api_sorted_with_limit() {
+        let (index, req, _principal) = warp::test::request()
+            .path(
+                "/api/v1/my-index/search/stream?query=obama&fastField=timestamp&sortOrder=desc&\
+                 limit=1000000",
+            )
+            .filter(&super::search_stream_filter(Arc::new(
+                Authenticator::disabled(),
+            )))
             .await
             .unwrap();
         assert_eq!(&index, "my-index");
@@ -616,9 +1369,47 @@ mod tests {
                 search_fields: None,
                 start_timestamp: None,
                 end_timestamp: None,
-                fast_field: "external_id".to_string(),
-                output_format: OutputFormat::ClickHouseRowBinary,
-                tags: Some(vec!["lang:english".to_string()])
+                fast_fields: Some(vec!["timestamp".to_string()]),
+                output_format: None,
+                tags: None,
+                csv_header: false,
+                sort_order: Some(SortOrder::Desc),
+                limit: Some(1_000_000),
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_rest_search_stream_api_post() {
+        let (index, req, _principal) = warp::test::request()
+            .method("POST")
+            .path("/api/v1/my-index/search/stream")
+            .json(&json!({
+                "query": "obama",
+                "fastFields": ["timestamp", "latency"],
+                "outputFormat": "arrowIpcStream",
+                "sortOrder": "asc",
+                "limit": 10,
+            }))
+            .filter(&super::search_stream_post_filter(Arc::new(
+                Authenticator::disabled(),
+            )))
+            .await
+            .unwrap();
+        assert_eq!(&index, "my-index");
+        assert_eq!(
+            &req,
+            &super::SearchStreamRequestQueryString {
+                query: "obama".to_string(),
+                search_fields: Some(Vec::new()),
+                start_timestamp: None,
+                end_timestamp: None,
+                fast_fields: Some(vec!["timestamp".to_string(), "latency".to_string()]),
+                output_format: Some(OutputFormat::ArrowIpcStream),
+                tags: Some(Vec::new()),
+                csv_header: false,
+                sort_order: Some(SortOrder::Asc),
+                limit: Some(10),
             }
         );
     }
@@ -630,14 +1421,61 @@ mod tests {
                 "/api/v1/my-index/search/stream?query=obama&fastField=external_id&\
                  outputFormat=click_house_row_binary",
             )
-            .filter(&super::search_stream_filter())
+            .filter(&super::search_stream_filter(Arc::new(
+                Authenticator::disabled(),
+            )))
             .await
             .unwrap_err();
         let parse_error = rejection.find::<serde_qs::Error>().unwrap();
         assert_eq!(
             parse_error.to_string(),
-            "failed with reason: unknown variant `click_house_row_binary`, expected `csv` or \
-             `clickHouseRowBinary`"
+            "failed with reason: unknown variant `click_house_row_binary`, expected one of `csv`, \
+             `clickHouseRowBinary`, `arrowIpcStream`, `parquet`"
         );
     }
+
+    #[test]
+    fn test_negotiate_output_format() {
+        assert_eq!(negotiate_output_format(None), OutputFormat::Csv);
+        assert_eq!(negotiate_output_format(Some("*/*")), OutputFormat::Csv);
+        assert_eq!(
+            negotiate_output_format(Some("application/vnd.apache.arrow.stream")),
+            OutputFormat::ArrowIpcStream
+        );
+        assert_eq!(
+            negotiate_output_format(Some(
+                "application/json, application/vnd.apache.parquet;q=0.9, text/csv;q=0.5"
+            )),
+            OutputFormat::Parquet
+        );
+    }
+
+    #[tokio::test]
+    async fn test_rest_search_stream_api_accept_header() -> anyhow::Result<()> {
+        let mut mock_search_service = MockSearchService::new();
+        let (data_sender, data_receiver) = tokio::sync::mpsc::channel(1);
+        data_sender.send(Ok(Bytes::from("arrow"))).await?;
+        drop(data_sender);
+        mock_search_service
+            .expect_root_search_stream()
+            .withf(|request| request.output_format == OutputFormat::ArrowIpcStream as i32)
+            .return_once(|_| Ok(ReceiverStream::new(data_receiver)));
+        let rest_search_stream_api_handler = super::search_stream_handler(
+            Arc::new(mock_search_service),
+            Arc::new(Authenticator::disabled()),
+        )
+        .recover(recover_fn);
+        let response = warp::test::request()
+            .path("/api/v1/my-index/search/stream?query=obama&fastField=external_id")
+            .header("accept", "application/vnd.apache.arrow.stream")
+            .reply(&rest_search_stream_api_handler)
+            .await;
+        assert_eq!(response.status(), 200);
+        assert_eq!(
+            response.headers().get(CONTENT_TYPE).unwrap(),
+            "application/vnd.apache.arrow.stream"
+        );
+        assert_eq!(response.body(), "arrow");
+        Ok(())
+    }
 }
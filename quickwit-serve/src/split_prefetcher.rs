@@ -0,0 +1,238 @@
+// Copyright (C) 2021 Quickwit, Inc.
+//
+// Quickwit is offered under the AGPL v3.0 and as commercial software.
+// For commercial licensing, contact us at hello@quickwit.io.
+//
+// AGPL:
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+use quickwit_common::split_file;
+use quickwit_metastore::{Metastore, SplitMetadataAndFooterOffsets, SplitState};
+use quickwit_storage::{LocalDiskCache, StorageUriResolver};
+use tracing::{debug, error, warn};
+
+/// Delay between two passes of the prefetcher.
+const PREFETCH_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Returns the ids of the `num_splits` most recent splits.
+///
+/// Splits are ordered by the end of their time range, or by their
+/// update timestamp when they have no time range.
+fn most_recent_split_ids(
+    mut splits: Vec<SplitMetadataAndFooterOffsets>,
+    num_splits: usize,
+) -> Vec<String> {
+    splits.sort_by_key(|split| {
+        let split_metadata = &split.split_metadata;
+        let recency = split_metadata
+            .time_range
+            .as_ref()
+            .map(|time_range| *time_range.end())
+            .unwrap_or(split_metadata.update_timestamp);
+        std::cmp::Reverse(recency)
+    });
+    splits
+        .into_iter()
+        .take(num_splits)
+        .map(|split| split.split_metadata.split_id)
+        .collect()
+}
+
+/// Prefetches the `num_splits` most recent splits of the index, as long as they fit in
+/// `remaining_num_bytes`, which is decremented by the length of the prefetched splits.
+///
+/// The prefetcher stops at the first split that does not fit: prefetching it would
+/// evict splits prefetched earlier in the pass, which would be downloaded again on the
+/// next pass.
+async fn prefetch_index(
+    index_id: &str,
+    num_splits: usize,
+    metastore: &dyn Metastore,
+    storage_resolver: &StorageUriResolver,
+    local_disk_cache: &LocalDiskCache,
+    remaining_num_bytes: &mut u64,
+) -> anyhow::Result<()> {
+    let index_metadata = metastore.index_metadata(index_id).await?;
+    let storage = storage_resolver.resolve(&index_metadata.index_uri)?;
+    let splits = metastore
+        .list_splits(index_id, SplitState::Published, None, &[])
+        .await?;
+    for split_id in most_recent_split_ids(splits, num_splits) {
+        let split_path = split_file(&split_id);
+        let split_path = Path::new(&split_path);
+        let split_num_bytes =
+            if let Some(split_num_bytes) = local_disk_cache.prefetched_num_bytes(split_path) {
+                split_num_bytes
+            } else {
+                storage.file_num_bytes(split_path).await?
+            };
+        if split_num_bytes > *remaining_num_bytes {
+            warn!(
+                index_id=%index_id,
+                split_id=%split_id,
+                "The local disk cache is full, skipping the remaining splits."
+            );
+            return Ok(());
+        }
+        *remaining_num_bytes -= split_num_bytes;
+        debug!(index_id=%index_id, split_id=%split_id, "Prefetching split.");
+        local_disk_cache.prefetch(&*storage, split_path).await?;
+    }
+    Ok(())
+}
+
+/// Spawns a task that periodically downloads the most recent splits of the given indexes
+/// into the local disk cache.
+///
+/// The prefetched splits never exceed the capacity of the cache: the indexes are
+/// prefetched in order, until the cache is full.
+pub fn spawn_split_prefetcher(
+    index_ids: Vec<String>,
+    num_splits: usize,
+    metastore: Arc<dyn Metastore>,
+    storage_resolver: StorageUriResolver,
+    local_disk_cache: Arc<LocalDiskCache>,
+) {
+    tokio::spawn(async move {
+        loop {
+            let mut remaining_num_bytes = local_disk_cache.capacity_in_bytes();
+            for index_id in &index_ids {
+                if let Err(err) = prefetch_index(
+                    index_id,
+                    num_splits,
+                    &*metastore,
+                    &storage_resolver,
+                    &local_disk_cache,
+                    &mut remaining_num_bytes,
+                )
+                .await
+                {
+                    error!(index_id=%index_id, err=?err, "Failed to prefetch splits.");
+                }
+            }
+            tokio::time::sleep(PREFETCH_INTERVAL).await;
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ops::Range;
+
+    use quickwit_index_config::WikipediaIndexConfig;
+    use quickwit_metastore::checkpoint::Checkpoint;
+    use quickwit_metastore::{IndexMetadata, MockMetastore, SplitMetadata};
+    use quickwit_storage::{Cache, PutPayload};
+
+    use super::*;
+
+    fn split(
+        split_id: &str,
+        time_range_end: Option<i64>,
+        update_timestamp: i64,
+    ) -> SplitMetadataAndFooterOffsets {
+        SplitMetadataAndFooterOffsets {
+            split_metadata: SplitMetadata {
+                split_id: split_id.to_string(),
+                time_range: time_range_end.map(|end| 0..=end),
+                update_timestamp,
+                ..Default::default()
+            },
+            footer_offsets: 0..0,
+        }
+    }
+
+    #[test]
+    fn test_most_recent_split_ids() {
+        let splits = vec![
+            split("split_1", Some(10), 100),
+            split("split_2", Some(30), 0),
+            split("split_3", None, 20),
+            split("split_4", Some(5), 200),
+        ];
+        assert_eq!(
+            most_recent_split_ids(splits, 2),
+            vec!["split_2".to_string(), "split_3".to_string()]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_prefetch_index_stops_when_cache_is_full() -> anyhow::Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let local_disk_cache = LocalDiskCache::open(temp_dir.path(), 10)?;
+        let storage_resolver = StorageUriResolver::for_test();
+        let storage = storage_resolver.resolve("ram:///indexes/test-idx")?;
+        for (split_id, content) in [("split_1", "abcd"), ("split_2", "efgh"), ("split_3", "ijk")] {
+            storage
+                .put(
+                    Path::new(&split_file(split_id)),
+                    PutPayload::from(content.as_bytes()),
+                )
+                .await?;
+        }
+        let mut metastore = MockMetastore::new();
+        metastore
+            .expect_index_metadata()
+            .returning(|_index_id: &str| {
+                Ok(IndexMetadata {
+                    index_id: "test-idx".to_string(),
+                    index_uri: "ram:///indexes/test-idx".to_string(),
+                    index_config: Arc::new(WikipediaIndexConfig::new()),
+                    checkpoint: Checkpoint::default(),
+                    encryption_key_id: None,
+                })
+            });
+        metastore.expect_list_splits().returning(
+            |_index_id: &str,
+             _split_state: SplitState,
+             _time_range: Option<Range<i64>>,
+             _tags: &[String]| {
+                Ok(vec![
+                    split("split_1", Some(30), 0),
+                    split("split_2", Some(20), 0),
+                    split("split_3", Some(10), 0),
+                ])
+            },
+        );
+        for _ in 0..2 {
+            let mut remaining_num_bytes = local_disk_cache.capacity_in_bytes();
+            prefetch_index(
+                "test-idx",
+                3,
+                &metastore,
+                &storage_resolver,
+                &local_disk_cache,
+                &mut remaining_num_bytes,
+            )
+            .await?;
+            assert_eq!(remaining_num_bytes, 2);
+        }
+        // The third split would have evicted the first one.
+        assert!(local_disk_cache
+            .prefetched_num_bytes(Path::new("split_1.split"))
+            .is_some());
+        assert!(local_disk_cache
+            .prefetched_num_bytes(Path::new("split_2.split"))
+            .is_some());
+        assert!(local_disk_cache
+            .prefetched_num_bytes(Path::new("split_3.split"))
+            .is_none());
+        assert_eq!(local_disk_cache.stats().num_evictions, 0);
+        Ok(())
+    }
+}
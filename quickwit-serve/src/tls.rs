@@ -0,0 +1,85 @@
+// Copyright (C) 2021 Quickwit, Inc.
+//
+// Quickwit is offered under the AGPL v3.0 and as commercial software.
+// For commercial licensing, contact us at hello@quickwit.io.
+//
+// AGPL:
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use tonic::transport::{Certificate, ClientTlsConfig, Identity, ServerTlsConfig};
+
+/// TLS settings of a node, shared by its REST and gRPC servers and by the gRPC clients
+/// connecting to the other nodes.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TlsConfig {
+    /// PEM file holding the certificate chain of the node.
+    pub cert_path: PathBuf,
+    /// PEM file holding the private key of the node.
+    pub key_path: PathBuf,
+    /// PEM file holding the certificate of the authority that issued the certificates of
+    /// the nodes.
+    pub ca_cert_path: PathBuf,
+    /// Name the certificates of the nodes are issued for. Nodes are addressed by IP, so
+    /// their certificates are checked against this name instead.
+    pub domain_name: String,
+    /// If set, nodes authenticate each other with their certificates (mTLS), so that only
+    /// the members of the cluster can send requests to the internal gRPC services.
+    pub client_auth: bool,
+}
+
+impl TlsConfig {
+    fn identity(&self) -> anyhow::Result<Identity> {
+        let cert = read_pem(&self.cert_path)?;
+        let key = read_pem(&self.key_path)?;
+        Ok(Identity::from_pem(cert, key))
+    }
+
+    fn ca_certificate(&self) -> anyhow::Result<Certificate> {
+        let ca_cert = read_pem(&self.ca_cert_path)?;
+        Ok(Certificate::from_pem(ca_cert))
+    }
+
+    /// Builds the TLS config of the gRPC server.
+    pub fn server_tls_config(&self) -> anyhow::Result<ServerTlsConfig> {
+        let mut server_tls_config = ServerTlsConfig::new().identity(self.identity()?);
+        if self.client_auth {
+            server_tls_config = server_tls_config.client_ca_root(self.ca_certificate()?);
+        }
+        Ok(server_tls_config)
+    }
+
+    /// Builds the TLS config of the public gRPC server. Its clients are not members of the
+    /// cluster, so they never have to present a certificate.
+    pub fn public_server_tls_config(&self) -> anyhow::Result<ServerTlsConfig> {
+        Ok(ServerTlsConfig::new().identity(self.identity()?))
+    }
+
+    /// Builds the TLS config of the gRPC clients connecting to the other nodes.
+    pub fn client_tls_config(&self) -> anyhow::Result<ClientTlsConfig> {
+        let mut client_tls_config = ClientTlsConfig::new()
+            .ca_certificate(self.ca_certificate()?)
+            .domain_name(self.domain_name.clone());
+        if self.client_auth {
+            client_tls_config = client_tls_config.identity(self.identity()?);
+        }
+        Ok(client_tls_config)
+    }
+}
+
+fn read_pem(path: &Path) -> anyhow::Result<Vec<u8>> {
+    std::fs::read(path).with_context(|| format!("Failed to read PEM file `{}`.", path.display()))
+}
@@ -173,6 +173,10 @@ impl Storage for BundleStorage {
         Ok(file_range.len() as u64)
     }
 
+    async fn list_directories(&self, path: &Path) -> StorageResult<Vec<String>> {
+        Err(unsupported_operation(path))
+    }
+
     fn uri(&self) -> String {
         self.storage.uri()
     }
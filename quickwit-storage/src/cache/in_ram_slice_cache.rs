@@ -25,6 +25,7 @@ use std::sync::Mutex;
 use bytes::Bytes;
 
 use super::memory_sized_cache::MemorySizedCache;
+use crate::CacheStats;
 
 #[derive(Hash, Debug, Clone, PartialEq, Eq)]
 struct SliceAddress {
@@ -70,6 +71,11 @@ impl SliceCache {
         let slice_addr = SliceAddress { path, byte_range };
         self.inner.lock().unwrap().put(slice_addr, bytes);
     }
+
+    /// Returns the hit, miss and eviction counters of the cache, and its current size.
+    pub fn stats(&self) -> CacheStats {
+        self.inner.lock().unwrap().stats()
+    }
 }
 
 #[cfg(test)]
@@ -0,0 +1,556 @@
+// Copyright (C) 2021 Quickwit, Inc.
+//
+// Quickwit is offered under the AGPL v3.0 and as commercial software.
+// For commercial licensing, contact us at hello@quickwit.io.
+//
+// AGPL:
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use std::io::{self, SeekFrom};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use lru::LruCache;
+use rand::distributions::Alphanumeric;
+use rand::Rng;
+use tokio::fs;
+use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
+use tracing::{debug, error, warn};
+
+use crate::{Cache, CacheStats, Storage, StorageResult};
+
+/// Extension of the files holding a slice of a cached file.
+const SLICE_FILE_EXTENSION: &str = "slice";
+/// Extension of the files holding an entire cached file.
+const FULL_FILE_EXTENSION: &str = "file";
+/// Extension of the files being written. They are renamed once complete, so any
+/// such file found when opening the cache is a leftover of a crash.
+const TEMP_FILE_EXTENSION: &str = "tmp";
+
+fn slice_entry_name(path: &Path, byte_range: &Range<usize>) -> String {
+    let key = format!(
+        "{}:{}..{}",
+        path.to_string_lossy(),
+        byte_range.start,
+        byte_range.end
+    );
+    format!(
+        "{:x}.{}",
+        md5::compute(key.as_bytes()),
+        SLICE_FILE_EXTENSION
+    )
+}
+
+fn full_file_entry_name(path: &Path) -> String {
+    let key = path.to_string_lossy();
+    format!("{:x}.{}", md5::compute(key.as_bytes()), FULL_FILE_EXTENSION)
+}
+
+fn is_cache_entry(file_name: &str) -> bool {
+    file_name.ends_with(SLICE_FILE_EXTENSION) || file_name.ends_with(FULL_FILE_EXTENSION)
+}
+
+struct NeedMutLocalDiskCache {
+    // Maps the name of an entry file to its length.
+    lru_cache: LruCache<String, u64>,
+    num_bytes: u64,
+    capacity_in_bytes: u64,
+    num_hits: u64,
+    num_misses: u64,
+    num_evictions: u64,
+}
+
+impl NeedMutLocalDiskCache {
+    fn contains(&mut self, entry_name: &str) -> bool {
+        // `get` also marks the entry as recently used.
+        self.lru_cache.get(&entry_name.to_string()).is_some()
+    }
+
+    fn remove(&mut self, entry_name: &str) {
+        if let Some(num_bytes) = self.lru_cache.pop(&entry_name.to_string()) {
+            self.num_bytes -= num_bytes;
+        }
+    }
+
+    /// Registers a new entry, and returns the names of the entries that
+    /// were evicted to make room for it.
+    fn insert(&mut self, entry_name: String, num_bytes: u64) -> Vec<String> {
+        self.remove(&entry_name);
+        let mut evicted_entries = Vec::new();
+        while self.num_bytes + num_bytes > self.capacity_in_bytes {
+            if let Some((evicted_entry_name, evicted_num_bytes)) = self.lru_cache.pop_lru() {
+                self.num_bytes -= evicted_num_bytes;
+                self.num_evictions += 1;
+                evicted_entries.push(evicted_entry_name);
+            } else {
+                error!(
+                    "Logical error. Even after removing all of the items in the cache the \
+                     capacity is insufficient. This case is guarded against and should never \
+                     happen."
+                );
+                return evicted_entries;
+            }
+        }
+        self.num_bytes += num_bytes;
+        self.lru_cache.put(entry_name, num_bytes);
+        evicted_entries
+    }
+}
+
+/// A cache storing its entries as files in a local directory.
+///
+/// Contrary to the in-memory caches, its content survives restarts: the
+/// entries present in the directory are loaded back when the cache is opened,
+/// in their modification time order.
+///
+/// Entries are first written in a temporary file which is then atomically renamed,
+/// so that a crash never leaves a truncated entry behind.
+///
+/// Entries are either slices of a file, or entire files (see
+/// [`LocalDiskCache::prefetch`]). Slices of a file are served from the entire
+/// file when it is available.
+pub struct LocalDiskCache {
+    root_path: PathBuf,
+    inner: Mutex<NeedMutLocalDiskCache>,
+}
+
+impl LocalDiskCache {
+    /// Opens (or creates) a cache in the `root_path` directory, with a capacity of
+    /// `capacity_in_bytes`.
+    ///
+    /// Leftover temporary files are removed, and the least recently modified entries are
+    /// evicted if the directory exceeds the capacity.
+    pub fn open(root_path: &Path, capacity_in_bytes: u64) -> io::Result<LocalDiskCache> {
+        std::fs::create_dir_all(root_path)?;
+        let mut entries: Vec<(SystemTime, String, u64)> = Vec::new();
+        for dir_entry_res in std::fs::read_dir(root_path)? {
+            let dir_entry = dir_entry_res?;
+            let file_name = dir_entry.file_name().to_string_lossy().to_string();
+            let metadata = dir_entry.metadata()?;
+            if !metadata.is_file() {
+                continue;
+            }
+            if file_name.ends_with(TEMP_FILE_EXTENSION) {
+                debug!(file_name=%file_name, "Removing leftover temporary file.");
+                std::fs::remove_file(dir_entry.path())?;
+                continue;
+            }
+            if !is_cache_entry(&file_name) {
+                continue;
+            }
+            let modified = metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+            entries.push((modified, file_name, metadata.len()));
+        }
+        entries.sort();
+        let mut inner = NeedMutLocalDiskCache {
+            lru_cache: LruCache::unbounded(),
+            num_bytes: 0,
+            capacity_in_bytes,
+            num_hits: 0,
+            num_misses: 0,
+            num_evictions: 0,
+        };
+        for (_, entry_name, num_bytes) in entries {
+            if num_bytes > capacity_in_bytes {
+                std::fs::remove_file(root_path.join(&entry_name))?;
+                continue;
+            }
+            for evicted_entry_name in inner.insert(entry_name, num_bytes) {
+                std::fs::remove_file(root_path.join(&evicted_entry_name))?;
+            }
+        }
+        // Evictions happening while loading the cache are not interesting.
+        inner.num_evictions = 0;
+        Ok(LocalDiskCache {
+            root_path: root_path.to_path_buf(),
+            inner: Mutex::new(inner),
+        })
+    }
+
+    /// Downloads the entire file at `path` from `storage` into the cache,
+    /// unless it is already there.
+    ///
+    /// The file is streamed to disk, and is never loaded in memory.
+    pub async fn prefetch(&self, storage: &dyn Storage, path: &Path) -> StorageResult<()> {
+        let entry_name = full_file_entry_name(path);
+        if self.inner.lock().unwrap().contains(&entry_name) {
+            return Ok(());
+        }
+        let num_bytes = storage.file_num_bytes(path).await?;
+        if !self.fits_in_cache(num_bytes) {
+            return Ok(());
+        }
+        let temp_path = self.temp_path(&entry_name);
+        if let Err(storage_err) = storage.copy_to_file(path, &temp_path).await {
+            let _ = fs::remove_file(&temp_path).await;
+            return Err(storage_err);
+        }
+        fs::rename(&temp_path, self.root_path.join(&entry_name)).await?;
+        self.register_entry(entry_name, num_bytes).await;
+        Ok(())
+    }
+
+    /// Returns the capacity of the cache.
+    pub fn capacity_in_bytes(&self) -> u64 {
+        self.inner.lock().unwrap().capacity_in_bytes
+    }
+
+    /// Returns the length of the file at `path` if it was prefetched in the cache.
+    ///
+    /// Contrary to lookups, this does not mark the entry as recently used.
+    pub fn prefetched_num_bytes(&self, path: &Path) -> Option<u64> {
+        self.inner
+            .lock()
+            .unwrap()
+            .lru_cache
+            .peek(&full_file_entry_name(path))
+            .copied()
+    }
+
+    fn record_lookup(&self, bytes_opt: Option<Bytes>) -> Option<Bytes> {
+        let mut inner = self.inner.lock().unwrap();
+        if bytes_opt.is_some() {
+            inner.num_hits += 1;
+        } else {
+            inner.num_misses += 1;
+        }
+        bytes_opt
+    }
+
+    fn fits_in_cache(&self, num_bytes: u64) -> bool {
+        let capacity_in_bytes = self.inner.lock().unwrap().capacity_in_bytes;
+        if num_bytes > capacity_in_bytes {
+            warn!(
+                capacity_in_bytes = capacity_in_bytes,
+                len = num_bytes,
+                "Downloaded a file larger than the local disk cache capacity."
+            );
+            return false;
+        }
+        true
+    }
+
+    fn temp_path(&self, entry_name: &str) -> PathBuf {
+        let suffix: String = rand::thread_rng()
+            .sample_iter(&Alphanumeric)
+            .take(8)
+            .map(char::from)
+            .collect();
+        self.root_path
+            .join(format!("{}.{}.{}", entry_name, suffix, TEMP_FILE_EXTENSION))
+    }
+
+    async fn register_entry(&self, entry_name: String, num_bytes: u64) {
+        let evicted_entries = self.inner.lock().unwrap().insert(entry_name, num_bytes);
+        for evicted_entry_name in evicted_entries {
+            if let Err(io_err) = fs::remove_file(self.root_path.join(&evicted_entry_name)).await {
+                warn!(entry=%evicted_entry_name, err=?io_err, "Failed to remove evicted cache entry.");
+            }
+        }
+    }
+
+    async fn write_entry(&self, entry_name: String, bytes: Bytes) {
+        let num_bytes = bytes.len() as u64;
+        if !self.fits_in_cache(num_bytes) {
+            return;
+        }
+        let temp_path = self.temp_path(&entry_name);
+        if let Err(io_err) = write_file(&temp_path, &bytes).await {
+            warn!(entry=%entry_name, err=?io_err, "Failed to write cache entry.");
+            let _ = fs::remove_file(&temp_path).await;
+            return;
+        }
+        if let Err(io_err) = fs::rename(&temp_path, self.root_path.join(&entry_name)).await {
+            warn!(entry=%entry_name, err=?io_err, "Failed to rename cache entry.");
+            let _ = fs::remove_file(&temp_path).await;
+            return;
+        }
+        self.register_entry(entry_name, num_bytes).await;
+    }
+
+    /// Reads `byte_range` (or the entire file if `None`) from the given entry.
+    ///
+    /// A missing entry file is a miss. If the entry file cannot be read, the entry is
+    /// dropped from the cache and its file is removed.
+    async fn read_entry(
+        &self,
+        entry_name: &str,
+        byte_range: Option<Range<usize>>,
+    ) -> Option<Bytes> {
+        if !self.inner.lock().unwrap().contains(entry_name) {
+            return None;
+        }
+        let entry_path = self.root_path.join(entry_name);
+        match read_file(&entry_path, byte_range).await {
+            Ok(bytes) => Some(bytes),
+            Err(io_err) if io_err.kind() == io::ErrorKind::NotFound => {
+                // Most likely, the entry was evicted between the lookup and the opening of
+                // its file, and may even have been written again since: evictions unregister
+                // entries before removing their file, and writes register entries after
+                // creating their file. The entry is only dropped if its file is still
+                // missing while it is registered, i.e. if it was removed behind our back.
+                let mut inner = self.inner.lock().unwrap();
+                if inner.lru_cache.contains(&entry_name.to_string()) && !entry_path.exists() {
+                    inner.remove(entry_name);
+                }
+                None
+            }
+            Err(io_err) => {
+                warn!(entry=%entry_name, err=?io_err, "Failed to read cache entry.");
+                self.inner.lock().unwrap().remove(entry_name);
+                if let Err(io_err) = fs::remove_file(&entry_path).await {
+                    warn!(entry=%entry_name, err=?io_err, "Failed to remove unreadable cache entry.");
+                }
+                None
+            }
+        }
+    }
+}
+
+async fn write_file(path: &Path, bytes: &[u8]) -> io::Result<()> {
+    let mut file = fs::File::create(path).await?;
+    file.write_all(bytes).await?;
+    Ok(())
+}
+
+async fn read_file(path: &Path, byte_range: Option<Range<usize>>) -> io::Result<Bytes> {
+    let mut file = fs::File::open(path).await?;
+    let byte_range = if let Some(byte_range) = byte_range {
+        byte_range
+    } else {
+        let num_bytes = file.metadata().await?.len() as usize;
+        0..num_bytes
+    };
+    file.seek(SeekFrom::Start(byte_range.start as u64)).await?;
+    let mut content_bytes: Vec<u8> = vec![0u8; byte_range.len()];
+    file.read_exact(&mut content_bytes).await?;
+    Ok(Bytes::from(content_bytes))
+}
+
+#[async_trait]
+impl Cache for LocalDiskCache {
+    async fn get(&self, path: &Path, byte_range: Range<usize>) -> Option<Bytes> {
+        let mut bytes_opt = self
+            .read_entry(&full_file_entry_name(path), Some(byte_range.clone()))
+            .await;
+        if bytes_opt.is_none() {
+            bytes_opt = self
+                .read_entry(&slice_entry_name(path, &byte_range), None)
+                .await;
+        }
+        self.record_lookup(bytes_opt)
+    }
+
+    async fn get_all(&self, path: &Path) -> Option<Bytes> {
+        let bytes_opt = self.read_entry(&full_file_entry_name(path), None).await;
+        self.record_lookup(bytes_opt)
+    }
+
+    async fn put(&self, path: PathBuf, byte_range: Range<usize>, bytes: Bytes) {
+        self.write_entry(slice_entry_name(&path, &byte_range), bytes)
+            .await;
+    }
+
+    async fn put_all(&self, path: PathBuf, bytes: Bytes) {
+        self.write_entry(full_file_entry_name(&path), bytes).await;
+    }
+
+    fn stats(&self) -> CacheStats {
+        let inner = self.inner.lock().unwrap();
+        CacheStats {
+            num_hits: inner.num_hits,
+            num_misses: inner.num_misses,
+            num_evictions: inner.num_evictions,
+            num_bytes: inner.num_bytes,
+        }
+    }
+}
+
+/// A view of a [`LocalDiskCache`] that only serves the files copied by
+/// [`LocalDiskCache::prefetch`].
+///
+/// Lookups are forwarded to the local disk cache, but the bytes fetched on a miss are not
+/// written to it: writing them to disk would slow down the reads that missed, and the
+/// slices would end up evicting the prefetched files.
+pub struct PrefetchedFilesCache {
+    local_disk_cache: Arc<LocalDiskCache>,
+}
+
+impl PrefetchedFilesCache {
+    /// Creates a view of the given local disk cache.
+    pub fn new(local_disk_cache: Arc<LocalDiskCache>) -> PrefetchedFilesCache {
+        PrefetchedFilesCache { local_disk_cache }
+    }
+}
+
+#[async_trait]
+impl Cache for PrefetchedFilesCache {
+    async fn get(&self, path: &Path, byte_range: Range<usize>) -> Option<Bytes> {
+        self.local_disk_cache.get(path, byte_range).await
+    }
+
+    async fn get_all(&self, path: &Path) -> Option<Bytes> {
+        self.local_disk_cache.get_all(path).await
+    }
+
+    async fn put(&self, _path: PathBuf, _byte_range: Range<usize>, _bytes: Bytes) {}
+
+    async fn put_all(&self, _path: PathBuf, _bytes: Bytes) {}
+
+    fn stats(&self) -> CacheStats {
+        self.local_disk_cache.stats()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RamStorage;
+
+    fn num_files(dir: &Path) -> usize {
+        std::fs::read_dir(dir).unwrap().count()
+    }
+
+    #[tokio::test]
+    async fn test_local_disk_cache_put_get() -> anyhow::Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let cache = LocalDiskCache::open(temp_dir.path(), 1_000)?;
+        let path = Path::new("split.split");
+        assert!(cache.get(path, 0..3).await.is_none());
+        cache
+            .put(path.to_path_buf(), 0..3, Bytes::from_static(b"abc"))
+            .await;
+        assert_eq!(cache.get(path, 0..3).await.unwrap(), &b"abc"[..]);
+        assert!(cache.get(path, 0..2).await.is_none());
+        assert!(cache.get_all(path).await.is_none());
+        cache
+            .put_all(path.to_path_buf(), Bytes::from_static(b"abcdef"))
+            .await;
+        assert_eq!(cache.get(path, 2..5).await.unwrap(), &b"cde"[..]);
+        assert_eq!(cache.get_all(path).await.unwrap(), &b"abcdef"[..]);
+        assert_eq!(cache.stats().num_bytes, 9);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_local_disk_cache_eviction() -> anyhow::Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let cache = LocalDiskCache::open(temp_dir.path(), 5)?;
+        let path = Path::new("split.split");
+        cache
+            .put(path.to_path_buf(), 0..3, Bytes::from_static(b"abc"))
+            .await;
+        cache
+            .put(path.to_path_buf(), 3..5, Bytes::from_static(b"de"))
+            .await;
+        // Marks the first entry as recently used.
+        assert!(cache.get(path, 0..3).await.is_some());
+        cache
+            .put(path.to_path_buf(), 5..7, Bytes::from_static(b"fg"))
+            .await;
+        assert!(cache.get(path, 0..3).await.is_some());
+        assert!(cache.get(path, 3..5).await.is_none());
+        assert!(cache.get(path, 5..7).await.is_some());
+        assert_eq!(num_files(temp_dir.path()), 2);
+        assert_eq!(cache.stats().num_evictions, 1);
+        // Entries larger than the capacity are not stored.
+        cache
+            .put(path.to_path_buf(), 0..6, Bytes::from_static(b"abcdef"))
+            .await;
+        assert!(cache.get(path, 0..6).await.is_none());
+        assert_eq!(cache.stats().num_bytes, 5);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_local_disk_cache_reopen() -> anyhow::Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let path = Path::new("split.split");
+        {
+            let cache = LocalDiskCache::open(temp_dir.path(), 1_000)?;
+            cache
+                .put(path.to_path_buf(), 0..3, Bytes::from_static(b"abc"))
+                .await;
+        }
+        std::fs::write(temp_dir.path().join("abc.slice.1234.tmp"), b"garbage")?;
+        let cache = LocalDiskCache::open(temp_dir.path(), 1_000)?;
+        assert_eq!(num_files(temp_dir.path()), 1);
+        assert_eq!(cache.stats().num_bytes, 3);
+        assert_eq!(cache.get(path, 0..3).await.unwrap(), &b"abc"[..]);
+        // Reopening with a smaller capacity evicts entries.
+        drop(cache);
+        let cache = LocalDiskCache::open(temp_dir.path(), 2)?;
+        assert!(cache.get(path, 0..3).await.is_none());
+        assert_eq!(num_files(temp_dir.path()), 0);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_local_disk_cache_prefetch() -> anyhow::Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let cache = LocalDiskCache::open(temp_dir.path(), 1_000)?;
+        let storage = RamStorage::builder().put("split.split", b"abcdef").build();
+        let path = Path::new("split.split");
+        cache.prefetch(&storage, path).await?;
+        assert_eq!(cache.get(path, 1..4).await.unwrap(), &b"bcd"[..]);
+        assert_eq!(cache.get_all(path).await.unwrap(), &b"abcdef"[..]);
+        assert!(cache
+            .prefetch(&storage, Path::new("missing.split"))
+            .await
+            .is_err());
+        assert_eq!(num_files(temp_dir.path()), 1);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_local_disk_cache_missing_entry_file() -> anyhow::Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let cache = LocalDiskCache::open(temp_dir.path(), 1_000)?;
+        let path = Path::new("split.split");
+        cache
+            .put_all(path.to_path_buf(), Bytes::from_static(b"abc"))
+            .await;
+        std::fs::remove_file(temp_dir.path().join(full_file_entry_name(path)))?;
+        assert!(cache.get_all(path).await.is_none());
+        assert_eq!(cache.stats().num_bytes, 0);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_prefetched_files_cache() -> anyhow::Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let local_disk_cache = Arc::new(LocalDiskCache::open(temp_dir.path(), 1_000)?);
+        let cache = PrefetchedFilesCache::new(local_disk_cache.clone());
+        let storage = RamStorage::builder().put("split.split", b"abcdef").build();
+        let path = Path::new("split.split");
+        cache
+            .put(path.to_path_buf(), 0..3, Bytes::from_static(b"abc"))
+            .await;
+        cache
+            .put_all(path.to_path_buf(), Bytes::from_static(b"abcdef"))
+            .await;
+        assert!(cache.get(path, 0..3).await.is_none());
+        assert_eq!(num_files(temp_dir.path()), 0);
+        assert_eq!(local_disk_cache.prefetched_num_bytes(path), None);
+        local_disk_cache.prefetch(&storage, path).await?;
+        assert_eq!(local_disk_cache.prefetched_num_bytes(path), Some(6));
+        assert_eq!(cache.get(path, 1..4).await.unwrap(), &b"bcd"[..]);
+        assert_eq!(cache.get_all(path).await.unwrap(), &b"abcdef"[..]);
+        Ok(())
+    }
+}
@@ -25,6 +25,8 @@ use bytes::Bytes;
 use lru::{KeyRef, LruCache};
 use tracing::{error, warn};
 
+use crate::CacheStats;
+
 #[derive(Clone, Copy, Debug)]
 enum Capacity {
     Unlimited,
@@ -43,6 +45,9 @@ struct NeedMutMemorySizedCache<K: Hash + Eq> {
     lru_cache: LruCache<K, Bytes>,
     num_bytes: usize,
     capacity: Capacity,
+    num_hits: u64,
+    num_misses: u64,
+    num_evictions: u64,
 }
 
 impl<K: Hash + Eq> NeedMutMemorySizedCache<K> {
@@ -55,6 +60,9 @@ impl<K: Hash + Eq> NeedMutMemorySizedCache<K> {
             lru_cache: LruCache::unbounded(),
             num_bytes: 0,
             capacity,
+            num_hits: 0,
+            num_misses: 0,
+            num_evictions: 0,
         }
     }
 
@@ -63,7 +71,22 @@ impl<K: Hash + Eq> NeedMutMemorySizedCache<K> {
         KeyRef<K>: Borrow<Q>,
         Q: Hash + Eq + ?Sized,
     {
-        self.lru_cache.get(cache_key).cloned()
+        let bytes_opt = self.lru_cache.get(cache_key).cloned();
+        if bytes_opt.is_some() {
+            self.num_hits += 1;
+        } else {
+            self.num_misses += 1;
+        }
+        bytes_opt
+    }
+
+    fn stats(&self) -> CacheStats {
+        CacheStats {
+            num_hits: self.num_hits,
+            num_misses: self.num_misses,
+            num_evictions: self.num_evictions,
+            num_bytes: self.num_bytes as u64,
+        }
     }
 
     /// Attempt to put the given amount of data in the cache.
@@ -85,6 +108,7 @@ impl<K: Hash + Eq> NeedMutMemorySizedCache<K> {
         while self.capacity.exceeds_capacity(self.num_bytes + bytes.len()) {
             if let Some((_, bytes)) = self.lru_cache.pop_lru() {
                 self.num_bytes -= bytes.len();
+                self.num_evictions += 1;
             } else {
                 error!(
                     "Logical error. Even after removing all of the items in the cache the \
@@ -136,6 +160,11 @@ impl<K: Hash + Eq> MemorySizedCache<K> {
     pub fn put(&self, val: K, bytes: Bytes) {
         self.inner.lock().unwrap().put(val, bytes);
     }
+
+    /// Returns the hit, miss and eviction counters of the cache, and its current size.
+    pub fn stats(&self) -> CacheStats {
+        self.inner.lock().unwrap().stats()
+    }
 }
 
 #[cfg(test)]
@@ -174,6 +203,15 @@ mod tests {
             // The previous entry should however be remaining.
             assert_eq!(cache.get(&"5".to_string()).unwrap(), &b"fghij"[..]);
         }
+        assert_eq!(
+            cache.stats(),
+            CacheStats {
+                num_hits: 5,
+                num_misses: 3,
+                num_evictions: 2,
+                num_bytes: 5,
+            }
+        );
     }
 
     #[test]
@@ -18,7 +18,10 @@
 // along with this program. If not, see <http://www.gnu.org/licenses/>.
 
 mod in_ram_slice_cache;
+mod local_disk_cache;
 mod memory_sized_cache;
+mod prefix_cache;
+mod read_coalescer;
 mod storage_with_cache;
 
 use std::ops::Range;
@@ -26,10 +29,37 @@ use std::path::{Path, PathBuf};
 
 use async_trait::async_trait;
 use bytes::Bytes;
+use serde::Serialize;
 
 pub use self::in_ram_slice_cache::SliceCache;
+pub use self::local_disk_cache::{LocalDiskCache, PrefetchedFilesCache};
 pub use self::memory_sized_cache::MemorySizedCache;
-pub use self::storage_with_cache::StorageWithCacheFactory;
+pub use self::prefix_cache::add_prefix_to_cache;
+pub use self::read_coalescer::ReadCoalescer;
+pub use self::storage_with_cache::{wrap_storage_with_cache, StorageWithCacheFactory};
+
+/// Counters describing the activity of a cache.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CacheStats {
+    /// Number of lookups that found their entry.
+    pub num_hits: u64,
+    /// Number of lookups that did not find their entry.
+    pub num_misses: u64,
+    /// Number of entries removed to make room for new ones.
+    pub num_evictions: u64,
+    /// Number of bytes currently held by the cache.
+    pub num_bytes: u64,
+}
+
+impl std::ops::AddAssign for CacheStats {
+    fn add_assign(&mut self, other: CacheStats) {
+        self.num_hits += other.num_hits;
+        self.num_misses += other.num_misses;
+        self.num_evictions += other.num_evictions;
+        self.num_bytes += other.num_bytes;
+    }
+}
 
 /// The `Cache` trait is the abstraction used to describe the caching logic
 /// used in front of a storage. See `StorageWithCache`.
@@ -44,4 +74,6 @@ pub trait Cache: Send + Sync + 'static {
     async fn put(&self, path: PathBuf, byte_range: Range<usize>, bytes: Bytes);
     /// Put an entire file into the cache.
     async fn put_all(&self, path: PathBuf, bytes: Bytes);
+    /// Returns the hit, miss and eviction counters of the cache.
+    fn stats(&self) -> CacheStats;
 }
@@ -0,0 +1,101 @@
+// Copyright (C) 2021 Quickwit, Inc.
+//
+// Quickwit is offered under the AGPL v3.0 and as commercial software.
+// For commercial licensing, contact us at hello@quickwit.io.
+//
+// AGPL:
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use bytes::Bytes;
+
+use crate::{Cache, CacheStats};
+
+/// This cache acts as a proxy to another cache that simply modifies each API call
+/// by preceding each path with a given a prefix.
+///
+/// It makes it possible to share a cache between storages whose paths may collide,
+/// such as the files of different bundles.
+struct PrefixCache {
+    cache: Arc<dyn Cache>,
+    prefix: PathBuf,
+}
+
+#[async_trait]
+impl Cache for PrefixCache {
+    async fn get(&self, path: &Path, byte_range: Range<usize>) -> Option<Bytes> {
+        self.cache.get(&self.prefix.join(path), byte_range).await
+    }
+
+    async fn get_all(&self, path: &Path) -> Option<Bytes> {
+        self.cache.get_all(&self.prefix.join(path)).await
+    }
+
+    async fn put(&self, path: PathBuf, byte_range: Range<usize>, bytes: Bytes) {
+        self.cache
+            .put(self.prefix.join(path), byte_range, bytes)
+            .await
+    }
+
+    async fn put_all(&self, path: PathBuf, bytes: Bytes) {
+        self.cache.put_all(self.prefix.join(path), bytes).await
+    }
+
+    fn stats(&self) -> CacheStats {
+        self.cache.stats()
+    }
+}
+
+/// Creates a [`PrefixCache`] using an underlying cache and a prefix.
+pub fn add_prefix_to_cache<P: Into<PathBuf>>(cache: Arc<dyn Cache>, prefix: P) -> Arc<dyn Cache> {
+    Arc::new(PrefixCache {
+        cache,
+        prefix: prefix.into(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MockCache;
+
+    #[tokio::test]
+    async fn test_prefix_cache() {
+        let mut mock_cache = MockCache::default();
+        mock_cache
+            .expect_get()
+            .times(1)
+            .withf(|path, byte_range| {
+                path == Path::new("split.split/seg.fast") && byte_range == &(2..5)
+            })
+            .returning(|_, _| Some(Bytes::from_static(b"abc")));
+        mock_cache
+            .expect_put_all()
+            .times(1)
+            .withf(|path, _| path == Path::new("split.split/seg.term"))
+            .returning(|_, _| ());
+        let prefix_cache = add_prefix_to_cache(Arc::new(mock_cache), "split.split");
+        assert_eq!(
+            prefix_cache.get(Path::new("seg.fast"), 2..5).await.unwrap(),
+            &b"abc"[..]
+        );
+        prefix_cache
+            .put_all(PathBuf::from("seg.term"), Bytes::from_static(b"def"))
+            .await;
+    }
+}
@@ -0,0 +1,295 @@
+// Copyright (C) 2021 Quickwit, Inc.
+//
+// Quickwit is offered under the AGPL v3.0 and as commercial software.
+// For commercial licensing, contact us at hello@quickwit.io.
+//
+// AGPL:
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use bytes::{Bytes, BytesMut};
+use futures::future::{BoxFuture, FutureExt, Shared};
+use tokio::sync::oneshot;
+
+type SharedRead = Shared<BoxFuture<'static, Option<Bytes>>>;
+
+struct InflightRead {
+    read_id: u64,
+    byte_range: Range<usize>,
+    shared_read: SharedRead,
+}
+
+#[derive(Default)]
+struct InflightReads {
+    next_read_id: u64,
+    reads_per_path: HashMap<PathBuf, Vec<InflightRead>>,
+}
+
+impl InflightReads {
+    /// Returns the in-flight reads that, put together, cover the entire `byte_range`.
+    fn find_covering_reads(
+        &self,
+        path: &Path,
+        byte_range: &Range<usize>,
+    ) -> Option<Vec<(Range<usize>, SharedRead)>> {
+        let inflight_reads = self.reads_per_path.get(path)?;
+        let mut covering_reads = Vec::new();
+        let mut position = byte_range.start;
+        while position < byte_range.end {
+            let inflight_read = inflight_reads
+                .iter()
+                .filter(|read| read.byte_range.start <= position && position < read.byte_range.end)
+                .max_by_key(|read| read.byte_range.end)?;
+            covering_reads.push((
+                inflight_read.byte_range.clone(),
+                inflight_read.shared_read.clone(),
+            ));
+            position = inflight_read.byte_range.end;
+        }
+        Some(covering_reads)
+    }
+
+    fn register(&mut self, path: &Path, byte_range: Range<usize>) -> (u64, oneshot::Sender<Bytes>) {
+        let (bytes_tx, bytes_rx) = oneshot::channel();
+        let read_id = self.next_read_id;
+        self.next_read_id += 1;
+        self.reads_per_path
+            .entry(path.to_path_buf())
+            .or_default()
+            .push(InflightRead {
+                read_id,
+                byte_range,
+                shared_read: bytes_rx.map(Result::ok).boxed().shared(),
+            });
+        (read_id, bytes_tx)
+    }
+
+    fn unregister(&mut self, path: &Path, read_id: u64) {
+        if let Some(inflight_reads) = self.reads_per_path.get_mut(path) {
+            inflight_reads.retain(|read| read.read_id != read_id);
+            if inflight_reads.is_empty() {
+                self.reads_per_path.remove(path);
+            }
+        }
+    }
+}
+
+enum ReadRole {
+    /// The read emits the request and shares its result.
+    Leader(u64, oneshot::Sender<Bytes>),
+    /// The read waits for in-flight reads.
+    Follower(Vec<(Range<usize>, SharedRead)>),
+}
+
+/// Removes the read from the in-flight reads, even if the read future is dropped
+/// before completion.
+struct InflightReadGuard<'a> {
+    inflight_reads: &'a Mutex<InflightReads>,
+    path: &'a Path,
+    read_id: u64,
+}
+
+impl<'a> Drop for InflightReadGuard<'a> {
+    fn drop(&mut self) {
+        self.inflight_reads
+            .lock()
+            .unwrap()
+            .unregister(self.path, self.read_id);
+    }
+}
+
+/// Waits for the covering reads and extracts `byte_range` from their results.
+///
+/// Returns `None` if one of the reads failed.
+async fn stitch_covering_reads(
+    covering_reads: Vec<(Range<usize>, SharedRead)>,
+    byte_range: Range<usize>,
+) -> Option<Bytes> {
+    if covering_reads.len() == 1 {
+        let (read_range, shared_read) = covering_reads.into_iter().next().unwrap();
+        let bytes = shared_read.await?;
+        return Some(
+            bytes.slice(byte_range.start - read_range.start..byte_range.end - read_range.start),
+        );
+    }
+    let mut buffer = BytesMut::with_capacity(byte_range.len());
+    let mut position = byte_range.start;
+    for (read_range, shared_read) in covering_reads {
+        let bytes = shared_read.await?;
+        let end = read_range.end.min(byte_range.end);
+        buffer.extend_from_slice(&bytes[position - read_range.start..end - read_range.start]);
+        position = end;
+    }
+    Some(buffer.freeze())
+}
+
+/// Deduplicates concurrent reads of the same bytes.
+///
+/// A read whose range is entirely covered by one in-flight read, or by several adjacent ones,
+/// waits for them instead of hitting the underlying storage.
+/// If one of these reads fails, the waiting read falls back to fetching its own range,
+/// so that errors are always reported by the read that emitted the request.
+#[derive(Default)]
+pub struct ReadCoalescer {
+    inflight_reads: Mutex<InflightReads>,
+}
+
+impl ReadCoalescer {
+    /// Reads `byte_range` of the file at `path`, either by waiting for in-flight reads of the
+    /// same bytes, or by calling `fetch`.
+    pub async fn read<F, Fut, E>(
+        &self,
+        path: &Path,
+        byte_range: Range<usize>,
+        fetch: F,
+    ) -> Result<Bytes, E>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<Bytes, E>>,
+    {
+        // The lock must be released before awaiting.
+        let read_role = {
+            let mut inflight_reads = self.inflight_reads.lock().unwrap();
+            match inflight_reads.find_covering_reads(path, &byte_range) {
+                Some(covering_reads) => ReadRole::Follower(covering_reads),
+                None => {
+                    let (read_id, bytes_tx) = inflight_reads.register(path, byte_range.clone());
+                    ReadRole::Leader(read_id, bytes_tx)
+                }
+            }
+        };
+        let (read_id, bytes_tx) = match read_role {
+            ReadRole::Leader(read_id, bytes_tx) => (read_id, bytes_tx),
+            ReadRole::Follower(covering_reads) => {
+                if let Some(bytes) = stitch_covering_reads(covering_reads, byte_range).await {
+                    return Ok(bytes);
+                }
+                return fetch().await;
+            }
+        };
+        let _guard = InflightReadGuard {
+            inflight_reads: &self.inflight_reads,
+            path,
+            read_id,
+        };
+        let bytes = fetch().await?;
+        // Waiting reads may have been dropped in the meantime, so we ignore the error.
+        let _ = bytes_tx.send(bytes.clone());
+        Ok(bytes)
+    }
+
+    /// Returns the number of reads currently being fetched.
+    pub fn num_inflight_reads(&self) -> usize {
+        self.inflight_reads
+            .lock()
+            .unwrap()
+            .reads_per_path
+            .values()
+            .map(Vec::len)
+            .sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    use super::*;
+
+    fn file_content() -> Bytes {
+        Bytes::from((0u8..100).collect::<Vec<u8>>())
+    }
+
+    async fn fetch(
+        num_fetches: &AtomicUsize,
+        byte_range: Range<usize>,
+    ) -> Result<Bytes, &'static str> {
+        num_fetches.fetch_add(1, Ordering::SeqCst);
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        Ok(file_content().slice(byte_range))
+    }
+
+    #[tokio::test]
+    async fn test_read_coalescer_same_range() {
+        let read_coalescer = ReadCoalescer::default();
+        let num_fetches = AtomicUsize::default();
+        let path = Path::new("file");
+        let (left, right) = futures::join!(
+            read_coalescer.read(path, 10..20, || fetch(&num_fetches, 10..20)),
+            read_coalescer.read(path, 10..20, || fetch(&num_fetches, 10..20)),
+        );
+        assert_eq!(left.unwrap(), file_content().slice(10..20));
+        assert_eq!(right.unwrap(), file_content().slice(10..20));
+        assert_eq!(num_fetches.load(Ordering::SeqCst), 1);
+        assert_eq!(read_coalescer.num_inflight_reads(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_read_coalescer_adjacent_ranges() {
+        let read_coalescer = ReadCoalescer::default();
+        let num_fetches = AtomicUsize::default();
+        let path = Path::new("file");
+        let (first, second, covered, other_path) = futures::join!(
+            read_coalescer.read(path, 0..30, || fetch(&num_fetches, 0..30)),
+            read_coalescer.read(path, 30..60, || fetch(&num_fetches, 30..60)),
+            read_coalescer.read(path, 20..50, || fetch(&num_fetches, 20..50)),
+            read_coalescer.read(Path::new("other"), 20..50, || fetch(&num_fetches, 20..50)),
+        );
+        assert_eq!(first.unwrap(), file_content().slice(0..30));
+        assert_eq!(second.unwrap(), file_content().slice(30..60));
+        assert_eq!(covered.unwrap(), file_content().slice(20..50));
+        assert_eq!(other_path.unwrap(), file_content().slice(20..50));
+        assert_eq!(num_fetches.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_read_coalescer_partial_overlap_is_fetched() {
+        let read_coalescer = ReadCoalescer::default();
+        let num_fetches = AtomicUsize::default();
+        let path = Path::new("file");
+        let (left, right) = futures::join!(
+            read_coalescer.read(path, 0..30, || fetch(&num_fetches, 0..30)),
+            read_coalescer.read(path, 20..40, || fetch(&num_fetches, 20..40)),
+        );
+        assert_eq!(left.unwrap(), file_content().slice(0..30));
+        assert_eq!(right.unwrap(), file_content().slice(20..40));
+        assert_eq!(num_fetches.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_read_coalescer_failed_read_is_retried() {
+        let read_coalescer = ReadCoalescer::default();
+        let num_fetches = AtomicUsize::default();
+        let path = Path::new("file");
+        let failing_fetch = || async {
+            num_fetches.fetch_add(1, Ordering::SeqCst);
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            Err("failed")
+        };
+        let (left, right) = futures::join!(
+            read_coalescer.read(path, 0..30, failing_fetch),
+            read_coalescer.read(path, 0..10, || fetch(&num_fetches, 0..10)),
+        );
+        assert_eq!(left.unwrap_err(), "failed");
+        assert_eq!(right.unwrap(), file_content().slice(0..10));
+        assert_eq!(num_fetches.load(Ordering::SeqCst), 2);
+        assert_eq!(read_coalescer.num_inflight_reads(), 0);
+    }
+}
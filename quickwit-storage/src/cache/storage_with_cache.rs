@@ -24,12 +24,32 @@ use std::sync::Arc;
 use async_trait::async_trait;
 use bytes::Bytes;
 
-use crate::{Cache, PutPayload, Storage, StorageFactory, StorageResult};
+use crate::{Cache, PutPayload, ReadCoalescer, Storage, StorageFactory, StorageResult};
 
 /// Use with care, StorageWithCache is read-only.
+///
+/// Concurrent cache misses on the same bytes are coalesced into a single request
+/// to the underlying storage.
 struct StorageWithCache {
     storage: Arc<dyn Storage>,
     cache: Arc<dyn Cache>,
+    read_coalescer: Arc<ReadCoalescer>,
+}
+
+impl StorageWithCache {
+    async fn get_slice_and_fill_cache(
+        &self,
+        path: &Path,
+        byte_range: Range<usize>,
+    ) -> StorageResult<Bytes> {
+        let bytes = self.storage.get_slice(path, byte_range.clone()).await?;
+        // The cache is filled before the read leaves the in-flight reads, so that
+        // subsequent reads are served by the cache.
+        self.cache
+            .put(path.to_owned(), byte_range, bytes.clone())
+            .await;
+        Ok(bytes)
+    }
 }
 
 #[async_trait]
@@ -44,14 +64,13 @@ impl Storage for StorageWithCache {
 
     async fn get_slice(&self, path: &Path, byte_range: Range<usize>) -> StorageResult<Bytes> {
         if let Some(bytes) = self.cache.get(path, byte_range.clone()).await {
-            Ok(bytes)
-        } else {
-            let bytes = self.storage.get_slice(path, byte_range.clone()).await?;
-            self.cache
-                .put(path.to_owned(), byte_range, bytes.clone())
-                .await;
-            Ok(bytes)
+            return Ok(bytes);
         }
+        self.read_coalescer
+            .read(path, byte_range.clone(), || {
+                self.get_slice_and_fill_cache(path, byte_range.clone())
+            })
+            .await
     }
 
     async fn get_all(&self, path: &Path) -> StorageResult<Bytes> {
@@ -76,17 +95,34 @@ impl Storage for StorageWithCache {
         self.storage.file_num_bytes(path).await
     }
 
+    async fn list_directories(&self, path: &Path) -> StorageResult<Vec<String>> {
+        self.storage.list_directories(path).await
+    }
+
     fn uri(&self) -> String {
         self.storage.uri()
     }
 }
 
+/// Wraps a storage with a cache. The resulting storage is read-only.
+pub fn wrap_storage_with_cache(
+    cache: Arc<dyn Cache>,
+    storage: Arc<dyn Storage>,
+) -> Arc<dyn Storage> {
+    Arc::new(StorageWithCache {
+        storage,
+        cache,
+        read_coalescer: Arc::new(ReadCoalescer::default()),
+    })
+}
+
 /// A StorageFactory that wraps all Storage that are produced with a cache.
 ///
-/// The cache is shared with all of the storage instances.
+/// The cache is shared with all of the storage instances, and so are in-flight reads.
 pub struct StorageWithCacheFactory {
     storage_factory: Arc<dyn StorageFactory>,
     cache: Arc<dyn Cache>,
+    read_coalescer: Arc<ReadCoalescer>,
 }
 
 impl StorageWithCacheFactory {
@@ -95,6 +131,7 @@ impl StorageWithCacheFactory {
         StorageWithCacheFactory {
             storage_factory,
             cache,
+            read_coalescer: Arc::new(ReadCoalescer::default()),
         }
     }
 }
@@ -109,6 +146,7 @@ impl StorageFactory for StorageWithCacheFactory {
         Ok(Arc::new(StorageWithCache {
             storage,
             cache: self.cache.clone(),
+            read_coalescer: self.read_coalescer.clone(),
         }))
     }
 }
@@ -150,6 +188,7 @@ mod tests {
         let storage_with_cache = StorageWithCache {
             storage: Arc::new(mock_storage),
             cache: Arc::new(mock_cache),
+            read_coalescer: Arc::new(ReadCoalescer::default()),
         };
 
         let data1 = storage_with_cache
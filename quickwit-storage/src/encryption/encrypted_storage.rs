@@ -0,0 +1,591 @@
+// Copyright (C) 2021 Quickwit, Inc.
+//
+// Quickwit is offered under the AGPL v3.0 and as commercial software.
+// For commercial licensing, contact us at hello@quickwit.io.
+//
+// AGPL:
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use lru::LruCache;
+use rand::RngCore;
+use tokio::io::AsyncWriteExt;
+
+use super::{
+    EncryptedFileLayout, EncryptionHeader, KeyProvider, DATA_KEY_NUM_BYTES,
+    DEFAULT_CHUNK_NUM_BYTES, MAGIC_NUMBER, MAX_HEADER_NUM_BYTES,
+};
+use crate::{PutPayload, Storage, StorageErrorKind, StorageResult};
+
+/// Number of files for which the layout is kept in memory.
+const FILE_LAYOUT_CACHE_CAPACITY: usize = 10_000;
+
+/// Number of chunks decrypted at once when copying an encrypted file.
+const NUM_CHUNKS_PER_COPY_BATCH: usize = 64;
+
+/// State shared by all the encrypted storages created by a storage resolver.
+pub(crate) struct EncryptionContext {
+    key_provider: Arc<dyn KeyProvider>,
+    // Migration escape hatch: lets storages with an encryption key read files that were
+    // written before encryption was enabled.
+    allow_plaintext_reads: bool,
+    // Opening an encrypted file requires reading its header and unwrapping its data key,
+    // so the result is kept around. `None` means the file is not encrypted.
+    file_layouts: Mutex<LruCache<String, Option<Arc<EncryptedFileLayout>>>>,
+}
+
+impl EncryptionContext {
+    pub fn new(key_provider: Arc<dyn KeyProvider>, allow_plaintext_reads: bool) -> Self {
+        EncryptionContext {
+            key_provider,
+            allow_plaintext_reads,
+            file_layouts: Mutex::new(LruCache::new(FILE_LAYOUT_CACHE_CAPACITY)),
+        }
+    }
+}
+
+/// Storage decrypting the encrypted files it reads, and encrypting the files it writes
+/// if it was given an encryption key ID.
+///
+/// Files that are not encrypted are read as is, unless the storage was given an encryption
+/// key ID: a file missing its encryption header could then have been swapped for a plaintext
+/// one, so reading it fails, unless plaintext reads are explicitly allowed for migrations.
+pub(crate) struct EncryptedStorage {
+    storage: Arc<dyn Storage>,
+    context: Arc<EncryptionContext>,
+    encryption_key_id_opt: Option<String>,
+}
+
+impl EncryptedStorage {
+    pub fn new(
+        storage: Arc<dyn Storage>,
+        context: Arc<EncryptionContext>,
+        encryption_key_id_opt: Option<String>,
+    ) -> Self {
+        EncryptedStorage {
+            storage,
+            context,
+            encryption_key_id_opt,
+        }
+    }
+
+    fn file_layout_cache_key(&self, path: &Path) -> String {
+        format!("{}/{}", self.storage.uri(), path.display())
+    }
+
+    /// Checks that the file at `path`, which is not encrypted, may be read as is.
+    fn check_plaintext_read(&self, path: &Path) -> StorageResult<()> {
+        if self.encryption_key_id_opt.is_some() && !self.context.allow_plaintext_reads {
+            return Err(StorageErrorKind::Unauthorized.with_error(anyhow::anyhow!(
+                "File `{}` is not encrypted, while its index is.",
+                path.display()
+            )));
+        }
+        Ok(())
+    }
+
+    fn forget_file_layout(&self, path: &Path) {
+        let cache_key = self.file_layout_cache_key(path);
+        self.context.file_layouts.lock().unwrap().pop(&cache_key);
+    }
+
+    /// Returns the layout of the file starting with `bytes`, or `None` if it is not encrypted.
+    async fn open_file_layout(&self, bytes: &[u8]) -> StorageResult<Option<EncryptedFileLayout>> {
+        let (header, header_num_bytes) = match EncryptionHeader::parse(bytes)? {
+            Some(header_and_num_bytes) => header_and_num_bytes,
+            None => return Ok(None),
+        };
+        let data_key = self
+            .context
+            .key_provider
+            .unwrap_data_key(&header.key_id, &header.wrapped_data_key)
+            .await?;
+        if data_key.len() != DATA_KEY_NUM_BYTES {
+            return Err(StorageErrorKind::InternalError
+                .with_error(anyhow::anyhow!("Unwrapped data key has an invalid length.")));
+        }
+        let header_bytes = bytes[..header_num_bytes].to_vec();
+        Ok(Some(EncryptedFileLayout::new(
+            header_bytes,
+            &header,
+            &data_key,
+        )))
+    }
+
+    async fn file_layout(&self, path: &Path) -> StorageResult<Option<Arc<EncryptedFileLayout>>> {
+        let cache_key = self.file_layout_cache_key(path);
+        if let Some(file_layout_opt) = self.context.file_layouts.lock().unwrap().get(&cache_key) {
+            return Ok(file_layout_opt.clone());
+        }
+        let file_num_bytes = self.storage.file_num_bytes(path).await? as usize;
+        let file_layout_opt = if file_num_bytes < MAGIC_NUMBER.len() {
+            None
+        } else {
+            let header_bytes = self
+                .storage
+                .get_slice(path, 0..file_num_bytes.min(MAX_HEADER_NUM_BYTES))
+                .await?;
+            self.open_file_layout(&header_bytes).await?.map(Arc::new)
+        };
+        self.context
+            .file_layouts
+            .lock()
+            .unwrap()
+            .put(cache_key, file_layout_opt.clone());
+        Ok(file_layout_opt)
+    }
+
+    async fn get_decrypted_slice(
+        &self,
+        path: &Path,
+        file_layout: &EncryptedFileLayout,
+        byte_range: Range<usize>,
+    ) -> StorageResult<Bytes> {
+        if byte_range.end as u64 > file_layout.plaintext_num_bytes {
+            return Err(StorageErrorKind::InternalError.with_error(anyhow::anyhow!(
+                "Range {:?} is out of the bounds of file `{}` ({} bytes).",
+                byte_range,
+                path.display(),
+                file_layout.plaintext_num_bytes
+            )));
+        }
+        if byte_range.is_empty() {
+            return Ok(Bytes::new());
+        }
+        let chunk_ords = file_layout.chunk_ords(&byte_range);
+        let ciphertext = self
+            .storage
+            .get_slice(path, file_layout.chunks_ciphertext_range(&chunk_ords)?)
+            .await?;
+        let mut plaintext = Vec::with_capacity(chunk_ords.len() * file_layout.chunk_num_bytes);
+        file_layout.decrypt_chunks(chunk_ords.start, &ciphertext, &mut plaintext)?;
+        let offset = chunk_ords.start * file_layout.chunk_num_bytes;
+        if plaintext.len() < byte_range.end - offset {
+            return Err(StorageErrorKind::InternalError.with_error(anyhow::anyhow!(
+                "Encrypted file `{}` is truncated.",
+                path.display()
+            )));
+        }
+        Ok(Bytes::from(plaintext).slice(byte_range.start - offset..byte_range.end - offset))
+    }
+
+    async fn encrypt_payload(
+        &self,
+        encryption_key_id: &str,
+        payload: PutPayload,
+    ) -> StorageResult<PutPayload> {
+        let mut data_key = [0u8; DATA_KEY_NUM_BYTES];
+        rand::thread_rng().fill_bytes(&mut data_key);
+        let wrapped_data_key = self
+            .context
+            .key_provider
+            .wrap_data_key(encryption_key_id, &data_key)
+            .await?;
+        let header = EncryptionHeader {
+            plaintext_num_bytes: payload.len().await?,
+            chunk_num_bytes: DEFAULT_CHUNK_NUM_BYTES,
+            key_id: encryption_key_id.to_string(),
+            wrapped_data_key,
+        };
+        let file_layout = EncryptedFileLayout::new(header.serialize()?, &header, &data_key);
+        match payload {
+            PutPayload::InMemory(plaintext) => {
+                let mut ciphertext = file_layout.header_bytes.clone();
+                for (chunk_ord, chunk) in plaintext.chunks(file_layout.chunk_num_bytes).enumerate()
+                {
+                    ciphertext.extend(file_layout.encrypt_chunk(chunk_ord, chunk)?);
+                }
+                Ok(PutPayload::InMemory(Bytes::from(ciphertext)))
+            }
+            PutPayload::LocalFile(plaintext_path) => {
+                let encrypted_path = encrypted_file_path(&plaintext_path);
+                let encrypted_path_clone = encrypted_path.clone();
+                tokio::task::spawn_blocking(move || {
+                    encrypt_file(&file_layout, &plaintext_path, &encrypted_path_clone)
+                })
+                .await
+                .map_err(|join_error| {
+                    StorageErrorKind::InternalError.with_error(anyhow::anyhow!(join_error))
+                })??;
+                Ok(PutPayload::LocalFile(encrypted_path))
+            }
+        }
+    }
+}
+
+/// Encrypted copies of local files are written next to them.
+fn encrypted_file_path(plaintext_path: &Path) -> PathBuf {
+    let mut encrypted_file_name = plaintext_path.file_name().unwrap_or_default().to_owned();
+    encrypted_file_name.push(".encrypted");
+    plaintext_path.with_file_name(encrypted_file_name)
+}
+
+fn encrypt_file(
+    file_layout: &EncryptedFileLayout,
+    plaintext_path: &Path,
+    encrypted_path: &Path,
+) -> StorageResult<()> {
+    let mut reader = BufReader::new(File::open(plaintext_path)?);
+    let mut writer = BufWriter::new(File::create(encrypted_path)?);
+    writer.write_all(&file_layout.header_bytes)?;
+    let mut chunk = vec![0u8; file_layout.chunk_num_bytes];
+    for chunk_ord in 0..file_layout.num_chunks() {
+        let chunk_num_bytes =
+            file_layout.chunk_ciphertext_range(chunk_ord)?.len() - super::TAG_NUM_BYTES;
+        reader.read_exact(&mut chunk[..chunk_num_bytes])?;
+        writer.write_all(&file_layout.encrypt_chunk(chunk_ord, &chunk[..chunk_num_bytes])?)?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+#[async_trait]
+impl Storage for EncryptedStorage {
+    async fn put(&self, path: &Path, payload: PutPayload) -> StorageResult<()> {
+        self.forget_file_layout(path);
+        let encryption_key_id = match self.encryption_key_id_opt.as_ref() {
+            Some(encryption_key_id) => encryption_key_id,
+            None => return self.storage.put(path, payload).await,
+        };
+        let encrypted_payload = self.encrypt_payload(encryption_key_id, payload).await?;
+        let put_result = self.storage.put(path, encrypted_payload.clone()).await;
+        if let PutPayload::LocalFile(encrypted_path) = encrypted_payload {
+            if let Err(io_error) = tokio::fs::remove_file(&encrypted_path).await {
+                if io_error.kind() != io::ErrorKind::NotFound {
+                    return Err(io_error.into());
+                }
+            }
+        }
+        put_result
+    }
+
+    async fn copy_to_file(&self, path: &Path, output_path: &Path) -> StorageResult<()> {
+        let file_layout = match self.file_layout(path).await? {
+            Some(file_layout) => file_layout,
+            None => {
+                self.check_plaintext_read(path)?;
+                return self.storage.copy_to_file(path, output_path).await;
+            }
+        };
+        let mut output_file = tokio::fs::File::create(output_path).await?;
+        let num_chunks = file_layout.num_chunks();
+        let mut plaintext = Vec::new();
+        for first_chunk_ord in (0..num_chunks).step_by(NUM_CHUNKS_PER_COPY_BATCH) {
+            let chunk_ords =
+                first_chunk_ord..(first_chunk_ord + NUM_CHUNKS_PER_COPY_BATCH).min(num_chunks);
+            let ciphertext = self
+                .storage
+                .get_slice(path, file_layout.chunks_ciphertext_range(&chunk_ords)?)
+                .await?;
+            plaintext.clear();
+            file_layout.decrypt_chunks(chunk_ords.start, &ciphertext, &mut plaintext)?;
+            output_file.write_all(&plaintext).await?;
+        }
+        output_file.flush().await?;
+        Ok(())
+    }
+
+    async fn get_slice(&self, path: &Path, byte_range: Range<usize>) -> StorageResult<Bytes> {
+        match self.file_layout(path).await? {
+            Some(file_layout) => {
+                self.get_decrypted_slice(path, &file_layout, byte_range)
+                    .await
+            }
+            None => {
+                self.check_plaintext_read(path)?;
+                self.storage.get_slice(path, byte_range).await
+            }
+        }
+    }
+
+    async fn get_all(&self, path: &Path) -> StorageResult<Bytes> {
+        let bytes = self.storage.get_all(path).await?;
+        let file_layout = match self.open_file_layout(&bytes).await? {
+            Some(file_layout) => file_layout,
+            None => {
+                self.check_plaintext_read(path)?;
+                return Ok(bytes);
+            }
+        };
+        let mut plaintext = Vec::with_capacity(file_layout.plaintext_num_bytes as usize);
+        file_layout.decrypt_chunks(0, &bytes[file_layout.header_bytes.len()..], &mut plaintext)?;
+        if plaintext.len() as u64 != file_layout.plaintext_num_bytes {
+            return Err(StorageErrorKind::InternalError.with_error(anyhow::anyhow!(
+                "Encrypted file `{}` is truncated.",
+                path.display()
+            )));
+        }
+        Ok(Bytes::from(plaintext))
+    }
+
+    async fn delete(&self, path: &Path) -> StorageResult<()> {
+        self.forget_file_layout(path);
+        self.storage.delete(path).await
+    }
+
+    async fn exists(&self, path: &Path) -> StorageResult<bool> {
+        self.storage.exists(path).await
+    }
+
+    async fn file_num_bytes(&self, path: &Path) -> StorageResult<u64> {
+        match self.file_layout(path).await? {
+            Some(file_layout) => Ok(file_layout.plaintext_num_bytes),
+            None => {
+                self.check_plaintext_read(path)?;
+                self.storage.file_num_bytes(path).await
+            }
+        }
+    }
+
+    async fn list_directories(&self, path: &Path) -> StorageResult<Vec<String>> {
+        self.storage.list_directories(path).await
+    }
+
+    fn uri(&self) -> String {
+        self.storage.uri()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{LocalFileStorage, LocalKeyFileProvider, RamStorage};
+
+    fn test_context(
+        allow_plaintext_reads: bool,
+    ) -> anyhow::Result<(tempfile::TempDir, Arc<EncryptionContext>)> {
+        let temp_dir = tempfile::tempdir()?;
+        let key_file_path = temp_dir.path().join("keys.json");
+        let key_file_content = serde_json::json!({
+            "keys": {"key-1": base64::encode([1u8; 32])}
+        });
+        std::fs::write(&key_file_path, key_file_content.to_string())?;
+        let key_provider = LocalKeyFileProvider::open(&key_file_path)?;
+        Ok((
+            temp_dir,
+            Arc::new(EncryptionContext::new(
+                Arc::new(key_provider),
+                allow_plaintext_reads,
+            )),
+        ))
+    }
+
+    fn test_content(num_bytes: usize) -> Vec<u8> {
+        (0..num_bytes).map(|i| (i % 251) as u8).collect()
+    }
+
+    #[tokio::test]
+    async fn test_encrypted_storage_range_reads() -> anyhow::Result<()> {
+        let (_temp_dir, context) = test_context(false)?;
+        let ram_storage = Arc::new(RamStorage::default());
+        let encrypted_storage =
+            EncryptedStorage::new(ram_storage.clone(), context, Some("key-1".to_string()));
+        let content = test_content(3 * DEFAULT_CHUNK_NUM_BYTES as usize + 17);
+        let path = Path::new("split");
+        encrypted_storage
+            .put(path, PutPayload::from(content.clone()))
+            .await?;
+
+        let raw_bytes = ram_storage.get_all(path).await?;
+        assert!(raw_bytes.starts_with(MAGIC_NUMBER));
+        assert!(!raw_bytes
+            .windows(64)
+            .any(|window| window == &content[1_000..1_064]));
+
+        assert_eq!(
+            encrypted_storage.file_num_bytes(path).await?,
+            content.len() as u64
+        );
+        assert_eq!(&encrypted_storage.get_all(path).await?[..], &content[..]);
+        let chunk_num_bytes = DEFAULT_CHUNK_NUM_BYTES as usize;
+        for byte_range in [
+            0..0,
+            0..10,
+            5..chunk_num_bytes,
+            chunk_num_bytes - 3..chunk_num_bytes + 3,
+            10..3 * chunk_num_bytes + 17,
+            3 * chunk_num_bytes..3 * chunk_num_bytes + 17,
+        ] {
+            let bytes = encrypted_storage
+                .get_slice(path, byte_range.clone())
+                .await?;
+            assert_eq!(&bytes[..], &content[byte_range]);
+        }
+        assert!(encrypted_storage
+            .get_slice(path, 0..content.len() + 1)
+            .await
+            .is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_encrypted_storage_local_file() -> anyhow::Result<()> {
+        let (temp_dir, context) = test_context(false)?;
+        let ram_storage = Arc::new(RamStorage::default());
+        let encrypted_storage =
+            EncryptedStorage::new(ram_storage.clone(), context, Some("key-1".to_string()));
+        let content =
+            test_content(NUM_CHUNKS_PER_COPY_BATCH * DEFAULT_CHUNK_NUM_BYTES as usize + 5);
+        let plaintext_path = temp_dir.path().join("bundle");
+        std::fs::write(&plaintext_path, &content)?;
+        let path = Path::new("split");
+        encrypted_storage
+            .put(path, PutPayload::from(plaintext_path.clone()))
+            .await?;
+        assert!(!encrypted_file_path(&plaintext_path).exists());
+
+        let output_path = temp_dir.path().join("output");
+        encrypted_storage.copy_to_file(path, &output_path).await?;
+        assert_eq!(std::fs::read(&output_path)?, content);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_encrypted_storage_reads_plaintext_files() -> anyhow::Result<()> {
+        let (_temp_dir, context) = test_context(false)?;
+        let ram_storage = Arc::new(RamStorage::default());
+        ram_storage
+            .put(Path::new("small"), PutPayload::from(&b"abc"[..]))
+            .await?;
+        ram_storage
+            .put(Path::new("large"), PutPayload::from(test_content(2_000)))
+            .await?;
+        let encrypted_storage = EncryptedStorage::new(ram_storage, context, None);
+        assert_eq!(
+            &encrypted_storage
+                .get_slice(Path::new("small"), 1..3)
+                .await?[..],
+            b"bc"
+        );
+        assert_eq!(
+            &encrypted_storage
+                .get_slice(Path::new("large"), 10..20)
+                .await?[..],
+            &test_content(2_000)[10..20]
+        );
+        assert_eq!(
+            encrypted_storage.file_num_bytes(Path::new("large")).await?,
+            2_000
+        );
+        // Without an encryption key ID, files are written in plaintext.
+        encrypted_storage
+            .put(Path::new("plain"), PutPayload::from(&b"hello"[..]))
+            .await?;
+        assert_eq!(
+            &encrypted_storage.get_all(Path::new("plain")).await?[..],
+            b"hello"
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_encrypted_storage_detects_tampering() -> anyhow::Result<()> {
+        let (_temp_dir, context) = test_context(false)?;
+        let ram_storage = Arc::new(RamStorage::default());
+        let encrypted_storage = EncryptedStorage::new(
+            ram_storage.clone(),
+            context.clone(),
+            Some("key-1".to_string()),
+        );
+        let path = Path::new("split");
+        encrypted_storage
+            .put(path, PutPayload::from(test_content(1_000)))
+            .await?;
+        let mut raw_bytes = ram_storage.get_all(path).await?.to_vec();
+        let last_byte_ord = raw_bytes.len() - 1;
+        raw_bytes[last_byte_ord] ^= 1;
+        ram_storage.put(path, PutPayload::from(raw_bytes)).await?;
+        let fresh_storage = EncryptedStorage::new(ram_storage, context, None);
+        assert!(fresh_storage.get_all(path).await.is_err());
+        assert!(fresh_storage.get_slice(path, 0..10).await.is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_encrypted_storage_detects_trailing_and_truncated_files() -> anyhow::Result<()> {
+        let (temp_dir, context) = test_context(false)?;
+        // Unlike the `RamStorage`, the `LocalFileStorage` fails on out of bounds reads,
+        // just like object storages.
+        let file_storage: Arc<dyn Storage> = Arc::new(LocalFileStorage::from_uri(&format!(
+            "file://{}",
+            temp_dir.path().display()
+        ))?);
+        let encrypted_storage = EncryptedStorage::new(
+            file_storage.clone(),
+            context.clone(),
+            Some("key-1".to_string()),
+        );
+        let content = test_content(DEFAULT_CHUNK_NUM_BYTES as usize + 10);
+        let path = Path::new("split");
+        encrypted_storage
+            .put(path, PutPayload::from(content.clone()))
+            .await?;
+        let raw_bytes = file_storage.get_all(path).await?.to_vec();
+
+        let mut trailing_bytes = raw_bytes.clone();
+        trailing_bytes.extend_from_slice(&[0u8; 100]);
+        file_storage
+            .put(path, PutPayload::from(trailing_bytes))
+            .await?;
+        assert!(encrypted_storage.get_all(path).await.is_err());
+
+        let truncated_bytes = raw_bytes[..raw_bytes.len() - 5].to_vec();
+        file_storage
+            .put(path, PutPayload::from(truncated_bytes))
+            .await?;
+        assert!(encrypted_storage.get_all(path).await.is_err());
+        assert!(encrypted_storage
+            .get_slice(path, content.len() - 3..content.len())
+            .await
+            .is_err());
+        assert_eq!(
+            &encrypted_storage.get_slice(path, 0..10).await?[..],
+            &content[..10]
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_encrypted_storage_rejects_plaintext_files() -> anyhow::Result<()> {
+        let ram_storage = Arc::new(RamStorage::default());
+        let path = Path::new("split");
+        ram_storage
+            .put(path, PutPayload::from(test_content(2_000)))
+            .await?;
+
+        let (_temp_dir, context) = test_context(false)?;
+        let encrypted_storage =
+            EncryptedStorage::new(ram_storage.clone(), context, Some("key-1".to_string()));
+        let get_slice_error = encrypted_storage.get_slice(path, 0..10).await.unwrap_err();
+        assert_eq!(get_slice_error.kind(), StorageErrorKind::Unauthorized);
+        assert!(encrypted_storage.get_all(path).await.is_err());
+        assert!(encrypted_storage.file_num_bytes(path).await.is_err());
+
+        let (_temp_dir, context) = test_context(true)?;
+        let migrating_storage =
+            EncryptedStorage::new(ram_storage, context, Some("key-1".to_string()));
+        assert_eq!(
+            &migrating_storage.get_slice(path, 0..10).await?[..],
+            &test_content(2_000)[..10]
+        );
+        Ok(())
+    }
+}
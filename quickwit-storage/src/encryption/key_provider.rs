@@ -0,0 +1,189 @@
+// Copyright (C) 2021 Quickwit, Inc.
+//
+// Quickwit is offered under the AGPL v3.0 and as commercial software.
+// For commercial licensing, contact us at hello@quickwit.io.
+//
+// AGPL:
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use anyhow::Context;
+use async_trait::async_trait;
+use chacha20poly1305::aead::{Aead, NewAead, Payload};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use rand::RngCore;
+use serde::Deserialize;
+
+use super::{DATA_KEY_NUM_BYTES, NONCE_NUM_BYTES};
+use crate::{StorageErrorKind, StorageResult};
+
+/// A key provider protects the data keys used to encrypt files, following the
+/// envelope encryption pattern.
+///
+/// Each encrypted file has its own data key. The data key is stored alongside the file,
+/// wrapped (encrypted) with a key encryption key that never leaves the key provider.
+/// Key encryption keys are identified by a key ID.
+#[async_trait]
+pub trait KeyProvider: Send + Sync + 'static {
+    /// Encrypts `data_key` with the key encryption key `key_id`.
+    async fn wrap_data_key(&self, key_id: &str, data_key: &[u8]) -> StorageResult<Vec<u8>>;
+
+    /// Decrypts a data key previously wrapped with the key encryption key `key_id`.
+    async fn unwrap_data_key(
+        &self,
+        key_id: &str,
+        wrapped_data_key: &[u8],
+    ) -> StorageResult<Vec<u8>>;
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct KeyFile {
+    keys: HashMap<String, String>,
+}
+
+/// A key provider reading its key encryption keys from a local JSON file of the form:
+/// `{"keys": {"<key ID>": "<base64 encoded 32 bytes key>"}}`.
+pub struct LocalKeyFileProvider {
+    ciphers: HashMap<String, ChaCha20Poly1305>,
+}
+
+impl LocalKeyFileProvider {
+    /// Loads the keys of the given key file.
+    pub fn open(key_file_path: &Path) -> anyhow::Result<Self> {
+        let key_file_content = std::fs::read(key_file_path)
+            .with_context(|| format!("Failed to read key file `{}`.", key_file_path.display()))?;
+        let key_file: KeyFile = serde_json::from_slice(&key_file_content)
+            .with_context(|| format!("Failed to parse key file `{}`.", key_file_path.display()))?;
+        let mut ciphers = HashMap::with_capacity(key_file.keys.len());
+        for (key_id, key_base64) in key_file.keys {
+            let key = base64::decode(&key_base64)
+                .with_context(|| format!("Key `{}` is not valid base64.", key_id))?;
+            if key.len() != DATA_KEY_NUM_BYTES {
+                anyhow::bail!(
+                    "Key `{}` must be {} bytes long, got {} bytes.",
+                    key_id,
+                    DATA_KEY_NUM_BYTES,
+                    key.len()
+                );
+            }
+            ciphers.insert(key_id, ChaCha20Poly1305::new(Key::from_slice(&key)));
+        }
+        Ok(LocalKeyFileProvider { ciphers })
+    }
+
+    fn cipher(&self, key_id: &str) -> StorageResult<&ChaCha20Poly1305> {
+        self.ciphers.get(key_id).ok_or_else(|| {
+            StorageErrorKind::Unauthorized
+                .with_error(anyhow::anyhow!("Unknown encryption key `{}`.", key_id))
+        })
+    }
+}
+
+#[async_trait]
+impl KeyProvider for LocalKeyFileProvider {
+    async fn wrap_data_key(&self, key_id: &str, data_key: &[u8]) -> StorageResult<Vec<u8>> {
+        let cipher = self.cipher(key_id)?;
+        let mut nonce = [0u8; NONCE_NUM_BYTES];
+        rand::thread_rng().fill_bytes(&mut nonce);
+        let payload = Payload {
+            msg: data_key,
+            aad: key_id.as_bytes(),
+        };
+        let wrapped_data_key =
+            cipher
+                .encrypt(Nonce::from_slice(&nonce), payload)
+                .map_err(|_| {
+                    StorageErrorKind::InternalError
+                        .with_error(anyhow::anyhow!("Failed to wrap data key."))
+                })?;
+        Ok([&nonce[..], &wrapped_data_key[..]].concat())
+    }
+
+    async fn unwrap_data_key(
+        &self,
+        key_id: &str,
+        wrapped_data_key: &[u8],
+    ) -> StorageResult<Vec<u8>> {
+        let cipher = self.cipher(key_id)?;
+        if wrapped_data_key.len() < NONCE_NUM_BYTES {
+            return Err(StorageErrorKind::InternalError
+                .with_error(anyhow::anyhow!("Wrapped data key is truncated.")));
+        }
+        let (nonce, wrapped_data_key) = wrapped_data_key.split_at(NONCE_NUM_BYTES);
+        let payload = Payload {
+            msg: wrapped_data_key,
+            aad: key_id.as_bytes(),
+        };
+        cipher
+            .decrypt(Nonce::from_slice(nonce), payload)
+            .map_err(|_| {
+                StorageErrorKind::Unauthorized.with_error(anyhow::anyhow!(
+                    "Failed to unwrap data key with encryption key `{}`.",
+                    key_id
+                ))
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_local_key_file_provider() -> anyhow::Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let key_file_path = temp_dir.path().join("keys.json");
+        let key_file_content = serde_json::json!({
+            "keys": {
+                "key-1": base64::encode([1u8; 32]),
+                "key-2": base64::encode([2u8; 32]),
+            }
+        });
+        std::fs::write(&key_file_path, key_file_content.to_string())?;
+        let key_provider = LocalKeyFileProvider::open(&key_file_path)?;
+        let data_key = [42u8; DATA_KEY_NUM_BYTES];
+        let wrapped_data_key = key_provider.wrap_data_key("key-1", &data_key).await?;
+        assert_ne!(&wrapped_data_key[NONCE_NUM_BYTES..], &data_key[..]);
+        let unwrapped_data_key = key_provider
+            .unwrap_data_key("key-1", &wrapped_data_key)
+            .await?;
+        assert_eq!(unwrapped_data_key, data_key);
+        let unwrap_error = key_provider
+            .unwrap_data_key("key-2", &wrapped_data_key)
+            .await
+            .unwrap_err();
+        assert_eq!(unwrap_error.kind(), StorageErrorKind::Unauthorized);
+        let unknown_key_error = key_provider
+            .wrap_data_key("key-3", &data_key)
+            .await
+            .unwrap_err();
+        assert_eq!(unknown_key_error.kind(), StorageErrorKind::Unauthorized);
+        Ok(())
+    }
+
+    #[test]
+    fn test_local_key_file_provider_invalid_key() -> anyhow::Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let key_file_path = temp_dir.path().join("keys.json");
+        let key_file_content = serde_json::json!({
+            "keys": {"key-1": base64::encode([1u8; 16])}
+        });
+        std::fs::write(&key_file_path, key_file_content.to_string())?;
+        assert!(LocalKeyFileProvider::open(&key_file_path).is_err());
+        Ok(())
+    }
+}
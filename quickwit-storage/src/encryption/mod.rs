@@ -0,0 +1,289 @@
+// Copyright (C) 2021 Quickwit, Inc.
+//
+// Quickwit is offered under the AGPL v3.0 and as commercial software.
+// For commercial licensing, contact us at hello@quickwit.io.
+//
+// AGPL:
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+//! Client-side envelope encryption of the files written to a storage.
+//!
+//! An encrypted file starts with a header holding the file's own data key, wrapped by a
+//! [`KeyProvider`], followed by the file content cut into fixed-size chunks, each of them sealed
+//! with ChaCha20-Poly1305:
+//!
+//! `[header][chunk 0 + tag][chunk 1 + tag]...[chunk n + tag]`
+//!
+//! Chunks make it possible to serve a range read by fetching and decrypting only the chunks
+//! overlapping with the range.
+
+mod encrypted_storage;
+mod key_provider;
+
+use std::convert::TryInto;
+use std::ops::Range;
+
+use chacha20poly1305::aead::{Aead, NewAead, Payload};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+
+pub(crate) use self::encrypted_storage::{EncryptedStorage, EncryptionContext};
+pub use self::key_provider::{KeyProvider, LocalKeyFileProvider};
+use crate::{StorageErrorKind, StorageResult};
+
+const MAGIC_NUMBER: &[u8; 8] = b"QWENC\x00\x00\x01";
+
+/// Upper bound of the header size. Reading that many bytes is enough to open any encrypted file.
+const MAX_HEADER_NUM_BYTES: usize = 1_024;
+
+/// Number of plaintext bytes per chunk.
+const DEFAULT_CHUNK_NUM_BYTES: u32 = 64 * 1_024;
+
+const DATA_KEY_NUM_BYTES: usize = 32;
+const NONCE_NUM_BYTES: usize = 12;
+const TAG_NUM_BYTES: usize = 16;
+
+/// The header of an encrypted file.
+///
+/// The serialized header is used as associated data for every chunk,
+/// so that tampering with the header is detected when decrypting the chunks.
+#[derive(Clone, Debug, Eq, PartialEq)]
+struct EncryptionHeader {
+    plaintext_num_bytes: u64,
+    chunk_num_bytes: u32,
+    key_id: String,
+    wrapped_data_key: Vec<u8>,
+}
+
+impl EncryptionHeader {
+    fn serialize(&self) -> StorageResult<Vec<u8>> {
+        let key_id_num_bytes: u16 = self.key_id.len().try_into().map_err(|_| {
+            StorageErrorKind::InternalError.with_error(anyhow::anyhow!("Key ID is too long."))
+        })?;
+        let wrapped_data_key_num_bytes: u16 =
+            self.wrapped_data_key.len().try_into().map_err(|_| {
+                StorageErrorKind::InternalError
+                    .with_error(anyhow::anyhow!("Wrapped data key is too long."))
+            })?;
+        let header_num_bytes = MAGIC_NUMBER.len()
+            + 4
+            + 8
+            + 4
+            + 2
+            + self.key_id.len()
+            + 2
+            + self.wrapped_data_key.len();
+        if header_num_bytes > MAX_HEADER_NUM_BYTES {
+            return Err(StorageErrorKind::InternalError.with_error(anyhow::anyhow!(
+                "Encryption header is too long ({} bytes).",
+                header_num_bytes
+            )));
+        }
+        let mut header_bytes = Vec::with_capacity(header_num_bytes);
+        header_bytes.extend_from_slice(MAGIC_NUMBER);
+        header_bytes.extend_from_slice(&(header_num_bytes as u32).to_le_bytes());
+        header_bytes.extend_from_slice(&self.plaintext_num_bytes.to_le_bytes());
+        header_bytes.extend_from_slice(&self.chunk_num_bytes.to_le_bytes());
+        header_bytes.extend_from_slice(&key_id_num_bytes.to_le_bytes());
+        header_bytes.extend_from_slice(self.key_id.as_bytes());
+        header_bytes.extend_from_slice(&wrapped_data_key_num_bytes.to_le_bytes());
+        header_bytes.extend_from_slice(&self.wrapped_data_key);
+        Ok(header_bytes)
+    }
+
+    /// Parses the header at the beginning of `bytes`, and returns it along with its length.
+    ///
+    /// Returns `None` if `bytes` does not start with an encryption header,
+    /// i.e. the file is not encrypted.
+    fn parse(bytes: &[u8]) -> StorageResult<Option<(EncryptionHeader, usize)>> {
+        if !bytes.starts_with(MAGIC_NUMBER) {
+            return Ok(None);
+        }
+        let mut reader = HeaderReader {
+            bytes,
+            position: MAGIC_NUMBER.len(),
+        };
+        let header_num_bytes = u32::from_le_bytes(reader.read_array()?) as usize;
+        let plaintext_num_bytes = u64::from_le_bytes(reader.read_array()?);
+        let chunk_num_bytes = u32::from_le_bytes(reader.read_array()?);
+        let key_id_num_bytes = u16::from_le_bytes(reader.read_array()?) as usize;
+        let key_id = String::from_utf8(reader.read(key_id_num_bytes)?.to_vec()).map_err(|_| {
+            StorageErrorKind::InternalError.with_error(anyhow::anyhow!("Key ID is not UTF-8."))
+        })?;
+        let wrapped_data_key_num_bytes = u16::from_le_bytes(reader.read_array()?) as usize;
+        let wrapped_data_key = reader.read(wrapped_data_key_num_bytes)?.to_vec();
+        if reader.position != header_num_bytes || chunk_num_bytes == 0 {
+            return Err(corrupted_header());
+        }
+        let header = EncryptionHeader {
+            plaintext_num_bytes,
+            chunk_num_bytes,
+            key_id,
+            wrapped_data_key,
+        };
+        Ok(Some((header, header_num_bytes)))
+    }
+}
+
+struct HeaderReader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> HeaderReader<'a> {
+    fn read(&mut self, num_bytes: usize) -> StorageResult<&'a [u8]> {
+        let bytes = self
+            .bytes
+            .get(self.position..self.position + num_bytes)
+            .ok_or_else(corrupted_header)?;
+        self.position += num_bytes;
+        Ok(bytes)
+    }
+
+    fn read_array<const N: usize>(&mut self) -> StorageResult<[u8; N]> {
+        Ok(self.read(N)?.try_into().unwrap())
+    }
+}
+
+fn corrupted_header() -> crate::StorageError {
+    StorageErrorKind::InternalError.with_error(anyhow::anyhow!("Corrupted encryption header."))
+}
+
+/// Describes how the content of an encrypted file is laid out, and holds its data key.
+struct EncryptedFileLayout {
+    header_bytes: Vec<u8>,
+    plaintext_num_bytes: u64,
+    chunk_num_bytes: usize,
+    cipher: ChaCha20Poly1305,
+}
+
+impl EncryptedFileLayout {
+    fn new(header_bytes: Vec<u8>, header: &EncryptionHeader, data_key: &[u8]) -> Self {
+        EncryptedFileLayout {
+            header_bytes,
+            plaintext_num_bytes: header.plaintext_num_bytes,
+            chunk_num_bytes: header.chunk_num_bytes as usize,
+            cipher: ChaCha20Poly1305::new(Key::from_slice(data_key)),
+        }
+    }
+
+    fn num_chunks(&self) -> usize {
+        let num_chunks = self.plaintext_num_bytes / self.chunk_num_bytes as u64
+            + (self.plaintext_num_bytes % self.chunk_num_bytes as u64 != 0) as u64;
+        num_chunks as usize
+    }
+
+    /// Returns the range of the chunk in the encrypted file, tag included.
+    ///
+    /// Fails if the file has no such chunk, e.g. because the ciphertext has trailing bytes.
+    fn chunk_ciphertext_range(&self, chunk_ord: usize) -> StorageResult<Range<usize>> {
+        self.checked_chunk_ciphertext_range(chunk_ord)
+            .ok_or_else(|| {
+                StorageErrorKind::InternalError.with_error(anyhow::anyhow!(
+                    "Encrypted file has no chunk {} ({} plaintext bytes).",
+                    chunk_ord,
+                    self.plaintext_num_bytes
+                ))
+            })
+    }
+
+    /// The plaintext size comes from the header, so a corrupted header must not make
+    /// the computation of the chunk range overflow.
+    fn checked_chunk_ciphertext_range(&self, chunk_ord: usize) -> Option<Range<usize>> {
+        let plaintext_start = (chunk_ord as u64).checked_mul(self.chunk_num_bytes as u64)?;
+        let remaining_num_bytes = self.plaintext_num_bytes.checked_sub(plaintext_start)?;
+        if remaining_num_bytes == 0 {
+            return None;
+        }
+        let chunk_plaintext_num_bytes = remaining_num_bytes.min(self.chunk_num_bytes as u64);
+        let start = chunk_ord
+            .checked_mul(self.chunk_num_bytes.checked_add(TAG_NUM_BYTES)?)?
+            .checked_add(self.header_bytes.len())?;
+        let end = start
+            .checked_add(chunk_plaintext_num_bytes as usize)?
+            .checked_add(TAG_NUM_BYTES)?;
+        Some(start..end)
+    }
+
+    /// Returns the range of chunks overlapping with the non-empty plaintext `byte_range`.
+    fn chunk_ords(&self, byte_range: &Range<usize>) -> Range<usize> {
+        byte_range.start / self.chunk_num_bytes..(byte_range.end - 1) / self.chunk_num_bytes + 1
+    }
+
+    /// Returns the range of the encrypted file holding the given non-empty range of chunks.
+    fn chunks_ciphertext_range(&self, chunk_ords: &Range<usize>) -> StorageResult<Range<usize>> {
+        let start = self.chunk_ciphertext_range(chunk_ords.start)?.start;
+        let end = self.chunk_ciphertext_range(chunk_ords.end - 1)?.end;
+        Ok(start..end)
+    }
+
+    fn nonce(chunk_ord: usize) -> [u8; NONCE_NUM_BYTES] {
+        // The data key is unique to the file, so the chunk ordinal is a valid nonce.
+        let mut nonce = [0u8; NONCE_NUM_BYTES];
+        nonce[..8].copy_from_slice(&(chunk_ord as u64).to_le_bytes());
+        nonce
+    }
+
+    fn encrypt_chunk(&self, chunk_ord: usize, plaintext: &[u8]) -> StorageResult<Vec<u8>> {
+        let payload = Payload {
+            msg: plaintext,
+            aad: &self.header_bytes,
+        };
+        self.cipher
+            .encrypt(Nonce::from_slice(&Self::nonce(chunk_ord)), payload)
+            .map_err(|_| {
+                StorageErrorKind::InternalError
+                    .with_error(anyhow::anyhow!("Failed to encrypt chunk {}.", chunk_ord))
+            })
+    }
+
+    /// Decrypts the chunks starting at `first_chunk_ord` contained in `ciphertext`,
+    /// and appends their plaintext to `output`.
+    fn decrypt_chunks(
+        &self,
+        first_chunk_ord: usize,
+        ciphertext: &[u8],
+        output: &mut Vec<u8>,
+    ) -> StorageResult<()> {
+        let mut chunk_ord = first_chunk_ord;
+        let mut position = 0;
+        while position < ciphertext.len() {
+            let chunk_num_bytes = self.chunk_ciphertext_range(chunk_ord)?.len();
+            let chunk_ciphertext = ciphertext
+                .get(position..position.saturating_add(chunk_num_bytes))
+                .ok_or_else(|| {
+                    StorageErrorKind::InternalError.with_error(anyhow::anyhow!(
+                        "Encrypted chunk {} is truncated.",
+                        chunk_ord
+                    ))
+                })?;
+            let payload = Payload {
+                msg: chunk_ciphertext,
+                aad: &self.header_bytes,
+            };
+            let plaintext = self
+                .cipher
+                .decrypt(Nonce::from_slice(&Self::nonce(chunk_ord)), payload)
+                .map_err(|_| {
+                    StorageErrorKind::InternalError.with_error(anyhow::anyhow!(
+                        "Failed to decrypt chunk {}. The file is corrupted.",
+                        chunk_ord
+                    ))
+                })?;
+            output.extend_from_slice(&plaintext);
+            position += chunk_num_bytes;
+            chunk_ord += 1;
+        }
+        Ok(())
+    }
+}
@@ -17,8 +17,10 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program. If not, see <http://www.gnu.org/licenses/>.
 
+use std::collections::BTreeMap;
 use std::{fmt, io};
 
+use quickwit_common::service_error::{ErrorCode, ServiceError};
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
@@ -60,6 +62,15 @@ pub enum StorageResolverError {
 }
 
 impl StorageErrorKind {
+    /// Returns the API error code matching this kind of error.
+    pub fn error_code(&self) -> ErrorCode {
+        match self {
+            StorageErrorKind::DoesNotExist => ErrorCode::NotFound,
+            StorageErrorKind::Unauthorized | StorageErrorKind::InternalError => ErrorCode::Internal,
+            StorageErrorKind::Service | StorageErrorKind::Io => ErrorCode::Unavailable,
+        }
+    }
+
     /// Creates a StorageError.
     pub fn with_error<E>(self, source: E) -> StorageError
     where anyhow::Error: From<E> {
@@ -80,6 +91,24 @@ impl From<StorageError> for io::Error {
     }
 }
 
+impl ServiceError for StorageResolverError {
+    fn error_code(&self) -> ErrorCode {
+        match self {
+            StorageResolverError::InvalidUri { .. }
+            | StorageResolverError::ProtocolUnsupported { .. } => ErrorCode::Internal,
+            StorageResolverError::FailedToOpenStorage { kind, .. } => kind.error_code(),
+        }
+    }
+
+    fn error_details(&self) -> BTreeMap<String, String> {
+        let mut details = BTreeMap::new();
+        if let StorageResolverError::FailedToOpenStorage { kind, .. } = self {
+            details.insert("storage_error_kind".to_string(), format!("{:?}", kind));
+        }
+        details
+    }
+}
+
 /// Generic StorageError.
 #[derive(Error, Debug)]
 #[error("StorageError(kind={kind:?}, source={source})")]
@@ -109,6 +138,18 @@ impl StorageError {
     }
 }
 
+impl ServiceError for StorageError {
+    fn error_code(&self) -> ErrorCode {
+        self.kind.error_code()
+    }
+
+    fn error_details(&self) -> BTreeMap<String, String> {
+        let mut details = BTreeMap::new();
+        details.insert("storage_error_kind".to_string(), format!("{:?}", self.kind));
+        details
+    }
+}
+
 impl From<io::Error> for StorageError {
     fn from(err: io::Error) -> StorageError {
         match err.kind() {
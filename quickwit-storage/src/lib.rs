@@ -33,8 +33,10 @@ mod storage;
 pub use self::storage::{PutPayload, Storage};
 
 mod bundle_storage;
+mod encryption;
 mod error;
 mod local_file_storage;
+mod metrics;
 mod object_storage;
 mod prefix_storage;
 mod ram_storage;
@@ -46,6 +48,7 @@ pub use self::bundle_storage::{
 };
 #[cfg(any(test, feature = "testsuite"))]
 pub use self::cache::MockCache;
+pub use self::encryption::{KeyProvider, LocalKeyFileProvider};
 pub use self::local_file_storage::{LocalFileStorage, LocalFileStorageFactory};
 pub use self::object_storage::{
     MultiPartPolicy, RegionProvider, S3CompatibleObjectStorage, S3CompatibleObjectStorageFactory,
@@ -61,7 +64,10 @@ pub use self::storage_resolver::{
 };
 #[cfg(feature = "testsuite")]
 pub use self::tests::storage_test_suite;
-pub use crate::cache::{Cache, MemorySizedCache, SliceCache, StorageWithCacheFactory};
+pub use crate::cache::{
+    add_prefix_to_cache, wrap_storage_with_cache, Cache, CacheStats, LocalDiskCache,
+    MemorySizedCache, PrefetchedFilesCache, ReadCoalescer, SliceCache, StorageWithCacheFactory,
+};
 pub use crate::error::{StorageError, StorageErrorKind, StorageResolverError, StorageResult};
 
 #[cfg(any(test, feature = "testsuite"))]
@@ -186,6 +192,32 @@ pub(crate) mod tests {
         Ok(())
     }
 
+    async fn test_list_directories(storage: &mut dyn Storage) -> anyhow::Result<()> {
+        let test_paths = [
+            "list_directories/foo/file",
+            "list_directories/bar/baz/file",
+            "list_directories/file",
+        ];
+        for test_path in &test_paths {
+            storage
+                .put(Path::new(test_path), PutPayload::from(b"".as_ref()))
+                .await?;
+        }
+        let mut directories = storage
+            .list_directories(Path::new("list_directories"))
+            .await?;
+        directories.sort();
+        assert_eq!(directories, vec!["bar".to_string(), "foo".to_string()]);
+        for test_path in &test_paths {
+            storage.delete(Path::new(test_path)).await?;
+        }
+        assert!(storage
+            .list_directories(Path::new("list_directories"))
+            .await?
+            .is_empty());
+        Ok(())
+    }
+
     /// Generic test suite for a storage.
     pub async fn storage_test_suite(storage: &mut dyn Storage) -> anyhow::Result<()> {
         test_get_inexistent_file(storage)
@@ -213,6 +245,9 @@ pub(crate) mod tests {
         test_delete_missing_file(storage)
             .await
             .with_context(|| "delete_missing_file")?;
+        test_list_directories(storage)
+            .await
+            .with_context(|| "list_directories")?;
         Ok(())
     }
 }
@@ -30,8 +30,12 @@ use tokio::fs;
 use tokio::io::{AsyncReadExt, AsyncSeekExt};
 use tracing::warn;
 
+use crate::metrics::STORAGE_METRICS;
 use crate::{PutPayload, Storage, StorageErrorKind, StorageFactory, StorageResult};
 
+/// Label of the local file storage in the storage metrics.
+const BACKEND: &str = "local_file";
+
 /// File system compatible storage implementation.
 #[derive(Clone)]
 pub struct LocalFileStorage {
@@ -126,39 +130,47 @@ fn missing_file_is_ok(io_result: io::Result<()>) -> io::Result<()> {
 #[async_trait]
 impl Storage for LocalFileStorage {
     async fn put(&self, path: &Path, payload: PutPayload) -> crate::StorageResult<()> {
+        let request_guard = STORAGE_METRICS.start_request(BACKEND, "put");
         let full_path = self.root.join(path);
         if let Some(parent_dir) = full_path.parent() {
             fs::create_dir_all(parent_dir).await?;
         }
-        match payload {
+        let num_bytes = match payload {
             PutPayload::InMemory(data) => {
+                let num_bytes = data.len() as u64;
                 fs::write(full_path, data).await?;
+                num_bytes
             }
-            PutPayload::LocalFile(filepath) => {
-                fs::copy(filepath, full_path).await?;
-            }
+            PutPayload::LocalFile(filepath) => fs::copy(filepath, full_path).await?,
         };
+        request_guard.record_write(num_bytes);
         Ok(())
     }
 
     async fn copy_to_file(&self, path: &Path, output_path: &Path) -> StorageResult<()> {
+        let request_guard = STORAGE_METRICS.start_request(BACKEND, "copy_to_file");
         let full_path = self.root.join(path);
-        fs::copy(full_path, output_path).await?;
+        let num_bytes = fs::copy(full_path, output_path).await?;
+        request_guard.record_read(num_bytes);
         Ok(())
     }
 
     async fn get_slice(&self, path: &Path, range: Range<usize>) -> StorageResult<Bytes> {
+        let request_guard = STORAGE_METRICS.start_request(BACKEND, "get_slice");
         let full_path = self.root.join(path);
         let mut file = fs::File::open(full_path).await?;
         file.seek(SeekFrom::Start(range.start as u64)).await?;
         let mut content_bytes = vec![0u8; range.len()];
         file.read_exact(&mut content_bytes).await?;
+        request_guard.record_read(range.len() as u64);
         Ok(Bytes::from(content_bytes))
     }
 
     async fn delete(&self, path: &Path) -> StorageResult<()> {
+        let request_guard = STORAGE_METRICS.start_request(BACKEND, "delete");
         let full_path = self.root.join(path);
         missing_file_is_ok(fs::remove_file(full_path).await)?;
+        request_guard.record_success();
         let parent = path.parent();
         if parent.is_none() {
             return Ok(());
@@ -171,8 +183,10 @@ impl Storage for LocalFileStorage {
     }
 
     async fn get_all(&self, path: &Path) -> StorageResult<Bytes> {
+        let request_guard = STORAGE_METRICS.start_request(BACKEND, "get_all");
         let full_path = self.root.join(path);
         let content_bytes = fs::read(full_path).await?;
+        request_guard.record_read(content_bytes.len() as u64);
         Ok(Bytes::from(content_bytes))
     }
 
@@ -200,6 +214,22 @@ impl Storage for LocalFileStorage {
             }
         }
     }
+
+    async fn list_directories(&self, path: &Path) -> StorageResult<Vec<String>> {
+        let full_path = self.root.join(path);
+        let mut dir_entries = match fs::read_dir(full_path).await {
+            Ok(dir_entries) => dir_entries,
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(err) => return Err(err.into()),
+        };
+        let mut directories = Vec::new();
+        while let Some(dir_entry) = dir_entries.next_entry().await? {
+            if dir_entry.file_type().await?.is_dir() {
+                directories.push(dir_entry.file_name().to_string_lossy().to_string());
+            }
+        }
+        Ok(directories)
+    }
 }
 
 /// A File storage resolver
@@ -247,6 +277,21 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_storage_metrics_count_failed_requests() -> anyhow::Result<()> {
+        let tempdir = tempdir()?;
+        let file_storage =
+            LocalFileStorage::from_uri(&format!("file://{}", tempdir.path().to_string_lossy()))?;
+        let error_counter = STORAGE_METRICS
+            .requests_total
+            .with_label_values(&[BACKEND, "get_all", "error"]);
+        let num_errors_before = error_counter.get();
+        assert!(file_storage.get_all(Path::new("missing")).await.is_err());
+        // Other tests may run concurrently, hence the inequality.
+        assert!(error_counter.get() > num_errors_before);
+        Ok(())
+    }
+
     #[test]
     fn test_storage_fail_if_uri_is_not_safe() {
         let storage = LocalFileStorage::from_uri("file:///tmp/../not_ok");
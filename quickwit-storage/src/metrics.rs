@@ -0,0 +1,121 @@
+// Copyright (C) 2021 Quickwit, Inc.
+//
+// Quickwit is offered under the AGPL v3.0 and as commercial software.
+// For commercial licensing, contact us at hello@quickwit.io.
+//
+// AGPL:
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use once_cell::sync::Lazy;
+use quickwit_common::metrics::{new_counter_vec, IntCounterVec};
+
+pub(crate) struct StorageMetrics {
+    pub requests_total: IntCounterVec,
+    pub read_bytes_total: IntCounterVec,
+    pub written_bytes_total: IntCounterVec,
+}
+
+impl Default for StorageMetrics {
+    fn default() -> Self {
+        StorageMetrics {
+            requests_total: new_counter_vec(
+                "quickwit_storage_requests_total",
+                "Number of requests emitted to the storage backends, by outcome.",
+                &["backend", "operation", "status"],
+            ),
+            read_bytes_total: new_counter_vec(
+                "quickwit_storage_read_bytes_total",
+                "Number of bytes read from the storage backends.",
+                &["backend"],
+            ),
+            written_bytes_total: new_counter_vec(
+                "quickwit_storage_written_bytes_total",
+                "Number of bytes written to the storage backends.",
+                &["backend"],
+            ),
+        }
+    }
+}
+
+impl StorageMetrics {
+    /// Starts tracking a request to a storage backend.
+    ///
+    /// The request is counted as an error, unless the returned guard is completed.
+    pub fn start_request(
+        &'static self,
+        backend: &'static str,
+        operation: &'static str,
+    ) -> StorageRequestGuard {
+        StorageRequestGuard {
+            metrics: self,
+            backend,
+            operation,
+            completed: false,
+        }
+    }
+}
+
+/// Counts a storage request once it is over, labelled with its outcome.
+pub(crate) struct StorageRequestGuard {
+    metrics: &'static StorageMetrics,
+    backend: &'static str,
+    operation: &'static str,
+    completed: bool,
+}
+
+impl StorageRequestGuard {
+    fn complete(mut self) {
+        self.metrics
+            .requests_total
+            .with_label_values(&[self.backend, self.operation, "ok"])
+            .inc();
+        self.completed = true;
+    }
+
+    pub fn record_read(self, num_bytes: u64) {
+        self.metrics
+            .read_bytes_total
+            .with_label_values(&[self.backend])
+            .inc_by(num_bytes);
+        self.complete();
+    }
+
+    pub fn record_write(self, num_bytes: u64) {
+        self.metrics
+            .written_bytes_total
+            .with_label_values(&[self.backend])
+            .inc_by(num_bytes);
+        self.complete();
+    }
+
+    pub fn record_success(self) {
+        self.complete();
+    }
+}
+
+impl Drop for StorageRequestGuard {
+    fn drop(&mut self) {
+        if !self.completed {
+            self.metrics
+                .requests_total
+                .with_label_values(&[self.backend, self.operation, "error"])
+                .inc();
+        }
+    }
+}
+
+/// Storage metrics, labelled by backend (`s3`, `local_file`).
+///
+/// Requests are also labelled by status: `ok`, or `error` for failed and abandoned requests.
+pub(crate) static STORAGE_METRICS: Lazy<StorageMetrics> = Lazy::new(StorageMetrics::default);
@@ -23,7 +23,8 @@ use std::{fmt, io};
 use rusoto_core::RusotoError;
 use rusoto_s3::{
     AbortMultipartUploadError, CompleteMultipartUploadError, CreateMultipartUploadError,
-    DeleteObjectError, GetObjectError, HeadObjectError, PutObjectError, UploadPartError,
+    DeleteObjectError, GetObjectError, HeadObjectError, ListObjectsV2Error, PutObjectError,
+    UploadPartError,
 };
 
 use crate::retry::IsRetryable;
@@ -144,3 +145,11 @@ impl ToStorageErrorKind for HeadObjectError {
         StorageErrorKind::Service
     }
 }
+
+impl ToStorageErrorKind for ListObjectsV2Error {
+    fn to_storage_error_kind(&self) -> StorageErrorKind {
+        match self {
+            ListObjectsV2Error::NoSuchBucket(_) => StorageErrorKind::DoesNotExist,
+        }
+    }
+}
@@ -34,8 +34,8 @@ use rusoto_core::{ByteStream, HttpClient, HttpConfig, Region, RusotoError};
 use rusoto_s3::{
     AbortMultipartUploadRequest, CompleteMultipartUploadRequest, CompletedMultipartUpload,
     CompletedPart, CreateMultipartUploadError, CreateMultipartUploadRequest, DeleteObjectRequest,
-    GetObjectRequest, HeadObjectError, HeadObjectRequest, PutObjectError, PutObjectRequest,
-    S3Client, UploadPartRequest, S3,
+    GetObjectRequest, HeadObjectError, HeadObjectRequest, ListObjectsV2Request, PutObjectError,
+    PutObjectRequest, S3Client, UploadPartRequest, S3,
 };
 use tokio::fs::File;
 use tokio::io::{AsyncReadExt, AsyncWriteExt, BufReader};
@@ -43,11 +43,15 @@ use tokio_util::io::ReaderStream;
 use tracing::warn;
 
 use super::error::RusotoErrorWrapper;
+use crate::metrics::STORAGE_METRICS;
 use crate::object_storage::file_slice_stream::FileSliceStream;
 use crate::object_storage::MultiPartPolicy;
 use crate::retry::{retry, IsRetryable, Retry};
 use crate::{PutPayload, Storage, StorageError, StorageErrorKind, StorageResult};
 
+/// Label of the S3 compatible object storage in the storage metrics.
+const BACKEND: &str = "s3";
+
 /// A credential timeout.
 const CREDENTIAL_TIMEOUT: u64 = 5;
 
@@ -490,6 +494,7 @@ async fn download_all(byte_stream: &mut ByteStream, output: &mut Vec<u8>) -> io:
 #[async_trait]
 impl Storage for S3CompatibleObjectStorage {
     async fn put(&self, path: &Path, payload: PutPayload) -> StorageResult<()> {
+        let request_guard = STORAGE_METRICS.start_request(BACKEND, "put");
         let key = self.key(path);
         let len = payload.len().await?;
         let part_num_bytes = self.multipart_policy.part_num_bytes(len);
@@ -499,11 +504,13 @@ impl Storage for S3CompatibleObjectStorage {
             self.put_multi_part(&key, payload, part_num_bytes, len)
                 .await?;
         }
+        request_guard.record_write(len);
         Ok(())
     }
 
     // TODO implement multipart
     async fn copy_to_file(&self, path: &Path, output_path: &Path) -> StorageResult<()> {
+        let request_guard = STORAGE_METRICS.start_request(BACKEND, "copy_to_file");
         let get_object_req = self.create_get_object_request(path, None);
         let get_object_output = retry(|| async {
             self.s3_client
@@ -517,12 +524,14 @@ impl Storage for S3CompatibleObjectStorage {
         })?;
         let mut body_read = BufReader::new(body.into_async_read());
         let mut dest_file = File::create(output_path).await?;
-        tokio::io::copy_buf(&mut body_read, &mut dest_file).await?;
+        let num_bytes = tokio::io::copy_buf(&mut body_read, &mut dest_file).await?;
         dest_file.flush().await?;
+        request_guard.record_read(num_bytes);
         Ok(())
     }
 
     async fn delete(&self, path: &Path) -> StorageResult<()> {
+        let request_guard = STORAGE_METRICS.start_request(BACKEND, "delete");
         let key = self.key(path);
         let delete_object_req = DeleteObjectRequest {
             bucket: self.bucket.clone(),
@@ -536,13 +545,18 @@ impl Storage for S3CompatibleObjectStorage {
                 .map_err(RusotoErrorWrapper::from)
         })
         .await?;
+        request_guard.record_success();
         Ok(())
     }
 
     async fn get_slice(&self, path: &Path, range: Range<usize>) -> StorageResult<Bytes> {
+        let request_guard = STORAGE_METRICS.start_request(BACKEND, "get_slice");
         self.get_to_vec(path, Some(range.clone()))
             .await
-            .map(Bytes::from)
+            .map(|bytes| {
+                request_guard.record_read(bytes.len() as u64);
+                Bytes::from(bytes)
+            })
             .map_err(|err| {
                 err.add_context(format!(
                     "Failed to fetch slice {:?} for object: {}",
@@ -553,9 +567,13 @@ impl Storage for S3CompatibleObjectStorage {
     }
 
     async fn get_all(&self, path: &Path) -> StorageResult<Bytes> {
+        let request_guard = STORAGE_METRICS.start_request(BACKEND, "get_all");
         self.get_to_vec(path, None)
             .await
-            .map(Bytes::from)
+            .map(|bytes| {
+                request_guard.record_read(bytes.len() as u64);
+                Bytes::from(bytes)
+            })
             .map_err(|err| err.add_context(format!("Failed to fetch object: {}", self.uri(path))))
     }
 
@@ -606,6 +624,51 @@ impl Storage for S3CompatibleObjectStorage {
             Err(err) => Err(err.into()),
         }
     }
+
+    async fn list_directories(&self, path: &Path) -> StorageResult<Vec<String>> {
+        let request_guard = STORAGE_METRICS.start_request(BACKEND, "list_directories");
+        let mut key_prefix = self.key(path);
+        if !key_prefix.is_empty() && !key_prefix.ends_with('/') {
+            key_prefix.push('/');
+        }
+        let mut directories = Vec::new();
+        let mut continuation_token = None;
+        loop {
+            let list_objects_req = ListObjectsV2Request {
+                bucket: self.bucket.clone(),
+                prefix: Some(key_prefix.clone()),
+                delimiter: Some("/".to_string()),
+                continuation_token: continuation_token.clone(),
+                ..Default::default()
+            };
+            let list_objects_output = retry(|| async {
+                self.s3_client
+                    .list_objects_v2(list_objects_req.clone())
+                    .await
+                    .map_err(RusotoErrorWrapper::from)
+            })
+            .await?;
+            // Common prefixes look like `<key prefix><directory>/`.
+            directories.extend(
+                list_objects_output
+                    .common_prefixes
+                    .unwrap_or_default()
+                    .into_iter()
+                    .filter_map(|common_prefix| common_prefix.prefix)
+                    .filter_map(|prefix| {
+                        let directory = prefix.strip_prefix(&key_prefix)?.trim_end_matches('/');
+                        Some(directory.to_string())
+                    }),
+            );
+            continuation_token = list_objects_output.next_continuation_token;
+            if continuation_token.is_none() {
+                break;
+            }
+        }
+        request_guard.record_success();
+        Ok(directories)
+    }
+
     fn uri(&self) -> String {
         format!("s3://{}/{}", self.bucket, self.prefix.to_string_lossy())
     }
@@ -71,6 +71,10 @@ impl Storage for PrefixStorage {
     async fn file_num_bytes(&self, path: &Path) -> crate::StorageResult<u64> {
         self.storage.file_num_bytes(&self.prefix.join(path)).await
     }
+
+    async fn list_directories(&self, path: &Path) -> crate::StorageResult<Vec<String>> {
+        self.storage.list_directories(&self.prefix.join(path)).await
+    }
 }
 
 /// Creates a [`PrefixStorage`] using an underlying storage and a prefix.
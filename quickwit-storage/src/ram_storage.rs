@@ -17,7 +17,7 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program. If not, see <http://www.gnu.org/licenses/>.
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::ops::Range;
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
@@ -126,6 +126,23 @@ impl Storage for RamStorage {
             Err(StorageErrorKind::DoesNotExist.with_error(err))
         }
     }
+
+    async fn list_directories(&self, path: &Path) -> StorageResult<Vec<String>> {
+        let directories: HashSet<String> = self
+            .files
+            .read()
+            .await
+            .keys()
+            .filter_map(|file_path| {
+                let mut components = file_path.strip_prefix(path).ok()?.components();
+                let first_component = components.next()?;
+                // Files right under `path` are not directories.
+                components.next()?;
+                Some(first_component.as_os_str().to_string_lossy().to_string())
+            })
+            .collect();
+        Ok(directories.into_iter().collect())
+    }
 }
 
 /// Builder to create a prepopulated [`RamStorage`]. This is mostly useful for tests.
@@ -118,6 +118,10 @@ pub trait Storage: Send + Sync + 'static {
     /// Returns a file size.
     async fn file_num_bytes(&self, path: &Path) -> StorageResult<u64>;
 
+    /// Returns the names of the directories directly under `path`, i.e. the distinct first
+    /// components of the paths of the files stored under `path`, in no particular order.
+    async fn list_directories(&self, path: &Path) -> StorageResult<Vec<String>>;
+
     /// Returns an URI identifying the storage
     fn uri(&self) -> String;
 }
@@ -23,9 +23,13 @@ use std::sync::Arc;
 
 use once_cell::sync::OnceCell;
 
+use crate::encryption::{EncryptedStorage, EncryptionContext};
 use crate::local_file_storage::LocalFileStorageFactory;
 use crate::ram_storage::RamStorageFactory;
-use crate::{RegionProvider, S3CompatibleObjectStorageFactory, Storage, StorageResolverError};
+use crate::{
+    KeyProvider, RegionProvider, S3CompatibleObjectStorageFactory, Storage, StorageErrorKind,
+    StorageResolverError,
+};
 
 /// Quickwit supported storage resolvers.
 pub fn quickwit_storage_uri_resolver() -> &'static StorageUriResolver {
@@ -57,6 +61,7 @@ pub trait StorageFactory: Send + Sync + 'static {
 #[derive(Clone)]
 pub struct StorageUriResolver {
     per_protocol_resolver: Arc<HashMap<String, Arc<dyn StorageFactory>>>,
+    encryption_context_opt: Option<Arc<EncryptionContext>>,
 }
 
 #[derive(Default)]
@@ -79,6 +84,7 @@ impl StorageUriResolverBuilder {
     pub fn build(self) -> StorageUriResolver {
         StorageUriResolver {
             per_protocol_resolver: Arc::new(self.per_protocol_resolver),
+            encryption_context_opt: None,
         }
     }
 }
@@ -101,8 +107,58 @@ impl StorageUriResolver {
             .build()
     }
 
+    /// Returns a copy of this resolver whose storages transparently decrypt the
+    /// encrypted files they read, using `key_provider` to unwrap their data keys.
+    ///
+    /// Storages resolved with an encryption key refuse to read files that are not encrypted,
+    /// unless `allow_plaintext_reads` is set, e.g. while migrating an index to encryption.
+    pub fn with_encryption_key_provider(
+        &self,
+        key_provider: Arc<dyn KeyProvider>,
+        allow_plaintext_reads: bool,
+    ) -> StorageUriResolver {
+        StorageUriResolver {
+            per_protocol_resolver: self.per_protocol_resolver.clone(),
+            encryption_context_opt: Some(Arc::new(EncryptionContext::new(
+                key_provider,
+                allow_plaintext_reads,
+            ))),
+        }
+    }
+
     /// Resolves the given URI.
     pub fn resolve(&self, uri: &str) -> Result<Arc<dyn Storage>, StorageResolverError> {
+        self.resolve_with_encryption_key(uri, None)
+    }
+
+    /// Resolves the given URI into a storage encrypting the files it writes with
+    /// the key `encryption_key_id`, if any.
+    ///
+    /// Fails if an encryption key is requested but this resolver has no key provider.
+    pub fn resolve_with_encryption_key(
+        &self,
+        uri: &str,
+        encryption_key_id_opt: Option<&str>,
+    ) -> Result<Arc<dyn Storage>, StorageResolverError> {
+        let storage = self.resolve_unencrypted(uri)?;
+        match (&self.encryption_context_opt, encryption_key_id_opt) {
+            (Some(encryption_context), _) => Ok(Arc::new(EncryptedStorage::new(
+                storage,
+                encryption_context.clone(),
+                encryption_key_id_opt.map(str::to_string),
+            ))),
+            (None, Some(encryption_key_id)) => Err(StorageResolverError::FailedToOpenStorage {
+                kind: StorageErrorKind::Unauthorized,
+                message: format!(
+                    "No key provider is configured to encrypt with key `{}`",
+                    encryption_key_id
+                ),
+            }),
+            (None, None) => Ok(storage),
+        }
+    }
+
+    fn resolve_unencrypted(&self, uri: &str) -> Result<Arc<dyn Storage>, StorageResolverError> {
         let protocol = uri
             .split("://")
             .next()
@@ -191,4 +247,16 @@ mod tests {
             Err(crate::StorageResolverError::ProtocolUnsupported { protocol }) if protocol == "protocol"
         ));
     }
+
+    #[test]
+    fn test_storage_resolver_encryption_key_requires_key_provider() {
+        let storage_resolver = StorageUriResolver::for_test();
+        assert!(matches!(
+            storage_resolver.resolve_with_encryption_key("ram://hello", Some("key-1")),
+            Err(crate::StorageResolverError::FailedToOpenStorage {
+                kind: StorageErrorKind::Unauthorized,
+                ..
+            })
+        ));
+    }
 }
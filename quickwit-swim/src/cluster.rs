@@ -1,18 +1,21 @@
+use std::collections::BTreeMap;
 use std::convert::AsRef;
 use std::net::SocketAddr;
 
 use flume;
+use mio::Waker;
 use tracing::debug;
 use uuid::Uuid;
 
 use super::state::ArtilleryEpidemic;
 use crate::cluster_config::ClusterConfig;
 use crate::errors::*;
-use crate::state::{ArtilleryClusterEvent, ArtilleryClusterRequest};
+use crate::state::{ArtilleryClusterEvent, ArtilleryClusterRequest, WAKER};
 
 #[derive(Debug)]
 pub struct Cluster {
     comm: flume::Sender<ArtilleryClusterRequest>,
+    waker: Waker,
 }
 
 impl Cluster {
@@ -25,6 +28,7 @@ impl Cluster {
 
         let (poll, state) =
             ArtilleryEpidemic::new(host_key, config, event_tx, internal_tx.clone())?;
+        let waker = Waker::new(poll.registry(), WAKER)?;
 
         debug!("Starting Artillery Cluster");
         tokio::task::spawn_blocking(move || {
@@ -32,30 +36,45 @@ impl Cluster {
                 .expect("Failed to create event loop");
         });
 
-        let cluster = Cluster { comm: internal_tx };
+        let cluster = Cluster {
+            comm: internal_tx,
+            waker,
+        };
         Ok((cluster, event_rx))
     }
 
+    /// Submits a request to the event loop, waking it up so that the request does not wait
+    /// for the next ping interval.
+    fn send(&self, request: ArtilleryClusterRequest) -> Result<()> {
+        self.comm.send(request)?;
+        self.waker.wake()?;
+        Ok(())
+    }
+
     pub fn add_seed_node(&self, addr: SocketAddr) {
-        let _ = self.comm.send(ArtilleryClusterRequest::AddSeed(addr));
+        let _ = self.send(ArtilleryClusterRequest::AddSeed(addr));
     }
 
     pub fn send_payload<T: AsRef<str>>(&self, id: Uuid, msg: T) {
-        self.comm
-            .send(ArtilleryClusterRequest::Payload(
-                id,
-                msg.as_ref().to_string(),
-            ))
-            .unwrap();
+        self.send(ArtilleryClusterRequest::Payload(
+            id,
+            msg.as_ref().to_string(),
+        ))
+        .unwrap();
+    }
+
+    /// Replaces the metadata gossiped along with this node.
+    pub fn set_metadata(&self, metadata: BTreeMap<String, String>) {
+        let _ = self.send(ArtilleryClusterRequest::SetMetadata(metadata));
     }
 
     pub fn leave_cluster(&self) {
-        let _ = self.comm.send(ArtilleryClusterRequest::LeaveCluster);
+        let _ = self.send(ArtilleryClusterRequest::LeaveCluster);
     }
 }
 
 impl Drop for Cluster {
     fn drop(&mut self) {
-        let _ = self.comm.send(ArtilleryClusterRequest::Exit);
+        let _ = self.send(ArtilleryClusterRequest::Exit);
     }
 }
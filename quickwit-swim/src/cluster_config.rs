@@ -1,6 +1,8 @@
 use std::net::{SocketAddr, ToSocketAddrs};
 use std::time::Duration;
 
+use crate::encryption::MessageCipher;
+
 // ARTIL = 27845
 /// Default Epidemic Port
 pub const CONST_INFECTION_PORT: u16 = 27845;
@@ -15,9 +17,17 @@ pub struct ClusterConfig {
     pub cluster_key: Vec<u8>,
     pub ping_interval: Duration,
     pub network_mtu: usize,
+    /// Number of members asked to ping a suspect member on our behalf.
     pub ping_request_host_count: usize,
     pub ping_timeout: Duration,
+    /// Number of members pinged every ping interval.
+    pub ping_fanout: usize,
+    /// Duration after which a suspect member that did not refute the suspicion is
+    /// declared down.
+    pub suspect_timeout: Duration,
     pub listen_addr: SocketAddr,
+    /// Cipher encrypting and authenticating messages. Messages travel in clear if not set.
+    pub message_cipher: Option<MessageCipher>,
 }
 
 impl Default for ClusterConfig {
@@ -30,7 +40,10 @@ impl Default for ClusterConfig {
             network_mtu: CONST_PACKET_SIZE,
             ping_request_host_count: 3,
             ping_timeout: Duration::from_secs(3),
+            ping_fanout: 1,
+            suspect_timeout: Duration::from_secs(3),
             listen_addr: directed.to_socket_addrs().unwrap().next().unwrap(),
+            message_cipher: None,
         }
     }
 }
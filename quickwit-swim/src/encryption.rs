@@ -0,0 +1,254 @@
+use std::collections::{HashSet, VecDeque};
+use std::fmt;
+use std::fmt::{Debug, Formatter};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use chacha20poly1305::aead::{Aead, NewAead, Payload};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use rand::RngCore;
+
+use crate::errors::*;
+
+/// Number of bytes of the keys used to encrypt messages.
+pub const KEY_NUM_BYTES: usize = 32;
+
+const NONCE_NUM_BYTES: usize = 12;
+
+const TAG_NUM_BYTES: usize = 16;
+
+const TIMESTAMP_NUM_BYTES: usize = 8;
+
+/// Number of bytes added to a message by its encryption.
+pub const ENCRYPTION_OVERHEAD_NUM_BYTES: usize =
+    NONCE_NUM_BYTES + TIMESTAMP_NUM_BYTES + TAG_NUM_BYTES;
+
+/// Maximum difference between the time a message was sent, as stated by its sender, and the
+/// time it is received. Older messages are rejected as replays, which requires the clocks of
+/// the nodes to be roughly in sync.
+const REPLAY_WINDOW: Duration = Duration::from_secs(30);
+
+/// Encrypts and authenticates the messages exchanged by the members of a cluster,
+/// so that only the nodes sharing the cluster secret can join it.
+///
+/// Messages are always encrypted with the first key, while all the keys are tried for
+/// decryption. This allows rotating keys without partitioning the cluster:
+/// 1. append the new key on every node,
+/// 2. move the new key first on every node,
+/// 3. remove the old key from every node.
+///
+/// Each message carries its sending time, authenticated along with it. A message is accepted
+/// once, and only if it was sent within the replay window: a captured message cannot be
+/// replayed to resurrect a member or roll back its state.
+#[derive(Clone)]
+pub struct MessageCipher {
+    ciphers: Vec<ChaCha20Poly1305>,
+    replay_guard: Arc<Mutex<ReplayGuard>>,
+}
+
+impl MessageCipher {
+    /// Creates a cipher from a list of keys. The first key is used for encryption.
+    pub fn new(keys: &[Vec<u8>]) -> Result<Self> {
+        if keys.is_empty() {
+            return Err(ArtilleryError::InvalidKey(
+                "At least one key is required.".to_string(),
+            ));
+        }
+        let mut ciphers = Vec::with_capacity(keys.len());
+        for key in keys {
+            if key.len() != KEY_NUM_BYTES {
+                return Err(ArtilleryError::InvalidKey(format!(
+                    "Keys must be {} bytes long, got {} bytes.",
+                    KEY_NUM_BYTES,
+                    key.len()
+                )));
+            }
+            ciphers.push(ChaCha20Poly1305::new(Key::from_slice(key)));
+        }
+        Ok(MessageCipher {
+            ciphers,
+            replay_guard: Arc::new(Mutex::new(ReplayGuard::default())),
+        })
+    }
+
+    /// Encrypts a message. `aad` is authenticated along with the message but not encrypted.
+    pub fn encrypt(&self, message: &[u8], aad: &[u8]) -> Vec<u8> {
+        self.encrypt_at(message, aad, now_millis())
+    }
+
+    fn encrypt_at(&self, message: &[u8], aad: &[u8], timestamp_millis: u64) -> Vec<u8> {
+        let mut nonce = [0u8; NONCE_NUM_BYTES];
+        rand::thread_rng().fill_bytes(&mut nonce);
+        let plaintext = [&timestamp_millis.to_be_bytes()[..], message].concat();
+        let payload = Payload {
+            msg: &plaintext,
+            aad,
+        };
+        let ciphertext = self.ciphers[0]
+            .encrypt(Nonce::from_slice(&nonce), payload)
+            .expect("Encrypting a message in memory should never fail.");
+        [&nonce[..], &ciphertext[..]].concat()
+    }
+
+    /// Decrypts a message encrypted with any of the keys, checking that it was not tampered
+    /// with nor replayed.
+    pub fn decrypt(&self, packet: &[u8], aad: &[u8]) -> Result<Vec<u8>> {
+        if packet.len() < ENCRYPTION_OVERHEAD_NUM_BYTES {
+            return Err(ArtilleryError::Decoding("Message is truncated.".to_string()));
+        }
+        let (nonce, ciphertext) = packet.split_at(NONCE_NUM_BYTES);
+        let mut plaintext = self
+            .ciphers
+            .iter()
+            .find_map(|cipher| {
+                let payload = Payload {
+                    msg: ciphertext,
+                    aad,
+                };
+                cipher.decrypt(Nonce::from_slice(nonce), payload).ok()
+            })
+            .ok_or_else(|| {
+                ArtilleryError::Decoding(
+                    "Message could not be authenticated with any of the keys.".to_string(),
+                )
+            })?;
+        let mut timestamp_bytes = [0u8; TIMESTAMP_NUM_BYTES];
+        timestamp_bytes.copy_from_slice(&plaintext[..TIMESTAMP_NUM_BYTES]);
+        let timestamp_millis = u64::from_be_bytes(timestamp_bytes);
+        let mut nonce_bytes = [0u8; NONCE_NUM_BYTES];
+        nonce_bytes.copy_from_slice(nonce);
+        self.replay_guard
+            .lock()
+            .unwrap()
+            .check(nonce_bytes, timestamp_millis, now_millis())?;
+        plaintext.drain(..TIMESTAMP_NUM_BYTES);
+        Ok(plaintext)
+    }
+}
+
+/// Remembers the nonces of the messages received within the replay window.
+#[derive(Default)]
+struct ReplayGuard {
+    seen_nonces: HashSet<[u8; NONCE_NUM_BYTES]>,
+    // Nonces along with their reception time, oldest first.
+    nonces_by_reception: VecDeque<(u64, [u8; NONCE_NUM_BYTES])>,
+}
+
+impl ReplayGuard {
+    fn check(
+        &mut self,
+        nonce: [u8; NONCE_NUM_BYTES],
+        timestamp_millis: u64,
+        now_millis: u64,
+    ) -> Result<()> {
+        let replay_window_millis = REPLAY_WINDOW.as_millis() as u64;
+        if timestamp_millis.saturating_add(replay_window_millis) < now_millis
+            || timestamp_millis > now_millis.saturating_add(replay_window_millis)
+        {
+            return Err(ArtilleryError::Decoding(
+                "Message was not sent within the replay window.".to_string(),
+            ));
+        }
+        // A message received earlier than twice the window ago has a timestamp out of the
+        // window, so its nonce does not need to be remembered anymore.
+        while let Some((reception_millis, old_nonce)) = self.nonces_by_reception.front() {
+            if reception_millis.saturating_add(2 * replay_window_millis) >= now_millis {
+                break;
+            }
+            self.seen_nonces.remove(old_nonce);
+            self.nonces_by_reception.pop_front();
+        }
+        if !self.seen_nonces.insert(nonce) {
+            return Err(ArtilleryError::Decoding("Message was replayed.".to_string()));
+        }
+        self.nonces_by_reception.push_back((now_millis, nonce));
+        Ok(())
+    }
+}
+
+fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+impl Debug for MessageCipher {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MessageCipher")
+            .field("num_keys", &self.ciphers.len())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_message_cipher_roundtrip() {
+        let cipher = MessageCipher::new(&[vec![1u8; KEY_NUM_BYTES]]).unwrap();
+        let packet = cipher.encrypt(b"hello", b"cluster");
+        assert_eq!(packet.len(), 5 + ENCRYPTION_OVERHEAD_NUM_BYTES);
+        assert_eq!(cipher.decrypt(&packet, b"cluster").unwrap(), b"hello");
+        assert!(cipher.decrypt(&packet, b"other-cluster").is_err());
+        assert!(cipher.decrypt(&packet[..10], b"cluster").is_err());
+
+        let mut tampered_packet = cipher.encrypt(b"hello", b"cluster");
+        *tampered_packet.last_mut().unwrap() ^= 1;
+        assert!(cipher.decrypt(&tampered_packet, b"cluster").is_err());
+    }
+
+    #[test]
+    fn test_message_cipher_rejects_replays() {
+        let cipher = MessageCipher::new(&[vec![1u8; KEY_NUM_BYTES]]).unwrap();
+        let packet = cipher.encrypt(b"hello", b"cluster");
+        assert_eq!(cipher.decrypt(&packet, b"cluster").unwrap(), b"hello");
+        assert!(cipher.decrypt(&packet, b"cluster").is_err());
+
+        let window_millis = REPLAY_WINDOW.as_millis() as u64;
+        let stale_packet = cipher.encrypt_at(b"hello", b"cluster", now_millis() - 2 * window_millis);
+        assert!(cipher.decrypt(&stale_packet, b"cluster").is_err());
+        let future_packet =
+            cipher.encrypt_at(b"hello", b"cluster", now_millis() + 2 * window_millis);
+        assert!(cipher.decrypt(&future_packet, b"cluster").is_err());
+    }
+
+    #[test]
+    fn test_replay_guard_forgets_nonces_out_of_the_window() {
+        let window_millis = REPLAY_WINDOW.as_millis() as u64;
+        let mut replay_guard = ReplayGuard::default();
+        let now = 1_000_000_000;
+        replay_guard.check([1u8; NONCE_NUM_BYTES], now, now).unwrap();
+        replay_guard.check([2u8; NONCE_NUM_BYTES], now, now + 1).unwrap();
+        assert!(replay_guard.check([1u8; NONCE_NUM_BYTES], now, now + 2).is_err());
+
+        let later = now + 2 * window_millis + 1;
+        replay_guard.check([3u8; NONCE_NUM_BYTES], later, later).unwrap();
+        assert_eq!(replay_guard.seen_nonces.len(), 2);
+        assert_eq!(replay_guard.nonces_by_reception.len(), 2);
+    }
+
+    #[test]
+    fn test_message_cipher_key_rotation() {
+        let old_key = vec![1u8; KEY_NUM_BYTES];
+        let new_key = vec![2u8; KEY_NUM_BYTES];
+        let old_cipher = MessageCipher::new(&[vec![1u8; KEY_NUM_BYTES]]).unwrap();
+        let rotating_cipher = MessageCipher::new(&[new_key, old_key]).unwrap();
+        let new_cipher = MessageCipher::new(&[vec![2u8; KEY_NUM_BYTES]]).unwrap();
+
+        let old_packet = old_cipher.encrypt(b"hello", b"cluster");
+        assert_eq!(rotating_cipher.decrypt(&old_packet, b"cluster").unwrap(), b"hello");
+        assert!(new_cipher.decrypt(&old_packet, b"cluster").is_err());
+
+        let rotating_packet = rotating_cipher.encrypt(b"hello", b"cluster");
+        assert_eq!(new_cipher.decrypt(&rotating_packet, b"cluster").unwrap(), b"hello");
+        assert!(old_cipher.decrypt(&rotating_packet, b"cluster").is_err());
+    }
+
+    #[test]
+    fn test_message_cipher_invalid_keys() {
+        assert!(MessageCipher::new(&[]).is_err());
+        assert!(MessageCipher::new(&[vec![1u8; 16]]).is_err());
+    }
+}
@@ -22,6 +22,8 @@ pub enum ArtilleryError {
     Decoding(String),
     #[error("Artillery :: Numeric Cast Error: {}", _0)]
     NumericCast(String),
+    #[error("Artillery :: Invalid Key: {}", _0)]
+    InvalidKey(String),
 }
 
 impl From<serde_json::error::Error> for ArtilleryError {
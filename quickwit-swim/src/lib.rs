@@ -1,5 +1,6 @@
 pub mod cluster;
 pub mod cluster_config;
+pub mod encryption;
 pub mod errors;
 pub mod member;
 pub mod membership;
@@ -8,6 +9,7 @@ pub mod state;
 pub mod prelude {
     pub use super::cluster::*;
     pub use super::cluster_config::*;
+    pub use super::encryption::MessageCipher;
     pub use super::errors::ArtilleryError;
     pub use super::member::*;
     pub use super::membership::*;
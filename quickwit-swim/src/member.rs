@@ -1,4 +1,5 @@
 use std::cmp::Ordering;
+use std::collections::BTreeMap;
 use std::fmt;
 use std::fmt::{Debug, Formatter};
 use std::net::SocketAddr;
@@ -35,6 +36,9 @@ pub struct ArtilleryMember {
     member_state: ArtilleryMemberState,
     #[serde(rename = "t", skip, default = "Instant::now")]
     last_state_change: Instant,
+    /// Arbitrary key/value pairs gossiped along with the member.
+    #[serde(rename = "md", default)]
+    metadata: BTreeMap<String, String>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialOrd, Ord, PartialEq, Eq)]
@@ -55,6 +59,7 @@ impl ArtilleryMember {
             incarnation_number,
             member_state: known_state,
             last_state_change: Instant::now(),
+            metadata: BTreeMap::new(),
         }
     }
 
@@ -65,6 +70,7 @@ impl ArtilleryMember {
             incarnation_number: 0,
             member_state: ArtilleryMemberState::Alive,
             last_state_change: Instant::now(),
+            metadata: BTreeMap::new(),
         }
     }
 
@@ -84,6 +90,10 @@ impl ArtilleryMember {
         self.remote_host.is_none()
     }
 
+    pub fn incarnation_number(&self) -> u64 {
+        self.incarnation_number
+    }
+
     pub fn state_change_older_than(&self, duration: Duration) -> bool {
         self.last_state_change + duration < Instant::now()
     }
@@ -99,6 +109,14 @@ impl ArtilleryMember {
         }
     }
 
+    pub fn metadata(&self) -> &BTreeMap<String, String> {
+        &self.metadata
+    }
+
+    pub fn set_metadata(&mut self, metadata: BTreeMap<String, String>) {
+        self.metadata = metadata;
+    }
+
     pub fn member_by_changing_host(&self, remote_host: SocketAddr) -> ArtilleryMember {
         ArtilleryMember {
             remote_host: Some(remote_host),
@@ -109,6 +127,13 @@ impl ArtilleryMember {
     pub fn reincarnate(&mut self) {
         self.incarnation_number += 1
     }
+
+    /// Refutes a suspicion raised against this member with the given incarnation number, by
+    /// declaring it alive with a greater incarnation number.
+    pub fn refute(&mut self, suspected_incarnation_number: u64) {
+        self.incarnation_number = self.incarnation_number.max(suspected_incarnation_number) + 1;
+        self.set_state(ArtilleryMemberState::Alive);
+    }
 }
 
 impl ArtilleryStateChange {
@@ -132,6 +157,7 @@ impl PartialOrd for ArtilleryMember {
             format!("{:?}", self.remote_host),
             self.incarnation_number,
             self.member_state,
+            &self.metadata,
         );
 
         let t2 = (
@@ -139,6 +165,7 @@ impl PartialOrd for ArtilleryMember {
             format!("{:?}", rhs.remote_host),
             rhs.incarnation_number,
             rhs.member_state,
+            &rhs.metadata,
         );
 
         t1.partial_cmp(&t2)
@@ -168,6 +195,7 @@ impl Debug for ArtilleryMember {
                     .map_or(String::from("(current)"), |r| format!("{}", r))
                     .as_str(),
             )
+            .field("metadata", &self.metadata)
             .finish()
     }
 }
@@ -180,20 +208,23 @@ pub fn most_uptodate_member_data<'a>(
     // It's important bit otherwise we won't understand.
     #![allow(clippy::match_same_arms)]
 
+    // Within the same incarnation, `Left` overrides `Down`, which overrides `Suspect`, which
+    // overrides `Alive`. A member refutes a suspicion by bumping its incarnation number, so
+    // a greater incarnation number always wins.
     let lhs_overrides = match (
         lhs.member_state,
         lhs.incarnation_number,
         rhs.member_state,
         rhs.incarnation_number,
     ) {
-        (ArtilleryMemberState::Alive, i, ArtilleryMemberState::Suspect, j) => i > j,
-        (ArtilleryMemberState::Alive, i, ArtilleryMemberState::Alive, j) => i > j,
-        (ArtilleryMemberState::Suspect, i, ArtilleryMemberState::Suspect, j) => i > j,
+        (ArtilleryMemberState::Alive, i, _, j) => i > j,
         (ArtilleryMemberState::Suspect, i, ArtilleryMemberState::Alive, j) => i >= j,
-        (ArtilleryMemberState::Down, _, ArtilleryMemberState::Alive, _) => true,
-        (ArtilleryMemberState::Down, _, ArtilleryMemberState::Suspect, _) => true,
-        (ArtilleryMemberState::Left, _, _, _) => true,
-        _ => false,
+        (ArtilleryMemberState::Suspect, i, _, j) => i > j,
+        (ArtilleryMemberState::Down, i, ArtilleryMemberState::Alive, j) => i >= j,
+        (ArtilleryMemberState::Down, i, ArtilleryMemberState::Suspect, j) => i >= j,
+        (ArtilleryMemberState::Down, i, _, j) => i > j,
+        (ArtilleryMemberState::Left, i, ArtilleryMemberState::Left, j) => i > j,
+        (ArtilleryMemberState::Left, i, _, j) => i >= j,
     };
 
     if lhs_overrides {
@@ -205,12 +236,13 @@ pub fn most_uptodate_member_data<'a>(
 
 #[cfg(test)]
 mod test {
+    use std::collections::BTreeMap;
     use std::str::FromStr;
     use std::time::{Duration, Instant};
 
     use uuid;
 
-    use super::{ArtilleryMember, ArtilleryMemberState};
+    use super::{most_uptodate_member_data, ArtilleryMember, ArtilleryMemberState};
 
     #[test]
     fn test_member_encode_decode() {
@@ -220,6 +252,7 @@ mod test {
             incarnation_number: 123,
             member_state: ArtilleryMemberState::Alive,
             last_state_change: Instant::now() - Duration::from_secs(3600),
+            metadata: BTreeMap::new(),
         };
 
         let encoded = bincode::serialize(&member).unwrap();
@@ -233,4 +266,65 @@ mod test {
 
         assert_eq!(encoded, encoded_again);
     }
+
+    #[test]
+    fn test_member_metadata_is_optional_on_the_wire() {
+        let mut member = ArtilleryMember::new(
+            uuid::Uuid::new_v4(),
+            FromStr::from_str("127.0.0.1:1337").unwrap(),
+            1,
+            ArtilleryMemberState::Alive,
+        );
+        let mut metadata = BTreeMap::new();
+        metadata.insert("role".to_string(), "searcher".to_string());
+        member.set_metadata(metadata);
+        let json_encoded = serde_json::to_string(&member).unwrap();
+        let decoded: ArtilleryMember = serde_json::from_str(&json_encoded).unwrap();
+        assert_eq!(decoded.metadata(), member.metadata());
+
+        // Members gossiped by nodes that do not know about metadata.
+        let mut json_value = serde_json::to_value(&member).unwrap();
+        json_value.as_object_mut().unwrap().remove("md");
+        let decoded: ArtilleryMember = serde_json::from_value(json_value).unwrap();
+        assert!(decoded.metadata().is_empty());
+    }
+
+    #[test]
+    fn test_most_uptodate_member_data() {
+        let host_key = uuid::Uuid::new_v4();
+        let member = |incarnation_number, state| {
+            ArtilleryMember::new(
+                host_key,
+                FromStr::from_str("127.0.0.1:1337").unwrap(),
+                incarnation_number,
+                state,
+            )
+        };
+        let alive_1 = member(1, ArtilleryMemberState::Alive);
+        let alive_2 = member(2, ArtilleryMemberState::Alive);
+        let suspect_1 = member(1, ArtilleryMemberState::Suspect);
+        let down_1 = member(1, ArtilleryMemberState::Down);
+        let left_1 = member(1, ArtilleryMemberState::Left);
+
+        assert_eq!(most_uptodate_member_data(&suspect_1, &alive_1), &suspect_1);
+        assert_eq!(most_uptodate_member_data(&alive_1, &suspect_1), &suspect_1);
+        assert_eq!(most_uptodate_member_data(&down_1, &suspect_1), &down_1);
+        assert_eq!(most_uptodate_member_data(&left_1, &down_1), &left_1);
+        assert_eq!(most_uptodate_member_data(&down_1, &left_1), &left_1);
+        // A refuted suspicion overrides the previous incarnation, whatever its state.
+        assert_eq!(most_uptodate_member_data(&alive_2, &suspect_1), &alive_2);
+        assert_eq!(most_uptodate_member_data(&alive_2, &down_1), &alive_2);
+        assert_eq!(most_uptodate_member_data(&suspect_1, &alive_2), &alive_2);
+        assert_eq!(most_uptodate_member_data(&down_1, &alive_2), &alive_2);
+    }
+
+    #[test]
+    fn test_member_refute() {
+        let mut member = ArtilleryMember::current(uuid::Uuid::new_v4());
+        member.refute(4);
+        assert_eq!(member.incarnation_number(), 5);
+        assert_eq!(member.state(), ArtilleryMemberState::Alive);
+        member.refute(1);
+        assert_eq!(member.incarnation_number(), 6);
+    }
 }
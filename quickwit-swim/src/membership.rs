@@ -1,5 +1,5 @@
 use std::collections::hash_map::Entry;
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::net::SocketAddr;
 use std::time::Duration;
 
@@ -36,6 +36,14 @@ impl ArtilleryMemberList {
             .collect()
     }
 
+    pub fn myself(&self) -> ArtilleryMember {
+        self.members
+            .iter()
+            .find(|member| member.is_current())
+            .cloned()
+            .expect("Could not find this instance as registered member")
+    }
+
     fn mut_myself(&mut self) -> &mut ArtilleryMember {
         for member in &mut self.members {
             if member.is_current() {
@@ -46,11 +54,19 @@ impl ArtilleryMemberList {
         panic!("Could not find this instance as registered member");
     }
 
-    pub fn reincarnate_self(&mut self) -> ArtilleryMember {
+    /// Replaces the metadata of this instance, and reincarnates it so that the new
+    /// metadata overrides the one known by the other members.
+    ///
+    /// Returns `None` if the metadata is unchanged.
+    pub fn set_metadata(&mut self, metadata: BTreeMap<String, String>) -> Option<ArtilleryMember> {
         let myself = self.mut_myself();
+        if myself.metadata() == &metadata {
+            return None;
+        }
+        myself.set_metadata(metadata);
         myself.reincarnate();
 
-        myself.clone()
+        Some(myself.clone())
     }
 
     pub fn leave(&mut self) -> ArtilleryMember {
@@ -61,13 +77,28 @@ impl ArtilleryMemberList {
         myself.clone()
     }
 
-    pub fn next_random_member(&mut self) -> Option<ArtilleryMember> {
+    /// Returns up to `count` distinct members to ping, going through all the members in a
+    /// random order before pinging the same member again.
+    pub fn next_random_members(&mut self, count: usize) -> Vec<ArtilleryMember> {
+        let num_pingable_members = self.members.iter().filter(|m| is_pingable(m)).count();
+        let mut members: Vec<ArtilleryMember> = Vec::new();
+        for _ in 0..count.min(num_pingable_members) {
+            if let Some(member) = self.next_random_member() {
+                if members.iter().all(|m| m.host_key() != member.host_key()) {
+                    members.push(member);
+                }
+            }
+        }
+        members
+    }
+
+    fn next_random_member(&mut self) -> Option<ArtilleryMember> {
         if self.periodic_index == 0 {
             let mut rng = rand::thread_rng();
             self.members.shuffle(&mut rng);
         }
 
-        let other_members: Vec<_> = self.members.iter().filter(|&m| m.is_remote()).collect();
+        let other_members: Vec<_> = self.members.iter().filter(|m| is_pingable(m)).collect();
 
         if other_members.is_empty() {
             None
@@ -77,32 +108,31 @@ impl ArtilleryMemberList {
         }
     }
 
+    /// Suspects the alive members that did not answer in time, and declares down the members
+    /// that stayed suspect for longer than `suspect_timeout`.
     pub fn time_out_nodes(
         &mut self,
         expired_hosts: &HashSet<SocketAddr>,
+        suspect_timeout: Duration,
     ) -> (Vec<ArtilleryMember>, Vec<ArtilleryMember>) {
         let mut suspect_members = Vec::new();
         let mut down_members = Vec::new();
 
         for member in &mut self.members {
             if let Some(remote_host) = member.remote_host() {
-                if !expired_hosts.contains(&remote_host) {
-                    continue;
-                }
-
                 match member.state() {
-                    ArtilleryMemberState::Alive => {
+                    ArtilleryMemberState::Alive if expired_hosts.contains(&remote_host) => {
                         member.set_state(ArtilleryMemberState::Suspect);
                         suspect_members.push(member.clone());
                     }
-                    // TODO: Config suspect timeout
                     ArtilleryMemberState::Suspect
-                        if member.state_change_older_than(Duration::from_secs(3)) =>
+                        if member.state_change_older_than(suspect_timeout) =>
                     {
                         member.set_state(ArtilleryMemberState::Down);
                         down_members.push(member.clone());
                     }
-                    ArtilleryMemberState::Suspect
+                    ArtilleryMemberState::Alive
+                    | ArtilleryMemberState::Suspect
                     | ArtilleryMemberState::Down
                     | ArtilleryMemberState::Left => {}
                 }
@@ -126,15 +156,24 @@ impl ArtilleryMemberList {
         None
     }
 
+    /// Applies the state changes received from `from`.
+    ///
+    /// Returns the new members, the members whose state changed, and the members whose
+    /// metadata only changed.
     pub fn apply_state_changes(
         &mut self,
         state_changes: Vec<ArtilleryStateChange>,
         from: &SocketAddr,
-    ) -> (Vec<ArtilleryMember>, Vec<ArtilleryMember>) {
+    ) -> (
+        Vec<ArtilleryMember>,
+        Vec<ArtilleryMember>,
+        Vec<ArtilleryMember>,
+    ) {
         let mut current_members = self.to_map();
 
         let mut changed_nodes = Vec::new();
         let mut new_nodes = Vec::new();
+        let mut updated_nodes = Vec::new();
 
         let my_host_key = self.mut_myself().host_key();
 
@@ -143,9 +182,18 @@ impl ArtilleryMemberList {
             let old_member_data = current_members.entry(new_member_data.host_key());
 
             if new_member_data.host_key() == my_host_key {
-                if new_member_data.state() != ArtilleryMemberState::Alive {
-                    let myself = self.reincarnate_self();
-                    changed_nodes.push(myself.clone());
+                // Refutes the suspicions raised against us, unless we are leaving the cluster.
+                let myself = self.mut_myself();
+                if new_member_data.state() != ArtilleryMemberState::Alive
+                    && myself.state() != ArtilleryMemberState::Left
+                    && new_member_data.incarnation_number() >= myself.incarnation_number()
+                {
+                    myself.refute(new_member_data.incarnation_number());
+                    let myself = myself.clone();
+                    if let Entry::Occupied(mut entry) = old_member_data {
+                        entry.insert(myself.clone());
+                    }
+                    changed_nodes.push(myself);
                 }
             } else {
                 match old_member_data {
@@ -161,6 +209,9 @@ impl ArtilleryMemberList {
                         if new_member.state() != entry.get().state() {
                             entry.insert(new_member.clone());
                             changed_nodes.push(new_member);
+                        } else if new_member.metadata() != entry.get().metadata() {
+                            entry.insert(new_member.clone());
+                            updated_nodes.push(new_member);
                         }
                     }
                     Entry::Vacant(entry) => {
@@ -176,7 +227,7 @@ impl ArtilleryMemberList {
 
         self.members = current_members.values().cloned().collect();
 
-        (new_nodes, changed_nodes)
+        (new_nodes, changed_nodes, updated_nodes)
     }
 
     /// Random ping enqueuing
@@ -230,3 +281,37 @@ impl ArtilleryMemberList {
         Some(member[0].clone())
     }
 }
+
+/// Left members are not pinged anymore, while down members still are, so that they can
+/// rejoin the cluster once they are reachable again.
+fn is_pingable(member: &ArtilleryMember) -> bool {
+    member.is_remote() && member.state() != ArtilleryMemberState::Left
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::BTreeMap;
+
+    use uuid::Uuid;
+
+    use super::ArtilleryMemberList;
+    use crate::member::ArtilleryMember;
+
+    #[test]
+    fn test_set_metadata_reincarnates_only_on_change() {
+        let mut members = ArtilleryMemberList::new(ArtilleryMember::current(Uuid::new_v4()));
+        let mut metadata = BTreeMap::new();
+        metadata.insert("load".to_string(), "1".to_string());
+
+        let myself = members.set_metadata(metadata.clone()).unwrap();
+        assert_eq!(myself.incarnation_number(), 1);
+        assert_eq!(myself.metadata(), &metadata);
+
+        assert!(members.set_metadata(metadata.clone()).is_none());
+        assert_eq!(members.myself().incarnation_number(), 1);
+
+        metadata.insert("load".to_string(), "2".to_string());
+        let myself = members.set_metadata(metadata).unwrap();
+        assert_eq!(myself.incarnation_number(), 2);
+    }
+}
@@ -1,5 +1,5 @@
 use std::collections::hash_map::Entry;
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::io;
 use std::net::SocketAddr;
 use std::sync::atomic::{AtomicBool, Ordering};
@@ -13,6 +13,7 @@ use uuid::Uuid;
 
 use super::cluster_config::ClusterConfig;
 use super::membership::ArtilleryMemberList;
+use crate::encryption::ENCRYPTION_OVERHEAD_NUM_BYTES;
 use crate::errors::*;
 use crate::member::{ArtilleryMember, ArtilleryMemberState, ArtilleryStateChange};
 use crate::prelude::CONST_PACKET_SIZE;
@@ -27,6 +28,7 @@ pub enum ArtilleryMemberEvent {
     SuspectedDown(ArtilleryMember),
     WentDown(ArtilleryMember),
     Left(ArtilleryMember),
+    MetadataUpdated(ArtilleryMember),
     Payload(ArtilleryMember, String),
 }
 
@@ -64,9 +66,12 @@ pub enum ArtilleryClusterRequest {
     LeaveCluster,
     Exit,
     Payload(Uuid, String),
+    SetMetadata(BTreeMap<String, String>),
 }
 
 const UDP_SERVER: Token = Token(0);
+/// Token of the waker interrupting the poll when a request is submitted to the event loop.
+pub(crate) const WAKER: Token = Token(1);
 
 pub struct ArtilleryEpidemic {
     host_key: Uuid,
@@ -149,19 +154,24 @@ impl ArtilleryEpidemic {
                 poll.poll(&mut events, Some(remaining))?;
             }
 
-            // Process our own events that are submitted to event loop
-            // Aka outbound events
-            while let Ok(msg) = receiver.try_recv() {
-                state.process_internal_request(msg);
-            }
-
             // Process inbound events
             for event in events.iter() {
                 if let UDP_SERVER = event.token() {
                     loop {
                         match state.server_socket.recv_from(&mut buf) {
                             Ok((packet_size, source_address)) => {
-                                let message = serde_json::from_slice(&buf[..packet_size])?;
+                                let message = match state.decode_message(&buf[..packet_size]) {
+                                    Ok(message) => message,
+                                    Err(error) => {
+                                        // Packets from nodes that do not share our cluster
+                                        // secret must not take the event loop down.
+                                        warn!(
+                                            "Dropping undecodable message from {}: {}",
+                                            source_address, error
+                                        );
+                                        continue;
+                                    }
+                                };
                                 state.request_tx.send(ArtilleryClusterRequest::Respond(
                                     source_address,
                                     message,
@@ -183,10 +193,17 @@ impl ArtilleryEpidemic {
                             }
                         }
                     }
-                } else {
+                } else if event.token() != WAKER {
                     warn!("Got event for unexpected token: {:?}", event);
                 }
             }
+
+            // Process our own events that are submitted to event loop, including the
+            // responses to the inbound events above.
+            // Aka outbound events
+            while let Ok(msg) = receiver.try_recv() {
+                state.process_internal_request(msg);
+            }
         }
 
         info!("Exiting...");
@@ -199,12 +216,28 @@ impl ArtilleryEpidemic {
         let timeout = Instant::now() + self.config.ping_timeout;
         // It was Ping before
         let should_add_pending = request.request == Heartbeat;
+        // Our own member always comes first, so that its metadata reaches the peers we
+        // talk to even when it is not part of the pending state changes anymore.
+        let state_changes: Vec<ArtilleryStateChange> =
+            std::iter::once(ArtilleryStateChange::new(self.members.myself()))
+                .chain(
+                    self.state_changes
+                        .iter()
+                        .filter(|state_change| state_change.member().host_key() != self.host_key)
+                        .cloned(),
+                )
+                .collect();
+        let network_mtu = if self.config.message_cipher.is_some() {
+            self.config.network_mtu - ENCRYPTION_OVERHEAD_NUM_BYTES
+        } else {
+            self.config.network_mtu
+        };
         let message = build_message(
             &self.host_key,
             &self.config.cluster_key,
             &request.request,
-            &self.state_changes,
-            self.config.network_mtu,
+            &state_changes,
+            network_mtu,
         );
 
         if should_add_pending {
@@ -212,12 +245,27 @@ impl ArtilleryEpidemic {
                 .push((timeout, request.target, message.state_changes.clone()));
         }
 
-        let encoded = serde_json::to_string(&message).unwrap();
+        let encoded = serde_json::to_vec(&message).unwrap();
+        let buf = match &self.config.message_cipher {
+            Some(message_cipher) => message_cipher.encrypt(&encoded, &self.config.cluster_key),
+            None => encoded,
+        };
+
+        assert!(buf.len() < self.config.network_mtu);
 
-        assert!(encoded.len() < self.config.network_mtu);
+        self.server_socket.send_to(&buf, request.target).unwrap();
+    }
 
-        let buf = encoded.as_bytes();
-        self.server_socket.send_to(buf, request.target).unwrap();
+    /// Decrypts, when the cluster is encrypted, and deserializes an incoming packet.
+    fn decode_message(&self, packet: &[u8]) -> Result<ArtilleryMessage> {
+        let message = match &self.config.message_cipher {
+            Some(message_cipher) => {
+                let decrypted_packet = message_cipher.decrypt(packet, &self.config.cluster_key)?;
+                serde_json::from_slice(&decrypted_packet)?
+            }
+            None => serde_json::from_slice(packet)?,
+        };
+        Ok(message)
     }
 
     fn enqueue_seed_nodes(&self) {
@@ -232,7 +280,7 @@ impl ArtilleryEpidemic {
     }
 
     fn enqueue_random_ping(&mut self) {
-        if let Some(member) = self.members.next_random_member() {
+        for member in self.members.next_random_members(self.config.ping_fanout) {
             self.request_tx
                 .send(ArtilleryClusterRequest::React(TargetedRequest {
                     request: Request::Heartbeat,
@@ -255,7 +303,9 @@ impl ArtilleryEpidemic {
 
         self.pending_responses = remaining;
 
-        let (suspect, down) = self.members.time_out_nodes(&expired_hosts);
+        let (suspect, down) = self
+            .members
+            .time_out_nodes(&expired_hosts, self.config.suspect_timeout);
 
         enqueue_state_change(&mut self.state_changes, &down);
         enqueue_state_change(&mut self.state_changes, &suspect);
@@ -299,6 +349,24 @@ impl ArtilleryEpidemic {
             LeaveCluster => {
                 let myself = self.members.leave();
                 enqueue_state_change(&mut self.state_changes, &[myself]);
+                // Tells every member right away rather than waiting for the departure to be
+                // gossiped, or worse, detected by timeouts.
+                for member in self.members.available_nodes() {
+                    if let Some(remote_host) = member.remote_host() {
+                        if member.state() != ArtilleryMemberState::Down {
+                            self.process_request(&TargetedRequest {
+                                request: Request::Heartbeat,
+                                target: remote_host,
+                            });
+                        }
+                    }
+                }
+            }
+            SetMetadata(metadata) => {
+                if let Some(myself) = self.members.set_metadata(metadata) {
+                    enqueue_state_change(&mut self.state_changes, &[myself.clone()]);
+                    self.send_member_event(ArtilleryMemberEvent::MetadataUpdated(myself));
+                }
             }
             Payload(id, msg) => {
                 if let Some(target_peer) = self.members.get_member(&id) {
@@ -415,7 +483,7 @@ impl ArtilleryEpidemic {
         use ArtilleryMemberEvent::*;
 
         match event {
-            Joined(_) | Payload(..) => {}
+            Joined(_) | MetadataUpdated(_) | Payload(..) => {}
             WentUp(ref m) => assert_eq!(m.state(), ArtilleryMemberState::Alive),
             WentDown(ref m) => assert_eq!(m.state(), ArtilleryMemberState::Down),
             SuspectedDown(ref m) => assert_eq!(m.state(), ArtilleryMemberState::Suspect),
@@ -427,10 +495,11 @@ impl ArtilleryEpidemic {
     }
 
     fn apply_state_changes(&mut self, state_changes: Vec<ArtilleryStateChange>, from: SocketAddr) {
-        let (new, changed) = self.members.apply_state_changes(state_changes, &from);
+        let (new, changed, updated) = self.members.apply_state_changes(state_changes, &from);
 
         enqueue_state_change(&mut self.state_changes, &new);
         enqueue_state_change(&mut self.state_changes, &changed);
+        enqueue_state_change(&mut self.state_changes, &updated);
 
         for member in new {
             self.send_member_event(ArtilleryMemberEvent::Joined(member));
@@ -439,6 +508,10 @@ impl ArtilleryEpidemic {
         for member in changed {
             self.send_member_event(determine_member_event(member));
         }
+
+        for member in updated {
+            self.send_member_event(ArtilleryMemberEvent::MetadataUpdated(member));
+        }
     }
 
     fn mark_node_alive(&mut self, src_addr: SocketAddr) {